@@ -0,0 +1,125 @@
+//! Timings of the battle's hot spots: `cargo bench`.
+//!
+//! A plain `harness = false` bench: Criterion isn't among the dependencies,
+//! so a small timer prints the mean, the fastest and the slowest samples instead.
+
+use std::time::{Duration, Instant};
+
+use zemeroth::core::{
+    battle::{
+        ability,
+        ai::{self, Ai},
+        command::{self, Command},
+        component::Prototypes,
+        effect::StackRules,
+        execute::execute,
+        movement::Pathfinder,
+        scenario::{Line, ObjectsGroup, Scenario},
+        state, PlayerId, State,
+    },
+    map::{self, PosHex},
+};
+
+fn bench(name: &str, samples: u32, mut f: impl FnMut()) {
+    f(); // warm up
+    let mut times = Vec::new();
+    for _ in 0..samples {
+        let start = Instant::now();
+        f();
+        times.push(start.elapsed());
+    }
+    let mean = times.iter().sum::<Duration>() / samples;
+    let min = times.iter().min().expect("No samples");
+    let max = times.iter().max().expect("No samples");
+    println!(
+        "{:<40} mean {:>12.3?}   min {:>12.3?}   max {:>12.3?}",
+        name, mean, min, max
+    );
+}
+
+fn group(owner: Option<PlayerId>, typename: &str, count: i32) -> ObjectsGroup {
+    ObjectsGroup {
+        owner,
+        typename: typename.into(),
+        line: owner.map(|_| Line::Any),
+        count,
+        extra_components: Vec::new(),
+    }
+}
+
+fn crowded_state(map_radius: i32, agents_per_player: i32, ai: ai::Mode) -> State {
+    let mut randomized_objects = vec![
+        group(None, "boulder", map_radius * 2),
+        group(None, "spike_trap", map_radius),
+    ];
+    for (player_id, typename) in [(PlayerId(0), "swordsman"), (PlayerId(1), "imp")] {
+        randomized_objects.push(group(Some(player_id), typename, agents_per_player));
+    }
+    let scenario = Scenario {
+        map_radius: map::Distance(map_radius),
+        rocky_tiles_count: map_radius * 3,
+        randomized_objects,
+        ai,
+        seed: Some(0),
+        ..Scenario::default()
+    };
+    let prototypes = Prototypes::from_str(include_str!("../assets/objects.ron"));
+    let stack_rules: StackRules =
+        ron::de::from_str(include_str!("../assets/effects.ron")).expect("Bad effects");
+    let abilities: ability::Definitions =
+        ron::de::from_str(include_str!("../assets/abilities.ron")).expect("Bad abilities");
+    State::new(
+        prototypes,
+        stack_rules,
+        abilities,
+        scenario,
+        &mut |_, _, _| {},
+    )
+}
+
+fn exec(state: &mut State, command: &Command) {
+    execute(state, command, &mut |_, _, _| {}).expect("Bad command");
+}
+
+fn bench_pathfinding() {
+    let state = crowded_state(20, 30, ai::Mode::default());
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    let id = state::players_agent_ids(&state, PlayerId(0))[0];
+    let far_corner = PosHex { q: 20, r: -20 };
+    bench("pathfinding on a large map", 200, || {
+        pathfinder.fill_map(&state, id);
+        let _ = pathfinder.path(far_corner);
+    });
+}
+
+/// Plays the current player's turn till the AI ends it.
+fn play_ai_turn(state: &mut State, ai: &mut Ai) {
+    for _ in 0..100 {
+        let command = match ai.command(state) {
+            Some(command) => command,
+            None => return,
+        };
+        exec(state, &command);
+        if let Command::EndTurn(_) = command {
+            return;
+        }
+    }
+}
+
+fn bench_ai_turn(mode: ai::Mode) {
+    let mut state = crowded_state(6, 12, mode);
+    exec(&mut state, &command::EndTurn.into());
+    let radius = state.map().radius();
+    let name = format!("{} AI turn on a crowded board", mode.title());
+    bench(&name, 10, || {
+        let mut state = state.clone();
+        let mut ai = Ai::new(PlayerId(1), radius);
+        play_ai_turn(&mut state, &mut ai);
+    });
+}
+
+fn main() {
+    bench_pathfinding();
+    bench_ai_turn(ai::Mode::Greedy);
+    bench_ai_turn(ai::Mode::Lookahead);
+}
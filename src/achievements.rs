@@ -0,0 +1,66 @@
+//! The reached achievements, kept as a RON file next to the game.
+
+use std::sync::RwLock;
+
+use crate::{
+    assets,
+    core::achievements::{Achievement, Trigger, Unlocked},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const PATH: &str = "achievements.ron";
+
+/// Read from the file on the first use.
+static UNLOCKED: RwLock<Option<Unlocked>> = RwLock::new(None);
+
+/// The missing or broken file means that nothing is reached yet.
+#[cfg(not(target_arch = "wasm32"))]
+fn load() -> Unlocked {
+    match std::fs::read_to_string(PATH) {
+        Ok(s) => ron::de::from_str(&s).unwrap_or_else(|err| {
+            log::warn!("Can't parse '{}', starting anew: {}", PATH, err);
+            Unlocked::default()
+        }),
+        Err(_) => Unlocked::default(),
+    }
+}
+
+/// There are no files in browsers, the achievements last till the page is closed.
+#[cfg(target_arch = "wasm32")]
+fn load() -> Unlocked {
+    Unlocked::default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save(unlocked: &Unlocked) {
+    let config = ron::ser::PrettyConfig::new();
+    let result = ron::ser::to_string_pretty(unlocked, config)
+        .map_err(|err| err.to_string())
+        .and_then(|s| std::fs::write(PATH, s).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        log::warn!("Can't save the achievements to '{}': {}", PATH, err);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save(_: &Unlocked) {}
+
+pub fn get() -> Unlocked {
+    let mut unlocked = UNLOCKED.write().expect("Can't read the achievements");
+    unlocked.get_or_insert_with(load).clone()
+}
+
+/// Checks all the achievements against the trigger and returns the newly reached ones.
+pub fn unlock(trigger: Trigger) -> Vec<Achievement> {
+    let mut unlocked = UNLOCKED.write().expect("Can't update the achievements");
+    let unlocked = unlocked.get_or_insert_with(load);
+    let new: Vec<Achievement> = unlocked
+        .check(&assets::get().achievements, trigger)
+        .into_iter()
+        .cloned()
+        .collect();
+    if !new.is_empty() {
+        save(unlocked);
+    }
+    new
+}
@@ -3,41 +3,60 @@
 // TODO: https://github.com/rust-lang/rust-clippy/issues/4637
 #![allow(clippy::eval_order_dependence)]
 
-use std::{collections::HashMap, hash::Hash};
+use std::{collections::HashMap, hash::Hash, sync::RwLock};
 
 use mq::{
     file::load_file,
     text::{self, Font},
     texture::{load_texture, Texture2D},
 };
-use once_cell::sync::OnceCell;
 use serde::{de::DeserializeOwned, Deserialize};
 
 use crate::{
     core::{
+        achievements::Achievement,
         battle::{
-            ability::Ability,
+            ability::{self, Ability},
             component::{ObjType, Prototypes, WeaponType},
             effect,
             scenario::Scenario,
+            tutorial::Tutorial,
         },
         campaign,
+        item::{Item, ItemType},
+        mod_diff, validation,
     },
     error::ZError,
+    i18n::{self, Language},
     ZResult,
 };
 
-static INSTANCE: OnceCell<Assets> = OnceCell::new();
+#[cfg(not(target_arch = "wasm32"))]
+mod mods;
+
+/// The relative path of the assets on PC.
+pub const DIR: &str = "assets";
+
+/// The replaced assets are leaked, so the references to them stay valid after a reload.
+static INSTANCE: RwLock<Option<&'static Assets>> = RwLock::new(None);
+
+fn set(assets: Assets) {
+    let assets = Box::leak(Box::new(assets));
+    *INSTANCE.write().expect("Can't set assets instance") = Some(assets);
+}
 
 pub async fn load() -> ZResult {
-    assert!(INSTANCE.get().is_none());
-    let assets = Assets::load().await?;
-    INSTANCE.set(assets).expect("Can't set assets instance");
+    assert!(INSTANCE
+        .read()
+        .expect("Can't read assets instance")
+        .is_none());
+    set(Assets::load().await?);
     Ok(())
 }
 
 pub fn get() -> &'static Assets {
-    INSTANCE.get().expect("Assets weren't loaded")
+    let instance = INSTANCE.read().expect("Can't read assets instance");
+    instance.expect("Assets weren't loaded")
 }
 
 /// Read a file to a string.
@@ -79,6 +98,21 @@ fn default_sub_tile_z() -> f32 {
 
 type SpritesInfo = HashMap<ObjType, SpriteInfo>;
 
+/// What the mods change in the built-in data, see `screen::Mods`.
+#[derive(Debug, Clone, Default)]
+pub struct ModsSummary {
+    /// The mods of the load order.
+    pub names: Vec<String>,
+
+    /// The mods of `Settings::disabled_mods` when the assets were loaded, they changed nothing.
+    pub disabled: Vec<String>,
+
+    pub diff: mod_diff::Diff,
+
+    /// The data files that the loaded mods have, e.g. `campaign_01.ron`.
+    pub files: Vec<&'static str>,
+}
+
 #[derive(Debug)]
 pub struct Assets {
     pub textures: Textures,
@@ -86,39 +120,206 @@ pub struct Assets {
     pub sprites_info: SpritesInfo,
     pub sprite_frames: HashMap<ObjType, HashMap<String, Texture2D>>,
     pub prototypes: Prototypes,
+    pub stack_rules: effect::StackRules,
+    pub ability_definitions: ability::Definitions,
     pub demo_scenario: Scenario,
+    pub skirmish_scenarios: Vec<Scenario>,
+    pub tutorial: Tutorial,
     pub campaign_plan: campaign::Plan,
     pub agent_campaign_info: HashMap<ObjType, campaign::AgentInfo>,
+    pub items: HashMap<ItemType, Item>,
+    pub achievements: Vec<Achievement>,
+    pub translations: HashMap<Language, i18n::Translation>,
+    pub mods: ModsSummary,
 }
 
 impl Assets {
     pub async fn load() -> ZResult<Self> {
-        let sprites_info: SpritesInfo = deserialize_from_file("sprites.ron").await?;
-        let sprite_frames = {
-            let mut sprite_frames = HashMap::new();
-            for (obj_type, SpriteInfo { paths, .. }) in sprites_info.iter() {
-                let mut frames = HashMap::new();
-                for (frame_name, path) in paths {
-                    frames.insert(frame_name.to_string(), load_texture(path).await?);
-                }
-                sprite_frames.insert(obj_type.clone(), frames);
-            }
-            sprite_frames
-        };
-        Ok(Self {
-            textures: Textures::load().await?,
-            font: text::load_ttf_font("OpenSans-Regular.ttf").await?,
-            sprites_info,
-            sprite_frames,
+        let textures = Textures::load().await?;
+        let font = text::load_ttf_font("OpenSans-Regular.ttf").await?;
+        Self::load_data(textures, font).await
+    }
+
+    /// Loads everything but the common textures and the font.
+    async fn load_data(textures: Textures, font: Font) -> ZResult<Self> {
+        #[allow(unused_mut)] // the mods are only loaded on PC
+        let mut assets = Self {
+            textures,
+            font,
+            sprites_info: deserialize_from_file("sprites.ron").await?,
+            sprite_frames: HashMap::new(),
             prototypes: Prototypes::from_str(&read_file("objects.ron").await?),
+            stack_rules: deserialize_from_file("effects.ron").await?,
+            ability_definitions: deserialize_from_file("abilities.ron").await?,
             demo_scenario: deserialize_from_file("scenario_01.ron").await?,
+            skirmish_scenarios: deserialize_from_file("skirmishes.ron").await?,
+            tutorial: deserialize_from_file("tutorial.ron").await?,
             campaign_plan: deserialize_from_file("campaign_01.ron").await?,
             agent_campaign_info: deserialize_from_file("agent_campaign_info.ron").await?,
-        })
+            items: deserialize_from_file("items.ron").await?,
+            achievements: deserialize_from_file("achievements.ron").await?,
+            translations: load_translations().await?,
+            mods: ModsSummary::default(),
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        assets.apply_mods().await?;
+        assets.validate()?;
+        for (obj_type, SpriteInfo { paths, .. }) in assets.sprites_info.iter() {
+            let mut frames = HashMap::new();
+            for (frame_name, path) in paths {
+                frames.insert(frame_name.to_string(), load_texture(path).await?);
+            }
+            assets.sprite_frames.insert(obj_type.clone(), frames);
+        }
+        Ok(assets)
+    }
+
+    /// Catches the broken references before anything else uses the data.
+    fn validate(&self) -> ZResult {
+        let has_sprite = |typename: &ObjType| self.sprites_info.contains_key(typename);
+        let has_ability_icon = |ability| self.textures.icons.abilities.contains_key(&ability);
+        let mut scenarios = vec![
+            ("demo scenario".to_string(), &self.demo_scenario),
+            ("tutorial".to_string(), &self.tutorial.scenario),
+        ];
+        for (i, scenario) in self.skirmish_scenarios.iter().enumerate() {
+            scenarios.push((format!("skirmish #{}", i), scenario));
+        }
+        let data = validation::Data {
+            prototypes: &self.prototypes,
+            abilities: &self.ability_definitions,
+            has_sprite: &has_sprite,
+            has_ability_icon: &has_ability_icon,
+            scenarios,
+            campaign: &self.campaign_plan,
+            agent_info: &self.agent_campaign_info,
+            item_info: &self.items,
+        };
+        let errors = validation::check(&data);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ZError::BadAssets(errors))
+        }
+    }
+
+    /// The named entries of the mods replace the entries with the same names or add new ones
+    /// (the achievements are matched by their ids),
+    /// the skirmishes are added to the built-in ones and the demo scenario,
+    /// the tutorial and the campaign are replaced.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn apply_mods(&mut self) -> ZResult {
+        let base_prototypes = self.prototypes.0.clone();
+        let base_abilities = self.ability_definitions.0.clone();
+        let base_skirmishes_count = self.skirmish_scenarios.len();
+        let names = mods::names()?;
+        let disabled_mods = crate::settings::get().disabled_mods;
+        let disabled = names
+            .iter()
+            .filter(|name| disabled_mods.contains(name))
+            .cloned()
+            .collect();
+        let mut summary = ModsSummary {
+            names,
+            disabled,
+            ..ModsSummary::default()
+        };
+        for dir in mods::dirs()? {
+            for &file in &DATA_FILES {
+                if mods::has_file(&dir, file) && !summary.files.contains(&file) {
+                    summary.files.push(file);
+                }
+            }
+            if let Some(mut prototypes) = load_override::<Prototypes>(&dir, "objects.ron").await? {
+                prototypes.init_components();
+                self.prototypes.0.extend(prototypes.0);
+            }
+            if let Some(mut sprites_info) =
+                load_override::<SpritesInfo>(&dir, "sprites.ron").await?
+            {
+                for info in sprites_info.values_mut() {
+                    for path in info.paths.values_mut() {
+                        *path = format!("{}/{}", dir, path);
+                    }
+                }
+                self.sprites_info.extend(sprites_info);
+            }
+            if let Some(rules) = load_override::<effect::StackRules>(&dir, "effects.ron").await? {
+                self.stack_rules.0.extend(rules.0);
+            }
+            if let Some(definitions) =
+                load_override::<ability::Definitions>(&dir, "abilities.ron").await?
+            {
+                self.ability_definitions.0.extend(definitions.0);
+            }
+            if let Some(scenario) = load_override(&dir, "scenario_01.ron").await? {
+                self.demo_scenario = scenario;
+            }
+            if let Some(scenarios) = load_override::<Vec<_>>(&dir, "skirmishes.ron").await? {
+                self.skirmish_scenarios.extend(scenarios);
+            }
+            if let Some(tutorial) = load_override(&dir, "tutorial.ron").await? {
+                self.tutorial = tutorial;
+            }
+            if let Some(plan) = load_override(&dir, "campaign_01.ron").await? {
+                self.campaign_plan = plan;
+            }
+            let info = load_override::<HashMap<_, _>>(&dir, "agent_campaign_info.ron").await?;
+            if let Some(info) = info {
+                self.agent_campaign_info.extend(info);
+            }
+            if let Some(items) = load_override::<HashMap<_, _>>(&dir, "items.ron").await? {
+                self.items.extend(items);
+            }
+            let achievements = load_override::<Vec<Achievement>>(&dir, "achievements.ron").await?;
+            for achievement in achievements.unwrap_or_default() {
+                let same_id = self
+                    .achievements
+                    .iter_mut()
+                    .find(|a| a.id == achievement.id);
+                match same_id {
+                    Some(old) => *old = achievement,
+                    None => self.achievements.push(achievement),
+                }
+            }
+        }
+        let base = mod_diff::Data {
+            prototypes: &base_prototypes,
+            abilities: &base_abilities,
+            skirmishes_count: base_skirmishes_count,
+        };
+        let merged = mod_diff::Data {
+            prototypes: &self.prototypes.0,
+            abilities: &self.ability_definitions.0,
+            skirmishes_count: self.skirmish_scenarios.len(),
+        };
+        summary.diff = mod_diff::diff(&base, &merged);
+        self.mods = summary;
+        Ok(())
     }
 }
 
-#[derive(Debug)]
+async fn load_translations() -> ZResult<HashMap<Language, i18n::Translation>> {
+    let mut translations = HashMap::new();
+    for &language in &Language::ALL {
+        if let Some(file) = language.file() {
+            translations.insert(language, deserialize_from_file(file).await?);
+        }
+    }
+    Ok(translations)
+}
+
+/// Loads the mod's version of the file if it has one.
+#[cfg(not(target_arch = "wasm32"))]
+async fn load_override<D: DeserializeOwned>(dir: &str, file: &str) -> ZResult<Option<D>> {
+    if !mods::has_file(dir, file) {
+        return Ok(None);
+    }
+    let path = format!("{}/{}", dir, file);
+    deserialize_from_file(&path).await.map(Some)
+}
+
+#[derive(Debug, Clone)]
 pub struct Textures {
     pub map: MapObjectTextures,
     pub weapon_flashes: HashMap<WeaponType, Texture2D>,
@@ -137,7 +338,7 @@ impl Textures {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MapObjectTextures {
     pub selection: Texture2D,
     pub white_hex: Texture2D,
@@ -164,7 +365,7 @@ impl MapObjectTextures {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IconTextures {
     pub info: Texture2D,
     pub end_turn: Texture2D,
@@ -210,8 +411,15 @@ async fn load_ability_icons() -> ZResult<HashMap<Ability, Texture2D>> {
         (Ability::Dash, "dash"),
         (Ability::Rage, "rage"),
         (Ability::Heal, "heal"),
-        (Ability::GreatHeal, "great_heal"),
         (Ability::Bloodlust, "bloodlust"),
+        // TODO: draw their own icons
+        (Ability::Shove, "knockback"),
+        (Ability::Totem, "heal"),
+        (Ability::Barricade, "club"),
+        (Ability::Defuse, "dash"),
+        (Ability::Phase, "dash"),
+        (Ability::Hide, "dash"),
+        (Ability::Raise, "summon"),
     ];
     load_map(map, |s| format!("img/icon_ability_{}.png", s)).await
 }
@@ -221,6 +429,89 @@ async fn load_lasting_effects() -> ZResult<HashMap<effect::Lasting, Texture2D>>
         (effect::Lasting::Stun, "stun"),
         (effect::Lasting::Poison, "poison"),
         (effect::Lasting::Bloodlust, "bloodlust"),
+        // TODO: draw its own icon
+        (effect::Lasting::Phased, "stun"),
+        // TODO: draw its own icon
+        (effect::Lasting::Hidden, "stun"),
+        // TODO: draw its own icon
+        (effect::Lasting::Injured, "poison"),
     ];
     load_map(map, |s| format!("img/effect_{}.png", s)).await
 }
+
+/// The files that `Watcher` reloads, see `Assets::load_data`.
+/// The same files of the mods are watched too.
+#[cfg(not(target_arch = "wasm32"))]
+const DATA_FILES: [&str; 12] = [
+    "sprites.ron",
+    "objects.ron",
+    "effects.ron",
+    "abilities.ron",
+    "scenario_01.ron",
+    "skirmishes.ron",
+    "tutorial.ron",
+    "campaign_01.ron",
+    "agent_campaign_info.ron",
+    "items.ron",
+    "achievements.ron",
+    "texts/ru.ron",
+];
+
+/// Reloads the data files of the development builds as soon as they're changed,
+/// so the stats and the scenarios can be tuned without restarting the game.
+///
+/// The running battles keep their own copies of the prototypes,
+/// only the new ones see the changes.
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+#[derive(Debug)]
+pub struct Watcher {
+    modified: Vec<Option<std::time::SystemTime>>,
+    last_check_time: f64,
+}
+
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+impl Watcher {
+    /// Seconds between the checks of the files.
+    const CHECK_INTERVAL: f64 = 0.5;
+
+    pub fn new() -> Self {
+        Self {
+            modified: Self::modification_times(),
+            last_check_time: mq::time::get_time(),
+        }
+    }
+
+    fn modification_times() -> Vec<Option<std::time::SystemTime>> {
+        let mut files: Vec<_> = DATA_FILES
+            .iter()
+            .map(|name| std::path::Path::new(DIR).join(name))
+            .collect();
+        files.extend(mods::watched_files());
+        files
+            .iter()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    /// A broken file is reported and the old assets are kept till it's fixed.
+    pub async fn reload_if_changed(&mut self) {
+        let time = mq::time::get_time();
+        if time - self.last_check_time < Self::CHECK_INTERVAL {
+            return;
+        }
+        self.last_check_time = time;
+        let modified = Self::modification_times();
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        let old = get();
+        match Assets::load_data(old.textures.clone(), old.font).await {
+            Ok(assets) => {
+                log::info!("Assets: the data files were reloaded");
+                set(assets);
+            }
+            Err(err) => log::error!("Assets: can't reload the data files: {}", err),
+        }
+    }
+}
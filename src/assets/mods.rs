@@ -0,0 +1,70 @@
+//! Directories of data files that are layered over the built-in assets.
+//!
+//! `mods/load_order.ron` lists the mods that are loaded, the later ones win:
+//!
+//! ```ron
+//! ["stronger_imps", "more_skirmishes"]
+//! ```
+//!
+//! A mod has any of the data files of the assets directory, see `Assets::apply_mods`.
+//! The paths in its `sprites.ron` are relative to the mod's own directory.
+//!
+//! `Settings::disabled_mods` backs out of some of them without editing the load order.
+
+use std::{fs, path::Path};
+
+use log::{info, warn};
+
+use crate::{assets, error::ZError, settings, ZResult};
+
+pub const DIR: &str = "mods";
+
+const LOAD_ORDER: &str = "load_order.ron";
+
+/// The names from the load order file, the skipped mods included.
+pub fn names() -> ZResult<Vec<String>> {
+    let load_order = Path::new(DIR).join(LOAD_ORDER);
+    if !load_order.exists() {
+        return Ok(Vec::new());
+    }
+    let s = fs::read_to_string(&load_order)?;
+    ron::de::from_str(&s).map_err(|e| ZError::from_ron_de_error(e, load_order))
+}
+
+/// The mods' directories relative to the assets directory, in the load order.
+///
+/// No mods are loaded without the load order file, the ones of `Settings::disabled_mods` are skipped.
+pub fn dirs() -> ZResult<Vec<String>> {
+    let disabled = settings::get().disabled_mods;
+    let mut dirs = Vec::new();
+    for name in names()? {
+        if disabled.contains(&name) {
+            info!("Mods: '{}' is disabled, see the mods screen", name);
+            continue;
+        }
+        if !Path::new(DIR).join(&name).is_dir() {
+            warn!("Mods: no '{}' directory in '{}', skipping it", name, DIR);
+            continue;
+        }
+        info!("Mods: loading '{}'", name);
+        dirs.push(format!("../{}/{}", DIR, name));
+    }
+    Ok(dirs)
+}
+
+/// Does the mod override this file?
+pub fn has_file(dir: &str, file: &str) -> bool {
+    Path::new(assets::DIR).join(dir).join(file).exists()
+}
+
+/// The files that can affect the loaded mods, see `assets::Watcher`.
+#[cfg(debug_assertions)]
+pub fn watched_files() -> Vec<std::path::PathBuf> {
+    let mut files = vec![Path::new(DIR).join(LOAD_ORDER)];
+    for dir in dirs().unwrap_or_default() {
+        for file in &assets::DATA_FILES {
+            files.push(Path::new(assets::DIR).join(&dir).join(file));
+        }
+    }
+    files
+}
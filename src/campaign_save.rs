@@ -0,0 +1,64 @@
+//! The ironman campaign in progress, kept as a RON file next to the game.
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    battle::{ai, dice},
+    campaign::State,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const PATH: &str = "campaign.ron";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Save {
+    pub state: State,
+    pub dice: dice::Mode,
+    pub ai: ai::Mode,
+
+    /// The game was closed in the middle of a battle,
+    /// the battle can't be started over, so it's lost.
+    pub is_in_battle: bool,
+}
+
+/// The missing or broken file means that there's nothing to continue.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load() -> Option<Save> {
+    let s = std::fs::read_to_string(PATH).ok()?;
+    ron::de::from_str(&s)
+        .map_err(|err| log::warn!("Can't parse '{}': {}", PATH, err))
+        .ok()
+}
+
+/// There are no files in browsers, the campaign can't be continued after the page is closed.
+#[cfg(target_arch = "wasm32")]
+pub fn load() -> Option<Save> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(save: &Save) {
+    let config = ron::ser::PrettyConfig::new();
+    let result = ron::ser::to_string_pretty(save, config)
+        .map_err(|err| err.to_string())
+        .and_then(|s| std::fs::write(PATH, s).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        log::warn!("Can't save the campaign to '{}': {}", PATH, err);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(_: &Save) {}
+
+/// The campaign is over, one way or another.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn remove() {
+    if let Err(err) = std::fs::remove_file(PATH) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Can't remove '{}': {}", PATH, err);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn remove() {}
@@ -1,4 +1,10 @@
+pub mod achievements;
 pub mod battle;
 pub mod campaign;
+pub mod editor;
+pub mod item;
 pub mod map;
+pub mod mod_diff;
+pub mod skirmish;
 pub mod utils;
+pub mod validation;
@@ -0,0 +1,183 @@
+//! Goals that are reached once and stay reached, across all the battles and campaigns.
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    battle::{ability::Ability, ai, state::BattleResult, stats::Stats, PlayerId},
+    campaign::AbilityUses,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Condition {
+    /// Win a battle where none of the player's agents has died.
+    FlawlessVictory,
+
+    /// Kill this many enemies with a single use of the ability.
+    MultiKill { ability: Ability, kills: i32 },
+
+    /// Win a campaign with these settings, `None` allows any.
+    WinCampaign {
+        #[serde(default)]
+        ability_uses: Option<AbilityUses>,
+
+        #[serde(default)]
+        ai: Option<ai::Mode>,
+    },
+}
+
+/// What has just happened, the conditions are checked against it.
+#[derive(Clone, Copy, Debug)]
+pub enum Trigger<'a> {
+    BattleEnd {
+        stats: &'a Stats,
+        result: &'a BattleResult,
+    },
+    CampaignEnd {
+        is_won: bool,
+        ability_uses: AbilityUses,
+        ai: ai::Mode,
+    },
+}
+
+fn matches<T: PartialEq>(required: Option<T>, actual: T) -> bool {
+    required.is_none_or(|required| required == actual)
+}
+
+impl Condition {
+    pub fn is_met(&self, trigger: Trigger) -> bool {
+        match (self, trigger) {
+            (Condition::FlawlessVictory, Trigger::BattleEnd { stats, result }) => {
+                let is_won = result.winner_id == PlayerId(0) && !result.is_retreat;
+                let is_anyone_lost = stats
+                    .agents()
+                    .iter()
+                    .any(|agent| agent.player_id == PlayerId(0) && agent.is_killed);
+                is_won && !is_anyone_lost
+            }
+            (&Condition::MultiKill { ability, kills }, Trigger::BattleEnd { stats, .. }) => {
+                stats.most_kills_at_once(PlayerId(0), ability) >= kills
+            }
+            (
+                &Condition::WinCampaign {
+                    ability_uses: required_ability_uses,
+                    ai: required_ai,
+                },
+                Trigger::CampaignEnd {
+                    is_won,
+                    ability_uses,
+                    ai,
+                },
+            ) => is_won && matches(required_ability_uses, ability_uses) && matches(required_ai, ai),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Achievement {
+    /// Stays the same when the title is changed, the unlocked ones are saved by it.
+    pub id: String,
+
+    pub title: String,
+    pub description: String,
+    pub condition: Condition,
+}
+
+/// Ids of the reached achievements, in the order they were reached.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Unlocked(Vec<String>);
+
+impl Unlocked {
+    pub fn contains(&self, id: &str) -> bool {
+        self.0.iter().any(|other| other == id)
+    }
+
+    /// Unlocks everything that the trigger meets and returns only the new ones.
+    pub fn check<'a>(
+        &mut self,
+        achievements: &'a [Achievement],
+        trigger: Trigger,
+    ) -> Vec<&'a Achievement> {
+        let mut new = Vec::new();
+        for achievement in achievements {
+            if !self.contains(&achievement.id) && achievement.condition.is_met(trigger) {
+                self.0.push(achievement.id.clone());
+                new.push(achievement);
+            }
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::core::{
+        achievements::{Achievement, Trigger, Unlocked},
+        battle::{ai, state::BattleResult, stats::Stats, PlayerId},
+        campaign::AbilityUses,
+    };
+
+    fn achievements() -> Vec<Achievement> {
+        ron::de::from_str(include_str!("../../assets/achievements.ron")).unwrap()
+    }
+
+    fn battle_result(winner_id: PlayerId) -> BattleResult {
+        BattleResult {
+            winner_id,
+            survivor_types: Vec::new(),
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
+        }
+    }
+
+    #[test]
+    fn achievements_are_unlocked_once() {
+        let achievements = achievements();
+        let mut unlocked = Unlocked::default();
+        let stats = Stats::default();
+        let result = battle_result(PlayerId(1));
+        let trigger = Trigger::BattleEnd {
+            stats: &stats,
+            result: &result,
+        };
+        assert!(unlocked.check(&achievements, trigger).is_empty());
+        let result = battle_result(PlayerId(0));
+        let trigger = Trigger::BattleEnd {
+            stats: &stats,
+            result: &result,
+        };
+        let new = unlocked.check(&achievements, trigger);
+        let ids: Vec<&str> = new.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["flawless_victory"]);
+        assert!(unlocked.contains("flawless_victory"));
+        assert!(unlocked.check(&achievements, trigger).is_empty());
+    }
+
+    #[test]
+    fn hard_campaign() {
+        let achievements = achievements();
+        let mut unlocked = Unlocked::default();
+        let trigger = Trigger::CampaignEnd {
+            is_won: true,
+            ability_uses: AbilityUses::PerBattle,
+            ai: ai::Mode::Lookahead,
+        };
+        let new = unlocked.check(&achievements, trigger);
+        let ids: Vec<&str> = new.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["campaign_won"]);
+        let trigger = Trigger::CampaignEnd {
+            is_won: true,
+            ability_uses: AbilityUses::PerDay,
+            ai: ai::Mode::Lookahead,
+        };
+        let new = unlocked.check(&achievements, trigger);
+        let ids: Vec<&str> = new.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["campaign_won_hard"]);
+    }
+}
@@ -9,16 +9,28 @@ pub use crate::core::{
 
 pub mod ability;
 pub mod ai;
+pub mod check;
+pub mod codex;
 pub mod command;
 pub mod component;
+pub mod decal;
+pub mod dice;
 pub mod effect;
 pub mod event;
 pub mod execute;
+pub mod forecast;
+pub mod hint;
+pub mod history;
+pub mod log;
 pub mod movement;
+pub mod objective;
+pub mod rng;
 pub mod scenario;
+pub mod simulate;
 pub mod state;
-
-mod check;
+pub mod stats;
+pub mod threat;
+pub mod tutorial;
 
 #[cfg(test)]
 mod tests;
@@ -136,6 +148,12 @@ pub struct Dodge(pub i32);
 pub enum TileType {
     Plain,
     Rocks,
+
+    /// Only the flyers can enter it, everyone else who's pushed in drowns.
+    Water,
+
+    /// The agents that end their turn here hide from the enemies, see `Lasting::Hidden`.
+    Thicket,
 }
 
 impl Default for TileType {
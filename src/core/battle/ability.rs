@@ -1,8 +1,17 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
-use crate::core::battle::{Rounds, Weight};
+use crate::core::{
+    battle::{component::ObjType, effect, Rounds, Weight},
+    map::Distance,
+};
 
 /// Active ability.
+///
+/// The movement, the bombs and the explosions, the summoning, the raising of the dead
+/// and defusing need their own code in `check` and `execute`, see `has_built_in_rules`.
+/// The rest is described by the `Definition`s from `abilities.ron`.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, derive_more::From)]
 pub enum Ability {
     Knockback,
@@ -24,22 +33,60 @@ pub enum Ability {
     Dash,
     Rage,
     Heal,
-    GreatHeal,
     Bloodlust,
+    Shove,
+    Totem,
+    Barricade,
+    Defuse,
+    Phase,
+    Hide,
+    Raise,
 }
 
+/// Every use spends a charge, the spent charges come back one at a time.
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-pub enum Status {
-    Ready,
-    Cooldown(Rounds),
+pub struct Status {
+    pub charges: i32,
+    pub max_charges: i32,
+
+    /// Rounds till the next spent charge is back, zero if nothing is recharging.
+    pub cooldown: Rounds,
 }
 
 impl Status {
-    pub fn update(&mut self) {
-        if let Status::Cooldown(ref mut rounds) = *self {
-            rounds.decrease();
-            if rounds.is_zero() {
-                *self = Status::Ready;
+    pub fn new(max_charges: i32) -> Self {
+        Self {
+            charges: max_charges,
+            max_charges,
+            cooldown: Rounds(0),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.charges > 0
+    }
+
+    /// The abilities without a cooldown never run out of charges.
+    pub fn spend(&mut self, cooldown: Rounds) {
+        assert!(self.is_ready());
+        if cooldown.is_zero() {
+            return;
+        }
+        self.charges -= 1;
+        if self.cooldown.is_zero() {
+            self.cooldown = cooldown;
+        }
+    }
+
+    pub fn update(&mut self, cooldown: Rounds) {
+        if self.cooldown.is_zero() {
+            return;
+        }
+        self.cooldown.decrease();
+        if self.cooldown.is_zero() {
+            self.charges += 1;
+            if self.charges < self.max_charges {
+                self.cooldown = cooldown;
             }
         }
     }
@@ -56,7 +103,7 @@ impl From<Ability> for RechargeableAbility {
     fn from(ability: Ability) -> Self {
         RechargeableAbility {
             ability,
-            status: Status::Ready,
+            status: Status::new(ability.base_charges()),
         }
     }
 }
@@ -83,36 +130,56 @@ impl Ability {
             Ability::Dash => "Dash".into(),
             Ability::Rage => "Rage".into(),
             Ability::Heal => "Heal".into(),
-            Ability::GreatHeal => "Great Heal".into(),
             Ability::Bloodlust => "Bloodlust".into(),
+            Ability::Shove => "Shove".into(),
+            Ability::Totem => "Healing Totem".into(),
+            Ability::Barricade => "Barricade".into(),
+            Ability::Defuse => "Defuse".into(),
+            Ability::Phase => "Phase".into(),
+            Ability::Hide => "Hide".into(),
+            Ability::Raise => "Raise Dead".into(),
         }
     }
 
-    pub fn base_cooldown(&self) -> Rounds {
-        let n = match self {
-            Ability::Knockback => 1,
-            Ability::Club => 2,
-            Ability::Jump => 2,
-            Ability::LongJump => 3,
-            Ability::Poison => 2,
-            Ability::ExplodePush => 2,
-            Ability::ExplodeDamage => 2,
-            Ability::ExplodeFire => 2,
-            Ability::ExplodePoison => 2,
+    /// The abilities that ignore the targets and the effects of their definitions.
+    pub fn has_built_in_rules(self) -> bool {
+        match self {
+            Ability::Knockback
+            | Ability::Shove
+            | Ability::Jump
+            | Ability::LongJump
+            | Ability::Dash
+            | Ability::Vanish
+            | Ability::Defuse
+            | Ability::ExplodePush
+            | Ability::ExplodeDamage
+            | Ability::ExplodeFire
+            | Ability::ExplodePoison
+            | Ability::Bomb
+            | Ability::BombPush
+            | Ability::BombFire
+            | Ability::BombPoison
+            | Ability::BombDemonic
+            | Ability::Summon
+            | Ability::Raise => true,
+            Ability::Club
+            | Ability::Poison
+            | Ability::Rage
+            | Ability::Heal
+            | Ability::Bloodlust
+            | Ability::Totem
+            | Ability::Barricade
+            | Ability::Phase
+            | Ability::Hide => false,
+        }
+    }
+
+    /// How many times the ability can be used in a row before it has to recharge.
+    pub fn base_charges(&self) -> i32 {
+        match self {
             Ability::Bomb => 2,
-            Ability::BombPush => 2,
-            Ability::BombFire => 2,
-            Ability::BombPoison => 2,
-            Ability::BombDemonic => 2,
-            Ability::Vanish => 2,
-            Ability::Summon => 3,
-            Ability::Dash => 1,
-            Ability::Rage => 3,
-            Ability::Heal => 3,
-            Ability::GreatHeal => 2,
-            Ability::Bloodlust => 3,
-        };
-        Rounds(n)
+            _ => 1,
+        }
     }
 
     pub fn description(&self) -> Vec<String> {
@@ -121,6 +188,11 @@ impl Ability {
                 "Push an adjusted object one tile away.".into(),
                 "Can move objects with a weight up to Normal.".into(),
             ],
+            Ability::Shove => vec![
+                "Push an adjusted object (not an agent) one tile away.".into(),
+                format!("Can move objects with a weight up to {}.", Weight::Heavy),
+                "A pushed object that hits an agent wounds it.".into(),
+            ],
             Ability::Club => vec!["Stun an adjusted agent for one turn.".into()],
             Ability::Jump => vec![
                 "Jump for up to 2 tiles.".into(),
@@ -152,10 +224,11 @@ impl Ability {
                 "Can be thrown for up to 3 tiles.".into(),
             ],
             Ability::BombDemonic => vec![
-                "Throw a demonic bomb".into(),
+                "Roll a demonic bomb".into(),
                 "that explodes on the next turn.".into(),
                 "Damages all agents on the neighbour tiles.".into(),
-                "Can be thrown for up to 3 tiles.".into(),
+                "Rolls for up to 3 tiles in a straight line".into(),
+                "and stops in front of the first obstacle.".into(),
             ],
             Ability::Dash => vec![
                 "Move one tile".into(),
@@ -163,23 +236,47 @@ impl Ability {
             ],
             Ability::Rage => vec!["Instantly receive 3 additional attacks.".into()],
             Ability::Heal => vec![
-                "Heal 2 strength points.".into(),
-                "Also, removes 'Poison' and 'Stun' lasting effects.".into(),
-            ],
-            Ability::GreatHeal => vec![
-                "Heal 3 strength points.".into(),
+                "Heal a few strength points of an agent nearby.".into(),
                 "Also, removes 'Poison' and 'Stun' lasting effects.".into(),
             ],
             Ability::Summon => vec![
                 "Summon a few lesser daemons.".into(),
                 "The number of summoned daemons increases".into(),
-                "by one with every use (up to six).".into(),
+                "by one with every use (up to six alive at once).".into(),
+                "The daemons die along with their summoner.".into(),
             ],
             Ability::Bloodlust => vec![
                 "Cast the 'Bloodlust' lasting effect on a friendly agent.".into(),
                 "This agent will receive three additional Jokers".into(),
                 "for a few turns.".into(),
             ],
+            Ability::Totem => vec![
+                "Put up a totem on a free tile nearby.".into(),
+                "Heals one strength point of every wounded ally".into(),
+                "next to it at the start of their turn.".into(),
+            ],
+            Ability::Barricade => vec![
+                "Put up a barricade on a free tile nearby.".into(),
+                "It blocks the way until it's broken.".into(),
+            ],
+            Ability::Defuse => vec![
+                "Safely remove a bomb on an adjusted tile".into(),
+                "before it explodes.".into(),
+            ],
+            Ability::Phase => vec![
+                "Cast the 'Phased' lasting effect on itself.".into(),
+                "Passes through the enemies till the next turn,".into(),
+                "but can't stop on their tiles.".into(),
+            ],
+            Ability::Hide => vec![
+                "Cast the 'Hidden' lasting effect on itself.".into(),
+                "Can't be seen or targeted by the enemies".into(),
+                "till it attacks or an enemy comes close.".into(),
+            ],
+            Ability::Raise => vec![
+                "Raise a skeleton from an adjusted corpse.".into(),
+                "The skeleton falls apart along with its master.".into(),
+            ],
             Ability::Poison
             | Ability::Vanish
             | Ability::ExplodePush
@@ -190,6 +287,156 @@ impl Ability {
     }
 }
 
+/// The first tier is the base one, every upgrade moves the ability one tier up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Tier(pub i32);
+
+impl Default for Tier {
+    fn default() -> Self {
+        Tier(1)
+    }
+}
+
+impl Tier {
+    pub fn title(self) -> String {
+        match self.0 {
+            1 => "I".into(),
+            2 => "II".into(),
+            3 => "III".into(),
+            4 => "IV".into(),
+            n => n.to_string(),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        Tier(self.0 + 1)
+    }
+}
+
+/// What one tier of an ability can do.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TierParams {
+    pub cooldown: Rounds,
+
+    /// Strength points that the ability restores or takes.
+    #[serde(default)]
+    pub power: i32,
+
+    /// `None` is for the abilities that reach anywhere.
+    #[serde(default = "default_distance")]
+    pub distance: Option<Distance>,
+}
+
+fn default_distance() -> Option<Distance> {
+    Some(Distance(1))
+}
+
+fn default_min_distance() -> Distance {
+    Distance(0)
+}
+
+/// Who a data-driven ability can be aimed at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Target {
+    /// The user itself, the position of the command is the user's own one.
+    Itself,
+
+    /// Any agent, friend or foe.
+    Agent,
+
+    /// An agent of the same side, the user included.
+    Ally,
+
+    /// A free tile for the objects that the ability creates.
+    Tile,
+}
+
+/// What a data-driven ability does to its target.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AbilityEffect {
+    /// Restores the tier's power, only the wounded agents can be targeted.
+    Heal,
+
+    /// Takes the tier's power, ignoring the armor.
+    Wound,
+
+    Lasting {
+        effect: effect::Lasting,
+        rounds: Rounds,
+    },
+
+    /// The user gets more attacks this turn.
+    Attacks(i32),
+
+    /// Puts an object of the user's side on the target tile.
+    Create(ObjType),
+}
+
+/// How a bomb gets to its target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Delivery {
+    /// Flies over anything in between, but needs a clear sky to aim by:
+    /// the fog and the darkness make the throws shorter and the wind carries the bombs.
+    #[default]
+    Thrown,
+
+    /// Rolls along a straight line and stops in front of the first blocker,
+    /// the weather and the darkness don't matter.
+    Rolled,
+}
+
+/// The rules of one ability.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Definition {
+    /// The abilities with built-in rules have no target,
+    /// only their tiers come from the definition.
+    #[serde(default)]
+    pub target: Option<Target>,
+
+    #[serde(default = "default_min_distance")]
+    pub min_distance: Distance,
+
+    #[serde(default)]
+    pub effects: Vec<AbilityEffect>,
+
+    /// Only the bombs care about it.
+    #[serde(default)]
+    pub delivery: Delivery,
+
+    /// From the first one, the abilities with more than one tier are upgradable.
+    pub tiers: Vec<TierParams>,
+}
+
+/// The abilities without a definition have one tier with no cooldown.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Definitions(pub HashMap<Ability, Definition>);
+
+impl Definitions {
+    pub fn get(&self, ability: Ability) -> Option<&Definition> {
+        self.0.get(&ability)
+    }
+
+    pub fn tiers_count(&self, ability: Ability) -> i32 {
+        self.get(ability)
+            .map_or(1, |definition| definition.tiers.len().max(1) as i32)
+    }
+
+    /// The tiers past the last one are the same as the last one.
+    pub fn params(&self, ability: Ability, tier: Tier) -> TierParams {
+        match self.get(ability).map(|definition| &definition.tiers) {
+            Some(tiers) if !tiers.is_empty() => {
+                let index = (tier.0 - 1).clamp(0, tiers.len() as i32 - 1);
+                tiers[index as usize]
+            }
+            _ => TierParams {
+                cooldown: Rounds(0),
+                power: 0,
+                distance: default_distance(),
+            },
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PassiveAbility {
     HeavyImpact,
@@ -199,6 +446,7 @@ pub enum PassiveAbility {
     SpikeTrap,
     PoisonAttack,
     Regenerate,
+    Light,
 }
 
 impl PassiveAbility {
@@ -211,6 +459,7 @@ impl PassiveAbility {
             PassiveAbility::SpikeTrap => "Spike Trap".into(),
             PassiveAbility::PoisonAttack => "Poison Attack".into(),
             PassiveAbility::Regenerate => "Regenerate".into(),
+            PassiveAbility::Light => "Light".into(),
         }
     }
 
@@ -235,6 +484,7 @@ impl PassiveAbility {
             }
             PassiveAbility::PoisonAttack => vec!["Regular attack poisons the target.".into()],
             PassiveAbility::Regenerate => vec!["Regenerates 1 strength points every turn.".into()],
+            PassiveAbility::Light => vec!["Lifts the night penalties on the nearby tiles.".into()],
         }
     }
 }
@@ -1,18 +1,116 @@
 use log::info;
+use serde::{Deserialize, Serialize};
 
 use crate::core::{
     battle::{
-        ability::Ability,
+        ability::{Ability, Delivery, PassiveAbility},
         check,
-        command::{self, Command},
-        effect,
+        command::{self, Command, CommandBatch},
+        component::{MovementType, Personality},
+        effect, execute,
+        forecast::{self, Forecast},
         movement::{self, Path, Pathfinder},
         state, Id, PlayerId, State,
     },
-    map::{self, Distance, HexMap},
+    map::{self, Distance, HexMap, PosHex},
     utils::shuffle_vec,
 };
 
+/// How the AI players choose their commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Mode {
+    /// Every agent follows a fixed list of simple rules.
+    #[default]
+    Greedy,
+
+    /// Agents simulate their possible moves and attacks on copies
+    /// of the state and pick the best scored ones.
+    /// Plays noticeably better, but costs a lot more CPU.
+    Lookahead,
+}
+
+impl Mode {
+    pub fn title(self) -> &'static str {
+        match self {
+            Mode::Greedy => "greedy",
+            Mode::Lookahead => "lookahead",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Mode::Greedy => Mode::Lookahead,
+            Mode::Lookahead => Mode::Greedy,
+        }
+    }
+}
+
+/// How much a kill is worth compared to a single point of damage.
+const KILL_SCORE: f32 = 3.0;
+
+/// How much every tile between an agent and its nearest enemy costs.
+const DISTANCE_SCORE: f32 = 0.5;
+
+/// Defenders only leave their posts for the enemies that come this close.
+const DEFENDER_RADIUS: Distance = Distance(3);
+
+/// How many extra move points an agent may spend to get behind its target.
+const FLANKING_DETOUR: movement::MovePoints = movement::MovePoints(1);
+
+/// AI agents notice enemies across the whole map during the day,
+/// but only half as far if the enemy stands in the dark.
+fn aggro_radius(state: &State, enemy_pos: map::PosHex) -> Distance {
+    let diameter = state.map().radius().0 * 2;
+    if state::is_lit(state, enemy_pos) {
+        Distance(diameter)
+    } else {
+        Distance(diameter / 2)
+    }
+}
+
+/// The hint from the agent's prototype or the one that suits its abilities.
+fn personality(state: &State, id: Id) -> Option<Personality> {
+    if let Some(&personality) = state.parts().personality.get_opt(id) {
+        return Some(personality);
+    }
+    if does_agent_have_ability(state, id, &Ability::Summon) {
+        Some(Personality::Cowardly)
+    } else if does_agent_have_ability(state, id, &Ability::BombDemonic) {
+        Some(Personality::Bomber)
+    } else {
+        None
+    }
+}
+
+/// How far from the enemies the agent wants to stay.
+fn preferred_distance(personality: Option<Personality>) -> Option<DistanceRange> {
+    match personality {
+        Some(Personality::Cowardly) => Some(DistanceRange {
+            min: Distance(2),
+            max: Distance(4),
+        }),
+        Some(Personality::Bomber) => Some(DistanceRange {
+            min: Distance(1),
+            max: Distance(3),
+        }),
+        _ => None,
+    }
+}
+
+/// How much the damage taken matters compared to the damage dealt.
+fn caution(personality: Option<Personality>) -> f32 {
+    match personality {
+        Some(Personality::Aggressive) => 0.5,
+        Some(Personality::Cowardly) => 2.0,
+        _ => 1.0,
+    }
+}
+
+fn is_badly_wounded(state: &State, id: Id) -> bool {
+    let strength = state.parts().strength.get(id);
+    strength.strength.0 * 2 < strength.base_strength.0
+}
+
 fn does_agent_have_ability(state: &State, id: Id, ability: &Ability) -> bool {
     if let Some(abilities) = state.parts().abilities.get_opt(id) {
         for current_ability in &abilities.0 {
@@ -30,6 +128,95 @@ fn check_path_is_ok(state: &State, id: Id, path: &Path) -> bool {
     check(state, &command).is_ok()
 }
 
+fn is_hazard(state: &State, pos: PosHex) -> bool {
+    let hazards = [
+        PassiveAbility::Burn,
+        PassiveAbility::Poison,
+        PassiveAbility::SpikeTrap,
+    ];
+    hazards
+        .iter()
+        .any(|&ability| state::obj_with_passive_ability_at(state, pos, ability).is_some())
+}
+
+/// How good it is to push the agent onto the tile, a drowned one is dead for sure.
+fn push_into_score(state: &State, id: Id, pos: PosHex) -> f32 {
+    if state::is_drowning_at(state, id, pos) {
+        3.0
+    } else if is_hazard(state, pos) {
+        2.0
+    } else {
+        0.0
+    }
+}
+
+fn has_lasting_effect(state: &State, id: Id, effect: effect::Lasting) -> bool {
+    match state.parts().effects.get_opt(id) {
+        Some(effects) => effects.0.iter().any(|timed| timed.effect == effect),
+        None => false,
+    }
+}
+
+/// Tiles that the bombs are going to hit before the agents can move again.
+fn explosion_zone(state: &State) -> Vec<PosHex> {
+    let mut zone = Vec::new();
+    for (pos, fuse) in state::bomb_fuses(state) {
+        if fuse > 1 {
+            continue;
+        }
+        zone.push(pos);
+        zone.extend(map::dirs().map(|dir| map::Dir::get_neighbor_pos(pos, dir)));
+    }
+    zone
+}
+
+/// The rolled bombs may stop short of the target, the thrown ones land right on it.
+fn bomb_landing_pos(state: &State, agent_pos: PosHex, ability: Ability, pos: PosHex) -> PosHex {
+    match state::bomb_delivery(state, ability) {
+        Delivery::Thrown => pos,
+        Delivery::Rolled => state::rolled_bomb_pos(state, agent_pos, pos).unwrap_or(pos),
+    }
+}
+
+fn tiles_in_range(state: &State, origin: PosHex, min: Distance, max: Distance) -> Vec<PosHex> {
+    let tiles = state.map().iter().filter(|&pos| {
+        let distance = map::distance_hex(origin, pos);
+        distance >= min && distance <= max
+    });
+    shuffle_vec(tiles.collect())
+}
+
+fn neighbors(pos: PosHex) -> impl Iterator<Item = PosHex> {
+    map::dirs().map(move |dir| map::Dir::get_neighbor_pos(pos, dir))
+}
+
+/// The expected damage of the attack with kills counted extra.
+fn attack_value(forecast: &forecast::AttackForecast) -> f32 {
+    forecast.expected_damage() + forecast.kill_chance() * KILL_SCORE
+}
+
+/// Damage dealt minus damage taken, both expected ones.
+fn attack_score(forecast: &forecast::AttackForecast, caution: f32) -> f32 {
+    let taken: f32 = forecast.retaliation.iter().map(attack_value).sum();
+    attack_value(forecast) - taken * caution
+}
+
+/// Runs the commands on a copy of the state, `None` if any of them fails.
+fn simulate(state: &State, commands: &[Command]) -> Option<State> {
+    let mut state = state.fork_for_simulation();
+    for command in commands {
+        execute::execute(&mut state, command, &mut |_, _, _| {}).ok()?;
+    }
+    Some(state)
+}
+
+/// A sequence of commands of a single agent and its score.
+#[derive(Clone, Debug)]
+struct Plan {
+    commands: Vec<Command>,
+    score: f32,
+}
+
 #[derive(Clone, Debug)]
 enum PathfindingResult {
     Path(Path),
@@ -62,12 +249,23 @@ impl Ai {
         }
     }
 
+    /// Enemies that are close enough for the agent to go after them.
+    fn noticed_enemy_ids(&self, state: &State, agent_id: Id) -> Vec<Id> {
+        let agent_pos = state.parts().pos.get(agent_id).0;
+        let mut ids = state::visible_enemy_agent_ids(state, self.id);
+        ids.retain(|&id| {
+            let enemy_pos = state.parts().pos.get(id).0;
+            map::distance_hex(agent_pos, enemy_pos) <= aggro_radius(state, enemy_pos)
+        });
+        ids
+    }
+
     /// Finds shortest path to some enemy.
     fn find_path_to_nearest_enemy(&mut self, state: &State, agent_id: Id) -> Option<Path> {
         self.pathfinder.fill_map(state, agent_id);
         let mut best_path = None;
         let mut best_cost = movement::max_cost();
-        for &target_id in &shuffle_vec(state::enemy_agent_ids(state, self.id)) {
+        for &target_id in &shuffle_vec(self.noticed_enemy_ids(state, agent_id)) {
             let target_pos = state.parts().pos.get(target_id).0;
             for dir in map::dirs() {
                 let pos = map::Dir::get_neighbor_pos(target_pos, dir);
@@ -78,7 +276,11 @@ impl Ai {
                     Some(path) => path,
                     None => continue,
                 };
-                let cost = path.cost_for(state, agent_id);
+                let mut cost = path.cost_for(state, agent_id);
+                // Try to flank: a backstab is worth a little detour.
+                if state::is_in_rear_arc(state, target_id, pos) {
+                    cost.0 -= FLANKING_DETOUR.0;
+                }
                 if best_cost > cost {
                     best_cost = cost;
                     best_path = Some(path);
@@ -100,13 +302,13 @@ impl Ai {
         }
 
         for pos in self.distance_map.iter() {
-            for &enemy_id in &state::enemy_agent_ids(state, self.id) {
+            for &enemy_id in &state::visible_enemy_agent_ids(state, self.id) {
                 let enemy_pos = state.parts().pos.get(enemy_id).0;
                 if map::distance_hex(pos, enemy_pos) <= distance_range.max {
                     self.distance_map.set_tile(pos, true);
                 }
             }
-            for &enemy_id in &state::enemy_agent_ids(state, self.id) {
+            for &enemy_id in &state::visible_enemy_agent_ids(state, self.id) {
                 let enemy_pos = state.parts().pos.get(enemy_id).0;
                 if map::distance_hex(pos, enemy_pos) <= distance_range.min {
                     self.distance_map.set_tile(pos, false);
@@ -144,7 +346,7 @@ impl Ai {
                 Some(path) => path,
                 None => continue,
             };
-            for &enemy_id in &state::enemy_agent_ids(state, self.id) {
+            for &enemy_id in &self.noticed_enemy_ids(state, agent_id) {
                 let enemy_pos = state.parts().pos.get(enemy_id).0;
                 let distance = map::distance_hex(pos, enemy_pos);
                 // TODO: compare path costs
@@ -157,56 +359,365 @@ impl Ai {
         best_path
     }
 
-    fn try_throw_bomb(&self, state: &State, agent_id: Id) -> Option<Command> {
-        // TODO: find ability in the parts and use it here:
-        let ability: Ability = Ability::BombDemonic;
-        for &target_id in &shuffle_vec(state::enemy_agent_ids(state, self.id)) {
-            let target_pos = state.parts().pos.get(target_id).0;
-            for dir in shuffle_vec(map::dirs().collect()) {
-                let pos = map::Dir::get_neighbor_pos(target_pos, dir);
-                if !state.map().is_inboard(pos) || state::is_tile_blocked(state, pos) {
-                    continue;
-                }
-                let id = agent_id;
-                let command = command::UseAbility { id, pos, ability }.into();
-                if check(state, &command).is_ok() {
-                    return Some(command);
-                }
+    fn is_enemy_agent(&self, state: &State, id: Id) -> bool {
+        state.parts().agent.get_opt(id).is_some()
+            && !state::are_allies(state, self.id, state.parts().belongs_to.get(id).0)
+    }
+
+    fn enemy_at(&self, state: &State, pos: PosHex) -> Option<Id> {
+        state::agent_id_at_opt(state, pos).filter(|&id| self.is_enemy_agent(state, id))
+    }
+
+    /// Pushing an enemy into a hazard or the water, or an object into an enemy.
+    fn score_push(&self, state: &State, agent_id: Id, pos: PosHex) -> f32 {
+        let target_id = match state::blocker_id_at_opt(state, pos) {
+            Some(id) => id,
+            None => return 0.0,
+        };
+        let agent_pos = state.parts().pos.get(agent_id).0;
+        let dir = map::Dir::get_dir_from_to(agent_pos, pos);
+        let destination = map::Dir::get_neighbor_pos(pos, dir);
+        if self.is_enemy_agent(state, target_id) {
+            push_into_score(state, target_id, destination)
+        } else if state.parts().agent.get_opt(target_id).is_none()
+            && self.enemy_at(state, destination).is_some()
+        {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// A stunned enemy doesn't deal its damage next turn.
+    fn score_club(&self, state: &State, pos: PosHex) -> f32 {
+        match self.enemy_at(state, pos) {
+            Some(id) if !has_lasting_effect(state, id, effect::Lasting::Stun) => {
+                state.parts().agent.get(id).attack_strength.0 as f32
             }
+            _ => 0.0,
         }
-        None
     }
 
-    fn try_summon_imp(&self, state: &State, agent_id: Id) -> Option<Command> {
-        // TODO: find ability in the parts and use it here:
-        let ability = Ability::Summon;
-        let pos = state.parts().pos.get(agent_id).0;
-        let id = agent_id;
-        let command = command::UseAbility { id, pos, ability }.into();
-        if check(state, &command).is_ok() {
-            return Some(command);
+    /// Landing next to an enemy is worth more if there's an attack left after the jump.
+    fn score_jump(&self, state: &State, agent_id: Id, pos: PosHex) -> f32 {
+        let agent_pos = state.parts().pos.get(agent_id).0;
+        if state::check_enemies_around(state, agent_pos, self.id)
+            || !state::check_enemies_around(state, pos, self.id)
+        {
+            return 0.0;
+        }
+        let agent = state.parts().agent.get(agent_id);
+        if agent.attacks.0 + agent.jokers.0 > 1 {
+            1.0
+        } else {
+            0.5
         }
-        None
     }
 
-    fn try_bloodlust_imp(&self, state: &State, agent_id: Id) -> Option<Command> {
-        let imps = ["imp", "toxic_imp"];
-        'target_loop: for target_id in shuffle_vec(state::players_agent_ids(state, self.id)) {
-            // AI can bloodlust only "imp"s for now.
-            let type_name = state.parts().meta.get(target_id).name.0.as_str();
-            if !imps.contains(&type_name) {
-                continue;
+    fn score_poison(&self, state: &State, pos: PosHex) -> f32 {
+        match self.enemy_at(state, pos) {
+            Some(id) if !has_lasting_effect(state, id, effect::Lasting::Poison) => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Enemies minus allies around the tile where a bomb explodes.
+    fn score_bomb(&self, state: &State, pos: PosHex) -> f32 {
+        let mut score = 0.0;
+        for neighbor_pos in neighbors(pos) {
+            if let Some(id) = state::agent_id_at_opt(state, neighbor_pos) {
+                score += if self.is_enemy_agent(state, id) {
+                    1.0
+                } else {
+                    -1.0
+                };
             }
-            // Make sure we won't try to make bloodlust an already frenzied imp.
-            if let Some(effects) = state.parts().effects.get_opt(target_id) {
-                for effect in &effects.0 {
-                    if effect.effect == effect::Lasting::Bloodlust {
-                        continue 'target_loop;
-                    }
+        }
+        score
+    }
+
+    /// Push bombs are only good for throwing the enemies into hazards or the water.
+    fn score_bomb_push(&self, state: &State, pos: PosHex) -> f32 {
+        let mut score = 0.0;
+        for dir in map::dirs() {
+            let neighbor_pos = map::Dir::get_neighbor_pos(pos, dir);
+            let id = match state::agent_id_at_opt(state, neighbor_pos) {
+                Some(id) => id,
+                None => continue,
+            };
+            let destination = map::Dir::get_neighbor_pos(neighbor_pos, dir);
+            let push_score = push_into_score(state, id, destination);
+            score += if self.is_enemy_agent(state, id) {
+                push_score
+            } else {
+                -push_score
+            };
+        }
+        score
+    }
+
+    /// Scared agents use a dash to get away from the enemies without reaction attacks.
+    fn score_dash(&self, state: &State, agent_id: Id, pos: PosHex) -> f32 {
+        let is_scared = personality(state, agent_id) == Some(Personality::Cowardly)
+            || is_badly_wounded(state, agent_id);
+        let agent_pos = state.parts().pos.get(agent_id).0;
+        if !is_scared || state::check_enemies_around(state, pos, self.id) {
+            return 0.0;
+        }
+        let enemies_around = neighbors(agent_pos)
+            .filter(|&pos| self.enemy_at(state, pos).is_some())
+            .count();
+        if enemies_around == 0 {
+            return 0.0;
+        }
+        1.0 + enemies_around as f32
+    }
+
+    /// Restored strength, cleansed harmful effects and wasted bloodlust.
+    fn score_heal(&self, state: &State, agent_id: Id, pos: PosHex, ability: Ability) -> f32 {
+        let target_id = match state::agent_id_at_opt(state, pos) {
+            Some(id) if !self.is_enemy_agent(state, id) => id,
+            _ => return 0.0,
+        };
+        let command = command::UseAbility {
+            id: agent_id,
+            pos,
+            ability,
+        }
+        .into();
+        let forecast = match forecast::forecast(state, &command) {
+            Some(Forecast::Heal(forecast)) if forecast.target_id == target_id => forecast,
+            _ => return 0.0,
+        };
+        let mut score = forecast.strength.0 as f32;
+        for effect in forecast.removed_effects {
+            score += match effect {
+                effect::Lasting::Poison | effect::Lasting::Stun => 0.5,
+                effect::Lasting::Bloodlust | effect::Lasting::Phased | effect::Lasting::Hidden => {
+                    -1.0
                 }
+                // Never removed, see `apply_effect_heal`.
+                effect::Lasting::Injured => 0.0,
+            };
+        }
+        score
+    }
+
+    /// The wounded allies that a totem on the tile would heal.
+    fn score_totem(&self, state: &State, pos: PosHex) -> f32 {
+        if state::is_tile_blocked(state, pos) {
+            return 0.0;
+        }
+        let wounded = state::players_agent_ids(state, self.id)
+            .into_iter()
+            .filter(|&id| {
+                let strength = state.parts().strength.get(id);
+                let agent_pos = state.parts().pos.get(id).0;
+                strength.strength < strength.base_strength
+                    && map::distance_hex(pos, agent_pos).0 <= 1
+            })
+            .count();
+        wounded as f32
+    }
+
+    /// A barricade is only worth it in the way of an enemy that is next to a wounded ally.
+    fn score_barricade(&self, state: &State, pos: PosHex) -> f32 {
+        if state::is_tile_blocked(state, pos) {
+            return 0.0;
+        }
+        if !neighbors(pos).any(|neighbor_pos| self.enemy_at(state, neighbor_pos).is_some()) {
+            return 0.0;
+        }
+        let wounded = neighbors(pos)
+            .filter_map(|neighbor_pos| state::agent_id_at_opt(state, neighbor_pos))
+            .filter(|&id| !self.is_enemy_agent(state, id))
+            .filter(|&id| {
+                let strength = state.parts().strength.get(id);
+                strength.strength < strength.base_strength
+            })
+            .count();
+        wounded as f32
+    }
+
+    /// A defused bomb spares everyone around it, so it's the opposite of throwing one.
+    fn score_defuse(&self, state: &State, pos: PosHex) -> f32 {
+        match state::blocker_id_at_opt(state, pos) {
+            Some(id) if state::bomb_fuse(state, id).is_some() => -self.score_bomb(state, pos),
+            _ => 0.0,
+        }
+    }
+
+    /// Bloodlust is for the allies that are about to fight.
+    fn score_bloodlust(&self, state: &State, pos: PosHex) -> f32 {
+        let id = match state::agent_id_at_opt(state, pos) {
+            Some(id) => id,
+            None => return 0.0,
+        };
+        if state.parts().belongs_to.get(id).0 != self.id
+            || state.parts().agent.get(id).attack_strength.0 == 0
+            || has_lasting_effect(state, id, effect::Lasting::Bloodlust)
+            || self.noticed_enemy_ids(state, id).is_empty()
+        {
+            return 0.0;
+        }
+        1.5
+    }
+
+    /// Phasing is for the walkers that can get to the enemies only through the others.
+    fn score_phase(&self, state: &State, agent_id: Id) -> f32 {
+        if state::movement_type(state, agent_id) != MovementType::Walk {
+            return 0.0;
+        }
+        let move_points = state.parts().agent.get(agent_id).move_points;
+        let targets: Vec<PosHex> = self
+            .noticed_enemy_ids(state, agent_id)
+            .into_iter()
+            .flat_map(|id| neighbors(state.parts().pos.get(id).0))
+            .filter(|&pos| state.map().is_inboard(pos))
+            .collect();
+        let is_reachable_as = |movement_type| {
+            let mut pathfinder = Pathfinder::new(state.map().radius());
+            pathfinder.fill_map_as(state, agent_id, movement_type);
+            targets.iter().any(|&pos| {
+                pathfinder
+                    .path(pos)
+                    .is_some_and(|path| path.cost_for(state, agent_id) <= move_points)
+            })
+        };
+        if is_reachable_as(MovementType::Walk) || !is_reachable_as(MovementType::Phase) {
+            return 0.0;
+        }
+        1.0
+    }
+
+    /// Hiding is for sneaking up on the enemies that aren't close yet.
+    fn score_hide(&self, state: &State, agent_id: Id) -> f32 {
+        if state::is_hidden(state, agent_id)
+            || state::is_next_to_enemy(state, agent_id)
+            || self.noticed_enemy_ids(state, agent_id).is_empty()
+        {
+            return 0.0;
+        }
+        0.5
+    }
+
+    /// The best use of the ability in the same units as the attack scores,
+    /// `None` if there's nothing good to do with it.
+    fn score_ability(
+        &self,
+        state: &State,
+        agent_id: Id,
+        ability: Ability,
+    ) -> Option<(Command, f32)> {
+        let agent_pos = state.parts().pos.get(agent_id).0;
+        let range = |min, max| tiles_in_range(state, agent_pos, Distance(min), Distance(max));
+        // The tiles in the range of the ability's definition, the unlimited ones aren't used here.
+        let defined_range = || {
+            let min = state
+                .ability_definitions()
+                .get(ability)
+                .map_or(Distance(0), |definition| definition.min_distance);
+            let params = state::ability_params(state, agent_id, ability);
+            tiles_in_range(state, agent_pos, min, params.distance.unwrap_or(min))
+        };
+        let candidates: Vec<(PosHex, f32)> = match ability {
+            Ability::Knockback | Ability::Shove => range(1, 1)
+                .into_iter()
+                .map(|pos| (pos, self.score_push(state, agent_id, pos)))
+                .collect(),
+            Ability::Club => defined_range()
+                .into_iter()
+                .map(|pos| (pos, self.score_club(state, pos)))
+                .collect(),
+            Ability::Jump | Ability::LongJump => {
+                let max = if ability == Ability::Jump { 2 } else { 3 };
+                range(2, max)
+                    .into_iter()
+                    .map(|pos| (pos, self.score_jump(state, agent_id, pos)))
+                    .collect()
+            }
+            Ability::Poison => defined_range()
+                .into_iter()
+                .map(|pos| (pos, self.score_poison(state, pos)))
+                .collect(),
+            Ability::Bomb | Ability::BombFire | Ability::BombPoison | Ability::BombDemonic => {
+                range(0, 3)
+                    .into_iter()
+                    .map(|pos| {
+                        let landing_pos = bomb_landing_pos(state, agent_pos, ability, pos);
+                        (pos, self.score_bomb(state, landing_pos))
+                    })
+                    .collect()
+            }
+            Ability::BombPush => range(0, 3)
+                .into_iter()
+                .map(|pos| (pos, self.score_bomb_push(state, pos)))
+                .collect(),
+            Ability::Summon => vec![(agent_pos, state::summon_count(state, agent_id) as f32)],
+            Ability::Phase => vec![(agent_pos, self.score_phase(state, agent_id))],
+            Ability::Hide => vec![(agent_pos, self.score_hide(state, agent_id))],
+            Ability::Dash => range(1, 1)
+                .into_iter()
+                .map(|pos| (pos, self.score_dash(state, agent_id, pos)))
+                .collect(),
+            Ability::Defuse => range(1, 1)
+                .into_iter()
+                .map(|pos| (pos, self.score_defuse(state, pos)))
+                .collect(),
+            // A new ally is always welcome, the check filters out the tiles without corpses.
+            Ability::Raise => range(1, 1).into_iter().map(|pos| (pos, 1.0)).collect(),
+            Ability::Rage => {
+                // Three extra attacks for the price of one.
+                let value = state::visible_enemy_agent_ids(state, self.id)
+                    .into_iter()
+                    .filter_map(|target_id| {
+                        let command = command::Attack {
+                            attacker_id: agent_id,
+                            target_id,
+                        }
+                        .into();
+                        match forecast::forecast(state, &command) {
+                            Some(Forecast::Attack(forecast)) => Some(attack_value(&forecast)),
+                            _ => None,
+                        }
+                    })
+                    .fold(0.0, f32::max);
+                vec![(agent_pos, value * 2.0)]
+            }
+            Ability::Heal => defined_range()
+                .into_iter()
+                .map(|pos| (pos, self.score_heal(state, agent_id, pos, ability)))
+                .collect(),
+            Ability::Totem => defined_range()
+                .into_iter()
+                .map(|pos| (pos, self.score_totem(state, pos)))
+                .collect(),
+            Ability::Barricade => defined_range()
+                .into_iter()
+                .map(|pos| (pos, self.score_barricade(state, pos)))
+                .collect(),
+            Ability::Bloodlust => state::players_agent_ids(state, self.id)
+                .into_iter()
+                .map(|id| state.parts().pos.get(id).0)
+                .map(|pos| (pos, self.score_bloodlust(state, pos)))
+                .collect(),
+            // Only bombs and clouds have these, and they're triggered by the schedules,
+            // see `try_to_avoid_explosions` for how the agents deal with them.
+            Ability::Vanish
+            | Ability::ExplodePush
+            | Ability::ExplodeDamage
+            | Ability::ExplodeFire
+            | Ability::ExplodePoison => Vec::new(),
+        };
+        let mut best: Option<(Command, f32)> = None;
+        for (pos, score) in candidates {
+            let is_better = match &best {
+                Some((_, best_score)) => score > *best_score,
+                None => score > 0.0,
+            };
+            if !is_better {
+                continue;
             }
-            let ability = Ability::Bloodlust;
-            let pos = state.parts().pos.get(target_id).0;
             let command = command::UseAbility {
                 id: agent_id,
                 pos,
@@ -214,14 +725,69 @@ impl Ai {
             }
             .into();
             if check(state, &command).is_ok() {
-                return Some(command);
+                best = Some((command, score));
             }
         }
-        None
+        best
+    }
+
+    /// The most valuable ability use, if it's worth more than the best plain attack.
+    fn try_abilities(&self, state: &State, agent_id: Id) -> Option<Command> {
+        let abilities = state.parts().abilities.get_opt(agent_id)?;
+        let mut best_score = self.best_attack(state, agent_id).map_or(0.0, |(_, s)| s);
+        let mut best = None;
+        for r_ability in &abilities.0 {
+            if !r_ability.status.is_ready() {
+                continue;
+            }
+            if let Some((command, score)) = self.score_ability(state, agent_id, r_ability.ability) {
+                if score > best_score {
+                    best_score = score;
+                    best = Some(command);
+                }
+            }
+        }
+        best
+    }
+
+    /// Steps out of the way of the bombs that are about to explode.
+    fn try_to_avoid_explosions(&mut self, state: &State, agent_id: Id) -> Option<Command> {
+        let zone = explosion_zone(state);
+        let agent_pos = state.parts().pos.get(agent_id).0;
+        if !zone.contains(&agent_pos) {
+            return None;
+        }
+        self.pathfinder.fill_map(state, agent_id);
+        let mut best_path = None;
+        let mut best_cost = movement::max_cost();
+        for pos in state.map().iter() {
+            if zone.contains(&pos) {
+                continue;
+            }
+            let path = match self.pathfinder.path(pos) {
+                Some(path) => path,
+                None => continue,
+            };
+            let cost = path.cost_for(state, agent_id);
+            if cost < best_cost && check_path_is_ok(state, agent_id, &path) {
+                best_cost = cost;
+                best_path = Some(path);
+            }
+        }
+        let path = best_path?;
+        Some(command::MoveTo { id: agent_id, path }.into())
     }
 
+    /// Prefers the targets that can be attacked from behind,
+    /// aggressive agents prefer the weakest ones instead.
     fn try_to_attack(&self, state: &State, agent_id: Id) -> Option<Command> {
-        for &target_id in &shuffle_vec(state::enemy_agent_ids(state, self.id)) {
+        let mut targets = shuffle_vec(state::visible_enemy_agent_ids(state, self.id));
+        if personality(state, agent_id) == Some(Personality::Aggressive) {
+            targets.sort_by_key(|&target_id| state.parts().strength.get(target_id).strength.0);
+        } else {
+            targets.sort_by_key(|&target_id| !state::is_backstab(state, agent_id, target_id));
+        }
+        for &target_id in &targets {
             let attacker_id = agent_id;
             let command = command::Attack {
                 attacker_id,
@@ -307,21 +873,57 @@ impl Ai {
         None
     }
 
-    fn try_to_move(&mut self, state: &State, agent_id: Id) -> Option<Command> {
-        let path_result = if does_agent_have_ability(state, agent_id, &Ability::Summon) {
-            let range = DistanceRange {
-                min: Distance(2),
-                max: Distance(4),
-            };
-            self.try_to_keep_distance(state, agent_id, range)
-        } else if does_agent_have_ability(state, agent_id, &Ability::BombDemonic) {
-            let range = DistanceRange {
-                min: Distance(1),
-                max: Distance(3),
+    /// Routed agents run towards their board edge.
+    fn try_to_flee(&mut self, state: &State, agent_id: Id) -> Option<Command> {
+        self.pathfinder.fill_map(state, agent_id);
+        let agent_pos = state.parts().pos.get(agent_id).0;
+        let mut best_path = None;
+        let mut best_distance = state::distance_to_home_edge(state, self.id, agent_pos);
+        for pos in state.map().iter() {
+            let distance = state::distance_to_home_edge(state, self.id, pos);
+            if distance >= best_distance {
+                continue;
+            }
+            let path = match self.pathfinder.path(pos) {
+                Some(path) => path,
+                None => continue,
             };
-            self.try_to_keep_distance(state, agent_id, range)
-        } else {
-            self.try_to_move_closer(state, agent_id)
+            if check_path_is_ok(state, agent_id, &path) {
+                best_distance = distance;
+                best_path = Some(path);
+            }
+        }
+        let path = best_path?;
+        Some(command::MoveTo { id: agent_id, path }.into())
+    }
+
+    /// Defenders hold their ground till some enemy comes close to their side.
+    fn may_leave_post(&self, state: &State, agent_id: Id) -> bool {
+        if personality(state, agent_id) != Some(Personality::Defender) {
+            return true;
+        }
+        let allies = state::players_agent_ids(state, self.id);
+        let enemies = state::visible_enemy_agent_ids(state, self.id);
+        allies.iter().any(|&ally_id| {
+            let ally_pos = state.parts().pos.get(ally_id).0;
+            enemies.iter().any(|&enemy_id| {
+                let enemy_pos = state.parts().pos.get(enemy_id).0;
+                map::distance_hex(ally_pos, enemy_pos) <= DEFENDER_RADIUS
+            })
+        })
+    }
+
+    fn try_to_move(&mut self, state: &State, agent_id: Id) -> Option<Command> {
+        // Wait in the dark till some enemy shows up.
+        if self.noticed_enemy_ids(state, agent_id).is_empty() {
+            return None;
+        }
+        if !self.may_leave_post(state, agent_id) {
+            return None;
+        }
+        let path_result = match preferred_distance(personality(state, agent_id)) {
+            Some(range) => self.try_to_keep_distance(state, agent_id, range),
+            None => self.try_to_move_closer(state, agent_id),
         };
         match path_result {
             PathfindingResult::Path(path) => {
@@ -337,30 +939,165 @@ impl Ai {
         }
     }
 
+    /// Scores the state after the agent has finished its commands.
+    ///
+    /// `strength_before` is the agent's strength before the commands:
+    /// reaction attacks along the way count as the damage taken.
+    fn plan_score(&self, state: &State, agent_id: Id, strength_before: i32) -> f32 {
+        let caution = caution(personality(state, agent_id));
+        let strength = match state.parts().strength.get_opt(agent_id) {
+            Some(strength) => strength.strength.0,
+            None => return -(strength_before as f32 + KILL_SCORE) * caution,
+        };
+        let mut score = (strength - strength_before) as f32 * caution;
+        let agent_pos = state.parts().pos.get(agent_id).0;
+        let nearest_enemy = self
+            .noticed_enemy_ids(state, agent_id)
+            .into_iter()
+            .map(|id| map::distance_hex(agent_pos, state.parts().pos.get(id).0).0)
+            .min();
+        if let Some(distance) = nearest_enemy {
+            score -= distance as f32 * DISTANCE_SCORE;
+        }
+        score
+    }
+
+    /// The best attack from the agent's current position, if any.
+    fn best_attack(&self, state: &State, agent_id: Id) -> Option<(Command, f32)> {
+        let caution = caution(personality(state, agent_id));
+        let mut best: Option<(Command, f32)> = None;
+        for target_id in state::visible_enemy_agent_ids(state, self.id) {
+            let command: Command = command::Attack {
+                attacker_id: agent_id,
+                target_id,
+            }
+            .into();
+            let score = match forecast::forecast(state, &command) {
+                Some(Forecast::Attack(forecast)) => attack_score(&forecast, caution),
+                _ => continue,
+            };
+            let is_better = match &best {
+                Some((_, best_score)) => score > *best_score,
+                None => true,
+            };
+            if is_better {
+                best = Some((command, score));
+            }
+        }
+        best
+    }
+
+    /// A plan that starts with the given commands and may end with an attack.
+    fn make_plan(&self, state: &State, agent_id: Id, commands: Vec<Command>) -> Option<Plan> {
+        let strength_before = state.parts().strength.get(agent_id).strength.0;
+        let state = simulate(state, &commands)?;
+        let mut plan = Plan {
+            score: self.plan_score(&state, agent_id, strength_before),
+            commands,
+        };
+        if state.parts().agent.get_opt(agent_id).is_none() {
+            return Some(plan);
+        }
+        if let Some((attack, score)) = self.best_attack(&state, agent_id) {
+            if score > 0.0 {
+                plan.commands.push(attack);
+                plan.score += score;
+            }
+        }
+        Some(plan)
+    }
+
+    /// Tries every tile the agent can reach this turn and every attack from there.
+    ///
+    /// Returns the whole plan, e.g. a move and an attack,
+    /// `None` if standing still is the best option.
+    fn try_lookahead(&mut self, state: &State, agent_id: Id) -> Option<CommandBatch> {
+        let mut best = self.make_plan(state, agent_id, Vec::new())?;
+        let may_move = self.may_leave_post(state, agent_id);
+        if may_move && !self.noticed_enemy_ids(state, agent_id).is_empty() {
+            self.pathfinder.fill_map(state, agent_id);
+            for pos in state.map().iter() {
+                let path = match self.pathfinder.path(pos) {
+                    Some(path) if path.tiles().len() > 1 => path,
+                    _ => continue,
+                };
+                if !check_path_is_ok(state, agent_id, &path) {
+                    continue;
+                }
+                let command = command::MoveTo { id: agent_id, path }.into();
+                if let Some(plan) = self.make_plan(state, agent_id, vec![command]) {
+                    if plan.score > best.score {
+                        best = plan;
+                    }
+                }
+            }
+        }
+        if best.commands.is_empty() {
+            None
+        } else {
+            Some(CommandBatch::new(best.commands))
+        }
+    }
+
+    /// Places the next waiting agent at a random free tile of the deployment zone.
+    fn deploy(&self, state: &State) -> Option<Command> {
+        let tiles = shuffle_vec(state::free_deployment_tiles(state));
+        state::deployment_command(state, *tiles.first()?)
+    }
+
+    /// The first command of the next batch.
     pub fn command(&mut self, state: &State) -> Option<Command> {
+        let batch = self.batch(state)?;
+        batch.commands.into_iter().next()
+    }
+
+    /// The next commands: either the whole lookahead plan of an agent
+    /// or a batch of a single command.
+    pub fn batch(&mut self, state: &State) -> Option<CommandBatch> {
+        let single = |command: Command| CommandBatch::new(vec![command]);
         if state.battle_result().is_some() {
             info!("AI: The battle has ended, can't create new commands.");
             return None;
         }
+        if state::is_deploying(state) {
+            return self.deploy(state).map(single);
+        }
         let mut ids = state::players_agent_ids(state, self.id);
         state::sort_agent_ids_by_distance_to_enemies(state, &mut ids);
         for agent_id in ids {
-            if let Some(summon_command) = self.try_summon_imp(state, agent_id) {
-                return Some(summon_command);
+            // Cowards run away too, but still fight if they can't get away.
+            let is_routed = state::is_routed(state, agent_id);
+            let is_scared = personality(state, agent_id) == Some(Personality::Cowardly)
+                && is_badly_wounded(state, agent_id);
+            if is_routed || is_scared {
+                if let Some(flee_command) = self.try_to_flee(state, agent_id) {
+                    return Some(single(flee_command));
+                }
+                if is_routed {
+                    continue;
+                }
             }
-            if let Some(bloodlust_command) = self.try_bloodlust_imp(state, agent_id) {
-                return Some(bloodlust_command);
+            if let Some(command) = self.try_to_avoid_explosions(state, agent_id) {
+                return Some(single(command));
             }
-            if let Some(bomb_command) = self.try_throw_bomb(state, agent_id) {
-                return Some(bomb_command);
+            if let Some(ability_command) = self.try_abilities(state, agent_id) {
+                return Some(single(ability_command));
+            }
+            // Agents that keep their distance stick to the simple rules.
+            let keeps_distance = preferred_distance(personality(state, agent_id)).is_some();
+            if state.scenario().ai == Mode::Lookahead && !keeps_distance {
+                if let Some(batch) = self.try_lookahead(state, agent_id) {
+                    return Some(batch);
+                }
+                continue;
             }
             if let Some(attack_command) = self.try_to_attack(state, agent_id) {
-                return Some(attack_command);
+                return Some(single(attack_command));
             }
             if let Some(move_command) = self.try_to_move(state, agent_id) {
-                return Some(move_command);
+                return Some(single(move_command));
             }
         }
-        Some(command::EndTurn.into())
+        Some(single(command::EndTurn.into()))
     }
 }
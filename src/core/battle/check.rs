@@ -3,9 +3,11 @@ use log::trace;
 use crate::core::{
     battle::{
         self,
-        ability::{self, Ability},
-        command::{self, Command},
-        state, Attacks, Id, Jokers, Moves, PushStrength, State, Weight,
+        ability::{Ability, AbilityEffect, Definition, Delivery, Target},
+        command::{self, Command, CommandBatch},
+        execute,
+        scenario::Weather,
+        state, Attacks, Id, Jokers, MovePoints, Moves, PushStrength, State, Weight,
     },
     map::{self, Distance, PosHex},
 };
@@ -15,38 +17,146 @@ pub fn check(state: &State, command: &Command) -> Result<(), Error> {
     if state.battle_result().is_some() {
         return Err(Error::BattleEnded);
     }
+    if state::is_deploying(state) {
+        return check_deployment(state, command);
+    }
     match *command {
         Command::Create(ref command) => check_command_create(state, command),
         Command::MoveTo(ref command) => check_command_move_to(state, command),
         Command::Attack(ref command) => check_command_attack(state, command),
         Command::EndTurn(ref command) => check_command_end_turn(state, command),
         Command::UseAbility(ref command) => check_command_use_ability(state, command),
+        Command::ConvertJoker(ref command) => check_command_convert_joker(state, command),
+        Command::Mount(ref command) => check_command_mount(state, command),
+        Command::Dismount(ref command) => check_command_dismount(state, command),
+        Command::Retreat(ref command) => check_command_retreat(state, command),
+    }
+}
+
+/// Checks every command of the batch against a simulated copy of the state
+/// where the previous commands have already been executed.
+///
+/// The copy rolls its own dice, so the real reaction attacks may still
+/// cancel the rest of the batch, see `execute::execute_batch`.
+pub fn check_batch(state: &State, batch: &CommandBatch) -> Result<(), Error> {
+    trace!("check_batch: {:?}", batch);
+    if batch.actor_id().is_none() {
+        return Err(Error::BadActorId);
+    }
+    let mut simulated_state = state.fork_for_simulation();
+    for command in &batch.commands {
+        execute::execute(&mut simulated_state, command, &mut |_, _, _| {})?;
     }
+    Ok(())
 }
 
+/// The variants carry the numbers and the positions that have made the command invalid.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Error {
-    NotEnoughMovePoints,
+    NotEnoughMovePoints {
+        required: MovePoints,
+        available: MovePoints,
+    },
     NotEnoughStrength,
     BadActorId,
     BadTargetId,
     BadTargetType,
-    TileIsBlocked,
-    DistanceIsTooBig,
-    DistanceIsTooSmall,
+    TileIsBlocked {
+        pos: PosHex,
+    },
+    DistanceIsTooBig {
+        distance: Distance,
+        max: Distance,
+    },
+    DistanceIsTooSmall {
+        distance: Distance,
+        min: Distance,
+    },
     CanNotCommandEnemyAgents,
     NotEnoughMoves,
     NotEnoughAttacks,
+    NotEnoughJokers,
     AbilityIsNotReady,
+    NoChargesLeft,
+    NotEnoughStamina {
+        required: i32,
+        available: i32,
+    },
     NoSuchAbility,
     NoTarget,
-    BadPos,
+    BadPos {
+        pos: PosHex,
+    },
     BadActorType,
     BattleEnded,
+    SummonLimitReached,
+    NotAtHomeEdge,
+    DeploymentIsNotOver,
+    NotInDeploymentZone {
+        pos: PosHex,
+    },
+    Noncombatant,
+    DeepWater {
+        pos: PosHex,
+    },
+    MountIsTaken,
+    NoRider,
+    TargetIsHidden,
+}
+
+impl Error {
+    /// The short reason the command was rejected, in the words of the player.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Error::NotEnoughMovePoints { .. } => "not enough move points",
+            Error::NotEnoughStrength => "not enough strength",
+            Error::BadActorId => "no one to do that",
+            Error::BadTargetId | Error::BadTargetType => "can't target that",
+            Error::TileIsBlocked { .. } => "the tile is blocked",
+            Error::DistanceIsTooBig { .. } => "out of range",
+            Error::DistanceIsTooSmall { .. } => "too close",
+            Error::CanNotCommandEnemyAgents => "that's an enemy",
+            Error::NotEnoughMoves => "no moves left",
+            Error::NotEnoughAttacks => "no attacks left",
+            Error::NotEnoughJokers => "no jokers left",
+            Error::AbilityIsNotReady => "the ability isn't ready",
+            Error::NoChargesLeft => "no charges left",
+            Error::NotEnoughStamina { .. } => "not enough stamina",
+            Error::NoSuchAbility => "no such ability",
+            Error::NoTarget => "no target there",
+            Error::BadPos { .. } => "can't be done there",
+            Error::BadActorType => "can't do that",
+            Error::BattleEnded => "the battle is over",
+            Error::SummonLimitReached => "can't summon more",
+            Error::NotAtHomeEdge => "not everyone is at the home edge",
+            Error::DeploymentIsNotOver => "the deployment isn't over",
+            Error::NotInDeploymentZone { .. } => "outside the deployment zone",
+            Error::Noncombatant => "can't fight",
+            Error::DeepWater { .. } => "can't swim",
+            Error::MountIsTaken => "already has a rider",
+            Error::NoRider => "has no rider",
+            Error::TargetIsHidden => "can't see the target",
+        }
+    }
 }
 
 const BOMB_THROW_DISTANCE_MAX: Distance = Distance(3);
 
+fn check_command_retreat(state: &State, _: &command::Retreat) -> Result<(), Error> {
+    let player_id = state.player_id();
+    let ids = state::players_agent_ids(state, player_id);
+    if ids.is_empty() {
+        return Err(Error::BadActorId);
+    }
+    for id in ids {
+        let pos = state.parts().pos.get(id).0;
+        if !state::is_at_home_edge(state, player_id, pos) {
+            return Err(Error::NotAtHomeEdge);
+        }
+    }
+    Ok(())
+}
+
 fn check_command_move_to(state: &State, command: &command::MoveTo) -> Result<(), Error> {
     let agent = try_get_actor(state, command.id)?;
     let agent_player_id = state.parts().belongs_to.get(command.id).0;
@@ -55,17 +165,51 @@ fn check_command_move_to(state: &State, command: &command::MoveTo) -> Result<(),
     }
     check_agent_can_move(state, command.id)?;
     for step in command.path.steps() {
-        check_not_blocked_and_is_inboard(state, step.to)?;
+        // The flyers and the phasers pass some blockers, but still need a free tile to stop on.
+        if step.to != command.path.to() {
+            check_can_pass(state, command.id, step.to)?;
+        } else {
+            check_can_enter(state, command.id, step.to)?;
+        }
     }
     let cost = command.path.cost_for(state, command.id);
     if cost > agent.move_points {
-        return Err(Error::NotEnoughMovePoints);
+        return Err(Error::NotEnoughMovePoints {
+            required: cost,
+            available: agent.move_points,
+        });
+    }
+    Ok(())
+}
+
+/// Only the waiting agents can be placed before the first turn, everything else has to wait.
+fn check_deployment(state: &State, command: &Command) -> Result<(), Error> {
+    let command = match command {
+        Command::Create(command) => command,
+        _ => return Err(Error::DeploymentIsNotOver),
+    };
+    if state::deployment_index(state, command).is_none() {
+        return Err(Error::DeploymentIsNotOver);
+    }
+    if !state::deployment_zone(state).contains(&command.pos) {
+        return Err(Error::NotInDeploymentZone { pos: command.pos });
+    }
+    let footprint = state::prototype_footprint(state, &command.prototype);
+    for pos in state::footprint_tiles(command.pos, &footprint) {
+        check_is_inboard(state, pos)?;
+        if !state::is_tile_completely_free(state, pos) {
+            return Err(Error::TileIsBlocked { pos });
+        }
+        check_not_water(state, pos)?;
     }
     Ok(())
 }
 
 fn check_command_create(state: &State, command: &command::Create) -> Result<(), Error> {
-    check_not_blocked_and_is_inboard(state, command.pos)?;
+    let footprint = state::prototype_footprint(state, &command.prototype);
+    for pos in state::footprint_tiles(command.pos, &footprint) {
+        check_not_blocked_and_is_inboard(state, pos)?;
+    }
     Ok(())
 }
 
@@ -78,18 +222,28 @@ fn check_command_attack(state: &State, command: &command::Attack) -> Result<(),
         None => return Err(Error::BadTargetId),
     };
     let parts = state.parts();
-    let attacker_agent = try_get_actor(state, command.attacker_id)?;
-    let attacker_pos = parts.pos.get(command.attacker_id).0;
+    try_get_actor(state, command.attacker_id)?;
     let attacker_player_id = parts.belongs_to.get(command.attacker_id).0;
     if attacker_player_id != state.player_id() {
         return Err(Error::CanNotCommandEnemyAgents);
     }
-    if parts.agent.get_opt(command.target_id).is_none() {
+    let is_agent = parts.agent.get_opt(command.target_id).is_some();
+    if !is_agent && !state::is_destructible(state, command.target_id) {
         return Err(Error::BadTargetId);
     };
+    if state::is_hidden_from(state, command.target_id, attacker_player_id) {
+        return Err(Error::TargetIsHidden);
+    }
     check_is_inboard(state, target_pos)?;
+    if parts.agent.get(command.attacker_id).is_noncombatant {
+        return Err(Error::Noncombatant);
+    }
     check_agent_can_attack(state, command.attacker_id)?;
-    check_max_distance(attacker_pos, target_pos, attacker_agent.attack_distance)?;
+    let distance = state::distance_between(state, command.attacker_id, command.target_id);
+    let max = state::attack_distance(state, command.attacker_id);
+    if distance > max {
+        return Err(Error::DistanceIsTooBig { distance, max });
+    }
     Ok(())
 }
 
@@ -97,32 +251,159 @@ fn check_command_end_turn(_: &State, _: &command::EndTurn) -> Result<(), Error>
     Ok(())
 }
 
+fn check_command_convert_joker(
+    state: &State,
+    command: &command::ConvertJoker,
+) -> Result<(), Error> {
+    let agent = try_get_actor(state, command.id)?;
+    check_agent_belongs_to_correct_player(state, command.id)?;
+    if agent.jokers == Jokers(0) {
+        return Err(Error::NotEnoughJokers);
+    }
+    Ok(())
+}
+
+/// The mounts and the large agents can't ride, only a foot agent can.
+fn check_command_mount(state: &State, command: &command::Mount) -> Result<(), Error> {
+    try_get_actor(state, command.id)?;
+    check_agent_belongs_to_correct_player(state, command.id)?;
+    check_agent_can_move(state, command.id)?;
+    let parts = state.parts();
+    if parts.mount.get_opt(command.id).is_some() || !state::footprint(state, command.id).is_empty()
+    {
+        return Err(Error::BadActorType);
+    }
+    if command.id == command.mount_id || parts.agent.get_opt(command.mount_id).is_none() {
+        return Err(Error::BadTargetId);
+    }
+    let mount = match parts.mount.get_opt(command.mount_id) {
+        Some(mount) => mount,
+        None => return Err(Error::BadTargetType),
+    };
+    if parts.belongs_to.get(command.mount_id).0 != state.player_id() {
+        return Err(Error::BadTargetId);
+    }
+    if mount.rider.is_some() {
+        return Err(Error::MountIsTaken);
+    }
+    let distance = state::distance_between(state, command.id, command.mount_id);
+    let max = Distance(1);
+    if distance > max {
+        return Err(Error::DistanceIsTooBig { distance, max });
+    }
+    Ok(())
+}
+
+fn check_command_dismount(state: &State, command: &command::Dismount) -> Result<(), Error> {
+    try_get_actor(state, command.mount_id)?;
+    check_agent_belongs_to_correct_player(state, command.mount_id)?;
+    if state::rider_id(state, command.mount_id).is_none() {
+        return Err(Error::NoRider);
+    }
+    check_agent_can_move(state, command.mount_id)?;
+    let distance = state::distance_to(state, command.mount_id, command.pos);
+    let max = Distance(1);
+    if distance > max {
+        return Err(Error::DistanceIsTooBig { distance, max });
+    }
+    check_not_blocked_and_is_inboard(state, command.pos)?;
+    Ok(())
+}
+
 fn check_command_use_ability(state: &State, command: &command::UseAbility) -> Result<(), Error> {
     check_agent_belongs_to_correct_player(state, command.id)?;
     check_agent_can_attack(state, command.id)?;
     check_agent_ability_ready(state, command.id, &command.ability)?;
+    check_not_hidden_at(state, command.pos)?;
+    if let Some(definition) = state.ability_definitions().get(command.ability) {
+        if let Some(target) = definition.target {
+            return check_ability_definition(state, command, definition, target);
+        }
+    }
     match command.ability {
         Ability::Knockback => check_ability_knockback(state, command.id, command.pos),
-        Ability::Club => check_ability_club(state, command.id, command.pos),
+        Ability::Shove => check_ability_shove(state, command.id, command.pos),
         Ability::Jump => check_ability_jump(state, command.id, command.pos, Distance(2)),
         Ability::LongJump => check_ability_jump(state, command.id, command.pos, Distance(3)),
-        Ability::Poison => check_ability_poison(state, command.id, command.pos),
         Ability::Bomb
         | Ability::BombPush
         | Ability::BombFire
         | Ability::BombPoison
-        | Ability::BombDemonic => check_ability_bomb_throw(state, command.id, command.pos),
+        | Ability::BombDemonic => {
+            check_ability_bomb_throw(state, command.id, command.ability, command.pos)
+        }
         Ability::Summon => check_ability_summon(state, command.id, command.pos),
         Ability::Vanish => check_ability_vanish(state, command.id, command.pos),
         Ability::Dash => check_ability_dash(state, command.id, command.pos),
-        Ability::Rage => check_ability_rage(state, command.id, command.pos),
-        Ability::Heal | Ability::GreatHeal => check_ability_heal(state, command.id, command.pos),
-        Ability::Bloodlust => check_ability_bloodlust(state, command.id, command.pos),
+        Ability::Defuse => check_ability_defuse(state, command.id, command.pos),
+        Ability::Raise => check_ability_raise(state, command.id, command.pos),
         Ability::ExplodePush
         | Ability::ExplodeDamage
         | Ability::ExplodeFire
         | Ability::ExplodePoison => check_ability_explode(state, command.id, command.pos),
+        // Their rules are in the definitions, the validation of the assets
+        // makes sure that they have the targets.
+        Ability::Club
+        | Ability::Poison
+        | Ability::Rage
+        | Ability::Heal
+        | Ability::Bloodlust
+        | Ability::Totem
+        | Ability::Barricade
+        | Ability::Phase
+        | Ability::Hide => Err(Error::NoSuchAbility),
+    }
+}
+
+fn check_ability_definition(
+    state: &State,
+    command: &command::UseAbility,
+    definition: &Definition,
+    target: Target,
+) -> Result<(), Error> {
+    let parts = state.parts();
+    if target == Target::Itself {
+        return check_object_pos(state, command.id, command.pos);
     }
+    let agent_pos = parts.pos.get(command.id).0;
+    check_min_distance(agent_pos, command.pos, definition.min_distance)?;
+    let params = state::ability_params(state, command.id, command.ability);
+    if let Some(distance) = params.distance {
+        check_max_distance(agent_pos, command.pos, distance)?;
+    }
+    if target == Target::Tile {
+        return check_not_blocked_and_is_inboard(state, command.pos);
+    }
+    // Same as the old hard-coded `Club`: boulders and other
+    // non-agent blockers can't be targeted.
+    let target_id = match state::agent_id_at_opt(state, command.pos) {
+        Some(id) => id,
+        None => return Err(Error::NoTarget),
+    };
+    if target == Target::Ally && parts.belongs_to.get(target_id) != parts.belongs_to.get(command.id)
+    {
+        return Err(Error::BadTargetType);
+    }
+    for effect in &definition.effects {
+        match effect {
+            AbilityEffect::Heal => match parts.strength.get_opt(target_id) {
+                Some(strength) if strength.strength == strength.base_strength => {
+                    return Err(Error::BadTargetType);
+                }
+                Some(_) => {}
+                None => return Err(Error::BadActorId),
+            },
+            AbilityEffect::Wound => {
+                if parts.strength.get_opt(target_id).is_none() {
+                    return Err(Error::BadTargetType);
+                }
+            }
+            AbilityEffect::Lasting { .. }
+            | AbilityEffect::Attacks(_)
+            | AbilityEffect::Create(_) => {}
+        }
+    }
+    Ok(())
 }
 
 fn check_ability_knockback(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
@@ -130,7 +411,7 @@ fn check_ability_knockback(state: &State, id: Id, pos: PosHex) -> Result<(), Err
     let selected_pos = state.parts().pos.get(id).0;
     check_min_distance(selected_pos, pos, Distance(1))?;
     check_max_distance(selected_pos, pos, Distance(1))?;
-    let target_id = match state::agent_id_at_opt(state, pos) {
+    let target_id = match state::blocker_id_at_opt(state, pos) {
         Some(id) => id,
         None => return Err(Error::NoTarget),
     };
@@ -141,6 +422,25 @@ fn check_ability_knockback(state: &State, id: Id, pos: PosHex) -> Result<(), Err
     Ok(())
 }
 
+fn check_ability_shove(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
+    let strength = PushStrength(Weight::Heavy);
+    let selected_pos = state.parts().pos.get(id).0;
+    check_min_distance(selected_pos, pos, Distance(1))?;
+    check_max_distance(selected_pos, pos, Distance(1))?;
+    let target_id = match state::blocker_id_at_opt(state, pos) {
+        Some(id) => id,
+        None => return Err(Error::NoTarget),
+    };
+    if state.parts().agent.get_opt(target_id).is_some() {
+        return Err(Error::BadTargetType);
+    }
+    let target_weight = state.parts().blocker.get(target_id).weight;
+    if !strength.can_push(target_weight) {
+        return Err(Error::NotEnoughStrength);
+    }
+    Ok(())
+}
+
 fn check_ability_jump(
     state: &State,
     id: Id,
@@ -151,27 +451,7 @@ fn check_ability_jump(
     let agent_pos = parts.pos.get(id).0;
     check_min_distance(agent_pos, pos, Distance(2))?;
     check_max_distance(agent_pos, pos, max_distance)?;
-    check_not_blocked_and_is_inboard(state, pos)?;
-    Ok(())
-}
-
-fn check_ability_club(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
-    let selected_pos = state.parts().pos.get(id).0;
-    check_min_distance(selected_pos, pos, Distance(1))?;
-    check_max_distance(selected_pos, pos, Distance(1))?;
-    if state::agent_id_at_opt(state, pos).is_none() {
-        return Err(Error::NoTarget);
-    }
-    Ok(())
-}
-
-fn check_ability_poison(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
-    let selected_pos = state.parts().pos.get(id).0;
-    check_min_distance(selected_pos, pos, Distance(1))?;
-    check_max_distance(selected_pos, pos, Distance(3))?;
-    if state::blocker_id_at_opt(state, pos).is_none() {
-        return Err(Error::NoTarget);
-    }
+    check_can_enter(state, id, pos)?;
     Ok(())
 }
 
@@ -179,15 +459,46 @@ fn check_ability_explode(state: &State, id: Id, pos: PosHex) -> Result<(), Error
     check_object_pos(state, id, pos)
 }
 
-fn check_ability_bomb_throw(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
+fn check_ability_bomb_throw(
+    state: &State,
+    id: Id,
+    ability: Ability,
+    pos: PosHex,
+) -> Result<(), Error> {
     let agent_pos = state.parts().pos.get(id).0;
-    check_max_distance(agent_pos, pos, BOMB_THROW_DISTANCE_MAX)?;
-    check_not_blocked_and_is_inboard(state, pos)?;
+    match state::bomb_delivery(state, ability) {
+        Delivery::Thrown => {
+            let mut penalty = 0;
+            if state.scenario().weather == Weather::Fog {
+                penalty += 1;
+            }
+            if !state::is_lit(state, agent_pos) {
+                penalty += 1;
+            }
+            let max = Distance((BOMB_THROW_DISTANCE_MAX.0 - penalty).max(1));
+            check_max_distance(agent_pos, pos, max)?;
+            check_not_blocked_and_is_inboard(state, pos)?;
+        }
+        Delivery::Rolled => {
+            check_max_distance(agent_pos, pos, BOMB_THROW_DISTANCE_MAX)?;
+            check_is_inboard(state, pos)?;
+            if state::rolled_bomb_pos(state, agent_pos, pos).is_none() {
+                return Err(Error::BadPos { pos });
+            }
+        }
+    }
     Ok(())
 }
 
 fn check_ability_summon(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
-    check_object_pos(state, id, pos)
+    check_object_pos(state, id, pos)?;
+    if state.parts().summoner.get_opt(id).is_none() {
+        return Err(Error::BadActorType);
+    }
+    if state::summon_count(state, id) == 0 {
+        return Err(Error::SummonLimitReached);
+    }
+    Ok(())
 }
 
 fn check_ability_vanish(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
@@ -199,7 +510,7 @@ fn check_ability_vanish(state: &State, id: Id, pos: PosHex) -> Result<(), Error>
         None => return Err(Error::BadActorType),
     };
     if pos != actor_pos {
-        return Err(Error::BadPos);
+        return Err(Error::BadPos { pos });
     }
     Ok(())
 }
@@ -207,37 +518,30 @@ fn check_ability_vanish(state: &State, id: Id, pos: PosHex) -> Result<(), Error>
 fn check_ability_dash(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
     let agent_pos = state.parts().pos.get(id).0;
     check_max_distance(agent_pos, pos, Distance(1))?;
-    check_not_blocked_and_is_inboard(state, pos)?;
+    check_can_enter(state, id, pos)?;
     Ok(())
 }
 
-fn check_ability_rage(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
-    check_object_pos(state, id, pos)
-}
-
-fn check_ability_heal(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
+fn check_ability_defuse(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
     let agent_pos = state.parts().pos.get(id).0;
+    check_min_distance(agent_pos, pos, Distance(1))?;
     check_max_distance(agent_pos, pos, Distance(1))?;
-    let target_id = match state::agent_id_at_opt(state, pos) {
-        Some(id) => id,
-        None => return Err(Error::NoTarget),
-    };
-    match state.parts().strength.get_opt(target_id) {
-        Some(strength) => {
-            if strength.strength == strength.base_strength {
-                return Err(Error::BadTargetType);
-            }
-        }
-        None => return Err(Error::BadActorId),
+    let is_bomb = state::blocker_id_at_opt(state, pos)
+        .is_some_and(|target_id| state::bomb_fuse(state, target_id).is_some());
+    if !is_bomb {
+        return Err(Error::NoTarget);
     }
     Ok(())
 }
 
-fn check_ability_bloodlust(state: &State, _id: Id, pos: PosHex) -> Result<(), Error> {
-    // TODO: check that the target belongs to the same player
-    if state::agent_id_at_opt(state, pos).is_none() {
+fn check_ability_raise(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
+    let agent_pos = state.parts().pos.get(id).0;
+    check_min_distance(agent_pos, pos, Distance(1))?;
+    check_max_distance(agent_pos, pos, Distance(1))?;
+    if state::corpse_id_at_opt(state, pos).is_none() {
         return Err(Error::NoTarget);
     }
+    check_not_blocked_and_is_inboard(state, pos)?;
     Ok(())
 }
 
@@ -261,7 +565,7 @@ fn check_agent_ability_ready(
     for ability in abilities {
         if ability.ability == *expected_ability {
             found = true;
-            if ability.status != ability::Status::Ready {
+            if !ability.status.is_ready() {
                 return Err(Error::AbilityIsNotReady);
             }
         }
@@ -269,6 +573,21 @@ fn check_agent_ability_ready(
     if !found {
         return Err(Error::NoSuchAbility);
     }
+    if let Some(charges) = state.parts().charges.get_opt(id) {
+        if charges.is_exhausted(*expected_ability) {
+            return Err(Error::NoChargesLeft);
+        }
+    }
+    if let Some(stamina) = state.parts().stamina.get_opt(id) {
+        if let Some(cost) = stamina.cost(*expected_ability) {
+            if stamina.stamina < cost {
+                return Err(Error::NotEnoughStamina {
+                    required: cost,
+                    available: stamina.stamina,
+                });
+            }
+        }
+    }
     Ok(())
 }
 
@@ -297,32 +616,81 @@ fn check_agent_can_move(state: &State, id: Id) -> Result<(), Error> {
 }
 
 fn check_min_distance(from: PosHex, to: PosHex, min: Distance) -> Result<(), Error> {
-    let dist = map::distance_hex(from, to);
-    if dist < min {
-        return Err(Error::DistanceIsTooSmall);
+    let distance = map::distance_hex(from, to);
+    if distance < min {
+        return Err(Error::DistanceIsTooSmall { distance, min });
     }
     Ok(())
 }
 
 fn check_max_distance(from: PosHex, to: PosHex, max: Distance) -> Result<(), Error> {
-    let dist = map::distance_hex(from, to);
-    if dist > max {
-        return Err(Error::DistanceIsTooBig);
+    let distance = map::distance_hex(from, to);
+    if distance > max {
+        return Err(Error::DistanceIsTooBig { distance, max });
     }
     Ok(())
 }
 
+/// Nothing can be placed into the water, it would sink.
 fn check_not_blocked_and_is_inboard(state: &State, pos: PosHex) -> Result<(), Error> {
     check_is_inboard(state, pos)?;
     if state::is_tile_blocked(state, pos) {
-        return Err(Error::TileIsBlocked);
+        return Err(Error::TileIsBlocked { pos });
+    }
+    check_not_water(state, pos)?;
+    Ok(())
+}
+
+/// The flyers can walk over the water, unlike everybody else.
+/// The large agents need all the tiles of their footprint.
+fn check_can_enter(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
+    for tile in state::footprint_at(state, id, pos) {
+        check_is_inboard(state, tile)?;
+        if state::is_tile_blocked_for(state, id, tile) {
+            return Err(Error::TileIsBlocked { pos: tile });
+        }
+    }
+    if state::is_drowning_at(state, id, pos) {
+        return Err(Error::DeepWater { pos });
+    }
+    Ok(())
+}
+
+fn check_not_hidden_at(state: &State, pos: PosHex) -> Result<(), Error> {
+    let is_hidden = state::agent_id_at_opt(state, pos)
+        .is_some_and(|id| state::is_hidden_from(state, id, state.player_id()));
+    if is_hidden {
+        return Err(Error::TargetIsHidden);
+    }
+    Ok(())
+}
+
+fn check_can_pass(state: &State, id: Id, pos: PosHex) -> Result<(), Error> {
+    let movement_type = state::movement_type(state, id);
+    for tile in state::footprint_at(state, id, pos) {
+        check_is_inboard(state, tile)?;
+        for blocker_id in state::blocker_ids_at(state, tile) {
+            if blocker_id != id && !state::can_pass_over(state, id, movement_type, blocker_id) {
+                return Err(Error::TileIsBlocked { pos: tile });
+            }
+        }
+    }
+    if state::is_drowning_at(state, id, pos) {
+        return Err(Error::DeepWater { pos });
+    }
+    Ok(())
+}
+
+fn check_not_water(state: &State, pos: PosHex) -> Result<(), Error> {
+    if state::is_water(state, pos) {
+        return Err(Error::DeepWater { pos });
     }
     Ok(())
 }
 
 fn check_is_inboard(state: &State, pos: PosHex) -> Result<(), Error> {
     if !state.map().is_inboard(pos) {
-        return Err(Error::BadPos);
+        return Err(Error::BadPos { pos });
     }
     Ok(())
 }
@@ -330,7 +698,7 @@ fn check_is_inboard(state: &State, pos: PosHex) -> Result<(), Error> {
 fn check_object_pos(state: &State, id: Id, expected_pos: PosHex) -> Result<(), Error> {
     let real_pos = state.parts().pos.get(id).0;
     if real_pos != expected_pos {
-        return Err(Error::BadPos);
+        return Err(Error::BadPos { pos: expected_pos });
     }
     Ok(())
 }
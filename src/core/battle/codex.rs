@@ -0,0 +1,187 @@
+//! The encyclopedia's facts about the object types.
+//!
+//! Everything here is derived from the prototypes,
+//! so it can't disagree with the assets or with the rules.
+
+use crate::core::battle::{
+    ability::{Ability, PassiveAbility},
+    component::{self, Component, DamageType, ObjType, Prototypes},
+    dice,
+    execute::AttackTerms,
+    Dodge, Strength,
+};
+
+/// How many counters of a type the codex lists.
+const COUNTERS_COUNT: usize = 3;
+
+fn components<'a>(prototypes: &'a Prototypes, typename: &ObjType) -> &'a [Component] {
+    &prototypes.0[typename]
+}
+
+fn agent(components: &[Component]) -> Option<&component::Agent> {
+    components.iter().find_map(|c| match c {
+        Component::Agent(agent) => Some(agent),
+        _ => None,
+    })
+}
+
+fn armor(components: &[Component]) -> Strength {
+    components
+        .iter()
+        .find_map(|c| match c {
+            Component::Armor(armor) => Some(armor.armor),
+            _ => None,
+        })
+        .unwrap_or(Strength(0))
+}
+
+fn resistance(components: &[Component], damage_type: DamageType) -> Strength {
+    components
+        .iter()
+        .find_map(|c| match c {
+            Component::Resistances(resistances) => Some(resistances.resistance(damage_type)),
+            _ => None,
+        })
+        .unwrap_or(Strength(0))
+}
+
+fn abilities(components: &[Component]) -> Vec<Ability> {
+    components
+        .iter()
+        .find_map(|c| match c {
+            Component::Abilities(abilities) => {
+                Some(abilities.0.iter().map(|r| r.ability).collect())
+            }
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+fn passive_abilities(components: &[Component]) -> Vec<PassiveAbility> {
+    components
+        .iter()
+        .find_map(|c| match c {
+            Component::PassiveAbilities(abilities) => Some(abilities.0.clone()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// All the types that can act, sorted by name.
+pub fn agent_types(prototypes: &Prototypes) -> Vec<ObjType> {
+    let mut types: Vec<ObjType> = prototypes
+        .0
+        .iter()
+        .filter(|(_, components)| agent(components).is_some())
+        .map(|(typename, _)| typename.clone())
+        .collect();
+    types.sort_by(|a, b| a.0.cmp(&b.0));
+    types
+}
+
+/// Every active ability of any type, in the order of the sorted types.
+pub fn all_abilities(prototypes: &Prototypes) -> Vec<Ability> {
+    let mut all = Vec::new();
+    for typename in agent_types(prototypes) {
+        for ability in abilities(components(prototypes, &typename)) {
+            if !all.contains(&ability) {
+                all.push(ability);
+            }
+        }
+    }
+    all
+}
+
+/// Every passive ability of any object type, the terrain objects included.
+pub fn all_passive_abilities(prototypes: &Prototypes) -> Vec<PassiveAbility> {
+    let mut types: Vec<&ObjType> = prototypes.0.keys().collect();
+    types.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut all = Vec::new();
+    for typename in types {
+        for ability in passive_abilities(components(prototypes, typename)) {
+            if !all.contains(&ability) {
+                all.push(ability);
+            }
+        }
+    }
+    all
+}
+
+/// The types that have this ability, sorted by name.
+pub fn users_of_ability(prototypes: &Prototypes, ability: Ability) -> Vec<ObjType> {
+    agent_types(prototypes)
+        .into_iter()
+        .filter(|typename| abilities(components(prototypes, typename)).contains(&ability))
+        .collect()
+}
+
+/// The object types that have this passive ability, sorted by name.
+pub fn owners_of_passive_ability(prototypes: &Prototypes, ability: PassiveAbility) -> Vec<ObjType> {
+    let mut types: Vec<ObjType> = prototypes
+        .0
+        .iter()
+        .filter(|(_, components)| passive_abilities(components).contains(&ability))
+        .map(|(typename, _)| typename.clone())
+        .collect();
+    types.sort_by(|a, b| a.0.cmp(&b.0));
+    types
+}
+
+/// The terms of a frontal attack of an unhurt `attacker` on a fresh `target`,
+/// `None` if the attacker can't attack.
+pub fn attack_terms(
+    prototypes: &Prototypes,
+    attacker: &ObjType,
+    target: &ObjType,
+) -> Option<AttackTerms> {
+    let attacker = agent(components(prototypes, attacker))?;
+    let target = components(prototypes, target);
+    let target_armor = armor(target);
+    let target_dodge = agent(target).map_or(Dodge(0), |agent| agent.dodge);
+    Some(AttackTerms {
+        base_damage: attacker.attack_strength,
+        backstab_bonus: Strength(0),
+        aura_bonus: Strength(0),
+        accuracy: attacker.attack_accuracy,
+        target_dodge,
+        wounds_penalty: 0,
+        injury_penalty: 0,
+        target_armor,
+        target_resistance: resistance(target, attacker.weapon_type.damage_type()),
+        armor_break: Strength(attacker.attack_break.0.min(target_armor.0)),
+    })
+}
+
+/// The average strength that one such attack takes, see `attack_terms`.
+pub fn expected_damage(prototypes: &Prototypes, attacker: &ObjType, target: &ObjType) -> f32 {
+    let terms = match attack_terms(prototypes, attacker, target) {
+        Some(terms) => terms,
+        None => return 0.0,
+    };
+    let total: i32 = (0..dice::ROLL_SIDES)
+        .filter_map(|roll| terms.roll_damage(roll))
+        .map(|damage| damage.0)
+        .sum();
+    total as f32 / dice::ROLL_SIDES as f32
+}
+
+/// The other agent types that hurt the type the most with a single attack,
+/// the best first. The ones that can't hurt it at all aren't counters.
+pub fn counters(prototypes: &Prototypes, typename: &ObjType) -> Vec<ObjType> {
+    let mut counters: Vec<(ObjType, f32)> = agent_types(prototypes)
+        .into_iter()
+        .filter(|other| other != typename)
+        .map(|other| {
+            let damage = expected_damage(prototypes, &other, typename);
+            (other, damage)
+        })
+        .filter(|&(_, damage)| damage > 0.0)
+        .collect();
+    // The sort is stable, so the equal ones stay sorted by name.
+    counters.sort_by(|a, b| b.1.partial_cmp(&a.1).expect("Damage is never NaN"));
+    counters
+        .into_iter()
+        .take(COUNTERS_COUNT)
+        .map(|(typename, _)| typename)
+        .collect()
+}
@@ -1,5 +1,10 @@
 use crate::core::{
-    battle::{ability::Ability, component::ObjType, movement::Path, Id, PlayerId},
+    battle::{
+        ability::Ability,
+        component::{Component, ObjType},
+        movement::Path,
+        Id, PlayerId,
+    },
     map::PosHex,
 };
 
@@ -10,6 +15,10 @@ pub enum Command {
     MoveTo(MoveTo),
     EndTurn(EndTurn),
     UseAbility(UseAbility),
+    ConvertJoker(ConvertJoker),
+    Mount(Mount),
+    Dismount(Dismount),
+    Retreat(Retreat),
 }
 
 #[derive(Debug, Clone)]
@@ -17,6 +26,9 @@ pub struct Create {
     pub owner: Option<PlayerId>,
     pub pos: PosHex,
     pub prototype: ObjType,
+
+    /// Replace the prototype's components of the same type.
+    pub extra_components: Vec<Component>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,3 +52,72 @@ pub struct UseAbility {
     pub pos: PosHex,
     pub ability: Ability,
 }
+
+/// What a joker is explicitly spent on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JokerUse {
+    Move,
+    Attack,
+}
+
+/// Turns one of the agent's jokers into an extra move or attack for this turn.
+#[derive(Debug, Clone)]
+pub struct ConvertJoker {
+    pub id: Id,
+    pub into: JokerUse,
+}
+
+/// The agent climbs onto an adjacent allied mount, they act as one agent till it dismounts.
+#[derive(Debug, Clone)]
+pub struct Mount {
+    pub id: Id,
+    pub mount_id: Id,
+}
+
+/// The mount's rider gets off to an adjacent tile.
+#[derive(Debug, Clone)]
+pub struct Dismount {
+    pub mount_id: Id,
+    pub pos: PosHex,
+}
+
+/// Ends the battle when all the player's agents are at their home edge of the map.
+#[derive(Debug, Clone)]
+pub struct Retreat;
+
+/// A short sequence of orders given to one agent at once, like a move and an attack.
+///
+/// Every command is checked against the state that the previous ones leave,
+/// so the whole batch is either valid or rejected before anything happens.
+#[derive(Debug, Clone)]
+pub struct CommandBatch {
+    pub commands: Vec<Command>,
+}
+
+impl CommandBatch {
+    pub fn new(commands: Vec<Command>) -> Self {
+        Self { commands }
+    }
+
+    /// The agent that executes the commands, `None` if they aren't all of one agent.
+    pub fn actor_id(&self) -> Option<Id> {
+        let actor_id = self.commands.first().and_then(command_actor_id)?;
+        let is_same_actor = self
+            .commands
+            .iter()
+            .all(|c| command_actor_id(c) == Some(actor_id));
+        is_same_actor.then_some(actor_id)
+    }
+}
+
+fn command_actor_id(command: &Command) -> Option<Id> {
+    match *command {
+        Command::MoveTo(ref command) => Some(command.id),
+        Command::Attack(ref command) => Some(command.attacker_id),
+        Command::UseAbility(ref command) => Some(command.id),
+        Command::ConvertJoker(ref command) => Some(command.id),
+        Command::Mount(ref command) => Some(command.id),
+        Command::Dismount(ref command) => Some(command.mount_id),
+        Command::Create(_) | Command::EndTurn(_) | Command::Retreat(_) => None,
+    }
+}
@@ -1,11 +1,13 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use zcomponents::zcomponents_storage;
 
 use crate::core::{
     battle::{
         self,
-        ability::{Ability, PassiveAbility, RechargeableAbility},
-        effect::Timed,
+        ability::{Ability, PassiveAbility, RechargeableAbility, Tier},
+        effect::{self, Timed},
         Attacks, Id, Jokers, MovePoints, Moves, Phase, PlayerId, Rounds,
     },
     map,
@@ -14,6 +16,10 @@ use crate::core::{
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Pos(pub map::PosHex);
 
+/// Where the agent looks. Attacks from behind deal more damage.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Facing(pub map::Dir);
+
 /// Blocks the whole tile. Two blocker objects can't coexist in one tile.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Blocker {
@@ -21,6 +27,25 @@ pub struct Blocker {
     pub weight: battle::Weight,
 }
 
+/// The extra tiles that a large object takes, as directions from its position.
+/// The footprint doesn't turn, the sprite's offset has to center it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Footprint(pub Vec<map::Dir>);
+
+/// Can carry an allied agent, the rider's move points are added to the mount's ones.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Mount {
+    #[serde(default)]
+    pub rider: Option<Rider>,
+}
+
+/// A mounted agent is taken off the map, its components wait here till it dismounts.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Rider {
+    pub id: Id,
+    pub components: Vec<Component>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Strength {
     #[serde(default)]
@@ -29,6 +54,39 @@ pub struct Strength {
     pub strength: battle::Strength,
 }
 
+/// Drops when allies die nearby. Agents with no morale left are routed.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Morale {
+    #[serde(default)]
+    pub base_morale: i32,
+
+    pub morale: i32,
+}
+
+/// A pool that some abilities cost, partly restored at the beginning of every own turn.
+///
+/// The abilities with a cost aren't put on cooldown, they're limited by the pool instead.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Stamina {
+    #[serde(default)]
+    pub base_stamina: i32,
+
+    pub stamina: i32,
+
+    /// Restored every turn, up to the base value.
+    #[serde(default)]
+    pub regeneration: i32,
+
+    #[serde(default)]
+    pub costs: HashMap<Ability, i32>,
+}
+
+impl Stamina {
+    pub fn cost(&self, ability: Ability) -> Option<i32> {
+        self.costs.get(&ability).copied()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Armor {
     pub armor: battle::Strength,
@@ -60,6 +118,62 @@ pub enum WeaponType {
     Claw,
 }
 
+impl WeaponType {
+    pub fn damage_type(self) -> DamageType {
+        match self {
+            WeaponType::Slash | WeaponType::Claw => DamageType::Slash,
+            WeaponType::Smash => DamageType::Smash,
+            WeaponType::Pierce => DamageType::Pierce,
+        }
+    }
+}
+
+/// What kind of harm is done, some objects resist some kinds of it.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Eq, Hash)]
+pub enum DamageType {
+    Slash,
+    Smash,
+    Pierce,
+
+    /// Burning tiles.
+    Fire,
+
+    /// The abilities that wound directly.
+    Magic,
+}
+
+impl DamageType {
+    pub const ALL: [DamageType; 5] = [
+        DamageType::Slash,
+        DamageType::Smash,
+        DamageType::Pierce,
+        DamageType::Fire,
+        DamageType::Magic,
+    ];
+
+    pub fn title(self) -> String {
+        match self {
+            DamageType::Slash => "Slash".into(),
+            DamageType::Smash => "Smash".into(),
+            DamageType::Pierce => "Pierce".into(),
+            DamageType::Fire => "Fire".into(),
+            DamageType::Magic => "Magic".into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Eq, Default)]
+pub enum MovementType {
+    #[default]
+    Walk,
+
+    /// Passes over the blockers, the rough terrain and the water, but lands on a free tile.
+    Fly,
+
+    /// Passes through the enemies, but not through the allies and the other objects.
+    Phase,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Agent {
     // dynamic
@@ -79,6 +193,17 @@ pub struct Agent {
     #[serde(default)]
     pub dodge: battle::Dodge,
 
+    /// Escorted civilians and the like, they can't attack, not even with jokers.
+    #[serde(default)]
+    pub is_noncombatant: bool,
+
+    /// Archers, bombers and the like, they don't strike back at melee attackers.
+    #[serde(default)]
+    pub cannot_counter: bool,
+
+    #[serde(default)]
+    pub movement_type: MovementType,
+
     pub move_points: MovePoints,
     pub reactive_attacks: Attacks,
 
@@ -98,9 +223,129 @@ pub struct Abilities(pub Vec<RechargeableAbility>);
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct PassiveAbilities(pub Vec<PassiveAbility>);
 
+/// Abilities that have been upgraded, the missing ones are at their first tier.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct AbilityTiers(pub HashMap<Ability, Tier>);
+
+/// Like an armor that only absorbs the damage of its types, but never breaks.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct Resistances(pub HashMap<DamageType, battle::Strength>);
+
+impl Resistances {
+    pub fn resistance(&self, damage_type: DamageType) -> battle::Strength {
+        self.0
+            .get(&damage_type)
+            .copied()
+            .unwrap_or(battle::Strength(0))
+    }
+}
+
+impl AbilityTiers {
+    pub fn tier(&self, ability: Ability) -> Tier {
+        self.0.get(&ability).copied().unwrap_or_default()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Effects(pub Vec<Timed>);
 
+/// When a reaction goes off.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Hook {
+    /// The object is wounded by an attack.
+    Hit,
+
+    Death,
+
+    /// The turn of the object's owner begins.
+    TurnStart,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ReactionTarget {
+    Itself,
+
+    /// The one whose attack has triggered a `Hit` reaction.
+    Attacker,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ReactionEffect {
+    /// Fires on all the tiles in the radius around the object.
+    Fire {
+        radius: map::Distance,
+    },
+
+    PoisonCloud {
+        radius: map::Distance,
+    },
+
+    Lasting {
+        target: ReactionTarget,
+        effect: effect::Lasting,
+        rounds: Rounds,
+    },
+
+    Wound {
+        target: ReactionTarget,
+        damage: battle::Strength,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Reaction {
+    pub hook: Hook,
+    pub effect: ReactionEffect,
+}
+
+/// What the object does when something happens to it,
+/// executed right after the event that has triggered it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Reactions(pub Vec<Reaction>);
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AuraTargets {
+    /// The agents of the same side, the aura's source excluded.
+    Allies,
+
+    Enemies,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum AuraEffect {
+    /// Added to the attack strength while the agent stays in the radius.
+    AttackStrength(i32),
+
+    /// Cast when the turn of the agent in the radius begins.
+    Lasting {
+        effect: effect::Lasting,
+        rounds: Rounds,
+    },
+
+    /// Restores the strength points of the wounded agents when their turn begins.
+    Heal(i32),
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Aura {
+    pub radius: map::Distance,
+    pub targets: AuraTargets,
+    pub effect: AuraEffect,
+}
+
+/// Effects on the agents around the object, they follow it as it moves.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Auras(pub Vec<Aura>);
+
+impl Reactions {
+    pub fn on(&self, hook: Hook) -> impl Iterator<Item = &ReactionEffect> {
+        self.0
+            .iter()
+            .filter(move |reaction| reaction.hook == hook)
+            .map(|reaction| &reaction.effect)
+    }
+}
+
 // TODO: Move to `ability` mod?
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct PlannedAbility {
@@ -117,6 +362,101 @@ pub struct Schedule {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Summoner {
     pub count: u32,
+
+    /// Max number of this summoner's creatures that can be alive at the same time.
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+/// Links a battle agent to a fighter from the campaign's roster.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Veteran {
+    /// An index of the fighter in the campaign's roster.
+    pub index: usize,
+
+    /// Kills made during the current battle.
+    #[serde(default)]
+    pub kills: i32,
+}
+
+/// A personal name and look that the player gave to a campaign fighter.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Persona {
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// An index of the portrait variant.
+    #[serde(default)]
+    pub portrait: usize,
+}
+
+/// Links a summoned creature to its summoner.
+/// Limited uses of some abilities that aren't restored between battles.
+///
+/// Abilities without an entry are only limited by their cooldowns.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct Charges(pub HashMap<Ability, i32>);
+
+impl Charges {
+    pub fn is_exhausted(&self, ability: Ability) -> bool {
+        self.0.get(&ability).is_some_and(|&charges| charges <= 0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SummonedBy(pub Id);
+
+/// What's left of a dead agent, a necromancer can raise it.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Corpse;
+
+/// A stage of a boss fight, the bonuses are added to the boss' current stats.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BossPhase {
+    /// The phase begins as soon as the boss' strength drops to this value.
+    pub strength: battle::Strength,
+
+    #[serde(default)]
+    pub abilities: Vec<Ability>,
+
+    #[serde(default)]
+    pub passive_abilities: Vec<PassiveAbility>,
+
+    #[serde(default)]
+    pub bonus_attacks: Attacks,
+
+    #[serde(default)]
+    pub bonus_moves: Moves,
+
+    #[serde(default)]
+    pub bonus_attack_strength: battle::Strength,
+}
+
+/// A boss changes its behavior when it gets weaker.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Boss {
+    /// Sorted by strength, from the strongest to the weakest.
+    pub phases: Vec<BossPhase>,
+
+    /// How many phases have already begun.
+    #[serde(default)]
+    pub phases_passed: usize,
+}
+
+/// A hint for the AI about how the agent should fight.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Personality {
+    /// Goes after the weakest enemies and doesn't care much about getting hurt.
+    Aggressive,
+
+    /// Keeps its distance and runs away when badly wounded.
+    Cowardly,
+
+    /// Stays put till some enemy comes close to it or its allies.
+    Defender,
+
+    /// Keeps a throwing distance.
+    Bomber,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, derive_more::From)]
@@ -124,36 +464,70 @@ pub enum Component {
     Pos(Pos),
     Strength(Strength),
     Armor(Armor),
+    Resistances(Resistances),
     Meta(Meta),
     BelongsTo(BelongsTo),
     Agent(Agent),
     Blocker(Blocker),
+    Footprint(Footprint),
+    Mount(Mount),
     Abilities(Abilities),
     PassiveAbilities(PassiveAbilities),
+    AbilityTiers(AbilityTiers),
     Effects(Effects),
+    Reactions(Reactions),
+    Auras(Auras),
     Schedule(Schedule),
     Summoner(Summoner),
+    SummonedBy(SummonedBy),
+    Corpse(Corpse),
+    Morale(Morale),
+    Stamina(Stamina),
+    Veteran(Veteran),
+    Persona(Persona),
+    Charges(Charges),
+    Boss(Boss),
+    Facing(Facing),
+    Personality(Personality),
 }
 
 zcomponents_storage!(Parts<Id>: {
     strength: Strength,
     armor: Armor,
+    resistances: Resistances,
     pos: Pos,
+    facing: Facing,
     meta: Meta,
     belongs_to: BelongsTo,
     agent: Agent,
     blocker: Blocker,
+    footprint: Footprint,
+    mount: Mount,
     abilities: Abilities,
     passive_abilities: PassiveAbilities,
+    ability_tiers: AbilityTiers,
     effects: Effects,
+    reactions: Reactions,
+    auras: Auras,
     schedule: Schedule,
     summoner: Summoner,
+    summoned_by: SummonedBy,
+    corpse: Corpse,
+    morale: Morale,
+    stamina: Stamina,
+    veteran: Veteran,
+    persona: Persona,
+    charges: Charges,
+    boss: Boss,
+    personality: Personality,
 });
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Prototypes(pub HashMap<ObjType, Vec<Component>>);
 
-fn init_component(component: &mut Component) {
+/// Sets the base values (e.g. `base_strength`) from the current ones,
+/// the data files only have the latter.
+pub fn init_component(component: &mut Component) {
     match component {
         Component::Agent(agent) => {
             agent.base_moves = agent.moves;
@@ -163,6 +537,12 @@ fn init_component(component: &mut Component) {
         Component::Strength(strength) => {
             strength.base_strength = strength.strength;
         }
+        Component::Morale(morale) => {
+            morale.base_morale = morale.morale;
+        }
+        Component::Stamina(stamina) => {
+            stamina.base_stamina = stamina.stamina;
+        }
         _ => {}
     }
 }
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::map::PosHex;
+
+/// A kind of a mark that some effect leaves on the battlefield.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    Blood,
+    Scorch,
+    Crater,
+}
+
+/// A purely cosmetic, persistent mark on a tile.
+///
+/// Decals don't affect the game rules in any way,
+/// but they're a part of the battle state, so replaying the same events
+/// always leaves the same wear on the battlefield.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decal {
+    pub pos: PosHex,
+    pub kind: Kind,
+}
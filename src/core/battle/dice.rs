@@ -0,0 +1,59 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::core::battle::{PlayerId, State};
+
+/// The biggest accuracy bonus that a streak of misses can give.
+const MAX_KARMA_BONUS: i32 = 3;
+
+/// How attack rolls are made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Mode {
+    /// Every roll is independent.
+    #[default]
+    Random,
+
+    /// Every miss in a row makes the next attack of the same player
+    /// a bit more accurate, a hit resets the streak.
+    Karma,
+}
+
+impl Mode {
+    pub fn title(self) -> &'static str {
+        match self {
+            Mode::Random => "random",
+            Mode::Karma => "karma",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Mode::Random => Mode::Karma,
+            Mode::Karma => Mode::Random,
+        }
+    }
+}
+
+/// An attack roll in `0..=10`, the lower the better for the attacker.
+///
+/// Both modes take exactly one number from the RNG per roll
+/// and the miss streaks are tracked by the applied events,
+/// so a battle replays identically under the same seed.
+pub fn roll_attack(state: &mut State, player_id: PlayerId) -> i32 {
+    let roll = state.rng().gen_range(0..ROLL_SIDES);
+    modify_roll(state, player_id, roll)
+}
+
+/// All raw rolls are equally likely.
+pub const ROLL_SIDES: i32 = 11;
+
+/// Turns a raw roll into the one that the attack actually uses.
+pub fn modify_roll(state: &State, player_id: PlayerId, roll: i32) -> i32 {
+    match state.scenario().dice {
+        Mode::Random => roll,
+        Mode::Karma => {
+            let bonus = state.miss_streak(player_id).min(MAX_KARMA_BONUS);
+            (roll - bonus).max(0)
+        }
+    }
+}
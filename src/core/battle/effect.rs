@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::core::battle::{
@@ -41,6 +43,8 @@ pub enum Effect {
     Throw(Throw),
     Dodge(Dodge),
     Bloodlust,
+    Demoralize(Demoralize),
+    Cleanse(Cleanse),
 }
 
 impl Effect {
@@ -57,6 +61,8 @@ impl Effect {
             Effect::Throw(_) => "Throw",
             Effect::Dodge(_) => "Dodge",
             Effect::Bloodlust => "Bloodlust",
+            Effect::Demoralize(_) => "Demoralize",
+            Effect::Cleanse(_) => "Cleanse",
         }
     }
 }
@@ -66,6 +72,9 @@ pub enum Lasting {
     Poison,
     Stun,
     Bloodlust,
+    Phased,
+    Hidden,
+    Injured,
 }
 
 impl Lasting {
@@ -74,6 +83,9 @@ impl Lasting {
             Lasting::Poison => "Poison",
             Lasting::Stun => "Stun",
             Lasting::Bloodlust => "Bloodlust",
+            Lasting::Phased => "Phased",
+            Lasting::Hidden => "Hidden",
+            Lasting::Injured => "Injured",
         }
     }
 
@@ -85,7 +97,73 @@ impl Lasting {
             ],
             Lasting::Stun => vec!["Removes all Actions/Moves/Jokers every turn.".into()],
             Lasting::Bloodlust => vec!["Gives three additional Jokers every turn.".into()],
+            Lasting::Phased => vec![
+                "Passes through the enemies,".into(),
+                "but can't stop on their tiles.".into(),
+            ],
+            Lasting::Hidden => vec![
+                "Can't be seen or targeted by the enemies".into(),
+                "till it attacks or an enemy comes close.".into(),
+            ],
+            Lasting::Injured => vec![
+                "Has lost more than half of its strength.".into(),
+                "Attacks with one accuracy less, and agents with".into(),
+                "several attacks have one attack less every turn.".into(),
+                "Lasts till the end of the battle, healing doesn't help.".into(),
+            ],
+        }
+    }
+}
+
+/// What happens when an object gets a lasting effect that it already has.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StackPolicy {
+    /// The new effect replaces the old one, restarting its duration.
+    #[default]
+    Refresh,
+
+    /// The new duration is added to the old one.
+    Extend,
+
+    /// Every effect is kept and ticks on its own, up to the limit.
+    /// Above the limit the oldest one is refreshed.
+    Stack { limit: usize },
+
+    /// The new effect is ignored.
+    Ignore,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StackRule {
+    pub policy: StackPolicy,
+
+    /// Fire removes the effect from the agents that stand in it.
+    pub is_burnt_by_fire: bool,
+}
+
+/// Stacking rules of the lasting effects, the missing ones are refreshed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StackRules(pub HashMap<Lasting, StackRule>);
+
+impl StackRule {
+    pub fn description(&self) -> Vec<String> {
+        let mut lines = vec![match self.policy {
+            StackPolicy::Refresh => "Reapplying restarts the duration.".into(),
+            StackPolicy::Extend => "Reapplying extends the duration.".into(),
+            StackPolicy::Stack { limit } => format!("Stacks up to {} times.", limit),
+            StackPolicy::Ignore => "Can't be reapplied while active.".into(),
+        }];
+        if self.is_burnt_by_fire {
+            lines.push("Fire burns it out.".into());
         }
+        lines
+    }
+}
+
+impl StackRules {
+    pub fn rule(&self, effect: Lasting) -> StackRule {
+        self.0.get(&effect).copied().unwrap_or_default()
     }
 }
 
@@ -100,6 +178,21 @@ pub struct Wound {
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct Kill {
     pub attacker_pos: Option<PosHex>,
+
+    /// Pushed into the water, there's no blood.
+    #[serde(default)]
+    pub is_drowned: bool,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct Demoralize {
+    pub morale: i32,
+}
+
+/// Removes all lasting effects of the kind.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct Cleanse {
+    pub effect: Lasting,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize)]
@@ -126,6 +219,10 @@ pub struct FlyOff {
 pub struct Throw {
     pub from: PosHex,
     pub to: PosHex,
+
+    /// Rolled along the ground instead of flying over everything.
+    #[serde(default)]
+    pub is_rolled: bool,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
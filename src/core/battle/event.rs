@@ -1,10 +1,11 @@
 use crate::core::battle::{
     ability::{Ability, PassiveAbility},
-    component::{PlannedAbility, WeaponType},
+    command::JokerUse,
+    component::{Hook, PlannedAbility, WeaponType},
     effect::{self, Effect},
     movement::Path,
     state::BattleResult,
-    Id, Moves, PlayerId, PosHex,
+    Id, Moves, PlayerId, PosHex, TileType,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -39,6 +40,21 @@ pub enum ActiveEvent {
     Attack(Attack),
     EffectTick(EffectTick),
     EffectEnd(EffectEnd),
+    Unsummon(Unsummon),
+    MoraleLoss(MoraleLoss),
+    LeaveCorpse(LeaveCorpse),
+    Injury(Injury),
+    Reaction(Reaction),
+    Aura(Aura),
+    Capture(Capture),
+    ConvertJoker(ConvertJoker),
+    Mount(Mount),
+    Dismount(Dismount),
+    Reveal(Reveal),
+    SuddenDeath(SuddenDeath),
+    ScriptTriggered(ScriptTriggered),
+    SetTiles(SetTiles),
+    BeginBossPhase(BeginBossPhase),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -48,10 +64,32 @@ pub struct MoveTo {
     pub id: Id,
 }
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertJoker {
+    pub id: Id,
+    pub into: JokerUse,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    pub id: Id,
+    pub mount_id: Id,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dismount {
+    pub mount_id: Id,
+    pub rider_id: Id,
+    pub pos: PosHex,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum AttackMode {
     Active,
     Reactive,
+
+    /// The target of a melee attack strikes back, see `execute::counter_attack_terms`.
+    Counter,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -72,6 +110,35 @@ pub struct EndTurn {
     pub player_id: PlayerId,
 }
 
+/// The condition of the scenario's script is met, its actions follow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptTriggered {
+    /// Index in `Scenario::scripts`.
+    pub index: usize,
+}
+
+/// The boss is weakened enough for its next phase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeginBossPhase {
+    pub id: Id,
+
+    /// Index in `component::Boss::phases`.
+    pub phase: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetTiles {
+    pub tiles: Vec<PosHex>,
+    pub tile: TileType,
+}
+
+/// The scenario has run out of turns and the edges of the map are set on fire.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuddenDeath {
+    /// Rounds passed since the turn limit has expired, zero on the first one.
+    pub rounds_over: i32,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct BeginTurn {
     pub player_id: PlayerId,
@@ -102,3 +169,57 @@ pub struct EffectEnd {
     pub id: Id,
     pub effect: effect::Lasting,
 }
+
+/// Summoned creatures are bound to their summoner and die with them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsummon {
+    pub summoner_id: Id,
+}
+
+/// The hidden agent is seen again, the effects remove the `Hidden` status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reveal {
+    pub id: Id,
+}
+
+/// An agent's death shakes the nearby allies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoraleLoss {
+    pub dead_id: Id,
+    pub pos: PosHex,
+}
+
+/// The dead agent's corpse stays on its tile for a while.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaveCorpse {
+    pub dead_id: Id,
+    pub pos: PosHex,
+}
+
+/// The agent has dropped below half of its strength, the effects injure it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Injury {
+    pub id: Id,
+}
+
+/// One of the object's reactions goes off, the object may be already dead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reaction {
+    pub id: Id,
+    pub pos: PosHex,
+    pub hook: Hook,
+}
+
+/// The object's auras with lasting effects reach the agents whose turn begins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aura {
+    pub id: Id,
+}
+
+/// The scenario's control point changes hands, see `scenario::ControlPoint`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub index: usize,
+    pub pos: PosHex,
+    pub player_id: PlayerId,
+}
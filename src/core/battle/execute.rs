@@ -1,22 +1,28 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, mem};
 
-use log::{error, trace};
+use log::{error, trace, warn};
 
 use crate::core::{
     battle::{
         self,
-        ability::{Ability, PassiveAbility},
-        check::{check, Error},
-        command::{self, Command},
-        component::{self, ObjType},
+        ability::{Ability, AbilityEffect, Definition, Delivery, PassiveAbility, Target},
+        check::{check, check_batch, Error},
+        command::{self, Command, CommandBatch},
+        component::{self, AuraEffect, DamageType, Hook, ObjType, ReactionEffect, ReactionTarget},
+        dice,
         effect::{self, Effect},
         event::{self, ActiveEvent, Event},
         movement::Path,
+        objective,
+        scenario::{
+            Condition, ControlReward, Expiry, Region, Script, ScriptAction, Trigger, TurnLimit,
+            Weather,
+        },
         state::{self, BattleResult, State},
-        Id, Moves, Phase, PlayerId, PushStrength, Rounds, Strength, Weight,
+        Id, Moves, Phase, PlayerId, PushStrength, Rounds, Strength, TileType, Weight,
     },
     map::{self, Dir, PosHex},
-    utils::{self, roll_dice},
+    utils,
 };
 
 #[derive(PartialEq, Clone, Copy, Debug)]
@@ -28,31 +34,363 @@ pub enum ApplyPhase {
 /// A callback for visualization of the events/effects with the correct state.
 pub type Cb<'c> = &'c mut dyn FnMut(&State, &Event, ApplyPhase);
 
+/// Executes the command and adds it to the state's history.
 pub fn execute(state: &mut State, command: &Command, cb: Cb) -> Result<(), Error> {
     trace!("Simulator: do_command: {:?}", command);
     if let Err(err) = check(state, command) {
         error!("Check failed: {:?}", err);
         return Err(err);
     }
+    // Added before the execution, so the turn that it ends is the one it belongs to.
+    state.add_command_to_history(command);
+    execute_checked(state, command, cb);
+    Ok(())
+}
+
+/// The replays execute only the commands from the history, this one doesn't get there.
+fn execute_nested(state: &mut State, command: &Command, cb: Cb) -> Result<(), Error> {
+    check(state, command)?;
+    execute_checked(state, command, cb);
+    Ok(())
+}
+
+fn execute_checked(state: &mut State, command: &Command, cb: Cb) {
     match *command {
         Command::Create(ref command) => execute_create(state, cb, command),
         Command::MoveTo(ref command) => execute_move_to(state, cb, command),
         Command::Attack(ref command) => execute_attack(state, cb, command),
         Command::EndTurn(ref command) => execute_end_turn(state, cb, command),
         Command::UseAbility(ref command) => execute_use_ability(state, cb, command),
+        Command::ConvertJoker(ref command) => execute_convert_joker(state, cb, command),
+        Command::Mount(ref command) => execute_mount(state, cb, command),
+        Command::Dismount(ref command) => execute_dismount(state, cb, command),
+        Command::Retreat(ref command) => execute_retreat(state, cb, command),
     }
     execute_planned_abilities(state, cb);
+    try_execute_unsummon(state, cb);
+    try_execute_boss_phases(state, cb);
     match *command {
         Command::Create(_) => {}
-        _ => try_execute_end_battle(state, cb),
+        _ => {
+            try_execute_scripts(state, cb);
+            try_execute_end_battle(state, cb);
+        }
     }
-    Ok(())
+    debug_assert_eq!(state::check_invariants(state), Ok(()), "{:?}", command);
+}
+
+/// Executes the batch's commands one by one.
+///
+/// The whole batch is checked before anything happens.
+/// If some intermediate command becomes invalid during the real execution
+/// (e.g. the agent was stunned by a reaction attack) the rest of the batch is cancelled.
+///
+/// Returns the number of actually executed commands.
+pub fn execute_batch(state: &mut State, batch: &CommandBatch, cb: Cb) -> Result<usize, Error> {
+    trace!("Simulator: execute_batch: {:?}", batch);
+    check_batch(state, batch)?;
+    for (i, command) in batch.commands.iter().enumerate() {
+        if check(state, command).is_err() {
+            return Ok(i);
+        }
+        execute(state, command, cb)?;
+    }
+    Ok(batch.commands.len())
 }
 
 fn do_event(state: &mut State, cb: Cb, event: &Event) {
+    do_event_chained(state, cb, event, &mut Vec::new());
+}
+
+/// Every reaction goes off only once in a chain of events,
+/// so the reactions can't keep triggering each other forever.
+fn do_event_chained(state: &mut State, cb: Cb, event: &Event, fired: &mut Vec<(Id, Hook)>) {
+    let morale_events = morale_loss_events(state, event);
+    let corpse_events = corpse_events(state, event);
+    let reactions = triggered_reactions(state, event, fired);
     cb(state, event, ApplyPhase::Pre);
     state.apply(event);
     cb(state, event, ApplyPhase::Post);
+    let injury_events = injury_events(state, event);
+    for event in morale_events
+        .into_iter()
+        .chain(corpse_events)
+        .chain(injury_events)
+    {
+        do_event_chained(state, cb, &event, fired);
+    }
+    for reaction in reactions {
+        let event = reaction_event(state, &reaction);
+        do_event_chained(state, cb, &event, fired);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TriggeredReaction {
+    id: Id,
+
+    /// Where the object was when the reaction was triggered, the dead lose their positions.
+    pos: PosHex,
+
+    hook: Hook,
+    attacker_id: Option<Id>,
+    effect: ReactionEffect,
+}
+
+/// Must be called before the event is applied, like `morale_loss_events`.
+fn triggered_reactions(
+    state: &State,
+    event: &Event,
+    fired: &mut Vec<(Id, Hook)>,
+) -> Vec<TriggeredReaction> {
+    let parts = state.parts();
+    let mut triggers = Vec::new();
+    for &(id, ref effects) in &event.instant_effects {
+        for effect in effects {
+            match (effect, &event.active_event) {
+                (Effect::Kill(_), _) => triggers.push((id, Hook::Death, None)),
+                (Effect::Wound(wound), ActiveEvent::Attack(attack)) if wound.damage.0 > 0 => {
+                    triggers.push((id, Hook::Hit, Some(attack.attacker_id)));
+                }
+                _ => {}
+            }
+        }
+    }
+    if let ActiveEvent::BeginTurn(begin_turn) = &event.active_event {
+        for id in parts.reactions.ids() {
+            if parts.belongs_to.get_opt(id).map(|belongs_to| belongs_to.0)
+                == Some(begin_turn.player_id)
+            {
+                triggers.push((id, Hook::TurnStart, None));
+            }
+        }
+    }
+    let mut reactions = Vec::new();
+    for (id, hook, attacker_id) in triggers {
+        let (component, pos) = match (parts.reactions.get_opt(id), parts.pos.get_opt(id)) {
+            (Some(component), Some(pos)) => (component, pos.0),
+            _ => continue,
+        };
+        if fired.contains(&(id, hook)) {
+            continue;
+        }
+        fired.push((id, hook));
+        for &effect in component.on(hook) {
+            reactions.push(TriggeredReaction {
+                id,
+                pos,
+                hook,
+                attacker_id,
+                effect,
+            });
+        }
+    }
+    reactions
+}
+
+fn reaction_event(state: &mut State, reaction: &TriggeredReaction) -> Event {
+    let mut context = ExecuteContext::default();
+    let target_id = |target| match target {
+        ReactionTarget::Itself => Some(reaction.id),
+        ReactionTarget::Attacker => reaction.attacker_id,
+    };
+    match reaction.effect {
+        ReactionEffect::Fire { radius } => {
+            for pos in tiles_around(state, reaction.pos, radius) {
+                context.merge_with(start_fire(state, pos));
+            }
+        }
+        ReactionEffect::PoisonCloud { radius } => {
+            for pos in tiles_around(state, reaction.pos, radius) {
+                context.merge_with(create_poison_cloud(state, pos));
+            }
+        }
+        ReactionEffect::Lasting {
+            target,
+            effect,
+            rounds,
+        } => {
+            if let Some(id) = target_id(target).filter(|&id| state.parts().is_exist(id)) {
+                context.merge_with(cast_lasting_effect(state, id, effect, rounds));
+            }
+        }
+        ReactionEffect::Wound { target, damage } => {
+            let id = target_id(target).filter(|&id| state.parts().strength.get_opt(id).is_some());
+            if let Some(id) = id {
+                let effect = wound_or_kill(state, id, damage);
+                context.instant_effects.push((id, vec![effect]));
+            }
+        }
+    }
+    let active_event = event::Reaction {
+        id: reaction.id,
+        pos: reaction.pos,
+        hook: reaction.hook,
+    };
+    Event {
+        active_event: active_event.into(),
+        actor_ids: context.actor_ids,
+        instant_effects: context.instant_effects,
+        timed_effects: context.timed_effects,
+        scheduled_abilities: context.scheduled_abilities,
+    }
+}
+
+fn tiles_around(state: &State, pos: PosHex, radius: map::Distance) -> Vec<PosHex> {
+    state
+        .map()
+        .iter()
+        .filter(|&other| map::distance_hex(pos, other) <= radius)
+        .collect()
+}
+
+const MORALE_LOSS_DISTANCE: map::Distance = map::Distance(2);
+
+/// How much an injury takes off the accuracy, see `effect::Lasting::Injured`.
+const INJURY_ACCURACY_PENALTY: i32 = 1;
+
+/// Extra damage of the attacks from the rear arc of the target.
+const BACKSTAB_DAMAGE: Strength = Strength(1);
+
+fn killed_agent_ids(state: &State, event: &Event) -> Vec<Id> {
+    let mut ids = Vec::new();
+    for &(id, ref effects) in &event.instant_effects {
+        for effect in effects {
+            if let Effect::Kill(_) = effect {
+                if state.parts().agent.get_opt(id).is_some() {
+                    ids.push(id);
+                }
+            }
+        }
+    }
+    ids
+}
+
+/// Must be called before the event is applied: dead agents lose their positions.
+fn morale_loss_events(state: &State, event: &Event) -> Vec<Event> {
+    let mut events = Vec::new();
+    let parts = state.parts();
+    let killed_ids = killed_agent_ids(state, event);
+    for &dead_id in &killed_ids {
+        let owner = match parts.belongs_to.get_opt(dead_id) {
+            Some(owner) => owner.0,
+            None => continue,
+        };
+        let pos = parts.pos.get(dead_id).0;
+        let mut instant_effects = Vec::new();
+        for id in state::players_agent_ids(state, owner) {
+            if killed_ids.contains(&id)
+                || parts.morale.get_opt(id).is_none()
+                || state::is_routed(state, id)
+                || map::distance_hex(parts.pos.get(id).0, pos) > MORALE_LOSS_DISTANCE
+            {
+                continue;
+            }
+            let effect = effect::Demoralize { morale: 1 }.into();
+            instant_effects.push((id, vec![effect]));
+        }
+        if instant_effects.is_empty() {
+            continue;
+        }
+        events.push(Event {
+            active_event: event::MoraleLoss { dead_id, pos }.into(),
+            actor_ids: Vec::new(),
+            instant_effects,
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        });
+    }
+    events
+}
+
+/// The wounded agents that drop below half of their strength are injured
+/// for the rest of the battle.
+///
+/// Must be called after the event is applied, unlike `morale_loss_events`.
+fn injury_events(state: &State, event: &Event) -> Vec<Event> {
+    let mut events = Vec::new();
+    for (id, effects) in &event.instant_effects {
+        let id = *id;
+        let is_wounded = effects
+            .iter()
+            .any(|effect| matches!(effect, Effect::Wound(_)));
+        let owner = match state.parts().belongs_to.get_opt(id) {
+            Some(belongs_to) => belongs_to.0,
+            None => continue,
+        };
+        if !is_wounded
+            || state.parts().agent.get_opt(id).is_none()
+            || !state::is_badly_wounded(state, id)
+            || state::is_injured(state, id)
+        {
+            continue;
+        }
+        let timed = effect::Timed {
+            duration: effect::Duration::Forever,
+            phase: Phase::from_player_id(owner),
+            effect: effect::Lasting::Injured,
+        };
+        events.push(Event {
+            active_event: event::Injury { id }.into(),
+            actor_ids: vec![id],
+            instant_effects: Vec::new(),
+            timed_effects: vec![(id, vec![timed])],
+            scheduled_abilities: Vec::new(),
+        });
+    }
+    events
+}
+
+/// How long the corpses lie before they rot away.
+const CORPSE_ROUNDS: Rounds = Rounds(3);
+
+/// The summoned, the large and the drowned agents leave no corpses,
+/// and a tile can't have two of them.
+///
+/// Must be called before the event is applied, like `morale_loss_events`.
+fn corpse_events(state: &mut State, event: &Event) -> Vec<Event> {
+    let mut events = Vec::new();
+    for &(dead_id, ref effects) in &event.instant_effects {
+        let parts = state.parts();
+        if parts.agent.get_opt(dead_id).is_none()
+            || parts.summoned_by.get_opt(dead_id).is_some()
+            || !state::footprint(state, dead_id).is_empty()
+        {
+            continue;
+        }
+        // The blow that kills the agent may throw it away first.
+        let mut pos = parts.pos.get(dead_id).0;
+        let mut is_killed = false;
+        for effect in effects {
+            match effect {
+                Effect::Knockback(effect::Knockback { to, .. })
+                | Effect::FlyOff(effect::FlyOff { to, .. }) => pos = *to,
+                Effect::Kill(kill) => is_killed = !kill.is_drowned,
+                _ => {}
+            }
+        }
+        if !is_killed
+            || state::is_water(state, pos)
+            || state::corpse_id_at_opt(state, pos).is_some()
+        {
+            continue;
+        }
+        let effect_create = effect_create_object(state, &"corpse".into(), pos);
+        let rot = component::PlannedAbility {
+            rounds: CORPSE_ROUNDS,
+            phase: Phase::from_player_id(state.player_id()),
+            ability: Ability::Vanish,
+        };
+        let id = state.alloc_id();
+        events.push(Event {
+            active_event: event::LeaveCorpse { dead_id, pos }.into(),
+            actor_ids: Vec::new(),
+            instant_effects: vec![(id, vec![effect_create])],
+            timed_effects: Vec::new(),
+            scheduled_abilities: vec![(id, vec![rot])],
+        });
+    }
+    events
 }
 
 fn execute_move_to(state: &mut State, cb: Cb, command: &command::MoveTo) {
@@ -70,10 +408,17 @@ fn execute_move_to(state: &mut State, cb: Cb, command: &command::MoveTo) {
     if state.parts().agent.get_opt(id).is_none() {
         return;
     }
+    let mut tiles = vec![command.path.from()];
     for step in command.path.steps() {
         assert!(state.parts().agent.get_opt(id).is_some());
-        let path = Path::new(vec![step.from, step.to]);
+        tiles.push(step.to);
+        // A flyer doesn't land on the blockers it passes over, it can't be stopped there.
+        if step.to != command.path.to() && !state::fits_at(state, command.id, step.to) {
+            continue;
+        }
+        let path = Path::new(mem::replace(&mut tiles, vec![step.to]));
         do_move(state, cb, id, cost.take(), path);
+        try_execute_reveals_around(state, cb, id);
         try_execute_passive_abilities_on_move(state, cb, id);
         let attack_status = try_execute_reaction_attacks(state, cb, id);
         let is_alive = state.parts().agent.get_opt(id).is_some();
@@ -83,6 +428,41 @@ fn execute_move_to(state: &mut State, cb: Cb, command: &command::MoveTo) {
     }
 }
 
+fn try_execute_reveal(state: &mut State, cb: Cb, id: Id) {
+    if !state::is_hidden(state, id) {
+        return;
+    }
+    let effect = effect::Cleanse {
+        effect: effect::Lasting::Hidden,
+    };
+    let event = Event {
+        active_event: event::Reveal { id }.into(),
+        actor_ids: vec![id],
+        instant_effects: vec![(id, vec![effect.into()])],
+        timed_effects: Vec::new(),
+        scheduled_abilities: Vec::new(),
+    };
+    do_event(state, cb, &event);
+}
+
+/// Nobody stays hidden next to an enemy: both the agent and its neighbors are revealed.
+fn try_execute_reveals_around(state: &mut State, cb: Cb, id: Id) {
+    if state.parts().agent.get_opt(id).is_none() {
+        return;
+    }
+    let player_id = state.parts().belongs_to.get(id).0;
+    let mut neighbor_ids = state::enemy_agent_ids(state, player_id);
+    neighbor_ids
+        .retain(|&other_id| state::distance_between(state, id, other_id) <= map::Distance(1));
+    neighbor_ids.sort();
+    if !neighbor_ids.is_empty() {
+        try_execute_reveal(state, cb, id);
+    }
+    for neighbor_id in neighbor_ids {
+        try_execute_reveal(state, cb, neighbor_id);
+    }
+}
+
 fn do_move(state: &mut State, cb: Cb, id: Id, cost: Option<Moves>, path: Path) {
     let cost = cost.unwrap_or(Moves(0));
     let active_event = event::MoveTo { path, cost, id }.into();
@@ -97,7 +477,22 @@ fn do_move(state: &mut State, cb: Cb, id: Id, cost: Option<Moves>, path: Path) {
 }
 
 fn execute_create(state: &mut State, cb: Cb, command: &command::Create) {
+    if state::is_deploying(state) {
+        if let Some(index) = state::deployment_index(state, command) {
+            state.remove_from_deployment(index);
+        }
+    }
     let mut components = state.prototype_for(&command.prototype);
+    for extra in &command.extra_components {
+        let mut extra = extra.clone();
+        component::init_component(&mut extra);
+        let same_type =
+            |c: &component::Component| mem::discriminant(c) == mem::discriminant(&extra);
+        match components.iter().position(same_type) {
+            Some(i) => components[i] = extra,
+            None => components.push(extra),
+        }
+    }
     if let Some(player_id) = command.owner {
         components.push(component::BelongsTo(player_id).into());
     }
@@ -142,6 +537,7 @@ fn execute_attack_internal(
 ) -> AttackStatus {
     let attacker_id = command.attacker_id;
     let target_id = command.target_id;
+    try_execute_reveal(state, cb, attacker_id);
     let weapon_type = state.parts().agent.get(attacker_id).weapon_type;
     let event_attack = event::Attack {
         attacker_id,
@@ -151,7 +547,7 @@ fn execute_attack_internal(
     };
     let mut context = ExecuteContext::default();
     let mut is_kill = false;
-    if let Some(effect) = try_attack(state, attacker_id, target_id) {
+    if let Some(effect) = try_attack(state, attacker_id, target_id, mode) {
         if let Effect::Kill(_) = effect {
             is_kill = true;
         }
@@ -163,7 +559,7 @@ fn execute_attack_internal(
         context.instant_effects.push((target_id, vec![dodge]));
         AttackStatus::Miss
     } else {
-        if !is_kill {
+        if !is_kill && !state::is_destructible(state, target_id) {
             let c = try_execute_passive_abilities_on_attack(state, attacker_id, target_id);
             context.merge_with(c);
         }
@@ -178,16 +574,46 @@ fn execute_attack_internal(
     };
     do_event(state, cb, &event);
     for id in context.moved_actor_ids {
+        try_execute_reveals_around(state, cb, id);
         try_execute_passive_abilities_on_move(state, cb, id);
     }
     status
 }
 
+/// Lasting effects of the agent that the fire burns out, see `effect::StackRule`.
+fn burnt_effects(state: &State, id: Id) -> Vec<effect::Lasting> {
+    let mut burnt = Vec::new();
+    if let Some(effects) = state.parts().effects.get_opt(id) {
+        for timed in &effects.0 {
+            let rule = state.stack_rules().rule(timed.effect);
+            if rule.is_burnt_by_fire && !burnt.contains(&timed.effect) {
+                burnt.push(timed.effect);
+            }
+        }
+    }
+    burnt
+}
+
 fn try_execute_passive_ability_burn(state: &mut State, target_id: Id) -> ExecuteContext {
     let mut context = ExecuteContext::default();
     let damage = battle::Strength(1);
-    let target_effects = vec![wound_or_kill(state, target_id, damage)];
-    context.instant_effects.push((target_id, target_effects));
+    let damage = correct_damage_with_resistance(state, target_id, damage, DamageType::Fire);
+    let mut target_effects = Vec::new();
+    let mut is_killed = false;
+    // The fire-resistant objects aren't even scratched.
+    if damage.0 > 0 {
+        let wound = wound_or_kill(state, target_id, damage);
+        is_killed = matches!(wound, Effect::Kill(_));
+        target_effects.push(wound);
+    }
+    if !is_killed {
+        for effect in burnt_effects(state, target_id) {
+            target_effects.push(effect::Cleanse { effect }.into());
+        }
+    }
+    if !target_effects.is_empty() {
+        context.instant_effects.push((target_id, target_effects));
+    }
     context
 }
 
@@ -278,7 +704,9 @@ fn try_execute_passive_abilities_tick(state: &mut State, cb: Cb, target_id: Id)
                 }
                 PassiveAbility::Burn => {
                     let context = try_execute_passive_ability_burn(state, target_id);
-                    do_passive_ability(state, cb, id, target_pos, ability, context);
+                    if !context.instant_effects.is_empty() {
+                        do_passive_ability(state, cb, id, target_pos, ability, context);
+                    }
                 }
                 PassiveAbility::Poison => {
                     let context = try_execute_passive_ability_poison(state, target_id);
@@ -289,6 +717,7 @@ fn try_execute_passive_abilities_tick(state: &mut State, cb: Cb, target_id: Id)
                 PassiveAbility::HeavyImpact
                 | PassiveAbility::PoisonAttack
                 | PassiveAbility::Regenerate
+                | PassiveAbility::Light
                 | PassiveAbility::SpawnPoisonCloudOnDeath => {}
             }
         }
@@ -341,6 +770,52 @@ fn try_execute_passive_abilities_on_begin_turn(state: &mut State, cb: Cb) {
     }
 }
 
+/// The lasting and healing auras catch the agents of the side whose turn has just begun.
+fn try_execute_auras(state: &mut State, cb: Cb) {
+    let source_ids = state.parts().auras.ids_collected();
+    for source_id in source_ids {
+        if !state.parts().is_exist(source_id) {
+            continue;
+        }
+        let mut context = ExecuteContext::default();
+        for id in state::players_agent_ids(state, state.player_id()) {
+            if id == source_id {
+                continue;
+            }
+            for (aura_source_id, aura) in state::auras_affecting(state, id) {
+                if aura_source_id != source_id {
+                    continue;
+                }
+                match aura.effect {
+                    AuraEffect::Lasting { effect, rounds } => {
+                        context.merge_with(cast_lasting_effect(state, id, effect, rounds));
+                    }
+                    AuraEffect::Heal(strength) => {
+                        let agent_strength = state.parts().strength.get(id);
+                        if agent_strength.strength < agent_strength.base_strength {
+                            let strength = Strength(strength);
+                            let effect = effect::Heal { strength }.into();
+                            extend_or_crate_sub_vec(&mut context.instant_effects, id, vec![effect]);
+                        }
+                    }
+                    AuraEffect::AttackStrength(_) => {}
+                }
+            }
+        }
+        if context.timed_effects.is_empty() && context.instant_effects.is_empty() {
+            continue;
+        }
+        let event = Event {
+            active_event: event::Aura { id: source_id }.into(),
+            actor_ids: vec![source_id],
+            instant_effects: context.instant_effects,
+            timed_effects: context.timed_effects,
+            scheduled_abilities: Vec::new(),
+        };
+        do_event(state, cb, &event);
+    }
+}
+
 fn try_execute_passive_abilities_on_attack(
     state: &mut State,
     attacker_id: Id,
@@ -349,14 +824,18 @@ fn try_execute_passive_abilities_on_attack(
     let mut context = ExecuteContext::default();
     let parts = state.parts();
     let target_pos = parts.pos.get(target_id).0;
-    let attacker_pos = parts.pos.get(attacker_id).0;
     if let Some(passive_abilities) = parts.passive_abilities.get_opt(attacker_id) {
         let abilities = passive_abilities.clone();
         for &ability in &abilities.0 {
             trace!("ability: {:?}", ability);
             match ability {
                 PassiveAbility::HeavyImpact => {
-                    let dir = Dir::get_dir_from_to(attacker_pos, target_pos);
+                    let (attacker_tile, target_tile) =
+                        state::closest_tiles(state, attacker_id, target_id);
+                    if map::distance_hex(attacker_tile, target_tile) != map::Distance(1) {
+                        continue;
+                    }
+                    let dir = Dir::get_dir_from_to(attacker_tile, target_tile);
                     let from = target_pos;
                     let strength = PushStrength(Weight::Normal);
                     let blocker_weight = parts.blocker.get(target_id).weight;
@@ -365,10 +844,10 @@ fn try_execute_passive_abilities_on_attack(
                     } else {
                         from
                     };
-                    let is_inboard = state.map().is_inboard(to);
-                    if to == from || is_inboard && !state::is_tile_blocked(state, to) {
-                        let effect = effect::FlyOff { from, to, strength }.into();
-                        context.instant_effects.push((target_id, vec![effect]));
+                    if to == from || state::fits_at(state, target_id, to) {
+                        let mut effects = vec![effect::FlyOff { from, to, strength }.into()];
+                        effects.extend(drown(state, target_id, to));
+                        context.instant_effects.push((target_id, effects));
                         context.moved_actor_ids.push(target_id);
                     }
                 }
@@ -385,6 +864,7 @@ fn try_execute_passive_abilities_on_attack(
                 | PassiveAbility::SpikeTrap
                 | PassiveAbility::Poison
                 | PassiveAbility::Regenerate
+                | PassiveAbility::Light
                 | PassiveAbility::SpawnPoisonCloudOnDeath => (),
             }
         }
@@ -405,7 +885,7 @@ fn try_execute_reaction_attacks(state: &mut State, cb: Cb, target_id: Id) -> Att
             continue;
         }
         let this_agent_owner = state.parts().belongs_to.get(obj_id).0;
-        if this_agent_owner == target_owner {
+        if state::are_allies(state, this_agent_owner, target_owner) {
             continue;
         }
         let command_attack = command::Attack {
@@ -427,9 +907,114 @@ fn try_execute_reaction_attacks(state: &mut State, cb: Cb, target_id: Id) -> Att
     status
 }
 
+/// The target of a melee attack strikes back right away if it still has an attack.
+fn try_execute_counter_attack(state: &mut State, cb: Cb, command: &command::Attack) {
+    let parts = state.parts();
+    if !parts.is_exist(command.attacker_id) {
+        return;
+    }
+    let agent = match parts.agent.get_opt(command.target_id) {
+        Some(agent) => agent,
+        None => return,
+    };
+    if agent.cannot_counter || agent.attacks.0 <= 0 {
+        return;
+    }
+    let (attacker_tile, target_tile) =
+        state::closest_tiles(state, command.attacker_id, command.target_id);
+    if map::distance_hex(attacker_tile, target_tile) != map::Distance(1) {
+        return;
+    }
+    let command_attack = command::Attack {
+        attacker_id: command.target_id,
+        target_id: command.attacker_id,
+    };
+    let initial_player_id = state.player_id();
+    state.set_player_id(state.parts().belongs_to.get(command.target_id).0);
+    if check(state, &command_attack.clone().into()).is_ok() {
+        let mode = event::AttackMode::Counter;
+        execute_attack_internal(state, cb, &command_attack, mode);
+    }
+    state.set_player_id(initial_player_id);
+}
+
 fn execute_attack(state: &mut State, cb: Cb, command: &command::Attack) {
     execute_attack_internal(state, cb, command, event::AttackMode::Active);
-    try_execute_reaction_attacks(state, cb, command.attacker_id);
+    try_execute_counter_attack(state, cb, command);
+    if state.parts().is_exist(command.attacker_id) {
+        try_execute_reaction_attacks(state, cb, command.attacker_id);
+    }
+}
+
+fn execute_convert_joker(state: &mut State, cb: Cb, command: &command::ConvertJoker) {
+    let active_event = event::ConvertJoker {
+        id: command.id,
+        into: command.into,
+    }
+    .into();
+    let event = Event {
+        active_event,
+        actor_ids: vec![command.id],
+        instant_effects: Vec::new(),
+        timed_effects: Vec::new(),
+        scheduled_abilities: Vec::new(),
+    };
+    do_event(state, cb, &event);
+}
+
+fn execute_mount(state: &mut State, cb: Cb, command: &command::Mount) {
+    let active_event = event::Mount {
+        id: command.id,
+        mount_id: command.mount_id,
+    }
+    .into();
+    let event = Event {
+        active_event,
+        actor_ids: vec![command.id, command.mount_id],
+        instant_effects: Vec::new(),
+        timed_effects: Vec::new(),
+        scheduled_abilities: Vec::new(),
+    };
+    do_event(state, cb, &event);
+}
+
+fn execute_dismount(state: &mut State, cb: Cb, command: &command::Dismount) {
+    let rider_id = state::rider_id(state, command.mount_id).expect("No rider");
+    let active_event = event::Dismount {
+        mount_id: command.mount_id,
+        rider_id,
+        pos: command.pos,
+    }
+    .into();
+    let event = Event {
+        active_event,
+        actor_ids: vec![command.mount_id, rider_id],
+        instant_effects: Vec::new(),
+        timed_effects: Vec::new(),
+        scheduled_abilities: Vec::new(),
+    };
+    do_event(state, cb, &event);
+}
+
+/// The agents that end their turn in a thicket away from the enemies hide till their next turn.
+fn thicket_hiding_effects(state: &State, ids: &[Id]) -> Vec<(Id, Vec<effect::Timed>)> {
+    let mut effects = Vec::new();
+    for &id in ids {
+        let is_in_thicket = state::object_tiles(state, id)
+            .into_iter()
+            .any(|pos| state::is_thicket(state, pos));
+        if !is_in_thicket || state::is_hidden(state, id) || state::is_next_to_enemy(state, id) {
+            continue;
+        }
+        let owner = state.parts().belongs_to.get(id).0;
+        let timed = effect::Timed {
+            duration: effect::Duration::Rounds(1.into()),
+            phase: Phase::from_player_id(owner),
+            effect: effect::Lasting::Hidden,
+        };
+        effects.push((id, vec![timed]));
+    }
+    effects
 }
 
 fn execute_event_end_turn(state: &mut State, cb: Cb) {
@@ -440,11 +1025,12 @@ fn execute_event_end_turn(state: &mut State, cb: Cb) {
     .into();
     let mut actor_ids = state::players_agent_ids(state, player_id_old);
     actor_ids.sort();
+    let timed_effects = thicket_hiding_effects(state, &actor_ids);
     let event = Event {
         active_event,
         actor_ids,
         instant_effects: Vec::new(),
-        timed_effects: Vec::new(),
+        timed_effects,
         scheduled_abilities: Vec::new(),
     };
     do_event(state, cb, &event);
@@ -497,26 +1083,44 @@ fn execute_planned_abilities(state: &mut State, cb: Cb) {
 }
 
 fn try_execute_end_battle(state: &mut State, cb: Cb) {
-    for i in 0..state.scenario().players_count {
-        let player_id = PlayerId(i);
-        let enemies_count = state::enemy_agent_ids(state, player_id).len();
-        if enemies_count == 0 {
-            let result = BattleResult {
-                winner_id: player_id,
-                survivor_types: state::players_agent_types(state, PlayerId(0)),
-            };
-            let event = Event {
-                active_event: event::EndBattle { result }.into(),
-                actor_ids: Vec::new(),
-                instant_effects: Vec::new(),
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
-            };
-            do_event(state, cb, &event);
-        }
+    if state.battle_result().is_some() {
+        return;
+    }
+    if let Some(winner_id) = objective::winner(state) {
+        execute_end_battle(state, cb, winner_id, false);
     }
 }
 
+fn execute_retreat(state: &mut State, cb: Cb, _: &command::Retreat) {
+    let player_id = state.player_id();
+    let winner_id = (0..state.scenario().players_count)
+        .map(PlayerId)
+        .find(|&id| !state::are_allies(state, id, player_id))
+        .expect("No enemy players");
+    execute_end_battle(state, cb, winner_id, true);
+}
+
+fn execute_end_battle(state: &mut State, cb: Cb, winner_id: PlayerId, is_retreat: bool) {
+    let result = BattleResult {
+        winner_id,
+        survivor_types: state::players_agent_types(state, PlayerId(0)),
+        veterans: state::players_veterans(state, PlayerId(0)),
+        fallen: state.fallen_veterans().to_vec(),
+        charges: state::players_veteran_charges(state, PlayerId(0)),
+        ability_uses: state.ability_uses(PlayerId(0)),
+        is_retreat,
+        control_renown: state.control_renown(),
+    };
+    let event = Event {
+        active_event: event::EndBattle { result }.into(),
+        actor_ids: Vec::new(),
+        instant_effects: Vec::new(),
+        timed_effects: Vec::new(),
+        scheduled_abilities: Vec::new(),
+    };
+    do_event(state, cb, &event);
+}
+
 // TODO: simplify
 /// Ticks and kills all the lasting effects.
 fn execute_effects(state: &mut State, cb: Cb) {
@@ -545,6 +1149,9 @@ fn execute_effects(state: &mut State, cb: Cb) {
                         target_effects.push(Effect::Stun);
                     }
                     effect::Lasting::Bloodlust => target_effects.push(Effect::Bloodlust),
+                    effect::Lasting::Phased
+                    | effect::Lasting::Hidden
+                    | effect::Lasting::Injured => {}
                 }
                 let instant_effects = vec![(id, target_effects)];
                 let event = Event {
@@ -582,23 +1189,318 @@ fn execute_effects(state: &mut State, cb: Cb) {
 }
 
 fn execute_end_turn(state: &mut State, cb: Cb, _: &command::EndTurn) {
+    update_control_points(state, cb);
     execute_event_end_turn(state, cb);
     execute_event_begin_turn(state, cb);
+    update_zone_occupation(state);
+    try_execute_reinforcements(state, cb);
+    try_execute_sudden_death(state, cb);
     try_execute_passive_abilities_on_begin_turn(state, cb);
+    try_execute_auras(state, cb);
     execute_effects(state, cb);
 }
 
-fn start_fire(state: &mut State, pos: PosHex) -> ExecuteContext {
-    let vanish = component::PlannedAbility {
-        rounds: 2.into(), // TODO: Replace this magic number
-        phase: Phase::from_player_id(state.player_id()),
-        ability: Ability::Vanish,
-    };
-    let mut context = ExecuteContext::default();
-    if let Some(id) = state::obj_with_passive_ability_at(state, pos, PassiveAbility::Burn) {
-        context.scheduled_abilities.push((id, vec![vanish]));
-    } else {
-        let effect_create = effect_create_object(state, &"fire".into(), pos);
+fn is_wave_triggered(state: &State, trigger: &Trigger) -> bool {
+    match *trigger {
+        Trigger::Round(round) => state.rounds_passed() >= round,
+        Trigger::FewerAgentsThan { owner, count } => {
+            (state::players_agent_ids(state, owner).len() as i32) < count
+        }
+    }
+}
+
+/// The tile or one of its neighbors where all the tiles of the prototype are free.
+fn reinforcement_pos(state: &State, prototype: &ObjType, pos: PosHex) -> Option<PosHex> {
+    let footprint = state::prototype_footprint(state, prototype);
+    let neighbors = map::dirs().map(|dir| Dir::get_neighbor_pos(pos, dir));
+    std::iter::once(pos).chain(neighbors).find(|&pos| {
+        state::footprint_tiles(pos, &footprint)
+            .into_iter()
+            .all(|tile| {
+                state::is_tile_completely_free(state, tile) && !state::is_water(state, tile)
+            })
+    })
+}
+
+fn execute_spawn(
+    state: &mut State,
+    cb: Cb,
+    owner: Option<PlayerId>,
+    objects: Vec<(ObjType, PosHex)>,
+) {
+    for (prototype, pos) in objects {
+        let pos = match reinforcement_pos(state, &prototype, pos) {
+            Some(pos) => pos,
+            None => {
+                warn!(
+                    "No room for a '{}' reinforcement near {:?}",
+                    prototype.0, pos
+                );
+                continue;
+            }
+        };
+        let command = command::Create {
+            prototype,
+            pos,
+            owner,
+            extra_components: Vec::new(),
+        }
+        .into();
+        if let Err(err) = execute_nested(state, &command, cb) {
+            warn!("Can't create a reinforcement: {:?}", err);
+        }
+    }
+}
+
+/// Brings in the waves of the current player whose triggers have fired.
+fn try_execute_reinforcements(state: &mut State, cb: Cb) {
+    let waves = state.scenario().reinforcements.clone();
+    for (index, wave) in waves.into_iter().enumerate() {
+        let is_ready = wave.owner == state.player_id()
+            && !state.is_wave_arrived(index)
+            && is_wave_triggered(state, &wave.trigger);
+        if !is_ready {
+            continue;
+        }
+        state.add_arrived_wave(index);
+        execute_spawn(state, cb, Some(wave.owner), wave.objects);
+    }
+}
+
+fn region_agent_ids(state: &State, region: &Region) -> Vec<Id> {
+    region
+        .tiles
+        .iter()
+        .flat_map(|&pos| state::agent_ids_at(state, pos))
+        .collect()
+}
+
+/// The agents of the player whose turn ends take the control points they stand on,
+/// then the player gets the rewards of the points it holds.
+fn update_control_points(state: &mut State, cb: Cb) {
+    let player_id = state.player_id();
+    let points = state.scenario().control_points.clone();
+    for (index, point) in points.into_iter().enumerate() {
+        let is_taken = state::agent_ids_at(state, point.pos)
+            .into_iter()
+            .any(|id| state::is_agent_belong_to(state, player_id, id));
+        if is_taken && state.control_point_holder(index) != Some(player_id) {
+            let active_event = event::Capture {
+                index,
+                pos: point.pos,
+                player_id,
+            }
+            .into();
+            let event = Event {
+                active_event,
+                actor_ids: Vec::new(),
+                instant_effects: Vec::new(),
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            };
+            do_event(state, cb, &event);
+            if let ControlReward::Reinforcement(ref prototype) = point.reward {
+                let objects = vec![(prototype.clone(), point.pos)];
+                execute_spawn(state, cb, Some(player_id), objects);
+            }
+        }
+        if state.control_point_holder(index) != Some(player_id) {
+            continue;
+        }
+        if let ControlReward::Renown(renown) = point.reward {
+            if state::is_on_human_side(state, player_id) {
+                state.add_control_renown(renown);
+            }
+        }
+    }
+}
+
+/// Counts the rounds the current player has been holding the `Occupy` regions.
+fn update_zone_occupation(state: &mut State) {
+    let player_id = state.player_id();
+    let scripts = state.scenario().scripts.clone();
+    for (index, script) in scripts.iter().enumerate() {
+        let (region, owner) = match script.condition {
+            Condition::Occupy {
+                ref region, owner, ..
+            } => (region, owner),
+            _ => continue,
+        };
+        if owner != player_id || state.is_script_fired(index) {
+            continue;
+        }
+        let region = state.scenario().region(region).expect("Bad region");
+        let owners: Vec<_> = region_agent_ids(state, region)
+            .into_iter()
+            .map(|id| state.parts().belongs_to.get(id).0)
+            .collect();
+        let is_held = !owners.is_empty()
+            && owners
+                .iter()
+                .all(|&agent_owner| state::are_allies(state, agent_owner, owner));
+        let rounds = if is_held {
+            state.zone_occupation(index) + 1
+        } else {
+            0
+        };
+        state.set_zone_occupation(index, rounds);
+    }
+}
+
+fn is_script_condition_met(state: &State, index: usize, script: &Script) -> bool {
+    let has_agents_of = |region: &str, player_id| {
+        let region = state.scenario().region(region).expect("Bad region");
+        region_agent_ids(state, region)
+            .into_iter()
+            .any(|id| state::is_agent_belong_to(state, player_id, id))
+    };
+    match script.condition {
+        Condition::RoundBegins(round) => state.rounds_passed() >= round,
+        Condition::Enter {
+            ref region,
+            player_id,
+        } => has_agents_of(region, player_id),
+        Condition::Occupy { rounds, .. } => state.zone_occupation(index) >= rounds,
+        Condition::Clear {
+            ref region,
+            player_id,
+        } => !has_agents_of(region, player_id),
+        Condition::Destroyed(ref typename) => {
+            let parts = state.parts();
+            !parts
+                .meta
+                .ids()
+                .any(|id| &parts.meta.get(id).name == typename)
+        }
+        Condition::StrengthDropsTo {
+            ref typename,
+            percent,
+        } => {
+            let parts = state.parts();
+            parts.strength.ids().any(|id| {
+                let strength = parts.strength.get(id);
+                let is_weak = strength.strength.0 * 100 <= strength.base_strength.0 * percent;
+                &parts.meta.get(id).name == typename && is_weak
+            })
+        }
+    }
+}
+
+fn execute_set_tiles(state: &mut State, cb: Cb, tiles: Vec<PosHex>, tile: TileType) {
+    let event = Event {
+        active_event: event::SetTiles { tiles, tile }.into(),
+        actor_ids: Vec::new(),
+        instant_effects: Vec::new(),
+        timed_effects: Vec::new(),
+        scheduled_abilities: Vec::new(),
+    };
+    do_event(state, cb, &event);
+}
+
+/// Fires the scenario's scripts whose conditions are met.
+pub(in crate::core) fn try_execute_scripts(state: &mut State, cb: Cb) {
+    let scripts = state.scenario().scripts.clone();
+    for (index, script) in scripts.into_iter().enumerate() {
+        if state.battle_result().is_some() {
+            return;
+        }
+        if state.is_script_fired(index) || !is_script_condition_met(state, index, &script) {
+            continue;
+        }
+        state.add_fired_script(index);
+        let event = Event {
+            active_event: event::ScriptTriggered { index }.into(),
+            actor_ids: Vec::new(),
+            instant_effects: Vec::new(),
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        };
+        do_event(state, cb, &event);
+        for action in script.actions {
+            match action {
+                ScriptAction::Spawn { owner, objects } => execute_spawn(state, cb, owner, objects),
+                ScriptAction::SetTiles { tiles, tile } => execute_set_tiles(state, cb, tiles, tile),
+                ScriptAction::Win(winner_id) => execute_end_battle(state, cb, winner_id, false),
+                // Texts are only shown by the visualizer of the `ScriptTriggered` event.
+                ScriptAction::Message(_) | ScriptAction::Dialogue(_) => {}
+            }
+        }
+    }
+}
+
+/// Sets the edges of the map on fire every round after the turn limit,
+/// closer to the center with every round.
+fn try_execute_sudden_death(state: &mut State, cb: Cb) {
+    let turns = match state.scenario().turn_limit {
+        Some(TurnLimit {
+            turns,
+            on_expire: Expiry::SuddenDeath,
+        }) => turns,
+        _ => return,
+    };
+    if state.player_id() != PlayerId(0) || state.rounds_passed() < turns {
+        return;
+    }
+    let rounds_over = state.rounds_passed() - turns;
+    let min_distance = state.map().radius().0 - rounds_over;
+    let origin = PosHex { q: 0, r: 0 };
+    let positions: Vec<_> = state
+        .map()
+        .iter()
+        .filter(|&pos| map::distance_hex(origin, pos).0 >= min_distance)
+        .collect();
+    let mut context = ExecuteContext::default();
+    for pos in positions {
+        context.merge_with(start_fire(state, pos));
+    }
+    let event = Event {
+        active_event: event::SuddenDeath { rounds_over }.into(),
+        actor_ids: context.actor_ids,
+        instant_effects: context.instant_effects,
+        timed_effects: context.timed_effects,
+        scheduled_abilities: context.scheduled_abilities,
+    };
+    do_event(state, cb, &event);
+}
+
+/// Rain makes fires burn out faster.
+fn fire_duration(state: &State) -> Rounds {
+    match state.scenario().weather {
+        Weather::Rain => 1.into(),
+        _ => 2.into(), // TODO: Replace this magic number
+    }
+}
+
+/// Wind carries thrown objects one more tile, unless that tile is taken.
+fn windblown_pos(state: &State, pos: PosHex) -> PosHex {
+    if let Weather::Wind(dir) = state.scenario().weather {
+        let blown_pos = Dir::get_neighbor_pos(pos, dir);
+        if state.map().is_inboard(blown_pos)
+            && !state::is_tile_blocked(state, blown_pos)
+            && !state::is_water(state, blown_pos)
+        {
+            return blown_pos;
+        }
+    }
+    pos
+}
+
+fn start_fire(state: &mut State, pos: PosHex) -> ExecuteContext {
+    let vanish = component::PlannedAbility {
+        rounds: fire_duration(state),
+        phase: Phase::from_player_id(state.player_id()),
+        ability: Ability::Vanish,
+    };
+    let mut context = ExecuteContext::default();
+    if let Some(corpse_id) = state::corpse_id_at_opt(state, pos) {
+        context
+            .instant_effects
+            .push((corpse_id, vec![Effect::Vanish]));
+    }
+    if let Some(id) = state::obj_with_passive_ability_at(state, pos, PassiveAbility::Burn) {
+        context.scheduled_abilities.push((id, vec![vanish]));
+    } else {
+        let effect_create = effect_create_object(state, &"fire".into(), pos);
         let id = state.alloc_id();
         context.instant_effects.push((id, vec![effect_create]));
         context.scheduled_abilities.push((id, vec![vanish]));
@@ -673,45 +1575,68 @@ impl ExecuteContext {
     }
 }
 
-fn execute_use_ability_knockback(
-    state: &mut State,
-    command: &command::UseAbility,
-) -> ExecuteContext {
+/// Pushes the object one tile away in the given direction.
+///
+/// The object stays in place if the tile is taken, but a non-agent object
+/// (a boulder, a bomb) that runs into an agent also hurts it.
+/// Kills the object that has been pushed into the water, see `state::is_drowning_at`.
+fn drown(state: &State, id: Id, pos: PosHex) -> Option<Effect> {
+    if !state::is_drowning_at(state, id, pos) {
+        return None;
+    }
+    let effect = effect::Kill {
+        attacker_pos: None,
+        is_drowned: true,
+    };
+    Some(effect.into())
+}
+
+fn knockback(state: &State, id: Id, dir: Dir, strength: PushStrength) -> ExecuteContext {
     let mut context = ExecuteContext::default();
-    let id = state::blocker_id_at(state, command.pos);
-    let from = command.pos;
-    let strength = PushStrength(Weight::Normal);
-    let actor_pos = state.parts().pos.get(command.id).0;
-    let dir = Dir::get_dir_from_to(actor_pos, command.pos);
-    let blocker_weight = state.parts().blocker.get(id).weight;
+    let parts = state.parts();
+    let from = parts.pos.get(id).0;
+    let is_agent = parts.agent.get_opt(id).is_some();
+    let blocker_weight = parts.blocker.get(id).weight;
     let to = if strength.can_push(blocker_weight) {
-        Dir::get_neighbor_pos(command.pos, dir)
+        Dir::get_neighbor_pos(from, dir)
     } else {
         from
     };
-    if to == from || state.map().is_inboard(to) && !state::is_tile_blocked(state, to) {
-        let effect = effect::Knockback { from, to, strength }.into();
-        context.instant_effects.push((id, vec![effect]));
-        context.moved_actor_ids.push(id);
+    let mut effects = Vec::new();
+    let mut crushed_id = None;
+    if to == from || state::fits_at(state, id, to) {
+        effects.push(effect::Knockback { from, to, strength }.into());
+        effects.extend(drown(state, id, to));
+        if is_agent {
+            context.moved_actor_ids.push(id);
+        }
+    } else if !is_agent {
+        crushed_id = state::agent_id_at_opt(state, to);
+        if crushed_id.is_some() {
+            let to = from;
+            effects.push(effect::Knockback { from, to, strength }.into());
+        }
+    }
+    context.instant_effects.push((id, effects));
+    if let Some(crushed_id) = crushed_id {
+        let effect = wound_or_kill(state, crushed_id, Strength(1));
+        context.instant_effects.push((crushed_id, vec![effect]));
     }
-    context.actor_ids.push(id);
     context
 }
 
-fn execute_use_ability_club(state: &mut State, command: &command::UseAbility) -> ExecuteContext {
-    let mut context = ExecuteContext::default();
+fn execute_use_ability_knockback(
+    state: &mut State,
+    command: &command::UseAbility,
+    strength: PushStrength,
+) -> ExecuteContext {
     let id = state::blocker_id_at(state, command.pos);
-    if state.parts().belongs_to.get_opt(id).is_some() {
-        let owner = state.parts().belongs_to.get(id).0;
-        let phase = Phase::from_player_id(owner);
-        let effect = effect::Timed {
-            duration: effect::Duration::Rounds(1.into()),
-            phase,
-            effect: effect::Lasting::Stun,
-        };
-        context.timed_effects.push((id, vec![effect]));
-        extend_or_crate_sub_vec(&mut context.instant_effects, id, vec![Effect::Stun]);
-    }
+    let actor_pos = state.parts().pos.get(command.id).0;
+    let dir = Dir::get_dir_from_to(actor_pos, command.pos);
+    let mut context = knockback(state, id, dir, strength);
+    context
+        .instant_effects
+        .retain(|(_, effects)| !effects.is_empty());
     context.actor_ids.push(id);
     context
 }
@@ -752,19 +1677,85 @@ fn execute_use_ability_dash(_: &mut State, command: &command::UseAbility) -> Exe
     context
 }
 
-fn execute_use_ability_rage(_: &mut State, _: &command::UseAbility) -> ExecuteContext {
-    ExecuteContext::default()
+/// How much strength the agent's ability restores, `None` for the non-healing abilities.
+pub fn heal_strength(state: &State, id: Id, ability: Ability) -> Option<Strength> {
+    let definition = state.ability_definitions().get(ability)?;
+    if !definition.effects.contains(&AbilityEffect::Heal) {
+        return None;
+    }
+    Some(Strength(state::ability_params(state, id, ability).power))
 }
 
-fn execute_use_ability_heal(
+/// Only the objects that belong to some side can have lasting effects.
+fn cast_lasting_effect(
+    state: &State,
+    id: Id,
+    effect: effect::Lasting,
+    rounds: Rounds,
+) -> ExecuteContext {
+    let mut context = ExecuteContext::default();
+    let owner = match state.parts().belongs_to.get_opt(id) {
+        Some(belongs_to) => belongs_to.0,
+        None => return context,
+    };
+    let timed = effect::Timed {
+        duration: effect::Duration::Rounds(rounds),
+        phase: Phase::from_player_id(owner),
+        effect,
+    };
+    context.timed_effects.push((id, vec![timed]));
+    if effect == effect::Lasting::Stun {
+        context.instant_effects.push((id, vec![Effect::Stun]));
+    }
+    context
+}
+
+fn execute_use_ability_definition(
     state: &mut State,
     command: &command::UseAbility,
-    strength: Strength,
+    definition: &Definition,
 ) -> ExecuteContext {
     let mut context = ExecuteContext::default();
-    let id = state::blocker_id_at(state, command.pos);
-    let effect = effect::Heal { strength }.into();
-    context.instant_effects.push((id, vec![effect]));
+    let id = match definition.target {
+        Some(Target::Itself) => command.id,
+        // Only the created objects are affected.
+        Some(Target::Tile) => {
+            for effect in &definition.effects {
+                if let AbilityEffect::Create(prototype) = effect {
+                    let owner = state.player_id();
+                    let effect_create = effect_create_agent(state, prototype, owner, command.pos);
+                    let id = state.alloc_id();
+                    context.instant_effects.push((id, vec![effect_create]));
+                    context.moved_actor_ids.push(id);
+                }
+            }
+            return context;
+        }
+        _ => state::agent_id_at_opt(state, command.pos).expect("No target agent"),
+    };
+    let power = Strength(state::ability_params(state, command.id, command.ability).power);
+    for effect in &definition.effects {
+        match *effect {
+            AbilityEffect::Heal => {
+                let effect = effect::Heal { strength: power }.into();
+                extend_or_crate_sub_vec(&mut context.instant_effects, id, vec![effect]);
+            }
+            AbilityEffect::Wound => {
+                let damage = correct_damage_with_resistance(state, id, power, DamageType::Magic);
+                let effect = wound_or_kill(state, id, damage);
+                extend_or_crate_sub_vec(&mut context.instant_effects, id, vec![effect]);
+            }
+            AbilityEffect::Lasting { effect, rounds } => {
+                context.merge_with(cast_lasting_effect(state, id, effect, rounds));
+            }
+            // The attacks are added when the event is applied
+            // and there's no tile to create an object on.
+            AbilityEffect::Attacks(_) | AbilityEffect::Create(_) => {}
+        }
+    }
+    if id != command.id {
+        context.actor_ids.push(id);
+    }
     context
 }
 
@@ -776,6 +1767,13 @@ fn execute_use_ability_vanish(state: &mut State, command: &command::UseAbility)
     context
 }
 
+fn execute_use_ability_defuse(state: &mut State, command: &command::UseAbility) -> ExecuteContext {
+    let mut context = ExecuteContext::default();
+    let id = state::blocker_id_at(state, command.pos);
+    context.instant_effects.push((id, vec![Effect::Vanish]));
+    context
+}
+
 fn execute_use_ability_explode_poison(
     state: &mut State,
     command: &command::UseAbility,
@@ -804,6 +1802,16 @@ fn correct_damage_with_armor(
     battle::Strength(utils::clamp_min(damage.0 - armor.0, 0))
 }
 
+fn correct_damage_with_resistance(
+    state: &State,
+    target_id: Id,
+    damage: battle::Strength,
+    damage_type: DamageType,
+) -> battle::Strength {
+    let resistance = state::get_resistance(state, target_id, damage_type);
+    battle::Strength(utils::clamp_min(damage.0 - resistance.0, 0))
+}
+
 fn wound_or_kill(state: &State, id: Id, damage: battle::Strength) -> Effect {
     let armor_break = battle::Strength(0);
     wound_break_kill(state, id, damage, armor_break)
@@ -831,36 +1839,138 @@ fn wound_break_kill(
         }
         .into()
     } else {
-        effect::Kill { attacker_pos }.into()
+        effect::Kill {
+            attacker_pos,
+            is_drowned: false,
+        }
+        .into()
+    }
+}
+
+/// Every number that goes into an attack, so that the result can be explained.
+///
+/// The roll must be below `hit_chance().0` for the full damage,
+/// every point above that takes one point of damage off
+/// and a roll above `hit_chance().1` is a total miss.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AttackTerms {
+    /// The attacker's own strength of the attack.
+    pub base_damage: Strength,
+
+    /// Extra damage of an attack from the rear arc of the target.
+    pub backstab_bonus: Strength,
+
+    /// Extra damage from the auras of the attacker's allies around it.
+    pub aura_bonus: Strength,
+
+    pub accuracy: battle::Accuracy,
+
+    /// Terrain objects never dodge.
+    pub target_dodge: battle::Dodge,
+
+    /// The attacker's lost strength points make it less accurate, at most by 3.
+    pub wounds_penalty: i32,
+
+    /// An injured attacker stays less accurate till the end of the battle.
+    pub injury_penalty: i32,
+
+    /// Absorbs some of the damage of every hit.
+    pub target_armor: Strength,
+
+    /// Absorbs some of the damage of the hits of the attacker's weapon type.
+    pub target_resistance: Strength,
+
+    /// How much of the target's armor a hit destroys.
+    pub armor_break: Strength,
+}
+
+impl AttackTerms {
+    pub fn damage(&self) -> Strength {
+        Strength(self.base_damage.0 + self.backstab_bonus.0 + self.aura_bonus.0)
+    }
+
+    pub fn hit_chance(&self) -> (i32, i32) {
+        let k_min =
+            self.accuracy.0 - self.target_dodge.0 - self.wounds_penalty - self.injury_penalty;
+        let k_max = k_min + self.damage().0;
+        (k_min, k_max)
+    }
+
+    /// The strength that a hit with the (already modified) roll takes off
+    /// after the armor, `None` if the roll misses.
+    pub fn roll_damage(&self, roll: i32) -> Option<Strength> {
+        let (_, k_max) = self.hit_chance();
+        let damage_raw = k_max - roll;
+        if damage_raw < 0 {
+            // That was a total miss
+            return None;
+        }
+        let damage = utils::clamp(damage_raw, 0, self.damage().0);
+        let absorbed = self.target_armor.0 + self.target_resistance.0;
+        Some(Strength(utils::clamp_min(damage - absorbed, 0)))
     }
 }
 
 // TODO: Return a `Result` or an `Option` (check that attack is possible at all?).
-// TODO: Return a struct with named fields.
-// TODO: Move to some other module.
-pub fn hit_chance(state: &State, attacker_id: Id, target_id: Id) -> (i32, i32) {
+pub fn attack_terms(state: &State, attacker_id: Id, target_id: Id) -> AttackTerms {
     let parts = state.parts();
-    let agent_target = parts.agent.get(target_id);
     let agent_attacker = parts.agent.get(attacker_id);
-    let attacker_strength = parts.strength.get(attacker_id).strength;
-    let attacker_base_strength = parts.strength.get(attacker_id).base_strength;
-    let attacker_wounds = utils::clamp_max(attacker_base_strength.0 - attacker_strength.0, 3);
-    let target_dodge = agent_target.dodge;
-    let attack_accuracy = agent_attacker.attack_accuracy;
-    let attack_strength = agent_attacker.attack_strength;
-    let k_min = attack_accuracy.0 - target_dodge.0 - attacker_wounds;
-    let k_max = k_min + attack_strength.0;
-    (k_min, k_max)
-}
-
-fn try_attack(state: &State, attacker_id: Id, target_id: Id) -> Option<Effect> {
-    let parts = state.parts();
-    let agent_attacker = state.parts().agent.get(attacker_id);
-    let target_strength = parts.strength.get(target_id).strength;
+    let attacker_strength = parts.strength.get(attacker_id);
+    let wounds = attacker_strength.base_strength.0 - attacker_strength.strength.0;
+    let backstab_bonus = if state::is_backstab(state, attacker_id, target_id) {
+        BACKSTAB_DAMAGE
+    } else {
+        Strength(0)
+    };
+    let target_dodge = parts
+        .agent
+        .get_opt(target_id)
+        .map_or(battle::Dodge(0), |agent| agent.dodge);
     let target_armor = state::get_armor(state, target_id);
-    let attack_strength = agent_attacker.attack_strength;
-    let attacker_pos = Some(state.parts().pos.get(attacker_id).0);
-    let (k_min, k_max) = hit_chance(state, attacker_id, target_id);
+    let damage_type = agent_attacker.weapon_type.damage_type();
+    AttackTerms {
+        base_damage: agent_attacker.attack_strength,
+        backstab_bonus,
+        aura_bonus: state::aura_attack_bonus(state, attacker_id),
+        accuracy: agent_attacker.attack_accuracy,
+        target_dodge,
+        wounds_penalty: utils::clamp_max(wounds, 3),
+        injury_penalty: if state::is_injured(state, attacker_id) {
+            INJURY_ACCURACY_PENALTY
+        } else {
+            0
+        },
+        target_armor,
+        target_resistance: state::get_resistance(state, target_id, damage_type),
+        armor_break: utils::clamp_max(agent_attacker.attack_break, target_armor),
+    }
+}
+
+/// A counter-attack hits with only a half of the strength, rounded up.
+pub fn counter_attack_terms(state: &State, attacker_id: Id, target_id: Id) -> AttackTerms {
+    let mut terms = attack_terms(state, attacker_id, target_id);
+    terms.base_damage = Strength((terms.base_damage.0 + 1) / 2);
+    terms
+}
+
+pub fn hit_chance(state: &State, attacker_id: Id, target_id: Id) -> (i32, i32) {
+    attack_terms(state, attacker_id, target_id).hit_chance()
+}
+
+fn try_attack(
+    state: &mut State,
+    attacker_id: Id,
+    target_id: Id,
+    mode: event::AttackMode,
+) -> Option<Effect> {
+    let terms = match mode {
+        event::AttackMode::Counter => counter_attack_terms(state, attacker_id, target_id),
+        event::AttackMode::Active | event::AttackMode::Reactive => {
+            attack_terms(state, attacker_id, target_id)
+        }
+    };
+    let parts = state.parts();
+    let (k_min, k_max) = terms.hit_chance();
     if state.deterministic_mode() {
         // I want to be sure that I either will totally miss
         // or that I'll surely hit the target at a full force.
@@ -872,24 +1982,41 @@ fn try_attack(state: &State, attacker_id: Id, target_id: Id) -> Option<Effect> {
             (k_min, k_max)
         );
     }
-    let r = roll_dice(0, 11);
-    let damage_raw = Strength(k_max - r);
-    let damage = Strength(utils::clamp(damage_raw.0, 0, attack_strength.0));
-    if damage_raw < Strength(0) {
-        // That was a total miss
-        return None;
-    }
-    let damage = correct_damage_with_armor(state, target_id, damage);
-    let attack_break = utils::clamp_max(agent_attacker.attack_break, target_armor);
+    let attacker_player_id = parts.belongs_to.get(attacker_id).0;
+    let roll = dice::roll_attack(state, attacker_player_id);
+    hit_effect(state, attacker_id, target_id, &terms, roll)
+}
+
+/// The effect of an attack with the given (already modified) roll, `None` if it misses.
+pub fn attack_effect(state: &State, attacker_id: Id, target_id: Id, roll: i32) -> Option<Effect> {
+    let terms = attack_terms(state, attacker_id, target_id);
+    hit_effect(state, attacker_id, target_id, &terms, roll)
+}
+
+fn hit_effect(
+    state: &State,
+    attacker_id: Id,
+    target_id: Id,
+    terms: &AttackTerms,
+    roll: i32,
+) -> Option<Effect> {
+    let parts = state.parts();
+    let target_strength = parts.strength.get(target_id).strength;
+    let attacker_pos = Some(parts.pos.get(attacker_id).0);
+    let damage = terms.roll_damage(roll)?;
     let effect = if target_strength > damage {
         effect::Wound {
             damage,
-            armor_break: attack_break,
+            armor_break: terms.armor_break,
             attacker_pos,
         }
         .into()
     } else {
-        effect::Kill { attacker_pos }.into()
+        effect::Kill {
+            attacker_pos,
+            is_drowned: false,
+        }
+        .into()
     };
     Some(effect)
 }
@@ -900,9 +2027,8 @@ fn execute_use_ability_explode_damage(
 ) -> ExecuteContext {
     let mut context = ExecuteContext::default();
     let from = state.parts().pos.get(command.id).0;
-    for id in state.parts().agent.ids() {
-        let pos = state.parts().pos.get(id).0;
-        let distance = map::distance_hex(from, pos);
+    for id in state.parts().strength.ids() {
+        let distance = state::distance_to(state, id, from);
         if distance.0 > 1 || command.id == id {
             continue;
         }
@@ -925,31 +2051,17 @@ fn execute_use_ability_explode_push(
     let mut context = ExecuteContext::default();
     let from = state.parts().pos.get(command.id).0;
     for id in state.parts().blocker.ids() {
-        let pos = state.parts().pos.get(id).0;
-        let distance = map::distance_hex(from, pos);
+        let distance = state::distance_to(state, id, from);
         if distance.0 > 1 || command.id == id {
             continue;
         }
-        let blocker_weight = state.parts().blocker.get(id).weight;
+        let pos = state::object_tiles(state, id)
+            .into_iter()
+            .min_by_key(|&pos| map::distance_hex(from, pos))
+            .expect("Objects take at least one tile");
         let dir = Dir::get_dir_from_to(from, pos);
-        let to = if PushStrength(Weight::Normal).can_push(blocker_weight) {
-            Dir::get_neighbor_pos(pos, dir)
-        } else {
-            pos
-        };
-        let mut effects = Vec::new();
-        if to == pos || (state.map().is_inboard(to) && !state::is_tile_blocked(state, to)) {
-            let effect = effect::Knockback {
-                from: pos,
-                to,
-                strength: PushStrength(Weight::Normal),
-            };
-            effects.push(effect.into());
-            if state.parts().agent.get_opt(id).is_some() {
-                context.moved_actor_ids.push(id);
-            }
-        }
-        context.instant_effects.push((id, effects));
+        let strength = PushStrength(Weight::Normal);
+        context.merge_with(knockback(state, id, dir, strength));
     }
     assert!(!any_effect_with_id(&context.instant_effects, command.id));
     let effects = vec![Effect::Vanish];
@@ -957,21 +2069,6 @@ fn execute_use_ability_explode_push(
     context
 }
 
-fn execute_use_ability_poison(state: &mut State, command: &command::UseAbility) -> ExecuteContext {
-    let mut context = ExecuteContext::default();
-    let id = state::blocker_id_at(state, command.pos);
-    let owner = state.parts().belongs_to.get(id).0;
-    let phase = Phase::from_player_id(owner);
-    let effect = effect::Timed {
-        duration: effect::Duration::Rounds(2.into()),
-        phase,
-        effect: effect::Lasting::Poison,
-    };
-    context.timed_effects.push((id, vec![effect]));
-    context.actor_ids.push(id);
-    context
-}
-
 fn effect_create_object(state: &State, prototype: &ObjType, pos: PosHex) -> Effect {
     let name = prototype.clone();
     let mut components = state.prototype_for(prototype);
@@ -1016,11 +2113,17 @@ fn throw_bomb(
 ) -> ExecuteContext {
     let mut context = ExecuteContext::default();
     let pos = state.parts().pos.get(command.id).0;
+    let delivery = state::bomb_delivery(state, command.ability);
+    let to = match delivery {
+        Delivery::Thrown => windblown_pos(state, command.pos),
+        Delivery::Rolled => state::rolled_bomb_pos(state, pos, command.pos).expect("Bad roll"),
+    };
     let effect_create = effect_create_object(state, prototype, pos);
     let id = state.alloc_id();
     let effect_throw = effect::Throw {
         from: pos,
-        to: command.pos,
+        to,
+        is_rolled: delivery == Delivery::Rolled,
     }
     .into();
     let effects = vec![effect_create, effect_throw];
@@ -1083,13 +2186,17 @@ fn execute_use_ability_bomb_demonic(
 
 fn execute_use_ability_summon(state: &mut State, command: &command::UseAbility) -> ExecuteContext {
     let mut context = ExecuteContext::default();
-    let max_summoned_count = state.parts().summoner.get(command.id).count;
+    let max_summoned_count = state::summon_count(state, command.id);
     let available_typenames = &["imp".into(), "toxic_imp".into(), "imp_bomber".into()];
     let existing_agents = existing_agent_typenames(state, state.player_id());
     let mut new_agents = Vec::new();
     for pos in state::free_neighbor_positions(state, command.pos, max_summoned_count as _) {
         let prototype = choose_who_to_summon(&existing_agents, &new_agents, available_typenames);
-        let effect_create = effect_create_agent(state, &prototype, state.player_id(), pos);
+        let mut effect_create = effect_create_agent(state, &prototype, state.player_id(), pos);
+        if let Effect::Create(ref mut effect) = effect_create {
+            let summoned_by = component::SummonedBy(command.id);
+            effect.components.push(summoned_by.into());
+        }
         let id = state.alloc_id();
         let effects = vec![effect_create, Effect::Stun];
         new_agents.push(prototype);
@@ -1100,49 +2207,82 @@ fn execute_use_ability_summon(state: &mut State, command: &command::UseAbility)
     context
 }
 
-fn execute_use_ability_bloodlust(
-    state: &mut State,
-    command: &command::UseAbility,
-) -> ExecuteContext {
+/// The corpse turns into a skeleton that serves the raiser.
+fn execute_use_ability_raise(state: &mut State, command: &command::UseAbility) -> ExecuteContext {
     let mut context = ExecuteContext::default();
-    let id = state::blocker_id_at(state, command.pos);
-    if state.parts().belongs_to.get_opt(id).is_some() {
-        let owner = state.parts().belongs_to.get(id).0;
-        let phase = Phase::from_player_id(owner);
-        let effect = effect::Timed {
-            duration: effect::Duration::Rounds(3.into()),
-            phase,
-            effect: effect::Lasting::Bloodlust,
-        };
-        context.timed_effects.push((id, vec![effect]));
+    let corpse_id = state::corpse_id_at_opt(state, command.pos).expect("No corpse to raise");
+    context
+        .instant_effects
+        .push((corpse_id, vec![Effect::Vanish]));
+    let prototype = "skeleton".into();
+    let mut effect_create = effect_create_agent(state, &prototype, state.player_id(), command.pos);
+    if let Effect::Create(ref mut effect) = effect_create {
+        let summoned_by = component::SummonedBy(command.id);
+        effect.components.push(summoned_by.into());
     }
-    context.actor_ids.push(id);
+    let id = state.alloc_id();
+    context
+        .instant_effects
+        .push((id, vec![effect_create, Effect::Stun]));
+    context.moved_actor_ids.push(id);
+    context.reaction_attack_targets.push(id);
     context
 }
 
-fn execute_use_ability(state: &mut State, cb: Cb, command: &command::UseAbility) {
-    let mut context = match command.ability {
-        Ability::Knockback => execute_use_ability_knockback(state, command),
-        Ability::Club => execute_use_ability_club(state, command),
+fn execute_use_ability_built_in(
+    state: &mut State,
+    command: &command::UseAbility,
+) -> ExecuteContext {
+    match command.ability {
+        Ability::Knockback => {
+            execute_use_ability_knockback(state, command, PushStrength(Weight::Normal))
+        }
+        Ability::Shove => {
+            execute_use_ability_knockback(state, command, PushStrength(Weight::Heavy))
+        }
         Ability::Jump => execute_use_ability_jump(state, command),
         Ability::LongJump => execute_use_ability_long_jump(state, command),
         Ability::Dash => execute_use_ability_dash(state, command),
-        Ability::Rage => execute_use_ability_rage(state, command),
-        Ability::Heal => execute_use_ability_heal(state, command, Strength(2)),
-        Ability::GreatHeal => execute_use_ability_heal(state, command, Strength(3)),
         Ability::Vanish => execute_use_ability_vanish(state, command),
+        Ability::Defuse => execute_use_ability_defuse(state, command),
         Ability::ExplodeFire => execute_use_ability_explode_fire(state, command),
         Ability::ExplodePoison => execute_use_ability_explode_poison(state, command),
         Ability::ExplodePush => execute_use_ability_explode_push(state, command),
         Ability::ExplodeDamage => execute_use_ability_explode_damage(state, command),
-        Ability::Poison => execute_use_ability_poison(state, command),
         Ability::Bomb => execute_use_ability_bomb_damage(state, command),
         Ability::BombPush => execute_use_ability_bomb_push(state, command),
         Ability::BombFire => execute_use_ability_bomb_fire(state, command),
         Ability::BombPoison => execute_use_ability_bomb_poison(state, command),
         Ability::BombDemonic => execute_use_ability_bomb_demonic(state, command),
         Ability::Summon => execute_use_ability_summon(state, command),
-        Ability::Bloodlust => execute_use_ability_bloodlust(state, command),
+        Ability::Raise => execute_use_ability_raise(state, command),
+        Ability::Club
+        | Ability::Poison
+        | Ability::Rage
+        | Ability::Heal
+        | Ability::Bloodlust
+        | Ability::Totem
+        | Ability::Barricade
+        | Ability::Phase
+        | Ability::Hide => {
+            panic!("{:?} has no definition", command.ability)
+        }
+    }
+}
+
+fn execute_use_ability(state: &mut State, cb: Cb, command: &command::UseAbility) {
+    // Only the abilities used on the agent's own tiles keep it hidden.
+    if !state::occupies(state, command.id, command.pos) {
+        try_execute_reveal(state, cb, command.id);
+    }
+    let definition = state
+        .ability_definitions()
+        .get(command.ability)
+        .filter(|definition| definition.target.is_some())
+        .cloned();
+    let mut context = match definition {
+        Some(definition) => execute_use_ability_definition(state, command, &definition),
+        None => execute_use_ability_built_in(state, command),
     };
     context.actor_ids.push(command.id);
     let active_event = event::UseAbility {
@@ -1160,6 +2300,7 @@ fn execute_use_ability(state: &mut State, cb: Cb, command: &command::UseAbility)
     };
     do_event(state, cb, &event);
     for id in context.moved_actor_ids {
+        try_execute_reveals_around(state, cb, id);
         try_execute_passive_abilities_on_move(state, cb, id);
     }
     for id in context.reaction_attack_targets {
@@ -1170,6 +2311,75 @@ fn execute_use_ability(state: &mut State, cb: Cb, command: &command::UseAbility)
     }
 }
 
+fn orphaned_summons(state: &State) -> Vec<(Id, Vec<Id>)> {
+    let mut orphans: HashMap<Id, Vec<Id>> = HashMap::new();
+    for id in state.parts().summoned_by.ids() {
+        let summoner_id = state.parts().summoned_by.get(id).0;
+        if !state.parts().is_exist(summoner_id) {
+            orphans.entry(summoner_id).or_default().push(id);
+        }
+    }
+    let mut orphans: Vec<_> = orphans.into_iter().collect();
+    orphans.sort_by_key(|&(summoner_id, _)| summoner_id);
+    orphans
+}
+
+fn try_execute_unsummon(state: &mut State, cb: Cb) {
+    // Summoned creatures can be summoners themselves, so repeat until
+    // there're no orphaned creatures left.
+    loop {
+        let orphans = orphaned_summons(state);
+        if orphans.is_empty() {
+            break;
+        }
+        for (summoner_id, ids) in orphans {
+            let kill = || {
+                effect::Kill {
+                    attacker_pos: None,
+                    is_drowned: false,
+                }
+                .into()
+            };
+            let instant_effects = ids.into_iter().map(|id| (id, vec![kill()])).collect();
+            let event = Event {
+                active_event: event::Unsummon { summoner_id }.into(),
+                actor_ids: Vec::new(),
+                instant_effects,
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            };
+            do_event(state, cb, &event);
+        }
+    }
+}
+
+/// Starts the next phases of the weakened bosses, one event per phase.
+fn try_execute_boss_phases(state: &mut State, cb: Cb) {
+    for id in state.parts().boss.ids_collected() {
+        loop {
+            let parts = state.parts();
+            let boss = parts.boss.get(id);
+            let phase = boss.phases_passed;
+            let strength = parts.strength.get(id).strength;
+            let is_ready = boss
+                .phases
+                .get(phase)
+                .is_some_and(|next| strength <= next.strength);
+            if !is_ready {
+                break;
+            }
+            let event = Event {
+                active_event: event::BeginBossPhase { id, phase }.into(),
+                actor_ids: vec![id],
+                instant_effects: Vec::new(),
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            };
+            do_event(state, cb, &event);
+        }
+    }
+}
+
 fn existing_agent_typenames(state: &State, player_id: PlayerId) -> Vec<ObjType> {
     let mut existing_agents = Vec::new();
     for id in state::players_agent_ids(state, player_id) {
@@ -1185,21 +2395,13 @@ fn choose_who_to_summon(
     available_typenames: &[ObjType],
 ) -> ObjType {
     assert!(!available_typenames.is_empty());
-    let agents = existing_agents.iter().chain(new_agents);
-    let mut map = HashMap::new();
-    for typename in available_typenames {
-        map.insert(typename, 0);
-    }
-    for typename in agents {
-        if let Some(count) = map.get_mut(typename) {
-            *count += 1;
-        }
-    }
-    let (key, _value) = map
-        .into_iter()
-        .min_by_key(|&(_key, value)| value)
-        .expect("The map can't be empty");
-    key.clone()
+    let agents: Vec<_> = existing_agents.iter().chain(new_agents).collect();
+    // The ties go to the first of the typenames, so the replays summon the same agents.
+    available_typenames
+        .iter()
+        .min_by_key(|&typename| agents.iter().filter(|&&agent| agent == typename).count())
+        .expect("The list can't be empty")
+        .clone()
 }
 
 #[cfg(test)]
@@ -1240,6 +2442,7 @@ mod tests {
         let attacker_pos = PosHex { q: 0, r: 0 };
         let effect_kill: Effect = effect::Kill {
             attacker_pos: Some(attacker_pos),
+            is_drowned: false,
         }
         .into();
         instant_effects1.push((Id(0), vec![effect_kill.clone(), Effect::Stun]));
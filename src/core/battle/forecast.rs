@@ -0,0 +1,204 @@
+//! Predicted outcomes of the commands, used by the GUI previews.
+//!
+//! Nothing here changes the state or touches the RNG.
+
+use crate::core::battle::{
+    check,
+    command::{self, Command},
+    dice,
+    effect::{self, Effect},
+    execute, state, Id, State, Strength,
+};
+
+/// One of the possible results of an attack roll.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outcome {
+    pub is_miss: bool,
+
+    /// Strength the target loses. A killed target loses all of it.
+    pub damage: Strength,
+
+    pub is_kill: bool,
+
+    /// In `0.0..=1.0`.
+    pub probability: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackForecast {
+    pub attacker_id: Id,
+    pub target_id: Id,
+
+    /// What the outcomes are calculated from.
+    pub terms: execute::AttackTerms,
+
+    /// The chance that the attack happens at all: a target can only
+    /// strike back if it survives.
+    pub chance: f32,
+
+    /// All possible outcomes, the misses first, then by damage.
+    pub outcomes: Vec<Outcome>,
+
+    /// Reaction attacks that the attacker is going to face afterwards.
+    pub retaliation: Vec<AttackForecast>,
+}
+
+impl AttackForecast {
+    fn probability(&self, f: impl Fn(&Outcome) -> bool) -> f32 {
+        let sum: f32 = self
+            .outcomes
+            .iter()
+            .filter(|o| f(o))
+            .map(|o| o.probability)
+            .sum();
+        sum * self.chance
+    }
+
+    pub fn hit_chance(&self) -> f32 {
+        self.probability(|o| !o.is_miss)
+    }
+
+    pub fn kill_chance(&self) -> f32 {
+        self.probability(|o| o.is_kill)
+    }
+
+    /// The average strength that the target loses.
+    pub fn expected_damage(&self) -> f32 {
+        let sum: f32 = self
+            .outcomes
+            .iter()
+            .map(|o| o.damage.0 as f32 * o.probability)
+            .sum();
+        sum * self.chance
+    }
+
+    /// The smallest and the biggest damage of a hit, `None` if it can't hit at all.
+    pub fn damage_range(&self) -> Option<(Strength, Strength)> {
+        let hits = self.outcomes.iter().filter(|o| !o.is_miss);
+        let min = hits.clone().map(|o| o.damage.0).min()?;
+        let max = hits.map(|o| o.damage.0).max()?;
+        Some((Strength(min), Strength(max)))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HealForecast {
+    pub target_id: Id,
+
+    /// The strength that is actually restored, never above the base one.
+    pub strength: Strength,
+
+    /// Lasting effects that the healing removes.
+    pub removed_effects: Vec<effect::Lasting>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Forecast {
+    Attack(AttackForecast),
+    Heal(HealForecast),
+}
+
+/// Possible outcomes of the command.
+///
+/// Returns `None` if the command is illegal or there's nothing to predict.
+pub fn forecast(state: &State, command: &Command) -> Option<Forecast> {
+    if check(state, command).is_err() {
+        return None;
+    }
+    match command {
+        Command::Attack(command) => {
+            let mut forecast = forecast_attack(state, command);
+            forecast.retaliation = retaliation(state, &forecast);
+            Some(Forecast::Attack(forecast))
+        }
+        Command::UseAbility(command) => forecast_heal(state, command).map(Forecast::Heal),
+        _ => None,
+    }
+}
+
+fn forecast_attack(state: &State, command: &command::Attack) -> AttackForecast {
+    AttackForecast {
+        attacker_id: command.attacker_id,
+        target_id: command.target_id,
+        terms: execute::attack_terms(state, command.attacker_id, command.target_id),
+        chance: 1.0,
+        outcomes: attack_outcomes(state, command.attacker_id, command.target_id),
+        retaliation: Vec::new(),
+    }
+}
+
+fn attack_outcomes(state: &State, attacker_id: Id, target_id: Id) -> Vec<Outcome> {
+    let player_id = state.parts().belongs_to.get(attacker_id).0;
+    let target_strength = state.parts().strength.get(target_id).strength;
+    let probability = 1.0 / dice::ROLL_SIDES as f32;
+    let mut outcomes: Vec<Outcome> = Vec::new();
+    for roll in 0..dice::ROLL_SIDES {
+        let roll = dice::modify_roll(state, player_id, roll);
+        let (is_miss, damage, is_kill) =
+            match execute::attack_effect(state, attacker_id, target_id, roll) {
+                None => (true, Strength(0), false),
+                Some(Effect::Wound(wound)) => (false, wound.damage, false),
+                Some(Effect::Kill(_)) => (false, target_strength, true),
+                Some(effect) => panic!("Unexpected attack effect: {:?}", effect),
+            };
+        let same =
+            |o: &&mut Outcome| o.is_miss == is_miss && o.damage == damage && o.is_kill == is_kill;
+        match outcomes.iter_mut().find(same) {
+            Some(outcome) => outcome.probability += probability,
+            None => outcomes.push(Outcome {
+                is_miss,
+                damage,
+                is_kill,
+                probability,
+            }),
+        }
+    }
+    outcomes.sort_by_key(|o| (!o.is_miss, o.damage.0));
+    outcomes
+}
+
+/// Reaction attacks of the attacker's enemies, see `execute::try_execute_reaction_attacks`.
+fn retaliation(state: &State, attack: &AttackForecast) -> Vec<AttackForecast> {
+    let attacker_id = attack.attacker_id;
+    let attacker_owner = state.parts().belongs_to.get(attacker_id).0;
+    // Reactions are checked from their owners' point of view,
+    // so the player is switched on a copy.
+    let mut state = state.clone();
+    let mut forecasts = Vec::new();
+    let mut ids = state::visible_enemy_agent_ids(&state, attacker_owner);
+    ids.sort();
+    for id in ids {
+        let command = command::Attack {
+            attacker_id: id,
+            target_id: attacker_id,
+        };
+        state.set_player_id(state.parts().belongs_to.get(id).0);
+        if check(&state, &command.clone().into()).is_err() {
+            continue;
+        }
+        let mut forecast = forecast_attack(&state, &command);
+        if id == attack.target_id {
+            forecast.chance = 1.0 - attack.kill_chance();
+        }
+        forecasts.push(forecast);
+    }
+    forecasts
+}
+
+fn forecast_heal(state: &State, command: &command::UseAbility) -> Option<HealForecast> {
+    let strength = execute::heal_strength(state, command.id, command.ability)?;
+    let target_id = state::blocker_id_at(state, command.pos);
+    let target = state.parts().strength.get(target_id);
+    let missing = target.base_strength.0 - target.strength.0;
+    let removed_effects = state
+        .lasting_effects(target_id)
+        .into_iter()
+        .map(|status| status.effect)
+        .filter(|&effect| effect != effect::Lasting::Injured)
+        .collect();
+    Some(HealForecast {
+        target_id,
+        strength: Strength(strength.0.min(missing)),
+        removed_effects,
+    })
+}
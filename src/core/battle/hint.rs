@@ -0,0 +1,65 @@
+use crate::core::battle::{
+    ability::Ability,
+    check,
+    command::{self, Command},
+    movement::Pathfinder,
+    state, Id, State,
+};
+
+/// Returns some legal commands for the agent, the most useful ones first.
+/// Used to help a player that looks stuck.
+///
+/// The list is never empty during the agent owner's turn:
+/// ending the turn is always possible.
+pub fn legal_commands(state: &State, id: Id) -> Vec<Command> {
+    let mut commands = Vec::new();
+    let player_id = state.parts().belongs_to.get(id).0;
+    if player_id != state.player_id() {
+        return commands;
+    }
+    for target_id in state::enemy_agent_ids(state, player_id) {
+        let command = command::Attack {
+            attacker_id: id,
+            target_id,
+        }
+        .into();
+        if check(state, &command).is_ok() {
+            commands.push(command);
+        }
+    }
+    for ability in abilities(state, id) {
+        let command = state
+            .map()
+            .iter()
+            .map(|pos| command::UseAbility { id, pos, ability }.into())
+            .find(|command| check(state, command).is_ok());
+        if let Some(command) = command {
+            commands.push(command);
+        }
+    }
+    if let Some(command) = longest_move(state, id) {
+        commands.push(command);
+    }
+    commands.push(command::EndTurn.into());
+    commands
+}
+
+fn abilities(state: &State, id: Id) -> Vec<Ability> {
+    match state.parts().abilities.get_opt(id) {
+        Some(abilities) => abilities.0.iter().map(|r| r.ability).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn longest_move(state: &State, id: Id) -> Option<Command> {
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    pathfinder.fill_map(state, id);
+    state
+        .map()
+        .iter()
+        .filter_map(|pos| pathfinder.path(pos))
+        .map(|path| command::MoveTo { id, path })
+        .filter(|command| check(state, &command.clone().into()).is_ok())
+        .max_by_key(|command| command.path.tiles().len())
+        .map(Into::into)
+}
@@ -0,0 +1,93 @@
+//! Everything that has been done in a battle, enough to play it again from the start.
+
+use std::{iter, mem, sync::Arc};
+
+use crate::core::battle::{
+    command::Command,
+    event::{ActiveEvent, Event},
+    execute::{execute, Cb},
+    PlayerId, State,
+};
+
+/// One turn of one player.
+#[derive(Clone, Debug)]
+pub struct Turn {
+    pub player_id: PlayerId,
+
+    /// In the order of their execution, the last one may have ended the turn.
+    pub commands: Vec<Command>,
+
+    /// Applied since the turn has begun, its `BeginTurn` event included.
+    pub events: Vec<Event>,
+}
+
+impl Turn {
+    fn new(player_id: PlayerId) -> Self {
+        Self {
+            player_id,
+            commands: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+/// The commands and events of the battle, turn by turn.
+///
+/// The finished turns never change, so they're shared by all the copies
+/// of the state that the AI and the checks of the command batches make.
+#[derive(Clone, Debug)]
+pub struct History {
+    finished_turns: Vec<Arc<Turn>>,
+    current_turn: Turn,
+}
+
+impl History {
+    /// The objects created with the state aren't a part of the history,
+    /// creating the state with the same scenario and seed creates them again.
+    pub fn new(player_id: PlayerId) -> Self {
+        Self {
+            finished_turns: Vec::new(),
+            current_turn: Turn::new(player_id),
+        }
+    }
+
+    /// The oldest first, the last one is the current turn.
+    pub fn turns(&self) -> impl Iterator<Item = &Turn> {
+        self.finished_turns
+            .iter()
+            .map(|turn| turn.as_ref())
+            .chain(iter::once(&self.current_turn))
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        self.turns().flat_map(|turn| turn.commands.iter())
+    }
+
+    pub fn commands_count(&self) -> usize {
+        self.turns().map(|turn| turn.commands.len()).sum()
+    }
+
+    pub(in crate::core) fn add_command(&mut self, command: &Command) {
+        self.current_turn.commands.push(command.clone());
+    }
+
+    pub(in crate::core) fn add_event(&mut self, event: &Event) {
+        if let ActiveEvent::BeginTurn(begin_turn) = &event.active_event {
+            let turn = mem::replace(&mut self.current_turn, Turn::new(begin_turn.player_id));
+            self.finished_turns.push(Arc::new(turn));
+        }
+        self.current_turn.events.push(event.clone());
+    }
+}
+
+/// Plays the battle again from its beginning till `commands` commands have been executed.
+///
+/// The dice are rolled by the state's own generator, so the same commands
+/// of the same battle always lead to the same events.
+pub fn replay(state: &State, commands: usize, cb: Cb) -> State {
+    let mut replayed = state.initial_state(cb);
+    for command in state.history().commands().take(commands) {
+        execute(&mut replayed, command, cb).expect("Can't replay the command");
+    }
+    replayed
+}
@@ -0,0 +1,329 @@
+//! Human-readable lines about the executed events: the battle's history.
+//!
+//! Every line is a template with named arguments like "{attacker} attacks {target}",
+//! so the UI can translate it and the `Display` impls give the plain English text
+//! that can be dumped to a file.
+
+use std::fmt;
+
+use heck::TitleCase;
+
+use crate::core::battle::{
+    ability::{Ability, PassiveAbility},
+    command::JokerUse,
+    component::{Hook, ObjType},
+    effect::{self, Effect},
+    event::{self, ActiveEvent, AttackMode, Event},
+    state, Id, PlayerId, State,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    Object {
+        typename: ObjType,
+        name: Option<String>,
+    },
+    Number(i32),
+    Ability(Ability),
+    PassiveAbility(PassiveAbility),
+    Effect(effect::Lasting),
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::Object {
+                name: Some(name), ..
+            } => write!(f, "{}", name),
+            Arg::Object { typename, .. } => write!(f, "{}", typename.0.to_title_case()),
+            Arg::Number(n) => write!(f, "{}", n),
+            Arg::Ability(ability) => write!(f, "{}", ability.title()),
+            Arg::PassiveAbility(ability) => write!(f, "{}", ability.title()),
+            Arg::Effect(effect) => write!(f, "{}", effect.title()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub template: &'static str,
+    pub args: Vec<(&'static str, Arg)>,
+}
+
+impl Line {
+    fn new(template: &'static str) -> Self {
+        Self {
+            template,
+            args: Vec::new(),
+        }
+    }
+
+    fn arg(mut self, name: &'static str, arg: Arg) -> Self {
+        self.args.push((name, arg));
+        self
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = self.template.to_string();
+        for (name, arg) in &self.args {
+            s = s.replace(&format!("{{{}}}", name), &arg.to_string());
+        }
+        write!(f, "{}", s)
+    }
+}
+
+/// All the lines of a battle, the oldest first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Log {
+    lines: Vec<Line>,
+}
+
+impl Log {
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Must be called in the `ApplyPhase::Pre` phase,
+    /// while the killed and the removed objects are still there.
+    pub fn record(&mut self, state: &State, event: &Event) {
+        self.lines.extend(describe(state, event));
+    }
+}
+
+impl fmt::Display for Log {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+struct Context<'a> {
+    state: &'a State,
+
+    /// The objects that the event creates aren't in the state yet.
+    created: Vec<(Id, &'a ObjType)>,
+}
+
+impl<'a> Context<'a> {
+    fn new(state: &'a State, event: &'a Event) -> Self {
+        let mut created = Vec::new();
+        for (id, effects) in &event.instant_effects {
+            for effect in effects {
+                if let Effect::Create(effect) = effect {
+                    created.push((*id, &effect.prototype));
+                }
+            }
+        }
+        Self { state, created }
+    }
+
+    fn object(&self, id: Id) -> Arg {
+        let parts = self.state.parts();
+        let typename = match parts.meta.get_opt(id) {
+            Some(meta) => meta.name.clone(),
+            None => match self.created.iter().find(|(other_id, _)| *other_id == id) {
+                Some((_, typename)) => (*typename).clone(),
+                None => "object".into(),
+            },
+        };
+        let name = parts.persona.get_opt(id).and_then(|p| p.name.clone());
+        Arg::Object { typename, name }
+    }
+}
+
+fn describe_active_event(context: &Context, event: &ActiveEvent) -> Option<Line> {
+    let line = match event {
+        // The initial objects aren't interesting and the rest is shown by the effects.
+        ActiveEvent::Create
+        | ActiveEvent::EndTurn(_)
+        | ActiveEvent::Unsummon(_)
+        | ActiveEvent::MoraleLoss(_)
+        | ActiveEvent::LeaveCorpse(_)
+        | ActiveEvent::Injury(_)
+        | ActiveEvent::ScriptTriggered(_)
+        | ActiveEvent::SetTiles(_) => return None,
+        // The removed `Hidden` effect tells it.
+        ActiveEvent::Reveal(_) => return None,
+        // The player can't see where the hidden enemies go.
+        ActiveEvent::MoveTo(event)
+            if state::is_hidden_from(context.state, event.id, PlayerId(0)) =>
+        {
+            return None
+        }
+        ActiveEvent::EndBattle(event::EndBattle { result }) => {
+            if result.winner_id == PlayerId(0) {
+                Line::new("The battle is won.")
+            } else {
+                Line::new("The battle is lost.")
+            }
+        }
+        ActiveEvent::BeginTurn(event::BeginTurn { player_id }) => {
+            if *player_id == PlayerId(0) {
+                Line::new("--- your turn ---")
+            } else if state::is_on_human_side(context.state, *player_id) {
+                Line::new("--- the allies' turn ---")
+            } else {
+                Line::new("--- the enemy's turn ---")
+            }
+        }
+        ActiveEvent::UseAbility(event) => Line::new("{agent} uses {ability}.")
+            .arg("agent", context.object(event.id))
+            .arg("ability", Arg::Ability(event.ability)),
+        ActiveEvent::UsePassiveAbility(event) => Line::new("{object}: {ability}.")
+            .arg("object", context.object(event.id))
+            .arg("ability", Arg::PassiveAbility(event.ability)),
+        ActiveEvent::Reaction(event) => {
+            let template = match event.hook {
+                Hook::Hit => "{object} strikes back.",
+                Hook::TurnStart => "{object} reacts to the new turn.",
+                // The dead object has no name anymore, its effects tell the rest.
+                Hook::Death => return None,
+            };
+            Line::new(template).arg("object", context.object(event.id))
+        }
+        ActiveEvent::Aura(event) => {
+            Line::new("The aura of {object} spreads.").arg("object", context.object(event.id))
+        }
+        ActiveEvent::Capture(event) => {
+            if state::is_on_human_side(context.state, event.player_id) {
+                Line::new("Your side captures a control point.")
+            } else {
+                Line::new("The enemy captures a control point.")
+            }
+        }
+        ActiveEvent::MoveTo(event) => {
+            let tiles = event.path.tiles().len() as i32 - 1;
+            let line = if tiles == 1 {
+                Line::new("{agent} moves 1 tile.")
+            } else {
+                Line::new("{agent} moves {count} tiles.").arg("count", Arg::Number(tiles))
+            };
+            line.arg("agent", context.object(event.id))
+        }
+        ActiveEvent::Attack(event) => {
+            let template = match event.mode {
+                AttackMode::Active => "{attacker} attacks {target}.",
+                AttackMode::Reactive => "{attacker} reacts with an attack on {target}.",
+                AttackMode::Counter => "{attacker} counters {target}.",
+            };
+            Line::new(template)
+                .arg("attacker", context.object(event.attacker_id))
+                .arg("target", context.object(event.target_id))
+        }
+        ActiveEvent::EffectTick(event) => Line::new("{object} suffers from {effect}.")
+            .arg("object", context.object(event.id))
+            .arg("effect", Arg::Effect(event.effect)),
+        ActiveEvent::EffectEnd(event) => Line::new("{effect} of {object} ends.")
+            .arg("object", context.object(event.id))
+            .arg("effect", Arg::Effect(event.effect)),
+        ActiveEvent::ConvertJoker(event) => {
+            let template = match event.into {
+                JokerUse::Move => "{agent} spends a joker on a move.",
+                JokerUse::Attack => "{agent} spends a joker on an attack.",
+            };
+            Line::new(template).arg("agent", context.object(event.id))
+        }
+        ActiveEvent::Mount(event) => Line::new("{agent} mounts {mount}.")
+            .arg("agent", context.object(event.id))
+            .arg("mount", context.object(event.mount_id)),
+        // The rider is off the map till the dismount is applied, so it has no name yet.
+        ActiveEvent::Dismount(event) => {
+            Line::new("The rider gets off {mount}.").arg("mount", context.object(event.mount_id))
+        }
+        ActiveEvent::SuddenDeath(_) => Line::new("Sudden death: the edges of the map burn."),
+        ActiveEvent::BeginBossPhase(event) => {
+            // The first phase is the boss' initial state.
+            let phase = event.phase as i32 + 2;
+            Line::new("{object} enters phase {phase}.")
+                .arg("object", context.object(event.id))
+                .arg("phase", Arg::Number(phase))
+        }
+    };
+    Some(line)
+}
+
+fn describe_effect(context: &Context, id: Id, effect: &Effect) -> Line {
+    let template = match effect {
+        Effect::Create(_) => "{object} appears.",
+        Effect::Kill(effect) if effect.is_drowned => "{object} drowns.",
+        Effect::Kill(_) => "{object} is killed.",
+        Effect::Vanish => "{object} vanishes.",
+        Effect::Stun => "{object} is stunned.",
+        Effect::Heal(effect) => {
+            let line = Line::new("{object} heals {strength} strength.")
+                .arg("object", context.object(id))
+                .arg("strength", Arg::Number(effect.strength.0));
+            return line;
+        }
+        Effect::Wound(effect) => {
+            let line = match (effect.damage.0, effect.armor_break.0) {
+                (0, 0) => Line::new("{object} takes no damage."),
+                (damage, 0) => Line::new("{object} loses {damage} strength.")
+                    .arg("damage", Arg::Number(damage)),
+                (0, armor) => {
+                    Line::new("{object} loses {armor} armor.").arg("armor", Arg::Number(armor))
+                }
+                (damage, armor) => Line::new("{object} loses {damage} strength and {armor} armor.")
+                    .arg("damage", Arg::Number(damage))
+                    .arg("armor", Arg::Number(armor)),
+            };
+            return line.arg("object", context.object(id));
+        }
+        Effect::Knockback(effect) if effect.from == effect.to => "{object} resists the push.",
+        Effect::Knockback(_) => "{object} is pushed back.",
+        Effect::FlyOff(effect) if effect.from == effect.to => "{object} resists the blow.",
+        Effect::FlyOff(_) => "{object} flies off.",
+        Effect::Throw(effect) if effect.is_rolled => "{object} rolls.",
+        Effect::Throw(_) => "{object} is thrown.",
+        Effect::Dodge(_) => "{object} dodges.",
+        Effect::Bloodlust => "{object} is filled with bloodlust.",
+        Effect::Demoralize(effect) => {
+            let line = Line::new("{object} loses {morale} morale.")
+                .arg("object", context.object(id))
+                .arg("morale", Arg::Number(effect.morale));
+            return line;
+        }
+        Effect::Cleanse(effect) => {
+            let line = Line::new("{effect} is removed from {object}.")
+                .arg("object", context.object(id))
+                .arg("effect", Arg::Effect(effect.effect));
+            return line;
+        }
+    };
+    Line::new(template).arg("object", context.object(id))
+}
+
+/// The log lines of the event: what happened and then what it did to everyone.
+pub fn describe(state: &State, event: &Event) -> Vec<Line> {
+    let context = Context::new(state, event);
+    let mut lines: Vec<Line> = describe_active_event(&context, &event.active_event)
+        .into_iter()
+        .collect();
+    // The kill has been told already, the corpse isn't worth its own line.
+    if matches!(
+        event.active_event,
+        ActiveEvent::Create | ActiveEvent::LeaveCorpse(_)
+    ) {
+        return lines;
+    }
+    for (id, effects) in &event.instant_effects {
+        lines.extend(
+            effects
+                .iter()
+                .map(|effect| describe_effect(&context, *id, effect)),
+        );
+    }
+    for (id, effects) in &event.timed_effects {
+        for timed in effects {
+            let line = Line::new("{object} gets {effect}.")
+                .arg("object", context.object(*id))
+                .arg("effect", Arg::Effect(timed.effect));
+            lines.push(line);
+        }
+    }
+    lines
+}
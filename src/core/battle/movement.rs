@@ -3,7 +3,7 @@ use std::{collections::VecDeque, slice::Windows};
 use serde::{Deserialize, Serialize};
 
 use crate::core::{
-    battle::{ability::PassiveAbility, state, Id, State, TileType},
+    battle::{ability::PassiveAbility, component::MovementType, state, Id, State, TileType},
     map::{dirs, Dir, Distance, HexMap, PosHex},
 };
 
@@ -39,26 +39,53 @@ pub const fn max_cost() -> MovePoints {
     MovePoints(i32::max_value())
 }
 
-pub fn tile_cost(state: &State, _: Id, _: PosHex, pos: PosHex) -> MovePoints {
+fn is_dangerous(ability: PassiveAbility) -> bool {
+    matches!(
+        ability,
+        PassiveAbility::SpikeTrap | PassiveAbility::Burn | PassiveAbility::Poison
+    )
+}
+
+const DANGEROUS_TILE_COST: MovePoints = MovePoints(4);
+
+fn terrain_cost(state: &State, pos: PosHex) -> MovePoints {
+    match state.map().tile(pos) {
+        TileType::Plain => MovePoints(1),
+        TileType::Rocks => MovePoints(3),
+        TileType::Water => MovePoints(1),
+        TileType::Thicket => MovePoints(2),
+    }
+}
+
+/// The flyers ignore both the terrain and the dangerous objects.
+const FLYING_TILE_COST: MovePoints = MovePoints(1);
+
+/// The large agents pay for the worst of their tiles.
+pub fn tile_cost(state: &State, id: Id, _: PosHex, pos: PosHex) -> MovePoints {
+    state::footprint_at(state, id, pos)
+        .into_iter()
+        .filter(|&tile| state.map().is_inboard(tile))
+        .map(|tile| single_tile_cost(state, id, tile))
+        .max()
+        .unwrap_or(FLYING_TILE_COST)
+}
+
+fn single_tile_cost(state: &State, id: Id, pos: PosHex) -> MovePoints {
+    if state::is_flying(state, id) {
+        return FLYING_TILE_COST;
+    }
     // taking other dangerous objects in the tile into account
     for id in state.parts().passive_abilities.ids() {
         if state.parts().pos.get(id).0 != pos {
             continue;
         }
-        for &ability in &state.parts().passive_abilities.get(id).0 {
-            match ability {
-                PassiveAbility::SpikeTrap | PassiveAbility::Burn | PassiveAbility::Poison => {
-                    return MovePoints(4);
-                }
-                _ => {}
-            }
+        let abilities = &state.parts().passive_abilities.get(id).0;
+        if abilities.iter().any(|&ability| is_dangerous(ability)) {
+            return DANGEROUS_TILE_COST;
         }
     }
     // just tile's cost
-    match state.map().tile(pos) {
-        TileType::Plain => MovePoints(1),
-        TileType::Rocks => MovePoints(3),
-    }
+    terrain_cost(state, pos)
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -148,6 +175,13 @@ impl<'a> Iterator for Steps<'a> {
 pub struct Pathfinder {
     queue: VecDeque<PosHex>,
     map: HexMap<Tile>,
+
+    /// The cost of entering every tile, `None` if it's blocked.
+    /// Filled once per search, so the objects aren't looked through for every step.
+    entry_costs: HexMap<Option<MovePoints>>,
+
+    /// Taken by the other blockers: the flyers pass over them, but can't stop there.
+    occupied: HexMap<bool>,
 }
 
 impl Pathfinder {
@@ -155,6 +189,8 @@ impl Pathfinder {
         Self {
             queue: VecDeque::new(),
             map: HexMap::new(map_radius),
+            entry_costs: HexMap::new(map_radius),
+            occupied: HexMap::new(map_radius),
         }
     }
 
@@ -164,13 +200,11 @@ impl Pathfinder {
 
     fn process_neighbor_pos(
         &mut self,
-        state: &State,
-        id: Id,
+        tile_cost: MovePoints,
         original_pos: PosHex,
         neighbor_pos: PosHex,
     ) {
         let old_cost = self.map.tile(original_pos).cost;
-        let tile_cost = tile_cost(state, id, original_pos, neighbor_pos);
         let new_cost = MovePoints(old_cost.0 + tile_cost.0);
         let tile = self.map.tile(neighbor_pos);
         if tile.cost > new_cost {
@@ -194,12 +228,81 @@ impl Pathfinder {
         }
     }
 
-    fn try_to_push_neighbors(&mut self, state: &State, id: Id, pos: PosHex) {
+    /// Does the same as `tile_cost` and `state::is_tile_blocked` for all the tiles at once.
+    fn fill_entry_costs(&mut self, state: &State, id: Id, movement_type: MovementType) {
+        let parts = state.parts();
+        let is_flying = movement_type == MovementType::Fly;
+        for pos in self.entry_costs.iter() {
+            let is_drowning = state::is_water(state, pos) && !is_flying;
+            let cost = if state.map().is_masked(pos) || is_drowning {
+                None
+            } else if is_flying {
+                Some(FLYING_TILE_COST)
+            } else {
+                Some(terrain_cost(state, pos))
+            };
+            self.entry_costs.set_tile(pos, cost);
+            self.occupied.set_tile(pos, false);
+        }
+        if !is_flying {
+            for id in parts.passive_abilities.ids() {
+                let abilities = &parts.passive_abilities.get(id).0;
+                if abilities.iter().any(|&ability| is_dangerous(ability)) {
+                    let pos = parts.pos.get(id).0;
+                    self.entry_costs.set_tile(pos, Some(DANGEROUS_TILE_COST));
+                }
+            }
+        }
+        for blocker_id in parts.blocker.ids() {
+            if blocker_id == id {
+                continue;
+            }
+            let can_pass_over = state::can_pass_over(state, id, movement_type, blocker_id);
+            for pos in state::object_tiles(state, blocker_id) {
+                if !self.occupied.is_inboard(pos) {
+                    continue;
+                }
+                self.occupied.set_tile(pos, true);
+                if !can_pass_over {
+                    self.entry_costs.set_tile(pos, None);
+                }
+            }
+        }
+        let footprint = state::footprint(state, id);
+        if !footprint.is_empty() {
+            self.spread_over_footprint(footprint);
+        }
+    }
+
+    /// A large agent enters all the tiles of its footprint at once.
+    fn spread_over_footprint(&mut self, footprint: &[Dir]) {
+        let entry_costs = self.entry_costs.clone();
+        let occupied = self.occupied.clone();
+        for pos in entry_costs.iter() {
+            let mut cost = entry_costs.tile(pos);
+            let mut is_occupied = occupied.tile(pos);
+            for tile in state::footprint_tiles(pos, footprint) {
+                if !entry_costs.is_inboard(tile) {
+                    cost = None;
+                    continue;
+                }
+                cost = cost.zip(entry_costs.tile(tile)).map(|(a, b)| a.max(b));
+                is_occupied |= occupied.tile(tile);
+            }
+            self.entry_costs.set_tile(pos, cost);
+            self.occupied.set_tile(pos, is_occupied);
+        }
+    }
+
+    fn try_to_push_neighbors(&mut self, pos: PosHex) {
         assert!(self.map.is_inboard(pos));
         for dir in dirs() {
             let neighbor_pos = Dir::get_neighbor_pos(pos, dir);
-            if self.map.is_inboard(neighbor_pos) && !state::is_tile_blocked(state, neighbor_pos) {
-                self.process_neighbor_pos(state, id, pos, neighbor_pos);
+            if !self.map.is_inboard(neighbor_pos) {
+                continue;
+            }
+            if let Some(tile_cost) = self.entry_costs.tile(neighbor_pos) {
+                self.process_neighbor_pos(tile_cost, pos, neighbor_pos);
             }
         }
     }
@@ -211,17 +314,24 @@ impl Pathfinder {
     }
 
     pub fn fill_map(&mut self, state: &State, id: Id) {
+        self.fill_map_as(state, id, state::movement_type(state, id));
+    }
+
+    /// Fills the map as if the agent was moving the given way.
+    pub fn fill_map_as(&mut self, state: &State, id: Id, movement_type: MovementType) {
         let agent_pos = state.parts().pos.get(id).0;
         assert!(self.queue.is_empty());
         self.clean_map();
+        self.fill_entry_costs(state, id, movement_type);
         self.push_start_pos_to_queue(agent_pos);
         while let Some(pos) = self.queue.pop_front() {
-            self.try_to_push_neighbors(state, id, pos);
+            self.try_to_push_neighbors(pos);
         }
     }
 
+    /// `None` if the tile can't be reached or is taken by some blocker.
     pub fn path(&self, destination: PosHex) -> Option<Path> {
-        if self.map.tile(destination).cost == max_cost() {
+        if self.map.tile(destination).cost == max_cost() || self.occupied.tile(destination) {
             return None;
         }
         let mut path = vec![destination];
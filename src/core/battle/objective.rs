@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    battle::{
+        component::ObjType,
+        scenario::{Expiry, TurnLimit},
+        state, Id, PlayerId, State,
+    },
+    map::{self, PosHex},
+};
+
+/// A goal of the player in a scenario.
+///
+/// Killing all the enemies always wins the battle,
+/// but a scenario may also be won by completing its own objectives.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Objective {
+    /// Hold out for the given number of rounds.
+    SurviveNTurns(i32),
+
+    /// Get any agent to the tile.
+    ReachHex(PosHex),
+
+    /// Keep all the agents of this type on the player's side alive.
+    /// The battle is lost as soon as the last one dies.
+    ProtectAgent(ObjType),
+
+    /// Destroy all the objects of this type.
+    DestroyObject(ObjType),
+
+    /// Kill all the enemy agents of this type.
+    KillBoss(ObjType),
+
+    /// Get an agent of this type on the player's side to the exit tile.
+    /// The battle is lost as soon as the last one dies.
+    Escort { typename: ObjType, exit: PosHex },
+}
+
+/// A kind of a battle objective.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Kill all enemy agents.
+    KillAll,
+
+    SurviveNTurns,
+    ReachHex,
+    ProtectAgent,
+    DestroyObject,
+    KillBoss,
+    Escort,
+
+    /// The battle is lost when the scenario runs out of turns.
+    TurnLimit,
+
+    /// The map starts burning when the scenario runs out of turns.
+    SuddenDeath,
+}
+
+impl Kind {
+    /// Constraints can only be failed, they don't win the battle by themselves.
+    pub fn is_constraint(self) -> bool {
+        matches!(
+            self,
+            Kind::ProtectAgent | Kind::TurnLimit | Kind::SuddenDeath
+        )
+    }
+}
+
+/// Current progress of some objective.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Status {
+    pub kind: Kind,
+
+    /// How much is still left to do: units to kill, turns to survive, etc.
+    pub remaining: i32,
+
+    /// The objective can't be completed anymore.
+    pub is_failed: bool,
+}
+
+impl Status {
+    pub fn is_complete(&self) -> bool {
+        !self.is_failed && self.remaining <= 0
+    }
+}
+
+fn kill_all_status(state: &State, player_id: PlayerId) -> Status {
+    let enemies = state::enemy_agent_ids(state, player_id);
+    Status {
+        kind: Kind::KillAll,
+        remaining: enemies.len() as _,
+        is_failed: false,
+    }
+}
+
+fn count_of_type(state: &State, ids: impl IntoIterator<Item = Id>, typename: &ObjType) -> i32 {
+    ids.into_iter()
+        .filter(|&id| &state.parts().meta.get(id).name == typename)
+        .count() as _
+}
+
+fn min_distance(state: &State, ids: impl IntoIterator<Item = Id>, pos: PosHex) -> Option<i32> {
+    ids.into_iter()
+        .map(|id| map::distance_hex(state.parts().pos.get(id).0, pos).0)
+        .min()
+}
+
+fn objective_status(state: &State, player_id: PlayerId, objective: &Objective) -> Status {
+    match objective {
+        Objective::SurviveNTurns(turns) => Status {
+            kind: Kind::SurviveNTurns,
+            remaining: turns - state.rounds_passed(),
+            is_failed: false,
+        },
+        Objective::ReachHex(pos) => {
+            let agents = state::players_agent_ids(state, player_id);
+            let distance = min_distance(state, agents, *pos);
+            Status {
+                kind: Kind::ReachHex,
+                remaining: distance.unwrap_or(0),
+                is_failed: distance.is_none(),
+            }
+        }
+        Objective::ProtectAgent(typename) => {
+            let agents = state::allied_agent_ids(state, player_id);
+            let alive = count_of_type(state, agents, typename);
+            Status {
+                kind: Kind::ProtectAgent,
+                remaining: 0,
+                is_failed: alive == 0,
+            }
+        }
+        Objective::DestroyObject(typename) => {
+            let ids = state.parts().meta.ids();
+            Status {
+                kind: Kind::DestroyObject,
+                remaining: count_of_type(state, ids, typename),
+                is_failed: false,
+            }
+        }
+        Objective::KillBoss(typename) => {
+            let enemies = state::enemy_agent_ids(state, player_id);
+            Status {
+                kind: Kind::KillBoss,
+                remaining: count_of_type(state, enemies, typename),
+                is_failed: false,
+            }
+        }
+        Objective::Escort { typename, exit } => {
+            let escorted = state::allied_agent_ids(state, player_id)
+                .into_iter()
+                .filter(|&id| &state.parts().meta.get(id).name == typename);
+            let distance = min_distance(state, escorted, *exit);
+            Status {
+                kind: Kind::Escort,
+                remaining: distance.unwrap_or(0),
+                is_failed: distance.is_none(),
+            }
+        }
+    }
+}
+
+fn turn_limit_status(state: &State, limit: &TurnLimit) -> Status {
+    let remaining = limit.turns - state.rounds_passed();
+    match limit.on_expire {
+        Expiry::Defeat => Status {
+            kind: Kind::TurnLimit,
+            remaining,
+            is_failed: remaining <= 0,
+        },
+        Expiry::SuddenDeath => Status {
+            kind: Kind::SuddenDeath,
+            remaining,
+            is_failed: false,
+        },
+    }
+}
+
+/// Progress of all objectives of the player.
+pub fn statuses(state: &State, player_id: PlayerId) -> Vec<Status> {
+    let objectives = &state.scenario().objectives;
+    if player_id != PlayerId(0) {
+        return vec![kill_all_status(state, player_id)];
+    }
+    let mut statuses: Vec<_> = if objectives.is_empty() {
+        vec![kill_all_status(state, player_id)]
+    } else {
+        objectives
+            .iter()
+            .map(|objective| objective_status(state, player_id, objective))
+            .collect()
+    };
+    if let Some(limit) = &state.scenario().turn_limit {
+        statuses.push(turn_limit_status(state, limit));
+    }
+    statuses
+}
+
+/// The player that has won the battle, if it's over.
+pub fn winner(state: &State) -> Option<PlayerId> {
+    for i in 0..state.scenario().players_count {
+        let player_id = PlayerId(i);
+        if state::enemy_agent_ids(state, player_id).is_empty() {
+            return Some(player_id);
+        }
+    }
+    let statuses = statuses(state, PlayerId(0));
+    if statuses.iter().any(|status| status.is_failed) {
+        return Some(PlayerId(1));
+    }
+    let has_goals = statuses.iter().any(|status| !status.kind.is_constraint());
+    if has_goals && statuses.iter().all(Status::is_complete) {
+        return Some(PlayerId(0));
+    }
+    None
+}
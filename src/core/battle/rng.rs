@@ -0,0 +1,68 @@
+//! The battle's own random number generator.
+//!
+//! Every random decision of the battle core goes through the RNG stored
+//! in the `State`, so the same seed and the same commands always
+//! result in the same battle.
+
+use rand::{Error, RngCore};
+
+use crate::core::utils::zrng;
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const INCREMENT: u64 = 1442695040888963407;
+const FORK_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A small PCG32 generator, the same one that `quad-rand` uses globally.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BattleRng {
+    state: u64,
+}
+
+impl BattleRng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Another generator that is seeded from this one without advancing it,
+    /// so its numbers say nothing about the upcoming numbers of this one.
+    pub fn fork(&self) -> Self {
+        Self::new(self.state ^ FORK_SALT)
+    }
+}
+
+impl RngCore for BattleRng {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// A fresh seed for the scenarios that don't have a fixed one.
+pub fn random_seed() -> u64 {
+    zrng().next_u64()
+}
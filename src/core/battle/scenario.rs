@@ -1,17 +1,23 @@
 use std::collections::HashMap;
 
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::core::{
     battle::{
-        component::ObjType,
+        ai,
+        component::{Component, ObjType},
+        dice,
+        objective::Objective,
         state::{self, State},
         PlayerId, TileType,
     },
-    map::{self, PosHex},
-    utils::roll_dice,
+    map::{self, generator::Biome, Dir, PosHex},
 };
 
+/// The type of the obstacles placed by `Scenario::random_map`.
+pub const RANDOM_MAP_OBSTACLE: &str = "boulder";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BattleType {
     Skirmish,
@@ -24,6 +30,10 @@ pub struct ObjectsGroup {
     pub typename: ObjType,
     pub line: Option<Line>,
     pub count: i32,
+
+    /// Replace the prototype's components of the same type.
+    #[serde(default)]
+    pub extra_components: Vec<Component>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +43,317 @@ pub struct Object {
     pub pos: PosHex,
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Size {
+    Small,
+    #[default]
+    Medium,
+    Large,
+}
+
+impl Size {
+    pub fn title(self) -> &'static str {
+        match self {
+            Size::Small => "small",
+            Size::Medium => "medium",
+            Size::Large => "large",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Size::Small => Size::Medium,
+            Size::Medium => Size::Large,
+            Size::Large => Size::Small,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn title(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Theme {
+    Wilderness,
+    Village,
+    Ruins,
+    Lair,
+}
+
+/// Notable game mechanics that a scenario relies on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Mechanic {
+    Traps,
+    Poison,
+    Bombs,
+    Summoning,
+}
+
+/// Metadata used to pick the appropriate scenarios.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Tags {
+    pub size: Size,
+    pub difficulty: Difficulty,
+    pub theme: Option<Theme>,
+    pub mechanics: Vec<Mechanic>,
+}
+
+/// A filter over the scenario tags. `None` fields match anything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub size: Option<Size>,
+    pub difficulty: Option<Difficulty>,
+    pub theme: Option<Theme>,
+
+    /// All of these mechanics must be used by the scenario.
+    pub required_mechanics: Vec<Mechanic>,
+
+    /// None of these mechanics may be used by the scenario.
+    pub excluded_mechanics: Vec<Mechanic>,
+}
+
+impl Query {
+    pub fn matches(&self, tags: &Tags) -> bool {
+        (self.size.is_none() || self.size == Some(tags.size))
+            && (self.difficulty.is_none() || self.difficulty == Some(tags.difficulty))
+            && (self.theme.is_none() || self.theme == tags.theme)
+            && self
+                .required_mechanics
+                .iter()
+                .all(|m| tags.mechanics.contains(m))
+            && !self
+                .excluded_mechanics
+                .iter()
+                .any(|m| tags.mechanics.contains(m))
+    }
+}
+
+/// All the scenarios that match the query, in their original order.
+pub fn select<'a>(scenarios: &'a [Scenario], query: &Query) -> Vec<&'a Scenario> {
+    scenarios
+        .iter()
+        .filter(|scenario| query.matches(&scenario.tags))
+        .collect()
+}
+
+/// What happens when the scenario runs out of turns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Expiry {
+    /// The player loses the battle.
+    #[default]
+    Defeat,
+
+    /// Fire starts to spread from the edges of the map,
+    /// one more ring of tiles every round.
+    SuddenDeath,
+}
+
+/// Global conditions of the battle that slightly change some rules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Weather {
+    #[default]
+    Clear,
+
+    /// Fires burn out faster.
+    Rain,
+
+    /// Attack distances are one tile shorter, but never shorter than one tile.
+    Fog,
+
+    /// Thrown bombs are carried one more tile in the given direction, if it's free.
+    Wind(map::Dir),
+}
+
+impl Weather {
+    pub fn title(self) -> String {
+        match self {
+            Weather::Clear => "clear".into(),
+            Weather::Rain => "rain".into(),
+            Weather::Fog => "fog".into(),
+            Weather::Wind(_) => "wind".into(),
+        }
+    }
+
+    pub fn description(self) -> String {
+        match self {
+            Weather::Clear => "no effects".into(),
+            Weather::Rain => "fires burn out faster".into(),
+            Weather::Fog => "shorter attack distance".into(),
+            Weather::Wind(_) => "thrown bombs drift by one tile".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TurnLimit {
+    /// Full rounds the player has before the limit expires.
+    pub turns: i32,
+
+    #[serde(default)]
+    pub on_expire: Expiry,
+}
+
+/// A condition that brings a reinforcement wave into the battle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Trigger {
+    /// After the given number of full rounds.
+    Round(i32),
+
+    /// As soon as the player has fewer agents left than `count`.
+    FewerAgentsThan { owner: PlayerId, count: i32 },
+}
+
+/// A group of objects that joins the battle later.
+///
+/// The wave arrives only once, at the beginning of the owner's turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Wave {
+    pub owner: PlayerId,
+    pub trigger: Trigger,
+
+    /// An object is placed next to its position if the tile is occupied.
+    pub objects: Vec<(ObjType, PosHex)>,
+}
+
+/// A named group of tiles that the scripts watch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Region {
+    pub name: String,
+    pub tiles: Vec<PosHex>,
+
+    /// Highlight the tiles on the map, e.g. for exits.
+    #[serde(default)]
+    pub is_marked: bool,
+}
+
+/// What holding a control point gives.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ControlReward {
+    /// Earned at the end of every turn of its holder, only the human side gets it.
+    Renown(i32),
+
+    /// Joins the new holder next to the point every time it changes hands.
+    Reinforcement(ObjType),
+}
+
+/// A tile that belongs to the side of the last agent that has ended a turn on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlPoint {
+    pub pos: PosHex,
+    pub reward: ControlReward,
+}
+
+/// What a script waits for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Condition {
+    /// The given round begins, the first one is zero.
+    RoundBegins(i32),
+
+    /// Any agent of the player steps into the region.
+    Enter { region: String, player_id: PlayerId },
+
+    /// Only the agents of the player's side have been standing in the region
+    /// at the start of the player's turn for the given number of rounds in a row.
+    Occupy {
+        region: String,
+        owner: PlayerId,
+        rounds: i32,
+    },
+
+    /// No agents of the player are left in the region.
+    Clear { region: String, player_id: PlayerId },
+
+    /// No objects of this type are left on the map.
+    ///
+    /// Met right away if the scenario doesn't place any of them.
+    Destroyed(ObjType),
+
+    /// Some object of this type has no more than the given percent of its base strength left.
+    StrengthDropsTo { typename: ObjType, percent: i32 },
+}
+
+impl Condition {
+    pub fn region(&self) -> Option<&str> {
+        match self {
+            Condition::Enter { region, .. }
+            | Condition::Occupy { region, .. }
+            | Condition::Clear { region, .. } => Some(region),
+            Condition::RoundBegins(_)
+            | Condition::Destroyed(_)
+            | Condition::StrengthDropsTo { .. } => None,
+        }
+    }
+}
+
+/// A line of a scripted dialogue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Remark {
+    /// The portrait is taken from this object's sprite.
+    pub speaker: ObjType,
+
+    /// Shown instead of the speaker's type.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    pub text: String,
+}
+
+/// What happens when a script fires.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScriptAction {
+    /// Place new objects, e.g. for ambushes.
+    Spawn {
+        owner: Option<PlayerId>,
+        objects: Vec<(ObjType, PosHex)>,
+    },
+
+    /// Show a text over the condition's region or in the middle of the screen.
+    Message(String),
+
+    /// Lines said by the characters, shown one by one over the battle.
+    Dialogue(Vec<Remark>),
+
+    /// Change the terrain, e.g. for collapsing bridges.
+    SetTiles { tiles: Vec<PosHex>, tile: TileType },
+
+    /// End the battle right away, e.g. for exits or capture points.
+    Win(PlayerId),
+}
+
+/// Fires its actions once, when its condition is met for the first time.
+///
+/// The conditions are checked when all the objects are created and then after every command,
+/// so `RoundBegins(0)` fires at the very start of the battle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Script {
+    pub condition: Condition,
+    pub actions: Vec<ScriptAction>,
+}
+
 // TODO: Split into `Scenario` (exact info) and `ScenarioTemplate`?
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -40,14 +361,57 @@ pub struct Scenario {
     pub map_radius: map::Distance,
     pub players_count: i32,
 
+    /// AI players that fight on the human player's side.
+    pub allies: Vec<PlayerId>,
+
     // TODO: rename it to `randomized_tiles` later (not only `TileType::Rocks`)
     pub rocky_tiles_count: i32,
 
     pub tiles: HashMap<PosHex, TileType>,
 
+    /// Tiles cut out of the hexagonal map, e.g. for ruined corridors or river crossings.
+    pub masked_tiles: Vec<PosHex>,
+
+    /// Generates the terrain and the obstacles, see `map::generator`.
+    /// The generated obstacles are `RANDOM_MAP_OBSTACLE` objects.
+    pub random_map: Option<Biome>,
+
     pub randomized_objects: Vec<ObjectsGroup>,
 
     pub objects: Vec<Object>,
+
+    pub dice: dice::Mode,
+
+    pub ai: ai::Mode,
+
+    /// The seed of the battle's RNG, a random one is picked if it's not set.
+    /// Battles with the same seed and the same commands play out identically.
+    pub seed: Option<u64>,
+
+    pub tags: Tags,
+
+    /// Extra ways to win or lose the battle, see `objective::winner`.
+    pub objectives: Vec<Objective>,
+
+    /// Maximum length of the battle, unlimited if `None`.
+    pub turn_limit: Option<TurnLimit>,
+
+    pub reinforcements: Vec<Wave>,
+
+    pub regions: Vec<Region>,
+
+    pub control_points: Vec<ControlPoint>,
+
+    /// The region where the human player places their agents before the first turn.
+    /// Without it the agents are placed at random positions.
+    pub deployment_zone: Option<String>,
+
+    pub scripts: Vec<Script>,
+
+    pub weather: Weather,
+
+    /// At night agents can't attack as far unless they stand near some light source.
+    pub is_night: bool,
 }
 
 #[derive(Clone, Debug, derive_more::From)]
@@ -57,21 +421,80 @@ pub enum Error {
     NoPlayerAgents,
     NoEnemyAgents,
     UnsupportedPlayersCount(i32),
+    BadAlly(PlayerId),
+    NoSuchRegion(String),
 }
 
 impl Scenario {
+    pub fn region(&self, name: &str) -> Option<&Region> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    /// Is the position on the map with the masked tiles taken into account.
+    fn is_inboard(&self, pos: PosHex) -> bool {
+        let origin = PosHex { q: 0, r: 0 };
+        map::distance_hex(origin, pos) <= self.map_radius && !self.masked_tiles.contains(&pos)
+    }
+
+    /// Positions used by the exact tiles, objects, waves, regions and scripts of the scenario.
+    pub fn fixed_positions(&self) -> Vec<PosHex> {
+        let mut positions: Vec<PosHex> = self.objects.iter().map(|obj| obj.pos).collect();
+        positions.extend(self.tiles.keys().copied());
+        for wave in &self.reinforcements {
+            positions.extend(wave.objects.iter().map(|&(_, pos)| pos));
+        }
+        for region in &self.regions {
+            positions.extend(region.tiles.iter().copied());
+        }
+        positions.extend(self.control_points.iter().map(|point| point.pos));
+        for script in &self.scripts {
+            for action in &script.actions {
+                match action {
+                    ScriptAction::Spawn { objects, .. } => {
+                        positions.extend(objects.iter().map(|&(_, pos)| pos));
+                    }
+                    ScriptAction::SetTiles { tiles, .. } => positions.extend(tiles.iter().copied()),
+                    _ => {}
+                }
+            }
+        }
+        positions
+    }
+
     pub fn check(&self) -> Result<(), Error> {
-        if self.players_count != 2 {
+        // Only the human player can have some extra AI allies: the enemy is always alone.
+        if self.players_count != 2 + self.allies.len() as i32 {
             return Err(Error::UnsupportedPlayersCount(self.players_count));
         }
+        for &ally in &self.allies {
+            if ally.0 < 2 || ally.0 >= self.players_count {
+                return Err(Error::BadAlly(ally));
+            }
+        }
         if self.map_radius.0 < 3 {
             return Err(Error::MapIsTooSmall);
         }
         let origin = PosHex { q: 0, r: 0 };
-        for obj in &self.objects {
-            let dist = map::distance_hex(origin, obj.pos);
-            if dist > self.map_radius {
-                return Err(Error::PosOutsideOfMap(obj.pos));
+        for &pos in &self.masked_tiles {
+            if map::distance_hex(origin, pos) > self.map_radius {
+                return Err(Error::PosOutsideOfMap(pos));
+            }
+        }
+        for pos in self.fixed_positions() {
+            if !self.is_inboard(pos) {
+                return Err(Error::PosOutsideOfMap(pos));
+            }
+        }
+        if let Some(zone) = &self.deployment_zone {
+            if self.region(zone).is_none() {
+                return Err(Error::NoSuchRegion(zone.clone()));
+            }
+        }
+        for script in &self.scripts {
+            if let Some(region) = script.condition.region() {
+                if self.region(region).is_none() {
+                    return Err(Error::NoSuchRegion(region.into()));
+                }
             }
         }
         let any_exact_player_agents = self
@@ -105,24 +528,45 @@ impl Default for Scenario {
         Self {
             map_radius: map::Distance(5),
             players_count: 2,
+            allies: Vec::new(),
             rocky_tiles_count: 0,
             tiles: HashMap::new(),
+            masked_tiles: Vec::new(),
+            random_map: None,
             randomized_objects: Vec::new(),
             objects: Vec::new(),
+            dice: dice::Mode::default(),
+            ai: ai::Mode::default(),
+            seed: None,
+            tags: Tags::default(),
+            objectives: Vec::new(),
+            turn_limit: None,
+            reinforcements: Vec::new(),
+            regions: Vec::new(),
+            control_points: Vec::new(),
+            deployment_zone: None,
+            scripts: Vec::new(),
+            weather: Weather::default(),
+            is_night: false,
         }
     }
 }
 
-pub fn random_free_pos(state: &State) -> Option<PosHex> {
+/// The large objects need all the tiles of their footprint to be free.
+pub fn random_free_pos(state: &mut State, footprint: &[Dir]) -> Option<PosHex> {
     assert!(!state.deterministic_mode());
     let attempts = 30;
     let radius = state.map().radius();
     for _ in 0..attempts {
         let pos = PosHex {
-            q: roll_dice(-radius.0, radius.0),
-            r: roll_dice(-radius.0, radius.0),
+            q: state.rng().gen_range(-radius.0..radius.0),
+            r: state.rng().gen_range(-radius.0..radius.0),
         };
-        if state::is_tile_plain_and_completely_free(state, pos) {
+        let tiles = state::footprint_tiles(pos, footprint);
+        if tiles
+            .into_iter()
+            .all(|pos| state::is_tile_plain_and_completely_free(state, pos))
+        {
             return Some(pos);
         }
     }
@@ -162,33 +606,46 @@ impl Line {
     }
 }
 
-fn random_free_sector_pos(state: &State, player_id: PlayerId, line: Line) -> Option<PosHex> {
+fn random_free_sector_pos(
+    state: &mut State,
+    player_id: PlayerId,
+    line: Line,
+    footprint: &[Dir],
+) -> Option<PosHex> {
     assert!(!state.deterministic_mode());
     let attempts = 30;
     let radius = state.map().radius();
     let (min, max) = line.to_range(radius);
     for _ in 0..attempts {
-        let q = radius.0 - roll_dice(min, max);
+        let q = radius.0 - state.rng().gen_range(min..max);
         let pos = PosHex {
-            q: match player_id.0 {
-                0 => -q,
-                1 => q,
-                _ => unimplemented!(),
+            q: if state::is_on_human_side(state, player_id) {
+                -q
+            } else {
+                q
             },
-            r: roll_dice(-radius.0, radius.0 + 1),
+            r: state.rng().gen_range(-radius.0..=radius.0),
         };
         let no_enemies_around = !state::check_enemies_around(state, pos, player_id);
-        if state::is_tile_completely_free(state, pos) && no_enemies_around {
+        let is_free = state::footprint_tiles(pos, footprint)
+            .into_iter()
+            .all(|pos| state::is_tile_completely_free(state, pos) && !state::is_water(state, pos));
+        if is_free && no_enemies_around {
             return Some(pos);
         }
     }
     None
 }
 
-pub fn random_pos(state: &State, owner: Option<PlayerId>, line: Option<Line>) -> Option<PosHex> {
+pub fn random_pos(
+    state: &mut State,
+    owner: Option<PlayerId>,
+    line: Option<Line>,
+    footprint: &[Dir],
+) -> Option<PosHex> {
     match (owner, line) {
-        (Some(player_id), Some(line)) => random_free_sector_pos(state, player_id, line),
-        _ => random_free_pos(state),
+        (Some(player_id), Some(line)) => random_free_sector_pos(state, player_id, line, footprint),
+        _ => random_free_pos(state, footprint),
     }
 }
 
@@ -0,0 +1,230 @@
+//! Headless AI-vs-AI battles, used to check the balance of units and abilities
+//! and to fight the campaign battles that the player doesn't want to play.
+
+use std::fmt;
+
+use log::error;
+
+use crate::core::battle::{
+    ability,
+    ai::Ai,
+    command::{self, Command},
+    component::Prototypes,
+    effect::{Effect, StackRules},
+    event::Event,
+    execute::{execute, ApplyPhase},
+    scenario::Scenario,
+    state::BattleResult,
+    PlayerId, State,
+};
+
+/// Battles that last longer than this are counted as draws.
+pub const MAX_ROUNDS: i32 = 100;
+
+/// Protects from the AIs that keep on issuing useless commands.
+const MAX_COMMANDS_PER_TURN: i32 = 100;
+
+/// What has happened to one of the players over all the simulated battles.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerStats {
+    pub wins: i32,
+
+    /// Strength lost by the player's objects, a kill counts as all the remaining strength.
+    pub damage_taken: i32,
+
+    /// Agents of the player that have been killed.
+    pub losses: i32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub battles: i32,
+
+    /// Battles that haven't ended in `MAX_ROUNDS`.
+    pub draws: i32,
+
+    /// Rounds passed in all the battles.
+    pub rounds: i32,
+
+    /// Indexed by `PlayerId`.
+    pub players: Vec<PlayerStats>,
+}
+
+impl Report {
+    fn per_battle(&self, value: i32) -> f32 {
+        if self.battles == 0 {
+            return 0.0;
+        }
+        value as f32 / self.battles as f32
+    }
+
+    pub fn win_rate(&self, player_id: PlayerId) -> f32 {
+        self.per_battle(self.players[player_id.0 as usize].wins)
+    }
+
+    pub fn average_rounds(&self) -> f32 {
+        self.per_battle(self.rounds)
+    }
+
+    pub fn average_damage_taken(&self, player_id: PlayerId) -> f32 {
+        self.per_battle(self.players[player_id.0 as usize].damage_taken)
+    }
+
+    pub fn average_losses(&self, player_id: PlayerId) -> f32 {
+        self.per_battle(self.players[player_id.0 as usize].losses)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "battles: {}", self.battles)?;
+        writeln!(f, "draws: {}", self.draws)?;
+        writeln!(f, "average rounds: {:.1}", self.average_rounds())?;
+        for i in 0..self.players.len() {
+            let player_id = PlayerId(i as _);
+            writeln!(
+                f,
+                "player {}: wins {:.0}%, damage taken {:.1}, losses {:.1}",
+                i,
+                self.win_rate(player_id) * 100.0,
+                self.average_damage_taken(player_id),
+                self.average_losses(player_id),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn record_damage(players: &mut [PlayerStats], state: &State, event: &Event) {
+    let parts = state.parts();
+    for (id, effects) in &event.instant_effects {
+        let owner = match parts.belongs_to.get_opt(*id) {
+            Some(owner) => owner.0,
+            None => continue,
+        };
+        let stats = &mut players[owner.0 as usize];
+        for effect in effects {
+            match effect {
+                Effect::Wound(wound) => stats.damage_taken += wound.damage.0,
+                Effect::Kill(_) => {
+                    if let Some(strength) = parts.strength.get_opt(*id) {
+                        stats.damage_taken += strength.strength.0;
+                    }
+                    if parts.agent.get_opt(*id).is_some() {
+                        stats.losses += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Plays one turn of the current player, returns `false` if the battle is over.
+fn play_turn(state: &mut State, ai: &mut Ai, players: &mut [PlayerStats]) -> bool {
+    let mut cb = |state: &State, event: &Event, phase| {
+        if phase == ApplyPhase::Pre {
+            record_damage(players, state, event);
+        }
+    };
+    for _ in 0..MAX_COMMANDS_PER_TURN {
+        let command = match ai.command(state) {
+            Some(command) => command,
+            None => return false,
+        };
+        if let Err(err) = execute(state, &command, &mut cb) {
+            error!("Simulation: bad AI command {:?}: {:?}", command, err);
+            break;
+        }
+        if let Command::EndTurn(_) = command {
+            return state.battle_result().is_none();
+        }
+        if state.battle_result().is_some() {
+            return false;
+        }
+    }
+    // The AI is stuck, so its turn is ended by force.
+    execute(state, &command::EndTurn.into(), &mut cb).expect("Can't end the turn");
+    state.battle_result().is_none()
+}
+
+/// Plays one battle to the end or till `MAX_ROUNDS` have passed.
+fn play_battle(
+    prototypes: &Prototypes,
+    stack_rules: &StackRules,
+    definitions: &ability::Definitions,
+    scenario: Scenario,
+    players: &mut [PlayerStats],
+) -> State {
+    let radius = scenario.map_radius;
+    let mut ais: Vec<_> = (0..scenario.players_count)
+        .map(|i| Ai::new(PlayerId(i), radius))
+        .collect();
+    let mut state = State::new(
+        prototypes.clone(),
+        stack_rules.clone(),
+        definitions.clone(),
+        scenario,
+        &mut |_, _, _| {},
+    );
+    while state.rounds_passed() < MAX_ROUNDS {
+        let ai = &mut ais[state.player_id().0 as usize];
+        if !play_turn(&mut state, ai, players) {
+            break;
+        }
+    }
+    state
+}
+
+/// Lets the AI fight for the human player too, `None` means a draw.
+pub fn resolve(
+    prototypes: &Prototypes,
+    stack_rules: &StackRules,
+    definitions: &ability::Definitions,
+    scenario: &Scenario,
+) -> Option<BattleResult> {
+    let mut players = vec![PlayerStats::default(); scenario.players_count as usize];
+    let state = play_battle(
+        prototypes,
+        stack_rules,
+        definitions,
+        scenario.clone(),
+        &mut players,
+    );
+    state.battle_result().clone()
+}
+
+/// Plays the scenario the given number of times with every player controlled by the AI.
+///
+/// If the scenario has a seed, the battles use consecutive seeds starting from it,
+/// so the whole report is reproducible.
+pub fn simulate(
+    prototypes: &Prototypes,
+    stack_rules: &StackRules,
+    definitions: &ability::Definitions,
+    scenario: &Scenario,
+    battles: i32,
+) -> Report {
+    let mut report = Report {
+        players: vec![PlayerStats::default(); scenario.players_count as usize],
+        ..Default::default()
+    };
+    for i in 0..battles {
+        let mut scenario = scenario.clone();
+        scenario.seed = scenario.seed.map(|seed| seed.wrapping_add(i as u64));
+        let state = play_battle(
+            prototypes,
+            stack_rules,
+            definitions,
+            scenario,
+            &mut report.players,
+        );
+        report.battles += 1;
+        report.rounds += state.rounds_passed();
+        match state.battle_result() {
+            Some(result) => report.players[result.winner_id.0 as usize].wins += 1,
+            None => report.draws += 1,
+        }
+    }
+    report
+}
@@ -1,17 +1,25 @@
+use std::{collections::HashMap, iter};
+
+use rand::seq::SliceRandom;
+
 use crate::core::{
     battle::{
         self,
-        ability::{self, Ability, PassiveAbility},
-        component::ObjType,
-        effect, Id, PlayerId, Strength, TileType,
+        ability::{Ability, Delivery, PassiveAbility, Tier, TierParams},
+        command,
+        component::{
+            self, AuraEffect, AuraTargets, Charges, Component, DamageType, MovementType, ObjType,
+        },
+        effect,
+        scenario::Weather,
+        Id, PlayerId, Strength, TileType,
     },
-    map::{self, PosHex},
-    utils,
+    map::{self, Dir, PosHex},
 };
 
 pub use self::{
     apply::apply,
-    private::{BattleResult, State},
+    private::{AbilityStatus, BattleResult, DeathCause, FallenVeteran, LastingEffectStatus, State},
 };
 
 mod apply;
@@ -21,14 +29,340 @@ pub fn is_agent_belong_to(state: &State, player_id: PlayerId, id: Id) -> bool {
     state.parts().belongs_to.get(id).0 == player_id
 }
 
-pub fn is_tile_blocked(state: &State, pos: PosHex) -> bool {
-    assert!(state.map().is_inboard(pos));
-    for id in state.parts().blocker.ids() {
-        if state.parts().pos.get(id).0 == pos {
-            return true;
+/// Is it the human player or one of their AI allies?
+pub fn is_on_human_side(state: &State, player_id: PlayerId) -> bool {
+    player_id == PlayerId(0) || state.scenario().allies.contains(&player_id)
+}
+
+/// Agents look at the opposite side of the map when they appear.
+pub fn initial_facing(state: &State, owner: Option<PlayerId>) -> map::Dir {
+    let dir = map::Dir::SouthEast;
+    match owner {
+        Some(player_id) if !is_on_human_side(state, player_id) => dir.opposite(),
+        _ => dir,
+    }
+}
+
+/// Is the position behind the agent or to the back side of it?
+pub fn is_in_rear_arc(state: &State, id: Id, pos: PosHex) -> bool {
+    let parts = state.parts();
+    let facing = match parts.facing.get_opt(id) {
+        Some(facing) => facing.0,
+        None => return false,
+    };
+    let agent_pos = parts.pos.get(id).0;
+    match map::Dir::approximate_from_to(agent_pos, pos) {
+        Some(dir) => facing.turns_to(dir) >= 2,
+        None => false,
+    }
+}
+
+pub fn is_backstab(state: &State, attacker_id: Id, target_id: Id) -> bool {
+    let attacker_pos = state.parts().pos.get(attacker_id).0;
+    is_in_rear_arc(state, target_id, attacker_pos)
+}
+
+/// How far a light source (e.g. a torch) lights up the night.
+pub const LIGHT_RADIUS: map::Distance = map::Distance(2);
+
+/// Everything is lit during the day, at night only the tiles around light sources are.
+pub fn is_lit(state: &State, pos: PosHex) -> bool {
+    if !state.scenario().is_night {
+        return true;
+    }
+    let parts = state.parts();
+    parts.passive_abilities.ids().any(|id| {
+        parts
+            .passive_abilities
+            .get(id)
+            .0
+            .contains(&PassiveAbility::Light)
+            && map::distance_hex(parts.pos.get(id).0, pos) <= LIGHT_RADIUS
+    })
+}
+
+fn pos_and_owner(state: &State, id: Id) -> Option<(PosHex, PlayerId)> {
+    let parts = state.parts();
+    let pos = parts.pos.get_opt(id)?.0;
+    let owner = parts.belongs_to.get_opt(id)?.0;
+    Some((pos, owner))
+}
+
+/// The auras of the other objects that reach the agent where it stands now,
+/// paired with the ids of their sources.
+pub fn auras_affecting(state: &State, id: Id) -> Vec<(Id, component::Aura)> {
+    let parts = state.parts();
+    let (pos, player_id) = match pos_and_owner(state, id) {
+        Some(pos_and_owner) => pos_and_owner,
+        None => return Vec::new(),
+    };
+    let mut auras = Vec::new();
+    for source_id in parts.auras.ids() {
+        let (source_pos, owner) = match pos_and_owner(state, source_id) {
+            Some(pos_and_owner) if source_id != id => pos_and_owner,
+            _ => continue,
+        };
+        for aura in &parts.auras.get(source_id).0 {
+            let is_target = match aura.targets {
+                AuraTargets::Allies => owner == player_id,
+                AuraTargets::Enemies => owner != player_id,
+            };
+            if is_target && map::distance_hex(source_pos, pos) <= aura.radius {
+                auras.push((source_id, *aura));
+            }
         }
     }
-    false
+    auras
+}
+
+pub fn aura_attack_bonus(state: &State, id: Id) -> Strength {
+    let bonus = auras_affecting(state, id)
+        .iter()
+        .map(|(_, aura)| match aura.effect {
+            AuraEffect::AttackStrength(bonus) => bonus,
+            AuraEffect::Lasting { .. } | AuraEffect::Heal(_) => 0,
+        })
+        .sum();
+    Strength(bonus)
+}
+
+/// The positions of the scenario's control points, `Some(true)` for the ones of the human side.
+pub fn control_points(state: &State) -> Vec<(PosHex, Option<bool>)> {
+    state
+        .scenario()
+        .control_points
+        .iter()
+        .enumerate()
+        .map(|(index, point)| {
+            let holder = state.control_point_holder(index);
+            let is_ours = holder.map(|player_id| is_on_human_side(state, player_id));
+            (point.pos, is_ours)
+        })
+        .collect()
+}
+
+/// The tiles under the auras, `true` for the ones that help the human side.
+pub fn aura_tiles(state: &State) -> Vec<(PosHex, bool)> {
+    let parts = state.parts();
+    let mut tiles = Vec::new();
+    for id in parts.auras.ids() {
+        let (pos, owner) = match pos_and_owner(state, id) {
+            Some(pos_and_owner) => pos_and_owner,
+            None => continue,
+        };
+        for aura in &parts.auras.get(id).0 {
+            let is_friendly =
+                is_on_human_side(state, owner) == (aura.targets == AuraTargets::Allies);
+            for tile in state.map().iter() {
+                if map::distance_hex(pos, tile) <= aura.radius {
+                    tiles.push((tile, is_friendly));
+                }
+            }
+        }
+    }
+    tiles
+}
+
+fn is_explosion(ability: Ability) -> bool {
+    matches!(
+        ability,
+        Ability::ExplodePush
+            | Ability::ExplodeDamage
+            | Ability::ExplodeFire
+            | Ability::ExplodePoison
+    )
+}
+
+/// How many times the thrower's turn has to begin before the bomb goes off,
+/// `None` for the objects that aren't going to explode.
+pub fn bomb_fuse(state: &State, id: Id) -> Option<i32> {
+    let schedule = state.parts().schedule.get_opt(id)?;
+    schedule
+        .planned
+        .iter()
+        .filter(|planned| is_explosion(planned.ability))
+        .map(|planned| planned.rounds.0.max(1))
+        .min()
+}
+
+/// The positions of all the bombs that are going to explode, with their fuses.
+pub fn bomb_fuses(state: &State) -> Vec<(PosHex, i32)> {
+    let parts = state.parts();
+    let mut ids = parts.schedule.ids_collected();
+    ids.sort();
+    ids.into_iter()
+        .filter_map(|id| Some((parts.pos.get_opt(id)?.0, bomb_fuse(state, id)?)))
+        .collect()
+}
+
+pub fn bomb_delivery(state: &State, ability: Ability) -> Delivery {
+    state
+        .ability_definitions()
+        .get(ability)
+        .map_or(Delivery::default(), |definition| definition.delivery)
+}
+
+/// Where a bomb rolled from `from` towards `to` stops: at `to` or in front of the first blocker
+/// or the water.
+///
+/// `None` if the tiles aren't on a straight line or there's no room for the bomb at all.
+pub fn rolled_bomb_pos(state: &State, from: PosHex, to: PosHex) -> Option<PosHex> {
+    let dir = map::Dir::approximate_from_to(from, to)?;
+    let distance = map::distance_hex(from, to).0;
+    let mut pos = from;
+    for _ in 0..distance {
+        pos = map::Dir::get_neighbor_pos(pos, dir);
+    }
+    if pos != to {
+        return None;
+    }
+    let mut landing_pos = from;
+    for _ in 0..distance {
+        let next = map::Dir::get_neighbor_pos(landing_pos, dir);
+        if !state.map().is_inboard(next) || is_tile_blocked(state, next) || is_water(state, next) {
+            break;
+        }
+        landing_pos = next;
+    }
+    if landing_pos == from {
+        None
+    } else {
+        Some(landing_pos)
+    }
+}
+
+/// The agent's attack distance with the weather and the darkness taken into account.
+///
+/// Melee attacks are never affected.
+pub fn attack_distance(state: &State, id: Id) -> map::Distance {
+    attack_distance_from(state, id, state.parts().pos.get(id).0)
+}
+
+/// The attack distance that the agent would have if it was at the position.
+pub fn attack_distance_from(state: &State, id: Id, pos: PosHex) -> map::Distance {
+    let distance = state.parts().agent.get(id).attack_distance.0;
+    let mut penalty = 0;
+    if state.scenario().weather == Weather::Fog {
+        penalty += 1;
+    }
+    if !is_lit(state, pos) {
+        penalty += 1;
+    }
+    map::Distance((distance - penalty).max(distance.min(1)))
+}
+
+/// How many columns of tiles the player can retreat from.
+const HOME_EDGE_DEPTH: i32 = 2;
+
+/// Distance from the position to the map edge the player's agents are deployed at.
+pub fn distance_to_home_edge(state: &State, player_id: PlayerId, pos: PosHex) -> i32 {
+    let radius = state.map().radius().0;
+    if is_on_human_side(state, player_id) {
+        radius + pos.q
+    } else {
+        radius - pos.q
+    }
+}
+
+pub fn is_at_home_edge(state: &State, player_id: PlayerId, pos: PosHex) -> bool {
+    distance_to_home_edge(state, player_id, pos) < HOME_EDGE_DEPTH
+}
+
+pub fn are_allies(state: &State, a: PlayerId, b: PlayerId) -> bool {
+    is_on_human_side(state, a) == is_on_human_side(state, b)
+}
+
+/// The position first, then the extra tiles of a large object.
+pub fn footprint_tiles(pos: PosHex, footprint: &[Dir]) -> Vec<PosHex> {
+    let extra_tiles = footprint.iter().map(|&dir| Dir::get_neighbor_pos(pos, dir));
+    iter::once(pos).chain(extra_tiles).collect()
+}
+
+pub fn footprint(state: &State, id: Id) -> &[Dir] {
+    match state.parts().footprint.get_opt(id) {
+        Some(footprint) => &footprint.0,
+        None => &[],
+    }
+}
+
+pub fn prototype_footprint(state: &State, prototype: &ObjType) -> Vec<Dir> {
+    for component in state.prototype_for(prototype) {
+        if let Component::Footprint(footprint) = component {
+            return footprint.0;
+        }
+    }
+    Vec::new()
+}
+
+/// The tiles that the object would take if it was standing at the position.
+pub fn footprint_at(state: &State, id: Id, pos: PosHex) -> Vec<PosHex> {
+    footprint_tiles(pos, footprint(state, id))
+}
+
+pub fn object_tiles(state: &State, id: Id) -> Vec<PosHex> {
+    footprint_at(state, id, state.parts().pos.get(id).0)
+}
+
+/// Is the tile one of the object's tiles?
+pub fn occupies(state: &State, id: Id, pos: PosHex) -> bool {
+    let object_pos = state.parts().pos.get(id).0;
+    object_pos == pos
+        || footprint(state, id)
+            .iter()
+            .any(|&dir| Dir::get_neighbor_pos(object_pos, dir) == pos)
+}
+
+/// The closest pair of the objects' tiles, the object's own tile goes first.
+pub fn closest_tiles(state: &State, id: Id, other_id: Id) -> (PosHex, PosHex) {
+    let other_tiles = object_tiles(state, other_id);
+    object_tiles(state, id)
+        .into_iter()
+        .flat_map(|tile| {
+            other_tiles
+                .iter()
+                .map(move |&other_tile| (tile, other_tile))
+        })
+        .min_by_key(|&(tile, other_tile)| map::distance_hex(tile, other_tile))
+        .expect("Objects take at least one tile")
+}
+
+pub fn distance_between(state: &State, id: Id, other_id: Id) -> map::Distance {
+    let (tile, other_tile) = closest_tiles(state, id, other_id);
+    map::distance_hex(tile, other_tile)
+}
+
+pub fn distance_to(state: &State, id: Id, pos: PosHex) -> map::Distance {
+    object_tiles(state, id)
+        .into_iter()
+        .map(|tile| map::distance_hex(tile, pos))
+        .min()
+        .expect("Objects take at least one tile")
+}
+
+pub fn is_tile_blocked(state: &State, pos: PosHex) -> bool {
+    assert!(state.map().is_inboard(pos));
+    state
+        .parts()
+        .blocker
+        .ids()
+        .any(|id| occupies(state, id, pos))
+}
+
+/// Is the tile taken by some blocker other than the object itself?
+pub fn is_tile_blocked_for(state: &State, id: Id, pos: PosHex) -> bool {
+    assert!(state.map().is_inboard(pos));
+    state
+        .parts()
+        .blocker
+        .ids()
+        .any(|other_id| other_id != id && occupies(state, other_id, pos))
+}
+
+/// Can the object stand at the position without overlapping the other blockers?
+pub fn fits_at(state: &State, id: Id, pos: PosHex) -> bool {
+    footprint_at(state, id, pos)
+        .into_iter()
+        .all(|tile| state.map().is_inboard(tile) && !is_tile_blocked_for(state, id, tile))
 }
 
 pub fn is_tile_plain_and_completely_free(state: &State, pos: PosHex) -> bool {
@@ -36,25 +370,77 @@ pub fn is_tile_plain_and_completely_free(state: &State, pos: PosHex) -> bool {
         return false;
     }
     for id in state.parts().pos.ids() {
-        if state.parts().pos.get(id).0 == pos {
+        if occupies(state, id, pos) {
             return false;
         }
     }
     true
 }
 
+/// Tiles where the human player places their agents before the first turn.
+pub fn deployment_zone(state: &State) -> &[PosHex] {
+    let scenario = state.scenario();
+    match &scenario.deployment_zone {
+        Some(name) => &scenario.region(name).expect("No deployment zone").tiles,
+        None => &[],
+    }
+}
+
+pub fn free_deployment_tiles(state: &State) -> Vec<PosHex> {
+    deployment_zone(state)
+        .iter()
+        .copied()
+        .filter(|&pos| is_tile_completely_free(state, pos) && !is_water(state, pos))
+        .collect()
+}
+
+/// Does the human player still have to place some agents before the first turn?
+///
+/// The agents that don't fit into the deployment zone stay out of the battle.
+pub fn is_deploying(state: &State) -> bool {
+    !state.deployment().is_empty() && !free_deployment_tiles(state).is_empty()
+}
+
+/// Which of the waiting agents this command places, if it's a deployment at all.
+pub fn deployment_index(state: &State, command: &command::Create) -> Option<usize> {
+    if command.owner != Some(PlayerId(0)) {
+        return None;
+    }
+    state.deployment().iter().position(|group| {
+        group.typename == command.prototype && group.extra_components == command.extra_components
+    })
+}
+
+/// A command that places the next waiting agent at the tile.
+pub fn deployment_command(state: &State, pos: PosHex) -> Option<command::Command> {
+    let group = state.deployment().first()?;
+    let command = command::Create {
+        prototype: group.typename.clone(),
+        pos,
+        owner: Some(PlayerId(0)),
+        extra_components: group.extra_components.clone(),
+    };
+    Some(command.into())
+}
+
 pub fn is_tile_completely_free(state: &State, pos: PosHex) -> bool {
     if !state.map().is_inboard(pos) {
         return false;
     }
     for id in state.parts().pos.ids() {
-        if state.parts().pos.get(id).0 == pos {
+        if occupies(state, id, pos) {
             return false;
         }
     }
     true
 }
 
+/// Non-agent objects with some strength, like boulders, can be attacked and destroyed.
+pub fn is_destructible(state: &State, id: Id) -> bool {
+    let parts = state.parts();
+    parts.agent.get_opt(id).is_none() && parts.strength.get_opt(id).is_some()
+}
+
 pub fn is_lasting_effect_over(state: &State, id: Id, timed_effect: &effect::Timed) -> bool {
     if let effect::Lasting::Poison = timed_effect.effect {
         let strength = state.parts().strength.get(id).strength;
@@ -71,7 +457,7 @@ pub fn check_enemies_around(state: &State, pos: PosHex, player_id: PlayerId) ->
         let neighbor_pos = map::Dir::get_neighbor_pos(pos, dir);
         if let Some(id) = agent_id_at_opt(state, neighbor_pos) {
             let neighbor_player_id = state.parts().belongs_to.get(id).0;
-            if neighbor_player_id != player_id {
+            if !are_allies(state, neighbor_player_id, player_id) {
                 return true;
             }
         }
@@ -81,7 +467,7 @@ pub fn check_enemies_around(state: &State, pos: PosHex, player_id: PlayerId) ->
 
 pub fn ids_at(state: &State, pos: PosHex) -> Vec<Id> {
     let i = state.parts().pos.ids();
-    i.filter(|&id| state.parts().pos.get(id).0 == pos).collect()
+    i.filter(|&id| occupies(state, id, pos)).collect()
 }
 
 pub fn obj_with_passive_ability_at(
@@ -125,12 +511,17 @@ pub fn agent_id_at_opt(state: &State, pos: PosHex) -> Option<Id> {
 
 pub fn agent_ids_at(state: &State, pos: PosHex) -> Vec<Id> {
     let i = state.parts().agent.ids();
-    i.filter(|&id| state.parts().pos.get(id).0 == pos).collect()
+    i.filter(|&id| occupies(state, id, pos)).collect()
 }
 
 pub fn blocker_ids_at(state: &State, pos: PosHex) -> Vec<Id> {
     let i = state.parts().blocker.ids();
-    i.filter(|&id| state.parts().pos.get(id).0 == pos).collect()
+    i.filter(|&id| occupies(state, id, pos)).collect()
+}
+
+pub fn corpse_id_at_opt(state: &State, pos: PosHex) -> Option<Id> {
+    let mut i = state.parts().corpse.ids();
+    i.find(|&id| state.parts().pos.get(id).0 == pos)
 }
 
 pub fn players_agent_ids(state: &State, player_id: PlayerId) -> Vec<Id> {
@@ -141,15 +532,136 @@ pub fn players_agent_ids(state: &State, player_id: PlayerId) -> Vec<Id> {
 
 pub fn enemy_agent_ids(state: &State, player_id: PlayerId) -> Vec<Id> {
     let i = state.parts().agent.ids();
-    i.filter(|&id| !is_agent_belong_to(state, player_id, id))
+    i.filter(|&id| !are_allies(state, player_id, state.parts().belongs_to.get(id).0))
         .collect()
 }
 
-pub fn free_neighbor_positions(state: &State, origin: PosHex, count: i32) -> Vec<PosHex> {
+/// The enemies that the player knows about: the hidden ones are left out.
+pub fn visible_enemy_agent_ids(state: &State, player_id: PlayerId) -> Vec<Id> {
+    let mut ids = enemy_agent_ids(state, player_id);
+    ids.retain(|&id| !is_hidden(state, id));
+    ids
+}
+
+/// Agents of the player and of all their allies.
+pub fn allied_agent_ids(state: &State, player_id: PlayerId) -> Vec<Id> {
+    let i = state.parts().agent.ids();
+    i.filter(|&id| are_allies(state, player_id, state.parts().belongs_to.get(id).0))
+        .collect()
+}
+
+pub fn is_thicket(state: &State, pos: PosHex) -> bool {
+    state.map().is_inboard(pos) && state.map().tile(pos) == TileType::Thicket
+}
+
+/// Are any enemy agents standing next to any tile of the agent?
+pub fn is_next_to_enemy(state: &State, id: Id) -> bool {
+    let player_id = state.parts().belongs_to.get(id).0;
+    enemy_agent_ids(state, player_id)
+        .into_iter()
+        .any(|enemy_id| distance_between(state, id, enemy_id) <= map::Distance(1))
+}
+
+pub fn is_water(state: &State, pos: PosHex) -> bool {
+    state.map().is_inboard(pos) && state.map().tile(pos) == TileType::Water
+}
+
+/// The effects that are over stay till the end of the turn, but don't work anymore.
+pub fn has_lasting_effect(state: &State, id: Id, effect: effect::Lasting) -> bool {
+    state.parts().effects.get_opt(id).is_some_and(|effects| {
+        effects
+            .0
+            .iter()
+            .any(|timed| timed.effect == effect && !timed.duration.is_over())
+    })
+}
+
+/// Less than half of the base strength is left.
+pub fn is_badly_wounded(state: &State, id: Id) -> bool {
+    let strength = state.parts().strength.get(id);
+    strength.strength.0 * 2 < strength.base_strength.0
+}
+
+pub fn is_injured(state: &State, id: Id) -> bool {
+    has_lasting_effect(state, id, effect::Lasting::Injured)
+}
+
+/// The attacks that the agent gets every turn, an injury takes one of several away.
+pub fn base_attacks(state: &State, id: Id) -> battle::Attacks {
+    let base_attacks = state.parts().agent.get(id).base_attacks;
+    if is_injured(state, id) && base_attacks.0 > 1 {
+        battle::Attacks(base_attacks.0 - 1)
+    } else {
+        base_attacks
+    }
+}
+
+/// The hidden agents can't be seen or targeted by their enemies.
+pub fn is_hidden(state: &State, id: Id) -> bool {
+    has_lasting_effect(state, id, effect::Lasting::Hidden)
+}
+
+/// Can the player see the object? Everyone sees their own and their allies' agents.
+pub fn is_hidden_from(state: &State, id: Id, player_id: PlayerId) -> bool {
+    let is_ally = state
+        .parts()
+        .belongs_to
+        .get_opt(id)
+        .is_some_and(|owner| are_allies(state, player_id, owner.0));
+    !is_ally && is_hidden(state, id)
+}
+
+/// The walkers under the `Phased` effect move like the phasers.
+pub fn movement_type(state: &State, id: Id) -> MovementType {
+    let movement_type = match state.parts().agent.get_opt(id) {
+        Some(agent) => agent.movement_type,
+        None => return MovementType::Walk,
+    };
+    let is_phased = has_lasting_effect(state, id, effect::Lasting::Phased);
+    if movement_type == MovementType::Walk && is_phased {
+        MovementType::Phase
+    } else {
+        movement_type
+    }
+}
+
+pub fn is_flying(state: &State, id: Id) -> bool {
+    movement_type(state, id) == MovementType::Fly
+}
+
+/// Can the agent moving this way pass the blocker's tile without stopping there?
+pub fn can_pass_over(state: &State, id: Id, movement_type: MovementType, blocker_id: Id) -> bool {
+    match movement_type {
+        MovementType::Walk => false,
+        MovementType::Fly => true,
+        MovementType::Phase => {
+            let parts = state.parts();
+            let player_id = parts.belongs_to.get(id).0;
+            parts.agent.get_opt(blocker_id).is_some()
+                && parts
+                    .belongs_to
+                    .get_opt(blocker_id)
+                    .is_some_and(|owner| !are_allies(state, player_id, owner.0))
+        }
+    }
+}
+
+/// Everything that doesn't fly drowns in the water.
+/// The large objects drown if any of their tiles is water.
+pub fn is_drowning_at(state: &State, id: Id, pos: PosHex) -> bool {
+    !is_flying(state, id)
+        && footprint_at(state, id, pos)
+            .into_iter()
+            .any(|tile| is_water(state, tile))
+}
+
+pub fn free_neighbor_positions(state: &mut State, origin: PosHex, count: i32) -> Vec<PosHex> {
     let mut positions = Vec::new();
-    for dir in utils::shuffle_vec(map::dirs().collect()) {
+    let mut dirs: Vec<_> = map::dirs().collect();
+    dirs.shuffle(state.rng());
+    for dir in dirs {
         let pos = map::Dir::get_neighbor_pos(origin, dir);
-        if state.map().is_inboard(pos) && !is_tile_blocked(state, pos) {
+        if state.map().is_inboard(pos) && !is_tile_blocked(state, pos) && !is_water(state, pos) {
             positions.push(pos);
             if positions.len() == count as usize {
                 break;
@@ -159,6 +671,35 @@ pub fn free_neighbor_positions(state: &State, origin: PosHex, count: i32) -> Vec
     positions
 }
 
+/// Routed agents have no morale left.
+pub fn is_routed(state: &State, id: Id) -> bool {
+    match state.parts().morale.get_opt(id) {
+        Some(morale) => morale.morale <= 0,
+        None => false,
+    }
+}
+
+/// Alive creatures summoned by the given summoner.
+pub fn summoned_agent_ids(state: &State, summoner_id: Id) -> Vec<Id> {
+    let summoned_by = &state.parts().summoned_by;
+    summoned_by
+        .ids()
+        .filter(|&id| summoned_by.get(id).0 == summoner_id)
+        .collect()
+}
+
+/// How many creatures the summoner can summon right now, respecting its limit.
+pub fn summon_count(state: &State, summoner_id: Id) -> u32 {
+    let summoner = state.parts().summoner.get(summoner_id);
+    match summoner.limit {
+        Some(limit) => {
+            let alive = summoned_agent_ids(state, summoner_id).len() as u32;
+            summoner.count.min(limit.saturating_sub(alive))
+        }
+        None => summoner.count,
+    }
+}
+
 pub fn sort_agent_ids_by_distance_to_enemies(state: &State, ids: &mut [Id]) {
     ids.sort_unstable_by_key(|&id| {
         let agent_player_id = state.parts().belongs_to.get(id).0;
@@ -181,13 +722,133 @@ pub fn get_armor(state: &State, id: Id) -> Strength {
     parts.armor.get_opt(id).map(|v| v.armor).unwrap_or(default)
 }
 
-pub fn players_agent_types(state: &State, player_id: PlayerId) -> Vec<ObjType> {
+pub fn get_resistance(state: &State, id: Id, damage_type: DamageType) -> Strength {
+    let parts = state.parts();
+    let default = Strength(0);
+    parts
+        .resistances
+        .get_opt(id)
+        .map(|v| v.resistance(damage_type))
+        .unwrap_or(default)
+}
+
+pub fn rider(state: &State, mount_id: Id) -> Option<&component::Rider> {
+    state.parts().mount.get_opt(mount_id)?.rider.as_ref()
+}
+
+pub fn rider_id(state: &State, mount_id: Id) -> Option<Id> {
+    rider(state, mount_id).map(|rider| rider.id)
+}
+
+/// The riders are off the map, but they're still fighting for the player.
+fn players_riders(state: &State, player_id: PlayerId) -> Vec<&component::Rider> {
     players_agent_ids(state, player_id)
         .into_iter()
-        .map(|id| state.parts().meta.get(id).name.clone())
+        .filter_map(|id| rider(state, id))
+        .collect()
+}
+
+fn rider_component<T>(
+    rider: &component::Rider,
+    f: impl Fn(&Component) -> Option<&T>,
+) -> Option<&T> {
+    rider.components.iter().find_map(f)
+}
+
+fn rider_veteran(rider: &component::Rider) -> Option<&component::Veteran> {
+    rider_component(rider, |component| match component {
+        Component::Veteran(veteran) => Some(veteran),
+        _ => None,
+    })
+}
+
+/// The adjacent mounts of the same player that the agent can climb onto.
+pub fn free_mounts_around(state: &State, id: Id) -> Vec<Id> {
+    let player_id = state.parts().belongs_to.get(id).0;
+    players_agent_ids(state, player_id)
+        .into_iter()
+        .filter(|&mount_id| {
+            let is_free = state
+                .parts()
+                .mount
+                .get_opt(mount_id)
+                .is_some_and(|mount| mount.rider.is_none());
+            is_free && mount_id != id && distance_between(state, id, mount_id) == map::Distance(1)
+        })
         .collect()
 }
 
+/// The first free tile around the mount that its rider can get off to.
+pub fn dismount_pos(state: &State, mount_id: Id) -> Option<PosHex> {
+    object_tiles(state, mount_id)
+        .into_iter()
+        .flat_map(|pos| map::dirs().map(move |dir| Dir::get_neighbor_pos(pos, dir)))
+        .find(|&pos| {
+            state.map().is_inboard(pos) && !is_tile_blocked(state, pos) && !is_water(state, pos)
+        })
+}
+
+pub fn players_agent_types(state: &State, player_id: PlayerId) -> Vec<ObjType> {
+    let mut types: Vec<_> = players_agent_ids(state, player_id)
+        .into_iter()
+        .map(|id| state.parts().meta.get(id).name.clone())
+        .collect();
+    for rider in players_riders(state, player_id) {
+        let meta = rider_component(rider, |component| match component {
+            Component::Meta(meta) => Some(meta),
+            _ => None,
+        });
+        types.extend(meta.map(|meta| meta.name.clone()));
+    }
+    types
+}
+
+pub fn players_veterans(state: &State, player_id: PlayerId) -> Vec<component::Veteran> {
+    let mut veterans: Vec<_> = players_agent_ids(state, player_id)
+        .into_iter()
+        .filter_map(|id| state.parts().veteran.get_opt(id).cloned())
+        .collect();
+    for rider in players_riders(state, player_id) {
+        veterans.extend(rider_veteran(rider).cloned());
+    }
+    veterans
+}
+
+pub fn players_veteran_charges(state: &State, player_id: PlayerId) -> HashMap<usize, Charges> {
+    let parts = state.parts();
+    let mut charges: HashMap<_, _> = players_agent_ids(state, player_id)
+        .into_iter()
+        .filter_map(|id| {
+            let veteran = parts.veteran.get_opt(id)?;
+            let charges = parts.charges.get_opt(id)?;
+            Some((veteran.index, charges.clone()))
+        })
+        .collect();
+    for rider in players_riders(state, player_id) {
+        let rider_charges = rider_component(rider, |component| match component {
+            Component::Charges(charges) => Some(charges),
+            _ => None,
+        });
+        if let (Some(veteran), Some(rider_charges)) = (rider_veteran(rider), rider_charges) {
+            charges.insert(veteran.index, rider_charges.clone());
+        }
+    }
+    charges
+}
+
+/// The first tier for the objects that haven't upgraded the ability.
+pub fn ability_tier(state: &State, id: Id, ability: Ability) -> Tier {
+    match state.parts().ability_tiers.get_opt(id) {
+        Some(tiers) => tiers.tier(ability),
+        None => Tier::default(),
+    }
+}
+
+pub fn ability_params(state: &State, id: Id, ability: Ability) -> TierParams {
+    let tier = ability_tier(state, id, ability);
+    state.ability_definitions().params(ability, tier)
+}
+
 pub fn can_agent_use_ability(state: &State, id: Id, ability: &Ability) -> bool {
     let parts = state.parts();
     let agent_player_id = parts.belongs_to.get(id).0;
@@ -196,6 +857,67 @@ pub fn can_agent_use_ability(state: &State, id: Id, ability: &Ability) -> bool {
     let is_player_agent = agent_player_id == state.player_id();
     let abilities = &parts.abilities.get(id).0;
     let r_ability = abilities.iter().find(|r| &r.ability == ability).unwrap();
-    let is_ready = r_ability.status == ability::Status::Ready;
-    is_player_agent && is_ready && has_actions
+    let is_ready = r_ability.status.is_ready();
+    let is_charged = parts
+        .charges
+        .get_opt(id)
+        .is_none_or(|charges| !charges.is_exhausted(*ability));
+    let has_stamina = parts.stamina.get_opt(id).is_none_or(|stamina| {
+        stamina
+            .cost(*ability)
+            .is_none_or(|cost| stamina.stamina >= cost)
+    });
+    is_player_agent && is_ready && is_charged && has_stamina && has_actions
+}
+
+/// A broken rule of the battle state, see `check_invariants`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InvariantError {
+    ObjectOutsideOfMap(Id),
+
+    /// Several blockers (e.g. an agent and a boulder) share the tile.
+    SharedTile(PosHex),
+
+    /// Agents always have a position and an owner.
+    IncompleteAgent(Id),
+
+    NegativeStrength(Id),
+
+    /// Some of the agent's moves, attacks or jokers are below zero.
+    NegativeActions(Id),
+}
+
+/// Checks the rules that must hold after any executed command.
+pub fn check_invariants(state: &State) -> Result<(), InvariantError> {
+    let parts = state.parts();
+    for id in parts.pos.ids() {
+        if !state.map().is_inboard(parts.pos.get(id).0) {
+            return Err(InvariantError::ObjectOutsideOfMap(id));
+        }
+    }
+    for id in parts.blocker.ids() {
+        let pos = parts.pos.get(id).0;
+        if blocker_ids_at(state, pos).len() > 1 {
+            return Err(InvariantError::SharedTile(pos));
+        }
+    }
+    for id in parts.strength.ids() {
+        if parts.strength.get(id).strength < Strength(0) {
+            return Err(InvariantError::NegativeStrength(id));
+        }
+    }
+    for id in parts.agent.ids() {
+        let is_complete = parts.pos.get_opt(id).is_some() && parts.belongs_to.get_opt(id).is_some();
+        if !is_complete {
+            return Err(InvariantError::IncompleteAgent(id));
+        }
+        let agent = parts.agent.get(id);
+        let zero_moves = battle::Moves(0);
+        let zero_attacks = battle::Attacks(0);
+        let zero_jokers = battle::Jokers(0);
+        if agent.moves < zero_moves || agent.attacks < zero_attacks || agent.jokers < zero_jokers {
+            return Err(InvariantError::NegativeActions(id));
+        }
+    }
+    Ok(())
 }
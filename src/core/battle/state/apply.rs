@@ -1,15 +1,24 @@
 use log::trace;
 
-use crate::core::battle::{
-    ability::{self, Ability},
-    component::{self, Component, Parts, PlannedAbility},
-    effect::{self, Duration, Effect},
-    event::{self, ActiveEvent, Event},
-    state, Attacks, Id, Jokers, Moves, Phase, PlayerId, State, Strength,
+use crate::core::{
+    battle::{
+        ability::{Ability, AbilityEffect},
+        command::JokerUse,
+        component::{self, Component, Parts, PlannedAbility},
+        decal::{self, Decal},
+        effect::{self, Duration, Effect, StackPolicy},
+        event::{self, ActiveEvent, Event},
+        state::{self, DeathCause, FallenVeteran},
+        Attacks, Id, Jokers, Moves, Phase, PlayerId, State, Strength,
+    },
+    map::{Dir, PosHex},
 };
 
 pub fn apply(state: &mut State, event: &Event) {
     trace!("event::apply: {:?}", event);
+    count_veteran_kills(state, event);
+    record_fallen_veterans(state, event);
+    update_miss_streaks(state, event);
     apply_event(state, event);
     for &(obj_id, ref effects) in &event.instant_effects {
         for effect in effects {
@@ -28,6 +37,68 @@ pub fn apply(state: &mut State, event: &Event) {
     }
 }
 
+fn count_veteran_kills(state: &mut State, event: &Event) {
+    let killer_id = match event.active_event {
+        ActiveEvent::Attack(ref ev) => ev.attacker_id,
+        ActiveEvent::UseAbility(ref ev) => ev.id,
+        _ => return,
+    };
+    let parts = state.parts_mut();
+    for &(id, ref effects) in &event.instant_effects {
+        for effect in effects {
+            if let Effect::Kill(_) = effect {
+                if parts.agent.get_opt(id).is_none() {
+                    continue;
+                }
+                if let Some(veteran) = parts.veteran.get_opt_mut(killer_id) {
+                    veteran.kills += 1;
+                }
+            }
+        }
+    }
+}
+
+fn update_miss_streaks(state: &mut State, event: &Event) {
+    let ev = match event.active_event {
+        ActiveEvent::Attack(ref ev) => ev,
+        _ => return,
+    };
+    let is_miss = event.instant_effects.iter().any(|(id, effects)| {
+        *id == ev.target_id && effects.iter().any(|e| matches!(e, Effect::Dodge(_)))
+    });
+    let player_id = state.parts().belongs_to.get(ev.attacker_id).0;
+    let streak = if is_miss {
+        state.miss_streak(player_id) + 1
+    } else {
+        0
+    };
+    state.set_miss_streak(player_id, streak);
+}
+
+fn death_cause(state: &State, event: &ActiveEvent) -> DeathCause {
+    let name = |id| state.parts().meta.get(id).name.clone();
+    match *event {
+        ActiveEvent::Attack(ref ev) => DeathCause::Attack(name(ev.attacker_id)),
+        ActiveEvent::UseAbility(ref ev) => DeathCause::Ability(name(ev.id), ev.ability),
+        ActiveEvent::UsePassiveAbility(ref ev) => DeathCause::PassiveAbility(ev.ability),
+        ActiveEvent::EffectTick(ref ev) => DeathCause::Effect(ev.effect),
+        _ => DeathCause::Unknown,
+    }
+}
+
+fn record_fallen_veterans(state: &mut State, event: &Event) {
+    for &(id, ref effects) in &event.instant_effects {
+        for effect in effects {
+            if let Effect::Kill(_) = effect {
+                if let Some(&veteran) = state.parts().veteran.get_opt(id) {
+                    let cause = death_cause(state, &event.active_event);
+                    state.add_fallen_veteran(FallenVeteran { veteran, cause });
+                }
+            }
+        }
+    }
+}
+
 fn apply_event(state: &mut State, event: &Event) {
     match event.active_event {
         ActiveEvent::Create => {}
@@ -40,6 +111,21 @@ fn apply_event(state: &mut State, event: &Event) {
         ActiveEvent::UsePassiveAbility(ref ev) => apply_event_use_passive_ability(state, ev),
         ActiveEvent::EffectTick(ref ev) => apply_event_effect_tick(state, ev),
         ActiveEvent::EffectEnd(ref ev) => apply_event_effect_end(state, ev),
+        ActiveEvent::ConvertJoker(ref ev) => apply_event_convert_joker(state, ev),
+        ActiveEvent::Mount(ref ev) => apply_event_mount(state, ev),
+        ActiveEvent::Dismount(ref ev) => apply_event_dismount(state, ev),
+        ActiveEvent::SetTiles(ref ev) => apply_event_set_tiles(state, ev),
+        ActiveEvent::Capture(ref ev) => apply_event_capture(state, ev),
+        ActiveEvent::BeginBossPhase(ref ev) => apply_event_begin_boss_phase(state, ev),
+        ActiveEvent::Unsummon(_)
+        | ActiveEvent::Reveal(_)
+        | ActiveEvent::MoraleLoss(_)
+        | ActiveEvent::LeaveCorpse(_)
+        | ActiveEvent::Injury(_)
+        | ActiveEvent::Reaction(_)
+        | ActiveEvent::Aura(_)
+        | ActiveEvent::SuddenDeath(_)
+        | ActiveEvent::ScriptTriggered(_) => {}
     }
 }
 
@@ -50,7 +136,102 @@ fn add_components(state: &mut State, id: Id, components: &[Component]) {
     }
 }
 
+/// Everything but the position, the object is removed from the parts.
+fn take_components(parts: &mut Parts, id: Id) -> Vec<Component> {
+    let mut components = Vec::new();
+    macro_rules! take {
+        ($($component:ident),*) => {
+            $(
+                if let Some(component) = parts.$component.get_opt(id) {
+                    components.push(component.clone().into());
+                }
+            )*
+        };
+    }
+    take!(
+        strength,
+        armor,
+        resistances,
+        facing,
+        meta,
+        belongs_to,
+        agent,
+        blocker,
+        footprint,
+        mount,
+        abilities,
+        passive_abilities,
+        ability_tiers,
+        effects,
+        reactions,
+        auras,
+        schedule,
+        summoner,
+        summoned_by,
+        morale,
+        stamina,
+        veteran,
+        persona,
+        charges,
+        boss,
+        personality
+    );
+    parts.remove(id);
+    components
+}
+
+fn spend_move(agent: &mut component::Agent) {
+    if agent.moves.0 > 0 {
+        agent.moves.0 -= 1;
+    } else {
+        agent.jokers.0 -= 1;
+    }
+    assert!(agent.moves >= Moves(0));
+    assert!(agent.jokers >= Jokers(0));
+}
+
+fn apply_event_mount(state: &mut State, event: &event::Mount) {
+    let parts = state.parts_mut();
+    let agent = parts.agent.get_mut(event.id);
+    spend_move(agent);
+    let move_points = agent.move_points;
+    let components = take_components(parts, event.id);
+    parts.agent.get_mut(event.mount_id).move_points.0 += move_points.0;
+    let rider = component::Rider {
+        id: event.id,
+        components,
+    };
+    parts.mount.get_mut(event.mount_id).rider = Some(rider);
+}
+
+fn apply_event_dismount(state: &mut State, event: &event::Dismount) {
+    spend_move(state.parts_mut().agent.get_mut(event.mount_id));
+    drop_rider(state, event.mount_id, event.pos);
+}
+
+/// Returns the rider to the map, the mount is left with its own move points.
+fn drop_rider(state: &mut State, mount_id: Id, pos: PosHex) {
+    let parts = state.parts_mut();
+    let rider = match parts.mount.get_mut(mount_id).rider.take() {
+        Some(rider) => rider,
+        None => return,
+    };
+    for component in rider.components {
+        if let Component::Agent(agent) = &component {
+            parts.agent.get_mut(mount_id).move_points.0 -= agent.move_points.0;
+        }
+        add_component(parts, rider.id, component);
+    }
+    parts.pos.insert(rider.id, component::Pos(pos));
+}
+
 fn apply_event_move_to(state: &mut State, event: &event::MoveTo) {
+    if let Some(step) = event.path.steps().last() {
+        let parts = state.parts_mut();
+        if let Some(facing) = parts.facing.get_opt_mut(event.id) {
+            facing.0 = Dir::get_dir_from_to(step.from, step.to);
+        }
+    }
     let parts = state.parts_mut();
     let agent = parts.agent.get_mut(event.id);
     let pos = parts.pos.get_mut(event.id);
@@ -65,6 +246,8 @@ fn apply_event_move_to(state: &mut State, event: &event::MoveTo) {
 }
 
 fn apply_event_attack(state: &mut State, event: &event::Attack) {
+    let target_pos = state.parts().pos.get(event.target_id).0;
+    turn_to(state, event.attacker_id, target_pos);
     let parts = state.parts_mut();
     let agent = parts.agent.get_mut(event.attacker_id);
     if agent.attacks.0 > 0 {
@@ -76,6 +259,16 @@ fn apply_event_attack(state: &mut State, event: &event::Attack) {
     assert!(agent.jokers >= Jokers(0));
 }
 
+fn apply_event_convert_joker(state: &mut State, event: &event::ConvertJoker) {
+    let agent = state.parts_mut().agent.get_mut(event.id);
+    agent.jokers.0 -= 1;
+    match event.into {
+        JokerUse::Move => agent.moves.0 += 1,
+        JokerUse::Attack => agent.attacks.0 += 1,
+    }
+    assert!(agent.jokers >= Jokers(0));
+}
+
 fn apply_event_end_turn(state: &mut State, event: &event::EndTurn) {
     // Update attacks
     {
@@ -142,10 +335,24 @@ fn update_lasting_effects_duration(state: &mut State) {
 
 fn reset_moves_and_attacks(state: &mut State, player_id: PlayerId) {
     for id in state::players_agent_ids(state, player_id) {
+        let is_routed = state::is_routed(state, id);
+        let base_attacks = state::base_attacks(state, id);
         let agent = state.parts_mut().agent.get_mut(id);
         agent.moves = agent.base_moves;
-        agent.attacks = agent.base_attacks;
-        agent.jokers = agent.base_jokers;
+        agent.attacks = base_attacks;
+        agent.jokers = if is_routed {
+            Jokers(0)
+        } else {
+            agent.base_jokers
+        };
+    }
+}
+
+fn regenerate_stamina(state: &mut State, player_id: PlayerId) {
+    for id in state::players_agent_ids(state, player_id) {
+        if let Some(stamina) = state.parts_mut().stamina.get_opt_mut(id) {
+            stamina.stamina = (stamina.stamina + stamina.regeneration).min(stamina.base_stamina);
+        }
     }
 }
 
@@ -154,28 +361,81 @@ fn apply_event_end_battle(state: &mut State, event: &event::EndBattle) {
 }
 
 fn apply_event_begin_turn(state: &mut State, event: &event::BeginTurn) {
+    if event.player_id == PlayerId(0) {
+        state.add_round();
+    }
     state.set_player_id(event.player_id);
     update_lasting_effects_duration(state);
     reset_moves_and_attacks(state, event.player_id);
+    regenerate_stamina(state, event.player_id);
     apply_lasting_effects(state);
     update_cooldowns(state, event.player_id);
     tick_planned_abilities(state);
 }
 
+fn add_decal_at_object(state: &mut State, id: Id, kind: decal::Kind) {
+    if let Some(pos) = state.parts().pos.get_opt(id) {
+        let pos = pos.0;
+        state.add_decal(Decal { pos, kind });
+    }
+}
+
+fn add_ability_decal(state: &mut State, event: &event::UseAbility) {
+    let kind = match event.ability {
+        Ability::ExplodeFire => decal::Kind::Scorch,
+        Ability::ExplodePush | Ability::ExplodeDamage | Ability::ExplodePoison => {
+            decal::Kind::Crater
+        }
+        _ => return,
+    };
+    add_decal_at_object(state, event.id, kind);
+}
+
 fn apply_event_use_ability(state: &mut State, event: &event::UseAbility) {
+    add_ability_decal(state, event);
     let id = event.id;
+    turn_to(state, id, event.pos);
+    if state.parts().agent.get_opt(id).is_some() {
+        let player_id = state.parts().belongs_to.get(id).0;
+        state.add_ability_use(player_id, event.ability);
+    }
+    let cooldown = state::ability_params(state, id, event.ability).cooldown;
+    let extra_attacks: i32 =
+        state
+            .ability_definitions()
+            .get(event.ability)
+            .map_or(0, |definition| {
+                definition
+                    .effects
+                    .iter()
+                    .map(|effect| match *effect {
+                        AbilityEffect::Attacks(attacks) => attacks,
+                        _ => 0,
+                    })
+                    .sum()
+            });
     let parts = state.parts_mut();
-    if let Some(abilities) = parts.abilities.get_opt_mut(id) {
+    let cost = parts
+        .stamina
+        .get_opt(id)
+        .and_then(|stamina| stamina.cost(event.ability));
+    if let Some(cost) = cost {
+        let stamina = parts.stamina.get_mut(id);
+        assert!(stamina.stamina >= cost);
+        stamina.stamina -= cost;
+    } else if let Some(abilities) = parts.abilities.get_opt_mut(id) {
         for r_ability in &mut abilities.0 {
             if r_ability.ability == event.ability {
-                let cooldown = r_ability.ability.base_cooldown();
-                assert_eq!(r_ability.status, ability::Status::Ready);
-                if !cooldown.is_zero() {
-                    r_ability.status = ability::Status::Cooldown(cooldown);
-                }
+                r_ability.status.spend(cooldown);
             }
         }
     }
+    if let Some(charges) = parts.charges.get_opt_mut(id) {
+        if let Some(charges) = charges.0.get_mut(&event.ability) {
+            assert!(*charges > 0);
+            *charges -= 1;
+        }
+    }
     if let Some(agent) = parts.agent.get_opt_mut(id) {
         if agent.attacks.0 > 0 {
             agent.attacks.0 -= 1;
@@ -185,14 +445,13 @@ fn apply_event_use_ability(state: &mut State, event: &event::UseAbility) {
             panic!("internal error: can't use ability if there're not attacks or jokers");
         }
     }
+    if extra_attacks != 0 {
+        parts.agent.get_mut(id).attacks.0 += extra_attacks;
+    }
     match event.ability {
         Ability::Jump | Ability::LongJump | Ability::Dash => {
             parts.pos.get_mut(id).0 = event.pos;
         }
-        Ability::Rage => {
-            let component = parts.agent.get_mut(id);
-            component.attacks.0 += 3;
-        }
         Ability::Summon => {
             assert!(parts.summoner.get_opt(id).is_some());
             let mut summoner = parts.summoner.get_mut(id);
@@ -202,6 +461,49 @@ fn apply_event_use_ability(state: &mut State, event: &event::UseAbility) {
     }
 }
 
+fn apply_event_set_tiles(state: &mut State, event: &event::SetTiles) {
+    for &pos in &event.tiles {
+        state.set_tile(pos, event.tile);
+    }
+}
+
+fn apply_event_capture(state: &mut State, event: &event::Capture) {
+    state.set_control_point_holder(event.index, event.player_id);
+}
+
+fn apply_event_begin_boss_phase(state: &mut State, event: &event::BeginBossPhase) {
+    let parts = state.parts_mut();
+    let id = event.id;
+    let boss = parts.boss.get_mut(id);
+    let phase = boss.phases[event.phase].clone();
+    boss.phases_passed = event.phase + 1;
+    let agent = parts.agent.get_mut(id);
+    agent.attacks.0 += phase.bonus_attacks.0;
+    agent.base_attacks.0 += phase.bonus_attacks.0;
+    agent.moves.0 += phase.bonus_moves.0;
+    agent.base_moves.0 += phase.bonus_moves.0;
+    agent.attack_strength.0 += phase.bonus_attack_strength.0;
+    if parts.abilities.get_opt(id).is_none() {
+        parts.abilities.insert(id, component::Abilities(Vec::new()));
+    }
+    let abilities = &mut parts.abilities.get_mut(id).0;
+    for ability in phase.abilities {
+        if !abilities.iter().any(|r| r.ability == ability) {
+            abilities.push(ability.into());
+        }
+    }
+    if parts.passive_abilities.get_opt(id).is_none() {
+        let component = component::PassiveAbilities(Vec::new());
+        parts.passive_abilities.insert(id, component);
+    }
+    let passive_abilities = &mut parts.passive_abilities.get_mut(id).0;
+    for ability in phase.passive_abilities {
+        if !passive_abilities.contains(&ability) {
+            passive_abilities.push(ability);
+        }
+    }
+}
+
 fn apply_event_use_passive_ability(_: &mut State, _: &event::UsePassiveAbility) {}
 
 fn apply_event_effect_tick(_: &mut State, _: &event::EffectTick) {}
@@ -219,9 +521,25 @@ fn add_component(parts: &mut Parts, id: Id, component: Component) {
         Component::Blocker(c) => parts.blocker.insert(id, c),
         Component::Abilities(c) => parts.abilities.insert(id, c),
         Component::PassiveAbilities(c) => parts.passive_abilities.insert(id, c),
+        Component::AbilityTiers(c) => parts.ability_tiers.insert(id, c),
         Component::Effects(c) => parts.effects.insert(id, c),
         Component::Schedule(c) => parts.schedule.insert(id, c),
         Component::Summoner(c) => parts.summoner.insert(id, c),
+        Component::SummonedBy(c) => parts.summoned_by.insert(id, c),
+        Component::Corpse(c) => parts.corpse.insert(id, c),
+        Component::Resistances(c) => parts.resistances.insert(id, c),
+        Component::Morale(c) => parts.morale.insert(id, c),
+        Component::Stamina(c) => parts.stamina.insert(id, c),
+        Component::Reactions(c) => parts.reactions.insert(id, c),
+        Component::Auras(c) => parts.auras.insert(id, c),
+        Component::Veteran(c) => parts.veteran.insert(id, c),
+        Component::Persona(c) => parts.persona.insert(id, c),
+        Component::Charges(c) => parts.charges.insert(id, c),
+        Component::Boss(c) => parts.boss.insert(id, c),
+        Component::Facing(c) => parts.facing.insert(id, c),
+        Component::Personality(c) => parts.personality.insert(id, c),
+        Component::Footprint(c) => parts.footprint.insert(id, c),
+        Component::Mount(c) => parts.mount.insert(id, c),
     }
 }
 
@@ -242,17 +560,42 @@ fn apply_scheduled_ability(state: &mut State, id: Id, planned_ability: &PlannedA
     }
 }
 
+fn extend_duration(duration: &mut Duration, extra: Duration) {
+    match (duration, extra) {
+        (Duration::Rounds(rounds), Duration::Rounds(extra)) => rounds.0 += extra.0,
+        (duration, Duration::Forever) => *duration = Duration::Forever,
+        (Duration::Forever, _) => {}
+    }
+}
+
 fn apply_effect_timed(state: &mut State, id: Id, timed_effect: &effect::Timed) {
     trace!("effect::apply_timed: {:?}", timed_effect);
+    let policy = state.stack_rules().rule(timed_effect.effect).policy;
     let effects = &mut state.parts_mut().effects;
     if effects.get_opt(id).is_none() {
         effects.insert(id, component::Effects(Vec::new()));
     }
     let effects = &mut effects.get_mut(id).0;
-    if let Some(i) = effects.iter().position(|e| e.effect == timed_effect.effect) {
-        effects[i] = timed_effect.clone();
-    } else {
-        effects.push(timed_effect.clone());
+    let same_count = effects
+        .iter()
+        .filter(|e| e.effect == timed_effect.effect)
+        .count();
+    let old = effects.iter().position(|e| e.effect == timed_effect.effect);
+    match (policy, old) {
+        (_, None) => effects.push(timed_effect.clone()),
+        (StackPolicy::Refresh, Some(i)) => effects[i] = timed_effect.clone(),
+        (StackPolicy::Extend, Some(i)) => {
+            extend_duration(&mut effects[i].duration, timed_effect.duration)
+        }
+        (StackPolicy::Stack { limit }, Some(_)) if same_count < limit => {
+            effects.push(timed_effect.clone());
+        }
+        (StackPolicy::Stack { .. }, Some(i)) => {
+            // The oldest one is refreshed.
+            effects.remove(i);
+            effects.push(timed_effect.clone());
+        }
+        (StackPolicy::Ignore, Some(_)) => {}
     }
 }
 
@@ -270,19 +613,50 @@ fn apply_effect_instant(state: &mut State, id: Id, effect: &Effect) {
         Effect::Throw(ref effect) => apply_effect_throw(state, id, effect),
         Effect::Dodge(_) => {}
         Effect::Bloodlust => apply_effect_bloodlust(state, id),
+        Effect::Demoralize(ref effect) => apply_effect_demoralize(state, id, effect),
+        Effect::Cleanse(ref effect) => apply_effect_cleanse(state, id, effect),
     }
 }
 
 fn apply_effect_create(state: &mut State, id: Id, effect: &effect::Create) {
     add_components(state, id, &effect.components);
+    let parts = state.parts();
+    if parts.agent.get_opt(id).is_some() && parts.facing.get_opt(id).is_none() {
+        let owner = parts.belongs_to.get_opt(id).map(|belongs_to| belongs_to.0);
+        let facing = component::Facing(state::initial_facing(state, owner));
+        state.parts_mut().facing.insert(id, facing);
+    }
 }
 
-fn apply_effect_kill(state: &mut State, id: Id, _: &effect::Kill) {
+/// Turns the agent towards the position, if it's not the agent's own one.
+fn turn_to(state: &mut State, id: Id, pos: PosHex) {
+    let parts = state.parts_mut();
+    let from = parts.pos.get(id).0;
+    if let Some(dir) = Dir::approximate_from_to(from, pos) {
+        if let Some(facing) = parts.facing.get_opt_mut(id) {
+            facing.0 = dir;
+        }
+    }
+}
+
+/// The rider of a killed mount falls to the mount's tile, unless they both drown.
+fn apply_effect_kill(state: &mut State, id: Id, effect: &effect::Kill) {
+    if state.parts().agent.get_opt(id).is_some() && !effect.is_drowned {
+        add_decal_at_object(state, id, decal::Kind::Blood);
+    }
+    if state.parts().mount.get_opt(id).is_some() && !effect.is_drowned {
+        let pos = state.parts().pos.get(id).0;
+        drop_rider(state, id, pos);
+    }
     let parts = state.parts_mut();
     parts.remove(id);
 }
 
 fn apply_effect_vanish(state: &mut State, id: Id) {
+    if state.parts().mount.get_opt(id).is_some() {
+        let pos = state.parts().pos.get(id).0;
+        drop_rider(state, id, pos);
+    }
     let parts = state.parts_mut();
     parts.remove(id);
 }
@@ -305,12 +679,21 @@ fn apply_effect_heal(state: &mut State, id: Id, effect: &effect::Heal) {
             component.strength = component.base_strength;
         }
     }
+    // An injury stays till the end of the battle.
     if let Some(effects) = parts.effects.get_opt_mut(id) {
-        effects.0.clear();
+        effects
+            .0
+            .retain(|timed| timed.effect == effect::Lasting::Injured);
+    }
+    if let Some(morale) = parts.morale.get_opt_mut(id) {
+        morale.morale = morale.base_morale;
     }
 }
 
 fn apply_effect_wound(state: &mut State, id: Id, effect: &effect::Wound) {
+    if effect.damage.0 > 0 && state.parts().agent.get_opt(id).is_some() {
+        add_decal_at_object(state, id, decal::Kind::Blood);
+    }
     let parts = state.parts_mut();
     let damage = effect.damage.0;
     assert!(damage >= 0);
@@ -330,8 +713,7 @@ fn apply_effect_wound(state: &mut State, id: Id, effect: &effect::Wound) {
         strength.strength.0 -= damage;
         assert!(strength.strength.0 > 0);
     }
-    {
-        let agent = parts.agent.get_mut(id);
+    if let Some(agent) = parts.agent.get_opt_mut(id) {
         agent.attacks.0 -= 1;
         if agent.attacks.0 < 0 {
             agent.attacks.0 = 0;
@@ -344,8 +726,7 @@ fn apply_effect_knockback(state: &mut State, id: Id, effect: &effect::Knockback)
     if effect.to == effect.from {
         return;
     }
-    assert!(state.map().is_inboard(effect.to));
-    assert!(!state::is_tile_blocked(state, effect.to));
+    assert!(state::fits_at(state, id, effect.to));
     let parts = state.parts_mut();
     parts.pos.get_mut(id).0 = effect.to;
 }
@@ -355,16 +736,14 @@ fn apply_effect_fly_off(state: &mut State, id: Id, effect: &effect::FlyOff) {
     if effect.to == effect.from {
         return;
     }
-    assert!(state.map().is_inboard(effect.to));
-    assert!(!state::is_tile_blocked(state, effect.to));
+    assert!(state::fits_at(state, id, effect.to));
     let parts = state.parts_mut();
     parts.pos.get_mut(id).0 = effect.to;
 }
 
 fn apply_effect_throw(state: &mut State, id: Id, effect: &effect::Throw) {
     assert!(state.map().is_inboard(effect.from));
-    assert!(state.map().is_inboard(effect.to));
-    assert!(!state::is_tile_blocked(state, effect.to));
+    assert!(state::fits_at(state, id, effect.to));
     let parts = state.parts_mut();
     parts.pos.get_mut(id).0 = effect.to;
 }
@@ -375,12 +754,32 @@ fn apply_effect_bloodlust(state: &mut State, id: Id) {
     agent.jokers.0 += 3;
 }
 
+fn apply_effect_demoralize(state: &mut State, id: Id, effect: &effect::Demoralize) {
+    let morale = state.parts_mut().morale.get_mut(id);
+    morale.morale -= effect.morale;
+    if morale.morale < 0 {
+        morale.morale = 0;
+    }
+}
+
+fn apply_effect_cleanse(state: &mut State, id: Id, effect: &effect::Cleanse) {
+    if let Some(effects) = state.parts_mut().effects.get_opt_mut(id) {
+        effects.0.retain(|timed| timed.effect != effect.effect);
+    }
+}
+
 fn update_cooldowns_for_object(state: &mut State, id: Id) {
-    let parts = state.parts_mut();
-    if let Some(abilities) = parts.abilities.get_opt_mut(id) {
-        for ability in &mut abilities.0 {
-            ability.status.update();
-        }
+    let abilities = match state.parts().abilities.get_opt(id) {
+        Some(abilities) => &abilities.0,
+        None => return,
+    };
+    let cooldowns: Vec<_> = abilities
+        .iter()
+        .map(|r_ability| state::ability_params(state, id, r_ability.ability).cooldown)
+        .collect();
+    let abilities = state.parts_mut().abilities.get_mut(id);
+    for (r_ability, cooldown) in abilities.0.iter_mut().zip(cooldowns) {
+        r_ability.status.update(cooldown);
     }
 }
 
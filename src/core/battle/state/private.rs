@@ -1,22 +1,91 @@
+use std::collections::HashMap;
+
 use log::error;
+use serde::{Deserialize, Serialize};
 
 use crate::core::{
     battle::{
+        ability::{self, Ability, PassiveAbility},
         command,
-        component::{Component, ObjType, Parts, Prototypes},
+        component::{Charges, Component, ObjType, Parts, Prototypes, Veteran},
+        decal::Decal,
+        effect,
         event::Event,
         execute,
-        scenario::{self, Scenario},
-        state::apply::apply,
+        history::History,
+        rng::{self, BattleRng},
+        scenario::{self, ObjectsGroup, Scenario},
+        state::{self, apply::apply},
         Id, PlayerId, TileType,
     },
-    map,
+    map::{self, generator, PosHex},
 };
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct BattleResult {
     pub winner_id: PlayerId,
     pub survivor_types: Vec<ObjType>,
+
+    /// Surviving campaign fighters with their kill counts.
+    pub veterans: Vec<Veteran>,
+
+    /// Campaign fighters that died in this battle.
+    pub fallen: Vec<FallenVeteran>,
+
+    /// Ability charges left to the surviving campaign fighters, by their roster index.
+    pub charges: HashMap<usize, Charges>,
+
+    /// How many times the human player's agents have used every ability.
+    pub ability_uses: HashMap<Ability, i32>,
+
+    /// The human player has fled the battle: the survivors are saved, but it's not a win.
+    pub is_retreat: bool,
+
+    /// Earned by holding the control points, see `scenario::ControlPoint`.
+    pub control_renown: i32,
+}
+
+/// What has killed an agent.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCause {
+    Attack(ObjType),
+    Ability(ObjType, Ability),
+    PassiveAbility(PassiveAbility),
+    Effect(effect::Lasting),
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FallenVeteran {
+    pub veteran: Veteran,
+    pub cause: DeathCause,
+}
+
+/// A lasting effect of an object with the time left till it wears off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LastingEffectStatus {
+    pub effect: effect::Lasting,
+
+    /// How many more turns of its phase's player the effect is going to tick.
+    /// It's removed right after the last tick. `None` if it never wears off.
+    pub turns_left: Option<i32>,
+}
+
+/// An active ability of an object with its charges and cooldown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AbilityStatus {
+    pub ability: Ability,
+    pub charges: i32,
+    pub max_charges: i32,
+
+    /// Rounds till the next spent charge is back, zero if nothing is recharging.
+    pub cooldown: i32,
+
+    /// Uses left until a rest, `None` if the ability isn't limited by them.
+    pub uses_left: Option<i32>,
+
+    /// The stamina that every use takes, `None` if the ability has a cooldown instead.
+    pub stamina_cost: Option<i32>,
 }
 
 #[derive(Clone, Debug)]
@@ -26,59 +95,188 @@ pub struct State {
     scenario: Scenario,
     player_id: PlayerId,
     prototypes: Prototypes,
+    stack_rules: effect::StackRules,
+    ability_definitions: ability::Definitions,
     battle_result: Option<BattleResult>,
 
+    /// Cosmetic marks left by the effects during this battle.
+    decals: Vec<Decal>,
+
+    /// Campaign fighters that died during this battle.
+    fallen_veterans: Vec<FallenVeteran>,
+
+    /// How many attacks in a row every player has missed.
+    miss_streaks: HashMap<PlayerId, i32>,
+
+    /// Full rounds that have passed since the start of the battle.
+    rounds_passed: i32,
+
+    /// Indices of the scenario's reinforcement waves that have already arrived.
+    arrived_waves: Vec<usize>,
+
+    /// Abilities used by the agents of every player.
+    ability_uses: HashMap<PlayerId, HashMap<Ability, i32>>,
+
+    /// Indices of the scenario's scripts that have already fired.
+    fired_scripts: Vec<usize>,
+
+    /// Rounds in a row every `Occupy` script's region has been held.
+    zone_occupation: HashMap<usize, i32>,
+
+    /// The sides that hold the scenario's control points, by their indices.
+    control_point_holders: HashMap<usize, PlayerId>,
+
+    /// Earned by the human side for holding the control points.
+    control_renown: i32,
+
+    /// The human player's agents that are still waiting to be placed, see `Scenario::deployment_zone`.
+    deployment: Vec<ObjectsGroup>,
+
+    /// The seed that the battle has started with, see `Scenario::seed`.
+    seed: u64,
+
+    rng: BattleRng,
+
+    /// Every command and event since the objects have been created.
+    history: History,
+
     /// Enables panics when non-deterministic functions are called.
     deterministic_mode: bool,
 }
 
 impl State {
-    pub fn new(prototypes: Prototypes, scenario: Scenario, cb: execute::Cb) -> Self {
+    pub fn new(
+        prototypes: Prototypes,
+        stack_rules: effect::StackRules,
+        ability_definitions: ability::Definitions,
+        scenario: Scenario,
+        cb: execute::Cb,
+    ) -> Self {
         scenario.check().expect("Bad scenario");
         assert!(scenario.map_radius.0 >= 3);
+        let seed = scenario.seed.unwrap_or_else(rng::random_seed);
         let mut this = Self {
             map: map::HexMap::new(scenario.map_radius),
             player_id: PlayerId(0),
             scenario,
             parts: Parts::new(),
             prototypes,
+            stack_rules,
+            ability_definitions,
             battle_result: None,
+            decals: Vec::new(),
+            fallen_veterans: Vec::new(),
+            miss_streaks: HashMap::new(),
+            rounds_passed: 0,
+            arrived_waves: Vec::new(),
+            ability_uses: HashMap::new(),
+            fired_scripts: Vec::new(),
+            zone_occupation: HashMap::new(),
+            control_point_holders: HashMap::new(),
+            control_renown: 0,
+            deployment: Vec::new(),
+            seed,
+            rng: BattleRng::new(seed),
+            history: History::new(PlayerId(0)),
             deterministic_mode: false,
         };
-        this.create_terrain();
-        this.create_objects(cb);
+        let obstacles = this.create_terrain();
+        this.create_objects(&obstacles, cb);
+        this.history = History::new(this.player_id());
         this
     }
 
+    /// The same battle at its very beginning: the same scenario, the same seed, no commands.
+    pub fn initial_state(&self, cb: execute::Cb) -> State {
+        let mut scenario = self.scenario.clone();
+        scenario.seed = Some(self.seed);
+        let mut state = State::new(
+            self.prototypes.clone(),
+            self.stack_rules.clone(),
+            self.ability_definitions.clone(),
+            scenario,
+            cb,
+        );
+        state.deterministic_mode = self.deterministic_mode;
+        state
+    }
+
     pub fn deterministic_mode(&self) -> bool {
         self.deterministic_mode
     }
 
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
     pub fn scenario(&self) -> &Scenario {
         &self.scenario
     }
 
-    // TODO: Handle Scenario::exact_tiles
-    fn create_terrain(&mut self) {
+    /// Returns the positions of the generated obstacles, if there're any.
+    fn create_terrain(&mut self) -> Vec<PosHex> {
+        for &pos in &self.scenario.masked_tiles {
+            self.map.mask_tile(pos);
+        }
+        for (&pos, &tile) in &self.scenario.tiles {
+            self.map.set_tile(pos, tile);
+        }
+        let mut obstacles = Vec::new();
+        if let Some(biome) = self.scenario.random_map {
+            let radius = self.scenario.map_radius;
+            let keep_free = self.scenario.fixed_positions();
+            let layout = generator::generate(&mut self.rng, radius, biome.params(), &keep_free);
+            for pos in layout.masked_tiles {
+                self.map.mask_tile(pos);
+            }
+            for pos in layout.rough_tiles {
+                self.map.set_tile(pos, TileType::Rocks);
+            }
+            obstacles = layout.obstacles;
+        }
         for _ in 0..self.scenario.rocky_tiles_count {
-            let pos = match scenario::random_free_pos(self) {
+            let pos = match scenario::random_free_pos(self, &[]) {
                 Some(pos) => pos,
                 None => continue,
             };
             self.map.set_tile(pos, TileType::Rocks);
         }
+        obstacles
     }
 
     // TODO: Handle Scenario::objects
-    fn create_objects(&mut self, cb: execute::Cb) {
+    fn create_objects(&mut self, obstacles: &[PosHex], cb: execute::Cb) {
         let player_id_initial = self.player_id();
+        for &pos in obstacles {
+            let command = command::Create {
+                prototype: scenario::RANDOM_MAP_OBSTACLE.into(),
+                pos,
+                owner: None,
+                extra_components: Vec::new(),
+            }
+            .into();
+            execute::execute(self, &command, cb).expect("Can't create an obstacle");
+        }
+        let mut deployment = Vec::new();
         // TODO: Merge the cycles. Generate `objects` based on `randomized_objects`.
         for group in self.scenario.randomized_objects.clone() {
             if let Some(player_id) = group.owner {
                 self.set_player_id(player_id);
             }
+            let is_deployed = group.owner == Some(PlayerId(0));
+            if is_deployed && self.scenario.deployment_zone.is_some() {
+                for _ in 0..group.count {
+                    deployment.push(ObjectsGroup {
+                        count: 1,
+                        line: None,
+                        ..group.clone()
+                    });
+                }
+                continue;
+            }
+            let footprint = state::prototype_footprint(self, &group.typename);
             for _ in 0..group.count {
-                let pos = match scenario::random_pos(self, group.owner, group.line) {
+                let pos = match scenario::random_pos(self, group.owner, group.line, &footprint) {
                     Some(pos) => pos,
                     None => {
                         error!("Can't find the position");
@@ -89,6 +287,7 @@ impl State {
                     prototype: group.typename.clone(),
                     pos,
                     owner: group.owner,
+                    extra_components: group.extra_components.clone(),
                 }
                 .into();
                 execute::execute(self, &command, cb).expect("Can't create an object");
@@ -102,11 +301,15 @@ impl State {
                 prototype: group.typename.clone(),
                 pos: group.pos,
                 owner: group.owner,
+                extra_components: Vec::new(),
             }
             .into();
             execute::execute(self, &command, cb).expect("Can't create an object");
         }
         self.set_player_id(player_id_initial);
+        execute::try_execute_scripts(self, cb);
+        // Set only now, so the creation of the other objects isn't mistaken for a deployment.
+        self.deployment = deployment;
     }
 
     pub fn player_id(&self) -> PlayerId {
@@ -135,9 +338,112 @@ impl State {
         prototypes[name].clone()
     }
 
+    pub fn stack_rules(&self) -> &effect::StackRules {
+        &self.stack_rules
+    }
+
+    pub fn ability_definitions(&self) -> &ability::Definitions {
+        &self.ability_definitions
+    }
+
     pub fn battle_result(&self) -> &Option<BattleResult> {
         &self.battle_result
     }
+
+    pub fn decals(&self) -> &[Decal] {
+        &self.decals
+    }
+
+    pub fn fallen_veterans(&self) -> &[FallenVeteran] {
+        &self.fallen_veterans
+    }
+
+    pub fn miss_streak(&self, player_id: PlayerId) -> i32 {
+        self.miss_streaks.get(&player_id).cloned().unwrap_or(0)
+    }
+
+    pub fn rounds_passed(&self) -> i32 {
+        self.rounds_passed
+    }
+
+    /// All active lasting effects of the object, the ones that wear off sooner go first.
+    pub fn lasting_effects(&self, id: Id) -> Vec<LastingEffectStatus> {
+        let effects = match self.parts.effects.get_opt(id) {
+            Some(effects) => &effects.0,
+            None => return Vec::new(),
+        };
+        let mut statuses: Vec<_> = effects
+            .iter()
+            .filter(|timed| !timed.duration.is_over())
+            .map(|timed| LastingEffectStatus {
+                effect: timed.effect,
+                turns_left: match timed.duration {
+                    effect::Duration::Forever => None,
+                    effect::Duration::Rounds(rounds) => Some(rounds.0),
+                },
+            })
+            .collect();
+        statuses.sort_by_key(|status| (status.turns_left.is_none(), status.turns_left));
+        statuses
+    }
+
+    /// All active abilities of the object, in the order of its prototype.
+    pub fn abilities(&self, id: Id) -> Vec<AbilityStatus> {
+        let abilities = match self.parts.abilities.get_opt(id) {
+            Some(abilities) => &abilities.0,
+            None => return Vec::new(),
+        };
+        let charges = self.parts.charges.get_opt(id);
+        let stamina = self.parts.stamina.get_opt(id);
+        abilities
+            .iter()
+            .map(|r_ability| AbilityStatus {
+                ability: r_ability.ability,
+                charges: r_ability.status.charges,
+                max_charges: r_ability.status.max_charges,
+                cooldown: r_ability.status.cooldown.0,
+                uses_left: charges.and_then(|charges| charges.0.get(&r_ability.ability).copied()),
+                stamina_cost: stamina.and_then(|stamina| stamina.cost(r_ability.ability)),
+            })
+            .collect()
+    }
+
+    pub fn is_wave_arrived(&self, index: usize) -> bool {
+        self.arrived_waves.contains(&index)
+    }
+
+    pub fn ability_uses(&self, player_id: PlayerId) -> HashMap<Ability, i32> {
+        self.ability_uses
+            .get(&player_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn is_script_fired(&self, index: usize) -> bool {
+        self.fired_scripts.contains(&index)
+    }
+
+    /// The human player's agents that are still waiting to be placed.
+    pub fn deployment(&self) -> &[ObjectsGroup] {
+        &self.deployment
+    }
+
+    pub fn zone_occupation(&self, index: usize) -> i32 {
+        self.zone_occupation.get(&index).cloned().unwrap_or(0)
+    }
+
+    /// `None` for the points that nobody has captured yet.
+    pub fn control_point_holder(&self, index: usize) -> Option<PlayerId> {
+        self.control_point_holders.get(&index).cloned()
+    }
+
+    pub fn control_renown(&self) -> i32 {
+        self.control_renown
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
 }
 
 /// Public mutators. Be careful with them!
@@ -159,11 +465,80 @@ impl State {
         self.deterministic_mode = value;
     }
 
+    /// A tile has at most one decal of every kind, so long battles don't pile them up.
+    pub(super) fn add_decal(&mut self, decal: Decal) {
+        if !self.decals.contains(&decal) {
+            self.decals.push(decal);
+        }
+    }
+
+    pub(super) fn set_miss_streak(&mut self, player_id: PlayerId, value: i32) {
+        self.miss_streaks.insert(player_id, value);
+    }
+
+    pub(super) fn add_round(&mut self) {
+        self.rounds_passed += 1;
+    }
+
+    pub(in crate::core) fn add_arrived_wave(&mut self, index: usize) {
+        self.arrived_waves.push(index);
+    }
+
+    pub(in crate::core) fn remove_from_deployment(&mut self, index: usize) {
+        self.deployment.remove(index);
+    }
+
+    pub(in crate::core) fn add_fired_script(&mut self, index: usize) {
+        self.fired_scripts.push(index);
+    }
+
+    pub(in crate::core) fn set_zone_occupation(&mut self, index: usize, rounds: i32) {
+        self.zone_occupation.insert(index, rounds);
+    }
+
+    pub(super) fn set_control_point_holder(&mut self, index: usize, player_id: PlayerId) {
+        self.control_point_holders.insert(index, player_id);
+    }
+
+    pub(in crate::core) fn add_control_renown(&mut self, renown: i32) {
+        self.control_renown += renown;
+    }
+
+    pub(super) fn set_tile(&mut self, pos: PosHex, tile: TileType) {
+        self.map.set_tile(pos, tile);
+    }
+
+    pub(super) fn add_ability_use(&mut self, player_id: PlayerId, ability: Ability) {
+        let uses = self.ability_uses.entry(player_id).or_default();
+        *uses.entry(ability).or_insert(0) += 1;
+    }
+
+    pub(super) fn add_fallen_veteran(&mut self, fallen: FallenVeteran) {
+        self.fallen_veterans.push(fallen);
+    }
+
+    /// A copy to try the commands out on. It rolls its own dice,
+    /// so the simulations don't see the rolls of the real battle.
+    pub fn fork_for_simulation(&self) -> Self {
+        let mut state = self.clone();
+        state.rng = self.rng.fork();
+        state
+    }
+
+    pub(in crate::core) fn rng(&mut self) -> &mut BattleRng {
+        &mut self.rng
+    }
+
     pub(in crate::core) fn alloc_id(&mut self) -> Id {
         self.parts.alloc_id()
     }
 
+    pub(in crate::core) fn add_command_to_history(&mut self, command: &command::Command) {
+        self.history.add_command(command);
+    }
+
     pub fn apply(&mut self, event: &Event) {
         apply(self, event);
+        self.history.add_event(event);
     }
 }
@@ -0,0 +1,162 @@
+//! The numbers of the end-of-battle summary, gathered from the executed events.
+
+use std::collections::HashMap;
+
+use crate::core::battle::{
+    ability::Ability,
+    component::{Component, ObjType},
+    effect::{self, Effect},
+    event::{ActiveEvent, Event},
+    Id, PlayerId, State,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AgentStats {
+    pub id: Id,
+    pub typename: ObjType,
+
+    /// The name of a campaign fighter.
+    pub name: Option<String>,
+
+    pub player_id: PlayerId,
+
+    /// Only the damage of the agent's own attacks and abilities,
+    /// fire, poison and bombs that go off later aren't anyone's.
+    pub damage_dealt: i32,
+
+    pub damage_taken: i32,
+    pub kills: i32,
+    pub abilities_used: i32,
+    pub is_killed: bool,
+}
+
+/// Every agent that has ever taken part in the battle, the summoned ones included.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    agents: Vec<AgentStats>,
+
+    /// Turns of the human player that have begun after the first one.
+    next_turns: i32,
+
+    /// The most enemies that a single use of the ability has killed, by the side that used it.
+    multi_kills: HashMap<(PlayerId, Ability), i32>,
+}
+
+impl Stats {
+    /// In the order of their appearance.
+    pub fn agents(&self) -> &[AgentStats] {
+        &self.agents
+    }
+
+    /// Turns of the human player, the current one included.
+    pub fn turns(&self) -> i32 {
+        // The battle starts with the player's turn without an event.
+        self.next_turns + 1
+    }
+
+    /// Bombs go off in the phase of the player that has thrown them,
+    /// so their explosions count too.
+    pub fn most_kills_at_once(&self, player_id: PlayerId, ability: Ability) -> i32 {
+        self.multi_kills
+            .get(&(player_id, ability))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Must be called in the `ApplyPhase::Pre` phase,
+    /// while the killed agents still have their strength.
+    pub fn record(&mut self, state: &State, event: &Event) {
+        let mut enemies_killed = 0;
+        let actor_id = match &event.active_event {
+            ActiveEvent::BeginTurn(event) => {
+                if event.player_id == PlayerId(0) {
+                    self.next_turns += 1;
+                }
+                None
+            }
+            ActiveEvent::Attack(event) => Some(event.attacker_id),
+            ActiveEvent::UseAbility(event) => {
+                if let Some(agent) = self.agent_mut(event.id) {
+                    agent.abilities_used += 1;
+                }
+                Some(event.id)
+            }
+            _ => None,
+        };
+        for (id, effects) in &event.instant_effects {
+            for effect in effects {
+                let damage = match effect {
+                    Effect::Create(effect) => {
+                        self.add_agent(*id, effect);
+                        continue;
+                    }
+                    Effect::Wound(effect::Wound { damage, .. }) => damage.0,
+                    Effect::Kill(_) => state
+                        .parts()
+                        .strength
+                        .get_opt(*id)
+                        .map_or(0, |s| s.strength.0),
+                    _ => continue,
+                };
+                let is_kill = matches!(effect, Effect::Kill(_));
+                let target = match self.agent_mut(*id) {
+                    Some(target) => target,
+                    None => continue,
+                };
+                target.damage_taken += damage;
+                if is_kill {
+                    target.is_killed = true;
+                    if target.player_id != state.player_id() {
+                        enemies_killed += 1;
+                    }
+                }
+                if let Some(actor) = actor_id.and_then(|actor_id| self.agent_mut(actor_id)) {
+                    actor.damage_dealt += damage;
+                    if is_kill {
+                        actor.kills += 1;
+                    }
+                }
+            }
+        }
+        if let ActiveEvent::UseAbility(event) = &event.active_event {
+            if enemies_killed > 0 {
+                let key = (state.player_id(), event.ability);
+                let best = self.multi_kills.entry(key).or_insert(0);
+                *best = (*best).max(enemies_killed);
+            }
+        }
+    }
+
+    fn agent_mut(&mut self, id: Id) -> Option<&mut AgentStats> {
+        self.agents.iter_mut().find(|agent| agent.id == id)
+    }
+
+    fn add_agent(&mut self, id: Id, effect: &effect::Create) {
+        let mut is_agent = false;
+        let mut player_id = None;
+        let mut name = None;
+        for component in &effect.components {
+            match component {
+                Component::Agent(_) => is_agent = true,
+                Component::BelongsTo(belongs_to) => player_id = Some(belongs_to.0),
+                Component::Persona(persona) => name = persona.name.clone(),
+                _ => {}
+            }
+        }
+        let player_id = match player_id {
+            Some(player_id) if is_agent => player_id,
+            _ => return,
+        };
+        self.agents.push(AgentStats {
+            id,
+            typename: effect.prototype.clone(),
+            name,
+            player_id,
+            damage_dealt: 0,
+            damage_taken: 0,
+            kills: 0,
+            abilities_used: 0,
+            is_killed: false,
+        });
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use pretty_assertions::assert_eq;
 
 // TODO: don't construct your own Id(*), get them from the state!
@@ -6,22 +8,39 @@ use pretty_assertions::assert_eq;
 
 use crate::core::{
     battle::{
-        ability::{Ability, PassiveAbility},
-        check,
+        ability::{self, Ability, PassiveAbility, Tier},
+        ai::{self, Ai},
+        check, codex,
         command::{self, Command},
-        component::{self, Component, ObjType, PlannedAbility, Prototypes, WeaponType},
-        effect::{self, Effect},
+        component::{
+            self, AuraEffect, AuraTargets, Component, DamageType, Hook, MovementType, ObjType,
+            Personality, PlannedAbility, Prototypes, ReactionEffect, ReactionTarget, WeaponType,
+        },
+        decal::{self, Decal},
+        dice,
+        effect::{self, Effect, StackPolicy, StackRule, StackRules},
         event::{self, ActiveEvent, AttackMode, Event},
-        execute::{execute, ApplyPhase},
-        movement::Path,
-        scenario::{Object, Scenario},
-        state::BattleResult,
-        Accuracy, Attacks, Dodge, Id, Jokers, MovePoints, Moves, Phase, PlayerId, PushStrength,
-        State, Strength, Weight,
+        execute::{self, execute, execute_batch, ApplyPhase},
+        forecast::{self, Forecast, HealForecast, Outcome},
+        hint, history, log,
+        movement::{Path, Pathfinder},
+        objective::{self, Objective},
+        scenario::{
+            self, Condition, ControlPoint, ControlReward, Difficulty, Expiry, Mechanic, Object,
+            Query, Region, Remark, Scenario, Script, ScriptAction, Size, Tags, Trigger, TurnLimit,
+            Wave, Weather,
+        },
+        simulate,
+        state::{self, BattleResult, LastingEffectStatus},
+        stats, threat, tutorial, Accuracy, Attacks, Dodge, Id, Jokers, MovePoints, Moves, Phase,
+        PlayerId, PushStrength, Rounds, State, Strength, TileType, Weight,
     },
-    map::{Distance, PosHex},
+    map::{self, generator::Biome, Dir, Distance, HexMap, PosHex},
 };
 
+mod bench;
+mod fuzz;
+
 const P0: PlayerId = PlayerId(0);
 const P1: PlayerId = PlayerId(1);
 
@@ -62,6 +81,9 @@ fn agent_dull() -> component::Agent {
         weapon_type: WeaponType::Slash,
         attack_break: Strength(0),
         dodge: Dodge(0),
+        is_noncombatant: false,
+        cannot_counter: false,
+        movement_type: MovementType::Walk,
         move_points: MovePoints(0),
         reactive_attacks: Attacks(0),
         base_moves: Moves(0),
@@ -83,14 +105,17 @@ fn component_agent_move_basic() -> Component {
     .into()
 }
 
-fn component_agent_always_hit() -> Component {
+fn agent_always_hit() -> component::Agent {
     component::Agent {
         attack_distance: Distance(1),
         attacks: Attacks(1),
         attack_accuracy: Accuracy(10 + 1),
         ..agent_dull()
     }
-    .into()
+}
+
+fn component_agent_always_hit() -> Component {
+    agent_always_hit().into()
 }
 
 fn component_agent_always_hit_strength_1() -> Component {
@@ -157,11 +182,42 @@ fn event_begin_turn(player_id: PlayerId, actor_ids: &[Id]) -> Event {
     }
 }
 
+fn event_leave_corpse(dead_id: Id, pos: PosHex, corpse_id: Id, player_id: PlayerId) -> Event {
+    let effect_create = effect::Create {
+        pos,
+        prototype: "corpse".into(),
+        components: vec![
+            component::Corpse.into(),
+            component::Pos(pos).into(),
+            component_meta("corpse"),
+        ],
+        is_teleported: false,
+    };
+    let rot = PlannedAbility {
+        rounds: Rounds(3),
+        phase: Phase::from_player_id(player_id),
+        ability: Ability::Vanish,
+    };
+    Event {
+        active_event: event::LeaveCorpse { dead_id, pos }.into(),
+        actor_ids: Vec::new(),
+        instant_effects: vec![(corpse_id, vec![effect_create.into()])],
+        timed_effects: Vec::new(),
+        scheduled_abilities: vec![(corpse_id, vec![rot])],
+    }
+}
+
 fn event_end_battle(winner_id: PlayerId, survivor_types: &[ObjType]) -> Event {
     let active_event = event::EndBattle {
         result: BattleResult {
             winner_id,
             survivor_types: survivor_types.to_vec(),
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
         },
     };
     Event {
@@ -174,27 +230,66 @@ fn event_end_battle(winner_id: PlayerId, survivor_types: &[ObjType]) -> Event {
 }
 
 fn prototypes(slice: &[(&str, Vec<Component>)]) -> Prototypes {
-    let map = slice
+    let mut map: HashMap<_, _> = slice
         .iter()
         .cloned()
         .map(|(name, components)| (name.into(), components))
         .collect();
+    // Every kill may leave a corpse, see `execute::corpse_events`.
+    map.entry("corpse".into())
+        .or_insert_with(|| vec![component::Corpse.into()]);
     let mut prototypes = Prototypes(map);
     prototypes.init_components();
     prototypes
 }
 
+/// The real objects of the game.
+fn game_prototypes() -> Prototypes {
+    Prototypes::from_str(include_str!("../../../assets/objects.ron"))
+}
+
+fn game_stack_rules() -> StackRules {
+    ron::de::from_str(include_str!("../../../assets/effects.ron")).unwrap()
+}
+
+fn game_ability_definitions() -> ability::Definitions {
+    ron::de::from_str(include_str!("../../../assets/abilities.ron")).unwrap()
+}
+
 fn debug_state_with_events(prototypes: Prototypes, scenario: Scenario) -> (State, Vec<Event>) {
     let mut events = Vec::new();
-    let mut state = State::new(prototypes, scenario, &mut |_, event, phase| {
-        if phase == ApplyPhase::Pre {
-            events.push(event.clone());
-        }
-    });
+    let stack_rules = StackRules::default();
+    let mut state = State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        scenario,
+        &mut |_, event, phase| {
+            if phase == ApplyPhase::Pre {
+                events.push(event.clone());
+            }
+        },
+    );
     state.set_deterministic_mode(true);
     (state, events)
 }
 
+fn debug_state_with_stack_rules(
+    prototypes: Prototypes,
+    stack_rules: StackRules,
+    scenario: Scenario,
+) -> State {
+    let mut state = State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        scenario,
+        &mut |_, _, _| {},
+    );
+    state.set_deterministic_mode(true);
+    state
+}
+
 fn debug_state(prototypes: Prototypes, scenario: Scenario) -> State {
     let (state, _events) = debug_state_with_events(prototypes, scenario);
     state
@@ -243,6 +338,558 @@ fn bad_scenario_bad_pos() {
     let (_state, _events) = debug_state_with_events(prototypes, scenario);
 }
 
+#[should_panic(expected = "PosOutsideOfMap")]
+#[test]
+fn bad_scenario_masked_pos() {
+    let prototypes = prototypes(&[("obj", Vec::new())]);
+    let pos = PosHex { q: 1, r: 0 };
+    let mut scenario = Scenario::default().object(P0, "obj", pos);
+    scenario.masked_tiles = vec![pos];
+    let (_state, _events) = debug_state_with_events(prototypes, scenario);
+}
+
+#[test]
+fn masked_tiles_block_movement() {
+    let prototypes = prototypes(&[
+        ("mover", [component_agent_move_basic()].to_vec()),
+        ("dull", [component_agent_dull()].to_vec()),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "mover", PosHex { q: 0, r: 0 })
+        .object(P1, "dull", PosHex { q: 0, r: 3 });
+    scenario.masked_tiles = vec![PosHex { q: 0, r: 1 }];
+    let mut state = debug_state(prototypes, scenario);
+    assert!(!state.map().is_inboard(PosHex { q: 0, r: 1 }));
+    assert!(state.map().iter().all(|pos| pos != PosHex { q: 0, r: 1 }));
+    let path = Path::new(vec![PosHex { q: 0, r: 0 }, PosHex { q: 0, r: 1 }]);
+    let command = command::MoveTo { id: Id(0), path };
+    assert_eq!(
+        try_exec(&mut state, command).map(|_| ()),
+        Err(check::Error::BadPos {
+            pos: PosHex { q: 0, r: 1 }
+        })
+    );
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    pathfinder.fill_map(&state, Id(0));
+    assert!(pathfinder.path(PosHex { q: 0, r: 1 }).is_none());
+    let detour = pathfinder.path(PosHex { q: 0, r: 2 }).unwrap();
+    assert_eq!(detour.tiles().len(), 4);
+}
+
+#[test]
+fn only_flyers_cross_the_water() {
+    let flyer = component::Agent {
+        movement_type: MovementType::Fly,
+        moves: Moves(1),
+        move_points: MovePoints(3),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        ("walker", [component_agent_move_basic()].to_vec()),
+        ("flyer", [flyer.into()].to_vec()),
+        ("dull", [component_agent_dull()].to_vec()),
+    ]);
+    let water_pos = PosHex { q: 0, r: 1 };
+    let mut scenario = Scenario::default()
+        .object(P0, "walker", PosHex { q: 0, r: 0 })
+        .object(P0, "flyer", PosHex { q: 1, r: 0 })
+        .object(P1, "dull", PosHex { q: 0, r: 3 });
+    scenario.tiles.insert(water_pos, TileType::Water);
+    let mut state = debug_state(prototypes, scenario);
+    assert_eq!(state.map().tile(water_pos), TileType::Water);
+    let path = Path::new(vec![PosHex { q: 0, r: 0 }, water_pos]);
+    let command = command::MoveTo { id: Id(0), path };
+    assert_eq!(
+        try_exec(&mut state, command).map(|_| ()),
+        Err(check::Error::DeepWater { pos: water_pos })
+    );
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    pathfinder.fill_map(&state, Id(0));
+    assert!(pathfinder.path(water_pos).is_none());
+    pathfinder.fill_map(&state, Id(1));
+    let path = pathfinder.path(water_pos).unwrap();
+    exec(&mut state, command::MoveTo { id: Id(1), path });
+    assert_eq!(state.parts().pos.get(Id(1)).0, water_pos);
+}
+
+#[test]
+fn flyers_pass_over_blockers_and_rocks() {
+    let flyer = component::Agent {
+        movement_type: MovementType::Fly,
+        moves: Moves(1),
+        move_points: MovePoints(2),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        ("walker", [component_agent_move_basic()].to_vec()),
+        ("flyer", [flyer.into()].to_vec()),
+        (
+            "dull",
+            [component_agent_dull(), component_blocker(Weight::Normal)].to_vec(),
+        ),
+    ]);
+    let start_pos = PosHex { q: 0, r: 0 };
+    let blocker_pos = PosHex { q: 0, r: 1 };
+    let rocks_pos = PosHex { q: 0, r: 2 };
+    let mut scenario = Scenario::default()
+        .object(P0, "flyer", start_pos)
+        .object(P1, "dull", blocker_pos)
+        .object(P0, "walker", PosHex { q: -1, r: 0 });
+    scenario.tiles.insert(rocks_pos, TileType::Rocks);
+    let mut state = debug_state(prototypes, scenario);
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    pathfinder.fill_map(&state, Id(0));
+    assert!(pathfinder.path(blocker_pos).is_none());
+    let path = Path::new(vec![start_pos, blocker_pos]);
+    let command = command::MoveTo { id: Id(0), path };
+    assert_eq!(
+        try_exec(&mut state, command).map(|_| ()),
+        Err(check::Error::TileIsBlocked { pos: blocker_pos })
+    );
+    let path = pathfinder.path(rocks_pos).unwrap();
+    assert_eq!(path.tiles(), &[start_pos, blocker_pos, rocks_pos]);
+    assert_eq!(path.cost_for(&state, Id(0)), MovePoints(2));
+    let events = exec(&mut state, command::MoveTo { id: Id(0), path });
+    let moves = events
+        .iter()
+        .filter(|event| matches!(event.active_event, ActiveEvent::MoveTo(_)))
+        .count();
+    assert_eq!(moves, 1);
+    assert_eq!(state.parts().pos.get(Id(0)).0, rocks_pos);
+    let path = Path::new(vec![
+        PosHex { q: -1, r: 0 },
+        PosHex { q: -1, r: 1 },
+        blocker_pos,
+    ]);
+    let command = command::MoveTo { id: Id(2), path };
+    assert_eq!(
+        try_exec(&mut state, command).map(|_| ()),
+        Err(check::Error::TileIsBlocked { pos: blocker_pos })
+    );
+}
+
+#[test]
+fn phased_agents_pass_through_enemies_only() {
+    let phaser = component::Agent {
+        moves: Moves(1),
+        attacks: Attacks(1),
+        move_points: MovePoints(3),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        (
+            "phaser",
+            vec![
+                phaser.into(),
+                component_blocker(Weight::Normal),
+                component_abilities(&[Ability::Phase]),
+            ],
+        ),
+        (
+            "dull",
+            vec![component_agent_dull(), component_blocker(Weight::Normal)],
+        ),
+    ]);
+    let start_pos = PosHex { q: 0, r: 0 };
+    let enemy_pos = PosHex { q: 0, r: 1 };
+    let ally_pos = PosHex { q: -1, r: 1 };
+    let scenario = Scenario::default()
+        .object(P0, "phaser", start_pos)
+        .object(P1, "dull", enemy_pos)
+        .object(P0, "dull", ally_pos);
+    let mut state = debug_state(prototypes, scenario);
+    let through_enemy = Path::new(vec![start_pos, enemy_pos, PosHex { q: 0, r: 2 }]);
+    let move_to = |path: &Path| command::MoveTo {
+        id: Id(0),
+        path: path.clone(),
+    };
+    assert_eq!(
+        try_exec(&mut state, move_to(&through_enemy)).map(|_| ()),
+        Err(check::Error::TileIsBlocked { pos: enemy_pos })
+    );
+    exec(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: start_pos,
+            ability: Ability::Phase,
+        },
+    );
+    assert_eq!(state::movement_type(&state, Id(0)), MovementType::Phase);
+    let onto_enemy = Path::new(vec![start_pos, enemy_pos]);
+    assert_eq!(
+        try_exec(&mut state, move_to(&onto_enemy)).map(|_| ()),
+        Err(check::Error::TileIsBlocked { pos: enemy_pos })
+    );
+    let through_ally = Path::new(vec![start_pos, ally_pos, PosHex { q: -1, r: 2 }]);
+    assert_eq!(
+        try_exec(&mut state, move_to(&through_ally)).map(|_| ()),
+        Err(check::Error::TileIsBlocked { pos: ally_pos })
+    );
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    pathfinder.fill_map(&state, Id(0));
+    assert!(pathfinder.path(enemy_pos).is_none());
+    assert_eq!(
+        pathfinder.path(PosHex { q: 0, r: 2 }).unwrap().tiles(),
+        through_enemy.tiles()
+    );
+    exec(&mut state, move_to(&through_enemy));
+    assert_eq!(state.parts().pos.get(Id(0)).0, PosHex { q: 0, r: 2 });
+}
+
+#[test]
+fn hidden_agents_are_revealed_by_adjacent_enemies() {
+    let hitter = component::Agent {
+        moves: Moves(1),
+        move_points: MovePoints(3),
+        attack_strength: Strength(1),
+        ..agent_always_hit()
+    };
+    let prototypes = prototypes(&[
+        (
+            "sneak",
+            vec![
+                component_agent_one_attack(),
+                component_blocker(Weight::Normal),
+                component_strength(3),
+                component_abilities(&[Ability::Hide]),
+            ],
+        ),
+        (
+            "hitter",
+            vec![
+                hitter.into(),
+                component_blocker(Weight::Normal),
+                component_strength(1),
+            ],
+        ),
+    ]);
+    let hitter_pos = PosHex { q: 0, r: 3 };
+    let scenario = Scenario::default()
+        .object(P0, "sneak", PosHex { q: 0, r: 0 })
+        .object(P1, "hitter", hitter_pos);
+    let mut state = debug_state(prototypes, scenario);
+    exec(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 0 },
+            ability: Ability::Hide,
+        },
+    );
+    assert!(state::is_hidden(&state, Id(0)));
+    assert!(!state::is_hidden_from(&state, Id(0), P0));
+    exec(&mut state, command::EndTurn);
+    assert!(state::visible_enemy_agent_ids(&state, P1).is_empty());
+    let attack = command::Attack {
+        attacker_id: Id(1),
+        target_id: Id(0),
+    };
+    assert_eq!(
+        try_exec(&mut state, attack.clone()).map(|_| ()),
+        Err(check::Error::TargetIsHidden)
+    );
+    let path = Path::new(vec![
+        hitter_pos,
+        PosHex { q: 0, r: 2 },
+        PosHex { q: 0, r: 1 },
+    ]);
+    exec(&mut state, command::MoveTo { id: Id(1), path });
+    assert!(!state::is_hidden(&state, Id(0)));
+    exec(&mut state, attack);
+    assert!(state.parts().strength.get(Id(0)).strength < Strength(3));
+}
+
+#[test]
+fn thickets_hide_the_agents_that_end_their_turn_there() {
+    let prototypes = prototypes(&[(
+        "dull",
+        vec![component_agent_dull(), component_blocker(Weight::Normal)],
+    )]);
+    let thicket_pos = PosHex { q: 0, r: 0 };
+    let mut scenario = Scenario::default()
+        .object(P0, "dull", thicket_pos)
+        .object(P0, "dull", PosHex { q: 2, r: 0 })
+        .object(P1, "dull", PosHex { q: 0, r: 3 });
+    scenario.tiles.insert(thicket_pos, TileType::Thicket);
+    let mut state = debug_state(prototypes, scenario);
+    exec(&mut state, command::EndTurn);
+    assert!(state::is_hidden(&state, Id(0)));
+    assert!(!state::is_hidden(&state, Id(1)));
+    assert_eq!(state::visible_enemy_agent_ids(&state, P1), vec![Id(1)]);
+    exec(&mut state, command::EndTurn);
+    assert!(!state::is_hidden(&state, Id(0)));
+}
+
+#[test]
+fn large_agents_take_all_their_tiles() {
+    let prototypes = prototypes(&[
+        (
+            "troll",
+            vec![
+                component_agent_move_basic(),
+                component_blocker(Weight::Normal),
+                component_strength(3),
+                component::Footprint(vec![Dir::East]).into(),
+            ],
+        ),
+        (
+            "hitter",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_blocker(Weight::Normal),
+                component_strength(1),
+            ],
+        ),
+        (
+            "dull",
+            vec![component_agent_dull(), component_blocker(Weight::Normal)],
+        ),
+    ]);
+    let troll_pos = PosHex { q: 0, r: 0 };
+    let troll_tile = PosHex { q: 1, r: -1 };
+    let scenario = Scenario::default()
+        .object(P1, "troll", troll_pos)
+        .object(P0, "hitter", PosHex { q: 2, r: -1 })
+        .object(P0, "dull", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    assert!(state::is_tile_blocked(&state, troll_tile));
+    assert_eq!(state::agent_id_at_opt(&state, troll_tile), Some(Id(0)));
+    let command = command::Create {
+        prototype: "dull".into(),
+        pos: troll_tile,
+        owner: Some(P0),
+        extra_components: Vec::new(),
+    };
+    assert_eq!(
+        check::check(&state, &command.into()),
+        Err(check::Error::TileIsBlocked { pos: troll_tile })
+    );
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(1),
+            target_id: Id(0),
+        },
+    );
+    assert!(state.parts().strength.get(Id(0)).strength < Strength(3));
+    exec(&mut state, command::EndTurn);
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    pathfinder.fill_map(&state, Id(0));
+    let blocked_pos = PosHex { q: 0, r: 1 };
+    assert!(pathfinder.path(blocked_pos).is_none());
+    let path = Path::new(vec![troll_pos, blocked_pos]);
+    assert_eq!(
+        try_exec(&mut state, command::MoveTo { id: Id(0), path }).map(|_| ()),
+        Err(check::Error::TileIsBlocked {
+            pos: PosHex { q: 1, r: 0 }
+        })
+    );
+    let path = pathfinder.path(troll_tile).unwrap();
+    exec(&mut state, command::MoveTo { id: Id(0), path });
+    assert_eq!(
+        state::object_tiles(&state, Id(0)),
+        vec![troll_tile, PosHex { q: 2, r: -2 }]
+    );
+    assert!(!state::is_tile_blocked(&state, troll_pos));
+}
+
+#[test]
+fn mounts_carry_their_riders() {
+    let prototypes = prototypes(&[
+        (
+            "horse",
+            vec![
+                component_agent_move_basic(),
+                component_blocker(Weight::Normal),
+                component_strength(1),
+                component::Mount::default().into(),
+            ],
+        ),
+        (
+            "rider",
+            vec![
+                component_agent_move_basic(),
+                component_blocker(Weight::Normal),
+                component_strength(1),
+            ],
+        ),
+        (
+            "hitter",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_blocker(Weight::Normal),
+                component_strength(1),
+            ],
+        ),
+    ]);
+    let horse_pos = PosHex { q: 0, r: 0 };
+    let rider_pos = PosHex { q: 1, r: 0 };
+    let scenario = Scenario::default()
+        .object(P0, "horse", horse_pos)
+        .object(P0, "rider", rider_pos)
+        .object(P1, "hitter", PosHex { q: 0, r: -1 });
+    let mut state = debug_state(prototypes, scenario);
+    let mount = command::Mount {
+        id: Id(1),
+        mount_id: Id(0),
+    };
+    exec(&mut state, mount.clone());
+    assert!(state.parts().pos.get_opt(Id(1)).is_none());
+    assert!(!state::is_tile_blocked(&state, rider_pos));
+    assert_eq!(state::rider_id(&state, Id(0)), Some(Id(1)));
+    assert_eq!(state.parts().agent.get(Id(0)).move_points, MovePoints(6));
+    assert_eq!(state::players_agent_types(&state, P0).len(), 2);
+    let dismount_pos = state::dismount_pos(&state, Id(0)).unwrap();
+    let dismount = command::Dismount {
+        mount_id: Id(0),
+        pos: dismount_pos,
+    };
+    exec(&mut state, dismount.clone());
+    assert_eq!(state.parts().pos.get(Id(1)).0, dismount_pos);
+    assert_eq!(state.parts().agent.get(Id(0)).move_points, MovePoints(3));
+    assert_eq!(
+        try_exec(&mut state, dismount).map(|_| ()),
+        Err(check::Error::NoRider)
+    );
+    exec(&mut state, command::EndTurn);
+    exec(&mut state, command::EndTurn);
+    exec(&mut state, mount);
+    exec(&mut state, command::EndTurn);
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(2),
+            target_id: Id(0),
+        },
+    );
+    assert!(state.parts().agent.get_opt(Id(0)).is_none());
+    assert_eq!(state.parts().pos.get(Id(1)).0, horse_pos);
+    assert_eq!(state.parts().agent.get(Id(1)).move_points, MovePoints(3));
+}
+
+#[test]
+fn random_map_keeps_fixed_objects() {
+    let agent_pos = PosHex { q: 0, r: 0 };
+    let enemy_pos = PosHex { q: 0, r: 2 };
+    let mut scenario = Scenario::default()
+        .object(P0, "swordsman", agent_pos)
+        .object(P1, "imp", enemy_pos);
+    scenario.random_map = Some(Biome::Canyon);
+    scenario.seed = Some(3);
+    let (prototypes, stack_rules) = (game_prototypes(), game_stack_rules());
+    let state = State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        scenario,
+        &mut |_, _, _| {},
+    );
+    assert!(state.map().iter().count() < HexMap::<TileType>::new(Distance(5)).iter().count());
+    assert!(state.map().is_inboard(agent_pos));
+    assert!(state.map().is_inboard(enemy_pos));
+    let parts = state.parts();
+    let obstacles = parts
+        .meta
+        .ids()
+        .filter(|&id| parts.meta.get(id).name == scenario::RANDOM_MAP_OBSTACLE.into());
+    assert!(obstacles.count() > 0);
+    assert_eq!(state::check_invariants(&state), Ok(()));
+}
+
+fn deployment_scenario() -> Scenario {
+    let mut scenario = Scenario::default().object(P1, "agent", PosHex { q: 0, r: 4 });
+    scenario.randomized_objects = vec![scenario::ObjectsGroup {
+        owner: Some(P0),
+        typename: "agent".into(),
+        line: Some(scenario::Line::Front),
+        count: 2,
+        extra_components: Vec::new(),
+    }];
+    scenario.regions = vec![Region {
+        name: "camp".into(),
+        tiles: vec![PosHex { q: 0, r: -1 }, PosHex { q: 1, r: -1 }],
+        is_marked: false,
+    }];
+    scenario.deployment_zone = Some("camp".into());
+    scenario
+}
+
+fn deploy(pos: PosHex) -> command::Create {
+    command::Create {
+        owner: Some(P0),
+        pos,
+        prototype: "agent".into(),
+        extra_components: Vec::new(),
+    }
+}
+
+#[test]
+fn extra_components_get_their_base_values() {
+    let prototypes = prototypes(&[(
+        "agent",
+        vec![component_agent_move_basic(), component_strength(1)],
+    )]);
+    let scenario = Scenario::default()
+        .object(P0, "agent", PosHex { q: 0, r: 0 })
+        .object(P1, "agent", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
+    // The data files have no base values, just like the prototypes.
+    let strength = component::Strength {
+        strength: Strength(4),
+        base_strength: Strength(0),
+    };
+    let agent = component::Agent {
+        moves: Moves(2),
+        attacks: Attacks(1),
+        ..agent_dull()
+    };
+    let command = command::Create {
+        prototype: "agent".into(),
+        pos: PosHex { q: 1, r: 0 },
+        owner: Some(P0),
+        extra_components: vec![strength.into(), agent.into()],
+    };
+    exec(&mut state, command);
+    let id = Id(2);
+    assert_eq!(state.parts().strength.get(id).base_strength, Strength(4));
+    let agent = state.parts().agent.get(id);
+    assert_eq!(agent.base_moves, Moves(2));
+    assert_eq!(agent.base_attacks, Attacks(1));
+}
+
+#[test]
+fn deployment_blocks_other_commands() {
+    let prototypes = prototypes(&[("agent", [component_agent_move_basic()].to_vec())]);
+    let mut state = debug_state(prototypes, deployment_scenario());
+    assert!(state::is_deploying(&state));
+    assert!(state::players_agent_ids(&state, P0).is_empty());
+    let result = try_exec(&mut state, command::EndTurn);
+    assert_eq!(result, Err(check::Error::DeploymentIsNotOver));
+    let result = try_exec(&mut state, deploy(PosHex { q: 0, r: 1 }));
+    let pos = PosHex { q: 0, r: 1 };
+    assert_eq!(result, Err(check::Error::NotInDeploymentZone { pos }));
+    let mut enemy = deploy(PosHex { q: 0, r: -1 });
+    enemy.owner = Some(P1);
+    let result = try_exec(&mut state, enemy);
+    assert_eq!(result, Err(check::Error::DeploymentIsNotOver));
+}
+
+#[test]
+fn deployment_ends_when_everyone_is_placed() {
+    let prototypes = prototypes(&[("agent", [component_agent_move_basic()].to_vec())]);
+    let mut state = debug_state(prototypes, deployment_scenario());
+    exec(&mut state, deploy(PosHex { q: 0, r: -1 }));
+    let result = try_exec(&mut state, deploy(PosHex { q: 0, r: -1 }));
+    let pos = PosHex { q: 0, r: -1 };
+    assert_eq!(result, Err(check::Error::TileIsBlocked { pos }));
+    assert!(state::is_deploying(&state));
+    exec(&mut state, deploy(PosHex { q: 1, r: -1 }));
+    assert!(!state::is_deploying(&state));
+    assert_eq!(state::players_agent_ids(&state, P0).len(), 2);
+    exec(&mut state, command::EndTurn);
+}
+
 // TODO: test that you can't execute any commands after the battle is over
 
 #[test]
@@ -331,6 +978,29 @@ fn basic_move() {
     );
 }
 
+#[test]
+fn too_long_move_reports_the_move_points() {
+    let prototypes = prototypes(&[
+        ("mover", [component_agent_move_basic()].to_vec()),
+        ("dull", [component_agent_dull()].to_vec()),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "mover", PosHex { q: 0, r: 0 })
+        .object(P1, "dull", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(prototypes, scenario);
+    let tiles = (0..5).map(|i| PosHex { q: 0, r: -i }).collect();
+    let command = command::MoveTo {
+        id: Id(0),
+        path: Path::new(tiles),
+    };
+    let result = try_exec(&mut state, command);
+    let expected = check::Error::NotEnoughMovePoints {
+        required: MovePoints(4),
+        available: MovePoints(3),
+    };
+    assert_eq!(result, Err(expected));
+}
+
 #[test]
 fn basic_attack() {
     let prototypes = prototypes(&[
@@ -379,13 +1049,528 @@ fn basic_attack() {
 }
 
 #[test]
-fn kill_and_end_the_battle() {
+fn log_of_an_attack() {
     let prototypes = prototypes(&[
         (
             "swordsman",
-            vec![
-                component_agent_always_hit_strength_1(),
-                component_strength(1),
+            [component_agent_always_hit(), component_strength(1)].to_vec(),
+        ),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(1)].to_vec(),
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    let mut state = debug_state(prototypes, scenario);
+    let mut log = log::Log::default();
+    let command = command::Attack {
+        attacker_id: Id(0),
+        target_id: Id(1),
+    };
+    execute(&mut state, &command.into(), &mut |state, event, phase| {
+        if phase == ApplyPhase::Pre {
+            log.record(state, event);
+        }
+    })
+    .unwrap();
+    let lines: Vec<String> = log.lines().iter().map(|line| line.to_string()).collect();
+    assert_eq!(lines, ["Swordsman attacks Imp.", "Imp takes no damage."]);
+    let line = &log.lines()[0];
+    assert_eq!(line.template, "{attacker} attacks {target}.");
+}
+
+#[test]
+fn stats_of_a_battle() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+                component_abilities(&[Ability::Club]),
+            ],
+        ),
+        (
+            "imp",
+            vec![
+                component_agent_dull(),
+                component_blocker(Weight::Normal),
+                component_strength(1),
+            ],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    let mut stats = stats::Stats::default();
+    let mut record = |state: &State, event: &Event, phase| {
+        if phase == ApplyPhase::Pre {
+            stats.record(state, event);
+        }
+    };
+    let stack_rules = StackRules::default();
+    let mut state = State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        scenario,
+        &mut record,
+    );
+    state.set_deterministic_mode(true);
+    let command_club = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 0, r: 1 },
+        ability: Ability::Club,
+    };
+    execute(&mut state, &command_club.into(), &mut record).unwrap();
+    for _ in 0..2 {
+        execute(&mut state, &command::EndTurn.into(), &mut record).unwrap();
+    }
+    let command_attack = command::Attack {
+        attacker_id: Id(0),
+        target_id: Id(1),
+    };
+    execute(&mut state, &command_attack.into(), &mut record).unwrap();
+    assert!(state.battle_result().is_some());
+    assert_eq!(stats.turns(), 2);
+    let agents = stats.agents();
+    assert_eq!(agents.len(), 2);
+    let swordsman = &agents[0];
+    assert_eq!(swordsman.typename, "swordsman".into());
+    assert_eq!(swordsman.player_id, P0);
+    assert_eq!(swordsman.damage_dealt, 1);
+    assert_eq!(swordsman.damage_taken, 0);
+    assert_eq!(swordsman.kills, 1);
+    assert_eq!(swordsman.abilities_used, 1);
+    let imp = &agents[1];
+    assert_eq!(imp.player_id, P1);
+    assert_eq!(imp.damage_dealt, 0);
+    assert_eq!(imp.damage_taken, 1);
+    assert_eq!(imp.kills, 0);
+    assert!(!swordsman.is_killed);
+    assert!(imp.is_killed);
+}
+
+#[test]
+fn stats_count_the_kills_of_a_bomb() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Bomb]),
+            ],
+        ),
+        (
+            "weak",
+            [component_agent_dull(), component_strength(1)].to_vec(),
+        ),
+        ("bomb_damage", Vec::new()),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "weak", PosHex { q: 0, r: 3 })
+        .object(P1, "weak", PosHex { q: 1, r: 2 })
+        .object(P1, "weak", PosHex { q: -1, r: 3 })
+        .object(P1, "weak", PosHex { q: 3, r: -3 });
+    let mut stats = stats::Stats::default();
+    let mut record = |state: &State, event: &Event, phase| {
+        if phase == ApplyPhase::Pre {
+            stats.record(state, event);
+        }
+    };
+    let stack_rules = StackRules::default();
+    let mut state = State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        scenario,
+        &mut record,
+    );
+    state.set_deterministic_mode(true);
+    let command_bomb = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 0, r: 2 },
+        ability: Ability::Bomb,
+    };
+    execute(&mut state, &command_bomb.into(), &mut record).unwrap();
+    for _ in 0..2 {
+        execute(&mut state, &command::EndTurn.into(), &mut record).unwrap();
+    }
+    assert_eq!(stats.most_kills_at_once(P0, Ability::ExplodeDamage), 3);
+    assert_eq!(stats.most_kills_at_once(P1, Ability::ExplodeDamage), 0);
+    let killed = stats.agents().iter().filter(|a| a.is_killed).count();
+    assert_eq!(killed, 3);
+}
+
+#[test]
+fn miss_streaks() {
+    let clumsy = component::Agent {
+        attack_distance: Distance(1),
+        attacks: Attacks(2),
+        ..agent_dull()
+    };
+    let dodger = component::Agent {
+        dodge: Dodge(20),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        ("clumsy", [clumsy.into(), component_strength(1)].to_vec()),
+        (
+            "swordsman",
+            [component_agent_always_hit(), component_strength(1)].to_vec(),
+        ),
+        ("imp", [dodger.into(), component_strength(1)].to_vec()),
+        (
+            "dull",
+            [component_agent_dull(), component_strength(1)].to_vec(),
+        ),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "clumsy", PosHex { q: 0, r: 0 })
+        .object(P0, "swordsman", PosHex { q: 1, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 })
+        .object(P1, "dull", PosHex { q: 1, r: 1 });
+    scenario.dice = dice::Mode::Karma;
+    let mut state = debug_state(prototypes, scenario);
+    let attack = |attacker_id, target_id| command::Attack {
+        attacker_id,
+        target_id,
+    };
+    exec(&mut state, attack(Id(0), Id(2)));
+    exec(&mut state, attack(Id(0), Id(2)));
+    assert_eq!(state.miss_streak(P0), 2);
+    assert_eq!(state.miss_streak(P1), 0);
+    exec(&mut state, attack(Id(1), Id(3)));
+    assert_eq!(state.miss_streak(P0), 0);
+}
+
+fn seeded_battle(seed: u64) -> (State, Vec<Event>) {
+    let fighter = component::Agent {
+        attack_distance: Distance(1),
+        attacks: Attacks(5),
+        attack_strength: Strength(1),
+        attack_accuracy: Accuracy(5),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        ("fighter", [fighter.into(), component_strength(10)].to_vec()),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(10)].to_vec(),
+        ),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "fighter", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    scenario.rocky_tiles_count = 5;
+    scenario.seed = Some(seed);
+    let stack_rules = StackRules::default();
+    let mut state = State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        scenario,
+        &mut |_, _, _| {},
+    );
+    let mut events = Vec::new();
+    for _ in 0..5 {
+        let command = command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        };
+        events.extend(exec(&mut state, command));
+    }
+    (state, events)
+}
+
+#[test]
+fn same_seed_same_battle() {
+    let (state_a, events_a) = seeded_battle(42);
+    let (state_b, events_b) = seeded_battle(42);
+    assert_eq!(state_a.seed(), 42);
+    assert_eq!(events_a, events_b);
+    let rocks = |state: &State| -> Vec<PosHex> {
+        state
+            .map()
+            .iter()
+            .filter(|&pos| state.map().tile(pos) == TileType::Rocks)
+            .collect()
+    };
+    assert_eq!(rocks(&state_a), rocks(&state_b));
+    let strength = |state: &State| state.parts().strength.get(Id(1)).strength;
+    assert_eq!(strength(&state_a), strength(&state_b));
+}
+
+#[test]
+fn simulations_roll_their_own_dice() {
+    let (state, _) = seeded_battle(42);
+    let rolls = |mut state: State| -> Vec<i32> {
+        (0..20).map(|_| dice::roll_attack(&mut state, P0)).collect()
+    };
+    assert_ne!(rolls(state.fork_for_simulation()), rolls(state));
+}
+
+#[test]
+fn history_replays_the_battle() {
+    let (mut state, mut events) = seeded_battle(7);
+    let strength = |state: &State| state.parts().strength.get(Id(1)).strength;
+    let strength_after_attacks = strength(&state);
+    events.extend(exec(&mut state, command::EndTurn));
+    events.extend(exec(&mut state, command::EndTurn));
+    let history = state.history();
+    assert_eq!(history.commands_count(), 7);
+    let players: Vec<PlayerId> = history.turns().map(|turn| turn.player_id).collect();
+    assert_eq!(players, [P0, P1, P0]);
+    assert_eq!(history.turns().last().unwrap().commands.len(), 0);
+    let recorded: Vec<Event> = history
+        .turns()
+        .flat_map(|turn| turn.events.clone())
+        .collect();
+    assert_eq!(recorded, events);
+    let replayed = history::replay(&state, 7, &mut |_, _, _| {});
+    let replayed_events: Vec<Event> = replayed
+        .history()
+        .turns()
+        .flat_map(|turn| turn.events.clone())
+        .collect();
+    assert_eq!(replayed_events, events);
+    assert_eq!(replayed.player_id(), P0);
+    let rewound = history::replay(&state, 5, &mut |_, _, _| {});
+    assert_eq!(strength(&rewound), strength_after_attacks);
+    assert_eq!(rewound.history().turns().count(), 1);
+}
+
+#[test]
+fn bomb_has_two_charges() {
+    let thrower = component::Agent {
+        attacks: Attacks(3),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                thrower.into(),
+                component_strength(1),
+                component_abilities(&[Ability::Bomb]),
+            ],
+        ),
+        ("imp", vec![component_agent_dull()]),
+        ("bomb_damage", vec![component_strength(1)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
+    let throw = |q, r| command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q, r },
+        ability: Ability::Bomb,
+    };
+    let status = |state: &State| state.abilities(Id(0))[0];
+    assert_eq!((status(&state).charges, status(&state).max_charges), (2, 2));
+    exec(&mut state, throw(2, 0));
+    assert_eq!((status(&state).charges, status(&state).cooldown), (1, 2));
+    exec(&mut state, throw(-2, 0));
+    assert_eq!((status(&state).charges, status(&state).cooldown), (0, 2));
+    let result = try_exec(&mut state, throw(0, -2));
+    assert_eq!(result, Err(check::Error::AbilityIsNotReady));
+    for _ in 0..2 {
+        exec(&mut state, command::EndTurn);
+        exec(&mut state, command::EndTurn);
+    }
+    // The charges come back one at a time.
+    assert_eq!((status(&state).charges, status(&state).cooldown), (1, 2));
+}
+
+#[test]
+fn jump_costs_stamina_instead_of_cooldown() {
+    let jumper = component::Agent {
+        attacks: Attacks(3),
+        ..agent_dull()
+    };
+    let stamina = component::Stamina {
+        base_stamina: 3,
+        stamina: 3,
+        regeneration: 1,
+        costs: vec![(Ability::Jump, 2)].into_iter().collect(),
+    };
+    let prototypes = prototypes(&[
+        (
+            "jumper",
+            vec![
+                jumper.into(),
+                component_strength(1),
+                component_abilities(&[Ability::Jump]),
+                stamina.into(),
+            ],
+        ),
+        ("imp", vec![component_agent_dull()]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "jumper", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 4 });
+    let mut state = debug_state(prototypes, scenario);
+    let jump = |q, r| command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q, r },
+        ability: Ability::Jump,
+    };
+    let stamina = |state: &State| state.parts().stamina.get(Id(0)).stamina;
+    exec(&mut state, jump(2, 0));
+    assert_eq!(stamina(&state), 1);
+    assert_eq!(state.abilities(Id(0))[0].cooldown, 0);
+    let result = try_exec(&mut state, jump(0, 0));
+    let expected = Err(check::Error::NotEnoughStamina {
+        required: 2,
+        available: 1,
+    });
+    assert_eq!(result, expected);
+    exec(&mut state, command::EndTurn);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(stamina(&state), 2);
+    exec(&mut state, jump(0, 0));
+    assert_eq!(stamina(&state), 0);
+}
+
+fn simulate_duel(attack: Component) -> simulate::Report {
+    let prototypes = prototypes(&[
+        ("swordsman", vec![attack, component_strength(1)]),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    scenario.seed = Some(1);
+    simulate::simulate(
+        &prototypes,
+        &StackRules::default(),
+        &game_ability_definitions(),
+        &scenario,
+        3,
+    )
+}
+
+#[test]
+fn simulate_wins() {
+    let report = simulate_duel(component_agent_always_hit_strength_1());
+    assert_eq!(report.battles, 3);
+    assert_eq!(report.draws, 0);
+    let winner = simulate::PlayerStats {
+        wins: 3,
+        damage_taken: 0,
+        losses: 0,
+    };
+    let loser = simulate::PlayerStats {
+        wins: 0,
+        damage_taken: 3,
+        losses: 3,
+    };
+    assert_eq!(report.players, vec![winner, loser]);
+}
+
+#[test]
+fn resolve_a_battle() {
+    let hitting = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    scenario.seed = Some(1);
+    let stack_rules = StackRules::default();
+    let result = simulate::resolve(
+        &hitting,
+        &stack_rules,
+        &game_ability_definitions(),
+        &scenario,
+    )
+    .unwrap();
+    assert_eq!(result.winner_id, P0);
+    assert_eq!(result.survivor_types, vec!["swordsman".into()]);
+    assert!(!result.is_retreat);
+    let dull = prototypes(&[
+        (
+            "swordsman",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    assert_eq!(
+        simulate::resolve(&dull, &stack_rules, &game_ability_definitions(), &scenario),
+        None
+    );
+}
+
+#[test]
+fn simulate_draws() {
+    let report = simulate_duel(component_agent_dull());
+    assert_eq!(report.draws, 3);
+    assert_eq!(report.rounds, simulate::MAX_ROUNDS * 3);
+    assert_eq!(report.players[0].wins, 0);
+}
+
+#[test]
+fn ability_charges() {
+    let charges = |n| component::Charges(vec![(Ability::Rage, n)].into_iter().collect());
+    let prototypes = prototypes(&[
+        (
+            "rested",
+            vec![
+                component_agent_one_attack(),
+                component_strength(1),
+                component_abilities(&[Ability::Rage]),
+                charges(1).into(),
+            ],
+        ),
+        (
+            "tired",
+            vec![
+                component_agent_one_attack(),
+                component_strength(1),
+                component_abilities(&[Ability::Rage]),
+                charges(0).into(),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let pos_rested = PosHex { q: 0, r: 0 };
+    let pos_tired = PosHex { q: 1, r: 0 };
+    let scenario = Scenario::default()
+        .object(P0, "rested", pos_rested)
+        .object(P0, "tired", pos_tired)
+        .object(P1, "imp", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(prototypes, scenario);
+    let rage = |id, pos| command::UseAbility {
+        id,
+        pos,
+        ability: Ability::Rage,
+    };
+    assert!(!state::can_agent_use_ability(&state, Id(1), &Ability::Rage));
+    let result = try_exec(&mut state, rage(Id(1), pos_tired));
+    assert_eq!(result, Err(check::Error::NoChargesLeft));
+    exec(&mut state, rage(Id(0), pos_rested));
+    assert_eq!(state.parts().charges.get(Id(0)), &charges(0));
+}
+
+#[test]
+fn kill_and_end_the_battle() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
             ],
         ),
         (
@@ -418,390 +1603,3205 @@ fn kill_and_end_the_battle() {
                     Id(1),
                     vec![effect::Kill {
                         attacker_pos: Some(attacker_pos),
+                        is_drowned: false,
                     }
                     .into()],
                 )],
                 timed_effects: Vec::new(),
                 scheduled_abilities: Vec::new(),
             },
+            event_leave_corpse(Id(1), PosHex { q: 0, r: 1 }, Id(2), P0),
             event_end_battle(PlayerId(0), &["swordsman".into()]),
         ],
     );
 }
 
 #[test]
-fn push_boulder() {
-    // TODO: hammerman push a boulder
-}
-
-#[test]
-fn stun_and_push_to_spikes() {
-    // TODO: Stun an agent to spikes
-    // They should die on these spikes before LastingEffect::Stun is over
-}
-
-#[test]
-fn throw_bomb_no_harm() {
+fn fallen_veteran_death_cause() {
+    let veteran = component::Veteran { index: 3, kills: 0 };
     let prototypes = prototypes(&[
         (
-            "thrower",
+            "swordsman",
+            [
+                component_agent_dull(),
+                component_strength(1),
+                veteran.into(),
+            ]
+            .to_vec(),
+        ),
+        (
+            "imp",
             vec![
-                component_agent_one_attack(),
-                component_abilities(&[Ability::Bomb]),
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
             ],
         ),
-        ("dull", [component_agent_dull()].to_vec()),
-        ("bomb_damage", Vec::new()),
     ]);
     let scenario = Scenario::default()
-        .object(P0, "thrower", PosHex { q: 0, r: 0 })
-        .object(P1, "dull", PosHex { q: 0, r: 3 });
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
     let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
+    exec(&mut state, command::EndTurn);
+    exec(
         &mut state,
-        command::UseAbility {
-            id: Id(0),
-            pos: PosHex { q: 0, r: -2 },
-            ability: Ability::Bomb,
+        command::Attack {
+            attacker_id: Id(1),
+            target_id: Id(0),
         },
-        &[Event {
-            active_event: event::UseAbility {
-                id: Id(0),
-                pos: PosHex { q: 0, r: -2 },
-                ability: Ability::Bomb,
-            }
-            .into(),
-            actor_ids: vec![Id(0)],
-            instant_effects: vec![(
-                Id(2),
-                vec![
-                    effect::Create {
-                        pos: PosHex { q: 0, r: 0 },
-                        prototype: "bomb_damage".into(),
-                        components: vec![
-                            component::Pos(PosHex { q: 0, r: 0 }).into(),
-                            component_meta("bomb_damage"),
-                        ],
-                        is_teleported: false,
-                    }
-                    .into(),
-                    effect::Throw {
-                        from: PosHex { q: 0, r: 0 },
-                        to: PosHex { q: 0, r: -2 },
-                    }
-                    .into(),
-                ],
-            )],
-            timed_effects: Vec::new(),
-            scheduled_abilities: vec![(
-                Id(2),
-                vec![PlannedAbility {
-                    rounds: 1.into(),
-                    phase: Phase(0),
-                    ability: Ability::ExplodeDamage,
-                }],
-            )],
-        }],
-    );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[event_end_turn(P0, &[Id(0)]), event_begin_turn(P1, &[Id(1)])],
-    );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P1, &[Id(1)]),
-            event_begin_turn(P0, &[Id(0)]),
-            Event {
-                active_event: event::UseAbility {
-                    id: Id(2),
-                    pos: PosHex { q: 0, r: -2 },
-                    ability: Ability::ExplodeDamage,
-                }
-                .into(),
-                actor_ids: vec![Id(2)],
-                instant_effects: vec![(Id(2), vec![Effect::Vanish])],
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
-            },
-        ],
     );
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P1);
+    let expected = state::FallenVeteran {
+        veteran,
+        cause: state::DeathCause::Attack("imp".into()),
+    };
+    assert_eq!(result.fallen, vec![expected]);
 }
 
 #[test]
-fn throw_bomb_damage() {
+fn kill_leaves_blood_decal() {
     let prototypes = prototypes(&[
         (
-            "thrower",
+            "swordsman",
             vec![
-                component_agent_one_attack(),
-                component_abilities(&[Ability::Bomb]),
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
             ],
         ),
         (
-            "weak",
-            [component_agent_dull(), component_strength(2)].to_vec(),
+            "imp",
+            [component_agent_dull(), component_strength(1)].to_vec(),
         ),
-        ("bomb_damage", Vec::new()),
     ]);
+    let target_pos = PosHex { q: 0, r: 1 };
     let scenario = Scenario::default()
-        .object(P0, "thrower", PosHex { q: 0, r: 0 })
-        .object(P1, "weak", PosHex { q: 0, r: 3 });
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", target_pos);
     let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
+    assert!(state.decals().is_empty());
+    exec(
         &mut state,
-        command::UseAbility {
-            id: Id(0),
-            pos: PosHex { q: 0, r: 2 },
-            ability: Ability::Bomb,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
         },
-        &[Event {
-            active_event: event::UseAbility {
-                id: Id(0),
-                pos: PosHex { q: 0, r: 2 },
-                ability: Ability::Bomb,
-            }
-            .into(),
-            actor_ids: vec![Id(0)],
-            instant_effects: vec![(
-                Id(2),
-                vec![
-                    effect::Create {
-                        pos: PosHex { q: 0, r: 0 },
-                        prototype: "bomb_damage".into(),
-                        components: vec![
-                            component::Pos(PosHex { q: 0, r: 0 }).into(),
-                            component_meta("bomb_damage"),
-                        ],
-                        is_teleported: false,
-                    }
-                    .into(),
-                    effect::Throw {
-                        from: PosHex { q: 0, r: 0 },
-                        to: PosHex { q: 0, r: 2 },
-                    }
-                    .into(),
-                ],
-            )],
-            timed_effects: Vec::new(),
-            scheduled_abilities: vec![(
-                Id(2),
-                vec![PlannedAbility {
-                    rounds: 1.into(),
-                    phase: Phase(0),
-                    ability: Ability::ExplodeDamage,
-                }],
-            )],
-        }],
-    );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[event_end_turn(P0, &[Id(0)]), event_begin_turn(P1, &[Id(1)])],
     );
+    let expected = [Decal {
+        pos: target_pos,
+        kind: decal::Kind::Blood,
+    }];
+    assert_eq!(state.decals(), &expected);
+}
+
+#[test]
+fn wounds_on_a_bloody_tile_leave_no_more_decals() {
+    let swordsman = component::Agent {
+        attacks: Attacks(2),
+        attack_strength: Strength(1),
+        ..agent_always_hit()
+    };
+    let prototypes = prototypes(&[
+        ("swordsman", vec![swordsman.into(), component_strength(1)]),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(5)].to_vec(),
+        ),
+    ]);
+    let target_pos = PosHex { q: 0, r: 1 };
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", target_pos);
+    let mut state = debug_state(prototypes, scenario);
+    let attack = command::Attack {
+        attacker_id: Id(0),
+        target_id: Id(1),
+    };
+    exec(&mut state, attack.clone());
+    exec(&mut state, attack);
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(3));
+    let expected = [Decal {
+        pos: target_pos,
+        kind: decal::Kind::Blood,
+    }];
+    assert_eq!(state.decals(), &expected);
+}
+
+#[test]
+fn summoned_agents_die_with_summoner() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        (
+            "summoner",
+            [component_agent_dull(), component_strength(1)].to_vec(),
+        ),
+        (
+            "imp",
+            vec![
+                component_agent_dull(),
+                component_strength(1),
+                component::SummonedBy(Id(1)).into(),
+            ],
+        ),
+    ]);
+    let attacker_pos = PosHex { q: 0, r: 0 };
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", attacker_pos)
+        .object(P1, "summoner", PosHex { q: 0, r: 1 })
+        .object(P1, "imp", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
     exec_and_check(
         &mut state,
-        command::EndTurn,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
         &[
-            event_end_turn(P1, &[Id(1)]),
-            event_begin_turn(P0, &[Id(0)]),
             Event {
-                active_event: event::UseAbility {
-                    id: Id(2),
-                    pos: PosHex { q: 0, r: 2 },
-                    ability: Ability::ExplodeDamage,
+                active_event: event::Attack {
+                    attacker_id: Id(0),
+                    target_id: Id(1),
+                    mode: AttackMode::Active,
+                    weapon_type: WeaponType::Slash,
                 }
                 .into(),
-                actor_ids: vec![Id(2)],
-                instant_effects: vec![
-                    (
-                        Id(1),
-                        vec![effect::Wound {
-                            damage: Strength(1),
-                            armor_break: Strength(0),
-                            attacker_pos: None,
-                        }
-                        .into()],
-                    ),
-                    (Id(2), vec![Effect::Vanish]),
-                ],
+                actor_ids: vec![Id(0)],
+                instant_effects: vec![(
+                    Id(1),
+                    vec![effect::Kill {
+                        attacker_pos: Some(attacker_pos),
+                        is_drowned: false,
+                    }
+                    .into()],
+                )],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+            event_leave_corpse(Id(1), PosHex { q: 0, r: 1 }, Id(3), P0),
+            Event {
+                active_event: event::Unsummon { summoner_id: Id(1) }.into(),
+                actor_ids: Vec::new(),
+                instant_effects: vec![(
+                    Id(2),
+                    vec![effect::Kill {
+                        attacker_pos: None,
+                        is_drowned: false,
+                    }
+                    .into()],
+                )],
                 timed_effects: Vec::new(),
                 scheduled_abilities: Vec::new(),
             },
+            event_end_battle(PlayerId(0), &["swordsman".into()]),
         ],
     );
 }
 
-#[test]
-fn throw_bomb_poison() {
-    let prototypes = prototypes(&[
+fn necromancer_prototypes() -> Prototypes {
+    prototypes(&[
         (
-            "thrower",
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+        (
+            "necromancer",
             vec![
                 component_agent_one_attack(),
-                component_abilities(&[Ability::BombPoison]),
+                component_strength(1),
+                component_abilities(&[Ability::Raise]),
             ],
         ),
-        ("weak", vec![component_agent_dull(), component_strength(2)]),
-        ("bomb_poison", Vec::new()),
         (
-            "poison_cloud",
-            vec![component_passive_abilities(&[PassiveAbility::Poison])],
+            "skeleton",
+            vec![component_agent_dull(), component_strength(1)],
         ),
-    ]);
+    ])
+}
+
+#[test]
+fn necromancer_raises_a_skeleton_from_a_corpse() {
+    let corpse_pos = PosHex { q: 0, r: 1 };
     let scenario = Scenario::default()
-        .object(P0, "thrower", PosHex { q: 0, r: 0 })
-        .object(P1, "weak", PosHex { q: 0, r: 3 });
-    let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", corpse_pos)
+        .object(P1, "necromancer", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(necromancer_prototypes(), scenario);
+    let raise = command::UseAbility {
+        id: Id(2),
+        pos: corpse_pos,
+        ability: Ability::Raise,
+    };
+    exec(
         &mut state,
-        command::UseAbility {
-            id: Id(0),
-            pos: PosHex { q: 0, r: 2 },
-            ability: Ability::BombPoison,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
         },
-        &[Event {
-            active_event: event::UseAbility {
-                id: Id(0),
-                pos: PosHex { q: 0, r: 2 },
-                ability: Ability::BombPoison,
+    );
+    let corpse_id = state::corpse_id_at_opt(&state, corpse_pos).unwrap();
+    exec(&mut state, command::EndTurn);
+    let raise_nothing = command::UseAbility {
+        pos: PosHex { q: 0, r: 3 },
+        ..raise.clone()
+    };
+    assert_eq!(
+        try_exec(&mut state, raise_nothing),
+        Err(check::Error::NoTarget)
+    );
+    exec(&mut state, raise);
+    assert!(!state.parts().is_exist(corpse_id));
+    let skeleton_id = state::agent_id_at_opt(&state, corpse_pos).unwrap();
+    assert_eq!(state.parts().belongs_to.get(skeleton_id).0, P1);
+    assert_eq!(state.parts().meta.get(skeleton_id).name, "skeleton".into());
+    // The skeleton is bound to the necromancer and leaves no corpse.
+    assert_eq!(state.parts().summoned_by.get(skeleton_id).0, Id(2));
+    exec(&mut state, command::EndTurn);
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: skeleton_id,
+        },
+    );
+    assert!(state::corpse_id_at_opt(&state, corpse_pos).is_none());
+}
+
+#[test]
+fn corpses_rot_away() {
+    let corpse_pos = PosHex { q: 0, r: 1 };
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", corpse_pos)
+        .object(P1, "necromancer", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(necromancer_prototypes(), scenario);
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
+    );
+    for _ in 0..4 {
+        exec(&mut state, command::EndTurn);
+    }
+    assert!(state::corpse_id_at_opt(&state, corpse_pos).is_some());
+    for _ in 0..2 {
+        exec(&mut state, command::EndTurn);
+    }
+    assert!(state::corpse_id_at_opt(&state, corpse_pos).is_none());
+}
+
+#[test]
+fn badly_wounded_agents_stay_injured() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component::Agent {
+                    attacks: Attacks(2),
+                    base_attacks: Attacks(2),
+                    attack_distance: Distance(1),
+                    attack_accuracy: Accuracy(10 + 1),
+                    attack_strength: Strength(1),
+                    ..agent_dull()
+                }
+                .into(),
+                component_strength(1),
+            ],
+        ),
+        (
+            "imp",
+            vec![
+                component::Agent {
+                    attacks: Attacks(2),
+                    base_attacks: Attacks(2),
+                    ..agent_dull()
+                }
+                .into(),
+                component_strength(3),
+            ],
+        ),
+        (
+            "healer",
+            vec![
+                component_agent_one_attack(),
+                component_strength(1),
+                component_abilities(&[Ability::Heal]),
+            ],
+        ),
+    ]);
+    let imp_pos = PosHex { q: 0, r: 1 };
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", imp_pos)
+        .object(P1, "healer", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(prototypes, scenario);
+    let imp_id = Id(1);
+    let attack = command::Attack {
+        attacker_id: Id(0),
+        target_id: imp_id,
+    };
+    exec(&mut state, attack.clone());
+    // Two thirds of the strength is still fine.
+    assert!(!state::is_injured(&state, imp_id));
+    let events = exec(&mut state, attack);
+    let expected = event::Injury { id: imp_id }.into();
+    assert!(events.iter().any(|event| event.active_event == expected));
+    assert!(state::is_injured(&state, imp_id));
+    let terms = execute::attack_terms(&state, imp_id, Id(0));
+    assert_eq!(terms.injury_penalty, 1);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.parts().agent.get(imp_id).attacks, Attacks(1));
+    exec(
+        &mut state,
+        command::UseAbility {
+            id: Id(2),
+            pos: imp_pos,
+            ability: Ability::Heal,
+        },
+    );
+    assert_eq!(state.parts().strength.get(imp_id).strength, Strength(3));
+    assert!(state::is_injured(&state, imp_id));
+}
+
+fn counter_prototypes(cannot_counter: bool) -> Prototypes {
+    prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(5),
+            ],
+        ),
+        (
+            "imp",
+            vec![
+                component::Agent {
+                    // A wound takes one of the attacks away.
+                    attacks: Attacks(2),
+                    attack_distance: Distance(1),
+                    attack_accuracy: Accuracy(15),
+                    attack_strength: Strength(3),
+                    cannot_counter,
+                    ..agent_dull()
+                }
+                .into(),
+                component_strength(5),
+            ],
+        ),
+    ])
+}
+
+fn attack_modes(events: &[Event]) -> Vec<AttackMode> {
+    events
+        .iter()
+        .filter_map(|event| match &event.active_event {
+            ActiveEvent::Attack(attack) => Some(attack.mode),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn melee_attacks_are_countered_with_half_strength() {
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    let mut state = debug_state(counter_prototypes(false), scenario);
+    let command = command::Attack {
+        attacker_id: Id(0),
+        target_id: Id(1),
+    };
+    let events = exec(&mut state, command);
+    assert_eq!(
+        attack_modes(&events),
+        [AttackMode::Active, AttackMode::Counter]
+    );
+    assert_eq!(state.parts().strength.get(Id(0)).strength, Strength(3));
+    assert_eq!(state.parts().agent.get(Id(1)).attacks, Attacks(0));
+}
+
+#[test]
+fn some_agents_never_counter() {
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    let mut state = debug_state(counter_prototypes(true), scenario);
+    let command = command::Attack {
+        attacker_id: Id(0),
+        target_id: Id(1),
+    };
+    let events = exec(&mut state, command);
+    // A regular reaction at the full strength is still possible.
+    assert_eq!(
+        attack_modes(&events),
+        [AttackMode::Active, AttackMode::Reactive]
+    );
+    assert_eq!(state.parts().strength.get(Id(0)).strength, Strength(2));
+}
+
+#[test]
+fn batch_move_and_attack() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component::Agent {
+                    moves: Moves(1),
+                    move_points: MovePoints(3),
+                    attacks: Attacks(1),
+                    attack_distance: Distance(1),
+                    attack_accuracy: Accuracy(10 + 1),
+                    ..agent_dull()
+                }
+                .into(),
+                component_strength(1),
+            ],
+        ),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(2)].to_vec(),
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(prototypes, scenario);
+    let command_move: Command = command::MoveTo {
+        id: Id(0),
+        path: Path::new(vec![PosHex { q: 0, r: 0 }, PosHex { q: 0, r: 1 }]),
+    }
+    .into();
+    let command_attack: Command = command::Attack {
+        attacker_id: Id(0),
+        target_id: Id(1),
+    }
+    .into();
+    // The attack is too far from the current state, but not after the move.
+    assert_eq!(
+        check::check(&state, &command_attack),
+        Err(check::Error::DistanceIsTooBig {
+            distance: Distance(2),
+            max: Distance(1)
+        })
+    );
+    // The second attack can't be executed, so the whole batch is rejected.
+    let bad_batch = command::CommandBatch::new(vec![
+        command_move.clone(),
+        command_attack.clone(),
+        command_attack.clone(),
+    ]);
+    let result = execute_batch(&mut state, &bad_batch, &mut |_, _, _| {});
+    assert_eq!(result, Err(check::Error::NotEnoughAttacks));
+    assert_eq!(state.parts().pos.get(Id(0)).0, PosHex { q: 0, r: 0 });
+    assert_eq!(state.history().commands_count(), 0);
+    let other_agent_batch = command::CommandBatch::new(vec![
+        command_move.clone(),
+        command::Attack {
+            attacker_id: Id(1),
+            target_id: Id(0),
+        }
+        .into(),
+    ]);
+    assert_eq!(
+        check::check_batch(&state, &other_agent_batch),
+        Err(check::Error::BadActorId)
+    );
+    let batch = command::CommandBatch::new(vec![command_move, command_attack]);
+    assert_eq!(check::check_batch(&state, &batch), Ok(()));
+    let mut events_count = 0;
+    let result = execute_batch(&mut state, &batch, &mut |_, _, phase| {
+        if phase == ApplyPhase::Pre {
+            events_count += 1;
+        }
+    });
+    assert_eq!(result, Ok(2));
+    assert_eq!(events_count, 2);
+    assert_eq!(state.parts().pos.get(Id(0)).0, PosHex { q: 0, r: 1 });
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(2));
+}
+
+#[test]
+fn reaction_attack_cancels_the_rest_of_a_batch() {
+    let swordsman = component::Agent {
+        moves: Moves(1),
+        move_points: MovePoints(3),
+        attacks: Attacks(1),
+        attack_distance: Distance(1),
+        attack_accuracy: Accuracy(10 + 1),
+        // Only the guard's hit can cancel the attack.
+        cannot_counter: true,
+        ..agent_dull()
+    };
+    let guard = component::Agent {
+        attack_strength: Strength(1),
+        attack_distance: Distance(1),
+        attack_accuracy: Accuracy(5),
+        attacks: Attacks(1),
+        reactive_attacks: Attacks(1),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        ("swordsman", vec![swordsman.into(), component_strength(1)]),
+        ("guard", vec![guard.into(), component_strength(2)]),
+    ]);
+    let batch = command::CommandBatch::new(vec![
+        command::MoveTo {
+            id: Id(0),
+            path: Path::new(vec![PosHex { q: 0, r: 0 }, PosHex { q: 0, r: 1 }]),
+        }
+        .into(),
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        }
+        .into(),
+    ]);
+    // The check rolls its own dice, so some of the real reactions
+    // still kill the swordsman after the whole batch has been accepted.
+    let is_cancelled = |seed| {
+        let mut scenario = Scenario::default()
+            .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+            .object(P1, "guard", PosHex { q: 0, r: 2 });
+        scenario.seed = Some(seed);
+        let mut state = debug_state(prototypes.clone(), scenario);
+        state.set_deterministic_mode(false);
+        let result = execute_batch(&mut state, &batch, &mut |_, _, _| {});
+        if result != Ok(1) {
+            return false;
+        }
+        assert!(!state.parts().is_exist(Id(0)));
+        assert_eq!(state.history().commands_count(), 1);
+        true
+    };
+    assert!((0..100).any(is_cancelled));
+}
+
+#[test]
+fn convert_joker_into_move() {
+    let mover = component::Agent {
+        moves: Moves(0),
+        jokers: Jokers(1),
+        base_jokers: Jokers(1),
+        move_points: MovePoints(3),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        ("mover", [mover.into()].to_vec()),
+        ("dull", [component_agent_dull()].to_vec()),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "mover", PosHex { q: 0, r: 0 })
+        .object(P1, "dull", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(prototypes, scenario);
+    let command = command::ConvertJoker {
+        id: Id(0),
+        into: command::JokerUse::Move,
+    };
+    exec_and_check(
+        &mut state,
+        command.clone(),
+        &[Event {
+            active_event: event::ConvertJoker {
+                id: Id(0),
+                into: command::JokerUse::Move,
+            }
+            .into(),
+            actor_ids: vec![Id(0)],
+            instant_effects: Vec::new(),
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        }],
+    );
+    let agent = state.parts().agent.get(Id(0));
+    assert_eq!(agent.moves, Moves(1));
+    assert_eq!(agent.jokers, Jokers(0));
+    assert_eq!(
+        try_exec(&mut state, command),
+        Err(check::Error::NotEnoughJokers)
+    );
+}
+
+#[test]
+fn legal_commands_hint() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component::Agent {
+                    moves: Moves(1),
+                    move_points: MovePoints(3),
+                    attacks: Attacks(1),
+                    attack_distance: Distance(1),
+                    attack_accuracy: Accuracy(10 + 1),
+                    ..agent_dull()
+                }
+                .into(),
+                component_strength(1),
+            ],
+        ),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(2)].to_vec(),
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(prototypes, scenario);
+    let commands = hint::legal_commands(&state, Id(0));
+    assert_eq!(commands.len(), 2);
+    match &commands[0] {
+        Command::MoveTo(command) => assert_eq!(command.path.tiles().len(), 4),
+        command => panic!("Unexpected command: {:?}", command),
+    }
+    assert!(matches!(commands[1], Command::EndTurn(_)));
+    assert!(hint::legal_commands(&state, Id(1)).is_empty());
+    let command_move = command::MoveTo {
+        id: Id(0),
+        path: Path::new(vec![PosHex { q: 0, r: 0 }, PosHex { q: 0, r: 1 }]),
+    };
+    exec(&mut state, command_move);
+    let commands = hint::legal_commands(&state, Id(0));
+    assert_eq!(commands.len(), 2);
+    match &commands[0] {
+        Command::Attack(command) => assert_eq!(command.target_id, Id(1)),
+        command => panic!("Unexpected command: {:?}", command),
+    }
+    assert!(matches!(commands[1], Command::EndTurn(_)));
+}
+
+#[test]
+fn kill_all_objective_status() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            [
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ]
+            .to_vec(),
+        ),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(1)].to_vec(),
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 })
+        .object(P1, "imp", PosHex { q: 1, r: 1 });
+    let mut state = debug_state(prototypes, scenario);
+    let status = |state: &State| objective::statuses(state, P0)[0];
+    assert_eq!(
+        status(&state),
+        objective::Status {
+            kind: objective::Kind::KillAll,
+            remaining: 2,
+            is_failed: false,
+        }
+    );
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
+    );
+    assert_eq!(status(&state).remaining, 1);
+    assert!(!status(&state).is_complete());
+}
+
+#[test]
+fn objective_kill_boss() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+        (
+            "imp_boss",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp_boss", PosHex { q: 0, r: 1 })
+        .object(P1, "imp", PosHex { q: 2, r: 2 });
+    scenario.objectives = vec![Objective::KillBoss("imp_boss".into())];
+    let mut state = debug_state(prototypes, scenario);
+    assert_eq!(objective::winner(&state), None);
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
+    );
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P0);
+}
+
+#[test]
+fn objective_survive_n_turns_and_protect() {
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 2, r: 2 });
+    scenario.objectives = vec![
+        Objective::SurviveNTurns(1),
+        Objective::ProtectAgent("peasant".into()),
+    ];
+    let mut state = debug_state(prototypes, scenario);
+    let statuses = objective::statuses(&state, P0);
+    assert_eq!(statuses[0].remaining, 1);
+    assert!(!statuses[1].is_failed);
+    exec(&mut state, command::EndTurn);
+    assert!(state.battle_result().is_none());
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.rounds_passed(), 1);
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P0);
+}
+
+#[test]
+fn turn_limit_defeat() {
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 2, r: 2 });
+    scenario.turn_limit = Some(TurnLimit {
+        turns: 1,
+        on_expire: Expiry::Defeat,
+    });
+    let mut state = debug_state(prototypes, scenario);
+    let statuses = objective::statuses(&state, P0);
+    assert_eq!(statuses[1].kind, objective::Kind::TurnLimit);
+    assert_eq!(statuses[1].remaining, 1);
+    exec(&mut state, command::EndTurn);
+    assert!(state.battle_result().is_none());
+    exec(&mut state, command::EndTurn);
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P1);
+}
+
+#[test]
+fn noncombatant_can_not_attack() {
+    let merchant = component::Agent {
+        is_noncombatant: true,
+        jokers: Jokers(1),
+        ..agent_always_hit()
+    };
+    let prototypes = prototypes(&[
+        ("merchant", [merchant.into()].to_vec()),
+        ("imp", [component_agent_dull()].to_vec()),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "merchant", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    let mut state = debug_state(prototypes, scenario);
+    let command = command::Attack {
+        attacker_id: Id(0),
+        target_id: Id(1),
+    };
+    assert_eq!(
+        try_exec(&mut state, command).map(|_| ()),
+        Err(check::Error::Noncombatant)
+    );
+}
+
+fn escort_scenario() -> (Prototypes, Scenario) {
+    let merchant = component::Agent {
+        is_noncombatant: true,
+        moves: Moves(1),
+        move_points: MovePoints(3),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        ("merchant", vec![merchant.into(), component_strength(1)]),
+        (
+            "guard",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "merchant", PosHex { q: 0, r: 0 })
+        .object(P0, "guard", PosHex { q: 1, r: 0 })
+        .object(P1, "imp", PosHex { q: -2, r: 3 });
+    scenario.objectives = vec![Objective::Escort {
+        typename: "merchant".into(),
+        exit: PosHex { q: 0, r: -2 },
+    }];
+    (prototypes, scenario)
+}
+
+#[test]
+fn escort_is_won_at_the_exit() {
+    let (prototypes, scenario) = escort_scenario();
+    let mut state = debug_state(prototypes, scenario);
+    let statuses = objective::statuses(&state, P0);
+    assert_eq!(statuses[0].kind, objective::Kind::Escort);
+    assert_eq!(statuses[0].remaining, 2);
+    let tiles = vec![
+        PosHex { q: 0, r: 0 },
+        PosHex { q: 0, r: -1 },
+        PosHex { q: 0, r: -2 },
+    ];
+    let command = command::MoveTo {
+        id: Id(0),
+        path: Path::new(tiles),
+    };
+    exec(&mut state, command);
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P0);
+}
+
+#[test]
+fn escort_is_lost_with_the_escorted() {
+    let (prototypes, scenario) = escort_scenario();
+    let mut state = debug_state(prototypes, scenario);
+    let command = command::Attack {
+        attacker_id: Id(1),
+        target_id: Id(0),
+    };
+    exec(&mut state, command);
+    assert!(state.parts().agent.get_opt(Id(0)).is_none());
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P1);
+}
+
+#[test]
+fn sudden_death_burns_the_edges() {
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+        (
+            "fire",
+            vec![component_passive_abilities(&[PassiveAbility::Burn])],
+        ),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: -3 });
+    scenario.map_radius = Distance(3);
+    scenario.turn_limit = Some(TurnLimit {
+        turns: 1,
+        on_expire: Expiry::SuddenDeath,
+    });
+    let mut state = debug_state(prototypes, scenario);
+    exec(&mut state, command::EndTurn);
+    assert!(state::obj_with_passive_ability_at(
+        &state,
+        PosHex { q: 0, r: 3 },
+        PassiveAbility::Burn
+    )
+    .is_none());
+    exec(&mut state, command::EndTurn);
+    let is_burning =
+        |pos| state::obj_with_passive_ability_at(&state, pos, PassiveAbility::Burn).is_some();
+    assert!(is_burning(PosHex { q: 0, r: 3 }));
+    assert!(is_burning(PosHex { q: -3, r: 1 }));
+    assert!(!is_burning(PosHex { q: 0, r: 2 }));
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P0);
+}
+
+#[test]
+fn reinforcement_waves() {
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 2, r: 2 });
+    scenario.reinforcements = vec![
+        Wave {
+            owner: P1,
+            trigger: Trigger::Round(1),
+            objects: vec![("imp".into(), PosHex { q: 2, r: 2 })],
+        },
+        Wave {
+            owner: P1,
+            trigger: Trigger::FewerAgentsThan {
+                owner: P1,
+                count: 3,
+            },
+            objects: vec![("imp".into(), PosHex { q: 3, r: 0 })],
+        },
+    ];
+    let mut state = debug_state(prototypes, scenario);
+    let imps_count = |state: &State| state::players_agent_ids(state, P1).len();
+    exec(&mut state, command::EndTurn);
+    assert_eq!(imps_count(&state), 2);
+    assert!(state.is_wave_arrived(1));
+    exec(&mut state, command::EndTurn);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(imps_count(&state), 3);
+    let positions: Vec<_> = state::players_agent_ids(&state, P1)
+        .into_iter()
+        .map(|id| state.parts().pos.get(id).0)
+        .collect();
+    assert!(positions.iter().all(|&pos| pos != PosHex { q: 0, r: 0 }));
+    assert_eq!(
+        positions
+            .iter()
+            .filter(|&&pos| pos == PosHex { q: 2, r: 2 })
+            .count(),
+        1
+    );
+    exec(&mut state, command::EndTurn);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(imps_count(&state), 3);
+}
+
+#[test]
+fn large_reinforcements_fit_all_their_tiles() {
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        (
+            "troll",
+            vec![
+                component_agent_dull(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+                component::Footprint(vec![Dir::East]).into(),
+            ],
+        ),
+        ("boulder", vec![component_blocker(Weight::Heavy)]),
+    ]);
+    let anchor = PosHex { q: 0, r: 0 };
+    let boulder_pos = PosHex { q: 1, r: -1 };
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: -3, r: 0 })
+        .object(P1, "troll", PosHex { q: 3, r: 0 })
+        .object_without_owner("boulder", boulder_pos);
+    scenario.reinforcements = vec![Wave {
+        owner: P1,
+        trigger: Trigger::Round(1),
+        objects: vec![("troll".into(), anchor)],
+    }];
+    let mut state = debug_state(prototypes, scenario);
+    for _ in 0..3 {
+        exec(&mut state, command::EndTurn);
+    }
+    assert!(state.is_wave_arrived(0));
+    let trolls = state::players_agent_ids(&state, P1);
+    assert_eq!(trolls.len(), 2);
+    let tiles = state::object_tiles(&state, trolls[1]);
+    assert!(!tiles.contains(&boulder_pos));
+    assert!(map::distance_hex(tiles[0], anchor) <= Distance(1));
+}
+
+#[test]
+fn control_point_earns_renown_for_its_holder() {
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 2, r: 2 });
+    scenario.control_points = vec![
+        ControlPoint {
+            pos: PosHex { q: 0, r: 0 },
+            reward: ControlReward::Renown(2),
+        },
+        ControlPoint {
+            pos: PosHex { q: 2, r: 2 },
+            reward: ControlReward::Renown(5),
+        },
+    ];
+    let mut state = debug_state(prototypes, scenario);
+    assert_eq!(state.control_point_holder(0), None);
+    let events = exec(&mut state, command::EndTurn);
+    let captures: Vec<_> = events
+        .iter()
+        .filter_map(|event| match &event.active_event {
+            ActiveEvent::Capture(capture) => Some((capture.index, capture.player_id)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(captures, [(0, P0)]);
+    assert_eq!(state.control_renown(), 2);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.control_point_holder(1), Some(P1));
+    assert_eq!(state.control_renown(), 2);
+    let events = exec(&mut state, command::EndTurn);
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event.active_event, ActiveEvent::Capture(_))));
+    assert_eq!(state.control_renown(), 4);
+    assert_eq!(
+        state::control_points(&state),
+        [
+            (PosHex { q: 0, r: 0 }, Some(true)),
+            (PosHex { q: 2, r: 2 }, Some(false)),
+        ]
+    );
+}
+
+#[test]
+fn control_point_sends_reinforcements_to_its_new_holder() {
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 2, r: 2 });
+    scenario.control_points = vec![ControlPoint {
+        pos: PosHex { q: 2, r: 2 },
+        reward: ControlReward::Reinforcement("imp".into()),
+    }];
+    let mut state = debug_state(prototypes, scenario);
+    let imps_count = |state: &State| state::players_agent_ids(state, P1).len();
+    exec(&mut state, command::EndTurn);
+    assert_eq!(imps_count(&state), 1);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(imps_count(&state), 2);
+    exec(&mut state, command::EndTurn);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(imps_count(&state), 2);
+    assert_eq!(state.control_renown(), 0);
+}
+
+#[test]
+fn retreat_from_home_edge() {
+    let prototypes = || {
+        prototypes(&[
+            (
+                "swordsman",
+                vec![component_agent_dull(), component_strength(1)],
+            ),
+            ("imp", vec![component_agent_dull(), component_strength(1)]),
+        ])
+    };
+    let scenario = |pos| {
+        Scenario::default()
+            .object(P0, "swordsman", PosHex { q: -5, r: 2 })
+            .object(P0, "swordsman", pos)
+            .object(P1, "imp", PosHex { q: 4, r: 0 })
+    };
+    let mut state = debug_state(prototypes(), scenario(PosHex { q: 0, r: 0 }));
+    assert_eq!(
+        try_exec(&mut state, command::Retreat),
+        Err(check::Error::NotAtHomeEdge)
+    );
+    let mut state = debug_state(prototypes(), scenario(PosHex { q: -4, r: 0 }));
+    exec(&mut state, command::Retreat);
+    let result = state.battle_result().clone().unwrap();
+    assert!(result.is_retreat);
+    assert_eq!(result.winner_id, P1);
+    assert_eq!(
+        result.survivor_types,
+        vec!["swordsman".into(), "swordsman".into()]
+    );
+}
+
+#[test]
+fn allied_ai_player() {
+    let p2 = PlayerId(2);
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        (
+            "guard",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: 0, r: 0 })
+        .object(p2, "guard", PosHex { q: 2, r: 0 })
+        .object(P1, "imp", PosHex { q: 3, r: 0 });
+    scenario.players_count = 3;
+    scenario.allies = vec![p2];
+    let mut state = debug_state(prototypes, scenario);
+    assert!(state::are_allies(&state, P0, p2));
+    assert!(!state::are_allies(&state, P1, p2));
+    assert_eq!(state::enemy_agent_ids(&state, P0), vec![Id(2)]);
+    assert!(!state::check_enemies_around(
+        &state,
+        PosHex { q: 1, r: 0 },
+        P0
+    ));
+    exec(&mut state, command::EndTurn);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.player_id(), p2);
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(1),
+            target_id: Id(2),
+        },
+    );
+    // The alliance wins even though the human player hasn't fought at all.
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P0);
+}
+
+#[test]
+fn scripts_on_regions() {
+    let prototypes = prototypes(&[
+        (
+            "peasant",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "peasant", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 4, r: 0 });
+    scenario.regions = vec![
+        Region {
+            name: "ambush".into(),
+            tiles: vec![PosHex { q: 0, r: 0 }, PosHex { q: 1, r: 0 }],
+            is_marked: false,
+        },
+        Region {
+            name: "camp".into(),
+            tiles: vec![PosHex { q: 0, r: 0 }],
+            is_marked: true,
+        },
+    ];
+    scenario.scripts = vec![
+        Script {
+            condition: Condition::Enter {
+                region: "ambush".into(),
+                player_id: P0,
+            },
+            actions: vec![
+                ScriptAction::Message("ambush!".into()),
+                ScriptAction::Spawn {
+                    owner: Some(P1),
+                    objects: vec![("imp".into(), PosHex { q: 0, r: 1 })],
+                },
+            ],
+        },
+        Script {
+            condition: Condition::Occupy {
+                region: "camp".into(),
+                owner: P0,
+                rounds: 1,
+            },
+            actions: vec![ScriptAction::Win(P0)],
+        },
+    ];
+    // The peasant starts in the ambush region.
+    let (mut state, events) = debug_state_with_events(prototypes, scenario);
+    assert!(events.iter().any(|event| matches!(
+        event.active_event,
+        ActiveEvent::ScriptTriggered(event::ScriptTriggered { index: 0 })
+    )));
+    assert!(state.is_script_fired(0));
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state::players_agent_ids(&state, P1).len(), 2);
+    assert!(state.battle_result().is_none());
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.zone_occupation(1), 1);
+    let result = state.battle_result().clone().unwrap();
+    assert_eq!(result.winner_id, P0);
+}
+
+#[test]
+fn scripts_on_round_and_destruction() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+        (
+            "imp_summoner",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+    ]);
+    let bridge = PosHex { q: 2, r: 0 };
+    let mut scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 })
+        .object(P1, "imp_summoner", PosHex { q: 4, r: 0 });
+    scenario.scripts = vec![
+        Script {
+            condition: Condition::RoundBegins(1),
+            actions: vec![ScriptAction::SetTiles {
+                tiles: vec![bridge],
+                tile: TileType::Rocks,
+            }],
+        },
+        Script {
+            condition: Condition::Destroyed("imp".into()),
+            actions: vec![ScriptAction::Spawn {
+                owner: Some(P1),
+                objects: vec![("imp".into(), PosHex { q: 4, r: -1 })],
+            }],
+        },
+    ];
+    let mut state = debug_state(prototypes, scenario);
+    exec(&mut state, command::EndTurn);
+    assert!(!state.is_script_fired(0));
+    let events = exec(&mut state, command::EndTurn);
+    let expected = event::SetTiles {
+        tiles: vec![bridge],
+        tile: TileType::Rocks,
+    };
+    assert!(events
+        .iter()
+        .any(|event| event.active_event == expected.clone().into()));
+    assert_eq!(state.map().tile(bridge), TileType::Rocks);
+    assert!(!state.is_script_fired(1));
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
+    );
+    assert!(state.is_script_fired(1));
+    assert_eq!(state::players_agent_ids(&state, P1).len(), 2);
+    assert!(state.battle_result().is_none());
+}
+
+#[test]
+fn dialogue_scripts() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        ("boss", vec![component_agent_dull(), component_strength(2)]),
+    ]);
+    let remark = |text: &str| Remark {
+        speaker: "boss".into(),
+        name: None,
+        text: text.into(),
+    };
+    let mut scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "boss", PosHex { q: 0, r: 1 });
+    scenario.scripts = vec![
+        Script {
+            condition: Condition::RoundBegins(0),
+            actions: vec![ScriptAction::Dialogue(vec![remark("who's there?")])],
+        },
+        Script {
+            condition: Condition::StrengthDropsTo {
+                typename: "boss".into(),
+                percent: 50,
+            },
+            actions: vec![ScriptAction::Dialogue(vec![remark("enough!")])],
+        },
+    ];
+    let (mut state, events) = debug_state_with_events(prototypes, scenario);
+    assert!(events.iter().any(|event| matches!(
+        event.active_event,
+        ActiveEvent::ScriptTriggered(event::ScriptTriggered { index: 0 })
+    )));
+    assert!(!state.is_script_fired(1));
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
+    );
+    assert!(state.is_script_fired(1));
+}
+
+#[test]
+fn boss_phases() {
+    let phase = |strength, abilities, bonus_attacks| component::BossPhase {
+        strength: Strength(strength),
+        abilities,
+        passive_abilities: Vec::new(),
+        bonus_attacks: Attacks(bonus_attacks),
+        bonus_moves: Moves(0),
+        bonus_attack_strength: Strength(0),
+    };
+    let boss = component::Boss {
+        phases: vec![
+            phase(4, vec![Ability::Rage], 0),
+            phase(3, vec![Ability::LongJump], 1),
+            phase(1, Vec::new(), 1),
+        ],
+        phases_passed: 0,
+    };
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component::Agent {
+                    attack_strength: Strength(2),
+                    ..agent_always_hit()
+                }
+                .into(),
+                component_strength(1),
+            ],
+        ),
+        (
+            "boss",
+            vec![component_agent_dull(), component_strength(5), boss.into()],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "boss", PosHex { q: 0, r: 1 });
+    let mut state = debug_state(prototypes, scenario);
+    let boss_id = Id(1);
+    let events = exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: boss_id,
+        },
+    );
+    let phases: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event.active_event {
+            ActiveEvent::BeginBossPhase(ref ev) => Some((ev.id, ev.phase)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(phases, vec![(boss_id, 0), (boss_id, 1)]);
+    let parts = state.parts();
+    assert_eq!(parts.boss.get(boss_id).phases_passed, 2);
+    let abilities: Vec<_> = parts
+        .abilities
+        .get(boss_id)
+        .0
+        .iter()
+        .map(|r| r.ability)
+        .collect();
+    assert_eq!(abilities, vec![Ability::Rage, Ability::LongJump]);
+    let agent = parts.agent.get(boss_id);
+    assert_eq!(agent.attacks, Attacks(1));
+    assert_eq!(agent.base_attacks, Attacks(1));
+}
+
+#[test]
+fn backstab() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(4)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P0, "swordsman", PosHex { q: 2, r: 0 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let imp_id = Id(2);
+    let facing = |state: &State, id| state.parts().facing.get(id).0;
+    assert_eq!(facing(&state, Id(0)), Dir::SouthEast);
+    assert_eq!(facing(&state, imp_id), Dir::NorthWest);
+    assert!(!state::is_backstab(&state, Id(0), imp_id));
+    assert!(state::is_backstab(&state, Id(1), imp_id));
+    let strength = |state: &State| state.parts().strength.get(imp_id).strength;
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: imp_id,
+        },
+    );
+    assert_eq!(strength(&state), Strength(3));
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(1),
+            target_id: imp_id,
+        },
+    );
+    assert_eq!(strength(&state), Strength(1));
+    assert_eq!(facing(&state, Id(1)), Dir::NorthWest);
+}
+
+#[test]
+fn ally_death_routs_nearby_agent() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+        (
+            "imp",
+            vec![
+                component_agent_dull(),
+                component_strength(1),
+                component::Morale {
+                    morale: 1,
+                    base_morale: 1,
+                }
+                .into(),
+            ],
+        ),
+    ]);
+    let attacker_pos = PosHex { q: 0, r: 0 };
+    let target_pos = PosHex { q: 0, r: 1 };
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", attacker_pos)
+        .object(P1, "imp", target_pos)
+        .object(P1, "imp", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
+        &[
+            Event {
+                active_event: event::Attack {
+                    attacker_id: Id(0),
+                    target_id: Id(1),
+                    mode: AttackMode::Active,
+                    weapon_type: WeaponType::Slash,
+                }
+                .into(),
+                actor_ids: vec![Id(0)],
+                instant_effects: vec![(
+                    Id(1),
+                    vec![effect::Kill {
+                        attacker_pos: Some(attacker_pos),
+                        is_drowned: false,
+                    }
+                    .into()],
+                )],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+            Event {
+                active_event: event::MoraleLoss {
+                    dead_id: Id(1),
+                    pos: target_pos,
+                }
+                .into(),
+                actor_ids: Vec::new(),
+                instant_effects: vec![(Id(2), vec![effect::Demoralize { morale: 1 }.into()])],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+            event_leave_corpse(Id(1), target_pos, Id(3), P0),
+        ],
+    );
+    assert!(state::is_routed(&state, Id(2)));
+}
+
+#[test]
+fn destructible_boulder() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![
+                component::Agent {
+                    attack_strength: Strength(2),
+                    ..agent_always_hit()
+                }
+                .into(),
+                component_strength(1),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+        (
+            "boulder",
+            vec![component_blocker(Weight::Heavy), component_strength(2)],
+        ),
+        ("spike_wall", vec![component_blocker(Weight::Heavy)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 3, r: 0 })
+        .object_without_owner("boulder", PosHex { q: 0, r: 1 })
+        .object_without_owner("spike_wall", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let boulder_id = Id(2);
+    let wall_id = Id(3);
+    assert!(state::is_destructible(&state, boulder_id));
+    assert!(!state::is_destructible(&state, wall_id));
+    let command_attack_wall = command::Attack {
+        attacker_id: Id(0),
+        target_id: wall_id,
+    };
+    assert_eq!(
+        try_exec(&mut state, command_attack_wall),
+        Err(check::Error::BadTargetId)
+    );
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: boulder_id,
+        },
+    );
+    assert!(!state.parts().is_exist(boulder_id));
+    assert!(!state::is_tile_blocked(&state, PosHex { q: 0, r: 1 }));
+}
+
+#[test]
+fn push_boulder() {
+    let prototypes = prototypes(&[
+        (
+            "hammerman",
+            vec![
+                component_agent_always_hit(),
+                component_strength(1),
+                component_abilities(&[Ability::Knockback, Ability::Shove]),
+            ],
+        ),
+        (
+            "imp",
+            vec![
+                component_agent_dull(),
+                component_strength(2),
+                component_blocker(Weight::Normal),
+            ],
+        ),
+        ("boulder", vec![component_blocker(Weight::Heavy)]),
+    ]);
+    let boulder_pos = PosHex { q: 0, r: 1 };
+    let scenario = Scenario::default()
+        .object(P0, "hammerman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 })
+        .object_without_owner("boulder", boulder_pos);
+    let mut state = debug_state(prototypes, scenario);
+    let boulder_id = Id(2);
+    let knockback = command::UseAbility {
+        id: Id(0),
+        pos: boulder_pos,
+        ability: Ability::Knockback,
+    };
+    assert_eq!(
+        try_exec(&mut state, knockback),
+        Err(check::Error::NotEnoughStrength)
+    );
+    let shove_imp = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 1, r: 0 },
+        ability: Ability::Shove,
+    };
+    assert_eq!(
+        try_exec(&mut state, shove_imp),
+        Err(check::Error::BadTargetType)
+    );
+    let new_boulder_pos = PosHex { q: 0, r: 2 };
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: boulder_pos,
+            ability: Ability::Shove,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(0),
+                pos: boulder_pos,
+                ability: Ability::Shove,
+            }
+            .into(),
+            actor_ids: vec![boulder_id, Id(0)],
+            instant_effects: vec![(
+                boulder_id,
+                vec![effect::Knockback {
+                    from: boulder_pos,
+                    to: new_boulder_pos,
+                    strength: PushStrength(Weight::Heavy),
+                }
+                .into()],
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        }],
+    );
+    assert_eq!(state.parts().pos.get(boulder_id).0, new_boulder_pos);
+}
+
+#[test]
+fn pushed_boulder_hits_agent() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::BombPush]),
+            ],
+        ),
+        (
+            "imp",
+            vec![
+                component_agent_dull(),
+                component_strength(2),
+                component_blocker(Weight::Normal),
+            ],
+        ),
+        ("boulder", vec![component_blocker(Weight::Normal)]),
+        ("bomb_push", Vec::new()),
+    ]);
+    let boulder_pos = PosHex { q: 0, r: 3 };
+    let imp_pos = PosHex { q: 0, r: 4 };
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", imp_pos)
+        .object_without_owner("boulder", boulder_pos);
+    let mut state = debug_state(prototypes, scenario);
+    let events = exec(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 2 },
+            ability: Ability::BombPush,
+        },
+    );
+    let explosion = events.last().unwrap();
+    assert_eq!(
+        explosion.instant_effects[..2],
+        [
+            (
+                Id(2),
+                vec![effect::Knockback {
+                    from: boulder_pos,
+                    to: boulder_pos,
+                    strength: PushStrength(Weight::Normal),
+                }
+                .into()],
+            ),
+            (
+                Id(1),
+                vec![effect::Wound {
+                    damage: Strength(1),
+                    armor_break: Strength(0),
+                    attacker_pos: None,
+                }
+                .into()],
+            ),
+        ]
+    );
+    let parts = state.parts();
+    assert_eq!(parts.pos.get(Id(2)).0, boulder_pos);
+    assert_eq!(parts.pos.get(Id(1)).0, imp_pos);
+    assert_eq!(parts.strength.get(Id(1)).strength, Strength(1));
+}
+
+#[test]
+fn stun_and_push_to_spikes() {
+    // TODO: Stun an agent to spikes
+    // They should die on these spikes before LastingEffect::Stun is over
+}
+
+#[test]
+fn defused_bomb_never_explodes() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Bomb]),
+            ],
+        ),
+        (
+            "defuser",
+            vec![
+                component_agent_one_attack(),
+                component_strength(1),
+                component_abilities(&[Ability::Defuse]),
+            ],
+        ),
+        ("bomb_damage", vec![component_blocker(Weight::Normal)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "defuser", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
+    let command = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 0, r: 2 },
+        ability: Ability::Bomb,
+    };
+    exec(&mut state, command);
+    assert_eq!(state::bomb_fuses(&state), [(PosHex { q: 0, r: 2 }, 1)]);
+    exec(&mut state, command::EndTurn);
+    let command = command::UseAbility {
+        id: Id(1),
+        pos: PosHex { q: 1, r: 2 },
+        ability: Ability::Defuse,
+    };
+    assert_eq!(
+        try_exec(&mut state, command).map(|_| ()),
+        Err(check::Error::NoTarget)
+    );
+    let command = command::UseAbility {
+        id: Id(1),
+        pos: PosHex { q: 0, r: 2 },
+        ability: Ability::Defuse,
+    };
+    exec(&mut state, command);
+    assert!(state::bomb_fuses(&state).is_empty());
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(1));
+}
+
+#[test]
+fn throw_bomb_no_harm() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Bomb]),
+            ],
+        ),
+        ("dull", [component_agent_dull()].to_vec()),
+        ("bomb_damage", Vec::new()),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "dull", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: -2 },
+            ability: Ability::Bomb,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(0),
+                pos: PosHex { q: 0, r: -2 },
+                ability: Ability::Bomb,
+            }
+            .into(),
+            actor_ids: vec![Id(0)],
+            instant_effects: vec![(
+                Id(2),
+                vec![
+                    effect::Create {
+                        pos: PosHex { q: 0, r: 0 },
+                        prototype: "bomb_damage".into(),
+                        components: vec![
+                            component::Pos(PosHex { q: 0, r: 0 }).into(),
+                            component_meta("bomb_damage"),
+                        ],
+                        is_teleported: false,
+                    }
+                    .into(),
+                    effect::Throw {
+                        from: PosHex { q: 0, r: 0 },
+                        to: PosHex { q: 0, r: -2 },
+                        is_rolled: false,
+                    }
+                    .into(),
+                ],
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: vec![(
+                Id(2),
+                vec![PlannedAbility {
+                    rounds: 1.into(),
+                    phase: Phase(0),
+                    ability: Ability::ExplodeDamage,
+                }],
+            )],
+        }],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[event_end_turn(P0, &[Id(0)]), event_begin_turn(P1, &[Id(1)])],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P1, &[Id(1)]),
+            event_begin_turn(P0, &[Id(0)]),
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(2),
+                    pos: PosHex { q: 0, r: -2 },
+                    ability: Ability::ExplodeDamage,
+                }
+                .into(),
+                actor_ids: vec![Id(2)],
+                instant_effects: vec![(Id(2), vec![Effect::Vanish])],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+        ],
+    );
+}
+
+#[test]
+fn throw_bomb_damage() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Bomb]),
+            ],
+        ),
+        (
+            "weak",
+            [component_agent_dull(), component_strength(2)].to_vec(),
+        ),
+        ("bomb_damage", Vec::new()),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "weak", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 2 },
+            ability: Ability::Bomb,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(0),
+                pos: PosHex { q: 0, r: 2 },
+                ability: Ability::Bomb,
+            }
+            .into(),
+            actor_ids: vec![Id(0)],
+            instant_effects: vec![(
+                Id(2),
+                vec![
+                    effect::Create {
+                        pos: PosHex { q: 0, r: 0 },
+                        prototype: "bomb_damage".into(),
+                        components: vec![
+                            component::Pos(PosHex { q: 0, r: 0 }).into(),
+                            component_meta("bomb_damage"),
+                        ],
+                        is_teleported: false,
+                    }
+                    .into(),
+                    effect::Throw {
+                        from: PosHex { q: 0, r: 0 },
+                        to: PosHex { q: 0, r: 2 },
+                        is_rolled: false,
+                    }
+                    .into(),
+                ],
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: vec![(
+                Id(2),
+                vec![PlannedAbility {
+                    rounds: 1.into(),
+                    phase: Phase(0),
+                    ability: Ability::ExplodeDamage,
+                }],
+            )],
+        }],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[event_end_turn(P0, &[Id(0)]), event_begin_turn(P1, &[Id(1)])],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P1, &[Id(1)]),
+            event_begin_turn(P0, &[Id(0)]),
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(2),
+                    pos: PosHex { q: 0, r: 2 },
+                    ability: Ability::ExplodeDamage,
+                }
+                .into(),
+                actor_ids: vec![Id(2)],
+                instant_effects: vec![
+                    (
+                        Id(1),
+                        vec![effect::Wound {
+                            damage: Strength(1),
+                            armor_break: Strength(0),
+                            attacker_pos: None,
+                        }
+                        .into()],
+                    ),
+                    (Id(2), vec![Effect::Vanish]),
+                ],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+        ],
+    );
+}
+
+#[test]
+fn throw_bomb_poison() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::BombPoison]),
+            ],
+        ),
+        ("weak", vec![component_agent_dull(), component_strength(2)]),
+        ("bomb_poison", Vec::new()),
+        (
+            "poison_cloud",
+            vec![component_passive_abilities(&[PassiveAbility::Poison])],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "weak", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 2 },
+            ability: Ability::BombPoison,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(0),
+                pos: PosHex { q: 0, r: 2 },
+                ability: Ability::BombPoison,
+            }
+            .into(),
+            actor_ids: vec![Id(0)],
+            instant_effects: vec![(
+                Id(2),
+                vec![
+                    effect::Create {
+                        pos: PosHex { q: 0, r: 0 },
+                        prototype: "bomb_poison".into(),
+                        components: vec![
+                            component::Pos(PosHex { q: 0, r: 0 }).into(),
+                            component_meta("bomb_poison"),
+                        ],
+                        is_teleported: false,
+                    }
+                    .into(),
+                    effect::Throw {
+                        from: PosHex { q: 0, r: 0 },
+                        to: PosHex { q: 0, r: 2 },
+                        is_rolled: false,
+                    }
+                    .into(),
+                ],
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: vec![(
+                Id(2),
+                vec![PlannedAbility {
+                    rounds: 1.into(),
+                    phase: Phase(0),
+                    ability: Ability::ExplodePoison,
+                }],
+            )],
+        }],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[event_end_turn(P0, &[Id(0)]), event_begin_turn(P1, &[Id(1)])],
+    );
+    let create_poison_cloud = |pos| -> Effect {
+        effect::Create {
+            pos,
+            prototype: "poison_cloud".into(),
+            components: vec![
+                component_passive_abilities(&[PassiveAbility::Poison]),
+                component::Pos(pos).into(),
+                component_meta("poison_cloud"),
+            ],
+            is_teleported: false,
+        }
+        .into()
+    };
+    let planned_ability_vanish = || -> PlannedAbility {
+        PlannedAbility {
+            rounds: 2.into(),
+            phase: Phase(0),
+            ability: Ability::Vanish,
+        }
+    };
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P1, &[Id(1)]),
+            event_begin_turn(P0, &[Id(0)]),
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(2),
+                    pos: PosHex { q: 0, r: 2 },
+                    ability: Ability::ExplodePoison,
+                }
+                .into(),
+                actor_ids: vec![Id(2)],
+                instant_effects: vec![
+                    (Id(2), vec![Effect::Vanish]),
+                    (Id(3), vec![create_poison_cloud(PosHex { q: 0, r: 2 })]),
+                    (Id(4), vec![create_poison_cloud(PosHex { q: 1, r: 2 })]),
+                    (Id(5), vec![create_poison_cloud(PosHex { q: 1, r: 1 })]),
+                    (Id(6), vec![create_poison_cloud(PosHex { q: 0, r: 1 })]),
+                    (Id(7), vec![create_poison_cloud(PosHex { q: -1, r: 2 })]),
+                    (Id(8), vec![create_poison_cloud(PosHex { q: -1, r: 3 })]),
+                    (Id(9), vec![create_poison_cloud(PosHex { q: 0, r: 3 })]),
+                ],
+                timed_effects: vec![(
+                    Id(1),
+                    vec![effect::Timed {
+                        duration: effect::Duration::Rounds(2.into()),
+                        phase: Phase(1),
+                        effect: effect::Lasting::Poison,
+                    }],
+                )],
+                scheduled_abilities: vec![
+                    (Id(3), vec![planned_ability_vanish()]),
+                    (Id(4), vec![planned_ability_vanish()]),
+                    (Id(5), vec![planned_ability_vanish()]),
+                    (Id(6), vec![planned_ability_vanish()]),
+                    (Id(7), vec![planned_ability_vanish()]),
+                    (Id(8), vec![planned_ability_vanish()]),
+                    (Id(9), vec![planned_ability_vanish()]),
+                ],
+            },
+        ],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P0, &[Id(0)]),
+            event_begin_turn(P1, &[Id(1)]),
+            Event {
+                active_event: event::UsePassiveAbility {
+                    id: Id(9),
+                    pos: PosHex { q: 0, r: 3 },
+                    ability: PassiveAbility::Poison,
+                }
+                .into(),
+                actor_ids: Vec::new(),
+                instant_effects: Vec::new(),
+                timed_effects: vec![(
+                    Id(1),
+                    vec![effect::Timed {
+                        duration: effect::Duration::Rounds(2.into()),
+                        phase: Phase(1),
+                        effect: effect::Lasting::Poison,
+                    }],
+                )],
+                scheduled_abilities: Vec::new(),
+            },
+            Event {
+                active_event: event::EffectTick {
+                    id: Id(1),
+                    effect: effect::Lasting::Poison,
+                }
+                .into(),
+                actor_ids: vec![Id(1)],
+                instant_effects: vec![(
+                    Id(1),
+                    vec![effect::Wound {
+                        damage: Strength(1),
+                        armor_break: Strength(0),
+                        attacker_pos: None,
+                    }
+                    .into()],
+                )],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+            Event {
+                active_event: event::EffectEnd {
+                    id: Id(1),
+                    effect: effect::Lasting::Poison,
+                }
+                .into(),
+                actor_ids: vec![Id(1)],
+                instant_effects: Vec::new(),
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+        ],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[event_end_turn(P1, &[Id(1)]), event_begin_turn(P0, &[Id(0)])],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[event_end_turn(P0, &[Id(0)]), event_begin_turn(P1, &[Id(1)])],
+    );
+    let event_vanish = |id, pos| -> Event {
+        Event {
+            active_event: event::UseAbility {
+                id,
+                pos,
+                ability: Ability::Vanish,
+            }
+            .into(),
+            actor_ids: vec![id],
+            instant_effects: vec![(id, vec![Effect::Vanish])],
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        }
+    };
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P1, &[Id(1)]),
+            event_begin_turn(P0, &[Id(0)]),
+            event_vanish(Id(3), PosHex { q: 0, r: 2 }),
+            event_vanish(Id(4), PosHex { q: 1, r: 2 }),
+            event_vanish(Id(5), PosHex { q: 1, r: 1 }),
+            event_vanish(Id(6), PosHex { q: 0, r: 1 }),
+            event_vanish(Id(7), PosHex { q: -1, r: 2 }),
+            event_vanish(Id(8), PosHex { q: -1, r: 3 }),
+            event_vanish(Id(9), PosHex { q: 0, r: 3 }),
+        ],
+    );
+}
+
+#[test]
+fn throw_two_fire_bombs() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::BombFire]),
+            ],
+        ),
+        ("dull", [component_agent_dull()].to_vec()),
+        ("bomb_fire", Vec::new()),
+        (
+            "fire",
+            vec![component_passive_abilities(&[PassiveAbility::Burn])],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P0, "thrower", PosHex { q: -1, r: 0 })
+        .object(P1, "dull", PosHex { q: 0, r: -3 });
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 2 },
+            ability: Ability::BombFire,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(0),
+                pos: PosHex { q: 0, r: 2 },
+                ability: Ability::BombFire,
+            }
+            .into(),
+            actor_ids: vec![Id(0)],
+            instant_effects: vec![(
+                Id(3),
+                vec![
+                    effect::Create {
+                        pos: PosHex { q: 0, r: 0 },
+                        prototype: "bomb_fire".into(),
+                        components: vec![
+                            component::Pos(PosHex { q: 0, r: 0 }).into(),
+                            component_meta("bomb_fire"),
+                        ],
+                        is_teleported: false,
+                    }
+                    .into(),
+                    effect::Throw {
+                        from: PosHex { q: 0, r: 0 },
+                        to: PosHex { q: 0, r: 2 },
+                        is_rolled: false,
+                    }
+                    .into(),
+                ],
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: vec![(
+                Id(3),
+                vec![PlannedAbility {
+                    rounds: 1.into(),
+                    phase: Phase(0),
+                    ability: Ability::ExplodeFire,
+                }],
+            )],
+        }],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P0, &[Id(0), Id(1)]),
+            event_begin_turn(P1, &[Id(2)]),
+        ],
+    );
+    let create_fire = |pos| -> Effect {
+        effect::Create {
+            pos,
+            prototype: "fire".into(),
+            components: vec![
+                component_passive_abilities(&[PassiveAbility::Burn]),
+                component::Pos(pos).into(),
+                component_meta("fire"),
+            ],
+            is_teleported: false,
+        }
+        .into()
+    };
+    let planned_ability_vanish = || -> PlannedAbility {
+        PlannedAbility {
+            rounds: 2.into(),
+            phase: Phase(0),
+            ability: Ability::Vanish,
+        }
+    };
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P1, &[Id(2)]),
+            event_begin_turn(P0, &[Id(0), Id(1)]),
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(3),
+                    pos: PosHex { q: 0, r: 2 },
+                    ability: Ability::ExplodeFire,
+                }
+                .into(),
+                actor_ids: vec![Id(3)],
+                instant_effects: vec![
+                    (Id(3), vec![Effect::Vanish]),
+                    (Id(4), vec![create_fire(PosHex { q: 0, r: 2 })]),
+                    (Id(5), vec![create_fire(PosHex { q: 1, r: 2 })]),
+                    (Id(6), vec![create_fire(PosHex { q: 1, r: 1 })]),
+                    (Id(7), vec![create_fire(PosHex { q: 0, r: 1 })]),
+                    (Id(8), vec![create_fire(PosHex { q: -1, r: 2 })]),
+                    (Id(9), vec![create_fire(PosHex { q: -1, r: 3 })]),
+                    (Id(10), vec![create_fire(PosHex { q: 0, r: 3 })]),
+                ],
+                timed_effects: Vec::new(),
+                scheduled_abilities: vec![
+                    (Id(4), vec![planned_ability_vanish()]),
+                    (Id(5), vec![planned_ability_vanish()]),
+                    (Id(6), vec![planned_ability_vanish()]),
+                    (Id(7), vec![planned_ability_vanish()]),
+                    (Id(8), vec![planned_ability_vanish()]),
+                    (Id(9), vec![planned_ability_vanish()]),
+                    (Id(10), vec![planned_ability_vanish()]),
+                ],
+            },
+        ],
+    );
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(1),
+            pos: PosHex { q: 1, r: 1 },
+            ability: Ability::BombFire,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(1),
+                pos: PosHex { q: 1, r: 1 },
+                ability: Ability::BombFire,
+            }
+            .into(),
+            actor_ids: vec![Id(1)],
+            instant_effects: vec![(
+                Id(11),
+                vec![
+                    effect::Create {
+                        pos: PosHex { q: -1, r: 0 },
+                        prototype: "bomb_fire".into(),
+                        components: vec![
+                            component::Pos(PosHex { q: -1, r: 0 }).into(),
+                            component_meta("bomb_fire"),
+                        ],
+                        is_teleported: false,
+                    }
+                    .into(),
+                    effect::Throw {
+                        from: PosHex { q: -1, r: 0 },
+                        to: PosHex { q: 1, r: 1 },
+                        is_rolled: false,
+                    }
+                    .into(),
+                ],
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: vec![(
+                Id(11),
+                vec![PlannedAbility {
+                    rounds: 1.into(),
+                    phase: Phase(0),
+                    ability: Ability::ExplodeFire,
+                }],
+            )],
+        }],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P0, &[Id(0), Id(1)]),
+            event_begin_turn(P1, &[Id(2)]),
+        ],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P1, &[Id(2)]),
+            event_begin_turn(P0, &[Id(0), Id(1)]),
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(11),
+                    pos: PosHex { q: 1, r: 1 },
+                    ability: Ability::ExplodeFire,
+                }
+                .into(),
+                actor_ids: vec![Id(11)],
+                instant_effects: vec![
+                    (Id(11), vec![Effect::Vanish]),
+                    (Id(12), vec![create_fire(PosHex { q: 2, r: 1 })]),
+                    (Id(13), vec![create_fire(PosHex { q: 2, r: 0 })]),
+                    (Id(14), vec![create_fire(PosHex { q: 1, r: 0 })]),
+                ],
+                timed_effects: Vec::new(),
+                scheduled_abilities: vec![
+                    (Id(6), vec![planned_ability_vanish()]),
+                    (Id(12), vec![planned_ability_vanish()]),
+                    (Id(13), vec![planned_ability_vanish()]),
+                    (Id(14), vec![planned_ability_vanish()]),
+                    (Id(7), vec![planned_ability_vanish()]),
+                    (Id(4), vec![planned_ability_vanish()]),
+                    (Id(5), vec![planned_ability_vanish()]),
+                ],
+            },
+        ],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P0, &[Id(0), Id(1)]),
+            event_begin_turn(P1, &[Id(2)]),
+        ],
+    );
+    let event_vanish = |id, pos| -> Event {
+        Event {
+            active_event: event::UseAbility {
+                id,
+                pos,
+                ability: Ability::Vanish,
+            }
+            .into(),
+            actor_ids: vec![id],
+            instant_effects: vec![(id, vec![Effect::Vanish])],
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        }
+    };
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P1, &[Id(2)]),
+            event_begin_turn(P0, &[Id(0), Id(1)]),
+            event_vanish(Id(8), PosHex { q: -1, r: 2 }),
+            event_vanish(Id(9), PosHex { q: -1, r: 3 }),
+            event_vanish(Id(10), PosHex { q: 0, r: 3 }),
+        ],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P0, &[Id(0), Id(1)]),
+            event_begin_turn(P1, &[Id(2)]),
+        ],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P1, &[Id(2)]),
+            event_begin_turn(P0, &[Id(0), Id(1)]),
+            event_vanish(Id(4), PosHex { q: 0, r: 2 }),
+            event_vanish(Id(5), PosHex { q: 1, r: 2 }),
+            event_vanish(Id(6), PosHex { q: 1, r: 1 }),
+            event_vanish(Id(7), PosHex { q: 0, r: 1 }),
+            event_vanish(Id(12), PosHex { q: 2, r: 1 }),
+            event_vanish(Id(13), PosHex { q: 2, r: 0 }),
+            event_vanish(Id(14), PosHex { q: 1, r: 0 }),
+        ],
+    );
+}
+
+#[test]
+fn stun() {
+    let prototypes = prototypes(&[
+        (
+            "attacker",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Club]),
+            ],
+        ),
+        (
+            "target",
+            vec![
+                component_agent_dull(),
+                component_blocker(Weight::Normal),
+                component_strength(1),
+            ],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "attacker", PosHex { q: 0, r: 0 })
+        .object(P1, "target", PosHex { q: 0, r: 1 });
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 1 },
+            ability: Ability::Club,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(0),
+                pos: PosHex { q: 0, r: 1 },
+                ability: Ability::Club,
+            }
+            .into(),
+            actor_ids: vec![Id(1), Id(0)],
+            instant_effects: vec![(Id(1), vec![Effect::Stun])],
+            timed_effects: vec![(
+                Id(1),
+                vec![effect::Timed {
+                    duration: effect::Duration::Rounds(1.into()),
+                    phase: Phase(1),
+                    effect: effect::Lasting::Stun,
+                }],
+            )],
+            scheduled_abilities: Vec::new(),
+        }],
+    );
+    exec_and_check(
+        &mut state,
+        command::EndTurn,
+        &[
+            event_end_turn(P0, &[Id(0)]),
+            event_begin_turn(P1, &[Id(1)]),
+            Event {
+                active_event: event::EffectTick {
+                    id: Id(1),
+                    effect: effect::Lasting::Stun,
+                }
+                .into(),
+                actor_ids: vec![Id(1)],
+                instant_effects: vec![(Id(1), vec![Effect::Stun])],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+            Event {
+                active_event: event::EffectEnd {
+                    id: Id(1),
+                    effect: effect::Lasting::Stun,
+                }
+                .into(),
+                actor_ids: vec![Id(1)],
+                instant_effects: Vec::new(),
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+        ],
+    );
+}
+
+#[test]
+fn club_needs_an_agent() {
+    let prototypes = prototypes(&[
+        (
+            "attacker",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Club]),
+            ],
+        ),
+        (
+            "target",
+            vec![
+                component_agent_dull(),
+                component_blocker(Weight::Normal),
+                component_strength(1),
+            ],
+        ),
+        ("boulder", vec![component_blocker(Weight::Heavy)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "attacker", PosHex { q: 0, r: 0 })
+        .object_without_owner("boulder", PosHex { q: 0, r: 1 })
+        .object(P1, "target", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let command_club = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 0, r: 1 },
+        ability: Ability::Club,
+    };
+    assert_eq!(
+        try_exec(&mut state, command_club),
+        Err(check::Error::NoTarget)
+    );
+}
+
+#[test]
+fn throw_bomb_push_normal() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::BombPush]),
+            ],
+        ),
+        (
+            "weak",
+            [
+                component_agent_dull(),
+                component_strength(1),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+        ("bomb_push", Vec::new()),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "weak", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 2 },
+            ability: Ability::BombPush,
+        },
+        &[
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(0),
+                    pos: PosHex { q: 0, r: 2 },
+                    ability: Ability::BombPush,
+                }
+                .into(),
+                actor_ids: vec![Id(0)],
+                instant_effects: vec![(
+                    Id(2),
+                    vec![
+                        effect::Create {
+                            pos: PosHex { q: 0, r: 0 },
+                            prototype: "bomb_push".into(),
+                            components: vec![
+                                component::Pos(PosHex { q: 0, r: 0 }).into(),
+                                component_meta("bomb_push"),
+                            ],
+                            is_teleported: false,
+                        }
+                        .into(),
+                        effect::Throw {
+                            from: PosHex { q: 0, r: 0 },
+                            to: PosHex { q: 0, r: 2 },
+                            is_rolled: false,
+                        }
+                        .into(),
+                    ],
+                )],
+                timed_effects: Vec::new(),
+                scheduled_abilities: vec![(
+                    Id(2),
+                    vec![PlannedAbility {
+                        rounds: 0.into(),
+                        phase: Phase(0),
+                        ability: Ability::ExplodePush,
+                    }],
+                )],
+            },
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(2),
+                    pos: PosHex { q: 0, r: 2 },
+                    ability: Ability::ExplodePush,
+                }
+                .into(),
+                actor_ids: vec![Id(2)],
+                instant_effects: vec![
+                    (
+                        Id(1),
+                        vec![effect::Knockback {
+                            from: PosHex { q: 0, r: 3 },
+                            to: PosHex { q: 0, r: 4 },
+                            strength: PushStrength(Weight::Normal),
+                        }
+                        .into()],
+                    ),
+                    (Id(2), vec![Effect::Vanish]),
+                ],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+        ],
+    );
+    assert_eq!(state.parts().pos.get(Id(1)).0, PosHex { q: 0, r: 4 });
+}
+
+#[test]
+fn throw_bomb_push_heavy() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::BombPush]),
+            ],
+        ),
+        (
+            "heavy",
+            [
+                component_agent_dull(),
+                component_strength(1),
+                component_blocker(Weight::Heavy),
+            ]
+            .to_vec(),
+        ),
+        ("bomb_push", Vec::new()),
+    ]);
+    let initial_heavy_position = PosHex { q: 1, r: 2 };
+    let scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "heavy", initial_heavy_position);
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 2 },
+            ability: Ability::BombPush,
+        },
+        &[
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(0),
+                    pos: PosHex { q: 0, r: 2 },
+                    ability: Ability::BombPush,
+                }
+                .into(),
+                actor_ids: vec![Id(0)],
+                instant_effects: vec![(
+                    Id(2),
+                    vec![
+                        effect::Create {
+                            pos: PosHex { q: 0, r: 0 },
+                            prototype: "bomb_push".into(),
+                            components: vec![
+                                component::Pos(PosHex { q: 0, r: 0 }).into(),
+                                component_meta("bomb_push"),
+                            ],
+                            is_teleported: false,
+                        }
+                        .into(),
+                        effect::Throw {
+                            from: PosHex { q: 0, r: 0 },
+                            to: PosHex { q: 0, r: 2 },
+                            is_rolled: false,
+                        }
+                        .into(),
+                    ],
+                )],
+                timed_effects: Vec::new(),
+                scheduled_abilities: vec![(
+                    Id(2),
+                    vec![PlannedAbility {
+                        rounds: 0.into(),
+                        phase: Phase(0),
+                        ability: Ability::ExplodePush,
+                    }],
+                )],
+            },
+            Event {
+                active_event: event::UseAbility {
+                    id: Id(2),
+                    pos: PosHex { q: 0, r: 2 },
+                    ability: Ability::ExplodePush,
+                }
+                .into(),
+                actor_ids: vec![Id(2)],
+                instant_effects: vec![
+                    (
+                        Id(1),
+                        vec![effect::Knockback {
+                            from: PosHex { q: 1, r: 2 },
+                            to: PosHex { q: 1, r: 2 },
+                            strength: PushStrength(Weight::Normal),
+                        }
+                        .into()],
+                    ),
+                    (Id(2), vec![Effect::Vanish]),
+                ],
+                timed_effects: Vec::new(),
+                scheduled_abilities: Vec::new(),
+            },
+        ],
+    );
+    assert_eq!(state.parts().pos.get(Id(1)).0, initial_heavy_position);
+}
+
+#[test]
+fn knockback_normal_vs_normal() {
+    let prototypes = prototypes(&[
+        (
+            "knockbacker",
+            vec![
+                component_agent_always_hit(),
+                component_abilities(&[Ability::Knockback]),
+            ],
+        ),
+        (
+            "normal_target",
+            [
+                component_agent_dull(),
+                component_strength(1),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+    ]);
+    let target_position_initial = PosHex { q: 0, r: 1 };
+    let target_position_updated = PosHex { q: 0, r: 2 };
+    let scenario = Scenario::default()
+        .object(P0, "knockbacker", PosHex { q: 0, r: 0 })
+        .object(P1, "normal_target", target_position_initial);
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: target_position_initial,
+            ability: Ability::Knockback,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(0),
+                pos: target_position_initial,
+                ability: Ability::Knockback,
             }
             .into(),
-            actor_ids: vec![Id(0)],
+            actor_ids: vec![Id(1), Id(0)],
             instant_effects: vec![(
-                Id(2),
+                Id(1),
+                vec![effect::Knockback {
+                    from: target_position_initial,
+                    to: target_position_updated,
+                    strength: PushStrength(Weight::Normal),
+                }
+                .into()],
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        }],
+    );
+    assert_eq!(state.parts().pos.get(Id(1)).0, target_position_updated);
+}
+
+#[test]
+fn knockback_into_water_drowns() {
+    let prototypes = prototypes(&[
+        (
+            "knockbacker",
+            vec![
+                component_agent_always_hit(),
+                component_abilities(&[Ability::Knockback]),
+            ],
+        ),
+        (
+            "normal_target",
+            [
+                component_agent_dull(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+    ]);
+    let target_pos = PosHex { q: 0, r: 1 };
+    let water_pos = PosHex { q: 0, r: 2 };
+    let mut scenario = Scenario::default()
+        .object(P0, "knockbacker", PosHex { q: 0, r: 0 })
+        .object(P1, "normal_target", target_pos)
+        .object(P1, "normal_target", PosHex { q: 0, r: -2 });
+    scenario.tiles.insert(water_pos, TileType::Water);
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos: target_pos,
+            ability: Ability::Knockback,
+        },
+        &[Event {
+            active_event: event::UseAbility {
+                id: Id(0),
+                pos: target_pos,
+                ability: Ability::Knockback,
+            }
+            .into(),
+            actor_ids: vec![Id(1), Id(0)],
+            instant_effects: vec![(
+                Id(1),
                 vec![
-                    effect::Create {
-                        pos: PosHex { q: 0, r: 0 },
-                        prototype: "bomb_poison".into(),
-                        components: vec![
-                            component::Pos(PosHex { q: 0, r: 0 }).into(),
-                            component_meta("bomb_poison"),
-                        ],
-                        is_teleported: false,
+                    effect::Knockback {
+                        from: target_pos,
+                        to: water_pos,
+                        strength: PushStrength(Weight::Normal),
                     }
                     .into(),
-                    effect::Throw {
-                        from: PosHex { q: 0, r: 0 },
-                        to: PosHex { q: 0, r: 2 },
+                    effect::Kill {
+                        attacker_pos: None,
+                        is_drowned: true,
                     }
                     .into(),
                 ],
             )],
             timed_effects: Vec::new(),
-            scheduled_abilities: vec![(
-                Id(2),
-                vec![PlannedAbility {
-                    rounds: 1.into(),
-                    phase: Phase(0),
-                    ability: Ability::ExplodePoison,
-                }],
-            )],
+            scheduled_abilities: Vec::new(),
         }],
     );
-    exec_and_check(
+    assert!(!state.parts().is_exist(Id(1)));
+    assert!(state.decals().is_empty());
+}
+
+#[test]
+fn knockback_normal_vs_heavy() {
+    let prototypes = prototypes(&[
+        (
+            "knockbacker",
+            vec![
+                component_agent_always_hit(),
+                component_abilities(&[Ability::Knockback]),
+            ],
+        ),
+        (
+            "heavy_target",
+            [
+                component_agent_dull(),
+                component_strength(1),
+                component_blocker(Weight::Heavy),
+            ]
+            .to_vec(),
+        ),
+    ]);
+    let initial_heavy_position = PosHex { q: 0, r: 1 };
+    let scenario = Scenario::default()
+        .object(P0, "knockbacker", PosHex { q: 0, r: 0 })
+        .object(P1, "heavy_target", initial_heavy_position);
+    let mut state = debug_state(prototypes, scenario);
+    let knockback_result = try_exec(
         &mut state,
-        command::EndTurn,
-        &[event_end_turn(P0, &[Id(0)]), event_begin_turn(P1, &[Id(1)])],
+        command::UseAbility {
+            id: Id(0),
+            pos: initial_heavy_position,
+            ability: Ability::Knockback,
+        },
     );
-    let create_poison_cloud = |pos| -> Effect {
-        effect::Create {
-            pos,
-            prototype: "poison_cloud".into(),
-            components: vec![
-                component_passive_abilities(&[PassiveAbility::Poison]),
-                component::Pos(pos).into(),
-                component_meta("poison_cloud"),
+    assert_eq!(knockback_result, Err(check::Error::NotEnoughStrength));
+    assert_eq!(state.parts().pos.get(Id(1)).0, initial_heavy_position);
+}
+
+#[test]
+fn heavy_strike_flyoff_normal_vs_normal() {
+    let prototypes = prototypes(&[
+        (
+            "heavy_impacter",
+            vec![
+                component_agent_always_hit(),
+                component_strength(1),
+                component_passive_abilities(&[PassiveAbility::HeavyImpact]),
             ],
-            is_teleported: false,
-        }
-        .into()
-    };
-    let planned_ability_vanish = || -> PlannedAbility {
-        PlannedAbility {
-            rounds: 2.into(),
-            phase: Phase(0),
-            ability: Ability::Vanish,
-        }
-    };
+        ),
+        (
+            "normal_target",
+            vec![
+                component_agent_dull(),
+                component_strength(1),
+                component_blocker(Weight::Normal),
+            ],
+        ),
+    ]);
+    let position_attacker = PosHex { q: 0, r: 0 };
+    let position_target_initial = PosHex { q: 0, r: 1 };
+    let position_target_updated = PosHex { q: 0, r: 2 };
+    let scenario = Scenario::default()
+        .object(P0, "heavy_impacter", position_attacker)
+        .object(P1, "normal_target", position_target_initial);
+    let mut state = debug_state(prototypes, scenario);
     exec_and_check(
         &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P1, &[Id(1)]),
-            event_begin_turn(P0, &[Id(0)]),
-            Event {
-                active_event: event::UseAbility {
-                    id: Id(2),
-                    pos: PosHex { q: 0, r: 2 },
-                    ability: Ability::ExplodePoison,
-                }
-                .into(),
-                actor_ids: vec![Id(2)],
-                instant_effects: vec![
-                    (Id(2), vec![Effect::Vanish]),
-                    (Id(3), vec![create_poison_cloud(PosHex { q: 0, r: 2 })]),
-                    (Id(4), vec![create_poison_cloud(PosHex { q: 1, r: 2 })]),
-                    (Id(5), vec![create_poison_cloud(PosHex { q: 1, r: 1 })]),
-                    (Id(6), vec![create_poison_cloud(PosHex { q: 0, r: 1 })]),
-                    (Id(7), vec![create_poison_cloud(PosHex { q: -1, r: 2 })]),
-                    (Id(8), vec![create_poison_cloud(PosHex { q: -1, r: 3 })]),
-                    (Id(9), vec![create_poison_cloud(PosHex { q: 0, r: 3 })]),
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
+        &[Event {
+            active_event: event::Attack {
+                attacker_id: Id(0),
+                target_id: Id(1),
+                mode: AttackMode::Active,
+                weapon_type: WeaponType::Slash,
+            }
+            .into(),
+            actor_ids: vec![Id(0)],
+            instant_effects: vec![(
+                Id(1),
+                vec![
+                    effect::Wound {
+                        damage: Strength(0),
+                        armor_break: Strength(0),
+                        attacker_pos: Some(position_attacker),
+                    }
+                    .into(),
+                    effect::FlyOff {
+                        from: position_target_initial,
+                        to: position_target_updated,
+                        strength: PushStrength(Weight::Normal),
+                    }
+                    .into(),
                 ],
-                timed_effects: vec![(
-                    Id(1),
-                    vec![effect::Timed {
-                        duration: effect::Duration::Rounds(2.into()),
-                        phase: Phase(1),
-                        effect: effect::Lasting::Poison,
-                    }],
-                )],
-                scheduled_abilities: vec![
-                    (Id(3), vec![planned_ability_vanish()]),
-                    (Id(4), vec![planned_ability_vanish()]),
-                    (Id(5), vec![planned_ability_vanish()]),
-                    (Id(6), vec![planned_ability_vanish()]),
-                    (Id(7), vec![planned_ability_vanish()]),
-                    (Id(8), vec![planned_ability_vanish()]),
-                    (Id(9), vec![planned_ability_vanish()]),
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        }],
+    );
+    assert_eq!(state.parts().pos.get(Id(1)).0, position_target_updated);
+}
+
+#[test]
+fn heavy_strike_flyoff_normal_vs_heavy() {
+    let prototypes = prototypes(&[
+        (
+            "heavy_impacter",
+            vec![
+                component_agent_always_hit(),
+                component_strength(1),
+                component_passive_abilities(&[PassiveAbility::HeavyImpact]),
+            ],
+        ),
+        (
+            "heavy_target",
+            vec![
+                component_agent_dull(),
+                component_strength(1),
+                component_blocker(Weight::Heavy),
+            ],
+        ),
+    ]);
+    let position_attacker = PosHex { q: 0, r: 0 };
+    let position_target = PosHex { q: 0, r: 1 };
+    let scenario = Scenario::default()
+        .object(P0, "heavy_impacter", position_attacker)
+        .object(P1, "heavy_target", position_target);
+    let mut state = debug_state(prototypes, scenario);
+    exec_and_check(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
+        &[Event {
+            active_event: event::Attack {
+                attacker_id: Id(0),
+                target_id: Id(1),
+                mode: AttackMode::Active,
+                weapon_type: WeaponType::Slash,
+            }
+            .into(),
+            actor_ids: vec![Id(0)],
+            instant_effects: vec![(
+                Id(1),
+                vec![
+                    effect::Wound {
+                        damage: Strength(0),
+                        armor_break: Strength(0),
+                        attacker_pos: Some(position_attacker),
+                    }
+                    .into(),
+                    effect::FlyOff {
+                        from: position_target,
+                        to: position_target,
+                        strength: PushStrength(Weight::Normal),
+                    }
+                    .into(),
                 ],
-            },
-        ],
+            )],
+            timed_effects: Vec::new(),
+            scheduled_abilities: Vec::new(),
+        }],
     );
+    assert_eq!(state.parts().pos.get(Id(1)).0, position_target);
+}
+
+#[test]
+fn heavy_strike_flyoff_on_spikes() {
+    let prototypes = prototypes(&[
+        (
+            "heavy_impacter",
+            vec![
+                component_agent_always_hit(),
+                component_strength(1),
+                component_passive_abilities(&[PassiveAbility::HeavyImpact]),
+            ],
+        ),
+        (
+            "normal_target",
+            vec![
+                component_agent_dull(),
+                component_strength(2),
+                component_blocker(Weight::Normal),
+            ],
+        ),
+        (
+            "spike_trap",
+            vec![component_passive_abilities(&[PassiveAbility::SpikeTrap])],
+        ),
+    ]);
+    let position_attacker = PosHex { q: 0, r: 0 };
+    let position_target_initial = PosHex { q: 0, r: 1 };
+    let position_target_updated = PosHex { q: 0, r: 2 };
+    let position_spikes = position_target_updated;
+    let scenario = Scenario::default()
+        .object(P0, "heavy_impacter", position_attacker)
+        .object(P1, "normal_target", position_target_initial)
+        .object_without_owner("spike_trap", position_spikes);
+    let mut state = debug_state(prototypes, scenario);
     exec_and_check(
         &mut state,
-        command::EndTurn,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
         &[
-            event_end_turn(P0, &[Id(0)]),
-            event_begin_turn(P1, &[Id(1)]),
             Event {
-                active_event: event::UsePassiveAbility {
-                    id: Id(9),
-                    pos: PosHex { q: 0, r: 3 },
-                    ability: PassiveAbility::Poison,
+                active_event: event::Attack {
+                    attacker_id: Id(0),
+                    target_id: Id(1),
+                    mode: AttackMode::Active,
+                    weapon_type: WeaponType::Slash,
                 }
                 .into(),
-                actor_ids: Vec::new(),
-                instant_effects: Vec::new(),
-                timed_effects: vec![(
+                actor_ids: vec![Id(0)],
+                instant_effects: vec![(
                     Id(1),
-                    vec![effect::Timed {
-                        duration: effect::Duration::Rounds(2.into()),
-                        phase: Phase(1),
-                        effect: effect::Lasting::Poison,
-                    }],
+                    vec![
+                        effect::Wound {
+                            damage: Strength(0),
+                            armor_break: Strength(0),
+                            attacker_pos: Some(position_attacker),
+                        }
+                        .into(),
+                        effect::FlyOff {
+                            from: position_target_initial,
+                            to: position_target_updated,
+                            strength: PushStrength(Weight::Normal),
+                        }
+                        .into(),
+                    ],
                 )],
+                timed_effects: Vec::new(),
                 scheduled_abilities: Vec::new(),
             },
             Event {
-                active_event: event::EffectTick {
-                    id: Id(1),
-                    effect: effect::Lasting::Poison,
+                active_event: event::UsePassiveAbility {
+                    id: Id(2),
+                    pos: position_spikes,
+                    ability: PassiveAbility::SpikeTrap,
                 }
                 .into(),
-                actor_ids: vec![Id(1)],
+                actor_ids: Vec::new(),
                 instant_effects: vec![(
                     Id(1),
                     vec![effect::Wound {
@@ -814,934 +4814,1871 @@ fn throw_bomb_poison() {
                 timed_effects: Vec::new(),
                 scheduled_abilities: Vec::new(),
             },
-            Event {
-                active_event: event::EffectEnd {
-                    id: Id(1),
-                    effect: effect::Lasting::Poison,
-                }
-                .into(),
-                actor_ids: vec![Id(1)],
-                instant_effects: Vec::new(),
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
-            },
         ],
     );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[event_end_turn(P1, &[Id(1)]), event_begin_turn(P0, &[Id(0)])],
+    assert_eq!(state.parts().pos.get(Id(1)).0, position_target_updated);
+}
+
+#[test]
+fn fog_shortens_attack_distance() {
+    let archer = component::Agent {
+        attack_distance: Distance(2),
+        attack_strength: Strength(1),
+        ..agent_always_hit()
+    };
+    let prototypes = prototypes(&[
+        ("archer", [archer.into(), component_strength(1)].to_vec()),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(3)].to_vec(),
+        ),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "archer", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 2 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 });
+    scenario.weather = Weather::Fog;
+    let mut state = debug_state(prototypes, scenario);
+    assert_eq!(state::attack_distance(&state, Id(0)), Distance(1));
+    let attack = |target_id| command::Attack {
+        attacker_id: Id(0),
+        target_id,
+    };
+    let result = try_exec(&mut state, attack(Id(1)));
+    let (distance, max) = (Distance(2), Distance(1));
+    assert_eq!(
+        result,
+        Err(check::Error::DistanceIsTooBig { distance, max })
     );
-    exec_and_check(
+    exec(&mut state, attack(Id(2)));
+    assert_eq!(state.parts().strength.get(Id(2)).strength, Strength(2));
+}
+
+#[test]
+fn night_shortens_attack_distance_away_from_torches() {
+    let archer = component::Agent {
+        attack_distance: Distance(2),
+        attack_strength: Strength(1),
+        ..agent_always_hit()
+    };
+    let prototypes = prototypes(&[
+        ("archer", [archer.into(), component_strength(1)].to_vec()),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(3)].to_vec(),
+        ),
+        (
+            "torch",
+            [component_passive_abilities(&[PassiveAbility::Light])].to_vec(),
+        ),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "archer", PosHex { q: 0, r: 0 })
+        .object(P0, "archer", PosHex { q: -3, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 2 })
+        .object(P1, "imp", PosHex { q: -3, r: 2 })
+        .object_without_owner("torch", PosHex { q: 1, r: 1 });
+    scenario.is_night = true;
+    let mut state = debug_state(prototypes, scenario);
+    assert!(state::is_lit(&state, PosHex { q: 0, r: 0 }));
+    assert!(!state::is_lit(&state, PosHex { q: -3, r: 0 }));
+    let attack = |attacker_id, target_id| command::Attack {
+        attacker_id,
+        target_id,
+    };
+    let result = try_exec(&mut state, attack(Id(1), Id(3)));
+    let (distance, max) = (Distance(2), Distance(1));
+    assert_eq!(
+        result,
+        Err(check::Error::DistanceIsTooBig { distance, max })
+    );
+    exec(&mut state, attack(Id(0), Id(2)));
+    assert_eq!(state.parts().strength.get(Id(2)).strength, Strength(2));
+}
+
+#[test]
+fn wind_carries_thrown_bombs() {
+    let prototypes = prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::BombPush]),
+            ],
+        ),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(1)].to_vec(),
+        ),
+        ("bomb_push", Vec::new()),
+    ]);
+    let mut scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: -3, r: 0 });
+    scenario.weather = Weather::Wind(Dir::SouthWest);
+    let mut state = debug_state(prototypes, scenario);
+    let events = exec(
         &mut state,
-        command::EndTurn,
-        &[event_end_turn(P0, &[Id(0)]), event_begin_turn(P1, &[Id(1)])],
+        command::UseAbility {
+            id: Id(0),
+            pos: PosHex { q: 0, r: 2 },
+            ability: Ability::BombPush,
+        },
     );
-    let event_vanish = |id, pos| -> Event {
-        Event {
-            active_event: event::UseAbility {
-                id,
-                pos,
-                ability: Ability::Vanish,
-            }
-            .into(),
-            actor_ids: vec![id],
-            instant_effects: vec![(id, vec![Effect::Vanish])],
-            timed_effects: Vec::new(),
-            scheduled_abilities: Vec::new(),
-        }
+    let effects = &events[0].instant_effects[0].1;
+    let expected_throw: Effect = effect::Throw {
+        from: PosHex { q: 0, r: 0 },
+        to: PosHex { q: 0, r: 3 },
+        is_rolled: false,
+    }
+    .into();
+    assert_eq!(effects[1], expected_throw);
+}
+
+fn bomb_delivery_prototypes() -> Prototypes {
+    prototypes(&[
+        (
+            "thrower",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Bomb, Ability::BombDemonic]),
+            ],
+        ),
+        ("boulder", vec![component_blocker(Weight::Normal)]),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(1)].to_vec(),
+        ),
+        ("bomb_damage", Vec::new()),
+        ("bomb_demonic", Vec::new()),
+    ])
+}
+
+#[test]
+fn thrown_bombs_fly_over_blockers_unless_foggy() {
+    let mut scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object_without_owner("boulder", PosHex { q: 0, r: 1 })
+        .object(P1, "imp", PosHex { q: -3, r: 0 });
+    let command = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 0, r: 3 },
+        ability: Ability::Bomb,
     };
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P1, &[Id(1)]),
-            event_begin_turn(P0, &[Id(0)]),
-            event_vanish(Id(3), PosHex { q: 0, r: 2 }),
-            event_vanish(Id(4), PosHex { q: 1, r: 2 }),
-            event_vanish(Id(5), PosHex { q: 1, r: 1 }),
-            event_vanish(Id(6), PosHex { q: 0, r: 1 }),
-            event_vanish(Id(7), PosHex { q: -1, r: 2 }),
-            event_vanish(Id(8), PosHex { q: -1, r: 3 }),
-            event_vanish(Id(9), PosHex { q: 0, r: 3 }),
-        ],
+    let mut state = debug_state(bomb_delivery_prototypes(), scenario.clone());
+    let events = exec(&mut state, command.clone());
+    let effects = &events[0].instant_effects[0].1;
+    let expected_throw: Effect = effect::Throw {
+        from: PosHex { q: 0, r: 0 },
+        to: PosHex { q: 0, r: 3 },
+        is_rolled: false,
+    }
+    .into();
+    assert_eq!(effects[1], expected_throw);
+    scenario.weather = Weather::Fog;
+    let mut state = debug_state(bomb_delivery_prototypes(), scenario);
+    assert_eq!(
+        try_exec(&mut state, command).map(|_| ()),
+        Err(check::Error::DistanceIsTooBig {
+            distance: Distance(3),
+            max: Distance(2),
+        })
+    );
+}
+
+#[test]
+fn rolled_bombs_stop_in_front_of_blockers() {
+    let mut scenario = Scenario::default()
+        .object(P0, "thrower", PosHex { q: 0, r: 0 })
+        .object_without_owner("boulder", PosHex { q: 0, r: 2 })
+        .object(P1, "imp", PosHex { q: -3, r: 0 });
+    scenario.weather = Weather::Fog;
+    let mut state = debug_state(bomb_delivery_prototypes(), scenario);
+    let command = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 1, r: 1 },
+        ability: Ability::BombDemonic,
+    };
+    assert_eq!(
+        try_exec(&mut state, command).map(|_| ()),
+        Err(check::Error::BadPos {
+            pos: PosHex { q: 1, r: 1 }
+        })
     );
+    let command = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 0, r: 3 },
+        ability: Ability::BombDemonic,
+    };
+    let events = exec(&mut state, command);
+    let effects = &events[0].instant_effects[0].1;
+    let expected_roll: Effect = effect::Throw {
+        from: PosHex { q: 0, r: 0 },
+        to: PosHex { q: 0, r: 1 },
+        is_rolled: true,
+    }
+    .into();
+    assert_eq!(effects[1], expected_roll);
+}
+
+#[test]
+fn lasting_effect_stack_policies() {
+    let poison_twice = |policy| -> Vec<effect::Duration> {
+        let prototypes = prototypes(&[
+            (
+                "poisoner",
+                vec![
+                    component_agent_one_attack(),
+                    component_abilities(&[Ability::Poison]),
+                ],
+            ),
+            (
+                "imp",
+                [
+                    component_agent_dull(),
+                    component_strength(5),
+                    component_blocker(Weight::Normal),
+                ]
+                .to_vec(),
+            ),
+        ]);
+        let scenario = Scenario::default()
+            .object(P0, "poisoner", PosHex { q: 0, r: 0 })
+            .object(P0, "poisoner", PosHex { q: 1, r: 0 })
+            .object(P1, "imp", PosHex { q: 0, r: 1 });
+        let rule = StackRule {
+            policy,
+            is_burnt_by_fire: false,
+        };
+        let stack_rules = StackRules(HashMap::from([(effect::Lasting::Poison, rule)]));
+        let mut state = debug_state_with_stack_rules(prototypes, stack_rules, scenario);
+        for id in [Id(0), Id(1)] {
+            let pos = PosHex { q: 0, r: 1 };
+            let ability = Ability::Poison;
+            exec(&mut state, command::UseAbility { id, pos, ability });
+        }
+        let effects = &state.parts().effects.get(Id(2)).0;
+        effects.iter().map(|effect| effect.duration).collect()
+    };
+    let rounds = |n: i32| effect::Duration::Rounds(n.into());
+    assert_eq!(poison_twice(StackPolicy::Refresh), vec![rounds(2)]);
+    assert_eq!(poison_twice(StackPolicy::Extend), vec![rounds(4)]);
+    let stack = StackPolicy::Stack { limit: 2 };
+    assert_eq!(poison_twice(stack), vec![rounds(2), rounds(2)]);
+    let stack = StackPolicy::Stack { limit: 1 };
+    assert_eq!(poison_twice(stack), vec![rounds(2)]);
+    assert_eq!(poison_twice(StackPolicy::Ignore), vec![rounds(2)]);
 }
 
 #[test]
-fn throw_two_fire_bombs() {
+fn lasting_effects_turns_left() {
     let prototypes = prototypes(&[
         (
-            "thrower",
+            "poisoner",
             vec![
                 component_agent_one_attack(),
-                component_abilities(&[Ability::BombFire]),
+                component_abilities(&[Ability::Poison]),
             ],
         ),
-        ("dull", [component_agent_dull()].to_vec()),
-        ("bomb_fire", Vec::new()),
         (
-            "fire",
-            vec![component_passive_abilities(&[PassiveAbility::Burn])],
+            "imp",
+            [
+                component_agent_dull(),
+                component_strength(5),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
         ),
     ]);
     let scenario = Scenario::default()
-        .object(P0, "thrower", PosHex { q: 0, r: 0 })
-        .object(P0, "thrower", PosHex { q: -1, r: 0 })
-        .object(P1, "dull", PosHex { q: 0, r: -3 });
+        .object(P0, "poisoner", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
     let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
+    assert_eq!(state.lasting_effects(Id(1)), Vec::new());
+    exec(
         &mut state,
         command::UseAbility {
             id: Id(0),
-            pos: PosHex { q: 0, r: 2 },
-            ability: Ability::BombFire,
+            pos: PosHex { q: 0, r: 1 },
+            ability: Ability::Poison,
         },
-        &[Event {
-            active_event: event::UseAbility {
-                id: Id(0),
-                pos: PosHex { q: 0, r: 2 },
-                ability: Ability::BombFire,
-            }
-            .into(),
-            actor_ids: vec![Id(0)],
-            instant_effects: vec![(
-                Id(3),
-                vec![
-                    effect::Create {
-                        pos: PosHex { q: 0, r: 0 },
-                        prototype: "bomb_fire".into(),
-                        components: vec![
-                            component::Pos(PosHex { q: 0, r: 0 }).into(),
-                            component_meta("bomb_fire"),
-                        ],
-                        is_teleported: false,
-                    }
-                    .into(),
-                    effect::Throw {
-                        from: PosHex { q: 0, r: 0 },
-                        to: PosHex { q: 0, r: 2 },
-                    }
-                    .into(),
-                ],
-            )],
-            timed_effects: Vec::new(),
-            scheduled_abilities: vec![(
-                Id(3),
-                vec![PlannedAbility {
-                    rounds: 1.into(),
-                    phase: Phase(0),
-                    ability: Ability::ExplodeFire,
-                }],
-            )],
-        }],
-    );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P0, &[Id(0), Id(1)]),
-            event_begin_turn(P1, &[Id(2)]),
-        ],
     );
-    let create_fire = |pos| -> Effect {
-        effect::Create {
-            pos,
-            prototype: "fire".into(),
-            components: vec![
-                component_passive_abilities(&[PassiveAbility::Burn]),
-                component::Pos(pos).into(),
-                component_meta("fire"),
-            ],
-            is_teleported: false,
-        }
-        .into()
+    let poison = |turns_left| LastingEffectStatus {
+        effect: effect::Lasting::Poison,
+        turns_left: Some(turns_left),
     };
-    let planned_ability_vanish = || -> PlannedAbility {
-        PlannedAbility {
-            rounds: 2.into(),
-            phase: Phase(0),
-            ability: Ability::Vanish,
-        }
+    assert_eq!(state.lasting_effects(Id(1)), vec![poison(2)]);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.lasting_effects(Id(1)), vec![poison(1)]);
+    exec(&mut state, command::EndTurn);
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.lasting_effects(Id(1)), Vec::new());
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(3));
+}
+
+#[test]
+fn fire_burns_out_poison() {
+    let prototypes = prototypes(&[
+        (
+            "poisoner",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Poison]),
+            ],
+        ),
+        (
+            "imp",
+            [
+                component_agent_dull(),
+                component_strength(5),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+        (
+            "fire",
+            [component_passive_abilities(&[PassiveAbility::Burn])].to_vec(),
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "poisoner", PosHex { q: 0, r: 0 })
+        .object_without_owner("fire", PosHex { q: 0, r: 1 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    let rule = StackRule {
+        policy: StackPolicy::Refresh,
+        is_burnt_by_fire: true,
     };
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P1, &[Id(2)]),
-            event_begin_turn(P0, &[Id(0), Id(1)]),
-            Event {
-                active_event: event::UseAbility {
-                    id: Id(3),
-                    pos: PosHex { q: 0, r: 2 },
-                    ability: Ability::ExplodeFire,
-                }
-                .into(),
-                actor_ids: vec![Id(3)],
-                instant_effects: vec![
-                    (Id(3), vec![Effect::Vanish]),
-                    (Id(4), vec![create_fire(PosHex { q: 0, r: 2 })]),
-                    (Id(5), vec![create_fire(PosHex { q: 1, r: 2 })]),
-                    (Id(6), vec![create_fire(PosHex { q: 1, r: 1 })]),
-                    (Id(7), vec![create_fire(PosHex { q: 0, r: 1 })]),
-                    (Id(8), vec![create_fire(PosHex { q: -1, r: 2 })]),
-                    (Id(9), vec![create_fire(PosHex { q: -1, r: 3 })]),
-                    (Id(10), vec![create_fire(PosHex { q: 0, r: 3 })]),
-                ],
-                timed_effects: Vec::new(),
-                scheduled_abilities: vec![
-                    (Id(4), vec![planned_ability_vanish()]),
-                    (Id(5), vec![planned_ability_vanish()]),
-                    (Id(6), vec![planned_ability_vanish()]),
-                    (Id(7), vec![planned_ability_vanish()]),
-                    (Id(8), vec![planned_ability_vanish()]),
-                    (Id(9), vec![planned_ability_vanish()]),
-                    (Id(10), vec![planned_ability_vanish()]),
-                ],
-            },
-        ],
-    );
-    exec_and_check(
+    let stack_rules = StackRules(HashMap::from([(effect::Lasting::Poison, rule)]));
+    let mut state = debug_state_with_stack_rules(prototypes, stack_rules, scenario);
+    exec(
         &mut state,
         command::UseAbility {
-            id: Id(1),
-            pos: PosHex { q: 1, r: 1 },
-            ability: Ability::BombFire,
-        },
-        &[Event {
-            active_event: event::UseAbility {
-                id: Id(1),
-                pos: PosHex { q: 1, r: 1 },
-                ability: Ability::BombFire,
-            }
-            .into(),
-            actor_ids: vec![Id(1)],
-            instant_effects: vec![(
-                Id(11),
-                vec![
-                    effect::Create {
-                        pos: PosHex { q: -1, r: 0 },
-                        prototype: "bomb_fire".into(),
-                        components: vec![
-                            component::Pos(PosHex { q: -1, r: 0 }).into(),
-                            component_meta("bomb_fire"),
-                        ],
-                        is_teleported: false,
-                    }
-                    .into(),
-                    effect::Throw {
-                        from: PosHex { q: -1, r: 0 },
-                        to: PosHex { q: 1, r: 1 },
-                    }
-                    .into(),
-                ],
-            )],
-            timed_effects: Vec::new(),
-            scheduled_abilities: vec![(
-                Id(11),
-                vec![PlannedAbility {
-                    rounds: 1.into(),
-                    phase: Phase(0),
-                    ability: Ability::ExplodeFire,
-                }],
-            )],
-        }],
-    );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P0, &[Id(0), Id(1)]),
-            event_begin_turn(P1, &[Id(2)]),
-        ],
-    );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P1, &[Id(2)]),
-            event_begin_turn(P0, &[Id(0), Id(1)]),
-            Event {
-                active_event: event::UseAbility {
-                    id: Id(11),
-                    pos: PosHex { q: 1, r: 1 },
-                    ability: Ability::ExplodeFire,
-                }
-                .into(),
-                actor_ids: vec![Id(11)],
-                instant_effects: vec![
-                    (Id(11), vec![Effect::Vanish]),
-                    (Id(12), vec![create_fire(PosHex { q: 2, r: 1 })]),
-                    (Id(13), vec![create_fire(PosHex { q: 2, r: 0 })]),
-                    (Id(14), vec![create_fire(PosHex { q: 1, r: 0 })]),
-                ],
-                timed_effects: Vec::new(),
-                scheduled_abilities: vec![
-                    (Id(6), vec![planned_ability_vanish()]),
-                    (Id(12), vec![planned_ability_vanish()]),
-                    (Id(13), vec![planned_ability_vanish()]),
-                    (Id(14), vec![planned_ability_vanish()]),
-                    (Id(7), vec![planned_ability_vanish()]),
-                    (Id(4), vec![planned_ability_vanish()]),
-                    (Id(5), vec![planned_ability_vanish()]),
-                ],
-            },
-        ],
-    );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P0, &[Id(0), Id(1)]),
-            event_begin_turn(P1, &[Id(2)]),
-        ],
-    );
-    let event_vanish = |id, pos| -> Event {
-        Event {
-            active_event: event::UseAbility {
-                id,
-                pos,
-                ability: Ability::Vanish,
-            }
-            .into(),
-            actor_ids: vec![id],
-            instant_effects: vec![(id, vec![Effect::Vanish])],
-            timed_effects: Vec::new(),
-            scheduled_abilities: Vec::new(),
-        }
+            id: Id(0),
+            pos: PosHex { q: 0, r: 1 },
+            ability: Ability::Poison,
+        },
+    );
+    assert_eq!(state.parts().effects.get(Id(2)).0.len(), 1);
+    exec(&mut state, command::EndTurn);
+    // The fire has burnt the imp once and the poison out, so it hasn't ticked.
+    assert!(state.parts().effects.get(Id(2)).0.is_empty());
+    assert_eq!(state.parts().strength.get(Id(2)).strength, Strength(4));
+}
+
+fn component_resistances(resistances: &[(DamageType, i32)]) -> Component {
+    let map = resistances
+        .iter()
+        .map(|&(damage_type, n)| (damage_type, Strength(n)))
+        .collect();
+    component::Resistances(map).into()
+}
+
+#[test]
+fn resistances_absorb_the_damage_of_their_type() {
+    let spearman = component::Agent {
+        attack_strength: Strength(2),
+        weapon_type: WeaponType::Pierce,
+        ..agent_always_hit()
     };
-    exec_and_check(
+    let swordsman = component::Agent {
+        attack_strength: Strength(2),
+        ..agent_always_hit()
+    };
+    let prototypes = prototypes(&[
+        ("spearman", vec![spearman.into(), component_strength(1)]),
+        ("swordsman", vec![swordsman.into(), component_strength(1)]),
+        (
+            "skeleton",
+            vec![
+                component_agent_dull(),
+                component_strength(5),
+                component_resistances(&[(DamageType::Pierce, 1)]),
+            ],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "spearman", PosHex { q: 0, r: 0 })
+        .object(P0, "swordsman", PosHex { q: 1, r: 0 })
+        .object(P1, "skeleton", PosHex { q: 0, r: 1 });
+    let mut state = debug_state(prototypes, scenario);
+    let skeleton_id = Id(2);
+    let terms = execute::attack_terms(&state, Id(0), skeleton_id);
+    assert_eq!(terms.target_resistance, Strength(1));
+    let terms = execute::attack_terms(&state, Id(1), skeleton_id);
+    assert_eq!(terms.target_resistance, Strength(0));
+    exec(
         &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P1, &[Id(2)]),
-            event_begin_turn(P0, &[Id(0), Id(1)]),
-            event_vanish(Id(8), PosHex { q: -1, r: 2 }),
-            event_vanish(Id(9), PosHex { q: -1, r: 3 }),
-            event_vanish(Id(10), PosHex { q: 0, r: 3 }),
-        ],
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: skeleton_id,
+        },
     );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P0, &[Id(0), Id(1)]),
-            event_begin_turn(P1, &[Id(2)]),
-        ],
+    assert_eq!(
+        state.parts().strength.get(skeleton_id).strength,
+        Strength(4)
     );
-    exec_and_check(
+}
+
+#[test]
+fn fire_resistant_agents_walk_through_fire() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            vec![component_agent_dull(), component_strength(1)],
+        ),
+        (
+            "imp",
+            vec![
+                component::Agent {
+                    moves: Moves(1),
+                    move_points: MovePoints(5),
+                    ..agent_dull()
+                }
+                .into(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+                component_resistances(&[(DamageType::Fire, 1)]),
+            ],
+        ),
+        (
+            "fire",
+            vec![component_passive_abilities(&[PassiveAbility::Burn])],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 2, r: 0 })
+        .object_without_owner("fire", PosHex { q: 0, r: 1 })
+        .object(P1, "imp", PosHex { q: 0, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    exec(&mut state, command::EndTurn);
+    let path = Path::new(vec![PosHex { q: 0, r: 0 }, PosHex { q: 0, r: 1 }]);
+    let events = exec(&mut state, command::MoveTo { id: Id(2), path });
+    let is_burnt = events
+        .iter()
+        .any(|event| matches!(event.active_event, ActiveEvent::UsePassiveAbility(_)));
+    assert!(!is_burnt);
+    assert_eq!(state.parts().strength.get(Id(2)).strength, Strength(3));
+}
+
+fn forecast_attack(state: &State, attacker_id: Id, target_id: Id) -> forecast::AttackForecast {
+    let command = command::Attack {
+        attacker_id,
+        target_id,
+    }
+    .into();
+    match forecast::forecast(state, &command) {
+        Some(Forecast::Attack(forecast)) => forecast,
+        forecast => panic!("Not an attack forecast: {:?}", forecast),
+    }
+}
+
+#[test]
+fn forecast_attack_outcomes() {
+    let attacker = component::Agent {
+        attack_accuracy: Accuracy(5),
+        attack_strength: Strength(2),
+        ..agent_always_hit()
+    };
+    let prototypes = prototypes(&[
+        (
+            "attacker",
+            [attacker.into(), component_strength(1)].to_vec(),
+        ),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(5)].to_vec(),
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "attacker", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 });
+    let state = debug_state(prototypes, scenario);
+    let forecast = forecast_attack(&state, Id(0), Id(1));
+    let outcome = |is_miss, damage, rolls: i32| Outcome {
+        is_miss,
+        damage: Strength(damage),
+        is_kill: false,
+        probability: rolls as f32 / dice::ROLL_SIDES as f32,
+    };
+    let expected = vec![
+        outcome(true, 0, 3),
+        outcome(false, 0, 1),
+        outcome(false, 1, 1),
+        outcome(false, 2, 6),
+    ];
+    assert_eq!(forecast.outcomes.len(), expected.len());
+    for (outcome, expected) in forecast.outcomes.iter().zip(&expected) {
+        assert_eq!(
+            (outcome.is_miss, outcome.damage),
+            (expected.is_miss, expected.damage)
+        );
+        assert!((outcome.probability - expected.probability).abs() < 0.001);
+    }
+    assert!((forecast.hit_chance() - 8.0 / 11.0).abs() < 0.001);
+    assert_eq!(forecast.kill_chance(), 0.0);
+    assert_eq!(forecast.damage_range(), Some((Strength(0), Strength(2))));
+    assert!(forecast.retaliation.is_empty());
+    // Nothing has been rolled or changed.
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(5));
+}
+
+#[test]
+fn forecast_attack_retaliation() {
+    let prototypes = prototypes(&[
+        (
+            "swordsman",
+            [
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ]
+            .to_vec(),
+        ),
+        (
+            "imp",
+            [
+                component_agent_always_hit_strength_1(),
+                component_strength(2),
+            ]
+            .to_vec(),
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 });
+    let state = debug_state(prototypes, scenario);
+    let forecast = forecast_attack(&state, Id(0), Id(1));
+    let is_about = |a: f32, b: f32| (a - b).abs() < 0.001;
+    assert!(is_about(forecast.hit_chance(), 1.0));
+    assert_eq!(forecast.kill_chance(), 0.0);
+    let reactors: Vec<_> = forecast
+        .retaliation
+        .iter()
+        .map(|reaction| reaction.attacker_id)
+        .collect();
+    assert_eq!(reactors, vec![Id(1), Id(2)]);
+    for reaction in &forecast.retaliation {
+        assert!(is_about(reaction.chance, 1.0));
+        assert!(is_about(reaction.kill_chance(), 1.0));
+    }
+}
+
+#[test]
+fn attack_terms() {
+    let swordsman = component::Agent {
+        attack_accuracy: Accuracy(5),
+        attack_strength: Strength(2),
+        attack_break: Strength(3),
+        ..agent_always_hit()
+    };
+    let imp = component::Agent {
+        dodge: Dodge(2),
+        ..agent_dull()
+    };
+    let armor = component::Armor { armor: Strength(1) };
+    let prototypes = prototypes(&[
+        ("swordsman", vec![swordsman.into(), component_strength(1)]),
+        (
+            "imp",
+            vec![imp.into(), component_strength(4), Component::Armor(armor)],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swordsman", PosHex { q: 0, r: 0 })
+        .object(P0, "swordsman", PosHex { q: 2, r: 0 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 });
+    let state = debug_state(prototypes, scenario);
+    let imp_id = Id(2);
+    let front = execute::attack_terms(&state, Id(0), imp_id);
+    let expected = execute::AttackTerms {
+        base_damage: Strength(2),
+        backstab_bonus: Strength(0),
+        aura_bonus: Strength(0),
+        accuracy: Accuracy(5),
+        target_dodge: Dodge(2),
+        wounds_penalty: 0,
+        injury_penalty: 0,
+        target_armor: Strength(1),
+        target_resistance: Strength(0),
+        armor_break: Strength(1),
+    };
+    assert_eq!(front, expected);
+    assert_eq!(front.hit_chance(), (3, 5));
+    let rear = execute::attack_terms(&state, Id(1), imp_id);
+    assert_eq!(rear.backstab_bonus, Strength(1));
+    assert_eq!(rear.damage(), Strength(3));
+    assert_eq!(rear.hit_chance(), (3, 6));
+    assert_eq!(forecast_attack(&state, Id(1), imp_id).terms, rear);
+}
+
+#[test]
+fn threatened_tiles() {
+    let imp = component::Agent {
+        attack_distance: Distance(1),
+        move_points: MovePoints(1),
+        moves: Moves(1),
+        attacks: Attacks(1),
+        ..agent_dull()
+    };
+    let prototypes = prototypes(&[
+        ("imp", vec![imp.into(), component_strength(1)]),
+        ("dull", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "dull", PosHex { q: -3, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 0 })
+        .object(P1, "dull", PosHex { q: 3, r: 0 });
+    let state = debug_state(prototypes, scenario);
+    let tiles = threat::threatened_tiles(&state, P0);
+    for pos in state.map().iter() {
+        let distance = map::distance_hex(PosHex { q: 0, r: 0 }, pos);
+        assert_eq!(tiles.tile(pos), distance <= Distance(2), "{:?}", pos);
+    }
+    // The enemies never threaten their own side.
+    let tiles = threat::threatened_tiles(&state, P1);
+    assert!(state.map().iter().all(|pos| !tiles.tile(pos)));
+}
+
+#[test]
+fn heal_tiers_change_power_and_range() {
+    let healer = |tier| {
+        let tiers = vec![(Ability::Heal, Tier(tier))].into_iter().collect();
+        vec![
+            component_agent_one_attack(),
+            component_abilities(&[Ability::Heal]),
+            component::AbilityTiers(tiers).into(),
+        ]
+    };
+    let prototypes = prototypes(&[
+        ("healer", healer(1)),
+        ("great_healer", healer(3)),
+        (
+            "swordsman",
+            vec![
+                component_agent_dull(),
+                component_strength(5),
+                component_blocker(Weight::Normal),
+            ],
+        ),
+        (
+            "hitter",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(1),
+            ],
+        ),
+    ]);
+    let heal = |id| command::UseAbility {
+        id: Id(id),
+        pos: PosHex { q: 0, r: 2 },
+        ability: Ability::Heal,
+    };
+    let scenario = Scenario::default()
+        .object(P0, "healer", PosHex { q: 0, r: 0 })
+        .object(P0, "great_healer", PosHex { q: 1, r: 0 })
+        .object(P0, "swordsman", PosHex { q: 0, r: 2 })
+        .object(P0, "hitter", PosHex { q: -1, r: 3 })
+        .object(P1, "swordsman", PosHex { q: 3, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    exec(
         &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P1, &[Id(2)]),
-            event_begin_turn(P0, &[Id(0), Id(1)]),
-            event_vanish(Id(4), PosHex { q: 0, r: 2 }),
-            event_vanish(Id(5), PosHex { q: 1, r: 2 }),
-            event_vanish(Id(6), PosHex { q: 1, r: 1 }),
-            event_vanish(Id(7), PosHex { q: 0, r: 1 }),
-            event_vanish(Id(12), PosHex { q: 2, r: 1 }),
-            event_vanish(Id(13), PosHex { q: 2, r: 0 }),
-            event_vanish(Id(14), PosHex { q: 1, r: 0 }),
-        ],
+        command::Attack {
+            attacker_id: Id(3),
+            target_id: Id(2),
+        },
     );
+    assert!(state.parts().strength.get(Id(2)).strength < Strength(4));
+    let result = try_exec(&mut state, heal(0));
+    let expected = Err(check::Error::DistanceIsTooBig {
+        distance: Distance(2),
+        max: Distance(1),
+    });
+    assert_eq!(result, expected);
+    exec(&mut state, heal(1));
+    assert_eq!(state.parts().strength.get(Id(2)).strength, Strength(5));
+    assert_eq!(state.abilities(Id(1))[0].cooldown, 2);
 }
 
 #[test]
-fn stun() {
+fn redefined_club_wounds_instead_of_stunning() {
+    let club = "#![enable(implicit_some, unwrap_newtypes)]
+        (
+            target: Agent,
+            min_distance: 1,
+            effects: [Wound],
+            tiers: [(cooldown: 1, power: 2, distance: 2)],
+        )";
+    let mut definitions = game_ability_definitions();
+    definitions
+        .0
+        .insert(Ability::Club, ron::de::from_str(club).unwrap());
     let prototypes = prototypes(&[
         (
-            "attacker",
+            "clubber",
             vec![
                 component_agent_one_attack(),
                 component_abilities(&[Ability::Club]),
             ],
         ),
         (
-            "target",
+            "imp",
             vec![
                 component_agent_dull(),
+                component_strength(3),
                 component_blocker(Weight::Normal),
-                component_strength(1),
             ],
         ),
     ]);
     let scenario = Scenario::default()
-        .object(P0, "attacker", PosHex { q: 0, r: 0 })
-        .object(P1, "target", PosHex { q: 0, r: 1 });
-    let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
+        .object(P0, "clubber", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 2 });
+    let mut state = State::new(
+        prototypes,
+        StackRules::default(),
+        definitions,
+        scenario,
+        &mut |_, _, _| {},
+    );
+    state.set_deterministic_mode(true);
+    exec(
         &mut state,
         command::UseAbility {
             id: Id(0),
-            pos: PosHex { q: 0, r: 1 },
+            pos: PosHex { q: 0, r: 2 },
             ability: Ability::Club,
         },
-        &[Event {
-            active_event: event::UseAbility {
-                id: Id(0),
-                pos: PosHex { q: 0, r: 1 },
-                ability: Ability::Club,
-            }
-            .into(),
-            actor_ids: vec![Id(1), Id(0)],
-            instant_effects: vec![(Id(1), vec![Effect::Stun])],
-            timed_effects: vec![(
-                Id(1),
-                vec![effect::Timed {
-                    duration: effect::Duration::Rounds(1.into()),
-                    phase: Phase(1),
-                    effect: effect::Lasting::Stun,
-                }],
-            )],
-            scheduled_abilities: Vec::new(),
-        }],
     );
-    exec_and_check(
-        &mut state,
-        command::EndTurn,
-        &[
-            event_end_turn(P0, &[Id(0)]),
-            event_begin_turn(P1, &[Id(1)]),
-            Event {
-                active_event: event::EffectTick {
-                    id: Id(1),
-                    effect: effect::Lasting::Stun,
-                }
-                .into(),
-                actor_ids: vec![Id(1)],
-                instant_effects: vec![(Id(1), vec![Effect::Stun])],
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
-            },
-            Event {
-                active_event: event::EffectEnd {
-                    id: Id(1),
-                    effect: effect::Lasting::Stun,
-                }
-                .into(),
-                actor_ids: vec![Id(1)],
-                instant_effects: Vec::new(),
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
-            },
-        ],
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(1));
+    assert!(!state::has_lasting_effect(
+        &state,
+        Id(1),
+        effect::Lasting::Stun
+    ));
+    assert_eq!(state.abilities(Id(0))[0].cooldown, 1);
+}
+
+#[test]
+fn bloodlust_is_only_for_allies() {
+    let prototypes = prototypes(&[
+        (
+            "shaman",
+            vec![
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Bloodlust]),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "shaman", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 3 });
+    let mut state = debug_state(prototypes, scenario);
+    let bloodlust = |pos| command::UseAbility {
+        id: Id(0),
+        pos,
+        ability: Ability::Bloodlust,
+    };
+    let result = try_exec(&mut state, bloodlust(PosHex { q: 0, r: 3 }));
+    assert_eq!(result, Err(check::Error::BadTargetType));
+    exec(&mut state, bloodlust(PosHex { q: 0, r: 0 }));
+}
+
+fn component_reactions(reactions: &[(Hook, ReactionEffect)]) -> Component {
+    let reactions = reactions
+        .iter()
+        .map(|&(hook, effect)| component::Reaction { hook, effect })
+        .collect();
+    component::Reactions(reactions).into()
+}
+
+#[test]
+fn reaction_on_death_starts_fires() {
+    let fire = ReactionEffect::Fire {
+        radius: Distance(1),
+    };
+    let prototypes = prototypes(&[
+        (
+            "hitter",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(3),
+            ],
+        ),
+        (
+            "barrel",
+            vec![
+                component_agent_dull(),
+                component_strength(1),
+                component_reactions(&[(Hook::Death, fire)]),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(1)]),
+        (
+            "fire",
+            vec![component_passive_abilities(&[PassiveAbility::Burn])],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "hitter", PosHex { q: 0, r: 0 })
+        .object(P1, "barrel", PosHex { q: 0, r: 1 })
+        .object(P1, "imp", PosHex { q: 0, r: -3 });
+    let mut state = debug_state(prototypes, scenario);
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
+        },
     );
+    assert!(!state.parts().is_exist(Id(1)));
+    let fires = state
+        .map()
+        .iter()
+        .filter(|&pos| {
+            state::obj_with_passive_ability_at(&state, pos, PassiveAbility::Burn).is_some()
+        })
+        .count();
+    assert_eq!(fires, 7);
+    assert!(state.parts().strength.get(Id(0)).strength < Strength(3));
 }
 
 #[test]
-fn throw_bomb_push_normal() {
+fn reaction_on_hit_poisons_the_attacker() {
+    let poison = ReactionEffect::Lasting {
+        target: ReactionTarget::Attacker,
+        effect: effect::Lasting::Poison,
+        rounds: Rounds(2),
+    };
     let prototypes = prototypes(&[
         (
-            "thrower",
+            "hitter",
             vec![
-                component_agent_one_attack(),
-                component_abilities(&[Ability::BombPush]),
+                component_agent_always_hit_strength_1(),
+                component_strength(3),
             ],
         ),
         (
-            "weak",
-            [
+            "toad",
+            vec![
                 component_agent_dull(),
-                component_strength(1),
-                component_blocker(Weight::Normal),
-            ]
-            .to_vec(),
+                component_strength(3),
+                component_reactions(&[(Hook::Hit, poison)]),
+            ],
         ),
-        ("bomb_push", Vec::new()),
     ]);
     let scenario = Scenario::default()
-        .object(P0, "thrower", PosHex { q: 0, r: 0 })
-        .object(P1, "weak", PosHex { q: 0, r: 3 });
+        .object(P0, "hitter", PosHex { q: 0, r: 0 })
+        .object(P1, "toad", PosHex { q: 0, r: 1 });
     let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
+    let events = try_exec(
         &mut state,
-        command::UseAbility {
-            id: Id(0),
-            pos: PosHex { q: 0, r: 2 },
-            ability: Ability::BombPush,
+        command::Attack {
+            attacker_id: Id(0),
+            target_id: Id(1),
         },
-        &[
-            Event {
-                active_event: event::UseAbility {
-                    id: Id(0),
-                    pos: PosHex { q: 0, r: 2 },
-                    ability: Ability::BombPush,
-                }
-                .into(),
-                actor_ids: vec![Id(0)],
-                instant_effects: vec![(
-                    Id(2),
-                    vec![
-                        effect::Create {
-                            pos: PosHex { q: 0, r: 0 },
-                            prototype: "bomb_push".into(),
-                            components: vec![
-                                component::Pos(PosHex { q: 0, r: 0 }).into(),
-                                component_meta("bomb_push"),
-                            ],
-                            is_teleported: false,
-                        }
-                        .into(),
-                        effect::Throw {
-                            from: PosHex { q: 0, r: 0 },
-                            to: PosHex { q: 0, r: 2 },
-                        }
-                        .into(),
-                    ],
-                )],
-                timed_effects: Vec::new(),
-                scheduled_abilities: vec![(
-                    Id(2),
-                    vec![PlannedAbility {
-                        rounds: 0.into(),
-                        phase: Phase(0),
-                        ability: Ability::ExplodePush,
-                    }],
-                )],
-            },
-            Event {
-                active_event: event::UseAbility {
-                    id: Id(2),
-                    pos: PosHex { q: 0, r: 2 },
-                    ability: Ability::ExplodePush,
-                }
-                .into(),
-                actor_ids: vec![Id(2)],
-                instant_effects: vec![
-                    (
-                        Id(1),
-                        vec![effect::Knockback {
-                            from: PosHex { q: 0, r: 3 },
-                            to: PosHex { q: 0, r: 4 },
-                            strength: PushStrength(Weight::Normal),
-                        }
-                        .into()],
-                    ),
-                    (Id(2), vec![Effect::Vanish]),
-                ],
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
-            },
-        ],
+    )
+    .unwrap();
+    let reactions: Vec<_> = events
+        .iter()
+        .filter_map(|event| match &event.active_event {
+            ActiveEvent::Reaction(reaction) => Some(reaction.hook),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(reactions, [Hook::Hit]);
+    let effects = &state.parts().effects.get(Id(0)).0;
+    assert_eq!(effects.len(), 1);
+    assert_eq!(effects[0].effect, effect::Lasting::Poison);
+    assert!(state
+        .parts()
+        .effects
+        .get_opt(Id(1))
+        .is_none_or(|effects| effects.0.is_empty()));
+}
+
+fn component_auras(auras: &[(Distance, AuraTargets, AuraEffect)]) -> Component {
+    let auras = auras
+        .iter()
+        .map(|&(radius, targets, effect)| component::Aura {
+            radius,
+            targets,
+            effect,
+        })
+        .collect();
+    component::Auras(auras).into()
+}
+
+#[test]
+fn aura_attack_bonus_follows_the_source() {
+    let banner = (
+        Distance(1),
+        AuraTargets::Allies,
+        AuraEffect::AttackStrength(1),
     );
-    assert_eq!(state.parts().pos.get(Id(1)).0, PosHex { q: 0, r: 4 });
+    let prototypes = prototypes(&[
+        (
+            "hitter",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(3),
+            ],
+        ),
+        (
+            "bannerman",
+            vec![
+                component_agent_move_basic(),
+                component_strength(3),
+                component_auras(&[banner]),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(3)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "hitter", PosHex { q: 0, r: 0 })
+        .object(P0, "bannerman", PosHex { q: -2, r: 0 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    assert_eq!(state::aura_attack_bonus(&state, Id(1)), Strength(0));
+    let terms = execute::attack_terms(&state, Id(0), Id(2));
+    assert_eq!(terms.aura_bonus, Strength(0));
+    let path = Path::new(vec![PosHex { q: -2, r: 0 }, PosHex { q: -1, r: 0 }]);
+    exec(&mut state, command::MoveTo { id: Id(1), path });
+    let terms = execute::attack_terms(&state, Id(0), Id(2));
+    assert_eq!(terms.aura_bonus, Strength(1));
+    assert_eq!(terms.damage(), Strength(2));
+    // The aura of an ally doesn't help the enemies next to it.
+    assert_eq!(state::aura_attack_bonus(&state, Id(2)), Strength(0));
 }
 
 #[test]
-fn throw_bomb_push_heavy() {
+fn lasting_aura_afflicts_enemies_at_their_turn_start() {
+    let miasma = AuraEffect::Lasting {
+        effect: effect::Lasting::Poison,
+        rounds: Rounds(1),
+    };
     let prototypes = prototypes(&[
         (
-            "thrower",
+            "swamp_toad",
+            vec![
+                component_agent_dull(),
+                component_strength(3),
+                component_auras(&[(Distance(1), AuraTargets::Enemies, miasma)]),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(3)]),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "swamp_toad", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 })
+        .object(P1, "imp", PosHex { q: 3, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let events = try_exec(&mut state, command::EndTurn).unwrap();
+    let auras: Vec<_> = events
+        .iter()
+        .filter_map(|event| match &event.active_event {
+            ActiveEvent::Aura(aura) => Some(aura.id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(auras, [Id(0)]);
+    let effects = &state.parts().effects.get(Id(1)).0;
+    assert_eq!(effects.len(), 1);
+    assert_eq!(effects[0].effect, effect::Lasting::Poison);
+    assert!(state
+        .parts()
+        .effects
+        .get_opt(Id(2))
+        .is_none_or(|effects| effects.0.is_empty()));
+}
+
+#[test]
+fn totem_heals_wounded_allies_each_turn() {
+    let totem_aura = (Distance(1), AuraTargets::Allies, AuraEffect::Heal(1));
+    let prototypes = prototypes(&[
+        (
+            "builder",
             vec![
                 component_agent_one_attack(),
-                component_abilities(&[Ability::BombPush]),
+                component_strength(3),
+                component_abilities(&[Ability::Totem]),
             ],
         ),
         (
-            "heavy",
-            [
-                component_agent_dull(),
-                component_strength(1),
-                component_blocker(Weight::Heavy),
-            ]
-            .to_vec(),
+            "swordsman",
+            vec![
+                component_agent_always_hit_strength_1(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+            ],
         ),
-        ("bomb_push", Vec::new()),
+        (
+            "healing_totem",
+            vec![
+                component_blocker(Weight::Normal),
+                component_strength(2),
+                component_auras(&[totem_aura]),
+            ],
+        ),
+        ("imp", vec![component_agent_dull(), component_strength(3)]),
     ]);
-    let initial_heavy_position = PosHex { q: 1, r: 2 };
     let scenario = Scenario::default()
-        .object(P0, "thrower", PosHex { q: 0, r: 0 })
-        .object(P1, "heavy", initial_heavy_position);
+        .object(P0, "builder", PosHex { q: 0, r: 0 })
+        .object(P0, "swordsman", PosHex { q: 2, r: 0 })
+        .object(P0, "swordsman", PosHex { q: 3, r: 0 })
+        .object(P1, "imp", PosHex { q: -3, r: 0 });
     let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
+    exec(
+        &mut state,
+        command::Attack {
+            attacker_id: Id(2),
+            target_id: Id(1),
+        },
+    );
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(2));
+    let pos = PosHex { q: 1, r: 0 };
+    exec(
         &mut state,
         command::UseAbility {
             id: Id(0),
-            pos: PosHex { q: 0, r: 2 },
-            ability: Ability::BombPush,
+            pos,
+            ability: Ability::Totem,
         },
-        &[
-            Event {
-                active_event: event::UseAbility {
-                    id: Id(0),
-                    pos: PosHex { q: 0, r: 2 },
-                    ability: Ability::BombPush,
-                }
-                .into(),
-                actor_ids: vec![Id(0)],
-                instant_effects: vec![(
-                    Id(2),
-                    vec![
-                        effect::Create {
-                            pos: PosHex { q: 0, r: 0 },
-                            prototype: "bomb_push".into(),
-                            components: vec![
-                                component::Pos(PosHex { q: 0, r: 0 }).into(),
-                                component_meta("bomb_push"),
-                            ],
-                            is_teleported: false,
-                        }
-                        .into(),
-                        effect::Throw {
-                            from: PosHex { q: 0, r: 0 },
-                            to: PosHex { q: 0, r: 2 },
-                        }
-                        .into(),
-                    ],
-                )],
-                timed_effects: Vec::new(),
-                scheduled_abilities: vec![(
-                    Id(2),
-                    vec![PlannedAbility {
-                        rounds: 0.into(),
-                        phase: Phase(0),
-                        ability: Ability::ExplodePush,
-                    }],
-                )],
-            },
-            Event {
-                active_event: event::UseAbility {
-                    id: Id(2),
-                    pos: PosHex { q: 0, r: 2 },
-                    ability: Ability::ExplodePush,
-                }
-                .into(),
-                actor_ids: vec![Id(2)],
-                instant_effects: vec![
-                    (
-                        Id(1),
-                        vec![effect::Knockback {
-                            from: PosHex { q: 1, r: 2 },
-                            to: PosHex { q: 1, r: 2 },
-                            strength: PushStrength(Weight::Normal),
-                        }
-                        .into()],
-                    ),
-                    (Id(2), vec![Effect::Vanish]),
-                ],
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
+    );
+    let totem_id = state::blocker_id_at(&state, pos);
+    assert_eq!(state.parts().belongs_to.get(totem_id).0, P0);
+    assert!(state.parts().agent.get_opt(totem_id).is_none());
+    exec(&mut state, command::EndTurn);
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(2));
+    let events = exec(&mut state, command::EndTurn);
+    assert!(events
+        .iter()
+        .any(|event| matches!(event.active_event, ActiveEvent::Aura(_))));
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(3));
+    // Nothing to heal, so the totem stays silent.
+    exec(&mut state, command::EndTurn);
+    let events = exec(&mut state, command::EndTurn);
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event.active_event, ActiveEvent::Aura(_))));
+}
+
+#[test]
+fn barricade_needs_a_free_tile() {
+    let prototypes = prototypes(&[
+        (
+            "builder",
+            vec![
+                component_agent_one_attack(),
+                component_strength(3),
+                component_abilities(&[Ability::Barricade]),
+            ],
+        ),
+        (
+            "barricade",
+            vec![component_blocker(Weight::Heavy), component_strength(3)],
+        ),
+        (
+            "imp",
+            vec![
+                component_agent_dull(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+            ],
+        ),
+    ]);
+    let scenario = Scenario::default()
+        .object(P0, "builder", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let blocked = PosHex { q: 1, r: 0 };
+    assert_eq!(
+        try_exec(
+            &mut state,
+            command::UseAbility {
+                id: Id(0),
+                pos: blocked,
+                ability: Ability::Barricade,
             },
-        ],
+        )
+        .map(|_| ()),
+        Err(check::Error::TileIsBlocked { pos: blocked })
     );
-    assert_eq!(state.parts().pos.get(Id(1)).0, initial_heavy_position);
+    let pos = PosHex { q: 0, r: 1 };
+    exec(
+        &mut state,
+        command::UseAbility {
+            id: Id(0),
+            pos,
+            ability: Ability::Barricade,
+        },
+    );
+    assert!(state::is_tile_blocked(&state, pos));
+    let barricade_id = state::blocker_id_at(&state, pos);
+    assert!(state::is_destructible(&state, barricade_id));
 }
 
 #[test]
-fn knockback_normal_vs_normal() {
+fn forecast_heal() {
     let prototypes = prototypes(&[
         (
-            "knockbacker",
+            "healer",
             vec![
-                component_agent_always_hit(),
-                component_abilities(&[Ability::Knockback]),
+                component_agent_one_attack(),
+                component_abilities(&[Ability::Heal]),
             ],
         ),
         (
-            "normal_target",
+            "swordsman",
             [
-                component_agent_dull(),
-                component_strength(1),
+                component_agent_always_hit_strength_1(),
+                component_strength(3),
                 component_blocker(Weight::Normal),
             ]
             .to_vec(),
         ),
+        (
+            "imp",
+            [component_agent_dull(), component_strength(1)].to_vec(),
+        ),
     ]);
-    let target_position_initial = PosHex { q: 0, r: 1 };
-    let target_position_updated = PosHex { q: 0, r: 2 };
     let scenario = Scenario::default()
-        .object(P0, "knockbacker", PosHex { q: 0, r: 0 })
-        .object(P1, "normal_target", target_position_initial);
+        .object(P0, "healer", PosHex { q: 0, r: 0 })
+        .object(P0, "swordsman", PosHex { q: 0, r: 1 })
+        .object(P0, "swordsman", PosHex { q: 1, r: 1 })
+        .object(P1, "imp", PosHex { q: 3, r: 0 });
     let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
+    exec(
         &mut state,
-        command::UseAbility {
-            id: Id(0),
-            pos: target_position_initial,
-            ability: Ability::Knockback,
+        command::Attack {
+            attacker_id: Id(2),
+            target_id: Id(1),
         },
-        &[Event {
-            active_event: event::UseAbility {
-                id: Id(0),
-                pos: target_position_initial,
-                ability: Ability::Knockback,
-            }
-            .into(),
-            actor_ids: vec![Id(1), Id(0)],
-            instant_effects: vec![(
-                Id(1),
-                vec![effect::Knockback {
-                    from: target_position_initial,
-                    to: target_position_updated,
-                    strength: PushStrength(Weight::Normal),
-                }
-                .into()],
-            )],
-            timed_effects: Vec::new(),
-            scheduled_abilities: Vec::new(),
-        }],
     );
-    assert_eq!(state.parts().pos.get(Id(1)).0, target_position_updated);
+    assert_eq!(state.parts().strength.get(Id(1)).strength, Strength(2));
+    let command = command::UseAbility {
+        id: Id(0),
+        pos: PosHex { q: 0, r: 1 },
+        ability: Ability::Heal,
+    }
+    .into();
+    let expected = HealForecast {
+        target_id: Id(1),
+        strength: Strength(1),
+        removed_effects: Vec::new(),
+    };
+    assert_eq!(
+        forecast::forecast(&state, &command),
+        Some(Forecast::Heal(expected))
+    );
 }
 
-#[test]
-fn knockback_normal_vs_heavy() {
+fn lookahead_state(scenario: Scenario) -> State {
+    let attacker = component::Agent {
+        attack_strength: Strength(2),
+        moves: Moves(1),
+        move_points: MovePoints(3),
+        ..agent_always_hit()
+    };
     let prototypes = prototypes(&[
         (
-            "knockbacker",
-            vec![
-                component_agent_always_hit(),
-                component_abilities(&[Ability::Knockback]),
-            ],
+            "imp",
+            [
+                attacker.into(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
         ),
         (
-            "heavy_target",
+            "weak",
             [
                 component_agent_dull(),
                 component_strength(1),
-                component_blocker(Weight::Heavy),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+        (
+            "strong",
+            [
+                component_agent_dull(),
+                component_strength(5),
+                component_blocker(Weight::Normal),
             ]
             .to_vec(),
         ),
     ]);
-    let initial_heavy_position = PosHex { q: 0, r: 1 };
+    let scenario = Scenario {
+        ai: ai::Mode::Lookahead,
+        ..scenario
+    };
+    let mut state = debug_state(prototypes, scenario);
+    exec(&mut state, command::EndTurn);
+    state
+}
+
+#[test]
+fn lookahead_ai_prefers_kills() {
     let scenario = Scenario::default()
-        .object(P0, "knockbacker", PosHex { q: 0, r: 0 })
-        .object(P1, "heavy_target", initial_heavy_position);
+        .object(P1, "imp", PosHex { q: 0, r: 0 })
+        .object(P0, "strong", PosHex { q: 1, r: 0 })
+        .object(P0, "weak", PosHex { q: 0, r: 1 });
+    let state = lookahead_state(scenario);
+    let mut ai = Ai::new(P1, state.map().radius());
+    for _ in 0..10 {
+        match ai.command(&state) {
+            Some(Command::Attack(command)) => {
+                assert_eq!((command.attacker_id, command.target_id), (Id(0), Id(2)));
+            }
+            command => panic!("Expected an attack, got {:?}", command),
+        }
+    }
+}
+
+#[test]
+fn lookahead_ai_moves_in_to_attack() {
+    let scenario = Scenario::default()
+        .object(P1, "imp", PosHex { q: 0, r: 0 })
+        .object(P0, "weak", PosHex { q: 3, r: 0 });
+    let state = lookahead_state(scenario);
+    let mut ai = Ai::new(P1, state.map().radius());
+    match ai.command(&state) {
+        Some(Command::MoveTo(command::MoveTo { id, path })) => {
+            assert_eq!(id, Id(0));
+            let last = *path.tiles().last().unwrap();
+            assert_eq!(map::distance_hex(last, PosHex { q: 3, r: 0 }), Distance(1));
+        }
+        command => panic!("Expected a move, got {:?}", command),
+    }
+}
+
+#[test]
+fn lookahead_ai_sends_the_move_and_the_attack_as_one_batch() {
+    let scenario = Scenario::default()
+        .object(P1, "imp", PosHex { q: 0, r: 0 })
+        .object(P0, "weak", PosHex { q: 3, r: 0 });
+    let mut state = lookahead_state(scenario);
+    let batch = Ai::new(P1, state.map().radius()).batch(&state).unwrap();
+    match batch.commands.as_slice() {
+        [Command::MoveTo(_), Command::Attack(command)] => {
+            assert_eq!((command.attacker_id, command.target_id), (Id(0), Id(1)));
+        }
+        commands => panic!("Expected a move and an attack, got {:?}", commands),
+    }
+    let executed = execute_batch(&mut state, &batch, &mut |_, _, _| {}).unwrap();
+    assert_eq!(executed, 2);
+}
+
+fn personality_prototypes(personality: Personality) -> Prototypes {
+    let imp = component::Agent {
+        attack_strength: Strength(1),
+        moves: Moves(1),
+        move_points: MovePoints(3),
+        ..agent_always_hit()
+    };
+    prototypes(&[
+        (
+            "imp",
+            [
+                imp.into(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+                personality.into(),
+            ]
+            .to_vec(),
+        ),
+        (
+            "weak",
+            [
+                component_agent_dull(),
+                component_strength(2),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+        (
+            "strong",
+            [
+                component_agent_dull(),
+                component_strength(5),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+    ])
+}
+
+#[test]
+fn aggressive_ai_attacks_weakest_enemy() {
+    let prototypes = personality_prototypes(Personality::Aggressive);
+    let scenario = Scenario::default()
+        .object(P1, "imp", PosHex { q: 0, r: 0 })
+        .object(P0, "strong", PosHex { q: 1, r: 0 })
+        .object(P0, "weak", PosHex { q: 0, r: 1 });
     let mut state = debug_state(prototypes, scenario);
-    let knockback_result = try_exec(
+    exec(&mut state, command::EndTurn);
+    let mut ai = Ai::new(P1, state.map().radius());
+    for _ in 0..10 {
+        match ai.command(&state) {
+            Some(Command::Attack(command)) => assert_eq!(command.target_id, Id(2)),
+            command => panic!("Expected an attack, got {:?}", command),
+        }
+    }
+}
+
+#[test]
+fn defender_ai_holds_position() {
+    let command_with_enemy_at = |enemy_pos| {
+        let prototypes = personality_prototypes(Personality::Defender);
+        let scenario = Scenario::default()
+            .object(P1, "imp", PosHex { q: 0, r: 0 })
+            .object(P0, "weak", enemy_pos);
+        let mut state = debug_state(prototypes, scenario);
+        exec(&mut state, command::EndTurn);
+        Ai::new(P1, state.map().radius()).command(&state)
+    };
+    let command = command_with_enemy_at(PosHex { q: 4, r: 0 });
+    assert!(matches!(command, Some(Command::EndTurn(_))));
+    // An enemy that comes close enough makes it fight.
+    let command = command_with_enemy_at(PosHex { q: 3, r: 0 });
+    assert!(matches!(command, Some(Command::MoveTo(_))));
+}
+
+/// An AI fighter that can use its abilities, but has no moves.
+fn ai_caster(abilities: &[Ability]) -> Vec<Component> {
+    let agent = component::Agent {
+        attacks: Attacks(2),
+        ..agent_always_hit()
+    };
+    [
+        agent.into(),
+        component_strength(3),
+        component_blocker(Weight::Normal),
+        component_abilities(abilities),
+    ]
+    .to_vec()
+}
+
+fn ai_test_prototypes(caster: Vec<Component>) -> Prototypes {
+    prototypes(&[
+        ("caster", caster),
+        (
+            "imp",
+            [
+                component_agent_always_hit_strength_1(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+        (
+            "enemy",
+            [
+                component_agent_always_hit_strength_1(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
+        ),
+        ("boulder", [component_blocker(Weight::Normal)].to_vec()),
+        (
+            "fire",
+            [component_passive_abilities(&[PassiveAbility::Burn])].to_vec(),
+        ),
+    ])
+}
+
+/// Starts the AI's turn and asks it for a command.
+fn ai_command(state: &mut State) -> Option<Command> {
+    if state.player_id() == P0 {
+        exec(state, command::EndTurn);
+    }
+    Ai::new(P1, state.map().radius()).command(state)
+}
+
+/// Returns the target tile of the expected ability.
+fn expect_ability(command: Option<Command>, expected: Ability) -> PosHex {
+    match command {
+        Some(Command::UseAbility(command)) if command.ability == expected => command.pos,
+        command => panic!("Expected {:?}, got {:?}", expected, command),
+    }
+}
+
+#[test]
+fn ai_heals_wounded_allies() {
+    let ability = Ability::Heal;
+    for tier in 1..=3 {
+        let mut caster = ai_caster(&[ability]);
+        let tiers = vec![(ability, Tier(tier))].into_iter().collect();
+        caster.push(component::AbilityTiers(tiers).into());
+        let prototypes = ai_test_prototypes(caster);
+        let scenario = Scenario::default()
+            .object(P1, "caster", PosHex { q: 0, r: 0 })
+            .object(P1, "imp", PosHex { q: 1, r: 0 })
+            .object(P1, "imp", PosHex { q: 0, r: 1 })
+            .object(P0, "enemy", PosHex { q: 4, r: 0 });
+        let mut state = debug_state(prototypes, scenario);
+        exec(&mut state, command::EndTurn);
+        exec(
+            &mut state,
+            command::Attack {
+                attacker_id: Id(1),
+                target_id: Id(2),
+            },
+        );
+        let pos = expect_ability(ai_command(&mut state), ability);
+        assert_eq!(pos, PosHex { q: 0, r: 1 });
+    }
+}
+
+#[test]
+fn ai_barricades_wounded_allies_from_enemies() {
+    let ability = Ability::Barricade;
+    let prototypes = ai_test_prototypes(ai_caster(&[ability]));
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 1, r: 0 })
+        .object(P1, "imp", PosHex { q: 2, r: 0 })
+        .object(P0, "enemy", PosHex { q: 0, r: 2 });
+    let mut state = debug_state(prototypes, scenario);
+    exec(&mut state, command::EndTurn);
+    exec(
         &mut state,
-        command::UseAbility {
-            id: Id(0),
-            pos: initial_heavy_position,
-            ability: Ability::Knockback,
+        command::Attack {
+            attacker_id: Id(2),
+            target_id: Id(1),
         },
     );
-    assert_eq!(knockback_result, Err(check::Error::NotEnoughStrength));
-    assert_eq!(state.parts().pos.get(Id(1)).0, initial_heavy_position);
+    let pos = expect_ability(ai_command(&mut state), ability);
+    assert_eq!(pos, PosHex { q: 0, r: 1 });
+}
+
+#[test]
+fn ai_bloodlusts_allies_near_enemies() {
+    let prototypes = ai_test_prototypes(ai_caster(&[Ability::Bloodlust]));
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P1, "imp", PosHex { q: 0, r: 1 })
+        .object(P0, "enemy", PosHex { q: 2, r: 1 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Bloodlust);
+    assert_eq!(pos, PosHex { q: 0, r: 1 });
+}
+
+#[test]
+fn ai_dashes_away_when_scared() {
+    let mut caster = ai_caster(&[Ability::Dash]);
+    caster.push(Personality::Cowardly.into());
+    let prototypes = ai_test_prototypes(caster);
+    let enemy_pos = PosHex { q: 1, r: 0 };
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", enemy_pos);
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Dash);
+    assert!(map::distance_hex(pos, enemy_pos) > Distance(1));
+}
+
+#[test]
+fn ai_clubs_dangerous_enemies() {
+    let prototypes = ai_test_prototypes(ai_caster(&[Ability::Club]));
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Club);
+    assert_eq!(pos, PosHex { q: 1, r: 0 });
+}
+
+#[test]
+fn ai_rages_before_attacking() {
+    let caster = [
+        component_agent_always_hit_strength_1(),
+        component_strength(3),
+        component_blocker(Weight::Normal),
+        component_abilities(&[Ability::Rage]),
+    ]
+    .to_vec();
+    let prototypes = ai_test_prototypes(caster);
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Rage);
+    assert_eq!(pos, PosHex { q: 0, r: 0 });
+}
+
+#[test]
+fn ai_jumps_to_enemies() {
+    for ability in [Ability::Jump, Ability::LongJump] {
+        let prototypes = ai_test_prototypes(ai_caster(&[ability]));
+        let enemy_pos = PosHex { q: 3, r: 0 };
+        let scenario = Scenario::default()
+            .object(P1, "caster", PosHex { q: 0, r: 0 })
+            .object(P0, "enemy", enemy_pos);
+        let mut state = debug_state(prototypes, scenario);
+        let pos = expect_ability(ai_command(&mut state), ability);
+        assert_eq!(map::distance_hex(pos, enemy_pos), Distance(1));
+    }
+}
+
+#[test]
+fn ai_poisons_enemies() {
+    let prototypes = ai_test_prototypes(ai_caster(&[Ability::Poison]));
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", PosHex { q: 2, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Poison);
+    assert_eq!(pos, PosHex { q: 2, r: 0 });
+}
+
+#[test]
+fn ai_throws_bombs_at_groups_of_enemies() {
+    let abilities = [Ability::Bomb, Ability::BombFire, Ability::BombPoison];
+    for ability in abilities {
+        let prototypes = ai_test_prototypes(ai_caster(&[ability]));
+        let scenario = Scenario::default()
+            .object(P1, "caster", PosHex { q: 0, r: 0 })
+            .object(P0, "enemy", PosHex { q: 2, r: 0 })
+            .object(P0, "enemy", PosHex { q: 3, r: 0 });
+        let mut state = debug_state(prototypes, scenario);
+        let pos = expect_ability(ai_command(&mut state), ability);
+        let best = [PosHex { q: 3, r: -1 }, PosHex { q: 2, r: 1 }];
+        assert!(best.contains(&pos), "{:?}: {:?}", ability, pos);
+    }
+}
+
+#[test]
+fn ai_rolls_bombs_along_straight_lines() {
+    let ability = Ability::BombDemonic;
+    let prototypes = ai_test_prototypes(ai_caster(&[ability]));
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", PosHex { q: 3, r: 0 })
+        .object(P0, "enemy", PosHex { q: 2, r: 1 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), ability);
+    let landing_pos = state::rolled_bomb_pos(&state, PosHex { q: 0, r: 0 }, pos);
+    assert_eq!(landing_pos, Some(PosHex { q: 2, r: 0 }));
+}
+
+#[test]
+fn ai_pushes_enemies_into_hazards() {
+    let fire_pos = PosHex { q: 3, r: 0 };
+    // A push bomb thrown behind the enemy.
+    let prototypes = ai_test_prototypes(ai_caster(&[Ability::BombPush]));
+    let scenario = Scenario::default()
+        .object_without_owner("fire", fire_pos)
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", PosHex { q: 2, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::BombPush);
+    assert_eq!(pos, PosHex { q: 1, r: 0 });
+    // A knockback of an adjacent enemy.
+    let prototypes = ai_test_prototypes(ai_caster(&[Ability::Knockback]));
+    let scenario = Scenario::default()
+        .object_without_owner("fire", PosHex { q: 2, r: 0 })
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", PosHex { q: 1, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Knockback);
+    assert_eq!(pos, PosHex { q: 1, r: 0 });
+    // A knockback of an adjacent enemy into the water.
+    let prototypes = ai_test_prototypes(ai_caster(&[Ability::Knockback]));
+    let mut scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", PosHex { q: 1, r: 0 });
+    scenario
+        .tiles
+        .insert(PosHex { q: 2, r: 0 }, TileType::Water);
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Knockback);
+    assert_eq!(pos, PosHex { q: 1, r: 0 });
+    // A boulder shoved into an enemy.
+    let prototypes = ai_test_prototypes(ai_caster(&[Ability::Shove]));
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object_without_owner("boulder", PosHex { q: 1, r: 0 })
+        .object(P0, "enemy", PosHex { q: 2, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Shove);
+    assert_eq!(pos, PosHex { q: 1, r: 0 });
 }
 
 #[test]
-fn heavy_strike_flyoff_normal_vs_normal() {
+fn summoners_have_a_limit() {
+    let summoner = vec![
+        component_agent_one_attack(),
+        component_strength(1),
+        component_abilities(&[Ability::Summon]),
+        component::Summoner {
+            count: 2,
+            limit: Some(2),
+        }
+        .into(),
+    ];
+    let imp = || vec![component_agent_dull(), component_strength(1)];
     let prototypes = prototypes(&[
-        (
-            "heavy_impacter",
-            vec![
-                component_agent_always_hit(),
-                component_strength(1),
-                component_passive_abilities(&[PassiveAbility::HeavyImpact]),
-            ],
-        ),
-        (
-            "normal_target",
-            vec![
-                component_agent_dull(),
-                component_strength(1),
-                component_blocker(Weight::Normal),
-            ],
-        ),
+        ("summoner", summoner),
+        ("imp", imp()),
+        ("toxic_imp", imp()),
+        ("imp_bomber", imp()),
     ]);
-    let position_attacker = PosHex { q: 0, r: 0 };
-    let position_target_initial = PosHex { q: 0, r: 1 };
-    let position_target_updated = PosHex { q: 0, r: 2 };
+    let summoner_pos = PosHex { q: 0, r: 0 };
     let scenario = Scenario::default()
-        .object(P0, "heavy_impacter", position_attacker)
-        .object(P1, "normal_target", position_target_initial);
+        .object(P0, "summoner", summoner_pos)
+        .object(P1, "imp", PosHex { q: 0, r: 4 });
     let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
-        &mut state,
-        command::Attack {
-            attacker_id: Id(0),
-            target_id: Id(1),
-        },
-        &[Event {
-            active_event: event::Attack {
-                attacker_id: Id(0),
-                target_id: Id(1),
-                mode: AttackMode::Active,
-                weapon_type: WeaponType::Slash,
-            }
-            .into(),
-            actor_ids: vec![Id(0)],
-            instant_effects: vec![(
-                Id(1),
-                vec![
-                    effect::Wound {
-                        damage: Strength(0),
-                        armor_break: Strength(0),
-                        attacker_pos: Some(position_attacker),
-                    }
-                    .into(),
-                    effect::FlyOff {
-                        from: position_target_initial,
-                        to: position_target_updated,
-                        strength: PushStrength(Weight::Normal),
-                    }
-                    .into(),
-                ],
-            )],
-            timed_effects: Vec::new(),
-            scheduled_abilities: Vec::new(),
-        }],
+    let summon = command::UseAbility {
+        id: Id(0),
+        pos: summoner_pos,
+        ability: Ability::Summon,
+    };
+    exec(&mut state, summon.clone());
+    assert_eq!(state::summoned_agent_ids(&state, Id(0)).len(), 2);
+    // Wait for the summoner's next turn when the ability is ready again.
+    loop {
+        exec(&mut state, command::EndTurn);
+        exec(&mut state, command::EndTurn);
+        let result = check::check(&state, &summon.clone().into());
+        if result != Err(check::Error::AbilityIsNotReady) {
+            break;
+        }
+    }
+    assert_eq!(
+        try_exec(&mut state, summon),
+        Err(check::Error::SummonLimitReached)
     );
-    assert_eq!(state.parts().pos.get(Id(1)).0, position_target_updated);
 }
 
 #[test]
-fn heavy_strike_flyoff_normal_vs_heavy() {
+fn ai_summons() {
+    let mut caster = ai_caster(&[Ability::Summon]);
+    caster.push(
+        component::Summoner {
+            count: 2,
+            limit: None,
+        }
+        .into(),
+    );
+    let prototypes = ai_test_prototypes(caster);
+    let scenario = Scenario::default()
+        .object(P1, "caster", PosHex { q: 0, r: 0 })
+        .object(P0, "enemy", PosHex { q: 4, r: 0 });
+    let mut state = debug_state(prototypes, scenario);
+    let pos = expect_ability(ai_command(&mut state), Ability::Summon);
+    assert_eq!(pos, PosHex { q: 0, r: 0 });
+}
+
+#[test]
+fn ai_steps_away_from_bombs() {
+    let walker = component::Agent {
+        moves: Moves(1),
+        move_points: MovePoints(3),
+        ..agent_always_hit()
+    };
+    let bomb = component::Schedule {
+        planned: vec![PlannedAbility {
+            rounds: 3.into(),
+            phase: Phase::from_player_id(P0),
+            ability: Ability::ExplodeDamage,
+        }],
+    };
     let prototypes = prototypes(&[
         (
-            "heavy_impacter",
-            vec![
-                component_agent_always_hit(),
-                component_strength(1),
-                component_passive_abilities(&[PassiveAbility::HeavyImpact]),
-            ],
+            "walker",
+            [
+                walker.into(),
+                component_strength(3),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
         ),
         (
-            "heavy_target",
-            vec![
+            "bomb",
+            [component_blocker(Weight::Normal), bomb.into()].to_vec(),
+        ),
+        (
+            "enemy",
+            [
                 component_agent_dull(),
-                component_strength(1),
-                component_blocker(Weight::Heavy),
-            ],
+                component_strength(3),
+                component_blocker(Weight::Normal),
+            ]
+            .to_vec(),
         ),
     ]);
-    let position_attacker = PosHex { q: 0, r: 0 };
-    let position_target = PosHex { q: 0, r: 1 };
+    let bomb_pos = PosHex { q: 1, r: 0 };
     let scenario = Scenario::default()
-        .object(P0, "heavy_impacter", position_attacker)
-        .object(P1, "heavy_target", position_target);
+        .object(P1, "walker", PosHex { q: 0, r: 0 })
+        .object_without_owner("bomb", bomb_pos)
+        .object(P0, "enemy", PosHex { q: -4, r: 0 });
     let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
-        &mut state,
-        command::Attack {
-            attacker_id: Id(0),
-            target_id: Id(1),
+    match ai_command(&mut state) {
+        Some(Command::MoveTo(command)) => {
+            let last = *command.path.tiles().last().unwrap();
+            assert!(map::distance_hex(last, bomb_pos) > Distance(1));
+        }
+        command => panic!("Expected a move, got {:?}", command),
+    }
+}
+
+#[test]
+fn select_scenarios_by_tags() {
+    let scenario = |size, difficulty, mechanics: &[Mechanic]| Scenario {
+        tags: Tags {
+            size,
+            difficulty,
+            theme: None,
+            mechanics: mechanics.to_vec(),
         },
-        &[Event {
-            active_event: event::Attack {
-                attacker_id: Id(0),
-                target_id: Id(1),
-                mode: AttackMode::Active,
-                weapon_type: WeaponType::Slash,
-            }
-            .into(),
-            actor_ids: vec![Id(0)],
-            instant_effects: vec![(
-                Id(1),
-                vec![
-                    effect::Wound {
-                        damage: Strength(0),
-                        armor_break: Strength(0),
-                        attacker_pos: Some(position_attacker),
-                    }
-                    .into(),
-                    effect::FlyOff {
-                        from: position_target,
-                        to: position_target,
-                        strength: PushStrength(Weight::Normal),
-                    }
-                    .into(),
-                ],
-            )],
-            timed_effects: Vec::new(),
-            scheduled_abilities: Vec::new(),
-        }],
-    );
-    assert_eq!(state.parts().pos.get(Id(1)).0, position_target);
+        ..Scenario::default()
+    };
+    let scenarios = vec![
+        scenario(Size::Small, Difficulty::Easy, &[]),
+        scenario(Size::Medium, Difficulty::Hard, &[Mechanic::Traps]),
+        scenario(
+            Size::Large,
+            Difficulty::Hard,
+            &[Mechanic::Traps, Mechanic::Poison],
+        ),
+    ];
+    let sizes = |query: &Query| -> Vec<Size> {
+        scenario::select(&scenarios, query)
+            .iter()
+            .map(|scenario| scenario.tags.size)
+            .collect()
+    };
+    assert_eq!(sizes(&Query::default()).len(), 3);
+    let hard = Query {
+        difficulty: Some(Difficulty::Hard),
+        ..Query::default()
+    };
+    assert_eq!(sizes(&hard), vec![Size::Medium, Size::Large]);
+    let hard_without_poison = Query {
+        excluded_mechanics: vec![Mechanic::Poison],
+        ..hard.clone()
+    };
+    assert_eq!(sizes(&hard_without_poison), vec![Size::Medium]);
+    let traps = Query {
+        required_mechanics: vec![Mechanic::Traps, Mechanic::Poison],
+        ..Query::default()
+    };
+    assert_eq!(sizes(&traps), vec![Size::Large]);
 }
 
 #[test]
-fn heavy_strike_flyoff_on_spikes() {
+fn codex_counters() {
+    let knight = component::Agent {
+        attack_strength: Strength(3),
+        ..agent_always_hit()
+    };
+    let archer = component::Agent {
+        attack_strength: Strength(2),
+        attack_accuracy: Accuracy(5),
+        ..agent_always_hit()
+    };
+    let peasant = component::Agent {
+        attack_strength: Strength(1),
+        ..agent_always_hit()
+    };
+    let armor = component::Armor { armor: Strength(1) };
     let prototypes = prototypes(&[
+        ("peasant", vec![peasant.into(), component_strength(1)]),
+        ("knight", vec![knight.into(), component_strength(3)]),
+        ("archer", vec![archer.into(), component_strength(2)]),
         (
-            "heavy_impacter",
-            vec![
-                component_agent_always_hit(),
-                component_strength(1),
-                component_passive_abilities(&[PassiveAbility::HeavyImpact]),
-            ],
-        ),
-        (
-            "normal_target",
+            "imp",
             vec![
                 component_agent_dull(),
-                component_strength(2),
-                component_blocker(Weight::Normal),
+                component_strength(3),
+                Component::Armor(armor),
             ],
         ),
-        (
-            "spike_trap",
-            vec![component_passive_abilities(&[PassiveAbility::SpikeTrap])],
-        ),
+        ("boulder", vec![component_strength(1)]),
     ]);
-    let position_attacker = PosHex { q: 0, r: 0 };
-    let position_target_initial = PosHex { q: 0, r: 1 };
-    let position_target_updated = PosHex { q: 0, r: 2 };
-    let position_spikes = position_target_updated;
-    let scenario = Scenario::default()
-        .object(P0, "heavy_impacter", position_attacker)
-        .object(P1, "normal_target", position_target_initial)
-        .object_without_owner("spike_trap", position_spikes);
-    let mut state = debug_state(prototypes, scenario);
-    exec_and_check(
-        &mut state,
-        command::Attack {
-            attacker_id: Id(0),
-            target_id: Id(1),
-        },
-        &[
-            Event {
-                active_event: event::Attack {
-                    attacker_id: Id(0),
-                    target_id: Id(1),
-                    mode: AttackMode::Active,
-                    weapon_type: WeaponType::Slash,
-                }
-                .into(),
-                actor_ids: vec![Id(0)],
-                instant_effects: vec![(
-                    Id(1),
-                    vec![
-                        effect::Wound {
-                            damage: Strength(0),
-                            armor_break: Strength(0),
-                            attacker_pos: Some(position_attacker),
-                        }
-                        .into(),
-                        effect::FlyOff {
-                            from: position_target_initial,
-                            to: position_target_updated,
-                            strength: PushStrength(Weight::Normal),
-                        }
-                        .into(),
-                    ],
-                )],
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
-            },
-            Event {
-                active_event: event::UsePassiveAbility {
-                    id: Id(2),
-                    pos: position_spikes,
-                    ability: PassiveAbility::SpikeTrap,
-                }
-                .into(),
-                actor_ids: Vec::new(),
-                instant_effects: vec![(
-                    Id(1),
-                    vec![effect::Wound {
-                        damage: Strength(1),
-                        armor_break: Strength(0),
-                        attacker_pos: None,
-                    }
-                    .into()],
-                )],
-                timed_effects: Vec::new(),
-                scheduled_abilities: Vec::new(),
-            },
-        ],
+    let names: Vec<ObjType> = ["archer", "imp", "knight", "peasant"]
+        .iter()
+        .map(|&name| name.into())
+        .collect();
+    assert_eq!(codex::agent_types(&prototypes), names);
+    let imp = "imp".into();
+    assert_eq!(
+        codex::expected_damage(&prototypes, &"knight".into(), &imp),
+        2.0
     );
-    assert_eq!(state.parts().pos.get(Id(1)).0, position_target_updated);
+    // Hits with the rolls 0..=5 take 1 strength after the armor, the roll 6 takes none.
+    assert_eq!(
+        codex::expected_damage(&prototypes, &"archer".into(), &imp),
+        6.0 / 11.0
+    );
+    assert_eq!(
+        codex::expected_damage(&prototypes, &"peasant".into(), &imp),
+        0.0
+    );
+    let counters: Vec<ObjType> = vec!["knight".into(), "archer".into()];
+    assert_eq!(codex::counters(&prototypes, &imp), counters);
+}
+
+/// The command that the tutorial step waits for.
+fn tutorial_command(state: &State, action: &tutorial::Action) -> Command {
+    let agent_id = |typename: &ObjType| {
+        let parts = state.parts();
+        parts
+            .agent
+            .ids()
+            .find(|&id| &parts.meta.get(id).name == typename)
+            .unwrap()
+    };
+    match action {
+        tutorial::Action::MoveTo { agent, pos } => {
+            let id = agent_id(agent);
+            let mut pathfinder = Pathfinder::new(state.map().radius());
+            pathfinder.fill_map(state, id);
+            let path = pathfinder.path(*pos).unwrap();
+            command::MoveTo { id, path }.into()
+        }
+        tutorial::Action::Attack { agent, target } => command::Attack {
+            attacker_id: agent_id(agent),
+            target_id: state::blocker_id_at(state, *target),
+        }
+        .into(),
+        &tutorial::Action::UseAbility {
+            ref agent,
+            ability,
+            pos,
+        } => command::UseAbility {
+            id: agent_id(agent),
+            pos,
+            ability,
+        }
+        .into(),
+        tutorial::Action::EndTurn => command::EndTurn.into(),
+    }
+}
+
+#[test]
+fn tutorial_can_be_completed() {
+    let tutorial: tutorial::Tutorial =
+        ron::de::from_str(include_str!("../../../assets/tutorial.ron")).unwrap();
+    let (prototypes, stack_rules) = (game_prototypes(), game_stack_rules());
+    let mut state = State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        tutorial.scenario,
+        &mut |_, _, _| {},
+    );
+    let mut progress = tutorial::Progress::new(tutorial.steps.clone());
+    for step in &tutorial.steps {
+        let command = tutorial_command(&state, &step.action);
+        assert_eq!(check(&state, &command), Ok(()), "{:?}", step.action);
+        // Nothing else is allowed, not even the next step.
+        assert!(!progress.try_command(&state, &command::Retreat.into()));
+        assert!(progress.try_command(&state, &command));
+        execute(&mut state, &command, &mut |_, _, _| {}).unwrap();
+    }
+    assert_eq!(progress.step(), None);
+    assert!(progress.try_command(&state, &command::Retreat.into()));
 }
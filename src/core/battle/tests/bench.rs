@@ -0,0 +1,126 @@
+//! Rough time budgets of the hot spots, so the big performance regressions fail.
+//!
+//! The budgets are about ten times the timings of a release build on a laptop.
+//! They're too slow for the debug builds, so they're ignored by default:
+//! `cargo test --release bench -- --ignored`
+//!
+//! The timings themselves are printed by `cargo bench`, see `benches/battle.rs`.
+
+use std::time::{Duration, Instant};
+
+use super::{game_ability_definitions, game_prototypes, game_stack_rules};
+use crate::core::{
+    battle::{
+        ai::{self, Ai},
+        command::Command,
+        execute::execute,
+        movement::Pathfinder,
+        scenario::{Line, ObjectsGroup, Scenario},
+        state, PlayerId, State,
+    },
+    map::{self, PosHex},
+};
+
+fn assert_average_time(name: &str, budget: Duration, iterations: u32, mut f: impl FnMut()) {
+    f(); // warm up
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let average: Duration = start.elapsed() / iterations;
+    assert!(
+        average < budget,
+        "{}: {:?} per iteration, the budget is {:?}",
+        name,
+        average,
+        budget
+    );
+}
+
+fn group(owner: Option<PlayerId>, typename: &str, count: i32) -> ObjectsGroup {
+    ObjectsGroup {
+        owner,
+        typename: typename.into(),
+        line: owner.map(|_| Line::Any),
+        count,
+        extra_components: Vec::new(),
+    }
+}
+
+fn crowded_state(map_radius: i32, agents_per_player: i32, ai: ai::Mode) -> State {
+    let mut randomized_objects = vec![
+        group(None, "boulder", map_radius * 2),
+        group(None, "spike_trap", map_radius),
+    ];
+    for (player_id, typename) in [(PlayerId(0), "swordsman"), (PlayerId(1), "imp")] {
+        randomized_objects.push(group(Some(player_id), typename, agents_per_player));
+    }
+    let scenario = Scenario {
+        map_radius: map::Distance(map_radius),
+        rocky_tiles_count: map_radius * 3,
+        randomized_objects,
+        ai,
+        seed: Some(0),
+        ..Scenario::default()
+    };
+    let (prototypes, stack_rules) = (game_prototypes(), game_stack_rules());
+    State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        scenario,
+        &mut |_, _, _| {},
+    )
+}
+
+#[test]
+#[ignore]
+fn bench_pathfinding_large_map() {
+    let state = crowded_state(20, 30, ai::Mode::default());
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    let id = state::players_agent_ids(&state, PlayerId(0))[0];
+    let far_corner = PosHex { q: 20, r: -20 };
+    let budget = Duration::from_millis(2);
+    assert_average_time("pathfinding on a large map", budget, 200, || {
+        pathfinder.fill_map(&state, id);
+        let _ = pathfinder.path(far_corner);
+    });
+}
+
+/// Plays the current player's turn till the AI ends it.
+fn play_ai_turn(state: &mut State, ai: &mut Ai) {
+    for _ in 0..100 {
+        let command = match ai.command(state) {
+            Some(command) => command,
+            None => return,
+        };
+        execute(state, &command, &mut |_, _, _| {}).expect("Bad AI command");
+        if let Command::EndTurn(_) = command {
+            return;
+        }
+    }
+}
+
+fn bench_ai_turn(mode: ai::Mode, budget: Duration) {
+    let mut state = crowded_state(6, 12, mode);
+    state.set_player_id(PlayerId(1));
+    let radius = state.map().radius();
+    let name = format!("{} AI turn on a crowded board", mode.title());
+    assert_average_time(&name, budget, 10, || {
+        let mut state = state.clone();
+        let mut ai = Ai::new(PlayerId(1), radius);
+        play_ai_turn(&mut state, &mut ai);
+    });
+}
+
+#[test]
+#[ignore]
+fn bench_greedy_ai_turn() {
+    bench_ai_turn(ai::Mode::Greedy, Duration::from_millis(250));
+}
+
+#[test]
+#[ignore]
+fn bench_lookahead_ai_turn() {
+    bench_ai_turn(ai::Mode::Lookahead, Duration::from_millis(1500));
+}
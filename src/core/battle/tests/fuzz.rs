@@ -0,0 +1,193 @@
+//! Random command streams against random battles of the real game objects.
+//!
+//! Every failure reports the seed, so it can be replayed with `fuzz_battle`.
+
+use std::panic;
+
+use rand::{seq::SliceRandom, Rng};
+
+use super::{game_ability_definitions, game_prototypes, game_stack_rules};
+use crate::core::{
+    battle::{
+        check,
+        command::{self, Command, JokerUse},
+        component::ObjType,
+        event::Event,
+        execute::execute,
+        history,
+        movement::Pathfinder,
+        rng::BattleRng,
+        scenario::{Line, ObjectsGroup, Scenario, Weather},
+        state, Id, PlayerId, State,
+    },
+    map::{self, Dir, PosHex},
+};
+
+const SEEDS: u64 = 25;
+const COMMANDS_PER_BATTLE: i32 = 300;
+
+const AGENTS: &[&str] = &[
+    "swordsman",
+    "heavy_swordsman",
+    "alchemist",
+    "healer",
+    "firer",
+    "hammerman",
+    "spearman",
+    "imp",
+    "toxic_imp",
+    "imp_bomber",
+    "imp_summoner",
+    "imp_necromancer",
+];
+
+const NEUTRALS: &[&str] = &["boulder", "spike_trap", "fire", "poison_cloud", "torch"];
+
+fn group(rng: &mut BattleRng, owner: Option<PlayerId>, typenames: &[&str]) -> ObjectsGroup {
+    let line = [Line::Front, Line::Middle, Line::Back].choose(rng).copied();
+    ObjectsGroup {
+        owner,
+        typename: ObjType::from(*typenames.choose(rng).unwrap()),
+        line: owner.and(line),
+        count: rng.gen_range(1..3),
+        extra_components: Vec::new(),
+    }
+}
+
+fn random_scenario(rng: &mut BattleRng, seed: u64) -> Scenario {
+    let mut randomized_objects = Vec::new();
+    for _ in 0..rng.gen_range(0..4) {
+        randomized_objects.push(group(rng, None, NEUTRALS));
+    }
+    for player_id in [PlayerId(0), PlayerId(1)] {
+        for _ in 0..rng.gen_range(1..4) {
+            randomized_objects.push(group(rng, Some(player_id), AGENTS));
+        }
+    }
+    let weathers = [
+        Weather::Clear,
+        Weather::Rain,
+        Weather::Fog,
+        Weather::Wind(Dir::from_int(rng.gen_range(0..6))),
+    ];
+    Scenario {
+        map_radius: map::Distance(rng.gen_range(4..6)),
+        rocky_tiles_count: rng.gen_range(0..8),
+        randomized_objects,
+        weather: *weathers.choose(rng).unwrap(),
+        seed: Some(seed),
+        ..Scenario::default()
+    }
+}
+
+fn random_pos(rng: &mut BattleRng, state: &State) -> PosHex {
+    let tiles: Vec<_> = state.map().iter().collect();
+    *tiles.choose(rng).unwrap()
+}
+
+fn random_command(rng: &mut BattleRng, state: &State, pathfinder: &mut Pathfinder) -> Command {
+    let parts = state.parts();
+    // Other players' agents can't do anything, so they're rarely picked.
+    let agent_ids = if rng.gen_range(0..10) == 0 {
+        parts.agent.ids_collected()
+    } else {
+        state::players_agent_ids(state, state.player_id())
+    };
+    let id: Id = match agent_ids.choose(rng) {
+        Some(&id) => id,
+        None => return command::EndTurn.into(),
+    };
+    match rng.gen_range(0..10) {
+        0 => command::EndTurn.into(),
+        1..=2 => {
+            let pos = parts.pos.get(id).0;
+            let distance = |&id: &Id| map::distance_hex(pos, parts.pos.get(id).0);
+            let mut target_ids = parts.strength.ids_collected();
+            target_ids.sort_by_key(distance);
+            target_ids.truncate(4);
+            command::Attack {
+                attacker_id: id,
+                target_id: *target_ids.choose(rng).unwrap(),
+            }
+            .into()
+        }
+        3..=5 => {
+            pathfinder.fill_map(state, id);
+            match pathfinder.path(random_pos(rng, state)) {
+                Some(path) => command::MoveTo { id, path }.into(),
+                None => command::EndTurn.into(),
+            }
+        }
+        6..=8 => {
+            let abilities = match parts.abilities.get_opt(id) {
+                Some(abilities) => abilities.0.clone(),
+                None => return command::EndTurn.into(),
+            };
+            let ability = match abilities.choose(rng) {
+                Some(ability) => ability.ability,
+                None => return command::EndTurn.into(),
+            };
+            let agent_pos = parts.pos.get(id).0;
+            let pos = match rng.gen_range(0..3) {
+                0 => random_pos(rng, state),
+                _ => {
+                    let tiles: Vec<_> = state
+                        .map()
+                        .iter()
+                        .filter(|&pos| map::distance_hex(agent_pos, pos).0 <= 3)
+                        .collect();
+                    *tiles.choose(rng).unwrap()
+                }
+            };
+            command::UseAbility { id, pos, ability }.into()
+        }
+        _ => {
+            let into = *[JokerUse::Move, JokerUse::Attack].choose(rng).unwrap();
+            command::ConvertJoker { id, into }.into()
+        }
+    }
+}
+
+fn fuzz_battle(seed: u64) {
+    let mut rng = BattleRng::new(seed);
+    let scenario = random_scenario(&mut rng, seed);
+    let radius = scenario.map_radius;
+    let prototypes = game_prototypes();
+    let stack_rules = game_stack_rules();
+    let mut state = State::new(
+        prototypes,
+        stack_rules,
+        game_ability_definitions(),
+        scenario,
+        &mut |_, _, _| {},
+    );
+    let mut pathfinder = Pathfinder::new(radius);
+    for _ in 0..COMMANDS_PER_BATTLE {
+        if state.battle_result().is_some() {
+            break;
+        }
+        let command = random_command(&mut rng, &state, &mut pathfinder);
+        if check(&state, &command).is_err() {
+            continue;
+        }
+        execute(&mut state, &command, &mut |_, _, _| {}).expect("Checked command has failed");
+        let invariants = state::check_invariants(&state);
+        assert_eq!(invariants, Ok(()), "{:?}", command);
+    }
+    // Every random battle must be replayed exactly from its history.
+    let commands = state.history().commands_count();
+    let events = |state: &State| -> Vec<Event> {
+        let turns = state.history().turns();
+        turns.flat_map(|turn| turn.events.clone()).collect()
+    };
+    let replayed = history::replay(&state, commands, &mut |_, _, _| {});
+    assert_eq!(events(&replayed), events(&state));
+}
+
+#[test]
+fn fuzz_random_commands() {
+    for seed in 0..SEEDS {
+        let result = panic::catch_unwind(|| fuzz_battle(seed));
+        assert!(result.is_ok(), "Fuzzing has failed with seed {}", seed);
+    }
+}
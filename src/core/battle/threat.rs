@@ -0,0 +1,71 @@
+//! The "danger zone": the tiles that the enemies can attack on their next turn.
+
+use crate::core::{
+    battle::{effect, movement::Pathfinder, state, Id, PlayerId, State},
+    map::{self, HexMap, PosHex},
+};
+
+/// How many moves the agent can make on its next turn and still attack,
+/// `None` if it can't attack at all.
+///
+/// A joker is either a move or an attack, so it's a move only
+/// if there's a normal attack left.
+fn moves_before_attack(state: &State, id: Id) -> Option<i32> {
+    let parts = state.parts();
+    if let Some(effects) = parts.effects.get_opt(id) {
+        // A stunned agent loses all its actions at the beginning of the turn.
+        if effects.0.iter().any(|e| e.effect == effect::Lasting::Stun) {
+            return None;
+        }
+    }
+    let agent = parts.agent.get(id);
+    let moves = agent.base_moves.0;
+    let jokers = agent.base_jokers.0;
+    if agent.base_attacks.0 > 0 {
+        Some(moves + jokers)
+    } else if jokers > 0 {
+        Some(moves + jokers - 1)
+    } else {
+        None
+    }
+}
+
+/// The tiles that the agent can attack on its next turn.
+fn agent_threat(state: &State, pathfinder: &mut Pathfinder, id: Id, tiles: &mut HexMap<bool>) {
+    let moves = match moves_before_attack(state, id) {
+        Some(moves) => moves,
+        None => return,
+    };
+    let move_points = state.parts().agent.get(id).move_points.0 * moves;
+    pathfinder.fill_map(state, id);
+    let agent_pos = state.parts().pos.get(id).0;
+    let reachable: Vec<PosHex> = state
+        .map()
+        .iter()
+        .filter(|&pos| pathfinder.map().tile(pos).cost().0 <= move_points)
+        .filter(|&pos| pos == agent_pos || !state::is_tile_blocked(state, pos))
+        .collect();
+    for &from in &reachable {
+        let distance = state::attack_distance_from(state, id, from);
+        for pos in state.map().iter() {
+            if map::distance_hex(from, pos) <= distance {
+                tiles.set_tile(pos, true);
+            }
+        }
+    }
+}
+
+/// Every tile that any enemy of the player could attack on its next turn:
+/// all the tiles in its attack distance from the tiles it can walk to.
+///
+/// Everyone else is assumed to stay where they are, so this is an estimate:
+/// the paths that are blocked now are considered blocked on the enemy's turn too.
+pub fn threatened_tiles(state: &State, player_id: PlayerId) -> HexMap<bool> {
+    let mut tiles = HexMap::new(state.map().radius());
+    let mut pathfinder = Pathfinder::new(state.map().radius());
+    // The player knows nothing about the hidden enemies.
+    for id in state::visible_enemy_agent_ids(state, player_id) {
+        agent_threat(state, &mut pathfinder, id, &mut tiles);
+    }
+    tiles
+}
@@ -0,0 +1,131 @@
+//! Scripted lessons: a battle where every step allows only one action of the player.
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    battle::{
+        ability::Ability,
+        command::Command,
+        component::ObjType,
+        scenario::{Remark, Scenario},
+        Id, State,
+    },
+    map::PosHex,
+};
+
+/// The only command that the player can make during a step.
+///
+/// The agents are referred to by their types,
+/// so a tutorial shouldn't give the player two agents of the same type.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    MoveTo {
+        agent: ObjType,
+        pos: PosHex,
+    },
+
+    /// Attack the object that stands at the tile.
+    Attack {
+        agent: ObjType,
+        target: PosHex,
+    },
+
+    UseAbility {
+        agent: ObjType,
+        ability: Ability,
+        pos: PosHex,
+    },
+
+    EndTurn,
+}
+
+fn is_agent(state: &State, id: Id, agent: &ObjType) -> bool {
+    &state.parts().meta.get(id).name == agent
+}
+
+impl Action {
+    /// The tile to point the player at.
+    pub fn pos(&self) -> Option<PosHex> {
+        match *self {
+            Action::MoveTo { pos, .. }
+            | Action::Attack { target: pos, .. }
+            | Action::UseAbility { pos, .. } => Some(pos),
+            Action::EndTurn => None,
+        }
+    }
+
+    pub fn allows(&self, state: &State, command: &Command) -> bool {
+        match (self, command) {
+            (Action::MoveTo { agent, pos }, Command::MoveTo(command)) => {
+                is_agent(state, command.id, agent) && command.path.to() == *pos
+            }
+            (Action::Attack { agent, target }, Command::Attack(command)) => {
+                is_agent(state, command.attacker_id, agent)
+                    && state.parts().pos.get(command.target_id).0 == *target
+            }
+            (
+                Action::UseAbility {
+                    agent,
+                    ability,
+                    pos,
+                },
+                Command::UseAbility(command),
+            ) => {
+                is_agent(state, command.id, agent)
+                    && command.ability == *ability
+                    && command.pos == *pos
+            }
+            (Action::EndTurn, Command::EndTurn(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// Explain what to do. Shown when the step begins
+    /// and every time the player tries to do something else.
+    pub remarks: Vec<Remark>,
+
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tutorial {
+    /// Should place everything at fixed positions, the steps refer to them.
+    pub scenario: Scenario,
+
+    pub steps: Vec<Step>,
+}
+
+/// The steps that the player hasn't made yet.
+#[derive(Clone, Debug, Default)]
+pub struct Progress {
+    steps: Vec<Step>,
+    current: usize,
+}
+
+impl Progress {
+    pub fn new(steps: Vec<Step>) -> Self {
+        Self { steps, current: 0 }
+    }
+
+    /// `None` when the tutorial is over (or there's none) and anything goes.
+    pub fn step(&self) -> Option<&Step> {
+        self.steps.get(self.current)
+    }
+
+    /// Moves to the next step if the command is the one the current step waits for.
+    ///
+    /// Must be called before the command is executed.
+    pub fn try_command(&mut self, state: &State, command: &Command) -> bool {
+        let is_allowed = match self.step() {
+            Some(step) => step.action.allows(state, command),
+            None => return true,
+        };
+        if is_allowed {
+            self.current += 1;
+        }
+        is_allowed
+    }
+}
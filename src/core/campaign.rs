@@ -4,10 +4,24 @@ use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 
 use crate::core::{
-    battle::{component::ObjType, scenario::Scenario, state::BattleResult, PlayerId},
+    battle::{
+        ability::{Ability, Tier},
+        component::{self, Component, ObjType, Prototypes},
+        scenario::{self, Scenario},
+        state::{BattleResult, DeathCause},
+        PlayerId,
+    },
+    item::{Equipment, Item, ItemType, Slot},
     utils::{self, zrng},
 };
 
+const XP_PER_SURVIVED_BATTLE: i32 = 1;
+const XP_PER_KILL: i32 = 1;
+const XP_PER_LEVEL: i32 = 3;
+
+/// How many battles a knocked out fighter has to sit out.
+const INJURY_BATTLES: i32 = 2;
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
 pub enum Mode {
     /// Recruiting/upgrading fighters or starting a new battle.
@@ -20,110 +34,1069 @@ pub enum Mode {
     Failed,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, derive_more::From)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default, derive_more::From)]
 #[serde(transparent)]
 pub struct Renown(pub i32);
 
 // TODO: impl `Add` and `Sub` traits for `Renown`.
 
 /// An award that is given to the player after the successful battle.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Award {
     #[serde(default)]
     pub recruits: Vec<ObjType>,
 
+    #[serde(default)]
+    pub items: Vec<ItemType>,
+
     pub renown: Renown,
 }
 
+/// A bonus bought in the shop that every fighter gets for the next battle only.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Boon {
+    /// +1 strength.
+    Strength,
+
+    /// +1 joker every turn.
+    Joker,
+}
+
+impl Boon {
+    pub fn title(self) -> &'static str {
+        match self {
+            Boon::Strength => "+1 strength for everyone",
+            Boon::Joker => "+1 joker for everyone",
+        }
+    }
+}
+
+/// Something that can be bought in the shop.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Goods {
+    Recruit(ObjType),
+    Item(ItemType),
+    Boon(Boon),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Offer {
+    pub goods: Goods,
+    pub price: Renown,
+}
+
+/// Something that the player can spend a camp action on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum CampChoice {
+    /// Heal the injuries of a fighter.
+    Heal,
+
+    /// Give a fighter some experience.
+    Train { xp: i32 },
+
+    /// Learn what enemies wait in the next battles.
+    Scout,
+
+    /// Make an item for the stash.
+    Craft(ItemType),
+}
+
+impl CampChoice {
+    /// Whether the choice is applied to one of the fighters.
+    pub fn needs_agent(&self) -> bool {
+        matches!(self, CampChoice::Heal | CampChoice::Train { .. })
+    }
+
+    /// One-off choices disappear after they are made.
+    fn is_repeatable(&self) -> bool {
+        self.needs_agent()
+    }
+}
+
+/// A campaign node where the group rests instead of fighting.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Camp {
+    /// How many choices the player can make here.
+    pub actions: i32,
+
+    pub choices: Vec<CampChoice>,
+}
+
+/// A bonus that a fighter gets on a level up.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Perk {
+    Strength,
+    Ability(Ability),
+
+    /// The ability moves to its next tier.
+    Upgrade(Ability),
+}
+
+/// How often the most powerful abilities can be used.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AbilityUses {
+    /// Every battle starts with all the abilities ready.
+    #[default]
+    PerBattle,
+
+    /// Some abilities have a few uses that are only restored by a rest.
+    PerDay,
+}
+
+impl AbilityUses {
+    pub fn title(self) -> &'static str {
+        match self {
+            AbilityUses::PerBattle => "per battle",
+            AbilityUses::PerDay => "per day",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            AbilityUses::PerBattle => AbilityUses::PerDay,
+            AbilityUses::PerDay => AbilityUses::PerBattle,
+        }
+    }
+}
+
+/// Whether the decisions of the campaign can be taken back.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SaveMode {
+    /// A lost battle can be fought again.
+    #[default]
+    Normal,
+
+    /// Every decision is saved at once and a lost battle ends the campaign.
+    Ironman,
+}
+
+impl SaveMode {
+    pub fn title(self) -> &'static str {
+        match self {
+            SaveMode::Normal => "with retries",
+            SaveMode::Ironman => "ironman",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SaveMode::Normal => SaveMode::Ironman,
+            SaveMode::Ironman => SaveMode::Normal,
+        }
+    }
+}
+
+/// How many times the ability can be used between rests in the `AbilityUses::PerDay` mode.
+fn daily_charges(ability: Ability) -> Option<i32> {
+    match ability {
+        Ability::Heal => Some(2),
+        Ability::Summon => Some(1),
+        _ => None,
+    }
+}
+
+const REST_COST: Renown = Renown(5);
+
+/// Experience, bonuses and equipment that a fighter accumulates during the campaign.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Progress {
+    pub xp: i32,
+    pub level: i32,
+    pub perks: Vec<Perk>,
+
+    #[serde(default)]
+    pub equipment: Equipment,
+
+    #[serde(default)]
+    pub battles_survived: i32,
+
+    /// Kills made during all the campaign battles.
+    #[serde(default)]
+    pub kills: i32,
+
+    /// How many more battles the fighter has to sit out to recover.
+    #[serde(default)]
+    pub injured_for: i32,
+
+    /// A name given by the player.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// An index of the chosen portrait variant.
+    #[serde(default)]
+    pub portrait: usize,
+
+    /// Uses of the day-limited abilities spent since the last rest.
+    #[serde(default)]
+    pub spent_charges: HashMap<Ability, i32>,
+}
+
+impl Progress {
+    pub fn xp_for_next_level(&self) -> i32 {
+        (self.level + 1) * XP_PER_LEVEL
+    }
+
+    fn persona(&self) -> Option<component::Persona> {
+        if self.name.is_none() && self.portrait == 0 {
+            return None;
+        }
+        Some(component::Persona {
+            name: self.name.clone(),
+            portrait: self.portrait,
+        })
+    }
+
+    pub fn is_injured(&self) -> bool {
+        self.injured_for > 0
+    }
+
+    fn charges(&self, abilities: &[(Ability, Tier)]) -> component::Charges {
+        let mut charges = HashMap::new();
+        for &(ability, tier) in abilities {
+            // The first tier of a heal is too weak to be limited.
+            if ability == Ability::Heal && tier == Tier::default() {
+                continue;
+            }
+            if let Some(max) = daily_charges(ability) {
+                let spent = self.spent_charges.get(&ability).cloned().unwrap_or(0);
+                charges.insert(ability, (max - spent).max(0));
+            }
+        }
+        component::Charges(charges)
+    }
+
+    fn update_spent_charges(&mut self, charges: &component::Charges) {
+        for (&ability, &left) in &charges.0 {
+            if let Some(max) = daily_charges(ability) {
+                self.spent_charges.insert(ability, max - left);
+            }
+        }
+    }
+
+    /// Whether a rest would restore anything.
+    fn is_tired(&self) -> bool {
+        self.spent_charges.values().any(|&spent| spent > 0)
+    }
+
+    pub fn can_level_up(&self) -> bool {
+        self.xp >= self.xp_for_next_level()
+    }
+
+    fn has_perk(&self, perk: Perk) -> bool {
+        self.perks.contains(&perk)
+    }
+
+    /// Components that override the prototype's ones in battle.
+    fn components(
+        &self,
+        prototype: &[Component],
+        item_info: &HashMap<ItemType, Item>,
+        boons: &[Boon],
+    ) -> Vec<Component> {
+        let mut components = Vec::new();
+        let count_boons = |boon| boons.iter().filter(|&&b| b == boon).count() as i32;
+        let bonus_strength = self.perks.iter().filter(|&&p| p == Perk::Strength).count() as i32
+            + count_boons(Boon::Strength);
+        let bonus_jokers = count_boons(Boon::Joker);
+        let has_items = self.equipment.items().next().is_some();
+        let new_abilities: Vec<Ability> = self
+            .perks
+            .iter()
+            .filter_map(|perk| match *perk {
+                Perk::Ability(ability) => Some(ability),
+                Perk::Strength | Perk::Upgrade(_) => None,
+            })
+            .collect();
+        let upgrades: Vec<Ability> = self
+            .perks
+            .iter()
+            .filter_map(|perk| match *perk {
+                Perk::Upgrade(ability) => Some(ability),
+                Perk::Strength | Perk::Ability(_) => None,
+            })
+            .collect();
+        for component in prototype {
+            match component {
+                Component::Agent(agent) if has_items || bonus_jokers > 0 => {
+                    let mut agent = agent.clone();
+                    for item in self.equipment.items() {
+                        item_info[item].apply(&mut agent);
+                    }
+                    agent.jokers.0 += bonus_jokers;
+                    agent.base_jokers.0 += bonus_jokers;
+                    components.push(agent.into());
+                }
+                Component::Strength(strength) if bonus_strength > 0 => {
+                    let mut strength = strength.clone();
+                    strength.strength.0 += bonus_strength;
+                    strength.base_strength.0 += bonus_strength;
+                    components.push(strength.into());
+                }
+                Component::Abilities(abilities) if !new_abilities.is_empty() => {
+                    let mut abilities = abilities.clone();
+                    for &ability in &new_abilities {
+                        if abilities.0.iter().all(|r| r.ability != ability) {
+                            abilities.0.push(ability.into());
+                        }
+                    }
+                    components.push(abilities.into());
+                }
+                _ => {}
+            }
+        }
+        let has_abilities = prototype
+            .iter()
+            .any(|c| matches!(c, Component::Abilities(_)));
+        if !has_abilities && !new_abilities.is_empty() {
+            let abilities = new_abilities.into_iter().map(Into::into).collect();
+            components.push(component::Abilities(abilities).into());
+        }
+        if !upgrades.is_empty() {
+            let mut tiers = prototype
+                .iter()
+                .find_map(|component| match component {
+                    Component::AbilityTiers(tiers) => Some(tiers.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            for ability in upgrades {
+                let tier = tiers.tier(ability);
+                tiers.0.insert(ability, tier.next());
+            }
+            components.push(tiers.into());
+        }
+        components
+    }
+}
+
+/// Abilities of an agent and their tiers with the campaign's progress applied.
+fn agent_abilities(
+    prototype: &[Component],
+    extra_components: &[Component],
+) -> Vec<(Ability, Tier)> {
+    let components = || extra_components.iter().chain(prototype);
+    let abilities = components().find_map(|component| match component {
+        Component::Abilities(abilities) => Some(abilities),
+        _ => None,
+    });
+    let tiers = components().find_map(|component| match component {
+        Component::AbilityTiers(tiers) => Some(tiers.clone()),
+        _ => None,
+    });
+    let tiers = tiers.unwrap_or_default();
+    match abilities {
+        Some(abilities) => abilities
+            .0
+            .iter()
+            .map(|r| (r.ability, tiers.tier(r.ability)))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// A fighter that has died during the campaign.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FallenAgent {
+    pub agent_type: ObjType,
+
+    #[serde(default)]
+    pub name: Option<String>,
+
+    pub level: i32,
+    pub battles_survived: i32,
+    pub kills: i32,
+    pub cause: DeathCause,
+
+    /// A name of the node where the fighter has died.
+    pub node: String,
+}
+
+/// A short record of a finished campaign battle.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BattleRecord {
+    pub node: String,
+    pub is_won: bool,
+
+    /// The group has fallen back and has to fight the battle again, it isn't lost.
+    #[serde(default)]
+    pub is_retreat: bool,
+
+    pub kills: i32,
+
+    /// Fighters that have died in this battle.
+    pub casualties: Vec<ObjType>,
+}
+
+/// A battle that has changed the course of the campaign.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TurningPoint {
+    /// The first battle where someone has died.
+    FirstLoss { node: String },
+
+    /// A won battle that has cost several lives.
+    CostlyVictory { node: String, casualties: usize },
+
+    /// The won battle with the most kills and no losses.
+    FlawlessVictory { node: String, kills: i32 },
+
+    /// The battle that the group has fallen back from.
+    Retreat { node: String },
+
+    /// The battle that has ended the campaign.
+    Defeat { node: String },
+}
+
+/// The fighter with the most kills during the whole campaign.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mvp {
+    pub agent_type: ObjType,
+    pub name: Option<String>,
+    pub kills: i32,
+    pub is_fallen: bool,
+}
+
+/// Highlights of the whole campaign for the final screen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub is_won: bool,
+    pub battles: usize,
+    pub battles_won: usize,
+    pub kills: i32,
+    pub casualties: usize,
+    pub mvp: Option<Mvp>,
+    pub favorite_ability: Option<Ability>,
+
+    /// The type that has fought the most battles.
+    pub favorite_unit: Option<ObjType>,
+
+    /// In the order they've happened.
+    pub turning_points: Vec<TurningPoint>,
+}
+
+impl Summary {
+    /// Victories and kills add up, the lost fighters cost some of it.
+    pub fn score(&self) -> i32 {
+        let victory_bonus = if self.is_won { 500 } else { 0 };
+        let score = victory_bonus + self.battles_won as i32 * 50 + self.kills * 10
+            - self.casualties as i32 * 25;
+        score.max(0)
+    }
+
+    pub fn to_run(&self) -> Run {
+        Run {
+            is_won: self.is_won,
+            score: self.score(),
+            battles_won: self.battles_won,
+            kills: self.kills,
+            casualties: self.casualties,
+            favorite_unit: self.favorite_unit.clone(),
+        }
+    }
+}
+
+/// How many runs the hall of fame keeps.
+const HALL_OF_FAME_SIZE: usize = 10;
+
+/// A finished campaign, as the hall of fame remembers it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Run {
+    pub is_won: bool,
+    pub score: i32,
+    pub battles_won: usize,
+    pub kills: i32,
+    pub casualties: usize,
+
+    #[serde(default)]
+    pub favorite_unit: Option<ObjType>,
+}
+
+/// The best finished campaigns, the highest score first.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct HallOfFame(Vec<Run>);
+
+impl HallOfFame {
+    pub fn runs(&self) -> &[Run] {
+        &self.0
+    }
+
+    /// Returns the place of the new run or `None` if it's not good enough.
+    /// The older runs keep their places on equal scores.
+    pub fn add(&mut self, run: Run) -> Option<usize> {
+        let place = self.0.iter().take_while(|r| r.score >= run.score).count();
+        if place >= HALL_OF_FAME_SIZE {
+            return None;
+        }
+        self.0.insert(place, run);
+        self.0.truncate(HALL_OF_FAME_SIZE);
+        Some(place)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum Action {
-    Recruit { agent_type: ObjType },
-    Upgrade { from: ObjType, to: ObjType },
+    Recruit {
+        agent_type: ObjType,
+    },
+    Upgrade {
+        from: ObjType,
+        to: ObjType,
+    },
+    LevelUp {
+        agent_index: usize,
+        perk: Perk,
+    },
+
+    /// Restore the charges of all the day-limited abilities.
+    Rest,
 }
 
+/// A battle (or a camp) on the campaign's world map.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CampaignNode {
+    pub name: String,
+
+    /// Names of the nodes that lead to this one.
+    /// The node is available right after any of them is won.
+    /// Nodes with no requirements are the starting ones.
+    #[serde(default)]
+    pub requires: Vec<String>,
+
+    #[serde(default)]
     pub scenario: Scenario,
+
+    #[serde(default)]
     pub award: Award,
+
+    /// Makes this node a camp: there's no battle, only the camp choices.
+    #[serde(default)]
+    pub camp: Option<Camp>,
+
+    /// What the shop offers after this node is won.
+    #[serde(default)]
+    pub shop: Vec<Offer>,
 }
 
-fn casualties(initial_agents: &[ObjType], survivors: &[ObjType]) -> Vec<ObjType> {
-    let mut agents = initial_agents.to_vec();
-    for typename in survivors {
-        assert!(utils::try_remove_item(&mut agents, typename));
-    }
-    agents
+/// Fighters knocked down by a plain blow are carried off the field
+/// by the winners and only need some rest.
+/// Explosions, fire, poison and the like are lethal.
+fn is_knockout(cause: &DeathCause) -> bool {
+    matches!(cause, DeathCause::Attack(_))
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Plan {
     initial_agents: Vec<ObjType>,
+
+    #[serde(default)]
+    initial_items: Vec<ItemType>,
+
     nodes: Vec<CampaignNode>,
 }
 
+impl Plan {
+    pub fn initial_agents(&self) -> &[ObjType] {
+        &self.initial_agents
+    }
+
+    pub fn initial_items(&self) -> &[ItemType] {
+        &self.initial_items
+    }
+
+    pub fn nodes(&self) -> &[CampaignNode] {
+        &self.nodes
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AgentInfo {
     pub cost: Renown,
 
     #[serde(default)]
     pub upgrades: Vec<ObjType>,
+
+    /// Abilities that this agent can learn on a level up.
+    #[serde(default)]
+    pub learnable_abilities: Vec<Ability>,
+
+    /// Abilities that can be moved to their next tier on a level up, once per fighter.
+    #[serde(default)]
+    pub upgradable_abilities: Vec<Ability>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct State {
-    scenarios: Vec<CampaignNode>,
-    current_scenario_index: i32,
+    nodes: Vec<CampaignNode>,
+
+    /// Indices of the won nodes, in the order they were won.
+    completed_nodes: Vec<usize>,
+
+    /// The node that will be played next.
+    current_node: usize,
+
     mode: Mode,
     agents: Vec<ObjType>,
+
+    /// Progress of every agent, in the same order as `agents`.
+    progress: Vec<Progress>,
+
     last_battle_casualties: Vec<ObjType>,
     agent_info: HashMap<ObjType, AgentInfo>,
+    item_info: HashMap<ItemType, Item>,
+
+    /// Items that aren't carried by anyone.
+    items: Vec<ItemType>,
+
+    /// Everyone who has died during the campaign, in the order of death.
+    #[serde(default)]
+    fallen_agents: Vec<FallenAgent>,
+
+    /// Offers that are still available in the shop.
+    #[serde(default)]
+    shop: Vec<Offer>,
+
+    /// Boons bought for the next battle.
+    #[serde(default)]
+    boons: Vec<Boon>,
+
+    #[serde(default)]
+    ability_uses: AbilityUses,
+
+    #[serde(default)]
+    save_mode: SaveMode,
+
+    /// The camp that the group is staying in, with the actions that are left.
+    #[serde(default)]
+    camp: Option<Camp>,
+
+    /// All the finished battles, in the order they were fought.
+    #[serde(default)]
+    history: Vec<BattleRecord>,
+
+    /// How many times the group has used every ability during the campaign.
+    #[serde(default)]
+    abilities_used: HashMap<Ability, i32>,
+
     actions: Vec<Action>,
     renown: Renown,
 }
 
 impl State {
-    pub fn new(plan: Plan, agent_info: HashMap<ObjType, AgentInfo>) -> Self {
+    pub fn new(
+        plan: Plan,
+        agent_info: HashMap<ObjType, AgentInfo>,
+        item_info: HashMap<ItemType, Item>,
+    ) -> Self {
         assert!(!plan.nodes.is_empty(), "No scenarios");
-        Self {
-            current_scenario_index: 0,
-            scenarios: plan.nodes,
+        for node in &plan.nodes {
+            for name in &node.requires {
+                let is_known = plan.nodes.iter().any(|n| &n.name == name);
+                assert!(is_known, "Unknown campaign node: {}", name);
+            }
+        }
+        let progress = vec![Progress::default(); plan.initial_agents.len()];
+        let mut this = Self {
+            progress,
+            completed_nodes: Vec::new(),
+            current_node: 0,
+            nodes: plan.nodes,
             mode: Mode::PreparingForBattle,
             agents: plan.initial_agents,
             last_battle_casualties: Vec::new(),
             actions: Vec::new(),
             agent_info,
+            item_info,
+            items: plan.initial_items,
+            fallen_agents: Vec::new(),
+            shop: Vec::new(),
+            boons: Vec::new(),
+            ability_uses: AbilityUses::default(),
+            save_mode: SaveMode::default(),
+            camp: None,
+            history: Vec::new(),
+            abilities_used: HashMap::new(),
             renown: Renown(0),
-        }
+        };
+        let available_nodes = this.available_nodes();
+        this.current_node = *available_nodes.first().expect("No starting nodes");
+        this
     }
 
     pub fn mode(&self) -> Mode {
         self.mode
     }
 
-    pub fn last_battle_casualties(&self) -> &[ObjType] {
-        &self.last_battle_casualties
+    pub fn last_battle_casualties(&self) -> &[ObjType] {
+        &self.last_battle_casualties
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.nodes[self.current_node].scenario
+    }
+
+    pub fn nodes(&self) -> &[CampaignNode] {
+        &self.nodes
+    }
+
+    pub fn completed_nodes(&self) -> &[usize] {
+        &self.completed_nodes
+    }
+
+    pub fn current_node(&self) -> usize {
+        self.current_node
+    }
+
+    /// Nodes that lead from the last won node (or the starting nodes).
+    pub fn available_nodes(&self) -> Vec<usize> {
+        let last_node = self.completed_nodes.last().map(|&i| &self.nodes[i].name);
+        let is_available = |node: &CampaignNode| match last_node {
+            Some(name) => node.requires.contains(name),
+            None => node.requires.is_empty(),
+        };
+        (0..self.nodes.len())
+            .filter(|&i| is_available(&self.nodes[i]))
+            .collect()
+    }
+
+    pub fn select_node(&mut self, node: usize) {
+        assert_eq!(self.mode(), Mode::PreparingForBattle);
+        assert!(self.available_nodes().contains(&node));
+        self.current_node = node;
+    }
+
+    pub fn camp(&self) -> Option<&Camp> {
+        self.camp.as_ref()
+    }
+
+    /// Visits a camp node instead of fighting a battle there.
+    /// The camp restores all the abilities and offers some choices.
+    pub fn visit_camp(&mut self, node: usize) {
+        self.select_node(node);
+        let camp = self.nodes[node].camp.clone().expect("Not a camp");
+        self.actions.clear();
+        self.shop.clear();
+        self.rest();
+        self.completed_nodes.push(node);
+        self.camp = Some(camp);
+        match self.available_nodes().first() {
+            Some(&next_node) => self.current_node = next_node,
+            None => self.mode = Mode::Won,
+        }
+    }
+
+    pub fn can_make_camp_choice(&self, choice: &CampChoice) -> bool {
+        match &self.camp {
+            Some(camp) => camp.actions > 0 && camp.choices.contains(choice),
+            None => false,
+        }
+    }
+
+    /// Spends one camp action. `agent_index` is required by the choices that need an agent.
+    pub fn make_camp_choice(&mut self, choice: CampChoice, agent_index: Option<usize>) {
+        assert!(self.can_make_camp_choice(&choice));
+        assert_eq!(choice.needs_agent(), agent_index.is_some());
+        let camp = self.camp.as_mut().expect("Not in a camp");
+        camp.actions -= 1;
+        if !choice.is_repeatable() {
+            assert!(utils::try_remove_item(&mut camp.choices, &choice));
+        }
+        match choice {
+            CampChoice::Heal => {
+                let agent_index = agent_index.expect("No fighter to heal");
+                self.progress[agent_index].injured_for = 0;
+            }
+            CampChoice::Train { xp } => {
+                let agent_index = agent_index.expect("No fighter to train");
+                self.progress[agent_index].xp += xp;
+                let has_level_up_actions = self.actions.iter().any(|action| {
+                    matches!(action, Action::LevelUp { agent_index: i, .. } if *i == agent_index)
+                });
+                if self.progress[agent_index].can_level_up() && !has_level_up_actions {
+                    self.add_level_up_actions(agent_index);
+                }
+            }
+            // Scouting only reveals the information that the screen shows.
+            CampChoice::Scout => {}
+            CampChoice::Craft(item) => self.items.push(item),
+        }
+    }
+
+    /// How many steps from the starting nodes it takes to get to every node.
+    pub fn node_depths(&self) -> Vec<usize> {
+        let mut depths = vec![0; self.nodes.len()];
+        // Every iteration settles at least one more level of the graph.
+        for _ in 0..self.nodes.len() {
+            for (i, node) in self.nodes.iter().enumerate() {
+                for (parent_i, parent) in self.nodes.iter().enumerate() {
+                    if node.requires.contains(&parent.name) {
+                        depths[i] = depths[i].max(depths[parent_i] + 1);
+                    }
+                }
+            }
+        }
+        depths
+    }
+
+    pub fn ability_uses(&self) -> AbilityUses {
+        self.ability_uses
+    }
+
+    pub fn set_ability_uses(&mut self, ability_uses: AbilityUses) {
+        self.ability_uses = ability_uses;
+    }
+
+    pub fn save_mode(&self) -> SaveMode {
+        self.save_mode
+    }
+
+    pub fn set_save_mode(&mut self, save_mode: SaveMode) {
+        self.save_mode = save_mode;
+    }
+
+    /// Lets all the fighters recover their day-limited abilities.
+    pub fn rest(&mut self) {
+        for progress in &mut self.progress {
+            progress.spent_charges.clear();
+        }
+    }
+
+    pub fn agents(&self) -> &[ObjType] {
+        &self.agents
+    }
+
+    pub fn agents_progress(&self) -> &[Progress] {
+        &self.progress
+    }
+
+    pub fn fallen_agents(&self) -> &[FallenAgent] {
+        &self.fallen_agents
+    }
+
+    fn mvp(&self) -> Option<Mvp> {
+        let alive = self
+            .agents
+            .iter()
+            .zip(&self.progress)
+            .map(|(agent_type, progress)| Mvp {
+                agent_type: agent_type.clone(),
+                name: progress.name.clone(),
+                kills: progress.kills,
+                is_fallen: false,
+            });
+        let fallen = self.fallen_agents.iter().map(|fallen| Mvp {
+            agent_type: fallen.agent_type.clone(),
+            name: fallen.name.clone(),
+            kills: fallen.kills,
+            is_fallen: true,
+        });
+        // The first one wins the ties, so the survivors are preferred.
+        alive
+            .chain(fallen)
+            .filter(|mvp| mvp.kills > 0)
+            .fold(None, |best: Option<Mvp>, mvp| match best {
+                Some(best) if best.kills >= mvp.kills => Some(best),
+                _ => Some(mvp),
+            })
+    }
+
+    fn turning_points(&self) -> Vec<TurningPoint> {
+        let first_loss = self.history.iter().position(|r| !r.casualties.is_empty());
+        let flawless = self
+            .history
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.is_won && r.casualties.is_empty() && r.kills > 0)
+            .fold(None, |best: Option<(usize, i32)>, (i, r)| match best {
+                Some((_, kills)) if kills >= r.kills => best,
+                _ => Some((i, r.kills)),
+            })
+            .map(|(i, _)| i);
+        let mut points = Vec::new();
+        for (i, record) in self.history.iter().enumerate() {
+            let node = record.node.clone();
+            if record.is_retreat {
+                if first_loss == Some(i) {
+                    points.push(TurningPoint::FirstLoss { node: node.clone() });
+                }
+                points.push(TurningPoint::Retreat { node });
+                continue;
+            }
+            if !record.is_won {
+                points.push(TurningPoint::Defeat { node });
+                continue;
+            }
+            if first_loss == Some(i) {
+                points.push(TurningPoint::FirstLoss { node: node.clone() });
+            }
+            if record.casualties.len() >= 2 {
+                let casualties = record.casualties.len();
+                points.push(TurningPoint::CostlyVictory {
+                    node: node.clone(),
+                    casualties,
+                });
+            }
+            if flawless == Some(i) {
+                let kills = record.kills;
+                points.push(TurningPoint::FlawlessVictory { node, kills });
+            }
+        }
+        points
+    }
+
+    fn favorite_unit(&self) -> Option<ObjType> {
+        let mut battles: HashMap<&ObjType, i32> = HashMap::new();
+        for (agent_type, progress) in self.agents.iter().zip(&self.progress) {
+            *battles.entry(agent_type).or_insert(0) += progress.battles_survived;
+        }
+        for fallen in &self.fallen_agents {
+            // The last battle counts too.
+            *battles.entry(&fallen.agent_type).or_insert(0) += fallen.battles_survived + 1;
+        }
+        battles
+            .into_iter()
+            .filter(|&(_, count)| count > 0)
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0 .0.cmp(&a.0 .0)))
+            .map(|(agent_type, _)| agent_type.clone())
+    }
+
+    /// Highlights of the campaign, only available once it's over.
+    pub fn summary(&self) -> Option<Summary> {
+        if self.mode == Mode::PreparingForBattle {
+            return None;
+        }
+        let favorite_ability = self
+            .abilities_used
+            .iter()
+            .max_by_key(|&(ability, &uses)| (uses, ability.title()))
+            .map(|(&ability, _)| ability);
+        Some(Summary {
+            is_won: self.mode == Mode::Won,
+            battles: self.history.len(),
+            battles_won: self.history.iter().filter(|r| r.is_won).count(),
+            kills: self.history.iter().map(|r| r.kills).sum(),
+            casualties: self.fallen_agents.len(),
+            mvp: self.mvp(),
+            favorite_ability,
+            favorite_unit: self.favorite_unit(),
+            turning_points: self.turning_points(),
+        })
+    }
+
+    /// Indices of the agents that go to the next battle.
+    /// The injured ones stay behind unless there's no one else.
+    fn battle_participants(&self) -> Vec<usize> {
+        let fit: Vec<usize> = (0..self.agents.len())
+            .filter(|&i| !self.progress[i].is_injured())
+            .collect();
+        if fit.is_empty() {
+            (0..self.agents.len()).collect()
+        } else {
+            fit
+        }
+    }
+
+    /// The current scenario with the player's agents (and their progress) added.
+    pub fn battle_scenario(&self, prototypes: &Prototypes) -> Scenario {
+        let mut scenario = self.scenario().clone();
+        for index in self.battle_participants() {
+            let typename = &self.agents[index];
+            let progress = &self.progress[index];
+            let prototype = &prototypes.0[typename];
+            let mut extra_components = progress.components(prototype, &self.item_info, &self.boons);
+            if self.ability_uses == AbilityUses::PerDay {
+                let abilities = agent_abilities(prototype, &extra_components);
+                let charges = progress.charges(&abilities);
+                if !charges.0.is_empty() {
+                    extra_components.push(charges.into());
+                }
+            }
+            extra_components.push(component::Veteran { index, kills: 0 }.into());
+            if let Some(persona) = progress.persona() {
+                extra_components.push(persona.into());
+            }
+            scenario.randomized_objects.push(scenario::ObjectsGroup {
+                owner: Some(PlayerId(0)),
+                typename: typename.clone(),
+                line: Some(scenario::Line::Middle),
+                count: 1,
+                extra_components,
+            });
+        }
+        scenario
+    }
+
+    /// Gives the agent a personal name. An empty name resets it.
+    pub fn rename(&mut self, agent_index: usize, name: &str) {
+        let name = name.trim();
+        self.progress[agent_index].name = if name.is_empty() {
+            None
+        } else {
+            Some(name.into())
+        };
+    }
+
+    pub fn set_portrait(&mut self, agent_index: usize, portrait: usize) {
+        self.progress[agent_index].portrait = portrait;
+    }
+
+    pub fn items(&self) -> &[ItemType] {
+        &self.items
+    }
+
+    pub fn item_info(&self, item: &ItemType) -> &Item {
+        &self.item_info[item]
+    }
+
+    /// Gives an item from the stash to the agent.
+    /// The item the agent carried in the same slot goes back to the stash.
+    pub fn equip(&mut self, agent_index: usize, item: ItemType) {
+        assert_eq!(self.mode(), Mode::PreparingForBattle);
+        assert!(utils::try_remove_item(&mut self.items, &item));
+        let slot = self.item_info[&item].slot;
+        let equipment = &mut self.progress[agent_index].equipment;
+        if let Some(old_item) = equipment.slot_mut(slot).replace(item) {
+            self.items.push(old_item);
+        }
+    }
+
+    /// Moves the agent's item back to the stash.
+    pub fn unequip(&mut self, agent_index: usize, slot: Slot) {
+        assert_eq!(self.mode(), Mode::PreparingForBattle);
+        let equipment = &mut self.progress[agent_index].equipment;
+        if let Some(item) = equipment.slot_mut(slot).take() {
+            self.items.push(item);
+        }
     }
 
-    pub fn scenario(&self) -> &Scenario {
-        assert!(!self.scenarios.is_empty());
-        let i = self.current_scenario_index as usize;
-        &self.scenarios[i].scenario
+    pub fn renown(&self) -> Renown {
+        self.renown
     }
 
-    pub fn current_scenario_index(&self) -> i32 {
-        self.current_scenario_index
+    pub fn shop(&self) -> &[Offer] {
+        &self.shop
     }
 
-    pub fn scenarios_count(&self) -> i32 {
-        self.scenarios.len() as _
+    pub fn boons(&self) -> &[Boon] {
+        &self.boons
     }
 
-    pub fn agents(&self) -> &[ObjType] {
-        &self.agents
+    pub fn can_buy(&self, offer: &Offer) -> bool {
+        self.shop.contains(offer) && offer.price.0 <= self.renown.0
     }
 
-    pub fn renown(&self) -> Renown {
-        self.renown
+    /// Spends the renown on the offer. Every offer can be bought only once.
+    pub fn buy(&mut self, offer: Offer) {
+        assert_eq!(self.mode(), Mode::PreparingForBattle);
+        assert!(self.can_buy(&offer));
+        assert!(utils::try_remove_item(&mut self.shop, &offer));
+        self.renown.0 -= offer.price.0;
+        match offer.goods {
+            Goods::Recruit(agent_type) => {
+                self.agents.push(agent_type);
+                self.progress.push(Progress::default());
+            }
+            Goods::Item(item) => self.items.push(item),
+            Goods::Boon(boon) => self.boons.push(boon),
+        }
     }
 
     pub fn available_actions(&self) -> &[Action] {
@@ -139,12 +1112,82 @@ impl State {
         match action {
             Action::Recruit { agent_type } => {
                 self.agents.push(agent_type);
+                self.progress.push(Progress::default());
             }
             Action::Upgrade { from, to } => {
-                assert!(utils::try_remove_item(&mut self.agents, &from));
-                self.agents.push(to);
+                // Upgrade in place to keep the agent's progress.
+                let i = self.agents.iter().position(|a| a == &from);
+                let i = i.expect("No agent to upgrade");
+                self.agents[i] = to;
+            }
+            Action::Rest => self.rest(),
+            Action::LevelUp { agent_index, perk } => {
+                let progress = &mut self.progress[agent_index];
+                assert!(progress.can_level_up());
+                progress.xp -= progress.xp_for_next_level();
+                progress.level += 1;
+                progress.perks.push(perk);
+                self.actions.retain(|action| match action {
+                    Action::LevelUp { agent_index: i, .. } => *i != agent_index,
+                    _ => true,
+                });
+                if self.progress[agent_index].can_level_up() {
+                    self.add_level_up_actions(agent_index);
+                }
+            }
+        }
+    }
+
+    fn add_level_up_actions(&mut self, agent_index: usize) {
+        let progress = &self.progress[agent_index];
+        let perk = Perk::Strength;
+        self.actions.push(Action::LevelUp { agent_index, perk });
+        let typename = &self.agents[agent_index];
+        let (learnable, upgradable) = match self.agent_info.get(typename) {
+            Some(info) => (
+                info.learnable_abilities.clone(),
+                info.upgradable_abilities.clone(),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        let perks = [
+            learnable.into_iter().map(Perk::Ability).collect::<Vec<_>>(),
+            upgradable.into_iter().map(Perk::Upgrade).collect(),
+        ];
+        for perks in perks {
+            let perks: Vec<Perk> = perks
+                .into_iter()
+                .filter(|&perk| !progress.has_perk(perk))
+                .collect();
+            if let Some(&perk) = perks.choose(&mut zrng()) {
+                self.actions.push(Action::LevelUp { agent_index, perk });
+            }
+        }
+    }
+
+    /// Indices of the surviving agents.
+    fn survivor_indices(&self, result: &BattleResult, participants: &[usize]) -> Vec<usize> {
+        if !result.veterans.is_empty() {
+            let mut indices: Vec<usize> = result
+                .veterans
+                .iter()
+                .map(|v| v.index)
+                .filter(|&i| i < self.agents.len())
+                .collect();
+            indices.sort_unstable();
+            return indices;
+        }
+        let mut indices = Vec::new();
+        for survivor in &result.survivor_types {
+            let index = self.agents.iter().enumerate().position(|(i, agent)| {
+                agent == survivor && participants.contains(&i) && !indices.contains(&i)
+            });
+            if let Some(index) = index {
+                indices.push(index);
             }
         }
+        indices.sort_unstable();
+        indices
     }
 
     pub fn action_cost(&self, action: &Action) -> Renown {
@@ -159,6 +1202,8 @@ impl State {
                 let cost_to = self.agent_info[to].cost;
                 Renown(cost_to.0 - cost_from.0)
             }
+            Action::LevelUp { .. } => Renown(0),
+            Action::Rest => REST_COST,
         }
     }
 
@@ -168,6 +1213,10 @@ impl State {
         }
 
         self.actions.clear();
+        self.shop.clear();
+        self.camp = None;
+        // The boons last for one battle only.
+        self.boons.clear();
 
         for survivor in &result.survivor_types {
             if !self.agents.contains(survivor) {
@@ -181,20 +1230,100 @@ impl State {
             return Err(());
         }
 
-        self.last_battle_casualties = casualties(&self.agents, &result.survivor_types);
-        self.agents = result.survivor_types.clone();
+        let is_won = result.winner_id == PlayerId(0);
+        let participants = self.battle_participants();
+        let survivor_indices = self.survivor_indices(result, &participants);
+        let node_name = self.nodes[self.current_node].name.clone();
+        self.last_battle_casualties.clear();
+        let mut battle_kills = 0;
+        let mut agents = Vec::new();
+        let mut progress = Vec::new();
+        for (index, mut agent_progress) in self.progress.clone().into_iter().enumerate() {
+            let agent_type = self.agents[index].clone();
+            if !participants.contains(&index) {
+                // Sitting out a battle is a part of the recovery.
+                agent_progress.injured_for -= 1;
+                agents.push(agent_type);
+                progress.push(agent_progress);
+                continue;
+            }
+            let fallen = result.fallen.iter().find(|f| f.veteran.index == index);
+            let veteran = result
+                .veterans
+                .iter()
+                .chain(fallen.map(|f| &f.veteran))
+                .find(|v| v.index == index);
+            let kills = veteran.map_or(0, |v| v.kills);
+            battle_kills += kills;
+            agent_progress.xp += kills * XP_PER_KILL;
+            agent_progress.kills += kills;
+            if let Some(charges) = result.charges.get(&index) {
+                agent_progress.update_spent_charges(charges);
+            }
+            if survivor_indices.contains(&index) {
+                if !result.is_retreat {
+                    agent_progress.xp += XP_PER_SURVIVED_BATTLE;
+                    agent_progress.battles_survived += 1;
+                }
+                agent_progress.injured_for = 0;
+            } else {
+                let cause = fallen.map_or(DeathCause::Unknown, |f| f.cause.clone());
+                if is_won && is_knockout(&cause) {
+                    agent_progress.injured_for = INJURY_BATTLES;
+                } else {
+                    // The equipment of the fallen agents is recovered.
+                    self.items.extend(agent_progress.equipment.items().cloned());
+                    self.fallen_agents.push(FallenAgent {
+                        agent_type: agent_type.clone(),
+                        name: agent_progress.name.clone(),
+                        level: agent_progress.level,
+                        battles_survived: agent_progress.battles_survived,
+                        kills: agent_progress.kills,
+                        cause,
+                        node: node_name.clone(),
+                    });
+                    self.last_battle_casualties.push(agent_type);
+                    continue;
+                }
+            }
+            agents.push(agent_type);
+            progress.push(agent_progress);
+        }
+        self.agents = agents;
+        self.progress = progress;
+        self.history.push(BattleRecord {
+            node: node_name,
+            is_won,
+            is_retreat: result.is_retreat,
+            kills: battle_kills,
+            casualties: self.last_battle_casualties.clone(),
+        });
+        for (&ability, &uses) in &result.ability_uses {
+            *self.abilities_used.entry(ability).or_insert(0) += uses;
+        }
+
+        if result.is_retreat {
+            // The battle has to be fought again, with no award for the attempt.
+            self.mode = Mode::PreparingForBattle;
+            return Ok(());
+        }
 
         if result.winner_id != PlayerId(0) {
             self.mode = Mode::Failed;
             return Ok(());
         }
 
-        if self.current_scenario_index + 1 >= self.scenarios.len() as _ {
+        self.renown.0 += result.control_renown;
+        self.completed_nodes.push(self.current_node);
+        let available_nodes = self.available_nodes();
+        if available_nodes.is_empty() {
             self.mode = Mode::Won;
         } else {
-            let i = self.current_scenario_index as usize;
-            let award = &self.scenarios[i].award;
+            let node = &self.nodes[self.current_node];
+            self.shop = node.shop.clone();
+            let award = &node.award;
             self.renown.0 += award.renown.0;
+            self.items.extend(award.items.iter().cloned());
             for recruit in &award.recruits {
                 let action = Action::Recruit {
                     agent_type: recruit.clone(),
@@ -219,7 +1348,15 @@ impl State {
                     self.actions.push(action.clone());
                 }
             }
-            self.current_scenario_index += 1;
+            for agent_index in 0..self.agents.len() {
+                if self.progress[agent_index].can_level_up() {
+                    self.add_level_up_actions(agent_index);
+                }
+            }
+            if self.progress.iter().any(Progress::is_tired) {
+                self.actions.push(Action::Rest);
+            }
+            self.current_node = available_nodes[0];
             self.mode = Mode::PreparingForBattle;
         }
 
@@ -233,12 +1370,19 @@ mod tests {
 
     use crate::core::{
         battle::{
-            component::ObjType,
+            ability::{Ability, Tier},
+            component::{self, Charges, Component, ObjType, Persona, Prototypes, Veteran},
+            effect::Lasting,
             scenario::{Line, ObjectsGroup, Scenario},
-            state::BattleResult,
+            state::{BattleResult, DeathCause, FallenVeteran},
             PlayerId,
         },
-        campaign::{Action, AgentInfo, Award, CampaignNode, Mode, Plan, State},
+        campaign::{
+            AbilityUses, Action, AgentInfo, Award, Boon, Camp, CampChoice, CampaignNode,
+            FallenAgent, Goods, HallOfFame, Mode, Offer, Perk, Plan, Run, SaveMode, State,
+            TurningPoint,
+        },
+        item::{Item, ItemType, Slot},
     };
 
     type GroupTuple<'a> = (Option<PlayerId>, &'a str, Option<Line>, i32);
@@ -252,6 +1396,7 @@ mod tests {
                 typename,
                 line,
                 count,
+                extra_components: Vec::new(),
             }
         }
     }
@@ -267,6 +1412,8 @@ mod tests {
             AgentInfo {
                 upgrades: Vec::new(),
                 cost: 10.into(),
+                learnable_abilities: Vec::new(),
+                upgradable_abilities: Vec::new(),
             },
         );
         m.insert(
@@ -274,6 +1421,8 @@ mod tests {
             AgentInfo {
                 upgrades: Vec::new(),
                 cost: 10.into(),
+                learnable_abilities: Vec::new(),
+                upgradable_abilities: Vec::new(),
             },
         );
         m
@@ -286,6 +1435,8 @@ mod tests {
             AgentInfo {
                 upgrades: vec!["heavy_swordsman".into()],
                 cost: 10.into(),
+                learnable_abilities: Vec::new(),
+                upgradable_abilities: Vec::new(),
             },
         );
         m.insert(
@@ -293,6 +1444,8 @@ mod tests {
             AgentInfo {
                 upgrades: Vec::new(),
                 cost: 15.into(),
+                learnable_abilities: Vec::new(),
+                upgradable_abilities: Vec::new(),
             },
         );
         m.insert(
@@ -300,6 +1453,8 @@ mod tests {
             AgentInfo {
                 upgrades: Vec::new(),
                 cost: 10.into(),
+                learnable_abilities: Vec::new(),
+                upgradable_abilities: Vec::new(),
             },
         );
         m.insert(
@@ -307,11 +1462,30 @@ mod tests {
             AgentInfo {
                 upgrades: Vec::new(),
                 cost: 10.into(),
+                learnable_abilities: Vec::new(),
+                upgradable_abilities: Vec::new(),
             },
         );
         m
     }
 
+    fn item_info() -> HashMap<ItemType, Item> {
+        let item = |slot| Item {
+            slot,
+            attack_distance: 0,
+            attack_strength: 0,
+            attack_accuracy: 0,
+            dodge: 0,
+            move_points: 0,
+            jokers: 0,
+        };
+        let mut m = HashMap::new();
+        m.insert("spear".into(), item(Slot::Weapon));
+        m.insert("axe".into(), item(Slot::Weapon));
+        m.insert("charm".into(), item(Slot::Trinket));
+        m
+    }
+
     fn campaign_plan_short() -> Plan {
         let initial_agents = initial_agents();
         let nodes = {
@@ -327,13 +1501,22 @@ mod tests {
             };
             let award = Award {
                 recruits: vec![],
+                items: Vec::new(),
                 renown: 10.into(),
             };
-            let node = CampaignNode { scenario, award };
+            let node = CampaignNode {
+                name: "first".into(),
+                requires: Vec::new(),
+                scenario,
+                award,
+                shop: Vec::new(),
+                camp: None,
+            };
             vec![node]
         };
         Plan {
             initial_agents,
+            initial_items: Vec::new(),
             nodes,
         }
     }
@@ -344,6 +1527,8 @@ mod tests {
         let id_1 = Some(PlayerId(1));
         let nodes = vec![
             CampaignNode {
+                name: "first".into(),
+                requires: Vec::new(),
                 scenario: Scenario {
                     randomized_objects: vec![
                         (None, "boulder", None, 3).into(),
@@ -354,10 +1539,15 @@ mod tests {
                 },
                 award: Award {
                     recruits: vec!["spearman".into()],
+                    items: Vec::new(),
                     renown: 20.into(),
                 },
+                shop: Vec::new(),
+                camp: None,
             },
             CampaignNode {
+                name: "second".into(),
+                requires: vec!["first".into()],
                 scenario: Scenario {
                     randomized_objects: vec![
                         (None, "boulder", None, 3).into(),
@@ -367,12 +1557,16 @@ mod tests {
                 },
                 award: Award {
                     recruits: Vec::new(),
+                    items: Vec::new(),
                     renown: 20.into(),
                 },
+                shop: Vec::new(),
+                camp: None,
             },
         ];
         Plan {
             initial_agents,
+            initial_items: Vec::new(),
             nodes,
         }
     }
@@ -383,18 +1577,25 @@ mod tests {
         let empty_plan = Plan {
             nodes: Vec::new(),
             initial_agents: Vec::new(),
+            initial_items: Vec::new(),
         };
-        let _state = State::new(empty_plan, agent_info_empty());
+        let _state = State::new(empty_plan, agent_info_empty(), HashMap::new());
     }
 
     #[test]
     fn short_happy_path() {
-        let mut state = State::new(campaign_plan_short(), agent_info_empty());
+        let mut state = State::new(campaign_plan_short(), agent_info_empty(), HashMap::new());
         assert!(state.available_actions().is_empty());
         assert_eq!(state.mode(), Mode::PreparingForBattle);
         let battle_result = BattleResult {
             winner_id: PlayerId(0),
             survivor_types: initial_agents(),
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
         };
         state.report_battle_results(&battle_result).unwrap();
         assert_eq!(state.mode(), Mode::Won);
@@ -402,47 +1603,134 @@ mod tests {
 
     #[test]
     fn short_fail_path() {
-        let mut state = State::new(campaign_plan_short(), agent_info_empty());
+        let mut state = State::new(campaign_plan_short(), agent_info_empty(), HashMap::new());
         assert!(state.available_actions().is_empty());
         assert_eq!(state.mode(), Mode::PreparingForBattle);
         let battle_result = BattleResult {
             winner_id: PlayerId(1),
             survivor_types: vec![],
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
         };
         state.report_battle_results(&battle_result).unwrap();
         assert_eq!(state.last_battle_casualties().to_vec(), initial_agents());
         assert_eq!(state.mode(), Mode::Failed);
     }
 
+    #[test]
+    fn retreat_keeps_survivors_without_award() {
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
+        let renown = state.renown();
+        let battle_result = BattleResult {
+            winner_id: PlayerId(1),
+            survivor_types: initial_agents(),
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: true,
+            control_renown: 0,
+        };
+        state.report_battle_results(&battle_result).unwrap();
+        assert_eq!(state.mode(), Mode::PreparingForBattle);
+        assert_eq!(state.current_node(), 0);
+        assert_eq!(state.agents(), initial_agents().as_slice());
+        assert!(state.last_battle_casualties().is_empty());
+        assert!(state.available_actions().is_empty());
+        assert_eq!(state.renown(), renown);
+    }
+
+    #[test]
+    fn retreat_is_not_a_defeat_in_the_summary() {
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
+        let battle_result = |is_retreat| BattleResult {
+            winner_id: if is_retreat { PlayerId(1) } else { PlayerId(0) },
+            survivor_types: initial_agents(),
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat,
+            control_renown: 0,
+        };
+        for is_retreat in [true, false, false] {
+            state
+                .report_battle_results(&battle_result(is_retreat))
+                .unwrap();
+        }
+        assert_eq!(state.mode(), Mode::Won);
+        let summary = state.summary().unwrap();
+        assert!(summary.is_won);
+        assert_eq!(summary.battles_won, 2);
+        let expected = vec![TurningPoint::Retreat {
+            node: "first".into(),
+        }];
+        assert_eq!(summary.turning_points, expected);
+    }
+
     #[test]
     fn bad_survivors() {
-        let mut state = State::new(campaign_plan_short(), agent_info_empty());
+        let mut state = State::new(campaign_plan_short(), agent_info_empty(), HashMap::new());
         let battle_result = BattleResult {
             winner_id: PlayerId(1),
             survivor_types: vec!["imp".into()],
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
         };
         assert!(state.report_battle_results(&battle_result).is_err());
     }
 
     #[test]
     fn bad_battle_win_no_survivors() {
-        let mut state = State::new(campaign_plan_short(), agent_info_empty());
+        let mut state = State::new(campaign_plan_short(), agent_info_empty(), HashMap::new());
         let battle_result = BattleResult {
             winner_id: PlayerId(0),
             survivor_types: vec![],
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
         };
         assert!(state.report_battle_results(&battle_result).is_err());
     }
 
     #[test]
     fn recruit_and_casualty() {
-        let mut state = State::new(campaign_plan_two_battles(), agent_info_empty());
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
         assert!(state.available_actions().is_empty());
         assert_eq!(state.mode(), Mode::PreparingForBattle);
         {
             let battle_result = BattleResult {
                 winner_id: PlayerId(0),
                 survivor_types: initial_agents(),
+                veterans: Vec::new(),
+                fallen: Vec::new(),
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
             };
             state.report_battle_results(&battle_result).unwrap();
         }
@@ -463,6 +1751,12 @@ mod tests {
             let battle_result = BattleResult {
                 winner_id: PlayerId(0),
                 survivor_types: initial_agents(),
+                veterans: Vec::new(),
+                fallen: Vec::new(),
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
             };
             state.report_battle_results(&battle_result).unwrap();
         }
@@ -475,6 +1769,7 @@ mod tests {
         let mut state = State::new(
             campaign_plan_two_battles(),
             agent_info_heavy_swordsman_upgrade(),
+            HashMap::new(),
         );
         assert!(state.available_actions().is_empty());
         assert_eq!(state.mode(), Mode::PreparingForBattle);
@@ -482,6 +1777,12 @@ mod tests {
             let battle_result = BattleResult {
                 winner_id: PlayerId(0),
                 survivor_types: initial_agents(),
+                veterans: Vec::new(),
+                fallen: Vec::new(),
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
             };
             state.report_battle_results(&battle_result).unwrap();
         }
@@ -505,10 +1806,572 @@ mod tests {
             let battle_result = BattleResult {
                 winner_id: PlayerId(0),
                 survivor_types: vec!["alchemist".into()],
+                veterans: Vec::new(),
+                fallen: Vec::new(),
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
             };
             state.report_battle_results(&battle_result).unwrap();
         }
         assert_eq!(state.mode(), Mode::Won);
         assert_eq!(state.last_battle_casualties(), &["heavy_swordsman".into()]);
     }
+
+    #[test]
+    fn veteran_levels_up() {
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
+        {
+            let battle_result = BattleResult {
+                winner_id: PlayerId(0),
+                survivor_types: vec!["alchemist".into()],
+                veterans: vec![Veteran { index: 1, kills: 2 }],
+                fallen: Vec::new(),
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
+            };
+            state.report_battle_results(&battle_result).unwrap();
+        }
+        assert_eq!(state.agents(), &["alchemist".into()]);
+        assert_eq!(state.agents_progress()[0].xp, 3);
+        let action_level_up = Action::LevelUp {
+            agent_index: 0,
+            perk: Perk::Strength,
+        };
+        let action_recruit = Action::Recruit {
+            agent_type: "spearman".into(),
+        };
+        assert_eq!(
+            state.available_actions(),
+            &[action_recruit.clone(), action_level_up.clone()]
+        );
+        state.execute_action(action_level_up);
+        assert_eq!(state.available_actions(), &[action_recruit]);
+        let progress = &state.agents_progress()[0];
+        assert_eq!(progress.level, 1);
+        assert_eq!(progress.xp, 0);
+        assert_eq!(progress.perks, vec![Perk::Strength]);
+    }
+
+    #[test]
+    fn veteran_upgrades_ability() {
+        let mut agent_info = agent_info_empty();
+        agent_info.insert(
+            "alchemist".into(),
+            AgentInfo {
+                upgrades: Vec::new(),
+                cost: 10.into(),
+                learnable_abilities: Vec::new(),
+                upgradable_abilities: vec![Ability::Heal],
+            },
+        );
+        let mut state = State::new(campaign_plan_two_battles(), agent_info, HashMap::new());
+        {
+            let battle_result = BattleResult {
+                winner_id: PlayerId(0),
+                survivor_types: vec!["alchemist".into()],
+                veterans: vec![Veteran { index: 1, kills: 2 }],
+                fallen: Vec::new(),
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
+            };
+            state.report_battle_results(&battle_result).unwrap();
+        }
+        let action_upgrade = Action::LevelUp {
+            agent_index: 0,
+            perk: Perk::Upgrade(Ability::Heal),
+        };
+        assert!(state.available_actions().contains(&action_upgrade));
+        state.execute_action(action_upgrade);
+        let heal = component::Abilities(vec![Ability::Heal.into()]);
+        let prototypes = Prototypes(
+            vec![("alchemist".into(), vec![heal.into()])]
+                .into_iter()
+                .collect(),
+        );
+        let tiers: Vec<component::AbilityTiers> = state
+            .battle_scenario(&prototypes)
+            .randomized_objects
+            .iter()
+            .flat_map(|group| &group.extra_components)
+            .filter_map(|component| match component {
+                Component::AbilityTiers(tiers) => Some(tiers.clone()),
+                _ => None,
+            })
+            .collect();
+        let expected =
+            component::AbilityTiers(vec![(Ability::Heal, Tier(2))].into_iter().collect());
+        assert_eq!(tiers, vec![expected]);
+    }
+
+    #[test]
+    fn fallen_agents_are_remembered() {
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
+        {
+            let battle_result = BattleResult {
+                winner_id: PlayerId(0),
+                survivor_types: vec!["alchemist".into()],
+                veterans: vec![Veteran { index: 1, kills: 1 }],
+                fallen: vec![FallenVeteran {
+                    veteran: Veteran { index: 0, kills: 2 },
+                    cause: DeathCause::Effect(Lasting::Poison),
+                }],
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
+            };
+            state.report_battle_results(&battle_result).unwrap();
+        }
+        let progress = &state.agents_progress()[0];
+        assert_eq!(progress.battles_survived, 1);
+        assert_eq!(progress.kills, 1);
+        let expected = FallenAgent {
+            agent_type: "swordsman".into(),
+            name: None,
+            level: 0,
+            battles_survived: 0,
+            kills: 2,
+            cause: DeathCause::Effect(Lasting::Poison),
+            node: "first".into(),
+        };
+        assert_eq!(state.fallen_agents(), &[expected]);
+    }
+
+    #[test]
+    fn campaign_summary() {
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
+        assert_eq!(state.summary(), None);
+        {
+            let battle_result = BattleResult {
+                winner_id: PlayerId(0),
+                survivor_types: vec!["alchemist".into()],
+                veterans: vec![Veteran { index: 1, kills: 1 }],
+                fallen: vec![FallenVeteran {
+                    veteran: Veteran { index: 0, kills: 1 },
+                    cause: DeathCause::Effect(Lasting::Poison),
+                }],
+                charges: HashMap::new(),
+                ability_uses: vec![(Ability::Heal, 2), (Ability::Bomb, 1)]
+                    .into_iter()
+                    .collect(),
+                is_retreat: false,
+                control_renown: 0,
+            };
+            state.report_battle_results(&battle_result).unwrap();
+        }
+        assert_eq!(state.summary(), None);
+        {
+            let battle_result = BattleResult {
+                winner_id: PlayerId(0),
+                survivor_types: vec!["alchemist".into()],
+                veterans: vec![Veteran { index: 0, kills: 3 }],
+                fallen: Vec::new(),
+                charges: HashMap::new(),
+                ability_uses: vec![(Ability::Bomb, 2)].into_iter().collect(),
+                is_retreat: false,
+                control_renown: 0,
+            };
+            state.report_battle_results(&battle_result).unwrap();
+        }
+        assert_eq!(state.mode(), Mode::Won);
+        let summary = state.summary().unwrap();
+        assert!(summary.is_won);
+        assert_eq!(summary.battles, 2);
+        assert_eq!(summary.battles_won, 2);
+        assert_eq!(summary.kills, 5);
+        assert_eq!(summary.casualties, 1);
+        assert_eq!(summary.score(), 500 + 2 * 50 + 5 * 10 - 25);
+        let mvp = summary.mvp.unwrap();
+        assert_eq!(mvp.agent_type, "alchemist".into());
+        assert_eq!(mvp.kills, 4);
+        assert!(!mvp.is_fallen);
+        assert_eq!(summary.favorite_ability, Some(Ability::Bomb));
+        assert_eq!(summary.favorite_unit, Some("alchemist".into()));
+        let expected = vec![
+            TurningPoint::FirstLoss {
+                node: "first".into(),
+            },
+            TurningPoint::FlawlessVictory {
+                node: "second".into(),
+                kills: 3,
+            },
+        ];
+        assert_eq!(summary.turning_points, expected);
+    }
+
+    #[test]
+    fn hall_of_fame_keeps_the_best_runs() {
+        let run = |score| Run {
+            is_won: false,
+            score,
+            battles_won: 0,
+            kills: 0,
+            casualties: 0,
+            favorite_unit: None,
+        };
+        let mut hall = HallOfFame::default();
+        assert_eq!(hall.add(run(100)), Some(0));
+        assert_eq!(hall.add(run(300)), Some(0));
+        assert_eq!(hall.add(run(100)), Some(2));
+        for _ in 0..7 {
+            hall.add(run(200));
+        }
+        assert_eq!(hall.runs().len(), 10);
+        assert_eq!(hall.add(run(50)), None);
+        assert_eq!(hall.add(run(250)), Some(1));
+        assert_eq!(hall.runs().len(), 10);
+        let scores: Vec<i32> = hall.runs().iter().map(|r| r.score).collect();
+        assert_eq!(scores, [300, 250, 200, 200, 200, 200, 200, 200, 200, 100]);
+    }
+
+    #[test]
+    fn knocked_out_agent_recovers() {
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
+        {
+            let battle_result = BattleResult {
+                winner_id: PlayerId(0),
+                survivor_types: vec!["alchemist".into()],
+                veterans: vec![Veteran { index: 1, kills: 0 }],
+                fallen: vec![FallenVeteran {
+                    veteran: Veteran { index: 0, kills: 0 },
+                    cause: DeathCause::Attack("imp".into()),
+                }],
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
+            };
+            state.report_battle_results(&battle_result).unwrap();
+        }
+        assert_eq!(state.agents().to_vec(), initial_agents());
+        assert!(state.last_battle_casualties().is_empty());
+        assert!(state.fallen_agents().is_empty());
+        assert_eq!(state.agents_progress()[0].injured_for, 2);
+        assert_eq!(state.battle_participants(), vec![1]);
+        {
+            let battle_result = BattleResult {
+                winner_id: PlayerId(0),
+                survivor_types: vec!["alchemist".into()],
+                veterans: vec![Veteran { index: 1, kills: 0 }],
+                fallen: Vec::new(),
+                charges: HashMap::new(),
+                ability_uses: HashMap::new(),
+                is_retreat: false,
+                control_renown: 0,
+            };
+            state.report_battle_results(&battle_result).unwrap();
+        }
+        assert_eq!(state.agents_progress()[0].injured_for, 1);
+        assert_eq!(state.agents_progress()[1].battles_survived, 2);
+    }
+
+    #[test]
+    fn buy_from_shop() {
+        let mut plan = campaign_plan_two_battles();
+        let recruit = Offer {
+            goods: Goods::Recruit("spearman".into()),
+            price: 15.into(),
+        };
+        let boon = Offer {
+            goods: Goods::Boon(Boon::Strength),
+            price: 5.into(),
+        };
+        plan.nodes[0].shop = vec![recruit.clone(), boon.clone()];
+        let mut state = State::new(plan, agent_info_empty(), HashMap::new());
+        assert!(state.shop().is_empty());
+        let battle_result = BattleResult {
+            winner_id: PlayerId(0),
+            survivor_types: initial_agents(),
+            veterans: vec![
+                Veteran { index: 0, kills: 0 },
+                Veteran { index: 1, kills: 0 },
+            ],
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
+        };
+        state.report_battle_results(&battle_result).unwrap();
+        assert_eq!(state.renown(), 20.into());
+        state.buy(boon.clone());
+        assert_eq!(state.renown(), 15.into());
+        assert_eq!(state.boons().to_vec(), vec![Boon::Strength]);
+        assert!(!state.can_buy(&boon));
+        state.buy(recruit.clone());
+        assert_eq!(state.renown(), 0.into());
+        assert_eq!(state.agents().len(), 3);
+        assert!(state.shop().is_empty());
+    }
+
+    #[test]
+    fn ability_charges_last_until_rest() {
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
+        state.set_ability_uses(AbilityUses::PerDay);
+        let heal = component::Abilities(vec![Ability::Heal.into()]);
+        let tiers = component::AbilityTiers(vec![(Ability::Heal, Tier(2))].into_iter().collect());
+        let prototypes = Prototypes(
+            vec![
+                ("swordsman".into(), Vec::new()),
+                ("alchemist".into(), vec![heal.into(), tiers.into()]),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let charges_in_battle = |state: &State| -> Vec<Charges> {
+            state
+                .battle_scenario(&prototypes)
+                .randomized_objects
+                .iter()
+                .flat_map(|group| &group.extra_components)
+                .filter_map(|component| match component {
+                    Component::Charges(charges) => Some(charges.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let charges = |n| Charges(vec![(Ability::Heal, n)].into_iter().collect());
+        assert_eq!(charges_in_battle(&state), vec![charges(2)]);
+        let battle_result = BattleResult {
+            winner_id: PlayerId(0),
+            survivor_types: initial_agents(),
+            veterans: vec![
+                Veteran { index: 0, kills: 0 },
+                Veteran { index: 1, kills: 0 },
+            ],
+            fallen: Vec::new(),
+            charges: vec![(1, charges(0))].into_iter().collect(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
+        };
+        state.report_battle_results(&battle_result).unwrap();
+        assert_eq!(charges_in_battle(&state), vec![charges(0)]);
+        assert!(state.available_actions().contains(&Action::Rest));
+        state.execute_action(Action::Rest);
+        assert_eq!(charges_in_battle(&state), vec![charges(2)]);
+    }
+
+    #[test]
+    fn visit_camp() {
+        let mut plan = campaign_plan_two_battles();
+        plan.nodes.push(CampaignNode {
+            name: "camp".into(),
+            requires: vec!["first".into()],
+            scenario: Scenario::default(),
+            award: Award::default(),
+            camp: Some(Camp {
+                actions: 2,
+                choices: vec![CampChoice::Heal, CampChoice::Craft("charm".into())],
+            }),
+            shop: Vec::new(),
+        });
+        plan.nodes[1].requires.push("camp".into());
+        let mut state = State::new(plan, agent_info_empty(), item_info());
+        let battle_result = BattleResult {
+            winner_id: PlayerId(0),
+            survivor_types: vec!["alchemist".into()],
+            veterans: vec![Veteran { index: 1, kills: 0 }],
+            fallen: vec![FallenVeteran {
+                veteran: Veteran { index: 0, kills: 0 },
+                cause: DeathCause::Attack("imp".into()),
+            }],
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
+        };
+        state.report_battle_results(&battle_result).unwrap();
+        assert!(state.agents_progress()[0].is_injured());
+        state.visit_camp(2);
+        assert_eq!(state.completed_nodes(), &[0, 2]);
+        state.make_camp_choice(CampChoice::Heal, Some(0));
+        assert!(!state.agents_progress()[0].is_injured());
+        let craft = CampChoice::Craft("charm".into());
+        state.make_camp_choice(craft.clone(), None);
+        assert_eq!(state.items(), &["charm".into()]);
+        assert!(!state.can_make_camp_choice(&CampChoice::Heal));
+        assert!(!state.can_make_camp_choice(&craft));
+        assert_eq!(state.available_nodes(), vec![1]);
+    }
+
+    #[test]
+    fn rename_agents() {
+        let mut state = State::new(
+            campaign_plan_two_battles(),
+            agent_info_empty(),
+            HashMap::new(),
+        );
+        state.rename(0, "  Boris ");
+        state.set_portrait(1, 2);
+        assert_eq!(state.agents_progress()[0].name, Some("Boris".into()));
+        let prototypes = Prototypes(
+            initial_agents()
+                .into_iter()
+                .map(|typename| (typename, Vec::new()))
+                .collect(),
+        );
+        let scenario = state.battle_scenario(&prototypes);
+        let personas: Vec<Persona> = scenario
+            .randomized_objects
+            .iter()
+            .flat_map(|group| &group.extra_components)
+            .filter_map(|component| match component {
+                Component::Persona(persona) => Some(persona.clone()),
+                _ => None,
+            })
+            .collect();
+        let boris = Persona {
+            name: Some("Boris".into()),
+            portrait: 0,
+        };
+        let alchemist = Persona {
+            name: None,
+            portrait: 2,
+        };
+        assert_eq!(personas, vec![boris, alchemist]);
+        state.rename(0, "");
+        assert_eq!(state.agents_progress()[0].name, None);
+    }
+
+    #[test]
+    fn equip_and_recover_items() {
+        let plan = Plan {
+            initial_items: vec!["spear".into(), "axe".into(), "charm".into()],
+            ..campaign_plan_two_battles()
+        };
+        let mut state = State::new(plan, agent_info_empty(), item_info());
+        state.equip(0, "spear".into());
+        state.equip(0, "charm".into());
+        assert_eq!(state.items(), &["axe".into()]);
+        // The old weapon goes back to the stash.
+        state.equip(0, "axe".into());
+        assert_eq!(state.items(), &["spear".into()]);
+        let equipment = &state.agents_progress()[0].equipment;
+        assert_eq!(equipment.weapon, Some("axe".into()));
+        assert_eq!(equipment.trinket, Some("charm".into()));
+        state.unequip(0, Slot::Trinket);
+        assert_eq!(state.items(), &["spear".into(), "charm".into()]);
+        // The swordsman dies, but the axe is recovered.
+        let battle_result = BattleResult {
+            winner_id: PlayerId(0),
+            survivor_types: vec!["alchemist".into()],
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
+        };
+        state.report_battle_results(&battle_result).unwrap();
+        assert_eq!(
+            state.items(),
+            &["spear".into(), "charm".into(), "axe".into()]
+        );
+    }
+
+    #[test]
+    fn branching_path() {
+        let node = |name: &str, requires: &[&str]| CampaignNode {
+            name: name.into(),
+            requires: requires.iter().map(|&s| s.into()).collect(),
+            scenario: Scenario::default(),
+            award: Award {
+                recruits: Vec::new(),
+                items: Vec::new(),
+                renown: 10.into(),
+            },
+            shop: Vec::new(),
+            camp: None,
+        };
+        let plan = Plan {
+            nodes: vec![
+                node("start", &[]),
+                node("forest", &["start"]),
+                node("swamp", &["start"]),
+                node("castle", &["forest", "swamp"]),
+            ],
+            ..campaign_plan_short()
+        };
+        let mut state = State::new(plan, agent_info_empty(), HashMap::new());
+        assert_eq!(state.node_depths(), vec![0, 1, 1, 2]);
+        assert_eq!(state.available_nodes(), vec![0]);
+        let battle_result = BattleResult {
+            winner_id: PlayerId(0),
+            survivor_types: initial_agents(),
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
+        };
+        state.report_battle_results(&battle_result).unwrap();
+        assert_eq!(state.available_nodes(), vec![1, 2]);
+        state.select_node(2);
+        state.report_battle_results(&battle_result).unwrap();
+        assert_eq!(state.completed_nodes(), &[0, 2]);
+        assert_eq!(state.available_nodes(), vec![3]);
+        assert_eq!(state.current_node(), 3);
+        state.report_battle_results(&battle_result).unwrap();
+        assert_eq!(state.mode(), Mode::Won);
+    }
+
+    #[test]
+    fn ironman_state_survives_saving() {
+        let plan = Plan {
+            initial_items: vec!["spear".into()],
+            ..campaign_plan_two_battles()
+        };
+        let mut state = State::new(plan, agent_info_empty(), item_info());
+        state.set_save_mode(SaveMode::Ironman);
+        state.rename(0, "Boris");
+        state.equip(0, "spear".into());
+        let battle_result = BattleResult {
+            winner_id: PlayerId(0),
+            survivor_types: initial_agents(),
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
+        };
+        state.report_battle_results(&battle_result).unwrap();
+        let saved = ron::ser::to_string(&state).unwrap();
+        let loaded: State = ron::de::from_str(&saved).unwrap();
+        assert_eq!(loaded.save_mode(), SaveMode::Ironman);
+        assert_eq!(loaded.mode(), state.mode());
+        assert_eq!(loaded.completed_nodes(), state.completed_nodes());
+        assert_eq!(loaded.current_node(), state.current_node());
+        assert_eq!(loaded.agents(), state.agents());
+        assert_eq!(loaded.agents_progress(), state.agents_progress());
+        assert_eq!(loaded.renown(), state.renown());
+    }
 }
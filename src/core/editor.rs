@@ -0,0 +1,280 @@
+//! Hand-made scenarios, painted tile by tile.
+
+use crate::core::{
+    battle::{
+        component::ObjType,
+        objective::Objective,
+        scenario::{Object, Scenario},
+        PlayerId, TileType,
+    },
+    map::{self, Distance, PosHex},
+};
+
+/// Bigger maps don't fit the editor's screen.
+pub const MAX_MAP_RADIUS: Distance = Distance(6);
+pub const MIN_MAP_RADIUS: Distance = Distance(3);
+
+/// The choices of `Objective::SurviveNTurns`, `None` means no such objective.
+const SURVIVE_TURNS: [Option<i32>; 4] = [None, Some(5), Some(8), Some(12)];
+
+const FIGHTERS: [&str; 6] = [
+    "swordsman",
+    "spearman",
+    "hammerman",
+    "alchemist",
+    "healer",
+    "firer",
+];
+const ENEMIES: [&str; 6] = [
+    "imp",
+    "toxic_imp",
+    "imp_bomber",
+    "imp_summoner",
+    "imp_necromancer",
+    "imp_warlord",
+];
+const NEUTRAL_OBJECTS: [&str; 3] = ["boulder", "spike_trap", "torch"];
+
+/// What a click on a tile does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Brush {
+    Tile(TileType),
+
+    Object {
+        owner: Option<PlayerId>,
+        typename: ObjType,
+    },
+
+    /// Toggles an `Objective::ReachHex` at the tile.
+    ReachHex,
+
+    /// Clears the tile: removes the object and the objective, makes the terrain plain.
+    Erase,
+}
+
+impl Brush {
+    pub fn title(&self) -> String {
+        match self {
+            Brush::Tile(TileType::Plain) => "plain".into(),
+            Brush::Tile(TileType::Rocks) => "rocks".into(),
+            Brush::Tile(TileType::Water) => "water".into(),
+            Brush::Tile(TileType::Thicket) => "thicket".into(),
+            Brush::Object { typename, .. } => typename.0.replace('_', " "),
+            Brush::ReachHex => "tile to reach".into(),
+            Brush::Erase => "erase".into(),
+        }
+    }
+}
+
+/// The brushes that don't place objects.
+pub fn terrain_brushes() -> Vec<Brush> {
+    vec![
+        Brush::Tile(TileType::Plain),
+        Brush::Tile(TileType::Rocks),
+        Brush::Tile(TileType::Water),
+        Brush::Tile(TileType::Thicket),
+        Brush::ReachHex,
+        Brush::Erase,
+    ]
+}
+
+/// The objects that can be placed, grouped by the owner.
+pub fn object_brushes() -> Vec<(Option<PlayerId>, Vec<Brush>)> {
+    let group = |owner, names: &[&str]| {
+        let brushes = names
+            .iter()
+            .map(|&name| Brush::Object {
+                owner,
+                typename: name.into(),
+            })
+            .collect();
+        (owner, brushes)
+    };
+    vec![
+        group(Some(PlayerId(0)), &FIGHTERS),
+        group(Some(PlayerId(1)), &ENEMIES),
+        group(None, &NEUTRAL_OBJECTS),
+    ]
+}
+
+/// A scenario that is being edited.
+#[derive(Debug, Clone, Default)]
+pub struct Draft {
+    scenario: Scenario,
+}
+
+impl Draft {
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn is_inboard(&self, pos: PosHex) -> bool {
+        let origin = PosHex { q: 0, r: 0 };
+        map::distance_hex(origin, pos) <= self.scenario.map_radius
+    }
+
+    pub fn is_tile_to_reach(&self, pos: PosHex) -> bool {
+        self.scenario.objectives.contains(&Objective::ReachHex(pos))
+    }
+
+    /// Switches to the next map size, everything that doesn't fit is dropped.
+    pub fn next_map_radius(&mut self) {
+        let radius = self.scenario.map_radius.0 + 1;
+        self.scenario.map_radius = if radius > MAX_MAP_RADIUS.0 {
+            MIN_MAP_RADIUS
+        } else {
+            Distance(radius)
+        };
+        let origin = PosHex { q: 0, r: 0 };
+        let radius = self.scenario.map_radius;
+        let is_inboard = |pos| map::distance_hex(origin, pos) <= radius;
+        self.scenario.objects.retain(|obj| is_inboard(obj.pos));
+        self.scenario.tiles.retain(|&pos, _| is_inboard(pos));
+        self.scenario
+            .objectives
+            .retain(|objective| match *objective {
+                Objective::ReachHex(pos) | Objective::Escort { exit: pos, .. } => is_inboard(pos),
+                _ => true,
+            });
+    }
+
+    pub fn survive_turns(&self) -> Option<i32> {
+        self.scenario
+            .objectives
+            .iter()
+            .find_map(|objective| match *objective {
+                Objective::SurviveNTurns(turns) => Some(turns),
+                _ => None,
+            })
+    }
+
+    pub fn next_survive_turns(&mut self) {
+        let current = self.survive_turns();
+        let index = SURVIVE_TURNS.iter().position(|&turns| turns == current);
+        let next = SURVIVE_TURNS[index.map_or(0, |i| (i + 1) % SURVIVE_TURNS.len())];
+        let objectives = &mut self.scenario.objectives;
+        objectives.retain(|objective| !matches!(objective, Objective::SurviveNTurns(_)));
+        if let Some(turns) = next {
+            objectives.push(Objective::SurviveNTurns(turns));
+        }
+    }
+
+    pub fn apply(&mut self, brush: &Brush, pos: PosHex) {
+        assert!(self.is_inboard(pos));
+        match brush {
+            Brush::Tile(TileType::Plain) => {
+                self.scenario.tiles.remove(&pos);
+            }
+            &Brush::Tile(tile) => {
+                self.scenario.tiles.insert(pos, tile);
+            }
+            Brush::Object { owner, typename } => {
+                self.remove_object(pos);
+                self.scenario.objects.push(Object {
+                    owner: *owner,
+                    typename: typename.clone(),
+                    pos,
+                });
+            }
+            Brush::ReachHex => {
+                let objective = Objective::ReachHex(pos);
+                if self.is_tile_to_reach(pos) {
+                    self.scenario.objectives.retain(|other| *other != objective);
+                } else {
+                    self.scenario.objectives.push(objective);
+                }
+            }
+            Brush::Erase => {
+                self.remove_object(pos);
+                self.scenario.tiles.remove(&pos);
+                let objective = Objective::ReachHex(pos);
+                self.scenario.objectives.retain(|other| *other != objective);
+            }
+        }
+    }
+
+    fn remove_object(&mut self, pos: PosHex) {
+        self.scenario.objects.retain(|obj| obj.pos != pos);
+    }
+
+    /// The scenario in the same format as the scenarios in the assets.
+    pub fn to_ron(&self) -> ron::Result<String> {
+        ron::ser::to_string_pretty(&self.scenario, ron::ser::PrettyConfig::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{
+        battle::{objective::Objective, scenario::Scenario, PlayerId, TileType},
+        editor::{Brush, Draft, MIN_MAP_RADIUS},
+        map::PosHex,
+    };
+
+    fn agent(owner: i32, typename: &str) -> Brush {
+        Brush::Object {
+            owner: Some(PlayerId(owner)),
+            typename: typename.into(),
+        }
+    }
+
+    #[test]
+    fn brushes_replace_and_erase() {
+        let mut draft = Draft::default();
+        let pos = PosHex { q: 1, r: 1 };
+        draft.apply(&agent(0, "swordsman"), pos);
+        draft.apply(&agent(1, "imp"), pos);
+        let objects = &draft.scenario().objects;
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].typename, "imp".into());
+        draft.apply(&Brush::Tile(TileType::Rocks), pos);
+        draft.apply(&Brush::ReachHex, pos);
+        assert!(draft.is_tile_to_reach(pos));
+        draft.apply(&Brush::Erase, pos);
+        assert!(draft.scenario().objects.is_empty());
+        assert!(draft.scenario().tiles.is_empty());
+        assert!(!draft.is_tile_to_reach(pos));
+    }
+
+    #[test]
+    fn smaller_map_drops_everything_outside() {
+        let mut draft = Draft::default();
+        let far = PosHex { q: 5, r: 0 };
+        draft.apply(&agent(1, "imp"), far);
+        draft.apply(&Brush::ReachHex, far);
+        draft.next_map_radius();
+        draft.next_map_radius();
+        assert_eq!(draft.scenario().map_radius, MIN_MAP_RADIUS);
+        assert!(draft.scenario().objects.is_empty());
+        assert!(draft.scenario().objectives.is_empty());
+    }
+
+    #[test]
+    fn survive_turns_cycle() {
+        let mut draft = Draft::default();
+        assert_eq!(draft.survive_turns(), None);
+        draft.next_survive_turns();
+        assert_eq!(draft.survive_turns(), Some(5));
+        draft.next_survive_turns();
+        draft.next_survive_turns();
+        draft.next_survive_turns();
+        assert_eq!(draft.survive_turns(), None);
+        assert!(draft.scenario().objectives.is_empty());
+    }
+
+    #[test]
+    fn saved_draft_loads_back() {
+        let mut draft = Draft::default();
+        draft.apply(&agent(0, "swordsman"), PosHex { q: -2, r: 0 });
+        draft.apply(&agent(1, "imp"), PosHex { q: 2, r: 0 });
+        draft.apply(&Brush::Tile(TileType::Rocks), PosHex { q: 0, r: 1 });
+        draft.apply(&Brush::ReachHex, PosHex { q: 3, r: -1 });
+        let s = draft.to_ron().unwrap();
+        let scenario: Scenario = ron::de::from_str(&s).unwrap();
+        scenario.check().unwrap();
+        assert_eq!(scenario.objects.len(), 2);
+        assert_eq!(scenario.tiles, draft.scenario().tiles);
+        let objective = Objective::ReachHex(PosHex { q: 3, r: -1 });
+        assert_eq!(scenario.objectives, vec![objective]);
+    }
+}
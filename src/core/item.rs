@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::battle::component;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct ItemType(pub String);
+
+impl From<&str> for ItemType {
+    fn from(s: &str) -> Self {
+        ItemType(s.into())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Slot {
+    Weapon,
+    Trinket,
+}
+
+/// A piece of equipment that modifies the stats of the agent that carries it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Item {
+    pub slot: Slot,
+
+    #[serde(default)]
+    pub attack_distance: i32,
+
+    #[serde(default)]
+    pub attack_strength: i32,
+
+    #[serde(default)]
+    pub attack_accuracy: i32,
+
+    #[serde(default)]
+    pub dodge: i32,
+
+    #[serde(default)]
+    pub move_points: i32,
+
+    /// Extra jokers granted every turn.
+    #[serde(default)]
+    pub jokers: i32,
+}
+
+impl Item {
+    pub fn apply(&self, agent: &mut component::Agent) {
+        agent.attack_distance.0 = (agent.attack_distance.0 + self.attack_distance).max(1);
+        agent.attack_strength.0 = (agent.attack_strength.0 + self.attack_strength).max(0);
+        agent.attack_accuracy.0 += self.attack_accuracy;
+        agent.dodge.0 += self.dodge;
+        agent.move_points.0 = (agent.move_points.0 + self.move_points).max(0);
+        agent.base_jokers.0 = (agent.base_jokers.0 + self.jokers).max(0);
+        agent.jokers.0 = (agent.jokers.0 + self.jokers).max(0);
+    }
+
+    /// The non-zero stat modifiers with the stats' names, like `(-1, "move")`.
+    pub fn modifiers(&self) -> Vec<(i32, &'static str)> {
+        let modifiers = [
+            (self.attack_distance, "distance"),
+            (self.attack_strength, "damage"),
+            (self.attack_accuracy, "accuracy"),
+            (self.dodge, "dodge"),
+            (self.move_points, "move"),
+            (self.jokers, "joker"),
+        ];
+        modifiers
+            .iter()
+            .copied()
+            .filter(|&(value, _)| value != 0)
+            .collect()
+    }
+}
+
+/// Items that an agent carries: one of each slot.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct Equipment {
+    pub weapon: Option<ItemType>,
+    pub trinket: Option<ItemType>,
+}
+
+impl Equipment {
+    pub fn slot(&self, slot: Slot) -> &Option<ItemType> {
+        match slot {
+            Slot::Weapon => &self.weapon,
+            Slot::Trinket => &self.trinket,
+        }
+    }
+
+    pub fn slot_mut(&mut self, slot: Slot) -> &mut Option<ItemType> {
+        match slot {
+            Slot::Weapon => &mut self.weapon,
+            Slot::Trinket => &mut self.trinket,
+        }
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &ItemType> {
+        self.weapon.iter().chain(self.trinket.iter())
+    }
+}
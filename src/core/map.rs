@@ -3,6 +3,8 @@ use std::{fmt::Debug, iter::repeat};
 use num::{Num, Signed};
 use serde::{Deserialize, Serialize};
 
+pub mod generator;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Distance(pub i32);
 
@@ -81,20 +83,30 @@ fn is_inboard(radius: Distance, pos: PosHex) -> bool {
     distance_hex(origin, pos) <= radius
 }
 
+fn hex_to_index(radius: Distance, hex: PosHex) -> usize {
+    let size = radius_to_diameter(radius);
+    let i = (hex.r + radius.0) + (hex.q + radius.0) * size.0;
+    i as usize
+}
+
 #[derive(Clone, Debug)]
 pub struct HexIter {
     cursor: PosHex,
     radius: Distance,
+
+    /// A copy of `HexMap::masked`.
+    masked: Vec<bool>,
 }
 
 impl HexIter {
-    fn new(radius: Distance) -> Self {
+    fn new(radius: Distance, masked: Vec<bool>) -> Self {
         let mut iter = Self {
             cursor: PosHex {
                 q: -radius.0,
                 r: -radius.0,
             },
             radius,
+            masked,
         };
         iter.inc_cursor_with_hex_bounds();
         iter
@@ -110,10 +122,17 @@ impl HexIter {
 
     fn inc_cursor_with_hex_bounds(&mut self) {
         self.inc_cursor();
-        while !is_inboard(self.radius, self.cursor) && self.cursor.r < self.radius.0 + 1 {
+        while !self.is_cursor_inboard() && self.cursor.r < self.radius.0 + 1 {
             self.inc_cursor();
         }
     }
+
+    fn is_cursor_inboard(&self) -> bool {
+        if !is_inboard(self.radius, self.cursor) {
+            return false;
+        }
+        self.masked.is_empty() || !self.masked[hex_to_index(self.radius, self.cursor)]
+    }
 }
 
 impl Iterator for HexIter {
@@ -172,16 +191,21 @@ pub fn radius_to_diameter(radius: Distance) -> Distance {
     Distance(radius.0 * 2 + 1)
 }
 
-///
+/// ```text
 ///     [-1, 0]  [0, -1]
 /// [-1, 1]  [0, 0]  [1, -1]
 ///     [ 0, 1]  [ 1, 0]
+/// ```
 ///
+/// The map is a hexagon, but some of its tiles can be masked out
+/// to get other shapes: masked tiles aren't inboard and aren't iterated over.
 #[derive(Debug, Clone)]
 pub struct HexMap<T: Copy + Debug> {
     tiles: Vec<T>,
-    size: Distance,
     radius: Distance,
+
+    /// `true` for the masked out tiles, empty if there're none.
+    masked: Vec<bool>,
 }
 
 impl<T: Copy + Default + Debug> HexMap<T> {
@@ -191,8 +215,8 @@ impl<T: Copy + Default + Debug> HexMap<T> {
         let tiles = repeat(Default::default()).take(tiles_count).collect();
         Self {
             tiles,
-            size,
             radius,
+            masked: Vec::new(),
         }
     }
 
@@ -205,31 +229,49 @@ impl<T: Copy + Default + Debug> HexMap<T> {
     }
 
     pub fn iter(&self) -> HexIter {
-        HexIter::new(self.radius)
+        HexIter::new(self.radius, self.masked.clone())
     }
 
     pub fn is_inboard(&self, pos: PosHex) -> bool {
-        is_inboard(self.radius, pos)
+        is_inboard(self.radius, pos) && !self.is_masked(pos)
+    }
+
+    pub fn is_masked(&self, pos: PosHex) -> bool {
+        if self.masked.is_empty() || !is_inboard(self.radius, pos) {
+            return false;
+        }
+        self.masked[self.hex_to_index(pos)]
+    }
+
+    /// Cuts the tile out of the map.
+    pub fn mask_tile(&mut self, pos: PosHex) {
+        assert!(is_inboard(self.radius, pos));
+        if self.masked.is_empty() {
+            self.masked = vec![false; self.tiles.len()];
+        }
+        let index = self.hex_to_index(pos);
+        self.masked[index] = true;
     }
 
     fn hex_to_index(&self, hex: PosHex) -> usize {
-        let i = (hex.r + self.radius.0) + (hex.q + self.radius.0) * self.size.0;
-        i as usize
+        hex_to_index(self.radius, hex)
     }
 
+    // Masked tiles still have their values, so other maps
+    // of the same radius don't have to know about the mask.
     pub fn tile(&self, pos: PosHex) -> T {
-        assert!(self.is_inboard(pos));
+        assert!(is_inboard(self.radius, pos));
         self.tiles[self.hex_to_index(pos)]
     }
 
     pub fn set_tile(&mut self, pos: PosHex, tile: T) {
-        assert!(self.is_inboard(pos));
+        assert!(is_inboard(self.radius, pos));
         let index = self.hex_to_index(pos);
         self.tiles[index] = tile;
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Dir {
     SouthEast,
     East,
@@ -267,6 +309,33 @@ impl Dir {
         }
     }
 
+    pub fn opposite(self) -> Self {
+        Dir::from_int((self.to_int() + 3) % 6)
+    }
+
+    /// Number of 60° turns between the directions, from 0 to 3.
+    pub fn turns_to(self, other: Self) -> i32 {
+        let diff = (self.to_int() - other.to_int()).abs();
+        diff.min(6 - diff)
+    }
+
+    /// Direction of the first step of a straight line between the tiles.
+    ///
+    /// Unlike `get_dir_from_to`, the tiles don't have to be neighbors.
+    pub fn approximate_from_to(from: PosHex, to: PosHex) -> Option<Self> {
+        if from == to {
+            return None;
+        }
+        let n = distance_hex(from, to).0 as f32;
+        // A small nudge to settle the ties of the rounding.
+        let nudge = 1e-3;
+        let step = PosHex {
+            q: from.q as f32 + (to.q - from.q) as f32 / n + nudge,
+            r: from.r as f32 + (to.r - from.r) as f32 / n + nudge,
+        };
+        Some(Dir::get_dir_from_to(from, hex_round(step)))
+    }
+
     pub fn get_dir_from_to(from: PosHex, to: PosHex) -> Self {
         assert_eq!(distance_hex(from, to), Distance(1));
         let diff = [to.q - from.q, to.r - from.r];
@@ -313,7 +382,7 @@ impl Iterator for DirIter {
 
 #[cfg(test)]
 mod tests {
-    use crate::core::map::{Distance, HexMap};
+    use crate::core::map::{dirs, Dir, Distance, HexMap, PosHex};
 
     #[test]
     fn test_map_height() {
@@ -321,4 +390,38 @@ mod tests {
         let height = map.height();
         assert_eq!(height, Distance(7));
     }
+
+    #[test]
+    fn test_masked_tiles() {
+        let mut map: HexMap<u8> = HexMap::new(Distance(3));
+        let origin = PosHex { q: 0, r: 0 };
+        let corner = PosHex { q: 3, r: -3 };
+        assert_eq!(map.iter().count(), 37);
+        map.mask_tile(origin);
+        map.mask_tile(corner);
+        assert!(!map.is_inboard(origin));
+        assert!(map.is_masked(corner));
+        assert!(map.is_inboard(PosHex { q: 1, r: 0 }));
+        assert!(!map.is_masked(PosHex { q: 4, r: 0 }));
+        assert_eq!(map.iter().count(), 35);
+        assert!(map.iter().all(|pos| pos != origin && pos != corner));
+    }
+
+    #[test]
+    fn test_dir_arithmetic() {
+        for dir in dirs() {
+            assert_eq!(dir.opposite().opposite(), dir);
+            assert_eq!(dir.turns_to(dir.opposite()), 3);
+            assert_eq!(dir.turns_to(dir), 0);
+        }
+        assert_eq!(Dir::SouthEast.turns_to(Dir::SouthWest), 1);
+        let origin = PosHex { q: 0, r: 0 };
+        assert_eq!(Dir::approximate_from_to(origin, origin), None);
+        for dir in dirs() {
+            let neighbor = Dir::get_neighbor_pos(origin, dir);
+            let far = Dir::get_neighbor_pos(Dir::get_neighbor_pos(neighbor, dir), dir);
+            assert_eq!(Dir::approximate_from_to(origin, neighbor), Some(dir));
+            assert_eq!(Dir::approximate_from_to(origin, far), Some(dir));
+        }
+    }
 }
@@ -0,0 +1,349 @@
+//! Procedural battlefield layouts.
+//!
+//! The generator only knows about the map: it decides which tiles are cut out,
+//! which tiles are rough and where the obstacles stand.
+//! Turning the layout into terrain and objects is up to the battle.
+
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::core::map::{self, dirs, Dir, Distance, HexMap, PosHex};
+
+/// A preset of the generator's parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Biome {
+    /// An open field with a few lone obstacles.
+    Plains,
+
+    /// Thickets of rough terrain and lots of trees.
+    Forest,
+
+    /// Clusters of broken walls.
+    Ruins,
+
+    /// A chasm splits the map in half, it can only be crossed at a couple of fords.
+    Canyon,
+}
+
+impl Biome {
+    pub fn title(self) -> &'static str {
+        match self {
+            Biome::Plains => "plains",
+            Biome::Forest => "forest",
+            Biome::Ruins => "ruins",
+            Biome::Canyon => "canyon",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Biome::Plains => Biome::Forest,
+            Biome::Forest => Biome::Ruins,
+            Biome::Ruins => Biome::Canyon,
+            Biome::Canyon => Biome::Plains,
+        }
+    }
+
+    pub fn params(self) -> Params {
+        match self {
+            Biome::Plains => Params {
+                chokepoints: 0,
+                rough_clusters: 1,
+                rough_cluster_size: 3,
+                ruin_clusters: 0,
+                obstacles: 2,
+            },
+            Biome::Forest => Params {
+                chokepoints: 0,
+                rough_clusters: 4,
+                rough_cluster_size: 4,
+                ruin_clusters: 0,
+                obstacles: 9,
+            },
+            Biome::Ruins => Params {
+                chokepoints: 0,
+                rough_clusters: 2,
+                rough_cluster_size: 2,
+                ruin_clusters: 2,
+                obstacles: 2,
+            },
+            Biome::Canyon => Params {
+                chokepoints: 2,
+                rough_clusters: 2,
+                rough_cluster_size: 3,
+                ruin_clusters: 0,
+                obstacles: 3,
+            },
+        }
+    }
+}
+
+/// All the counts except `chokepoints` are per 100 tiles, so they scale with the map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Params {
+    /// Fords in a chasm that splits the map between the players, no chasm if zero.
+    pub chokepoints: i32,
+
+    pub rough_clusters: i32,
+    pub rough_cluster_size: i32,
+
+    /// Broken rings of obstacles.
+    pub ruin_clusters: i32,
+
+    /// Lone obstacles.
+    pub obstacles: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layout {
+    pub masked_tiles: Vec<PosHex>,
+    pub rough_tiles: Vec<PosHex>,
+    pub obstacles: Vec<PosHex>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Cell {
+    #[default]
+    Plain,
+    Rough,
+    Obstacle,
+
+    /// Stays plain and free, e.g. the fords and the fixed positions of the scenario.
+    Reserved,
+}
+
+struct Generator<'a, R: Rng> {
+    rng: &'a mut R,
+    map: HexMap<Cell>,
+}
+
+impl<'a, R: Rng> Generator<'a, R> {
+    fn tiles(&self) -> Vec<PosHex> {
+        self.map.iter().collect()
+    }
+
+    fn scaled(&self, count_per_100_tiles: i32) -> i32 {
+        let tiles_count = self.map.iter().count() as i32;
+        (count_per_100_tiles * tiles_count + 50) / 100
+    }
+
+    fn random_tile(&mut self) -> PosHex {
+        *self.tiles().choose(self.rng).expect("Empty map")
+    }
+
+    fn reserve(&mut self, pos: PosHex) {
+        if self.map.is_inboard(pos) {
+            self.map.set_tile(pos, Cell::Reserved);
+        }
+    }
+
+    /// Cuts out the line between the players' halves, except for the fords.
+    fn add_chasm(&mut self, fords_count: i32) {
+        let radius = self.map.radius().0;
+        let height = radius * 2 + 1;
+        let mut fords = Vec::new();
+        for i in 0..fords_count {
+            // Every ford gets its own stretch of the chasm, the map edges are avoided.
+            let from = -radius + 1 + i * (height - 2) / fords_count;
+            let to = -radius + 1 + (i + 1) * (height - 2) / fords_count;
+            fords.push(self.rng.gen_range(from..to.max(from + 1)));
+        }
+        for r in -radius..=radius {
+            let pos = PosHex { q: 0, r };
+            if fords.contains(&r) {
+                self.reserve(pos);
+                // The banks of the ford are kept clear too.
+                for dir in dirs() {
+                    let neighbor = Dir::get_neighbor_pos(pos, dir);
+                    if neighbor.q != 0 {
+                        self.reserve(neighbor);
+                    }
+                }
+            } else if self.map.tile(pos) != Cell::Reserved {
+                self.map.mask_tile(pos);
+            }
+        }
+    }
+
+    fn add_rough_cluster(&mut self, size: i32) {
+        let mut cluster = vec![self.random_tile()];
+        while (cluster.len() as i32) < size {
+            let pos = *cluster.choose(self.rng).unwrap();
+            let dir = Dir::from_int(self.rng.gen_range(0..6));
+            let neighbor = Dir::get_neighbor_pos(pos, dir);
+            if !self.map.is_inboard(neighbor) {
+                break;
+            }
+            if !cluster.contains(&neighbor) {
+                cluster.push(neighbor);
+            }
+        }
+        for pos in cluster {
+            if self.map.tile(pos) == Cell::Plain {
+                self.map.set_tile(pos, Cell::Rough);
+            }
+        }
+    }
+
+    /// Are all the tiles without obstacles reachable from each other?
+    fn is_connected(&self) -> bool {
+        let is_walkable = |pos| self.map.is_inboard(pos) && self.map.tile(pos) != Cell::Obstacle;
+        let walkable: Vec<_> = self.map.iter().filter(|&pos| is_walkable(pos)).collect();
+        let start = match walkable.first() {
+            Some(&pos) => pos,
+            None => return true,
+        };
+        let mut visited = HexMap::new(self.map.radius());
+        visited.set_tile(start, true);
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        let mut visited_count = 1;
+        while let Some(pos) = queue.pop_front() {
+            for dir in dirs() {
+                let neighbor = Dir::get_neighbor_pos(pos, dir);
+                if is_walkable(neighbor) && !visited.tile(neighbor) {
+                    visited.set_tile(neighbor, true);
+                    visited_count += 1;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        visited_count == walkable.len()
+    }
+
+    /// Obstacles that would split the map aren't placed.
+    fn try_add_obstacle(&mut self, pos: PosHex) {
+        if !self.map.is_inboard(pos) {
+            return;
+        }
+        let old = self.map.tile(pos);
+        if old == Cell::Obstacle || old == Cell::Reserved {
+            return;
+        }
+        self.map.set_tile(pos, Cell::Obstacle);
+        if !self.is_connected() {
+            self.map.set_tile(pos, old);
+        }
+    }
+
+    fn add_ruin_cluster(&mut self) {
+        let center = self.random_tile();
+        let ring_radius = self.rng.gen_range(1..3);
+        let ring: Vec<_> = self
+            .tiles()
+            .into_iter()
+            .filter(|&pos| map::distance_hex(center, pos) == Distance(ring_radius))
+            .collect();
+        for pos in ring {
+            // A third of the wall is gone.
+            if self.rng.gen_range(0..3) == 0 {
+                if self.map.tile(pos) == Cell::Plain {
+                    self.map.set_tile(pos, Cell::Rough);
+                }
+            } else {
+                self.try_add_obstacle(pos);
+            }
+        }
+    }
+
+    fn into_layout(self) -> Layout {
+        let mut layout = Layout::default();
+        let radius = self.map.radius();
+        for pos in HexMap::<Cell>::new(radius).iter() {
+            if !self.map.is_inboard(pos) {
+                layout.masked_tiles.push(pos);
+                continue;
+            }
+            match self.map.tile(pos) {
+                Cell::Rough => layout.rough_tiles.push(pos),
+                Cell::Obstacle => layout.obstacles.push(pos),
+                Cell::Plain | Cell::Reserved => {}
+            }
+        }
+        layout
+    }
+}
+
+/// Creates a random layout, the tiles from `keep_free` stay plain and free.
+///
+/// All the tiles without obstacles are always reachable from each other.
+pub fn generate<R: Rng>(
+    rng: &mut R,
+    radius: Distance,
+    params: Params,
+    keep_free: &[PosHex],
+) -> Layout {
+    let mut generator = Generator {
+        rng,
+        map: HexMap::new(radius),
+    };
+    for &pos in keep_free {
+        generator.reserve(pos);
+    }
+    if params.chokepoints > 0 {
+        generator.add_chasm(params.chokepoints);
+    }
+    for _ in 0..generator.scaled(params.rough_clusters) {
+        generator.add_rough_cluster(params.rough_cluster_size);
+    }
+    for _ in 0..generator.scaled(params.ruin_clusters) {
+        generator.add_ruin_cluster();
+    }
+    for _ in 0..generator.scaled(params.obstacles) {
+        let pos = generator.random_tile();
+        generator.try_add_obstacle(pos);
+    }
+    generator.into_layout()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{
+        battle::rng::BattleRng,
+        map::{
+            generator::{generate, Biome},
+            Distance, PosHex,
+        },
+    };
+
+    const BIOMES: [Biome; 4] = [Biome::Plains, Biome::Forest, Biome::Ruins, Biome::Canyon];
+
+    #[test]
+    fn same_seed_same_layout() {
+        for biome in BIOMES {
+            let params = biome.params();
+            let a = generate(&mut BattleRng::new(7), Distance(6), params, &[]);
+            let b = generate(&mut BattleRng::new(7), Distance(6), params, &[]);
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn keep_free_tiles_stay_free() {
+        let keep_free: Vec<_> = (-5..=5).map(|r| PosHex { q: 0, r }).collect();
+        for seed in 0..20 {
+            for biome in BIOMES {
+                let mut rng = BattleRng::new(seed);
+                let layout = generate(&mut rng, Distance(5), biome.params(), &keep_free);
+                for pos in &keep_free {
+                    assert!(!layout.masked_tiles.contains(pos));
+                    assert!(!layout.rough_tiles.contains(pos));
+                    assert!(!layout.obstacles.contains(pos));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn canyon_has_fords() {
+        for seed in 0..20 {
+            let mut rng = BattleRng::new(seed);
+            let layout = generate(&mut rng, Distance(5), Biome::Canyon.params(), &[]);
+            let chasm_length = 11;
+            assert_eq!(layout.masked_tiles.len(), chasm_length - 2);
+            assert!(layout.masked_tiles.iter().all(|pos| pos.q == 0));
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! What the mods change in the built-in data, see `screen::Mods`.
+//!
+//! The values are compared by their RON text, field by field,
+//! so a change reads the way a modder has written it.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::core::battle::{
+    ability::{self, Ability},
+    component::{Component, ObjType},
+};
+
+/// One changed value, e.g. the `strength` of an object's `Strength` component.
+/// `None` is a missing value: the mod has added or removed the whole thing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub name: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diff {
+    pub new_prototypes: Vec<ObjType>,
+
+    /// The built-in objects with other stats.
+    pub changed_prototypes: Vec<(ObjType, Vec<Change>)>,
+
+    pub changed_abilities: Vec<(Ability, Vec<Change>)>,
+    pub new_skirmishes: usize,
+}
+
+/// The data that the mods are layered over, as it's before or after them.
+#[derive(Clone, Copy, Debug)]
+pub struct Data<'a> {
+    pub prototypes: &'a HashMap<ObjType, Vec<Component>>,
+    pub abilities: &'a HashMap<Ability, ability::Definition>,
+    pub skirmishes_count: usize,
+}
+
+pub fn diff(base: &Data, merged: &Data) -> Diff {
+    let mut diff = Diff::default();
+    for (typename, components) in merged.prototypes {
+        match base.prototypes.get(typename) {
+            None => diff.new_prototypes.push(typename.clone()),
+            Some(base_components) => {
+                let changes = components_changes(base_components, components);
+                if !changes.is_empty() {
+                    diff.changed_prototypes.push((typename.clone(), changes));
+                }
+            }
+        }
+    }
+    diff.new_prototypes.sort_by(|a, b| a.0.cmp(&b.0));
+    diff.changed_prototypes
+        .sort_by(|a, b| (a.0).0.cmp(&(b.0).0));
+    for (&ability, definition) in merged.abilities {
+        let before = base.abilities.get(&ability).map(to_ron);
+        let changes = value_changes("definition", before, Some(to_ron(definition)));
+        if !changes.is_empty() {
+            diff.changed_abilities.push((ability, changes));
+        }
+    }
+    diff.changed_abilities
+        .sort_by_key(|(ability, _)| ability.title());
+    diff.new_skirmishes = merged
+        .skirmishes_count
+        .saturating_sub(base.skirmishes_count);
+    diff
+}
+
+/// The components are matched by their kinds, a prototype has one of each.
+fn components_changes(base: &[Component], merged: &[Component]) -> Vec<Change> {
+    let base: Vec<_> = base.iter().map(|c| variant(to_ron(c))).collect();
+    let merged: Vec<_> = merged.iter().map(|c| variant(to_ron(c))).collect();
+    let find = |list: &[(String, String)], name: &str| {
+        list.iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, value)| value.clone())
+    };
+    let mut names: Vec<&String> = base.iter().map(|(name, _)| name).collect();
+    for (name, _) in &merged {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    let mut changes = Vec::new();
+    for name in names {
+        let before = find(&base, name);
+        let after = find(&merged, name);
+        changes.extend(value_changes(name, before, after));
+    }
+    changes
+}
+
+/// The fields that differ if both values are structs, the whole values otherwise.
+/// The `base_` fields just repeat the current ones, see `Prototypes::init_components`.
+fn value_changes(name: &str, before: Option<String>, after: Option<String>) -> Vec<Change> {
+    if before == after {
+        return Vec::new();
+    }
+    let whole = || {
+        vec![Change {
+            name: name.to_string(),
+            before: before.as_deref().map(unwrap_newtype),
+            after: after.as_deref().map(unwrap_newtype),
+        }]
+    };
+    let (before_fields, after_fields) = match (&before, &after) {
+        (Some(before), Some(after)) => match (struct_fields(before), struct_fields(after)) {
+            (Some(before), Some(after)) => (before, after),
+            _ => return whole(),
+        },
+        _ => return whole(),
+    };
+    let mut changes = Vec::new();
+    for &(field, after_value) in &after_fields {
+        if field.starts_with("base_") {
+            continue;
+        }
+        let before_value = before_fields
+            .iter()
+            .find(|&&(other, _)| other == field)
+            .map(|&(_, value)| value);
+        if before_value != Some(after_value) {
+            changes.push(Change {
+                name: field.to_string(),
+                before: before_value.map(unwrap_newtype),
+                after: Some(unwrap_newtype(after_value)),
+            });
+        }
+    }
+    changes
+}
+
+fn to_ron(value: &impl Serialize) -> String {
+    ron::ser::to_string(value).expect("Can't serialize the data")
+}
+
+/// `Strength((strength:(3)))` is `("Strength", "(strength:(3))")`.
+fn variant(s: String) -> (String, String) {
+    match s.find('(') {
+        Some(index) if s.ends_with(')') => {
+            let inner = s[index + 1..s.len() - 1].to_string();
+            (s[..index].to_string(), inner)
+        }
+        _ => (s, String::new()),
+    }
+}
+
+/// `(a:1,b:(2,3))` is `[("a", "1"), ("b", "(2,3)")]`, a value that isn't a struct is `None`.
+fn struct_fields(s: &str) -> Option<Vec<(&str, &str)>> {
+    let inner = s.strip_prefix('(')?.strip_suffix(')')?;
+    let mut fields = Vec::new();
+    for item in split_top_level(inner) {
+        let (name, value) = item.split_once(':')?;
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+        fields.push((name, value));
+    }
+    Some(fields)
+}
+
+/// Splits the text at the commas that aren't inside any brackets or strings.
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut is_in_string = false;
+    let mut is_escaped = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if is_in_string {
+            match c {
+                _ if is_escaped => is_escaped = false,
+                '\\' => is_escaped = true,
+                '"' => is_in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => is_in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < s.len() {
+        items.push(&s[start..]);
+    }
+    items
+}
+
+/// `(3)` is shown as `3`.
+fn unwrap_newtype(s: &str) -> String {
+    if let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        let items = split_top_level(inner);
+        if items.len() == 1 && struct_fields(s).is_none() {
+            return inner.to_string();
+        }
+    }
+    s.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::core::{
+        battle::{
+            ability::{self, Ability},
+            component::{self, Component, ObjType},
+            Rounds, Strength,
+        },
+        map::{Dir, Distance},
+        mod_diff::{diff, Change, Data},
+    };
+
+    fn strength(n: i32) -> Component {
+        let mut component: Component = component::Strength {
+            base_strength: Strength(0),
+            strength: Strength(n),
+        }
+        .into();
+        component::init_component(&mut component);
+        component
+    }
+
+    fn prototypes(list: &[(&str, Vec<Component>)]) -> HashMap<ObjType, Vec<Component>> {
+        list.iter()
+            .map(|(name, components)| ((*name).into(), components.clone()))
+            .collect()
+    }
+
+    fn no_abilities() -> HashMap<Ability, ability::Definition> {
+        HashMap::new()
+    }
+
+    fn change(name: &str, before: Option<&str>, after: Option<&str>) -> Change {
+        Change {
+            name: name.into(),
+            before: before.map(Into::into),
+            after: after.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn nothing_changed() {
+        let prototypes = prototypes(&[("imp", vec![strength(2)])]);
+        let abilities = no_abilities();
+        let data = Data {
+            prototypes: &prototypes,
+            abilities: &abilities,
+            skirmishes_count: 3,
+        };
+        assert_eq!(diff(&data, &data), Default::default());
+    }
+
+    #[test]
+    fn changed_fields_have_old_and_new_values() {
+        let base = prototypes(&[("imp", vec![strength(2)]), ("troll", vec![strength(5)])]);
+        let footprint = component::Footprint(vec![Dir::East]).into();
+        let merged = prototypes(&[
+            ("imp", vec![strength(3)]),
+            ("troll", vec![strength(5), footprint]),
+            ("devil", vec![strength(9)]),
+        ]);
+        let abilities = no_abilities();
+        let data = |prototypes, skirmishes_count| Data {
+            prototypes,
+            abilities: &abilities,
+            skirmishes_count,
+        };
+        let diff = diff(&data(&base, 2), &data(&merged, 3));
+        assert_eq!(diff.new_prototypes, vec!["devil".into()]);
+        assert_eq!(diff.new_skirmishes, 1);
+        let changed: Vec<_> = diff
+            .changed_prototypes
+            .iter()
+            .map(|(typename, changes)| (typename.0.as_str(), changes.clone()))
+            .collect();
+        assert_eq!(
+            changed,
+            vec![
+                ("imp", vec![change("strength", Some("2"), Some("3"))]),
+                ("troll", vec![change("Footprint", None, Some("[East]"))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn changed_ability_definition_fields() {
+        let prototypes = prototypes(&[]);
+        let definition = |distance| ability::Definition {
+            target: None,
+            min_distance: Distance(0),
+            effects: Vec::new(),
+            delivery: ability::Delivery::Thrown,
+            tiers: vec![ability::TierParams {
+                cooldown: Rounds(2),
+                power: 0,
+                distance: Some(Distance(distance)),
+            }],
+        };
+        let base: HashMap<_, _> = vec![(Ability::Jump, definition(2))].into_iter().collect();
+        let merged: HashMap<_, _> = vec![(Ability::Jump, definition(3))].into_iter().collect();
+        let data = |abilities| Data {
+            prototypes: &prototypes,
+            abilities,
+            skirmishes_count: 0,
+        };
+        let diff = diff(&data(&base), &data(&merged));
+        assert_eq!(diff.changed_abilities.len(), 1);
+        let (ability, changes) = &diff.changed_abilities[0];
+        assert_eq!(*ability, Ability::Jump);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].name, "tiers");
+    }
+}
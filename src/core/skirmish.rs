@@ -0,0 +1,265 @@
+//! One-off battles outside of the campaign, set up by the player.
+
+use std::collections::BTreeMap;
+
+use rand::{seq::SliceRandom, Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    battle::{
+        component::ObjType,
+        rng::BattleRng,
+        scenario::{Line, ObjectsGroup, Scenario, Size, Tags},
+        state::BattleResult,
+        stats::Stats,
+        PlayerId,
+    },
+    map::{generator::Biome, Distance},
+};
+
+pub const MAX_SQUAD_SIZE: usize = 6;
+
+const BIOMES: [Biome; 4] = [Biome::Plains, Biome::Forest, Biome::Ruins, Biome::Canyon];
+const SIZES: [Size; 3] = [Size::Small, Size::Medium, Size::Large];
+
+fn enemies(size: Size) -> &'static [(&'static str, Line, i32)] {
+    match size {
+        Size::Small => &[("imp", Line::Front, 3), ("imp_bomber", Line::Middle, 1)],
+        Size::Medium => &[
+            ("imp", Line::Front, 4),
+            ("toxic_imp", Line::Front, 1),
+            ("imp_bomber", Line::Middle, 1),
+            ("imp_summoner", Line::Back, 1),
+        ],
+        Size::Large => &[
+            ("imp", Line::Front, 5),
+            ("toxic_imp", Line::Front, 2),
+            ("imp_bomber", Line::Middle, 2),
+            ("imp_summoner", Line::Back, 2),
+        ],
+    }
+}
+
+fn map_radius(size: Size) -> Distance {
+    match size {
+        Size::Small => Distance(5),
+        Size::Medium => Distance(6),
+        Size::Large => Distance(7),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setup {
+    pub biome: Biome,
+
+    /// The player's fighters in the order they were picked.
+    pub squad: Vec<ObjType>,
+
+    /// Also sets the size of the map.
+    pub enemy_force: Size,
+
+    /// Fixes the map, the placement and the dice, see `Scenario::seed`.
+    pub seed: Option<u64>,
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Self {
+            biome: Biome::Plains,
+            squad: vec!["swordsman".into(), "spearman".into()],
+            enemy_force: Size::Small,
+            seed: None,
+        }
+    }
+}
+
+impl Setup {
+    /// The same skirmish for everyone who plays on that day.
+    ///
+    /// `day` is the number of days since the Unix epoch
+    /// and `fighters` must come in the same order every time.
+    pub fn daily_challenge(day: u64, fighters: &[ObjType]) -> Self {
+        let mut rng = BattleRng::new(day);
+        let biome = *BIOMES.choose(&mut rng).expect("No biomes");
+        let enemy_force = *SIZES.choose(&mut rng).expect("No sizes");
+        let squad_size = rng.gen_range(2..=4).min(fighters.len());
+        let squad = fighters
+            .choose_multiple(&mut rng, squad_size)
+            .cloned()
+            .collect();
+        Self {
+            biome,
+            squad,
+            enemy_force,
+            seed: Some(rng.next_u64()),
+        }
+    }
+
+    pub fn can_add_fighter(&self) -> bool {
+        self.squad.len() < MAX_SQUAD_SIZE
+    }
+
+    pub fn add_fighter(&mut self, typename: ObjType) {
+        assert!(self.can_add_fighter());
+        self.squad.push(typename);
+    }
+
+    pub fn remove_fighter(&mut self, index: usize) {
+        self.squad.remove(index);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self.squad.is_empty()
+    }
+
+    pub fn scenario(&self) -> Scenario {
+        assert!(self.is_ready());
+        let mut randomized_objects = Vec::new();
+        for typename in &self.squad {
+            randomized_objects.push(ObjectsGroup {
+                owner: Some(PlayerId(0)),
+                typename: typename.clone(),
+                line: Some(Line::Middle),
+                count: 1,
+                extra_components: Vec::new(),
+            });
+        }
+        for &(typename, line, count) in enemies(self.enemy_force) {
+            randomized_objects.push(ObjectsGroup {
+                owner: Some(PlayerId(1)),
+                typename: typename.into(),
+                line: Some(line),
+                count,
+                extra_components: Vec::new(),
+            });
+        }
+        Scenario {
+            map_radius: map_radius(self.enemy_force),
+            random_map: Some(self.biome),
+            randomized_objects,
+            tags: Tags {
+                size: self.enemy_force,
+                ..Tags::default()
+            },
+            seed: self.seed,
+            ..Scenario::default()
+        }
+    }
+}
+
+/// Quick and bloodless victories score the most, a defeat only keeps the kills.
+pub fn daily_score(stats: &Stats, result: &BattleResult) -> i32 {
+    let count = |f: &dyn Fn(PlayerId) -> bool| {
+        stats
+            .agents()
+            .iter()
+            .filter(|agent| agent.is_killed && f(agent.player_id))
+            .count() as i32
+    };
+    let kills = count(&|player_id| player_id != PlayerId(0));
+    let losses = count(&|player_id| player_id == PlayerId(0));
+    let mut score = kills * 10 - losses * 20;
+    if result.winner_id == PlayerId(0) && !result.is_retreat {
+        let speed_bonus = (20 - stats.turns()).max(0) * 5;
+        score += 100 + speed_bonus;
+    }
+    score.max(0)
+}
+
+/// The best daily challenge score of every day that was played.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DailyRecords(BTreeMap<u64, i32>);
+
+impl DailyRecords {
+    pub fn best(&self, day: u64) -> Option<i32> {
+        self.0.get(&day).copied()
+    }
+
+    /// Returns `true` if the score beats the day's record.
+    pub fn add(&mut self, day: u64, score: i32) -> bool {
+        let is_record = self.best(day).is_none_or(|best| score > best);
+        if is_record {
+            self.0.insert(day, score);
+        }
+        is_record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::core::{
+        battle::{component::ObjType, state::BattleResult, stats::Stats, PlayerId},
+        skirmish::{daily_score, DailyRecords, Setup, MAX_SQUAD_SIZE},
+    };
+
+    fn fighters() -> Vec<ObjType> {
+        ["swordsman", "spearman", "hammerman", "alchemist"]
+            .iter()
+            .map(|&name| name.into())
+            .collect()
+    }
+
+    #[test]
+    fn squad_size_is_limited() {
+        let mut setup = Setup::default();
+        while setup.can_add_fighter() {
+            setup.add_fighter("hammerman".into());
+        }
+        assert_eq!(setup.squad.len(), MAX_SQUAD_SIZE);
+        setup.remove_fighter(0);
+        assert!(setup.can_add_fighter());
+    }
+
+    #[test]
+    fn scenario_has_the_squad() {
+        let setup = Setup::default();
+        let scenario = setup.scenario();
+        scenario.check().unwrap();
+        let squad: Vec<_> = scenario
+            .randomized_objects
+            .iter()
+            .filter(|group| group.owner == Some(PlayerId(0)))
+            .map(|group| group.typename.clone())
+            .collect();
+        assert_eq!(squad, setup.squad);
+        assert_eq!(scenario.random_map, Some(setup.biome));
+    }
+
+    #[test]
+    fn daily_challenge_depends_only_on_the_day() {
+        let fighters = fighters();
+        let today = Setup::daily_challenge(20_000, &fighters);
+        assert_eq!(today, Setup::daily_challenge(20_000, &fighters));
+        assert!((2..=4).contains(&today.squad.len()));
+        assert!(today.seed.is_some());
+        today.scenario().check().unwrap();
+        let days = (20_001..20_010).map(|day| Setup::daily_challenge(day, &fighters));
+        assert!(days.into_iter().any(|other| other != today));
+    }
+
+    #[test]
+    fn daily_records() {
+        let result = BattleResult {
+            winner_id: PlayerId(0),
+            survivor_types: Vec::new(),
+            veterans: Vec::new(),
+            fallen: Vec::new(),
+            charges: HashMap::new(),
+            ability_uses: HashMap::new(),
+            is_retreat: false,
+            control_renown: 0,
+        };
+        // A win on the first turn.
+        let score = daily_score(&Stats::default(), &result);
+        assert_eq!(score, 100 + 19 * 5);
+        let mut records = DailyRecords::default();
+        assert!(records.add(1, score));
+        assert!(!records.add(1, score));
+        assert!(records.add(1, score + 1));
+        assert!(records.add(2, 0));
+        assert_eq!(records.best(1), Some(score + 1));
+        assert_eq!(records.best(3), None);
+    }
+}
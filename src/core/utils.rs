@@ -51,8 +51,37 @@ pub fn clamp<T: PartialOrd>(value: T, min: T, max: T) -> T {
     }
 }
 
+/// The year, month and day of the date that is `days` after the Unix epoch.
+pub fn date_from_days(days: u64) -> (i64, u32, u32) {
+    // Counts in the eras of 400 years that start at the 1st of March,
+    // so the leap day is the last day of a year.
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn test_date_from_days() {
+        assert_eq!(super::date_from_days(0), (1970, 1, 1));
+        assert_eq!(super::date_from_days(59), (1970, 3, 1));
+        assert_eq!(super::date_from_days(11_016), (2000, 2, 29));
+        assert_eq!(super::date_from_days(20_740), (2026, 10, 14));
+    }
+
     #[test]
     fn test_clamp_min() {
         assert_eq!(super::clamp_min(1, 0), 1);
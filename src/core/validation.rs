@@ -0,0 +1,486 @@
+//! Cross-checks of the data files.
+//!
+//! Serde only checks that every file is well-formed on its own.
+//! These checks find the names that point nowhere, so a typo is reported right away
+//! along with all the other problems and not in the middle of a battle.
+
+use std::{collections::HashMap, fmt};
+
+use crate::core::{
+    battle::{
+        ability::{self, Ability, AbilityEffect},
+        component::{Component, ObjType, Prototypes},
+        objective::Objective,
+        scenario::{
+            self, Condition, Line, ObjectsGroup, Scenario, ScriptAction, RANDOM_MAP_OBSTACLE,
+        },
+        PlayerId,
+    },
+    campaign::{AgentInfo, CampChoice, Goods, Plan},
+    item::{Item, ItemType},
+};
+
+/// The objects that the battle creates by itself, see `battle::execute`.
+const ENGINE_OBJECTS: [&str; 3] = ["fire", "poison_cloud", "corpse"];
+
+/// The objects that the built-in rules of the ability create, see `battle::execute`,
+/// the data-driven ones are listed in their definitions.
+fn created_objects(ability: Ability) -> &'static [&'static str] {
+    match ability {
+        Ability::Bomb => &["bomb_damage"],
+        Ability::BombPush => &["bomb_push"],
+        Ability::BombFire => &["bomb_fire"],
+        Ability::BombPoison => &["bomb_poison"],
+        Ability::BombDemonic => &["bomb_demonic"],
+        Ability::Summon => &["imp", "toxic_imp", "imp_bomber"],
+        Ability::Raise => &["skeleton"],
+        _ => &[],
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Error {
+    NoPrototype {
+        typename: ObjType,
+        used_by: String,
+    },
+    NoSprite {
+        typename: ObjType,
+        used_by: String,
+    },
+    NoAbilityIcon {
+        ability: Ability,
+        used_by: String,
+    },
+    NoAbilityTarget {
+        ability: Ability,
+        used_by: String,
+    },
+    NoAgentInfo {
+        typename: ObjType,
+        used_by: String,
+    },
+    NoItemInfo {
+        item: ItemType,
+        used_by: String,
+    },
+    NoSuchCampaignNode {
+        name: String,
+        used_by: String,
+    },
+    BadScenario {
+        error: scenario::Error,
+        used_by: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoPrototype { typename, used_by } => {
+                write!(f, "{}: no '{}' prototype", used_by, typename.0)
+            }
+            Error::NoSprite { typename, used_by } => {
+                write!(f, "{}: no sprite for '{}'", used_by, typename.0)
+            }
+            Error::NoAbilityIcon { ability, used_by } => {
+                write!(f, "{}: no icon for {:?}", used_by, ability)
+            }
+            Error::NoAbilityTarget { ability, used_by } => {
+                write!(
+                    f,
+                    "{}: no target in the definition of {:?}",
+                    used_by, ability
+                )
+            }
+            Error::NoAgentInfo { typename, used_by } => {
+                write!(f, "{}: no campaign info for '{}'", used_by, typename.0)
+            }
+            Error::NoItemInfo { item, used_by } => {
+                write!(f, "{}: no info for the {:?} item", used_by, item)
+            }
+            Error::NoSuchCampaignNode { name, used_by } => {
+                write!(f, "{}: no '{}' campaign node", used_by, name)
+            }
+            Error::BadScenario { error, used_by } => write!(f, "{}: {:?}", used_by, error),
+        }
+    }
+}
+
+/// Everything that is checked, the sprites and the icons are only known to the caller.
+pub struct Data<'a> {
+    pub prototypes: &'a Prototypes,
+    pub abilities: &'a ability::Definitions,
+    pub has_sprite: &'a dyn Fn(&ObjType) -> bool,
+    pub has_ability_icon: &'a dyn Fn(Ability) -> bool,
+
+    /// The standalone scenarios with their names for the reports.
+    pub scenarios: Vec<(String, &'a Scenario)>,
+
+    pub campaign: &'a Plan,
+    pub agent_info: &'a HashMap<ObjType, AgentInfo>,
+    pub item_info: &'a HashMap<ItemType, Item>,
+}
+
+struct Checker<'a> {
+    data: &'a Data<'a>,
+    errors: Vec<Error>,
+}
+
+impl<'a> Checker<'a> {
+    fn prototype(&mut self, typename: &ObjType, used_by: &str) {
+        if !self.data.prototypes.0.contains_key(typename) {
+            self.errors.push(Error::NoPrototype {
+                typename: typename.clone(),
+                used_by: used_by.into(),
+            });
+        }
+    }
+
+    fn sprite(&mut self, typename: &ObjType, used_by: &str) {
+        if !(self.data.has_sprite)(typename) {
+            self.errors.push(Error::NoSprite {
+                typename: typename.clone(),
+                used_by: used_by.into(),
+            });
+        }
+    }
+
+    fn ability_icon(&mut self, ability: Ability, used_by: &str) {
+        if !(self.data.has_ability_icon)(ability) {
+            self.errors.push(Error::NoAbilityIcon {
+                ability,
+                used_by: used_by.into(),
+            });
+        }
+    }
+
+    /// Only the abilities with built-in rules can do without the targets.
+    fn ability_rules(&mut self, ability: Ability, used_by: &str) {
+        let has_target = self
+            .data
+            .abilities
+            .get(ability)
+            .is_some_and(|definition| definition.target.is_some());
+        if !ability.has_built_in_rules() && !has_target {
+            self.errors.push(Error::NoAbilityTarget {
+                ability,
+                used_by: used_by.into(),
+            });
+        }
+    }
+
+    /// A fighter that the player can have in the campaign.
+    fn campaign_agent(&mut self, typename: &ObjType, used_by: &str) {
+        self.prototype(typename, used_by);
+        if !self.data.agent_info.contains_key(typename) {
+            self.errors.push(Error::NoAgentInfo {
+                typename: typename.clone(),
+                used_by: used_by.into(),
+            });
+        }
+    }
+
+    fn item(&mut self, item: &ItemType, used_by: &str) {
+        if !self.data.item_info.contains_key(item) {
+            self.errors.push(Error::NoItemInfo {
+                item: item.clone(),
+                used_by: used_by.into(),
+            });
+        }
+    }
+
+    fn check_prototypes(&mut self) {
+        for &name in &ENGINE_OBJECTS {
+            self.prototype(&name.into(), "the battle rules");
+        }
+        let mut typenames: Vec<_> = self.data.prototypes.0.keys().collect();
+        typenames.sort_by_key(|typename| &typename.0);
+        for typename in typenames {
+            let used_by = format!("prototype '{}'", typename.0);
+            self.sprite(typename, &used_by);
+            let components = &self.data.prototypes.0[typename];
+            let is_agent = components
+                .iter()
+                .any(|component| matches!(component, Component::Agent(_)));
+            for component in components {
+                if let Component::Abilities(abilities) = component {
+                    for ability in &abilities.0 {
+                        self.ability_rules(ability.ability, &used_by);
+                        // Only the agents show their abilities as buttons.
+                        if is_agent {
+                            self.ability_icon(ability.ability, &used_by);
+                        }
+                        for &name in created_objects(ability.ability) {
+                            self.prototype(&name.into(), &used_by);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_abilities(&mut self) {
+        let mut abilities: Vec<_> = self.data.abilities.0.iter().collect();
+        abilities.sort_by_key(|(ability, _)| format!("{:?}", ability));
+        for (ability, definition) in abilities {
+            let used_by = format!("ability {:?}", ability);
+            for effect in &definition.effects {
+                if let AbilityEffect::Create(typename) = effect {
+                    self.prototype(typename, &used_by);
+                }
+            }
+        }
+    }
+
+    fn check_scenario(&mut self, scenario: &Scenario, used_by: &str) {
+        if let Err(error) = scenario.check() {
+            self.errors.push(Error::BadScenario {
+                error,
+                used_by: used_by.into(),
+            });
+        }
+        for obj in &scenario.objects {
+            self.prototype(&obj.typename, used_by);
+        }
+        for group in &scenario.randomized_objects {
+            self.prototype(&group.typename, used_by);
+        }
+        for wave in &scenario.reinforcements {
+            for (typename, _) in &wave.objects {
+                self.prototype(typename, used_by);
+            }
+        }
+        if scenario.random_map.is_some() {
+            self.prototype(&RANDOM_MAP_OBSTACLE.into(), used_by);
+        }
+        for objective in &scenario.objectives {
+            match objective {
+                Objective::ProtectAgent(typename)
+                | Objective::DestroyObject(typename)
+                | Objective::KillBoss(typename)
+                | Objective::Escort { typename, .. } => self.prototype(typename, used_by),
+                Objective::SurviveNTurns(_) | Objective::ReachHex(_) => {}
+            }
+        }
+        for script in &scenario.scripts {
+            match &script.condition {
+                Condition::Destroyed(typename) | Condition::StrengthDropsTo { typename, .. } => {
+                    self.prototype(typename, used_by);
+                }
+                _ => {}
+            }
+            for action in &script.actions {
+                match action {
+                    ScriptAction::Spawn { objects, .. } => {
+                        for (typename, _) in objects {
+                            self.prototype(typename, used_by);
+                        }
+                    }
+                    ScriptAction::Dialogue(remarks) => {
+                        for remark in remarks {
+                            self.sprite(&remark.speaker, used_by);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn check_campaign(&mut self) {
+        let plan = self.data.campaign;
+        for typename in plan.initial_agents() {
+            self.campaign_agent(typename, "campaign's initial agents");
+        }
+        for item in plan.initial_items() {
+            self.item(item, "campaign's initial items");
+        }
+        for node in plan.nodes() {
+            let used_by = format!("campaign node '{}'", node.name);
+            for name in &node.requires {
+                if !plan.nodes().iter().any(|other| &other.name == name) {
+                    self.errors.push(Error::NoSuchCampaignNode {
+                        name: name.clone(),
+                        used_by: used_by.clone(),
+                    });
+                }
+            }
+            // Camps have no battles.
+            if node.camp.is_none() {
+                let scenario = with_squad(&node.scenario, plan.initial_agents());
+                self.check_scenario(&scenario, &used_by);
+            }
+            for typename in &node.award.recruits {
+                self.campaign_agent(typename, &used_by);
+            }
+            for item in &node.award.items {
+                self.item(item, &used_by);
+            }
+            for offer in &node.shop {
+                match &offer.goods {
+                    Goods::Recruit(typename) => self.campaign_agent(typename, &used_by),
+                    Goods::Item(item) => self.item(item, &used_by),
+                    Goods::Boon(_) => {}
+                }
+            }
+            for choice in node.camp.iter().flat_map(|camp| &camp.choices) {
+                if let CampChoice::Craft(item) = choice {
+                    self.item(item, &used_by);
+                }
+            }
+        }
+        let mut typenames: Vec<_> = self.data.agent_info.keys().collect();
+        typenames.sort_by_key(|typename| &typename.0);
+        for typename in typenames {
+            let used_by = format!("campaign info of '{}'", typename.0);
+            self.prototype(typename, &used_by);
+            let info = &self.data.agent_info[typename];
+            for upgrade in &info.upgrades {
+                self.campaign_agent(upgrade, &used_by);
+            }
+            for &ability in &info.learnable_abilities {
+                self.ability_rules(ability, &used_by);
+                self.ability_icon(ability, &used_by);
+            }
+        }
+    }
+}
+
+/// The player's fighters are only added to the campaign battles when they start,
+/// see `campaign::State::battle_scenario`.
+fn with_squad(scenario: &Scenario, squad: &[ObjType]) -> Scenario {
+    let mut scenario = scenario.clone();
+    for typename in squad {
+        scenario.randomized_objects.push(ObjectsGroup {
+            owner: Some(PlayerId(0)),
+            typename: typename.clone(),
+            line: Some(Line::Middle),
+            count: 1,
+            extra_components: Vec::new(),
+        });
+    }
+    scenario
+}
+
+/// Returns all the problems at once.
+pub fn check(data: &Data) -> Vec<Error> {
+    let mut checker = Checker {
+        data,
+        errors: Vec::new(),
+    };
+    checker.check_prototypes();
+    checker.check_abilities();
+    for (name, scenario) in &data.scenarios {
+        checker.check_scenario(scenario, name);
+    }
+    checker.check_campaign();
+    checker.errors
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::core::{
+        battle::{
+            ability::{self, Ability, AbilityEffect},
+            component::{ObjType, Prototypes},
+            scenario::{Object, Scenario},
+            tutorial::Tutorial,
+            PlayerId,
+        },
+        campaign::{AgentInfo, Plan},
+        item::{Item, ItemType},
+        map::PosHex,
+        validation::{check, Data, Error},
+    };
+
+    struct GameData {
+        prototypes: Prototypes,
+        abilities: ability::Definitions,
+        sprites: HashMap<ObjType, ron::Value>,
+        scenarios: Vec<Scenario>,
+        campaign: Plan,
+        agent_info: HashMap<ObjType, AgentInfo>,
+        item_info: HashMap<ItemType, Item>,
+    }
+
+    /// The real data files of the game.
+    fn game_data() -> GameData {
+        let mut scenarios: Vec<Scenario> =
+            ron::de::from_str(include_str!("../../assets/skirmishes.ron")).unwrap();
+        scenarios.push(ron::de::from_str(include_str!("../../assets/scenario_01.ron")).unwrap());
+        let tutorial: Tutorial =
+            ron::de::from_str(include_str!("../../assets/tutorial.ron")).unwrap();
+        scenarios.push(tutorial.scenario);
+        GameData {
+            prototypes: Prototypes::from_str(include_str!("../../assets/objects.ron")),
+            abilities: ron::de::from_str(include_str!("../../assets/abilities.ron")).unwrap(),
+            sprites: ron::de::from_str(include_str!("../../assets/sprites.ron")).unwrap(),
+            scenarios,
+            campaign: ron::de::from_str(include_str!("../../assets/campaign_01.ron")).unwrap(),
+            agent_info: ron::de::from_str(include_str!("../../assets/agent_campaign_info.ron"))
+                .unwrap(),
+            item_info: ron::de::from_str(include_str!("../../assets/items.ron")).unwrap(),
+        }
+    }
+
+    fn errors(game: &GameData) -> Vec<Error> {
+        let has_sprite = |typename: &ObjType| game.sprites.contains_key(typename);
+        let data = Data {
+            prototypes: &game.prototypes,
+            abilities: &game.abilities,
+            has_sprite: &has_sprite,
+            has_ability_icon: &|_| true,
+            scenarios: game
+                .scenarios
+                .iter()
+                .enumerate()
+                .map(|(i, scenario)| (format!("scenario #{}", i), scenario))
+                .collect(),
+            campaign: &game.campaign,
+            agent_info: &game.agent_info,
+            item_info: &game.item_info,
+        };
+        check(&data)
+    }
+
+    #[test]
+    fn game_data_is_valid() {
+        let errors: Vec<_> = errors(&game_data()).iter().map(Error::to_string).collect();
+        assert!(errors.is_empty(), "{:#?}", errors);
+    }
+
+    #[test]
+    fn all_errors_are_reported() {
+        let mut game = game_data();
+        game.sprites.remove(&"imp".into());
+        game.scenarios[0].objects.push(Object {
+            owner: Some(PlayerId(1)),
+            typename: "imp_king".into(),
+            pos: PosHex { q: 0, r: 0 },
+        });
+        game.agent_info.remove(&"spearman".into());
+        let totem = game.abilities.0.get_mut(&Ability::Totem).unwrap();
+        totem.effects = vec![AbilityEffect::Create("big_totem".into())];
+        game.abilities.0.get_mut(&Ability::Heal).unwrap().target = None;
+        let errors = errors(&game);
+        let has = |f: &dyn Fn(&Error) -> bool| errors.iter().any(f);
+        assert!(has(
+            &|e| matches!(e, Error::NoSprite { typename, .. } if typename.0 == "imp")
+        ));
+        assert!(has(
+            &|e| matches!(e, Error::NoPrototype { typename, .. } if typename.0 == "imp_king")
+        ));
+        assert!(has(
+            &|e| matches!(e, Error::NoAgentInfo { typename, .. } if typename.0 == "spearman")
+        ));
+        assert!(has(
+            &|e| matches!(e, Error::NoPrototype { typename, .. } if typename.0 == "big_totem")
+        ));
+        assert!(has(
+            &|e| matches!(e, Error::NoAbilityTarget { ability, .. } if *ability == Ability::Heal)
+        ));
+    }
+}
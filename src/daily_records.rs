@@ -0,0 +1,59 @@
+//! The best daily challenge scores, kept as a RON file next to the game.
+
+use std::sync::RwLock;
+
+use crate::core::skirmish::DailyRecords;
+
+#[cfg(not(target_arch = "wasm32"))]
+const PATH: &str = "daily_records.ron";
+
+/// Read from the file on the first use.
+static RECORDS: RwLock<Option<DailyRecords>> = RwLock::new(None);
+
+/// The missing or broken file means that no day was played yet.
+#[cfg(not(target_arch = "wasm32"))]
+fn load() -> DailyRecords {
+    match std::fs::read_to_string(PATH) {
+        Ok(s) => ron::de::from_str(&s).unwrap_or_else(|err| {
+            log::warn!("Can't parse '{}', starting anew: {}", PATH, err);
+            DailyRecords::default()
+        }),
+        Err(_) => DailyRecords::default(),
+    }
+}
+
+/// There are no files in browsers, the records last till the page is closed.
+#[cfg(target_arch = "wasm32")]
+fn load() -> DailyRecords {
+    DailyRecords::default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save(records: &DailyRecords) {
+    let config = ron::ser::PrettyConfig::new();
+    let result = ron::ser::to_string_pretty(records, config)
+        .map_err(|err| err.to_string())
+        .and_then(|s| std::fs::write(PATH, s).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        log::warn!("Can't save the daily records to '{}': {}", PATH, err);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save(_: &DailyRecords) {}
+
+pub fn best(day: u64) -> Option<i32> {
+    let mut records = RECORDS.write().expect("Can't read the daily records");
+    records.get_or_insert_with(load).best(day)
+}
+
+/// Returns `true` if the score is the day's new record.
+pub fn add(day: u64, score: i32) -> bool {
+    let mut records = RECORDS.write().expect("Can't update the daily records");
+    let records = records.get_or_insert_with(load);
+    let is_record = records.add(day, score);
+    if is_record {
+        save(records);
+    }
+    is_record
+}
@@ -1,5 +1,7 @@
 use std::{error, fmt, io, path::PathBuf};
 
+use crate::core::validation;
+
 #[derive(Debug, derive_more::From)]
 pub enum ZError {
     UiError(ui::Error),
@@ -8,9 +10,14 @@ pub enum ZError {
         error: ron::de::Error,
         path: PathBuf,
     },
+    RonSerializeError(ron::Error),
     IOError(io::Error),
     MqFileError(mq::file::FileError),
     MqFontError(mq::text::FontError),
+
+    /// The data files point to things that don't exist.
+    #[from(ignore)]
+    BadAssets(Vec<validation::Error>),
 }
 
 impl ZError {
@@ -28,9 +35,17 @@ impl fmt::Display for ZError {
                 let s = path.to_str().unwrap_or("<no path>");
                 write!(f, "Can't deserialize '{}': {}", s, error)
             }
+            ZError::RonSerializeError(ref e) => write!(f, "Can't serialize: {}", e),
             ZError::IOError(ref e) => write!(f, "IO Error: {}", e),
             ZError::MqFileError(ref e) => write!(f, "Macroquad File error: {}", e),
             ZError::MqFontError(ref e) => write!(f, "Macroquad Font error: {}", e),
+            ZError::BadAssets(errors) => {
+                writeln!(f, "Bad assets:")?;
+                for error in errors {
+                    writeln!(f, "- {}", error)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -41,9 +56,11 @@ impl error::Error for ZError {
             ZError::UiError(ref e) => Some(e),
             ZError::SceneError(ref e) => Some(e),
             ZError::RonDeserializeError { error, .. } => Some(error),
+            ZError::RonSerializeError(ref e) => Some(e),
             ZError::IOError(ref e) => Some(e),
             ZError::MqFileError(ref e) => Some(e),
             ZError::MqFontError(ref e) => Some(e),
+            ZError::BadAssets(_) => None,
         }
     }
 }
@@ -0,0 +1,134 @@
+//! A debug overlay with the frame timings, toggled with F3.
+
+use std::collections::VecDeque;
+
+use mq::{
+    camera::set_default_camera,
+    color::BLACK,
+    input::{is_key_pressed, KeyCode},
+    text::draw_text,
+    time::get_time,
+};
+
+/// How many last frames are used for the averages.
+const HISTORY_LEN: usize = 60;
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Sample {
+    frame: f64,
+    input: f64,
+    update: f64,
+    draw: f64,
+    update_steps: usize,
+}
+
+/// Measures the stages of every frame.
+///
+/// Call `begin_*` right before each stage and `end_frame` after the drawing.
+#[derive(Debug)]
+pub struct FrameTiming {
+    is_visible: bool,
+    history: VecDeque<Sample>,
+    current: Sample,
+    frame_start: f64,
+    stage_start: f64,
+
+    /// Time from the start of a frame with clicks till the end of its drawing.
+    last_click_to_draw: Option<f64>,
+    has_clicks: bool,
+}
+
+impl FrameTiming {
+    pub fn new() -> Self {
+        let now = get_time();
+        Self {
+            is_visible: false,
+            history: VecDeque::new(),
+            current: Sample::default(),
+            frame_start: now,
+            stage_start: now,
+            last_click_to_draw: None,
+            has_clicks: false,
+        }
+    }
+
+    fn finish_stage(&mut self) -> f64 {
+        let now = get_time();
+        let duration = now - self.stage_start;
+        self.stage_start = now;
+        duration
+    }
+
+    pub fn begin_frame(&mut self) {
+        let now = get_time();
+        self.current.frame = now - self.frame_start;
+        self.frame_start = now;
+        self.stage_start = now;
+        if is_key_pressed(KeyCode::F3) {
+            self.is_visible = !self.is_visible;
+        }
+    }
+
+    pub fn begin_update(&mut self, clicks_count: usize) {
+        self.current.input = self.finish_stage();
+        self.has_clicks = clicks_count > 0;
+    }
+
+    pub fn begin_draw(&mut self, update_steps: usize) {
+        self.current.update = self.finish_stage();
+        self.current.update_steps = update_steps;
+    }
+
+    pub fn end_frame(&mut self) {
+        self.current.draw = self.finish_stage();
+        if self.has_clicks {
+            self.last_click_to_draw = Some(self.stage_start - self.frame_start);
+        }
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.current);
+        if self.is_visible {
+            self.draw();
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let ms = |s: f64| s * 1000.0;
+        let len = self.history.len().max(1) as f64;
+        let average = |f: fn(&Sample) -> f64| self.history.iter().map(f).sum::<f64>() / len;
+        let max_frame = self.history.iter().map(|s| s.frame).fold(0.0, f64::max);
+        let frame = average(|s| s.frame);
+        let fps = if frame > 0.0 { 1.0 / frame } else { 0.0 };
+        let click_to_draw = match self.last_click_to_draw {
+            Some(time) => format!("{:.1} ms", ms(time)),
+            None => "-".into(),
+        };
+        vec![
+            format!(
+                "frame: {:.1} ms (max {:.1} ms, {:.0} fps)",
+                ms(frame),
+                ms(max_frame),
+                fps
+            ),
+            format!("input: {:.2} ms", ms(average(|s| s.input))),
+            format!(
+                "update: {:.2} ms ({} steps)",
+                ms(average(|s| s.update)),
+                self.current.update_steps
+            ),
+            format!("draw: {:.2} ms", ms(average(|s| s.draw))),
+            format!("last click to draw: {}", click_to_draw),
+        ]
+    }
+
+    fn draw(&self) {
+        // The overlay is drawn in pixels, the next frame sets its own camera anyway.
+        set_default_camera();
+        let font_size = 20.0;
+        for (i, line) in self.lines().iter().enumerate() {
+            let y = font_size * (i + 1) as f32;
+            draw_text(line, font_size / 2.0, y, font_size, BLACK);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use mq::math::Vec2;
 
 use crate::core::{
-    map::{hex_round, PosHex},
+    map::{hex_round, Dir, PosHex},
     utils::roll_dice,
 };
 
@@ -50,6 +50,13 @@ impl Facing {
         })
     }
 
+    /// Sprites can only look to the left or to the right.
+    pub fn from_dir(dir: Dir) -> Self {
+        let from = PosHex { q: 0, r: 0 };
+        let to = Dir::get_neighbor_pos(from, dir);
+        Self::from_positions(1.0, from, to).expect("Neighbors are never at the same position")
+    }
+
     pub fn to_scene_facing(self) -> zscene::Facing {
         match self {
             Facing::Left => zscene::Facing::Left,
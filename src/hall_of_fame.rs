@@ -0,0 +1,32 @@
+//! The best finished campaigns, kept as a RON file next to the game.
+
+use std::fs;
+
+use log::warn;
+
+use crate::core::campaign::HallOfFame;
+
+const PATH: &str = "hall_of_fame.ron";
+
+/// The missing or broken file means an empty hall.
+pub fn load() -> HallOfFame {
+    let s = match fs::read_to_string(PATH) {
+        Ok(s) => s,
+        Err(_) => return HallOfFame::default(),
+    };
+    ron::de::from_str(&s).unwrap_or_else(|err| {
+        warn!("Can't parse '{}', starting a new one: {}", PATH, err);
+        HallOfFame::default()
+    })
+}
+
+/// Failing to save isn't fatal, the run is just forgotten.
+pub fn save(hall: &HallOfFame) {
+    let config = ron::ser::PrettyConfig::new();
+    let result = ron::ser::to_string_pretty(hall, config)
+        .map_err(|err| err.to_string())
+        .and_then(|s| fs::write(PATH, s).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        warn!("Can't save the hall of fame to '{}': {}", PATH, err);
+    }
+}
@@ -0,0 +1,139 @@
+//! Keyboard bindings.
+//!
+//! The screens never look at the keys themselves, they get `Hotkey`s,
+//! so any key can be bound to anything without touching the screens.
+//!
+//! The game can be played without a mouse: the battle has a tile cursor
+//! (`Hotkey::Cursor`, `Hotkey::Select`) and the menus have a focus
+//! (`Hotkey::FocusNext`, `Hotkey::FocusPrev`, `Hotkey::Select`),
+//! so a controller mapped to these keys by the system (as Steam Input does)
+//! is enough to play.
+//!
+//! TODO: Read gamepads directly. macroquad 0.3 doesn't read them yet, its input module
+//! is still "mouse, keyboard (and gamepads soon)", see
+//! <https://docs.rs/macroquad/0.3.6/src/macroquad/input.rs.html>.
+//! So a controller backend (e.g. `gilrs`) would have to feed its buttons
+//! in here as `Hotkey`s, next to the keys.
+
+use mq::{
+    input::{is_key_down, KeyCode},
+    math::Vec2,
+};
+
+use crate::core::map::Dir;
+
+/// How fast the held arrows scroll the view, as fast as the battle map scrolls at the edges.
+const PAN_SPEED: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    /// The selected agent's ability by its position in the panel, from zero.
+    Ability(usize),
+
+    NextAgent,
+    EndTurn,
+    Menu,
+    ToggleAnimationSpeed,
+    ToggleThreat,
+
+    /// Moves the battle's tile cursor to the neighboring tile.
+    Cursor(Dir),
+
+    /// Clicks the tile under the cursor or the focused button.
+    Select,
+
+    /// Moves the focus over the buttons of a menu.
+    FocusNext,
+    FocusPrev,
+
+    /// These ones work while the key is held, see `Bindings::pan`.
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+}
+
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    keys: Vec<(KeyCode, Hotkey)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let digits = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ];
+        let mut keys: Vec<_> = digits
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| (key, Hotkey::Ability(i)))
+            .collect();
+        keys.extend_from_slice(&[
+            (KeyCode::Tab, Hotkey::NextAgent),
+            (KeyCode::Space, Hotkey::EndTurn),
+            (KeyCode::Escape, Hotkey::Menu),
+            (KeyCode::S, Hotkey::ToggleAnimationSpeed),
+            (KeyCode::Z, Hotkey::ToggleThreat),
+            (KeyCode::Left, Hotkey::PanLeft),
+            (KeyCode::Right, Hotkey::PanRight),
+            (KeyCode::Up, Hotkey::PanUp),
+            (KeyCode::Down, Hotkey::PanDown),
+            // The menus are vertical, so the same arrows move their focus.
+            (KeyCode::Down, Hotkey::FocusNext),
+            (KeyCode::Up, Hotkey::FocusPrev),
+            (KeyCode::Tab, Hotkey::FocusNext),
+            (KeyCode::Enter, Hotkey::Select),
+            (KeyCode::KpEnter, Hotkey::Select),
+            (KeyCode::Kp5, Hotkey::Select),
+            // The numpad keys around the 5 by where the neighbors are on the screen.
+            // The dirs are named by the axes, they're turned from the screen ones.
+            (KeyCode::Kp6, Hotkey::Cursor(Dir::SouthEast)),
+            (KeyCode::Kp9, Hotkey::Cursor(Dir::East)),
+            (KeyCode::Kp7, Hotkey::Cursor(Dir::NorthEast)),
+            (KeyCode::Kp4, Hotkey::Cursor(Dir::NorthWest)),
+            (KeyCode::Kp1, Hotkey::Cursor(Dir::West)),
+            (KeyCode::Kp3, Hotkey::Cursor(Dir::SouthWest)),
+        ]);
+        Self { keys }
+    }
+}
+
+impl Bindings {
+    /// A key can have a few hotkeys, each screen takes the ones it knows.
+    pub fn hotkeys(&self, key: KeyCode) -> impl Iterator<Item = Hotkey> + '_ {
+        self.keys
+            .iter()
+            .filter(move |&&(other_key, _)| other_key == key)
+            .map(|&(_, hotkey)| hotkey)
+    }
+
+    fn is_held(&self, hotkey: Hotkey) -> bool {
+        self.keys
+            .iter()
+            .any(|&(key, other)| other == hotkey && is_key_down(key))
+    }
+
+    /// How far the held keys move the view during `dtime` seconds,
+    /// in the same units as a mouse drag.
+    pub fn pan(&self, dtime: f32) -> Vec2 {
+        let axis = |positive, negative| match (self.is_held(positive), self.is_held(negative)) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        };
+        // Dragging the map to the right shows its left part.
+        let dir = Vec2::new(
+            axis(Hotkey::PanLeft, Hotkey::PanRight),
+            axis(Hotkey::PanUp, Hotkey::PanDown),
+        );
+        dir * PAN_SPEED * dtime
+    }
+}
@@ -0,0 +1,95 @@
+//! Translations of the UI texts.
+//!
+//! The keys are the English texts themselves, gettext-style, so English needs no file
+//! and a missing translation just shows the English text.
+//! Arguments are named: `{count}` in a key is replaced by the `count` argument
+//! and a translation can put it anywhere.
+
+use std::{collections::HashMap, fmt};
+
+use heck::TitleCase;
+use serde::{Deserialize, Serialize};
+
+use crate::{assets, settings};
+
+/// English texts mapped to the translated ones.
+pub type Translation = HashMap<String, String>;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Russian,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Russian];
+
+    /// The language's own name, so it can be found by the people who speak it.
+    pub fn title(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Russian => "Русский",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Language::English => Language::Russian,
+            Language::Russian => Language::English,
+        }
+    }
+
+    /// The translation's path in the assets, English is the original.
+    pub fn file(self) -> Option<&'static str> {
+        match self {
+            Language::English => None,
+            Language::Russian => Some("texts/ru.ron"),
+        }
+    }
+}
+
+/// Set in the settings. The screens that are already built keep their texts till they're rebuilt.
+pub fn language() -> Language {
+    settings::get().language
+}
+
+/// The text in the current language.
+pub fn translate(text: &str) -> String {
+    let translation = assets::get().translations.get(&language());
+    match translation.and_then(|translation| translation.get(text)) {
+        Some(translated) => translated.clone(),
+        None => text.into(),
+    }
+}
+
+/// The text in the current language with the named arguments filled in.
+pub fn translate_with(text: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut s = translate(text);
+    for (name, value) in args {
+        s = s.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    s
+}
+
+/// Translates every line, e.g. of an ability's description.
+pub fn translate_lines(lines: Vec<String>) -> Vec<String> {
+    lines.iter().map(|line| translate(line)).collect()
+}
+
+/// "Imp Bomber" for "imp_bomber": the names of the objects, items and campaign nodes.
+pub fn name_title(typename: &str) -> String {
+    translate(&typename.to_title_case())
+}
+
+/// `tr!("Actions left: {count}", count = camp.actions)`
+macro_rules! tr {
+    ($text:expr) => {
+        $crate::i18n::translate($text)
+    };
+    ($text:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate_with($text, &[$((stringify!($name), &$value)),+])
+    };
+}
+
+pub(crate) use tr;
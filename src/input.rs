@@ -0,0 +1,226 @@
+//! Raw input events collected between the frames.
+
+use std::{collections::HashMap, mem};
+
+use mq::{
+    input::utils::{register_input_subscriber, repeat_all_miniquad_input},
+    math::Vec2,
+    miniquad::{Context, EventHandler, KeyCode, KeyMods, MouseButton, TouchPhase},
+    time::get_time,
+};
+
+/// How much one step of the mouse wheel zooms.
+const WHEEL_ZOOM: f32 = 1.1;
+
+/// How long a finger has to be held to make a long press, in seconds.
+const LONG_PRESS_TIME: f64 = 0.5;
+
+/// How far (in pixels) a held finger can slide and still make a long press.
+const LONG_PRESS_SLOP: f32 = 16.0;
+
+/// A single finger that may turn into a long press.
+#[derive(Clone, Copy, Debug)]
+struct PressCandidate {
+    id: u64,
+    pos: Vec2,
+    start_time: f64,
+}
+
+/// A request to move the view of the current screen, all positions are in screen pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CameraMove {
+    /// The content under the cursor is dragged from one point to another.
+    Drag { from: Vec2, to: Vec2 },
+
+    /// Zoom in (`k > 1.0`) or out around the point.
+    Zoom { k: f32, pos: Vec2 },
+}
+
+/// All the clicks (and touches) that came since the last frame.
+///
+/// `is_mouse_button_pressed` only tells if there was a click during the frame
+/// and the click is then handled at the current mouse position.
+/// In browsers the frames are often long enough for that to lose quick clicks
+/// or to place them at a wrong tile, so every click is kept with its own position.
+pub struct Input {
+    subscriber: usize,
+    clicks: Vec<Vec2>,
+    key_presses: Vec<KeyCode>,
+    camera_moves: Vec<CameraMove>,
+    mouse_pos: Vec2,
+
+    /// Set while the right or the middle mouse button is held.
+    is_dragging: bool,
+
+    /// Current positions of the fingers, two of them pinch and drag the view.
+    touches: HashMap<u64, Vec2>,
+
+    press_candidate: Option<PressCandidate>,
+    long_presses: Vec<Vec2>,
+
+    /// Physical pixels per logical one, taken from the events' context.
+    dpi_scale: f32,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            subscriber: register_input_subscriber(),
+            clicks: Vec::new(),
+            key_presses: Vec::new(),
+            camera_moves: Vec::new(),
+            mouse_pos: Vec2::new(0.0, 0.0),
+            is_dragging: false,
+            touches: HashMap::new(),
+            press_candidate: None,
+            long_presses: Vec::new(),
+            dpi_scale: 1.0,
+        }
+    }
+
+    /// Collects the events that came since the last call.
+    pub fn poll(&mut self) {
+        let subscriber = self.subscriber;
+        repeat_all_miniquad_input(self, subscriber);
+        if let Some(candidate) = self.press_candidate {
+            if get_time() - candidate.start_time >= LONG_PRESS_TIME {
+                self.long_presses.push(candidate.pos);
+                self.press_candidate = None;
+            }
+        }
+    }
+
+    /// Screen positions of the left clicks in the order they happened.
+    pub fn take_clicks(&mut self) -> Vec<Vec2> {
+        mem::take(&mut self.clicks)
+    }
+
+    /// Screen positions of the fingers that were held still for a while.
+    ///
+    /// The touch has already made a click when it began.
+    pub fn take_long_presses(&mut self) -> Vec<Vec2> {
+        mem::take(&mut self.long_presses)
+    }
+
+    /// The pressed keys in the order they were pressed, the auto-repeats are skipped.
+    pub fn take_key_presses(&mut self) -> Vec<KeyCode> {
+        mem::take(&mut self.key_presses)
+    }
+
+    pub fn take_camera_moves(&mut self) -> Vec<CameraMove> {
+        mem::take(&mut self.camera_moves)
+    }
+
+    /// Stays 1.0 till the first mouse or touch event comes.
+    pub fn dpi_scale(&self) -> f32 {
+        self.dpi_scale
+    }
+
+    fn pinch(&mut self, id: u64, pos: Vec2) {
+        let other = match self.touches.iter().find(|&(&other_id, _)| other_id != id) {
+            Some((_, &other)) => other,
+            None => return,
+        };
+        let old = self.touches[&id];
+        let old_middle = (old + other) / 2.0;
+        let new_middle = (pos + other) / 2.0;
+        let old_distance = (old - other).length();
+        let new_distance = (pos - other).length();
+        if old_distance > 0.0 && new_distance > 0.0 {
+            let k = new_distance / old_distance;
+            self.camera_moves
+                .push(CameraMove::Zoom { k, pos: new_middle });
+        }
+        self.camera_moves.push(CameraMove::Drag {
+            from: old_middle,
+            to: new_middle,
+        });
+    }
+}
+
+impl EventHandler for Input {
+    fn update(&mut self, _: &mut Context) {}
+
+    fn draw(&mut self, _: &mut Context) {}
+
+    fn key_down_event(&mut self, _: &mut Context, key: KeyCode, _: KeyMods, repeat: bool) {
+        if !repeat {
+            self.key_presses.push(key);
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, _: &mut Context, button: MouseButton, x: f32, y: f32) {
+        match button {
+            MouseButton::Left => self.clicks.push(Vec2::new(x, y)),
+            MouseButton::Right | MouseButton::Middle => self.is_dragging = true,
+            _ => {}
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, _: &mut Context, button: MouseButton, _: f32, _: f32) {
+        if let MouseButton::Right | MouseButton::Middle = button {
+            self.is_dragging = false;
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32) {
+        self.dpi_scale = ctx.dpi_scale();
+        let pos = Vec2::new(x, y);
+        if self.is_dragging {
+            let from = self.mouse_pos;
+            self.camera_moves.push(CameraMove::Drag { from, to: pos });
+        }
+        self.mouse_pos = pos;
+    }
+
+    fn mouse_wheel_event(&mut self, _: &mut Context, _: f32, y: f32) {
+        if y == 0.0 {
+            return;
+        }
+        let k = if y > 0.0 {
+            WHEEL_ZOOM
+        } else {
+            1.0 / WHEEL_ZOOM
+        };
+        let pos = self.mouse_pos;
+        self.camera_moves.push(CameraMove::Zoom { k, pos });
+    }
+
+    // The clicks and the hovering are already emulated by macroquad,
+    // so only the two-finger gestures are handled here.
+    fn touch_event(&mut self, ctx: &mut Context, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.dpi_scale = ctx.dpi_scale();
+        let pos = Vec2::new(x, y);
+        match phase {
+            TouchPhase::Started => {
+                self.touches.insert(id, pos);
+                // A second finger means a gesture, not a press.
+                self.press_candidate = if self.touches.len() == 1 {
+                    let start_time = get_time();
+                    Some(PressCandidate {
+                        id,
+                        pos,
+                        start_time,
+                    })
+                } else {
+                    None
+                };
+            }
+            TouchPhase::Moved => {
+                if self.touches.len() == 2 && self.touches.contains_key(&id) {
+                    self.pinch(id, pos);
+                }
+                self.touches.insert(id, pos);
+                if let Some(candidate) = self.press_candidate {
+                    if candidate.id == id && (pos - candidate.pos).length() > LONG_PRESS_SLOP {
+                        self.press_candidate = None;
+                    }
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+                self.press_candidate = None;
+            }
+        }
+    }
+}
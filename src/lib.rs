@@ -0,0 +1,6 @@
+//! The rules of the game without the graphics, so the benches can link to them.
+
+// The core has grown as a part of the binary, its API isn't for other crates.
+#![allow(clippy::should_implement_trait, clippy::result_unit_err)]
+
+pub mod core;
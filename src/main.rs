@@ -2,53 +2,151 @@
 
 use std::time::Duration;
 
-use mq::{input, window};
+use mq::window;
+use zemeroth::core;
 
+mod achievements;
 mod assets;
-mod core;
+mod campaign_save;
+mod daily_records;
 mod error;
+mod frame_timing;
 mod geom;
+#[cfg(not(target_arch = "wasm32"))] // no file system in browsers
+mod hall_of_fame;
+mod hotkeys;
+mod i18n;
+mod input;
 mod screen;
+mod settings;
+#[cfg(not(target_arch = "wasm32"))] // no file system in browsers
+mod user_scenarios;
 mod utils;
 
 type ZResult<T = ()> = Result<T, error::ZError>;
 
+/// Long frames are split into several updates of at most this length,
+/// so the animations and the game logic don't jump over big time gaps.
+const MAX_UPDATE_STEP: f32 = 1.0 / 30.0;
+
+/// The game doesn't try to catch up with anything longer than this,
+/// e.g. after a browser tab was in the background.
+const MAX_FRAME_TIME: f32 = 0.25;
+
 struct MainState {
     screens: screen::ScreenStack,
+    input: input::Input,
+    bindings: hotkeys::Bindings,
+    frame_timing: frame_timing::FrameTiming,
 }
 
 impl MainState {
     fn new() -> ZResult<Self> {
         let start_screen = Box::new(screen::MainMenu::new()?);
         let screens = screen::ScreenStack::new(start_screen)?;
-        Ok(Self { screens })
+        Ok(Self {
+            screens,
+            input: input::Input::new(),
+            bindings: hotkeys::Bindings::default(),
+            frame_timing: frame_timing::FrameTiming::new(),
+        })
     }
 
     fn tick(&mut self) -> ZResult {
+        self.frame_timing.begin_frame();
         // Handle possible window resize and create a camera.
         let aspect_ratio = utils::aspect_ratio();
         let camera = utils::make_and_set_camera(aspect_ratio);
+        let ui_scale = settings::get().ui_scale;
+        ui::set_scale(ui_scale.factor(window::screen_height(), self.input.dpi_scale()));
         self.screens.resize(aspect_ratio)?;
-        // Handle user input events.
+        // Handle user input events before the update, so they're visible in this frame.
         let pos = utils::get_world_mouse_pos(&camera);
         self.screens.move_mouse(pos)?;
-        if input::is_mouse_button_pressed(input::MouseButton::Left) {
-            self.screens.click(pos)?;
+        self.input.poll();
+        let clicks = self.input.take_clicks();
+        for &click in &clicks {
+            self.screens.click(camera.screen_to_world(click))?;
+        }
+        for long_press in self.input.take_long_presses() {
+            self.screens
+                .long_press(camera.screen_to_world(long_press))?;
+        }
+        for key in self.input.take_key_presses() {
+            for hotkey in self.bindings.hotkeys(key) {
+                self.screens.hotkey(hotkey)?;
+            }
         }
+        let pan = self
+            .bindings
+            .pan(mq::time::get_frame_time().min(MAX_FRAME_TIME));
+        if pan != mq::math::Vec2::new(0.0, 0.0) {
+            self.screens.drag(pan)?;
+        }
+        for camera_move in self.input.take_camera_moves() {
+            match camera_move {
+                input::CameraMove::Drag { from, to } => {
+                    let delta = camera.screen_to_world(to) - camera.screen_to_world(from);
+                    self.screens.drag(delta)?;
+                }
+                input::CameraMove::Zoom { k, pos } => {
+                    self.screens.zoom(k, camera.screen_to_world(pos))?;
+                }
+            }
+        }
+        self.frame_timing.begin_update(clicks.len());
         // Update the game state.
-        let dtime = Duration::from_secs_f32(mq::time::get_frame_time());
-        self.screens.update(dtime)?;
+        let mut remaining = mq::time::get_frame_time().min(MAX_FRAME_TIME);
+        let mut steps = 0;
+        while remaining > 0.0 {
+            let step = remaining.min(MAX_UPDATE_STEP);
+            self.screens.update(Duration::from_secs_f32(step))?;
+            remaining -= step;
+            steps += 1;
+        }
+        self.frame_timing.begin_draw(steps);
         // Draw everything.
         mq::window::clear_background(screen::COLOR_SCREEN_BG);
         self.screens.draw()?;
+        self.frame_timing.end_frame();
         Ok(())
     }
 }
 
+/// `zemeroth --simulate [battles] [scenario.ron]` plays headless AI-vs-AI battles
+/// of the scenario (the demo one by default) and prints their stats instead of starting the game.
+#[cfg(not(target_arch = "wasm32"))]
+fn try_simulate() -> ZResult<bool> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) != Some("--simulate") {
+        return Ok(false);
+    }
+    let battles = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(100);
+    let assets = assets::get();
+    let scenario = match args.get(3) {
+        Some(path) => {
+            let s = std::fs::read_to_string(path)?;
+            ron::de::from_str(&s).map_err(|e| error::ZError::from_ron_de_error(e, path.into()))?
+        }
+        None => assets.demo_scenario.clone(),
+    };
+    let report = core::battle::simulate::simulate(
+        &assets.prototypes,
+        &assets.stack_rules,
+        &assets.ability_definitions,
+        &scenario,
+        battles,
+    );
+    println!("{}", report);
+    Ok(true)
+}
+
 fn window_conf() -> window::Conf {
     window::Conf {
         window_title: "Zemeroth".to_owned(),
         high_dpi: true,
+        // The window is created before `main` runs, so the settings are loaded here.
+        fullscreen: settings::load().fullscreen,
         ..Default::default()
     }
 }
@@ -63,10 +161,18 @@ async fn main() -> ZResult {
     }
     env_logger::init();
     quad_rand::srand(mq::miniquad::date::now() as _);
-    mq::file::set_pc_assets_folder("assets");
+    mq::file::set_pc_assets_folder(assets::DIR);
     assets::load().await.expect("Can't load assets");
+    #[cfg(not(target_arch = "wasm32"))]
+    if try_simulate().expect("Can't simulate the battles") {
+        return Ok(());
+    }
     let mut state = MainState::new().expect("Can't create the main state");
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    let mut watcher = assets::Watcher::new();
     loop {
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        watcher.reload_if_changed().await;
         state.tick().expect("Tick failed");
         window::next_frame().await;
     }
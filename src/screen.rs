@@ -6,18 +6,30 @@ use mq::{
     math::{Rect, Vec2},
 };
 
-use crate::{utils, ZResult};
+use crate::{hotkeys::Hotkey, utils, ZResult};
 
 mod agent_info;
 mod battle;
+mod camp;
 mod campaign;
+mod codex;
 mod confirm;
+mod editor;
 mod general_info;
 mod main_menu;
+mod mods;
+mod rename;
+mod settings;
+mod shop;
+mod skirmish;
+mod summary;
+mod world_map;
 
 pub use self::{
-    agent_info::AgentInfo, battle::Battle, campaign::Campaign, confirm::Confirm,
-    general_info::GeneralInfo, main_menu::MainMenu,
+    agent_info::AgentInfo, battle::Battle, camp::Camp, campaign::Campaign, codex::Codex,
+    confirm::Confirm, editor::Editor, general_info::GeneralInfo, main_menu::MainMenu, mods::Mods,
+    rename::Rename, settings::Settings, shop::Shop, skirmish::Skirmish, summary::Summary,
+    world_map::WorldMap,
 };
 
 pub const COLOR_SCREEN_BG: Color = Color::new(0.9, 0.9, 0.8, 1.0);
@@ -40,6 +52,31 @@ pub trait Screen: Debug {
     fn move_mouse(&mut self, _pos: Vec2) -> ZResult {
         Ok(())
     }
+
+    /// Moves the view of the screen along with the cursor, e.g. scrolls a battle map.
+    fn drag(&mut self, _delta: Vec2) -> ZResult {
+        Ok(())
+    }
+
+    /// Zooms in (`k > 1.0`) or out around the point.
+    fn zoom(&mut self, _k: f32, _pos: Vec2) -> ZResult {
+        Ok(())
+    }
+
+    fn hotkey(&mut self, _hotkey: Hotkey) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    /// The buttons that `Hotkey::FocusNext`, `FocusPrev` and `Select` go through,
+    /// for the screens that are nothing but menus.
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        None
+    }
+
+    /// A finger is held at the point, it has already clicked there.
+    fn long_press(&mut self, _pos: Vec2) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
 }
 
 const ERR_MSG_STACK_EMPTY: &str = "Screen stack is empty";
@@ -96,10 +133,46 @@ impl ScreenStack {
         self.handle_command(command)
     }
 
+    pub fn hotkey(&mut self, hotkey: Hotkey) -> ZResult {
+        let top = self.screen_mut().top_mut();
+        if let Some(focus) = top.focus() {
+            match hotkey {
+                Hotkey::FocusNext | Hotkey::FocusPrev => {
+                    let step = if hotkey == Hotkey::FocusNext { 1 } else { -1 };
+                    focus.move_focus(step);
+                    return Ok(());
+                }
+                Hotkey::Select => {
+                    // Pressing a focused button is clicking it.
+                    return match focus.focused_point() {
+                        Some(point) => self.click(point),
+                        None => Ok(()),
+                    };
+                }
+                _ => {}
+            }
+        }
+        let command = top.hotkey(hotkey)?;
+        self.handle_command(command)
+    }
+
+    pub fn long_press(&mut self, pos: Vec2) -> ZResult {
+        let command = self.screen_mut().top_mut().long_press(pos)?;
+        self.handle_command(command)
+    }
+
     pub fn move_mouse(&mut self, pos: Vec2) -> ZResult {
         self.screen_mut().top_mut().move_mouse(pos)
     }
 
+    pub fn drag(&mut self, delta: Vec2) -> ZResult {
+        self.screen_mut().top_mut().drag(delta)
+    }
+
+    pub fn zoom(&mut self, k: f32, pos: Vec2) -> ZResult {
+        self.screen_mut().top_mut().zoom(k, pos)
+    }
+
     pub fn resize(&mut self, aspect_ratio: f32) -> ZResult {
         for screen in &mut self.screens {
             screen.screen.resize(aspect_ratio);
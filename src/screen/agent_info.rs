@@ -1,15 +1,16 @@
 use std::time::Duration;
 
-use heck::TitleCase;
 use mq::{color::Color, math::Vec2};
 use ui::{self, Drawable, Gui, Widget};
 
 use crate::{
     assets,
     core::battle::{
-        ability::{Ability, PassiveAbility},
-        component::{self, Component, ObjType, Prototypes},
+        ability::{Ability, PassiveAbility, Tier},
+        codex,
+        component::{self, Component, MovementType, ObjType, Prototypes},
     },
+    i18n::{self, tr},
     screen::{self, Screen, StackCommand},
     utils, ZResult,
 };
@@ -21,11 +22,14 @@ struct StaticObjectInfo {
     meta: Option<component::Meta>,
     strength: Option<component::Strength>,
     armor: Option<component::Armor>,
+    resistances: Option<component::Resistances>,
     agent: Option<component::Agent>,
     blocker: Option<component::Blocker>,
     abilities: Option<component::Abilities>,
+    ability_tiers: Option<component::AbilityTiers>,
     passive_abilities: Option<component::PassiveAbilities>,
     summoner: Option<component::Summoner>,
+    stamina: Option<component::Stamina>,
 }
 
 impl StaticObjectInfo {
@@ -37,16 +41,32 @@ impl StaticObjectInfo {
             match component.clone() {
                 Component::Strength(c) => this.strength = Some(c),
                 Component::Armor(c) => this.armor = Some(c),
+                Component::Resistances(c) => this.resistances = Some(c),
                 Component::Meta(c) => this.meta = Some(c),
                 Component::Agent(c) => this.agent = Some(c),
                 Component::Abilities(c) => this.abilities = Some(c),
                 Component::PassiveAbilities(c) => this.passive_abilities = Some(c),
                 Component::Summoner(c) => this.summoner = Some(c),
                 Component::Blocker(c) => this.blocker = Some(c),
+                Component::Stamina(c) => this.stamina = Some(c),
+                Component::AbilityTiers(c) => this.ability_tiers = Some(c),
                 Component::BelongsTo(_)
                 | Component::Pos(_)
                 | Component::Effects(_)
-                | Component::Schedule(_) => (),
+                | Component::Reactions(_)
+                | Component::Auras(_)
+                | Component::Schedule(_)
+                | Component::SummonedBy(_)
+                | Component::Corpse(_)
+                | Component::Morale(_)
+                | Component::Veteran(_)
+                | Component::Persona(_)
+                | Component::Charges(_)
+                | Component::Boss(_)
+                | Component::Facing(_)
+                | Component::Personality(_)
+                | Component::Footprint(_)
+                | Component::Mount(_) => (),
             }
         }
         this
@@ -67,6 +87,7 @@ fn agent_image(typename: &ObjType) -> ZResult<Box<dyn ui::Widget>> {
 #[derive(Clone, Debug)]
 enum Message {
     Back,
+    Codex,
     AbilityInfo(Ability),
     PassiveAbilityInfo(PassiveAbility),
 }
@@ -79,6 +100,7 @@ fn info_panel(
     let font = assets::get().font;
     let proto = &prototypes.0[typename];
     let info = StaticObjectInfo::new(typename, proto);
+    let is_agent = info.agent.is_some();
     let h = utils::line_heights().normal;
     let space_between_buttons = h / 8.0;
     let mut layout = Box::new(ui::VLayout::new().stretchable(true));
@@ -92,7 +114,7 @@ fn info_panel(
     let spacer_s = || Box::new(ui::Spacer::new_horizontal(h * 0.5).stretchable(true));
     let line = |arg: &str, val: &str| -> ZResult<_> {
         let mut line = ui::HLayout::new().stretchable(true);
-        line.add(label(arg)?);
+        line.add(label(&tr!(arg))?);
         line.add(spacer_s());
         line.add(label(val)?);
         Ok(Box::new(line))
@@ -100,7 +122,7 @@ fn info_panel(
     let line_i = |arg: &str, val: i32| -> ZResult<_> { line(arg, &val.to_string()) };
     {
         if let Some(meta) = info.meta {
-            let title = meta.name.0.to_title_case();
+            let title = i18n::name_title(&meta.name.0);
             add(label_s(&format!("~~~ {} ~~~", title))?);
             add(spacer_v());
         }
@@ -108,6 +130,15 @@ fn info_panel(
             add(line_i("strength:", strength.base_strength.0)?);
         }
         if let Some(a) = info.agent {
+            if a.is_noncombatant {
+                add(label(&tr!("can't fight"))?);
+            }
+            if a.cannot_counter {
+                add(label(&tr!("doesn't counter"))?);
+            }
+            if a.movement_type == MovementType::Fly {
+                add(label(&tr!("flies"))?);
+            }
             add(line_i("attacks:", a.base_attacks.0)?);
             add(line_i("moves:", a.base_moves.0)?);
             if a.base_jokers.0 != 0 {
@@ -135,16 +166,50 @@ fn info_panel(
                 add(line_i("armor:", armor)?);
             }
         }
+        if let Some(resistances) = &info.resistances {
+            add(line(
+                "resistances:",
+                &screen::codex::resistances_title(resistances),
+            )?);
+        }
+        if let Some(stamina) = &info.stamina {
+            let text = tr!(
+                "{stamina} (+{regeneration}/t)",
+                stamina = stamina.base_stamina,
+                regeneration = stamina.regeneration,
+            );
+            add(line("stamina:", &text)?);
+        }
         if let Some(blocker) = info.blocker {
-            add(line("weight:", &format!("{}", blocker.weight))?);
+            add(line("weight:", &tr!(&blocker.weight.to_string()))?);
         }
         if let Some(abilities) = info.abilities {
             if !abilities.0.is_empty() {
-                add(label_s("~ abilities ~")?);
+                add(label_s(&tr!("~ abilities ~"))?);
+                let definitions = &assets::get().ability_definitions;
+                let tiers = info.ability_tiers.unwrap_or_default();
                 for r_ability in &abilities.0 {
-                    let s = r_ability.ability.title();
-                    let cooldown = r_ability.ability.base_cooldown();
-                    let text = format!("{} (cooldown: {}t)", s, cooldown);
+                    let tier = tiers.tier(r_ability.ability);
+                    let ability =
+                        screen::codex::ability_title(definitions, r_ability.ability, tier);
+                    let cost = info
+                        .stamina
+                        .as_ref()
+                        .and_then(|stamina| stamina.cost(r_ability.ability));
+                    let text = if let Some(cost) = cost {
+                        tr!(
+                            "{ability} (stamina: {cost})",
+                            ability = ability,
+                            cost = cost
+                        )
+                    } else {
+                        let cooldown = definitions.params(r_ability.ability, tier).cooldown;
+                        tr!(
+                            "{ability} (cooldown: {cooldown}t)",
+                            ability = ability,
+                            cooldown = cooldown,
+                        )
+                    };
                     let mut line_layout = ui::HLayout::new().stretchable(true);
                     line_layout.add(label(&text)?);
                     line_layout.add(spacer_s());
@@ -159,10 +224,10 @@ fn info_panel(
         }
         if let Some(abilities) = info.passive_abilities {
             if !abilities.0.is_empty() {
-                add(label_s("~ passive abilities ~")?);
+                add(label_s(&tr!("~ passive abilities ~"))?);
                 for &ability in &abilities.0 {
                     let mut line_layout = ui::HLayout::new().stretchable(true);
-                    line_layout.add(label(&ability.title())?);
+                    line_layout.add(label(&tr!(&ability.title()))?);
                     line_layout.add(spacer_s());
                     let icon = Drawable::Texture(assets::get().textures.icons.info);
                     let message = Message::PassiveAbilityInfo(ability);
@@ -173,16 +238,33 @@ fn info_panel(
                 }
             }
         }
+        if is_agent {
+            let counters = codex::counters(prototypes, typename);
+            if !counters.is_empty() {
+                add(label_s(&tr!("~ countered by ~"))?);
+                for counter in &counters {
+                    add(label(&i18n::name_title(&counter.0))?);
+                }
+            }
+        }
     }
     layout.stretch_to_self();
     Ok(layout)
 }
 
 fn button_back(gui: &mut ui::Gui<Message>, layout_width: f32) -> ZResult<Box<dyn ui::Widget>> {
+    small_button(gui, &tr!("back"), Message::Back, layout_width)
+}
+
+fn small_button(
+    gui: &mut ui::Gui<Message>,
+    text: &str,
+    msg: Message,
+    layout_width: f32,
+) -> ZResult<Box<dyn ui::Widget>> {
     let font = assets::get().font;
     let h = utils::line_heights().normal;
-    let text = ui::Drawable::text("back", font);
-    let msg = Message::Back;
+    let text = ui::Drawable::text(text, font);
     let mut button = ui::Button::new(text, h, gui.sender(), msg)?.stretchable(true);
     button.stretch(layout_width / 3.0);
     button.set_stretchable(false);
@@ -192,20 +274,48 @@ fn button_back(gui: &mut ui::Gui<Message>, layout_width: f32) -> ZResult<Box<dyn
 #[derive(Debug)]
 pub struct AgentInfo {
     gui: Gui<Message>,
+
+    /// For the codex, which can be opened from here.
+    prototypes: Prototypes,
 }
 
 impl AgentInfo {
     pub fn new_agent_info(prototypes: &Prototypes, typename: &ObjType) -> ZResult<Self> {
+        Self::new_agent_panel(prototypes, typename, true)
+    }
+
+    /// An entry opened from the codex itself, so there's no way back into it.
+    pub fn new_codex_entry(prototypes: &Prototypes, typename: &ObjType) -> ZResult<Self> {
+        Self::new_agent_panel(prototypes, typename, false)
+    }
+
+    fn new_agent_panel(
+        prototypes: &Prototypes,
+        typename: &ObjType,
+        has_codex_button: bool,
+    ) -> ZResult<Self> {
         let mut gui = ui::Gui::new();
         let mut layout = ui::VLayout::new();
         let h = utils::line_heights().big;
         layout.add(info_panel(&mut gui, prototypes, typename)?);
         layout.add(Box::new(ui::Spacer::new_vertical(h)));
-        layout.add(button_back(&mut gui, layout.rect().w)?);
+        let width = layout.rect().w;
+        if has_codex_button {
+            layout.add(small_button(
+                &mut gui,
+                &tr!("codex"),
+                Message::Codex,
+                width,
+            )?);
+            let space = utils::line_heights().normal / 8.0;
+            layout.add(Box::new(ui::Spacer::new_vertical(space)));
+        }
+        layout.add(button_back(&mut gui, width)?);
         let layout = utils::add_offsets_and_bg_big(Box::new(layout))?;
         let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
         gui.add(&ui::pack(layout), anchor);
-        Ok(Self { gui })
+        let prototypes = prototypes.clone();
+        Ok(Self { gui, prototypes })
     }
 
     pub fn new_upgrade_info(
@@ -241,7 +351,8 @@ impl AgentInfo {
         let layout = utils::add_offsets_and_bg_big(Box::new(layout))?;
         let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
         gui.add(&ui::pack(layout), anchor);
-        Ok(Self { gui })
+        let prototypes = prototypes.clone();
+        Ok(Self { gui, prototypes })
     }
 }
 
@@ -259,14 +370,26 @@ impl Screen for AgentInfo {
         let message = self.gui.click(pos);
         match message {
             Some(Message::Back) => Ok(StackCommand::Pop),
+            Some(Message::Codex) => {
+                let screen = screen::Codex::new(&self.prototypes)?;
+                Ok(StackCommand::PushPopup(Box::new(screen)))
+            }
             Some(Message::AbilityInfo(info)) => {
-                let mut description = info.description();
-                description.push(format!("Cooldown: {}t", info.base_cooldown()));
-                let screen = screen::GeneralInfo::new(&info.title(), &description)?;
+                let definitions = &assets::get().ability_definitions;
+                let mut description = i18n::translate_lines(info.description());
+                let tiers = screen::codex::tier_lines(definitions, info);
+                if tiers.is_empty() {
+                    let cooldown = definitions.params(info, Tier::default()).cooldown;
+                    description.push(tr!("Cooldown: {cooldown}t", cooldown = cooldown));
+                }
+                description.extend(tiers);
+                let screen = screen::GeneralInfo::new(&tr!(&info.title()), &description)?;
                 Ok(StackCommand::PushPopup(Box::new(screen)))
             }
             Some(Message::PassiveAbilityInfo(info)) => {
-                let screen = screen::GeneralInfo::new(&info.title(), &info.description())?;
+                let title = tr!(&info.title());
+                let description = i18n::translate_lines(info.description());
+                let screen = screen::GeneralInfo::new(&title, &description)?;
                 Ok(StackCommand::PushPopup(Box::new(screen)))
             }
             None => Ok(StackCommand::None),
@@ -277,6 +400,10 @@ impl Screen for AgentInfo {
         self.gui.resize_if_needed(aspect_ratio);
     }
 
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
     fn move_mouse(&mut self, pos: Vec2) -> ZResult {
         self.gui.move_mouse(pos);
         Ok(())
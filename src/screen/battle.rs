@@ -3,7 +3,6 @@ use std::{
     time::Duration,
 };
 
-use heck::TitleCase;
 use log::{info, trace};
 use mq::{color::Color, math::Vec2};
 
@@ -11,47 +10,87 @@ use ui::{self, Gui, Widget};
 use zscene::{action, Action, Boxed};
 
 use crate::{
-    assets,
+    achievements, assets,
     core::{
+        self,
+        achievements::Trigger,
         battle::{
             self,
-            ability::{self, Ability, PassiveAbility},
+            ability::{Ability, PassiveAbility},
             ai::Ai,
-            check, command,
-            component::Prototypes,
+            check,
+            command::{self, JokerUse},
+            component::{Component, Prototypes},
             effect,
+            execute::{execute_batch, ApplyPhase},
+            forecast, hint,
+            log::Log,
             movement::Pathfinder,
             scenario,
-            state::{self, BattleResult},
-            Id, PlayerId, State,
+            state::{self, AbilityStatus, BattleResult, LastingEffectStatus},
+            stats::Stats,
+            threat, tutorial, Id, PlayerId, State,
         },
-        map::PosHex,
+        map::{self, Dir, PosHex},
+        skirmish,
     },
-    geom,
+    daily_records, geom,
+    hotkeys::Hotkey,
+    i18n::{self, tr},
     screen::{
         self,
         battle::{
+            ai_worker::AiWorker,
+            dialogue::Dialogue,
+            objectives::build_panel_objectives,
+            preview::build_panel_forecast,
             view::{make_action_create_map, BattleView, SelectionMode},
             visualize::{color, fork, visualize},
         },
         Screen, StackCommand,
     },
+    settings,
     utils::{self, line_heights, time_s},
     ZResult,
 };
 
+mod ai_worker;
+mod dialogue;
+mod history;
+mod objectives;
+mod preview;
+mod rewind;
 mod view;
 mod visualize;
 
+pub use self::view::{tile_size, tile_sprite};
+
 #[derive(Clone, Debug)]
 enum Message {
     Exit,
+    ToggleCamera,
+    ToggleAnimationSpeed,
+    ToggleThreat,
+    ToggleHistory,
+    ScrollHistory(Scroll),
+    SaveHistory,
+    Rewind,
     EndTurn,
+    Retreat,
     Ability(Ability),
+    ConvertJoker(JokerUse),
+    Mount(Id),
+    Dismount,
     PassiveAbilityInfo(PassiveAbility),
     LastingEffectInfo(effect::Lasting),
 }
 
+#[derive(Clone, Copy, Debug)]
+enum Scroll {
+    Older,
+    Newer,
+}
+
 fn textures() -> &'static assets::Textures {
     &assets::get().textures
 }
@@ -72,12 +111,54 @@ fn line_with_info_button(
     Ok(line)
 }
 
+/// One dot for every turn left till the lasting effect wears off,
+/// highlighted during the last one. Endless effects get no dots.
+fn build_turns_left_pips(status: &LastingEffectStatus) -> ZResult<Box<dyn Widget>> {
+    let h = line_heights().normal;
+    let turns_left = status.turns_left.unwrap_or(0);
+    let color = if turns_left == 1 {
+        color::ATTACKS
+    } else {
+        color::JOKERS
+    };
+    let mut pips = ui::HLayout::new();
+    for _ in 0..turns_left {
+        let param = ui::LabelParam {
+            drawable_k: 0.3,
+            ..Default::default()
+        };
+        let dot = ui::Drawable::Texture(textures().dot);
+        let pip = ui::Label::from_params(dot, h * 0.6, param)?.with_color(color);
+        pips.add(Box::new(pip));
+    }
+    Ok(Box::new(pips))
+}
+
+/// One dot for every charge of a multi-charge ability, the spent ones are dimmed.
+fn build_charges_pips(status: &AbilityStatus, h: f32) -> ZResult<Box<dyn Widget>> {
+    let mut pips = ui::HLayout::new();
+    for i in 0..status.max_charges {
+        let param = ui::LabelParam {
+            drawable_k: 0.3,
+            ..Default::default()
+        };
+        let color = if i < status.charges {
+            color::JOKERS
+        } else {
+            color::DAMAGE
+        };
+        let dot = ui::Drawable::Texture(textures().dot);
+        let pip = ui::Label::from_params(dot, h, param)?.with_color(color);
+        pips.add(Box::new(pip));
+    }
+    Ok(Box::new(pips))
+}
+
 // TODO: consider moving ui `build_*` functions to a sub-module
 fn build_panel_agent_info(gui: &mut Gui<Message>, state: &State, id: Id) -> ZResult<ui::RcWidget> {
     let font = assets::get().font;
     let parts = state.parts();
     let st = parts.strength.get(id);
-    let meta = parts.meta.get(id);
     let a = parts.agent.get(id);
     let mut layout = Box::new(ui::VLayout::new().stretchable(true));
     let h = line_heights().normal;
@@ -89,7 +170,7 @@ fn build_panel_agent_info(gui: &mut Gui<Message>, state: &State, id: Id) -> ZRes
     let label_s = |text: &str| -> ZResult<_> { Ok(Box::new(label_(text)?.stretchable(true))) };
     let line = |arg: &str, val: &str| -> ZResult<_> {
         let mut line = ui::HLayout::new().stretchable(true);
-        line.add(label(arg)?);
+        line.add(label(&tr!(arg))?);
         line.add(Box::new(ui::Spacer::new_horizontal(h).stretchable(true)));
         line.add(label(val)?);
         Ok(Box::new(line))
@@ -106,13 +187,13 @@ fn build_panel_agent_info(gui: &mut Gui<Message>, state: &State, id: Id) -> ZRes
             .with_color(dot_color);
         line.add(Box::new(label_dot));
         line.add(Box::new(ui::Spacer::new_horizontal(h * 0.1)));
-        line.add(label(arg)?);
+        line.add(label(&tr!(arg))?);
         line.add(Box::new(ui::Spacer::new_horizontal(h).stretchable(true)));
         line.add(label(val)?);
         Ok(Box::new(line))
     };
     {
-        let title = meta.name.0.to_title_case();
+        let title = agent_title(state, id);
         add(label_s(&format!("~~~ {} ~~~", title))?);
         add(line_dot(
             "strength:",
@@ -125,6 +206,27 @@ fn build_panel_agent_info(gui: &mut Gui<Message>, state: &State, id: Id) -> ZRes
                 add(line_dot("armor:", &armor.to_string(), color::ARMOR)?);
             }
         }
+        if let Some(resistances) = parts.resistances.get_opt(id) {
+            add(line(
+                "resistances:",
+                &screen::codex::resistances_title(resistances),
+            )?);
+        }
+        if let Some(morale) = parts.morale.get_opt(id) {
+            let text = if state::is_routed(state, id) {
+                tr!("routed")
+            } else {
+                format!("{}/{}", morale.morale, morale.base_morale)
+            };
+            add(line("morale:", &text)?);
+        }
+        if let Some(stamina) = parts.stamina.get_opt(id) {
+            add(line_dot(
+                "stamina:",
+                &format!("{}/{}", stamina.stamina, stamina.base_stamina),
+                color::STAMINA,
+            )?);
+        }
         if a.jokers.0 != 0 || a.base_jokers.0 != 0 {
             add(line_dot(
                 "jokers:",
@@ -132,9 +234,38 @@ fn build_panel_agent_info(gui: &mut Gui<Message>, state: &State, id: Id) -> ZRes
                 color::JOKERS,
             )?);
         }
+        let is_players_agent = parts.belongs_to.get(id).0 == PlayerId(0);
+        if a.jokers.0 > 0 && is_players_agent && state.player_id() == PlayerId(0) {
+            let mut line = ui::HLayout::new().stretchable(true);
+            line.add(label(&tr!("spend a joker:"))?);
+            line.add(Box::new(ui::Spacer::new_horizontal(h).stretchable(true)));
+            for &(text, into) in &[("move", JokerUse::Move), ("attack", JokerUse::Attack)] {
+                let message = Message::ConvertJoker(into);
+                let button = ui::Button::new(text_(&tr!(text)), h, gui.sender(), message)?;
+                line.add(Box::new(button));
+                line.add(Box::new(ui::Spacer::new_horizontal(space_between_buttons)));
+            }
+            add(Box::new(line));
+            add(Box::new(ui::Spacer::new_vertical(space_between_buttons)));
+        }
+        if is_players_agent && state.player_id() == PlayerId(0) {
+            for mount_id in state::free_mounts_around(state, id) {
+                let text = tr!("mount {mount}", mount = agent_title(state, mount_id));
+                let message = Message::Mount(mount_id);
+                let button = ui::Button::new(text_(&text), h, gui.sender(), message)?;
+                add(Box::new(button));
+                add(Box::new(ui::Spacer::new_vertical(space_between_buttons)));
+            }
+            if state::rider_id(state, id).is_some() {
+                let text = text_(&tr!("dismount"));
+                let button = ui::Button::new(text, h, gui.sender(), Message::Dismount)?;
+                add(Box::new(button));
+                add(Box::new(ui::Spacer::new_vertical(space_between_buttons)));
+            }
+        }
         add(line_dot(
             "attacks:",
-            &format!("{}/{}", a.attacks.0, a.base_attacks.0),
+            &format!("{}/{}", a.attacks.0, state::base_attacks(state, id).0),
             color::ATTACKS,
         )?);
         if a.reactive_attacks.0 != 0 {
@@ -162,49 +293,45 @@ fn build_panel_agent_info(gui: &mut Gui<Message>, state: &State, id: Id) -> ZRes
         }
         add(line_i("move points:", a.move_points.0)?);
         if let Some(blocker) = parts.blocker.get_opt(id) {
-            add(line("weight:", &blocker.weight.to_string())?);
+            add(line("weight:", &tr!(&blocker.weight.to_string()))?);
         }
         if let Some(abilities) = parts.passive_abilities.get_opt(id) {
             if !abilities.0.is_empty() {
-                add(label_s("~ passive abilities ~")?);
+                add(label_s(&tr!("~ passive abilities ~"))?);
                 for &ability in &abilities.0 {
-                    let text = ability.title();
+                    let text = tr!(&ability.title());
                     let message = Message::PassiveAbilityInfo(ability);
                     add(line_with_info_button(gui, &text, message)?);
                     add(Box::new(ui::Spacer::new_vertical(space_between_buttons)));
                 }
             }
         }
-        if let Some(effects) = parts.effects.get_opt(id) {
-            if !effects.0.is_empty() {
-                add(label_s("~ effects ~")?);
-                for effect in &effects.0 {
-                    let s = effect.effect.title();
-                    let text = match effect.duration {
-                        effect::Duration::Forever => s.into(),
-                        effect::Duration::Rounds(n) => format!("{} ({}t)", s, n),
-                    };
-                    let message = Message::LastingEffectInfo(effect.effect);
-                    let text = ui::Drawable::text(text, font);
-                    let tex_info = ui::Drawable::Texture(textures().icons.info);
-                    let button_info = ui::Button::new(tex_info, h, gui.sender(), message)?;
-                    let icon_effect = visualize::get_effect_icon(&effect.effect);
-                    let param = ui::LabelParam {
-                        drawable_k: 0.6,
-                        ..Default::default()
-                    };
-                    let label_effect =
-                        ui::Label::from_params(ui::Drawable::Texture(icon_effect), h, param)?
-                            .with_color(Color::new(1.0, 1.0, 1.0, 1.0));
-                    let mut line = Box::new(ui::HLayout::new().stretchable(true));
-                    line.add(Box::new(label_effect));
-                    line.add(Box::new(ui::Spacer::new_horizontal(h * 0.1)));
-                    line.add(Box::new(ui::Label::new(text, h)?));
-                    line.add(Box::new(ui::Spacer::new_horizontal(0.0).stretchable(true)));
-                    line.add(Box::new(button_info));
-                    add(line);
-                    add(Box::new(ui::Spacer::new_vertical(space_between_buttons)));
-                }
+        let effects = state.lasting_effects(id);
+        if !effects.is_empty() {
+            add(label_s(&tr!("~ effects ~"))?);
+            for status in &effects {
+                let message = Message::LastingEffectInfo(status.effect);
+                let text = ui::Drawable::text(tr!(status.effect.title()), font);
+                let tex_info = ui::Drawable::Texture(textures().icons.info);
+                let button_info = ui::Button::new(tex_info, h, gui.sender(), message)?;
+                let icon_effect = visualize::get_effect_icon(&status.effect);
+                let param = ui::LabelParam {
+                    drawable_k: 0.6,
+                    ..Default::default()
+                };
+                let label_effect =
+                    ui::Label::from_params(ui::Drawable::Texture(icon_effect), h, param)?
+                        .with_color(Color::new(1.0, 1.0, 1.0, 1.0));
+                let mut line = Box::new(ui::HLayout::new().stretchable(true));
+                line.add(Box::new(label_effect));
+                line.add(Box::new(ui::Spacer::new_horizontal(h * 0.1)));
+                line.add(Box::new(ui::Label::new(text, h)?));
+                line.add(Box::new(ui::Spacer::new_horizontal(h * 0.2)));
+                line.add(build_turns_left_pips(status)?);
+                line.add(Box::new(ui::Spacer::new_horizontal(0.0).stretchable(true)));
+                line.add(Box::new(button_info));
+                add(line);
+                add(Box::new(ui::Spacer::new_vertical(space_between_buttons)));
             }
         }
     }
@@ -223,14 +350,17 @@ fn build_panel_agent_abilities(
     mode: &SelectionMode,
 ) -> ZResult<Option<ui::RcWidget>> {
     let font = assets::get().font;
-    let parts = state.parts();
-    let abilities = match parts.abilities.get_opt(id) {
-        Some(abilities) => &abilities.0,
-        None => return Ok(None),
-    };
+    let abilities = state.abilities(id);
+    if abilities.is_empty() {
+        return Ok(None);
+    }
     let mut layout = ui::VLayout::new().stretchable(true);
-    let h = line_heights().large;
-    for ability in abilities {
+    let (h, spacing) = if settings::get().large_buttons {
+        (line_heights().large * 1.3, 1.0 / 3.0)
+    } else {
+        (line_heights().large, 1.0 / 8.0)
+    };
+    for ability in &abilities {
         let icons = &assets::get().textures.icons.abilities;
         let texture = *icons.get(&ability.ability).expect("No such icon found");
         let drawable = ui::Drawable::Texture(texture);
@@ -244,17 +374,20 @@ fn build_panel_agent_abilities(
                 button.set_color(Color::new(0.0, 0.0, 0.9, 1.0));
             }
         }
-        if let ability::Status::Cooldown(n) = ability.status {
+        if ability.charges == 0 {
             let mut layers = ui::LayersLayout::new();
             layers.add(Box::new(button));
-            let text = ui::Drawable::text(format!(" ({})", n).as_str(), font);
-            let label = ui::Label::new(text, h / 2.0)?;
+            let text = format!(" ({})", ability.cooldown);
+            let label = ui::Label::new(ui::Drawable::text(text.as_str(), font), h / 2.0)?;
             layers.add(Box::new(label));
             layout.add(Box::new(layers));
         } else {
             layout.add(Box::new(button));
         }
-        layout.add(Box::new(ui::Spacer::new_vertical(h / 8.0)));
+        if ability.max_charges > 1 {
+            layout.add(build_charges_pips(ability, h / 4.0)?);
+        }
+        layout.add(Box::new(ui::Spacer::new_vertical(h * spacing)));
     }
     let anchor = ui::Anchor(ui::HAnchor::Right, ui::VAnchor::Middle);
     let packed_layout = ui::pack(layout);
@@ -262,11 +395,44 @@ fn build_panel_agent_abilities(
     Ok(Some(packed_layout))
 }
 
-fn build_panel_end_turn(gui: &mut Gui<Message>) -> ZResult<ui::RcWidget> {
+fn build_panel_end_turn(gui: &mut Gui<Message>, state: &State) -> ZResult<ui::RcWidget> {
     let h = line_heights().large;
+    let mut layout = ui::VLayout::new();
+    if check(state, &command::Retreat.into()).is_ok() {
+        let text = ui::Drawable::text(tr!("retreat"), assets::get().font);
+        let button = ui::Button::new(text, line_heights().normal, gui.sender(), Message::Retreat)?;
+        layout.add(Box::new(button));
+        layout.add(Box::new(ui::Spacer::new_vertical(h / 8.0)));
+    }
     let tex = ui::Drawable::Texture(textures().icons.end_turn);
     let button = ui::Button::new(tex, h, gui.sender(), Message::EndTurn)?;
-    let layout = ui::VLayout::from_widget(Box::new(button));
+    layout.add(Box::new(button));
+    let anchor = ui::Anchor(ui::HAnchor::Right, ui::VAnchor::Bottom);
+    let packed_layout = ui::pack(layout);
+    gui.add(&packed_layout, anchor);
+    Ok(packed_layout)
+}
+
+fn build_panel_deployment(gui: &mut Gui<Message>, state: &State) -> ZResult<ui::RcWidget> {
+    let font = assets::get().font;
+    let h = line_heights().normal;
+    let deployment = state.deployment();
+    let group = &deployment[0];
+    let name = group
+        .extra_components
+        .iter()
+        .find_map(|component| match component {
+            Component::Persona(persona) => persona.name.as_deref(),
+            _ => None,
+        });
+    let title = utils::agent_name(name, &group.typename.0);
+    let text = tr!(
+        "Place {agent} ({count} left)",
+        agent = title,
+        count = deployment.len(),
+    );
+    let label = ui::Label::new_with_bg(ui::Drawable::text(text, font), h)?;
+    let layout = ui::VLayout::from_widget(Box::new(label));
     let anchor = ui::Anchor(ui::HAnchor::Right, ui::VAnchor::Bottom);
     let packed_layout = ui::pack(layout);
     gui.add(&packed_layout, anchor);
@@ -283,35 +449,77 @@ fn build_panel_ability_description(
     let text = |s: &str| ui::Drawable::text(s, font);
     let h = line_heights().normal;
     let mut layout = Box::new(ui::VLayout::new().stretchable(true));
-    let text_title = text(&format!("~~~ {} ~~~", ability.title()));
+    let definitions = state.ability_definitions();
+    let tier = state::ability_tier(state, id, *ability);
+    let title = screen::codex::ability_title(definitions, *ability, tier);
+    let text_title = text(&format!("~~~ {} ~~~", title));
     let label_title = ui::Label::new(text_title, h)?.stretchable(true);
     layout.add(Box::new(label_title));
     layout.add(Box::new(ui::Spacer::new_vertical(h / 2.0)));
-    for line in ability.description() {
+    for line in i18n::translate_lines(ability.description()) {
         layout.add(Box::new(ui::Label::new(text(&line), h)?));
     }
     let agent_player_id = state.parts().belongs_to.get(id).0;
-    let abilities = &state.parts().abilities.get(id).0;
-    let r_ability = abilities.iter().find(|r| &r.ability == ability).unwrap();
+    let abilities = state.abilities(id);
+    let status = abilities.iter().find(|s| &s.ability == ability).unwrap();
     let is_enemy_agent = agent_player_id != state.player_id();
-    let cooldown = r_ability.ability.base_cooldown();
-    let text_cooldown = text(&format!("Cooldown: {}t", cooldown));
-    layout.add(Box::new(ui::Label::new(text_cooldown, h)?));
+    let stamina = state.parts().stamina.get_opt(id).map_or(0, |s| s.stamina);
+    if let Some(cost) = status.stamina_cost {
+        let text_cost = text(&tr!("Stamina cost: {cost}", cost = cost));
+        layout.add(Box::new(ui::Label::new(text_cost, h)?));
+    } else {
+        let cooldown = definitions.params(*ability, tier).cooldown;
+        let text_cooldown = text(&tr!("Cooldown: {cooldown}t", cooldown = cooldown));
+        layout.add(Box::new(ui::Label::new(text_cooldown, h)?));
+    }
+    if definitions.tiers_count(*ability) > 1 {
+        let params = definitions.params(*ability, tier);
+        let text_params = text(&tr!(
+            "Power: {power}, range: {distance}",
+            power = params.power,
+            distance = screen::codex::range_title(params.distance)
+        ));
+        layout.add(Box::new(ui::Label::new(text_params, h)?));
+    }
+    if status.max_charges > 1 {
+        let text_charges = text(&tr!(
+            "Charges: {charges} of {max}",
+            charges = status.charges,
+            max = status.max_charges
+        ));
+        layout.add(Box::new(ui::Label::new(text_charges, h)?));
+    }
+    if let Some(charges_left) = status.uses_left {
+        let text_charges = text(&tr!(
+            "Uses left until a rest: {count}",
+            count = charges_left
+        ));
+        layout.add(Box::new(ui::Label::new(text_charges, h)?));
+    }
     if !state::can_agent_use_ability(state, id, ability) {
         layout.add(Box::new(ui::Spacer::new_vertical(h / 2.0)));
         let s = if is_enemy_agent {
-            "Can't be used: enemy agent.".into()
-        } else if let ability::Status::Cooldown(n) = r_ability.status {
-            format!("Can't be used: cooldown ({}t).", n)
+            tr!("Can't be used: enemy agent.")
+        } else if status.charges == 0 {
+            tr!(
+                "Can't be used: cooldown ({cooldown}t).",
+                cooldown = status.cooldown
+            )
+        } else if status.uses_left == Some(0) {
+            tr!("Can't be used: no uses left until a rest.")
+        } else if status.stamina_cost.is_some_and(|cost| cost > stamina) {
+            tr!("Can't be used: not enough stamina.")
         } else {
-            "Can't be used: no attacks or jokers.".into()
+            tr!("Can't be used: no attacks or jokers.")
         };
         let color = Color::new(0.5, 0.0, 0.0, 1.0);
         let label = ui::Label::new(text(&s), h)?.with_color(color);
         layout.add(Box::new(label));
     }
     layout.add(Box::new(ui::Spacer::new_vertical(h / 2.0)));
-    let text_cancel = text("Click on an empty tile or the ability icon to cancel.");
+    let text_cancel = text(&tr!(
+        "Click on an empty tile or the ability icon to cancel."
+    ));
     let color_cancel = Color::new(0.4, 0.4, 0.4, 1.0);
     let label_cancel_text = ui::Label::new(text_cancel, h)?.with_color(color_cancel);
     layout.add(Box::new(label_cancel_text));
@@ -323,15 +531,239 @@ fn build_panel_ability_description(
     Ok(layout)
 }
 
-fn make_gui() -> ZResult<ui::Gui<Message>> {
-    let mut gui = ui::Gui::new();
+fn build_panel_menu(
+    gui: &mut Gui<Message>,
+    is_cinematic_camera: bool,
+    is_threat_shown: bool,
+) -> ZResult<ui::RcWidget> {
+    let font = assets::get().font;
     let h = line_heights().large;
     let icon = textures().icons.main_menu;
     let button = ui::Button::new(ui::Drawable::Texture(icon), h, gui.sender(), Message::Exit)?;
-    let layout = ui::VLayout::from_widget(Box::new(button));
+    let mut layout = ui::VLayout::from_widget(Box::new(button));
+    layout.add(Box::new(ui::Spacer::new_vertical(h / 8.0)));
+    let camera_text = if is_cinematic_camera {
+        tr!("camera: cinematic")
+    } else {
+        tr!("camera: fixed")
+    };
+    let text = ui::Drawable::text(&camera_text, font);
+    let h = line_heights().normal;
+    let button = ui::Button::new(text, h, gui.sender(), Message::ToggleCamera)?;
+    layout.add(Box::new(button));
+    let speed = tr!(settings::get().animation_speed.title());
+    let speed_text = tr!("speed: {speed}", speed = speed);
+    let text = ui::Drawable::text(speed_text, font);
+    let message = Message::ToggleAnimationSpeed;
+    let button = ui::Button::new(text, h, gui.sender(), message)?;
+    layout.add(Box::new(button));
+    let threat_text = if is_threat_shown {
+        tr!("danger zone: on")
+    } else {
+        tr!("danger zone: off")
+    };
+    let text = ui::Drawable::text(threat_text, font);
+    let button = ui::Button::new(text, h, gui.sender(), Message::ToggleThreat)?;
+    layout.add(Box::new(button));
+    let text = ui::Drawable::text(tr!("log"), font);
+    let button = ui::Button::new(text, h, gui.sender(), Message::ToggleHistory)?;
+    layout.add(Box::new(button));
     let anchor = ui::Anchor(ui::HAnchor::Left, ui::VAnchor::Top);
-    gui.add(&ui::pack(layout), anchor);
-    Ok(gui)
+    let packed_layout = ui::pack(layout);
+    gui.add(&packed_layout, anchor);
+    Ok(packed_layout)
+}
+
+/// The battle log is saved next to the game, every save replaces the previous one.
+#[cfg(not(target_arch = "wasm32"))]
+const HISTORY_PATH: &str = "battle_log.txt";
+
+/// How many lines of the battle log are visible at once.
+const HISTORY_LINES_COUNT: usize = 12;
+
+/// The lines are scrolled up by `scroll` lines from the newest ones.
+fn build_panel_history(
+    gui: &mut Gui<Message>,
+    log: &Log,
+    scroll: usize,
+    status: &str,
+) -> ZResult<ui::RcWidget> {
+    let font = assets::get().font;
+    let h = line_heights().small;
+    let mut layout = Box::new(ui::VLayout::new());
+    let lines = log.lines();
+    if lines.is_empty() {
+        let text = ui::Drawable::text(tr!("Nothing has happened yet."), font);
+        layout.add(Box::new(ui::Label::new(text, h)?));
+    }
+    let end = lines.len().saturating_sub(scroll);
+    let begin = end.saturating_sub(HISTORY_LINES_COUNT);
+    for line in &lines[begin..end] {
+        let text = ui::Drawable::text(history::line_text(line), font);
+        layout.add(Box::new(ui::Label::new(text, h)?));
+    }
+    layout.add(Box::new(ui::Spacer::new_vertical(h / 2.0)));
+    let mut buttons = ui::HLayout::new();
+    let text = ui::Drawable::text(tr!("older"), font);
+    let message = Message::ScrollHistory(Scroll::Older);
+    let mut button = ui::Button::new(text, h, gui.sender(), message)?;
+    button.set_active(begin > 0);
+    buttons.add(Box::new(button));
+    buttons.add(Box::new(ui::Spacer::new_horizontal(h / 2.0)));
+    let text = ui::Drawable::text(tr!("newer"), font);
+    let message = Message::ScrollHistory(Scroll::Newer);
+    let mut button = ui::Button::new(text, h, gui.sender(), message)?;
+    button.set_active(scroll > 0);
+    buttons.add(Box::new(button));
+    buttons.add(Box::new(ui::Spacer::new_horizontal(h / 2.0)));
+    let text = ui::Drawable::text(tr!("what happened?"), font);
+    let button = ui::Button::new(text, h, gui.sender(), Message::Rewind)?;
+    buttons.add(Box::new(button));
+    #[cfg(not(target_arch = "wasm32"))] // can't save files in browsers
+    {
+        buttons.add(Box::new(ui::Spacer::new_horizontal(h / 2.0)));
+        let text = ui::Drawable::text(tr!("save"), font);
+        let button = ui::Button::new(text, h, gui.sender(), Message::SaveHistory)?;
+        buttons.add(Box::new(button));
+    }
+    layout.add(Box::new(buttons));
+    if !status.is_empty() {
+        let text = ui::Drawable::text(status, font);
+        layout.add(Box::new(ui::Label::new(text, h)?));
+    }
+    let layout = utils::add_offsets_and_bg(layout, h / 2.0)?;
+    let anchor = ui::Anchor(ui::HAnchor::Left, ui::VAnchor::Middle);
+    let packed_layout = ui::pack(layout);
+    gui.add(&packed_layout, anchor);
+    Ok(packed_layout)
+}
+
+/// Skipped animations are ticked with this step till they're all finished.
+const SKIP_STEP: Duration = Duration::from_secs(1);
+
+/// The map scrolls when the cursor is this close to the edge of the screen.
+const EDGE_SCROLL_MARGIN: f32 = 0.03;
+
+/// In the default camera's units per second.
+const EDGE_SCROLL_SPEED: f32 = 1.0;
+
+/// How long the player can do nothing before getting a hint.
+const STUCK_IDLE_TIME: Duration = Duration::from_secs(30);
+
+/// How many times in a row the same click can be rejected before getting a hint.
+const STUCK_REJECTIONS_COUNT: i32 = 3;
+
+/// Tracks if the player looks stuck.
+#[derive(Debug, Default)]
+struct StuckDetector {
+    idle_time: Duration,
+    last_rejected_pos: Option<PosHex>,
+    rejections_count: i32,
+}
+
+impl StuckDetector {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn update(&mut self, dtime: Duration) {
+        self.idle_time += dtime;
+    }
+
+    fn reject(&mut self, pos: PosHex) {
+        if self.last_rejected_pos == Some(pos) {
+            self.rejections_count += 1;
+        } else {
+            self.last_rejected_pos = Some(pos);
+            self.rejections_count = 1;
+        }
+    }
+
+    fn is_stuck(&self) -> bool {
+        self.idle_time >= STUCK_IDLE_TIME || self.rejections_count >= STUCK_REJECTIONS_COUNT
+    }
+}
+
+/// The player can only click what they see, the hidden enemies' tiles look empty.
+fn visible_agent_id_at(state: &State, pos: PosHex) -> Option<Id> {
+    state::agent_id_at_opt(state, pos).filter(|&id| !state::is_hidden_from(state, id, PlayerId(0)))
+}
+
+fn visible_blocker_id_at(state: &State, pos: PosHex) -> Option<Id> {
+    state::blocker_id_at_opt(state, pos)
+        .filter(|&id| !state::is_hidden_from(state, id, PlayerId(0)))
+}
+
+fn agent_title(state: &State, id: Id) -> String {
+    let parts = state.parts();
+    let typename = &parts.meta.get(id).name.0;
+    let name = parts.persona.get_opt(id).and_then(|p| p.name.as_deref());
+    utils::agent_name(name, typename)
+}
+
+/// Why the command can't be executed, with the numbers when they help.
+fn check_error_text(err: check::Error) -> String {
+    match err {
+        check::Error::NotEnoughMovePoints {
+            required,
+            available,
+        } => tr!(
+            "not enough move points ({required} of {available})",
+            required = required.0,
+            available = available.0
+        ),
+        check::Error::NotEnoughStamina {
+            required,
+            available,
+        } => tr!(
+            "not enough stamina ({required} of {available})",
+            required = required,
+            available = available
+        ),
+        check::Error::DistanceIsTooBig { max, .. } => {
+            tr!("out of range (max {distance})", distance = max.0)
+        }
+        check::Error::DistanceIsTooSmall { min, .. } => {
+            tr!("too close (min {distance})", distance = min.0)
+        }
+        _ => tr!(err.title()),
+    }
+}
+
+fn hint_text(state: &State, command: &command::Command) -> String {
+    match command {
+        command::Command::Attack(command) => {
+            tr!(
+                "attack {target}",
+                target = agent_title(state, command.target_id)
+            )
+        }
+        command::Command::UseAbility(command) => {
+            tr!("use {ability}", ability = tr!(&command.ability.title()))
+        }
+        command::Command::MoveTo(command) => {
+            let distance = command.path.tiles().len() - 1;
+            if distance == 1 {
+                tr!("move 1 hex")
+            } else {
+                tr!("move {count} hexes", count = distance)
+            }
+        }
+        command::Command::EndTurn(_) => tr!("end turn"),
+        command::Command::ConvertJoker(command) => match command.into {
+            JokerUse::Move => tr!("spend a joker on a move"),
+            JokerUse::Attack => tr!("spend a joker on an attack"),
+        },
+        command::Command::Mount(command) => {
+            tr!(
+                "mount {mount}",
+                mount = agent_title(state, command.mount_id)
+            )
+        }
+        command::Command::Dismount(_) => tr!("dismount"),
+        command::Command::Retreat(_) => tr!("retreat"),
+        command::Command::Create(_) => unreachable!(),
+    }
 }
 
 #[derive(PartialEq, Copy, Clone)]
@@ -350,13 +782,63 @@ pub struct Battle {
     selected_agent_id: Option<Id>,
     pathfinder: Pathfinder,
     block_timer: Option<Duration>,
-    ai: Ai,
+    stuck_detector: StuckDetector,
+
+    ai_worker: AiWorker,
     panel_info: Option<ui::RcWidget>,
     panel_abilities: Option<ui::RcWidget>,
     panel_ability_description: Option<ui::RcWidget>,
     panel_end_turn: Option<ui::RcWidget>,
+    panel_objectives: Option<ui::RcWidget>,
+    panel_menu: Option<ui::RcWidget>,
+    panel_forecast: Option<ui::RcWidget>,
+    panel_deployment: Option<ui::RcWidget>,
+    panel_history: Option<ui::RcWidget>,
+
+    /// Everything that has happened in the battle.
+    log: Log,
+
+    /// The numbers for the summary that follows the battle.
+    stats: Stats,
+
+    /// The summary popup is shown once, the battle is over after it's closed.
+    is_summary_shown: bool,
+
+    /// How many of the newest lines of the log are scrolled out of the panel.
+    history_scroll: usize,
+
+    /// The result of the last save of the log.
+    history_status: String,
+
+    /// The tiles that the enemies can attack next turn are highlighted.
+    is_threat_shown: bool,
+
+    /// The tile that was tapped once in the confirm taps mode, the next tap there acts.
+    armed_pos: Option<PosHex>,
+
+    /// For the info popups of the agents.
+    prototypes: Prototypes,
+
+    /// The steps of the tutorial that are left, empty in the usual battles.
+    tutorial: tutorial::Progress,
+
+    /// The day of the daily challenge that is played, its score is recorded.
+    daily_challenge: Option<u64>,
+
+    /// The tile the forecast panel or the path preview was built for.
+    forecast_pos: Option<PosHex>,
+
+    /// The last position of the cursor in the default camera's coordinates.
+    mouse_point: Option<Vec2>,
+
+    /// The tile that the keys have moved the tile marker to, until the mouse moves.
+    key_cursor_pos: Option<PosHex>,
+    dialogue: Dialogue,
     sender: Sender<Option<BattleResult>>,
     confirmation_receiver_exit: Option<Receiver<screen::confirm::Message>>,
+    confirmation_receiver_joker: Option<Receiver<screen::confirm::Message>>,
+    confirmation_receiver_retreat: Option<Receiver<screen::confirm::Message>>,
+    pending_joker_use: Option<JokerUse>,
 }
 
 impl Battle {
@@ -367,17 +849,45 @@ impl Battle {
         sender: Sender<Option<BattleResult>>,
     ) -> ZResult<Self> {
         let radius = scenario.map_radius;
+        let ais = (1..scenario.players_count)
+            .map(|i| Ai::new(PlayerId(i), radius))
+            .collect();
         let mut view = BattleView::new(radius)?;
-        let mut gui = make_gui()?;
+        let mut gui = ui::Gui::new();
+        let panel_menu = Some(build_panel_menu(&mut gui, false, false)?);
         let mut actions = Vec::new();
-        let state = State::new(prototypes, scenario, &mut |state, event, phase| {
-            let action =
-                visualize(state, &mut view, event, phase).expect("Can't visualize the event");
-            actions.push(fork(action));
-        });
+        let mut log = Log::default();
+        let mut stats = Stats::default();
+        let stack_rules = assets::get().stack_rules.clone();
+        let ability_definitions = assets::get().ability_definitions.clone();
+        let state = State::new(
+            prototypes.clone(),
+            stack_rules,
+            ability_definitions,
+            scenario,
+            &mut |state, event, phase| {
+                if phase == ApplyPhase::Pre {
+                    log.record(state, event);
+                    stats.record(state, event);
+                }
+                let action =
+                    visualize(state, &mut view, event, phase).expect("Can't visualize the event");
+                actions.push(fork(action));
+            },
+        );
+        info!("Battle: seed={}", state.seed());
         actions.push(make_action_create_map(&state, &view)?);
         view.add_action(action::Sequence::new(actions).boxed());
-        let panel_end_turn = Some(build_panel_end_turn(&mut gui)?);
+        let mut panel_end_turn = None;
+        let mut panel_deployment = None;
+        if state::is_deploying(&state) {
+            panel_deployment = Some(build_panel_deployment(&mut gui, &state)?);
+            view.show_deployment_zone(&state::free_deployment_tiles(&state))?;
+        } else {
+            panel_end_turn = Some(build_panel_end_turn(&mut gui, &state)?);
+        }
+        view.show_control_points(&state::control_points(&state))?;
+        let panel_objectives = Some(build_panel_objectives(&mut gui, &state)?);
         Ok(Self {
             gui,
             view,
@@ -387,41 +897,179 @@ impl Battle {
             selected_agent_id: None,
             pathfinder: Pathfinder::new(radius),
             block_timer: None,
-            ai: Ai::new(PlayerId(1), radius),
+            stuck_detector: StuckDetector::default(),
+            ai_worker: AiWorker::new(ais),
             panel_info: None,
             panel_abilities: None,
             panel_end_turn,
+            panel_objectives,
+            panel_menu,
             panel_ability_description: None,
+            panel_forecast: None,
+            panel_deployment,
+            panel_history: None,
+            log,
+            stats,
+            is_summary_shown: false,
+            history_scroll: 0,
+            history_status: String::new(),
+            is_threat_shown: false,
+            armed_pos: None,
+            prototypes,
+            tutorial: tutorial::Progress::default(),
+            daily_challenge: None,
+            forecast_pos: None,
+            mouse_point: None,
+            key_cursor_pos: None,
+            dialogue: Dialogue::default(),
             sender,
             confirmation_receiver_exit: None,
+            confirmation_receiver_joker: None,
+            confirmation_receiver_retreat: None,
+            pending_joker_use: None,
         })
     }
 
+    /// A battle that lets the player make only the tutorial's steps, one by one.
+    pub fn new_tutorial(
+        tutorial: tutorial::Tutorial,
+        prototypes: Prototypes,
+        sender: Sender<Option<BattleResult>>,
+    ) -> ZResult<Self> {
+        let battle_type = scenario::BattleType::Skirmish;
+        let mut battle = Self::new(tutorial.scenario, battle_type, prototypes, sender)?;
+        battle.tutorial = tutorial::Progress::new(tutorial.steps);
+        battle.begin_tutorial_step()?;
+        Ok(battle)
+    }
+
+    /// A skirmish with a score that is compared to the earlier tries of the same day.
+    pub fn new_daily_challenge(
+        day: u64,
+        scenario: scenario::Scenario,
+        prototypes: Prototypes,
+        sender: Sender<Option<BattleResult>>,
+    ) -> ZResult<Self> {
+        let battle_type = scenario::BattleType::Skirmish;
+        let mut battle = Self::new(scenario, battle_type, prototypes, sender)?;
+        battle.daily_challenge = Some(day);
+        Ok(battle)
+    }
+
+    /// Saves the score and describes it for the summary.
+    fn record_daily_score(&self, day: u64, result: &BattleResult) -> Vec<String> {
+        let score = skirmish::daily_score(&self.stats, result);
+        let (year, month, day_of_month) = core::utils::date_from_days(day);
+        let date = format!("{}-{:02}-{:02}", year, month, day_of_month);
+        let mut notes = vec![tr!(
+            "Daily challenge of {date}: {score} points",
+            date = date,
+            score = score,
+        )];
+        if daily_records::add(day, score) {
+            notes.push(tr!("That's the best score of the day!"));
+        } else if let Some(best) = daily_records::best(day) {
+            notes.push(tr!("The best score of the day: {best}", best = best));
+        }
+        notes
+    }
+
+    /// Explains the current step after the current animations and marks its tile.
+    fn begin_tutorial_step(&mut self) -> ZResult {
+        self.view.hide_tutorial_tile();
+        if let Some(step) = self.tutorial.step() {
+            self.view.queue_remarks(&step.remarks);
+            if let Some(pos) = step.action.pos() {
+                self.view.show_tutorial_tile(pos)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows the current step's explanation again.
+    fn remind_tutorial_step(&mut self) -> ZResult {
+        if let Some(step) = self.tutorial.step() {
+            let remarks = step.remarks.clone();
+            utils::remove_widget(&mut self.gui, &mut self.panel_end_turn)?;
+            self.dialogue.start(&mut self.gui, remarks)?;
+        }
+        Ok(())
+    }
+
+    /// Lets through only the command that the current tutorial step waits for.
+    fn check_tutorial(&mut self, command: &command::Command) -> ZResult<bool> {
+        if self.tutorial.step().is_none() {
+            return Ok(true);
+        }
+        if self.tutorial.try_command(&self.state, command) {
+            self.begin_tutorial_step()?;
+            Ok(true)
+        } else {
+            self.remind_tutorial_step()?;
+            Ok(false)
+        }
+    }
+
     fn end_turn(&mut self) -> ZResult {
+        if !self.check_tutorial(&command::EndTurn.into())? {
+            return Ok(());
+        }
+        self.stuck_detector.reset();
         utils::remove_widget(&mut self.gui, &mut self.panel_end_turn)?;
         self.deselect()?;
         let command = command::EndTurn.into();
-        let actions = vec![
-            self.do_command_inner(&command, CommandOrigin::Internal),
-            self.do_ai(),
-        ];
-        self.add_actions(actions);
+        let action = self.do_command_inner(&command, CommandOrigin::Internal);
+        self.add_action(action);
+        self.view.messages_map_mut().clear();
         Ok(())
     }
 
-    fn do_ai(&mut self) -> Box<dyn Action> {
-        trace!("AI: <");
-        let mut actions = Vec::new();
-        while let Some(command) = self.ai.command(&self.state) {
-            trace!("AI: command = {:?}", command);
-            actions.push(self.do_command_inner(&command, CommandOrigin::Internal));
-            actions.push(action::Sleep::new(time_s(0.2)).boxed());
-            if let command::Command::EndTurn(_) = command {
-                break;
+    fn is_ai_turn(&self) -> bool {
+        self.state.player_id() != PlayerId(0) && self.state.battle_result().is_none()
+    }
+
+    /// Plays the next command of the AI, if it has already chosen one.
+    ///
+    /// The AI starts thinking about its next move right away,
+    /// while the previous one is still being shown.
+    fn update_ai(&mut self) {
+        if !self.is_ai_turn() {
+            return;
+        }
+        if !self.ai_worker.is_thinking() {
+            self.ai_worker.start(&self.state);
+        }
+        if self.block_timer.is_some() {
+            return;
+        }
+        let batch = match self.ai_worker.try_receive() {
+            Some(Some(batch)) => batch,
+            Some(None) | None => return,
+        };
+        trace!("AI: batch = {:?}", batch);
+        let action = match batch.commands.as_slice() {
+            [command] => self.do_command_inner(command, CommandOrigin::Internal),
+            // A rejected plan must not be asked for again and again,
+            // so its first step is made alone or the turn is over.
+            [first, ..] if check::check_batch(&self.state, &batch).is_err() => {
+                info!("AI: the batch is rejected, falling back to its first command");
+                let command = if check::check(&self.state, first).is_ok() {
+                    first.clone()
+                } else {
+                    command::EndTurn.into()
+                };
+                self.do_command_inner(&command, CommandOrigin::Internal)
             }
+            _ => self
+                .do_batch_inner(&batch, CommandOrigin::Internal)
+                .expect("Can't visualize the AI's batch"),
+        };
+        let actions = vec![action, action::Sleep::new(time_s(0.2)).boxed()];
+        self.add_actions(actions);
+        self.view.messages_map_mut().clear();
+        if self.is_ai_turn() {
+            self.ai_worker.start(&self.state);
         }
-        trace!("AI: >");
-        action::Sequence::new(actions).boxed()
     }
 
     fn use_ability(&mut self, ability: Ability) -> ZResult {
@@ -435,17 +1083,227 @@ impl Battle {
         self.set_mode(id, SelectionMode::Ability(ability))
     }
 
+    /// The hotkeys of the abilities follow their order in the panel.
+    fn use_ability_by_index(&mut self, index: usize) -> ZResult {
+        let id = match self.selected_agent_id {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let ability = match self.state.parts().abilities.get_opt(id) {
+            Some(abilities) => match abilities.0.get(index) {
+                Some(ability) => ability.ability,
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        if state::can_agent_use_ability(&self.state, id, &ability) {
+            self.use_ability(ability)?;
+        }
+        Ok(())
+    }
+
+    /// Selects the player's agent that goes after the selected one.
+    fn select_next_agent(&mut self) -> ZResult {
+        let mut ids = state::players_agent_ids(&self.state, PlayerId(0));
+        ids.sort();
+        let index = match self.selected_agent_id {
+            Some(selected_id) => ids.iter().position(|&id| id > selected_id).unwrap_or(0),
+            None => 0,
+        };
+        match ids.get(index) {
+            Some(&id) => self.set_mode(id, SelectionMode::Normal),
+            None => Ok(()),
+        }
+    }
+
+    fn toggle_camera(&mut self) -> ZResult {
+        let is_cinematic_camera = !self.view.is_cinematic_camera();
+        self.view.set_cinematic_camera(is_cinematic_camera);
+        self.rebuild_panel_menu()
+    }
+
+    /// Also has a hotkey, so it can be changed in the middle of a long turn.
+    fn toggle_animation_speed(&mut self) -> ZResult {
+        let mut settings = settings::get();
+        settings.animation_speed = settings.animation_speed.next();
+        settings::set(settings);
+        self.rebuild_panel_menu()
+    }
+
+    fn toggle_threat(&mut self) -> ZResult {
+        self.is_threat_shown = !self.is_threat_shown;
+        if self.is_threat_shown {
+            self.show_threat_zone()?;
+        } else {
+            self.view.hide_threat_zone();
+        }
+        self.rebuild_panel_menu()
+    }
+
+    fn show_threat_zone(&mut self) -> ZResult {
+        let tiles = threat::threatened_tiles(&self.state, PlayerId(0));
+        self.view.show_threat_zone(&tiles)
+    }
+
+    fn toggle_history(&mut self) -> ZResult {
+        if self.panel_history.is_some() {
+            utils::remove_widget(&mut self.gui, &mut self.panel_history)
+        } else {
+            self.history_scroll = 0;
+            self.rebuild_panel_history()
+        }
+    }
+
+    fn scroll_history(&mut self, scroll: Scroll) -> ZResult {
+        let max_scroll = self.log.lines().len().saturating_sub(HISTORY_LINES_COUNT);
+        self.history_scroll = match scroll {
+            Scroll::Older => (self.history_scroll + HISTORY_LINES_COUNT / 2).min(max_scroll),
+            Scroll::Newer => self.history_scroll.saturating_sub(HISTORY_LINES_COUNT / 2),
+        };
+        self.rebuild_panel_history()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_history(&mut self) -> ZResult {
+        self.history_status = match std::fs::write(HISTORY_PATH, self.log.to_string()) {
+            Ok(()) => tr!("Saved to '{path}'", path = HISTORY_PATH),
+            Err(err) => tr!("Can't save: {error}", error = err),
+        };
+        self.rebuild_panel_history()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save_history(&mut self) -> ZResult {
+        Ok(())
+    }
+
+    fn rebuild_panel_history(&mut self) -> ZResult {
+        utils::remove_widget(&mut self.gui, &mut self.panel_history)?;
+        let panel = build_panel_history(
+            &mut self.gui,
+            &self.log,
+            self.history_scroll,
+            &self.history_status,
+        )?;
+        self.panel_history = Some(panel);
+        Ok(())
+    }
+
+    fn rebuild_panel_menu(&mut self) -> ZResult {
+        utils::remove_widget(&mut self.gui, &mut self.panel_menu)?;
+        let is_cinematic_camera = self.view.is_cinematic_camera();
+        let panel = build_panel_menu(&mut self.gui, is_cinematic_camera, self.is_threat_shown)?;
+        self.panel_menu = Some(panel);
+        Ok(())
+    }
+
+    /// Plays all the started animations to the end right away.
+    fn skip_animations(&mut self) -> ZResult {
+        while self.view.is_busy() {
+            self.view.tick(SKIP_STEP);
+        }
+        if let Some(time) = self.block_timer {
+            self.update_block_timer(time + SKIP_STEP)?;
+        }
+        Ok(())
+    }
+
     fn popup_confirm_exit(&mut self) -> ZResult<Box<dyn Screen>> {
         let (sender, receiver) = channel();
         self.confirmation_receiver_exit = Some(receiver);
         let message = match self.battle_type {
-            scenario::BattleType::Skirmish => "Abandon this battle?",
-            scenario::BattleType::CampaignNode => "Abandon the whole campaign?",
+            scenario::BattleType::Skirmish => tr!("Abandon this battle?"),
+            scenario::BattleType::CampaignNode => tr!("Abandon the whole campaign?"),
+        };
+        let popup = screen::Confirm::from_line(&message, sender)?;
+        Ok(Box::new(popup))
+    }
+
+    fn popup_confirm_retreat(&mut self) -> ZResult<Box<dyn Screen>> {
+        let (sender, receiver) = channel();
+        self.confirmation_receiver_retreat = Some(receiver);
+        let lines = match self.battle_type {
+            scenario::BattleType::Skirmish => vec![tr!("Retreat from this battle?")],
+            scenario::BattleType::CampaignNode => vec![
+                tr!("Retreat from this battle?"),
+                tr!("The survivors will be saved, but you'll get no award."),
+            ],
+        };
+        let popup = screen::Confirm::from_lines(&lines, sender)?;
+        Ok(Box::new(popup))
+    }
+
+    fn retreat(&mut self) -> ZResult {
+        let command = command::Retreat.into();
+        if check(&self.state, &command).is_ok() {
+            utils::remove_widget(&mut self.gui, &mut self.panel_end_turn)?;
+            self.deselect()?;
+            self.do_command(&command)?;
+        }
+        Ok(())
+    }
+
+    fn popup_confirm_joker(&mut self, into: JokerUse) -> ZResult<Box<dyn Screen>> {
+        let (sender, receiver) = channel();
+        self.confirmation_receiver_joker = Some(receiver);
+        self.pending_joker_use = Some(into);
+        let message = match into {
+            JokerUse::Move => tr!("Spend a joker on an extra move?"),
+            JokerUse::Attack => tr!("Spend a joker on an extra attack?"),
         };
-        let popup = screen::Confirm::from_line(message, sender)?;
+        let popup = screen::Confirm::from_line(&message, sender)?;
         Ok(Box::new(popup))
     }
 
+    fn convert_joker(&mut self) -> ZResult {
+        let (id, into) = match (self.selected_agent_id, self.pending_joker_use.take()) {
+            (Some(id), Some(into)) => (id, into),
+            _ => return Ok(()),
+        };
+        let command = command::ConvertJoker { id, into }.into();
+        if check(&self.state, &command).is_ok() {
+            self.do_command(&command)?;
+        }
+        Ok(())
+    }
+
+    fn mount(&mut self, mount_id: Id) -> ZResult {
+        let id = match self.selected_agent_id {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let command = command::Mount { id, mount_id }.into();
+        match check(&self.state, &command) {
+            Ok(()) => {
+                self.do_command(&command)?;
+                // The rider is gone from the map, the mount carries it now.
+                self.selected_agent_id = Some(mount_id);
+            }
+            Err(err) => self.show_check_error(&command, err)?,
+        }
+        Ok(())
+    }
+
+    fn dismount(&mut self) -> ZResult {
+        let mount_id = match self.selected_agent_id {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let pos = match state::dismount_pos(&self.state, mount_id) {
+            Some(pos) => pos,
+            None => {
+                let pos = self.state.parts().pos.get(mount_id).0;
+                return self.view.message(pos, &tr!("no room to dismount"));
+            }
+        };
+        let command = command::Dismount { mount_id, pos }.into();
+        match check(&self.state, &command) {
+            Ok(()) => self.do_command(&command)?,
+            Err(err) => self.show_check_error(&command, err)?,
+        }
+        Ok(())
+    }
+
     fn do_command_inner(
         &mut self,
         command: &command::Command,
@@ -456,7 +1314,13 @@ impl Battle {
         let mut actions = Vec::new();
         let state = &mut self.state;
         let view = &mut self.view;
+        let log = &mut self.log;
+        let stats = &mut self.stats;
         battle::execute(state, command, &mut |state, event, phase| {
+            if phase == ApplyPhase::Pre {
+                log.record(state, event);
+                stats.record(state, event);
+            }
             let action =
                 visualize::visualize(state, view, event, phase).expect("Can't visualize the event");
             view.messages_map_mut().update(action.duration());
@@ -468,13 +1332,79 @@ impl Battle {
             }
         })
         .expect("Can't execute command");
+        if origin == CommandOrigin::Player {
+            actions.push(self.view.reset_camera());
+        }
         action::Sequence::new(actions).boxed()
     }
 
-    fn do_command(&mut self, command: &command::Command) {
+    fn do_batch(&mut self, batch: &command::CommandBatch) -> ZResult {
+        // The steps are made one command at a time.
+        if self.tutorial.step().is_some() {
+            return self.remind_tutorial_step();
+        }
+        self.stuck_detector.reset();
+        let action = self.do_batch_inner(batch, CommandOrigin::Player)?;
+        self.add_action(action);
+        self.view.messages_map_mut().clear();
+        Ok(())
+    }
+
+    fn do_batch_inner(
+        &mut self,
+        batch: &command::CommandBatch,
+        origin: CommandOrigin,
+    ) -> ZResult<Box<dyn Action>> {
+        self.view.messages_map_mut().clear();
+        let mut actions = Vec::new();
+        let view = &mut self.view;
+        let log = &mut self.log;
+        let stats = &mut self.stats;
+        let result = execute_batch(&mut self.state, batch, &mut |state, event, phase| {
+            if phase == ApplyPhase::Pre {
+                log.record(state, event);
+                stats.record(state, event);
+            }
+            let action =
+                visualize::visualize(state, view, event, phase).expect("Can't visualize the event");
+            view.messages_map_mut().update(action.duration());
+            actions.push(action);
+            if origin != CommandOrigin::Player {
+                let actual_sleep_duration = view.messages_map().total_duration().mul_f32(0.3);
+                actions.push(action::Sleep::new(actual_sleep_duration).boxed());
+                view.messages_map_mut().update(actual_sleep_duration);
+            }
+        });
+        match result {
+            Ok(executed_count) if executed_count < batch.commands.len() => {
+                let id = batch.actor_id().expect("The batch has been checked");
+                if let Some(pos) = self.state.parts().pos.get_opt(id) {
+                    actions.push(visualize::message(
+                        &mut self.view,
+                        pos.0,
+                        &tr!("cancelled"),
+                    )?);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => info!("Can't execute the batch: {:?}", err),
+        }
+        if origin == CommandOrigin::Player {
+            actions.push(self.view.reset_camera());
+        }
+        Ok(action::Sequence::new(actions).boxed())
+    }
+
+    fn do_command(&mut self, command: &command::Command) -> ZResult {
+        if !self.check_tutorial(command)? {
+            return Ok(());
+        }
+        self.stuck_detector.reset();
+        self.view.hide_path();
         let action = self.do_command_inner(command, CommandOrigin::Player);
         self.add_action(action);
         self.view.messages_map_mut().clear();
+        Ok(())
     }
 
     fn add_actions(&mut self, actions: Vec<Box<dyn Action>>) {
@@ -487,6 +1417,7 @@ impl Battle {
     }
 
     fn deselect(&mut self) -> ZResult {
+        self.armed_pos = None;
         self.remove_selected_highlighted_tiles_and_widgets()?;
         if self.selected_agent_id.is_some() {
             self.view.deselect();
@@ -500,12 +1431,65 @@ impl Battle {
         utils::remove_widget(&mut self.gui, &mut self.panel_info)?;
         utils::remove_widget(&mut self.gui, &mut self.panel_abilities)?;
         utils::remove_widget(&mut self.gui, &mut self.panel_ability_description)?;
+        self.remove_forecast()?;
         if self.selected_agent_id.is_some() {
             self.view.remove_highlights();
         }
         Ok(())
     }
 
+    fn remove_forecast(&mut self) -> ZResult {
+        utils::remove_widget(&mut self.gui, &mut self.panel_forecast)?;
+        self.view.hide_path();
+        self.forecast_pos = None;
+        Ok(())
+    }
+
+    /// The command that a click at the tile would execute, if it has something to predict.
+    fn hovered_command(&self, pos: PosHex) -> Option<command::Command> {
+        let id = self.selected_agent_id?;
+        let target_id = visible_blocker_id_at(&self.state, pos)?;
+        let command = match self.mode {
+            SelectionMode::Normal => command::Attack {
+                attacker_id: id,
+                target_id,
+            }
+            .into(),
+            SelectionMode::Ability(ability) => command::UseAbility { id, pos, ability }.into(),
+        };
+        Some(command)
+    }
+
+    fn update_forecast(&mut self, pos: PosHex) -> ZResult {
+        if self.forecast_pos == Some(pos) {
+            return Ok(());
+        }
+        self.remove_forecast()?;
+        let command = match self.hovered_command(pos) {
+            Some(command) => command,
+            None => return self.update_path_preview(pos),
+        };
+        if let Some(forecast) = forecast::forecast(&self.state, &command) {
+            let panel = build_panel_forecast(&mut self.gui, &self.state, &forecast)?;
+            self.panel_forecast = Some(panel);
+            self.forecast_pos = Some(pos);
+        }
+        Ok(())
+    }
+
+    /// Shows the path that a click at the tile would move the selected agent along.
+    fn update_path_preview(&mut self, pos: PosHex) -> ZResult {
+        if self.block_timer.is_some() || self.mode != SelectionMode::Normal {
+            return Ok(());
+        }
+        if let Some(command) = self.move_command(pos) {
+            let cost = command.path.cost_for(&self.state, command.id);
+            self.view.show_path(&command.path, cost)?;
+            self.forecast_pos = Some(pos);
+        }
+        Ok(())
+    }
+
     fn set_mode(&mut self, id: Id, mode: SelectionMode) -> ZResult {
         match mode {
             SelectionMode::Normal => self.deselect()?,
@@ -526,9 +1510,9 @@ impl Battle {
             }
             SelectionMode::Normal => {
                 self.pathfinder.fill_map(state, id);
-                if self.panel_end_turn.is_none() {
-                    self.panel_end_turn = Some(build_panel_end_turn(gui)?);
-                }
+                // The agent may have just reached the edge of the map, update the retreat button.
+                utils::remove_widget(gui, &mut self.panel_end_turn)?;
+                self.panel_end_turn = Some(build_panel_end_turn(gui, state)?);
             }
         }
         self.panel_abilities = build_panel_agent_abilities(gui, state, id, &mode)?;
@@ -539,6 +1523,30 @@ impl Battle {
         Ok(())
     }
 
+    /// Attacks a destructible object, other blockers are ignored.
+    fn handle_blocker_click(&mut self, id: Id) -> ZResult {
+        let attacker_id = match self.selected_agent_id {
+            Some(selected_agent_id) => selected_agent_id,
+            None => return Ok(()),
+        };
+        if !state::is_destructible(&self.state, id) {
+            return Ok(());
+        }
+        let command_attack = command::Attack {
+            attacker_id,
+            target_id: id,
+        }
+        .into();
+        match check(&self.state, &command_attack) {
+            Ok(()) => {
+                self.do_command(&command_attack)?;
+                self.fill_map();
+            }
+            Err(err) => self.show_check_error(&command_attack, err)?,
+        }
+        Ok(())
+    }
+
     fn handle_agent_click(&mut self, id: Id) -> ZResult {
         if self.state.parts().agent.get_opt(id).is_none() {
             // only agents can be selected
@@ -551,7 +1559,7 @@ impl Battle {
                 self.deselect()?;
                 return Ok(());
             }
-            if other_agent_player_id == selected_agent_player_id
+            if state::are_allies(&self.state, other_agent_player_id, selected_agent_player_id)
                 || other_agent_player_id == self.state.player_id()
             {
                 self.set_mode(id, SelectionMode::Normal)?;
@@ -562,10 +1570,19 @@ impl Battle {
                 target_id: id,
             }
             .into();
-            if check(&self.state, &command_attack).is_err() {
-                return Ok(());
+            let parts = self.state.parts();
+            let distance =
+                map::distance_hex(parts.pos.get(selected_agent_id).0, parts.pos.get(id).0);
+            let is_too_far = distance > state::attack_distance(&self.state, selected_agent_id);
+            match check(&self.state, &command_attack) {
+                Ok(()) => self.do_command(&command_attack)?,
+                Err(err) if is_too_far => {
+                    if !self.try_move_and_attack(selected_agent_id, id)? {
+                        return self.show_check_error(&command_attack, err);
+                    }
+                }
+                Err(err) => return self.show_check_error(&command_attack, err),
             }
-            self.do_command(&command_attack);
             self.fill_map();
         } else {
             self.set_mode(id, SelectionMode::Normal)?;
@@ -573,6 +1590,37 @@ impl Battle {
         Ok(())
     }
 
+    /// Queues a move to the closest tile the target can be attacked from
+    /// and the attack itself, returns `false` if there's no such tile in reach.
+    fn try_move_and_attack(&mut self, attacker_id: Id, target_id: Id) -> ZResult<bool> {
+        let state = &self.state;
+        let target_pos = state.parts().pos.get(target_id).0;
+        let attack_distance = state::attack_distance(state, attacker_id);
+        let map = self.pathfinder.map();
+        let command_move = map
+            .iter()
+            .filter(|&pos| map::distance_hex(pos, target_pos) <= attack_distance)
+            .filter_map(|pos| self.pathfinder.path(pos))
+            .map(|path| command::MoveTo {
+                id: attacker_id,
+                path,
+            })
+            .filter(|command| check(state, &command.clone().into()).is_ok())
+            .min_by_key(|command| command.path.cost_for(state, attacker_id));
+        let command_move = match command_move {
+            Some(command) => command.into(),
+            None => return Ok(false),
+        };
+        let command_attack = command::Attack {
+            attacker_id,
+            target_id,
+        }
+        .into();
+        let batch = command::CommandBatch::new(vec![command_move, command_attack]);
+        self.do_batch(&batch)?;
+        Ok(true)
+    }
+
     fn fill_map(&mut self) {
         let selected_agent_id = self.selected_agent_id.unwrap();
         let parts = self.state.parts();
@@ -581,52 +1629,252 @@ impl Battle {
         }
     }
 
-    fn try_move_selected_agent(&mut self, pos: PosHex) {
-        if let Some(id) = self.selected_agent_id {
-            let path = match self.pathfinder.path(pos) {
-                Some(path) => path,
-                None => return,
-            };
-            assert_eq!(path.from(), self.state.parts().pos.get(id).0);
-            let command_move = command::MoveTo { id, path }.into();
-            if check(&self.state, &command_move).is_err() {
-                return;
-            }
-            self.do_command(&command_move);
+    /// The move that a click at the tile would make, both the preview and the click use it.
+    fn move_command(&self, pos: PosHex) -> Option<command::MoveTo> {
+        let id = self.selected_agent_id?;
+        let path = self.pathfinder.path(pos)?;
+        assert_eq!(path.from(), self.state.parts().pos.get(id).0);
+        let command = command::MoveTo { id, path };
+        check(&self.state, &command.clone().into()).ok()?;
+        Some(command)
+    }
+
+    fn try_move_selected_agent(&mut self, pos: PosHex) -> ZResult {
+        if let Some(command) = self.move_command(pos) {
+            self.do_command(&command.into())?;
             self.fill_map();
+            return Ok(());
+        }
+        let id = match self.selected_agent_id {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        match self.pathfinder.path(pos) {
+            Some(path) => {
+                let command = command::MoveTo { id, path }.into();
+                if let Err(err) = check(&self.state, &command) {
+                    self.show_check_error(&command, err)?;
+                }
+            }
+            None => self.view.message(pos, &tr!("can't get there"))?,
+        }
+        Ok(())
+    }
+
+    /// The reason is shown at the offending tile or at the one that was clicked.
+    fn show_check_error(&mut self, command: &command::Command, err: check::Error) -> ZResult {
+        let pos = match (err, command) {
+            (check::Error::TileIsBlocked { pos }, _) | (check::Error::BadPos { pos }, _)
+                if self.state.map().is_inboard(pos) =>
+            {
+                pos
+            }
+            (_, command::Command::Attack(command)) => {
+                self.state.parts().pos.get(command.target_id).0
+            }
+            (_, command::Command::MoveTo(command)) => command.path.to(),
+            (_, command::Command::UseAbility(command)) => command.pos,
+            (_, command::Command::Create(command)) => command.pos,
+            _ => return Ok(()),
+        };
+        let text = check_error_text(err);
+        self.view.message(pos, &text)
+    }
+
+    /// Places the next waiting agent before the first turn.
+    fn try_deploy(&mut self, pos: PosHex) -> ZResult {
+        let command = match state::deployment_command(&self.state, pos) {
+            Some(command) => command,
+            None => return Ok(()),
+        };
+        if let Err(err) = check(&self.state, &command) {
+            self.stuck_detector.reject(pos);
+            return self.show_check_error(&command, err);
         }
+        self.do_command(&command)?;
+        utils::remove_widget(&mut self.gui, &mut self.panel_deployment)?;
+        self.view.remove_highlights();
+        if state::is_deploying(&self.state) {
+            self.panel_deployment = Some(build_panel_deployment(&mut self.gui, &self.state)?);
+            let tiles = state::free_deployment_tiles(&self.state);
+            self.view.show_deployment_zone(&tiles)?;
+        }
+        Ok(())
     }
 
     fn handle_click(&mut self, point: Vec2) -> ZResult {
-        let pos = geom::point_to_hex(self.view.tile_size(), point);
+        let map_point = self.view.to_camera_point(point);
+        let pos = geom::point_to_hex(self.view.tile_size(), map_point);
         self.gui.click(point);
-        if self.block_timer.is_some() {
+        self.handle_tile_click(pos)
+    }
+
+    /// A click at the tile, by the mouse or by the key cursor.
+    fn handle_tile_click(&mut self, pos: PosHex) -> ZResult {
+        if self.block_timer.is_some() || self.state.player_id() != PlayerId(0) {
             return Ok(());
         }
+        if state::is_deploying(&self.state) {
+            return self.try_deploy(pos);
+        }
+        let selection_before = (self.selected_agent_id, self.mode);
         if let SelectionMode::Ability(ability) = self.mode {
             let id = self.selected_agent_id.unwrap();
             let command = command::UseAbility { id, pos, ability }.into();
-            if check(&self.state, &command).is_ok() {
-                self.do_command(&command);
-            } else {
-                self.view.message(pos, "cancelled")?;
+            match check(&self.state, &command) {
+                Ok(()) => self.do_command(&command)?,
+                Err(err) => self.show_check_error(&command, err)?,
             }
             self.set_mode(id, SelectionMode::Normal)?;
         } else if self.state.map().is_inboard(pos) {
-            if let Some(id) = state::agent_id_at_opt(&self.state, pos) {
+            if self.needs_confirmation(pos) {
+                self.armed_pos = Some(pos);
+                self.view.message(pos, &tr!("tap again"))?;
+                return Ok(());
+            }
+            self.armed_pos = None;
+            if let Some(id) = visible_agent_id_at(&self.state, pos) {
                 self.handle_agent_click(id)?;
+            } else if let Some(id) = visible_blocker_id_at(&self.state, pos) {
+                self.handle_blocker_click(id)?;
             } else {
-                self.try_move_selected_agent(pos);
+                self.try_move_selected_agent(pos)?;
             }
         }
+        let is_command_accepted = self.block_timer.is_some();
+        let is_selection_changed = selection_before != (self.selected_agent_id, self.mode);
+        if selection_before.0.is_some() && !is_command_accepted && !is_selection_changed {
+            self.stuck_detector.reject(pos);
+        }
         self.view.messages_map_mut().clear();
         Ok(())
     }
 
+    /// In the confirm taps mode, the moves and the attacks need a second tap at the same tile,
+    /// selecting a friendly agent doesn't.
+    fn needs_confirmation(&self, pos: PosHex) -> bool {
+        let selected_id = match self.selected_agent_id {
+            Some(id) => id,
+            None => return false,
+        };
+        if !settings::get().confirm_taps || self.armed_pos == Some(pos) {
+            return false;
+        }
+        let state = &self.state;
+        if let Some(id) = visible_agent_id_at(state, pos) {
+            let player_id = state.parts().belongs_to.get(id).0;
+            let selected_player_id = state.parts().belongs_to.get(selected_id).0;
+            let is_friendly = state::are_allies(state, player_id, selected_player_id)
+                || player_id == state.player_id();
+            return !is_friendly;
+        }
+        if state::blocker_id_at_opt(state, pos).is_some() {
+            return self
+                .hovered_command(pos)
+                .is_some_and(|command| check(state, &command).is_ok());
+        }
+        self.move_command(pos).is_some()
+    }
+
+    /// Moves the key cursor to the neighboring tile. The first press only shows it,
+    /// next to the selected agent if there's one.
+    fn move_key_cursor(&mut self, dir: Dir) -> ZResult {
+        let pos = match self.key_cursor_pos {
+            Some(pos) => {
+                let neighbor = Dir::get_neighbor_pos(pos, dir);
+                if self.state.map().is_inboard(neighbor) {
+                    neighbor
+                } else {
+                    pos
+                }
+            }
+            None => {
+                let player_id = self.state.player_id();
+                let id = self.selected_agent_id.or_else(|| {
+                    state::players_agent_ids(&self.state, player_id)
+                        .first()
+                        .copied()
+                });
+                match id {
+                    Some(id) => self.state.parts().pos.get(id).0,
+                    None => PosHex { q: 0, r: 0 },
+                }
+            }
+        };
+        self.key_cursor_pos = Some(pos);
+        self.view.show_current_tile_marker(pos);
+        self.update_forecast(pos)
+    }
+
+    /// Shows a few commands that the player can still do.
+    fn show_hint(&mut self) -> ZResult {
+        self.stuck_detector.reset();
+        let player_id = self.state.player_id();
+        let id = match self.selected_agent_id {
+            Some(id) => id,
+            None => match state::players_agent_ids(&self.state, player_id).first() {
+                Some(&id) => id,
+                None => return Ok(()),
+            },
+        };
+        let commands = hint::legal_commands(&self.state, id);
+        let texts: Vec<String> = commands
+            .iter()
+            .map(|command| hint_text(&self.state, command))
+            .collect();
+        let text = match texts.as_slice() {
+            [] => return Ok(()),
+            [last] => tr!("you can {command}", command = last),
+            [first, .., last] => tr!(
+                "you can still {first} or {last}",
+                first = first,
+                last = last,
+            ),
+        };
+        let pos = self.state.parts().pos.get(id).0;
+        self.view.message(pos, &text)
+    }
+
+    /// Scrolls the map when the cursor is at the edge of the screen.
+    fn scroll_at_edges(&mut self, dtime: Duration) {
+        let point = match self.mouse_point {
+            Some(point) => point,
+            None => return,
+        };
+        let aspect_ratio = utils::aspect_ratio();
+        let edge = |value: f32, max: f32| {
+            if value < -max + EDGE_SCROLL_MARGIN {
+                1.0
+            } else if value > max - EDGE_SCROLL_MARGIN {
+                -1.0
+            } else {
+                0.0
+            }
+        };
+        let dir = Vec2::new(edge(point.x, aspect_ratio), edge(point.y, 1.0));
+        if dir != Vec2::new(0.0, 0.0) {
+            let distance = EDGE_SCROLL_SPEED * dtime.as_secs_f32();
+            self.view.pan_camera(dir * distance);
+        }
+    }
+
     fn update_block_timer(&mut self, dtime: Duration) -> ZResult {
         if let Some(time) = self.block_timer {
             if time < dtime {
                 self.block_timer = None;
+                utils::remove_widget(&mut self.gui, &mut self.panel_objectives)?;
+                let panel = build_panel_objectives(&mut self.gui, &self.state)?;
+                self.panel_objectives = Some(panel);
+                if self.panel_history.is_some() {
+                    self.rebuild_panel_history()?;
+                }
+                if self.is_threat_shown {
+                    self.show_threat_zone()?;
+                }
+                self.view.show_auras(&state::aura_tiles(&self.state))?;
+                let control_points = state::control_points(&self.state);
+                self.view.show_control_points(&control_points)?;
+                self.view.show_fuses(&state::bomb_fuses(&self.state));
                 if let Some(id) = self.selected_agent_id {
                     self.set_mode(id, SelectionMode::Normal)?;
                 }
@@ -651,15 +1899,73 @@ impl Screen for Battle {
             self.send_battle_result(None);
             return Ok(StackCommand::Pop);
         }
-        self.view.tick(dtime);
-        self.update_block_timer(dtime)?;
+        if screen::confirm::try_receive_yes(&self.confirmation_receiver_joker) {
+            self.confirmation_receiver_joker = None;
+            self.convert_joker()?;
+        }
+        if screen::confirm::try_receive_yes(&self.confirmation_receiver_retreat) {
+            self.confirmation_receiver_retreat = None;
+            self.retreat()?;
+        }
+        // The animations and the waiting for them are sped up together.
+        let animation_dtime = dtime.mul_f32(settings::get().animation_speed.factor());
+        self.view.tick(animation_dtime);
+        // There's no cursor on touch screens, the map is scrolled with two fingers there.
+        #[cfg(not(target_os = "android"))]
+        self.scroll_at_edges(dtime);
+        self.update_block_timer(animation_dtime)?;
+        if self.block_timer.is_none() {
+            let remarks = self.view.take_remarks();
+            if !remarks.is_empty() {
+                utils::remove_widget(&mut self.gui, &mut self.panel_end_turn)?;
+                self.dialogue.start(&mut self.gui, remarks)?;
+            }
+        }
+        if self.dialogue.is_active() {
+            return Ok(StackCommand::None);
+        }
+        self.update_ai();
+        if settings::get().instant_ai_turns && self.is_ai_turn() {
+            self.skip_animations()?;
+        }
+        if self.block_timer.is_none() && self.state.player_id() == PlayerId(0) {
+            self.stuck_detector.update(dtime);
+            if self.stuck_detector.is_stuck() {
+                self.show_hint()?;
+            }
+        }
         if self.block_timer.is_none() {
             if let Some(result) = self.state.battle_result().clone() {
-                self.send_battle_result(Some(result));
-                return Ok(StackCommand::Pop);
+                // Back here after the summary is closed.
+                if self.is_summary_shown {
+                    self.send_battle_result(Some(result));
+                    return Ok(StackCommand::Pop);
+                }
+                self.is_summary_shown = true;
+                let trigger = Trigger::BattleEnd {
+                    stats: &self.stats,
+                    result: &result,
+                };
+                let mut notes: Vec<String> = achievements::unlock(trigger)
+                    .iter()
+                    .map(|achievement| {
+                        let title = tr!(&achievement.title);
+                        tr!("Achievement unlocked: {title}", title = title)
+                    })
+                    .collect();
+                if let Some(day) = self.daily_challenge {
+                    notes.extend(self.record_daily_score(day, &result));
+                }
+                let screen = screen::Summary::new(&self.stats, &result, &notes)?;
+                return Ok(StackCommand::PushPopup(Box::new(screen)));
             }
-            if self.panel_end_turn.is_none() && self.mode == SelectionMode::Normal {
-                self.panel_end_turn = Some(build_panel_end_turn(&mut self.gui)?);
+            let is_players_turn = self.state.player_id() == PlayerId(0);
+            if is_players_turn
+                && !state::is_deploying(&self.state)
+                && self.panel_end_turn.is_none()
+                && self.mode == SelectionMode::Normal
+            {
+                self.panel_end_turn = Some(build_panel_end_turn(&mut self.gui, &self.state)?);
             }
         }
         Ok(StackCommand::None)
@@ -672,27 +1978,63 @@ impl Screen for Battle {
     }
 
     fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        if self.dialogue.is_active() {
+            self.dialogue.advance(&mut self.gui)?;
+            return Ok(StackCommand::None);
+        }
         let message = self.gui.click(pos);
         info!("Battle: click: pos={:?}, message={:?}", pos, message);
         match message {
             Some(Message::Exit) => {
                 return Ok(StackCommand::PushPopup(self.popup_confirm_exit()?));
             }
+            Some(Message::ToggleCamera) => self.toggle_camera()?,
+            Some(Message::ToggleAnimationSpeed) => self.toggle_animation_speed()?,
+            Some(Message::ToggleThreat) => self.toggle_threat()?,
+            Some(Message::ToggleHistory) => self.toggle_history()?,
+            Some(Message::ScrollHistory(scroll)) => self.scroll_history(scroll)?,
+            Some(Message::SaveHistory) => self.save_history()?,
+            Some(Message::Rewind) => {
+                let popup = rewind::Rewind::new(&self.state)?;
+                return Ok(StackCommand::PushPopup(Box::new(popup)));
+            }
             Some(Message::EndTurn) => {
                 assert!(self.block_timer.is_none());
                 self.end_turn()?;
             }
+            Some(Message::Retreat) => {
+                if self.block_timer.is_none() {
+                    return Ok(StackCommand::PushPopup(self.popup_confirm_retreat()?));
+                }
+            }
             Some(Message::Ability(ability)) => self.use_ability(ability)?,
+            Some(Message::ConvertJoker(into)) => {
+                if self.block_timer.is_none() {
+                    return Ok(StackCommand::PushPopup(self.popup_confirm_joker(into)?));
+                }
+            }
+            Some(Message::Mount(mount_id)) => {
+                if self.block_timer.is_none() {
+                    self.mount(mount_id)?;
+                }
+            }
+            Some(Message::Dismount) => {
+                if self.block_timer.is_none() {
+                    self.dismount()?;
+                }
+            }
             Some(Message::PassiveAbilityInfo(ability)) => {
-                let title = &ability.title();
-                let description = &ability.description();
+                let title = &tr!(&ability.title());
+                let description = &i18n::translate_lines(ability.description());
                 let popup = screen::GeneralInfo::new(title, description)?;
                 return Ok(StackCommand::PushPopup(Box::new(popup)));
             }
             Some(Message::LastingEffectInfo(effect)) => {
-                let title = &effect.title();
-                let description = &effect.description();
-                let popup = screen::GeneralInfo::new(title, description)?;
+                let title = &tr!(effect.title());
+                let mut description = i18n::translate_lines(effect.description());
+                let rule = self.state.stack_rules().rule(effect);
+                description.extend(i18n::translate_lines(rule.description()));
+                let popup = screen::GeneralInfo::new(title, &description)?;
                 return Ok(StackCommand::PushPopup(Box::new(popup)));
             }
             None => self.handle_click(pos)?,
@@ -704,12 +2046,94 @@ impl Screen for Battle {
         self.gui.resize_if_needed(aspect_ratio);
     }
 
+    /// Shows the info about the pressed agent: there's no hovering on touch screens.
+    fn long_press(&mut self, point: Vec2) -> ZResult<StackCommand> {
+        if self.dialogue.is_active() {
+            return Ok(StackCommand::None);
+        }
+        let map_point = self.view.to_camera_point(point);
+        let pos = geom::point_to_hex(self.view.tile_size(), map_point);
+        if !self.state.map().is_inboard(pos) {
+            return Ok(StackCommand::None);
+        }
+        let id = match visible_agent_id_at(&self.state, pos) {
+            Some(id) => id,
+            None => return Ok(StackCommand::None),
+        };
+        let typename = &self.state.parts().meta.get(id).name;
+        let popup = screen::AgentInfo::new_agent_info(&self.prototypes, typename)?;
+        Ok(StackCommand::PushPopup(Box::new(popup)))
+    }
+
+    fn hotkey(&mut self, hotkey: Hotkey) -> ZResult<StackCommand> {
+        if self.dialogue.is_active() {
+            if hotkey == Hotkey::Select {
+                self.dialogue.advance(&mut self.gui)?;
+            }
+            return Ok(StackCommand::None);
+        }
+        // The same checks as the ones that the clicks go through.
+        let is_players_move = self.block_timer.is_none()
+            && self.state.player_id() == PlayerId(0)
+            && !state::is_deploying(&self.state);
+        match hotkey {
+            Hotkey::Menu => return Ok(StackCommand::PushPopup(self.popup_confirm_exit()?)),
+            Hotkey::ToggleAnimationSpeed => self.toggle_animation_speed()?,
+            Hotkey::ToggleThreat => self.toggle_threat()?,
+            Hotkey::EndTurn => {
+                if is_players_move && self.panel_end_turn.is_some() {
+                    self.end_turn()?;
+                }
+            }
+            Hotkey::NextAgent => {
+                if is_players_move {
+                    self.select_next_agent()?;
+                }
+            }
+            Hotkey::Ability(index) => {
+                if is_players_move {
+                    self.use_ability_by_index(index)?;
+                }
+            }
+            Hotkey::Cursor(dir) => self.move_key_cursor(dir)?,
+            Hotkey::Select => {
+                if let Some(pos) = self.key_cursor_pos {
+                    self.handle_tile_click(pos)?;
+                }
+            }
+            // The battle's buttons have their own hotkeys.
+            Hotkey::FocusNext | Hotkey::FocusPrev => {}
+            // Sent as drags.
+            Hotkey::PanLeft | Hotkey::PanRight | Hotkey::PanUp | Hotkey::PanDown => {}
+        }
+        Ok(StackCommand::None)
+    }
+
+    fn drag(&mut self, delta: Vec2) -> ZResult {
+        self.view.pan_camera(delta);
+        Ok(())
+    }
+
+    fn zoom(&mut self, k: f32, pos: Vec2) -> ZResult {
+        self.view.zoom_camera(k, pos);
+        Ok(())
+    }
+
     fn move_mouse(&mut self, point: Vec2) -> ZResult {
-        let pos = geom::point_to_hex(self.view.tile_size(), point);
+        // It's called every frame, the key cursor stays until the mouse really moves.
+        if self.key_cursor_pos.is_some() && self.mouse_point == Some(point) {
+            return Ok(());
+        }
+        self.key_cursor_pos = None;
+        self.mouse_point = Some(point);
+        let map_point = self.view.to_camera_point(point);
+        let pos = geom::point_to_hex(self.view.tile_size(), map_point);
         if self.state.map().is_inboard(pos) {
             self.view.show_current_tile_marker(pos);
+            self.update_forecast(pos)?;
         } else {
             self.view.hide_current_tile_marker();
+            self.remove_forecast()?;
         }
         self.gui.move_mouse(point);
         Ok(())
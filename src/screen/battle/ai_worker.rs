@@ -0,0 +1,68 @@
+//! Runs the AI players off the render thread, so the window doesn't freeze
+//! while they're thinking.
+
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+use crate::core::battle::{ai::Ai, command::CommandBatch, State};
+
+/// What the AI of a player has come up with.
+type Reply = (usize, Ai, Option<CommandBatch>);
+
+/// Asks the AIs for their command batches one by one.
+#[derive(Debug)]
+pub struct AiWorker {
+    /// One for every player except the human one,
+    /// `None` while that AI is busy thinking.
+    ais: Vec<Option<Ai>>,
+
+    receiver: Option<Receiver<Reply>>,
+}
+
+impl AiWorker {
+    pub fn new(ais: Vec<Ai>) -> Self {
+        Self {
+            ais: ais.into_iter().map(Some).collect(),
+            receiver: None,
+        }
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// Starts thinking about the next commands of the current player.
+    ///
+    /// The AI gets its own copy of the state, so the battle can go on
+    /// showing the previous commands in the meantime.
+    pub fn start(&mut self, state: &State) {
+        assert!(!self.is_thinking());
+        let index = state.player_id().0 as usize - 1;
+        let mut ai = self.ais[index].take().expect("The AI is already busy");
+        let state = state.clone();
+        let (sender, receiver) = channel();
+        let think = move || {
+            let batch = ai.batch(&state);
+            // The battle may have already been closed, that's fine.
+            let _ = sender.send((index, ai, batch));
+        };
+        // No threads on the web, so the batch is found right away there.
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(think);
+        #[cfg(target_arch = "wasm32")]
+        think();
+        self.receiver = Some(receiver);
+    }
+
+    /// Returns `Some` once the AI has made its choice,
+    /// `Some(None)` if it has nothing to do.
+    pub fn try_receive(&mut self) -> Option<Option<CommandBatch>> {
+        let (index, ai, batch) = match self.receiver.as_ref()?.try_recv() {
+            Ok(reply) => reply,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => panic!("The AI thread has crashed"),
+        };
+        self.ais[index] = Some(ai);
+        self.receiver = None;
+        Some(batch)
+    }
+}
@@ -0,0 +1,104 @@
+//! An overlay with the scripted dialogues of the scenario.
+
+use std::collections::VecDeque;
+
+use mq::color::Color;
+use ui::{self, Gui};
+
+use crate::{
+    assets,
+    core::battle::scenario::Remark,
+    i18n::{self, tr},
+    utils::{self, line_heights},
+    ZResult,
+};
+
+/// Maximum number of characters in a line of a remark.
+const LINE_WIDTH: usize = 40;
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        // Counted in chars and not in bytes for the translations.
+        let line_width = line.chars().count();
+        if !line.is_empty() && line_width + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn build_panel<Message: Clone>(gui: &mut Gui<Message>, remark: &Remark) -> ZResult<ui::RcWidget> {
+    let assets = assets::get();
+    let font = assets.font;
+    let text = |s: &str| ui::Drawable::text(s, font);
+    let h = line_heights().normal;
+    let mut layout = ui::HLayout::new();
+    if let Some(frames) = assets.sprite_frames.get(&remark.speaker) {
+        let portrait = ui::Drawable::Texture(frames[""]);
+        let label = ui::Label::new(portrait, h * 4.0)?.with_color(Color::new(1.0, 1.0, 1.0, 1.0));
+        layout.add(Box::new(label));
+        layout.add(Box::new(ui::Spacer::new_horizontal(h * 0.5)));
+    }
+    let mut text_layout = Box::new(ui::VLayout::new());
+    let name = match &remark.name {
+        Some(name) => tr!(name),
+        None => i18n::name_title(&remark.speaker.0),
+    };
+    text_layout.add(Box::new(ui::Label::new(text(&format!("{}:", name)), h)?));
+    for line in wrap(&tr!(&remark.text), LINE_WIDTH) {
+        text_layout.add(Box::new(ui::Label::new(text(&line), h)?));
+    }
+    text_layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    let hint = ui::Label::new(text(&tr!("(click to continue)")), h * 0.7)?;
+    text_layout.add(Box::new(hint));
+    layout.add(text_layout);
+    let layout = utils::add_offsets_and_bg_big(Box::new(layout))?;
+    let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Bottom);
+    let packed_layout = ui::pack(layout);
+    gui.add(&packed_layout, anchor);
+    Ok(packed_layout)
+}
+
+/// Shows the remarks one by one, every click shows the next one.
+#[derive(Debug, Default)]
+pub struct Dialogue {
+    remarks: VecDeque<Remark>,
+    panel: Option<ui::RcWidget>,
+}
+
+impl Dialogue {
+    /// The battle waits while some remark is on the screen.
+    pub fn is_active(&self) -> bool {
+        self.panel.is_some()
+    }
+
+    pub fn start<Message: Clone>(
+        &mut self,
+        gui: &mut Gui<Message>,
+        remarks: Vec<Remark>,
+    ) -> ZResult {
+        self.remarks.extend(remarks);
+        if !self.is_active() {
+            self.advance(gui)?;
+        }
+        Ok(())
+    }
+
+    /// Shows the next remark or closes the overlay if there're none left.
+    pub fn advance<Message: Clone>(&mut self, gui: &mut Gui<Message>) -> ZResult {
+        utils::remove_widget(gui, &mut self.panel)?;
+        if let Some(remark) = self.remarks.pop_front() {
+            self.panel = Some(build_panel(gui, &remark)?);
+        }
+        Ok(())
+    }
+}
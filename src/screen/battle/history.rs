@@ -0,0 +1,35 @@
+//! The texts of the battle log panel.
+
+use std::fmt::Display;
+
+use crate::{
+    core::battle::log::{Arg, Line},
+    i18n::{self, tr},
+};
+
+fn arg_text(arg: &Arg) -> String {
+    match arg {
+        Arg::Object {
+            name: Some(name), ..
+        } => name.clone(),
+        Arg::Object { typename, .. } => i18n::name_title(&typename.0),
+        Arg::Number(n) => n.to_string(),
+        Arg::Ability(ability) => tr!(&ability.title()),
+        Arg::PassiveAbility(ability) => tr!(&ability.title()),
+        Arg::Effect(effect) => tr!(effect.title()),
+    }
+}
+
+/// The line in the current language.
+pub fn line_text(line: &Line) -> String {
+    let args: Vec<(&str, String)> = line
+        .args
+        .iter()
+        .map(|(name, arg)| (*name, arg_text(arg)))
+        .collect();
+    let args: Vec<(&str, &dyn Display)> = args
+        .iter()
+        .map(|(name, text)| (*name, text as &dyn Display))
+        .collect();
+    i18n::translate_with(line.template, &args)
+}
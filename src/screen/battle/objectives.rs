@@ -0,0 +1,188 @@
+//! HUD widgets for the battle objectives.
+//!
+//! Every objective kind registers an icon and a progress renderer in
+//! the `widget_info` table, the rest of the HUD is built automatically.
+
+use mq::texture::Texture2D;
+use ui::{self, Gui};
+
+use crate::{
+    assets,
+    core::battle::{
+        ability::Ability,
+        component::WeaponType,
+        objective::{self, Kind, Status},
+        scenario::Weather,
+        state, PlayerId, State,
+    },
+    i18n::tr,
+    utils::{self, line_heights},
+    ZResult,
+};
+
+/// How an objective is shown in the HUD.
+///
+/// `None` fields fall back to the default icon and renderer.
+struct WidgetInfo {
+    title: &'static str,
+    icon: Option<fn() -> Texture2D>,
+    progress: Option<fn(&Status) -> String>,
+}
+
+fn widget_info(kind: Kind) -> WidgetInfo {
+    match kind {
+        Kind::KillAll => WidgetInfo {
+            title: "Kill all enemies",
+            icon: Some(|| assets::get().textures.weapon_flashes[&WeaponType::Slash]),
+            progress: Some(|status| tr!("enemies left: {count}", count = status.remaining)),
+        },
+        Kind::SurviveNTurns => WidgetInfo {
+            title: "Survive",
+            icon: None,
+            progress: Some(|status| tr!("turns to survive: {count}", count = status.remaining)),
+        },
+        Kind::ReachHex => WidgetInfo {
+            title: "Reach the marked tile",
+            icon: None,
+            progress: Some(|status| tr!("tiles to go: {count}", count = status.remaining)),
+        },
+        Kind::ProtectAgent => WidgetInfo {
+            title: "Protect",
+            icon: None,
+            progress: Some(|status| {
+                if status.is_failed {
+                    tr!("protected fighter: lost")
+                } else {
+                    tr!("protected fighter: alive")
+                }
+            }),
+        },
+        Kind::DestroyObject => WidgetInfo {
+            title: "Destroy",
+            icon: Some(|| assets::get().textures.weapon_flashes[&WeaponType::Smash]),
+            progress: None,
+        },
+        Kind::KillBoss => WidgetInfo {
+            title: "Kill the leader",
+            icon: Some(|| assets::get().textures.weapon_flashes[&WeaponType::Pierce]),
+            progress: Some(|status| tr!("leaders left: {count}", count = status.remaining)),
+        },
+        Kind::Escort => WidgetInfo {
+            title: "Escort",
+            icon: None,
+            progress: Some(|status| {
+                if status.is_failed {
+                    tr!("escorted fighter: lost")
+                } else {
+                    tr!("tiles to the exit: {count}", count = status.remaining)
+                }
+            }),
+        },
+        Kind::TurnLimit => WidgetInfo {
+            title: "Turn limit",
+            icon: None,
+            progress: Some(|status| tr!("turns left: {count}", count = status.remaining.max(0))),
+        },
+        Kind::SuddenDeath => WidgetInfo {
+            title: "Sudden death",
+            icon: Some(|| assets::get().textures.icons.abilities[&Ability::BombFire]),
+            progress: Some(|status| {
+                if status.remaining > 0 {
+                    tr!("sudden death in: {count} turns", count = status.remaining)
+                } else {
+                    tr!("sudden death: the map is burning")
+                }
+            }),
+        },
+    }
+}
+
+fn icon(info: &WidgetInfo) -> Texture2D {
+    match info.icon {
+        Some(icon) => icon(),
+        None => assets::get().textures.icons.info,
+    }
+}
+
+fn progress_text(info: &WidgetInfo, status: &Status) -> String {
+    let text = match info.progress {
+        Some(progress) => progress(status),
+        None => tr!(
+            "{objective}: {count} left",
+            objective = tr!(info.title),
+            count = status.remaining,
+        ),
+    };
+    if status.is_failed {
+        tr!("{objective} (failed)", objective = text)
+    } else if status.kind.is_constraint() {
+        text
+    } else if status.is_complete() {
+        tr!("{objective} (done)", objective = text)
+    } else {
+        text
+    }
+}
+
+fn info_line(text: &str) -> ZResult<ui::HLayout> {
+    let assets = assets::get();
+    let h = line_heights().normal;
+    let mut line = ui::HLayout::new();
+    let icon = ui::Drawable::Texture(assets.textures.icons.info);
+    line.add(Box::new(ui::Label::new(icon, h)?));
+    line.add(Box::new(ui::Spacer::new_horizontal(h / 4.0)));
+    let text = ui::Drawable::text(text, assets.font);
+    line.add(Box::new(ui::Label::new(text, h)?));
+    Ok(line)
+}
+
+pub fn build_panel_objectives<Message: Clone + 'static>(
+    gui: &mut Gui<Message>,
+    state: &State,
+) -> ZResult<ui::RcWidget> {
+    let font = assets::get().font;
+    let h = line_heights().normal;
+    let mut layout = Box::new(ui::VLayout::new());
+    for status in objective::statuses(state, PlayerId(0)) {
+        let info = widget_info(status.kind);
+        let mut line = ui::HLayout::new();
+        let icon = ui::Drawable::Texture(icon(&info));
+        line.add(Box::new(ui::Label::new(icon, h)?));
+        line.add(Box::new(ui::Spacer::new_horizontal(h / 4.0)));
+        let text = ui::Drawable::text(progress_text(&info, &status).as_str(), font);
+        line.add(Box::new(ui::Label::new(text, h)?));
+        layout.add(Box::new(line));
+    }
+    let weather = state.scenario().weather;
+    if weather != Weather::Clear {
+        let text = tr!(
+            "weather: {weather} ({description})",
+            weather = tr!(&weather.title()),
+            description = tr!(&weather.description()),
+        );
+        layout.add(Box::new(info_line(&text)?));
+    }
+    if state.scenario().is_night {
+        let text = tr!("night (shorter attacks away from light)");
+        layout.add(Box::new(info_line(&text)?));
+    }
+    let points = state::control_points(state);
+    if !points.is_empty() {
+        let held = points
+            .iter()
+            .filter(|&&(_, holder)| holder == Some(true))
+            .count();
+        let text = tr!(
+            "control points: {held}/{count}, renown: {renown}r",
+            held = held,
+            count = points.len(),
+            renown = state.control_renown(),
+        );
+        layout.add(Box::new(info_line(&text)?));
+    }
+    let layout = utils::add_offsets_and_bg(layout, utils::OFFSET_SMALL)?;
+    let layout = ui::pack(layout);
+    let anchor = ui::Anchor(ui::HAnchor::Right, ui::VAnchor::Top);
+    gui.add(&layout, anchor);
+    Ok(layout)
+}
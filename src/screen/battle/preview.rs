@@ -0,0 +1,123 @@
+//! A tooltip with the predicted outcomes of the hovered command.
+
+use ui::{self, Gui};
+
+use crate::{
+    assets,
+    core::battle::{
+        execute::AttackTerms,
+        forecast::{AttackForecast, Forecast, HealForecast},
+        State,
+    },
+    i18n::tr,
+    screen::battle::agent_title,
+    utils::{self, line_heights},
+    ZResult,
+};
+
+fn percent(probability: f32) -> String {
+    format!("{:.0}%", probability * 100.0)
+}
+
+/// Where the numbers come from: only the terms that change anything.
+fn terms_lines(terms: &AttackTerms) -> Vec<String> {
+    let mut lines = vec![tr!("base damage: {damage}", damage = terms.base_damage.0)];
+    if terms.backstab_bonus.0 > 0 {
+        lines.push(tr!("backstab: +{damage}", damage = terms.backstab_bonus.0));
+    }
+    if terms.aura_bonus.0 > 0 {
+        lines.push(tr!("aura: +{damage}", damage = terms.aura_bonus.0));
+    }
+    if terms.target_armor.0 > 0 {
+        lines.push(tr!("target armor: -{armor}", armor = terms.target_armor.0));
+    }
+    if terms.target_resistance.0 > 0 {
+        let resistance = terms.target_resistance.0;
+        lines.push(tr!(
+            "target resistance: -{resistance}",
+            resistance = resistance
+        ));
+    }
+    if terms.armor_break.0 > 0 {
+        lines.push(tr!("breaks {armor} armor", armor = terms.armor_break.0));
+    }
+    lines.push(tr!("accuracy: {accuracy}", accuracy = terms.accuracy.0));
+    if terms.target_dodge.0 > 0 {
+        lines.push(tr!("target dodge: -{dodge}", dodge = terms.target_dodge.0));
+    }
+    if terms.wounds_penalty > 0 {
+        lines.push(tr!("wounds: -{wounds}", wounds = terms.wounds_penalty));
+    }
+    if terms.injury_penalty > 0 {
+        lines.push(tr!("injury: -{injury}", injury = terms.injury_penalty));
+    }
+    lines
+}
+
+fn attack_lines(state: &State, forecast: &AttackForecast) -> Vec<String> {
+    let mut lines = vec![
+        tr!(
+            "attack {target}",
+            target = agent_title(state, forecast.target_id)
+        ),
+        tr!("hit: {chance}", chance = percent(forecast.hit_chance())),
+    ];
+    if let Some((min, max)) = forecast.damage_range() {
+        let damage = if min == max {
+            min.0.to_string()
+        } else {
+            format!("{}-{}", min.0, max.0)
+        };
+        lines.push(tr!("damage: {damage}", damage = damage));
+    }
+    let kill_chance = forecast.kill_chance();
+    if kill_chance > 0.0 {
+        lines.push(tr!("kill: {chance}", chance = percent(kill_chance)));
+    }
+    lines.extend(terms_lines(&forecast.terms));
+    for reaction in &forecast.retaliation {
+        lines.push(tr!(
+            "reaction of {attacker}: {chance} hit",
+            attacker = agent_title(state, reaction.attacker_id),
+            chance = percent(reaction.hit_chance()),
+        ));
+    }
+    lines
+}
+
+fn heal_lines(state: &State, forecast: &HealForecast) -> Vec<String> {
+    let mut lines = vec![
+        tr!(
+            "heal {target}",
+            target = agent_title(state, forecast.target_id)
+        ),
+        tr!("strength: +{strength}", strength = forecast.strength.0),
+    ];
+    for effect in &forecast.removed_effects {
+        lines.push(tr!("removes [{effect}]", effect = tr!(effect.title())));
+    }
+    lines
+}
+
+pub fn build_panel_forecast<Message: Clone + 'static>(
+    gui: &mut Gui<Message>,
+    state: &State,
+    forecast: &Forecast,
+) -> ZResult<ui::RcWidget> {
+    let font = assets::get().font;
+    let h = line_heights().normal;
+    let lines = match forecast {
+        Forecast::Attack(forecast) => attack_lines(state, forecast),
+        Forecast::Heal(forecast) => heal_lines(state, forecast),
+    };
+    let mut layout = Box::new(ui::VLayout::new());
+    for line in &lines {
+        let text = ui::Drawable::text(line.as_str(), font);
+        layout.add(Box::new(ui::Label::new(text, h)?));
+    }
+    let layout = utils::add_offsets_and_bg(layout, utils::OFFSET_SMALL)?;
+    let layout = ui::pack(layout);
+    let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Top);
+    gui.add(&layout, anchor);
+    Ok(layout)
+}
@@ -0,0 +1,214 @@
+//! The "what happened?" popup: the battle is played again from its history, turn by turn.
+
+use std::time::Duration;
+
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    core::battle::{
+        event::ActiveEvent,
+        execute::ApplyPhase,
+        history,
+        log::{Line, Log},
+        state, PlayerId, State,
+    },
+    i18n::tr,
+    screen::{
+        battle::{agent_title, history::line_text},
+        Screen, StackCommand,
+    },
+    utils, ZResult,
+};
+
+/// The rest of the turn's lines are only counted.
+const LINES_COUNT: usize = 14;
+
+#[derive(Clone, Copy, Debug)]
+enum Message {
+    Older,
+    Newer,
+    Back,
+}
+
+#[derive(Clone, Debug)]
+struct Turn {
+    player_id: PlayerId,
+
+    /// The player's or one of the allies' turn.
+    is_on_human_side: bool,
+
+    lines: Vec<Line>,
+
+    /// The strength of every side's agents when the turn was over,
+    /// the allies are on the player's side.
+    forces: [String; 2],
+}
+
+fn forces(state: &State) -> [String; 2] {
+    let mut sides = [Vec::new(), Vec::new()];
+    for id in state.parts().agent.ids() {
+        let player_id = state.parts().belongs_to.get(id).0;
+        let side = if state::is_on_human_side(state, player_id) {
+            0
+        } else {
+            1
+        };
+        let strength = state.parts().strength.get(id).strength.0;
+        sides[side].push(format!("{} {}", agent_title(state, id), strength));
+    }
+    sides.map(|agents| agents.join(", "))
+}
+
+/// The log lines of the replayed events, split by the turns they've happened in.
+fn replay_turns(state: &State) -> Vec<Turn> {
+    let mut log = Log::default();
+    let mut turn_starts = vec![0];
+    let mut turn_forces = Vec::new();
+    let commands = state.history().commands_count();
+    history::replay(state, commands, &mut |state, event, phase| {
+        if phase != ApplyPhase::Pre {
+            return;
+        }
+        if let ActiveEvent::BeginTurn(_) = event.active_event {
+            turn_forces.push(forces(state));
+            turn_starts.push(log.lines().len());
+        }
+        log.record(state, event);
+    });
+    turn_forces.push(forces(state));
+    turn_starts.push(log.lines().len());
+    state
+        .history()
+        .turns()
+        .zip(turn_starts.windows(2))
+        .zip(turn_forces)
+        .map(|((turn, range), forces)| Turn {
+            player_id: turn.player_id,
+            is_on_human_side: state::is_on_human_side(state, turn.player_id),
+            lines: log.lines()[range[0]..range[1]].to_vec(),
+            forces,
+        })
+        .collect()
+}
+
+fn make_gui(turns: &[Turn], index: usize) -> ZResult<Gui<Message>> {
+    let font = assets::get().font;
+    let mut gui = ui::Gui::new();
+    let h = utils::line_heights().normal;
+    let h_small = utils::line_heights().small;
+    let text = |s: &str| ui::Drawable::text(s, font);
+    let label = |s: &str| -> ZResult<_> { Ok(Box::new(ui::Label::new(text(s), h_small)?)) };
+    let label_s =
+        |s: &str| -> ZResult<_> { Ok(Box::new(ui::Label::new(text(s), h)?.stretchable(true))) };
+    let spacer = || Box::new(ui::Spacer::new_vertical(h * 0.5));
+    let turn = &turns[index];
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    let title = tr!(
+        "Turn {turn} of {count}",
+        turn = index + 1,
+        count = turns.len()
+    );
+    layout.add(label_s(&format!("~~~ {} ~~~", title))?);
+    let side = if turn.player_id == PlayerId(0) {
+        tr!("~ your move ~")
+    } else if turn.is_on_human_side {
+        tr!("~ the allies' move ~")
+    } else {
+        tr!("~ the enemy's move ~")
+    };
+    layout.add(label_s(&side)?);
+    layout.add(spacer());
+    if turn.lines.is_empty() {
+        layout.add(label(&tr!("Nothing has happened."))?);
+    }
+    for line in turn.lines.iter().take(LINES_COUNT) {
+        layout.add(label(&line_text(line))?);
+    }
+    if turn.lines.len() > LINES_COUNT {
+        let count = turn.lines.len() - LINES_COUNT;
+        layout.add(label(&tr!("...and {count} more", count = count))?);
+    }
+    layout.add(spacer());
+    layout.add(label(&tr!("Afterwards:"))?);
+    let [ours, theirs] = &turn.forces;
+    layout.add(label(&tr!("your side: {agents}", agents = ours))?);
+    layout.add(label(&tr!("enemies: {agents}", agents = theirs))?);
+    layout.add(spacer());
+    let mut buttons = Box::new(ui::HLayout::new());
+    let mut button = ui::Button::new(text(&tr!("older")), h, gui.sender(), Message::Older)?;
+    button.set_active(index > 0);
+    buttons.add(Box::new(button));
+    buttons.add(Box::new(ui::Spacer::new_horizontal(h * 0.5)));
+    let mut button = ui::Button::new(text(&tr!("newer")), h, gui.sender(), Message::Newer)?;
+    button.set_active(index + 1 < turns.len());
+    buttons.add(Box::new(button));
+    buttons.add(Box::new(ui::Spacer::new_horizontal(h * 0.5)));
+    let button = ui::Button::new(text(&tr!("back")), h, gui.sender(), Message::Back)?;
+    buttons.add(Box::new(button));
+    layout.add(buttons);
+    layout.stretch_to_self();
+    let layout = utils::add_offsets_and_bg_big(layout)?;
+    let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+    gui.add(&ui::pack(layout), anchor);
+    Ok(gui)
+}
+
+/// Starts with the current turn, the older ones are a click away.
+#[derive(Debug)]
+pub struct Rewind {
+    gui: Gui<Message>,
+    turns: Vec<Turn>,
+    index: usize,
+}
+
+impl Rewind {
+    pub fn new(state: &State) -> ZResult<Self> {
+        let turns = replay_turns(state);
+        let index = turns.len() - 1;
+        let gui = make_gui(&turns, index)?;
+        Ok(Self { gui, turns, index })
+    }
+
+    fn show_turn(&mut self, index: usize) -> ZResult {
+        self.index = index;
+        self.gui.replace(make_gui(&self.turns, index)?);
+        Ok(())
+    }
+}
+
+impl Screen for Rewind {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::Older) => self.show_turn(self.index.saturating_sub(1))?,
+            Some(Message::Newer) => self.show_turn((self.index + 1).min(self.turns.len() - 1))?,
+            Some(Message::Back) => return Ok(StackCommand::Pop),
+            None => {}
+        }
+        Ok(StackCommand::None)
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
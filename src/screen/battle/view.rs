@@ -1,6 +1,10 @@
-use std::{collections::HashMap, default::Default, time::Duration};
+use std::{cell::RefCell, collections::HashMap, default::Default, mem, rc::Rc, time::Duration};
 
-use mq::{color::Color, math::Vec2};
+use mq::{
+    camera::{set_camera, Camera2D},
+    color::Color,
+    math::{Rect, Vec2},
+};
 
 use zscene::{action, Action, Boxed, Layer, Scene, Sprite};
 
@@ -8,15 +12,17 @@ use crate::{
     assets,
     core::{
         battle::{
-            self, ability::Ability, command, component::ObjType, execute::hit_chance, movement,
-            state, Id, Jokers, Moves, State, TileType, Turns,
+            self, ability::Ability, command, component::ObjType, decal, execute::hit_chance,
+            movement, objective::Objective, scenario::Remark, state, Id, Jokers, MovePoints, Moves,
+            State, TileType, Turns,
         },
         map::{self, Dir, Distance, HexMap, PosHex},
-        utils::roll_dice,
+        utils::{clamp, roll_dice},
     },
     geom::{self, hex_to_point},
     screen::battle::visualize,
-    utils::time_s,
+    settings,
+    utils::{self, time_s},
     ZResult,
 };
 
@@ -28,15 +34,39 @@ pub enum SelectionMode {
 
 const TILE_COLOR_WALKABLE: Color = Color::new(0.1, 0.6, 0.1, 0.3);
 const TILE_COLOR_ATTACKABLE: Color = Color::new(0.8, 0.0, 0.0, 0.3);
+const TILE_COLOR_WALKABLE_COLORBLIND: Color = Color::new(0.3, 0.7, 0.9, 0.4);
+const TILE_COLOR_ATTACKABLE_COLORBLIND: Color = Color::new(0.9, 0.6, 0.0, 0.4);
 const TILE_COLOR_ABILITY: Color = Color::new(0.0, 0.0, 0.9, 0.3);
+const TILE_COLOR_THREAT: Color = Color::new(0.6, 0.0, 0.3, 0.25);
+const PATH_DOT_COLOR: Color = Color::new(0.1, 0.1, 0.1, 0.6);
+const TILE_COLOR_THREAT_COLORBLIND: Color = Color::new(0.5, 0.0, 0.7, 0.3);
+const TILE_COLOR_OBJECTIVE: Color = Color::new(0.9, 0.7, 0.0, 0.5);
+const TILE_COLOR_REGION: Color = Color::new(0.3, 0.5, 0.9, 0.4);
+const TILE_COLOR_DEPLOYMENT: Color = Color::new(0.9, 0.9, 0.2, 0.3);
+const TILE_COLOR_TUTORIAL: Color = Color::new(1.0, 1.0, 1.0, 0.5);
+// TODO: draw a texture for the water instead of tinting the plain tile.
+const TILE_COLOR_WATER: Color = Color::new(0.4, 0.6, 1.0, 1.0);
+const TILE_COLOR_THICKET: Color = Color::new(0.4, 0.7, 0.3, 1.0);
+const TILE_COLOR_AURA_FRIENDLY: Color = Color::new(0.2, 0.8, 0.8, 0.15);
+const TILE_COLOR_AURA_HOSTILE: Color = Color::new(0.8, 0.4, 0.0, 0.15);
+const BANNER_COLOR_NEUTRAL: Color = Color::new(0.6, 0.6, 0.6, 0.6);
+const BANNER_COLOR_FRIENDLY: Color = Color::new(0.1, 0.3, 0.9, 0.6);
+const BANNER_COLOR_HOSTILE: Color = Color::new(0.8, 0.1, 0.1, 0.6);
+const FUSE_COLOR: Color = Color::new(0.8, 0.1, 0.0, 1.0);
 
 #[derive(Debug, Clone, Default)]
 pub struct Layers {
     pub bg: Layer,
+    pub decals: Layer,
     pub blood: Layer,
     pub shadows: Layer,
     pub grass: Layer,
+    pub auras: Layer,
+    pub banners: Layer,
+    pub threat: Layer,
+    pub tutorial: Layer,
     pub highlighted_tiles: Layer,
+    pub path: Layer,
     pub selection_marker: Layer,
     pub current_tile_marker: Layer,
     pub particles: Layer,
@@ -50,10 +80,16 @@ impl Layers {
     fn sorted(self) -> Vec<Layer> {
         vec![
             self.bg,
+            self.decals,
             self.blood,
             self.shadows,
             self.grass,
+            self.auras,
+            self.banners,
+            self.threat,
+            self.tutorial,
             self.highlighted_tiles,
+            self.path,
             self.selection_marker,
             self.current_tile_marker,
             self.particles,
@@ -69,8 +105,37 @@ fn textures() -> &'static assets::Textures {
     &assets::get().textures
 }
 
+/// Blue and orange instead of green and red in the colorblind mode.
+fn tile_color_walkable() -> Color {
+    if settings::get().colorblind {
+        TILE_COLOR_WALKABLE_COLORBLIND
+    } else {
+        TILE_COLOR_WALKABLE
+    }
+}
+
+fn tile_color_attackable() -> Color {
+    if settings::get().colorblind {
+        TILE_COLOR_ATTACKABLE_COLORBLIND
+    } else {
+        TILE_COLOR_ATTACKABLE
+    }
+}
+
+fn tile_color_threat() -> Color {
+    if settings::get().colorblind {
+        TILE_COLOR_THREAT_COLORBLIND
+    } else {
+        TILE_COLOR_THREAT
+    }
+}
+
+/// Bigger maps don't fit the screen, so the player has to scroll them.
+const MAX_FITTED_MAP_HEIGHT: Distance = Distance(13);
+
 pub fn tile_size(map_height: Distance) -> f32 {
-    1.0 / (map_height.0 as f32 * 0.75)
+    let height = map_height.0.min(MAX_FITTED_MAP_HEIGHT.0);
+    1.0 / (height as f32 * 0.75)
 }
 
 #[derive(Debug)]
@@ -87,11 +152,18 @@ struct Sprites {
     selection_marker: Sprite,
     current_tile_marker: Sprite,
     highlighted_tiles: Vec<Sprite>,
+    aura_tiles: Vec<Sprite>,
+    banners: Vec<Sprite>,
+    fuses: Vec<Sprite>,
+    threat_tiles: Vec<Sprite>,
+    tutorial_tiles: Vec<Sprite>,
+    path: Vec<Sprite>,
     labels: Vec<Sprite>,
     id_to_sprite_map: HashMap<Id, Sprite>,
     id_to_shadow_map: HashMap<Id, Sprite>,
     agent_info: HashMap<Id, Vec<Sprite>>,
     disappearing_sprites: Vec<DisappearingSprite>,
+    decals: Vec<Sprite>,
 }
 
 #[derive(Debug)]
@@ -155,6 +227,50 @@ impl MessagesMap {
     }
 }
 
+/// How fast the cinematic camera catches up with its target.
+const CAMERA_SPEED: f32 = 4.0;
+
+/// The closest the player can zoom in.
+const MAX_ZOOM: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CameraFrame {
+    pos: Vec2,
+    zoom: f32,
+}
+
+impl Default for CameraFrame {
+    fn default() -> Self {
+        Self {
+            pos: Vec2::new(0.0, 0.0),
+            zoom: 1.0,
+        }
+    }
+}
+
+/// A camera that smoothly moves to the frame set by the battle actions.
+#[derive(Debug, Default)]
+struct Camera {
+    current: CameraFrame,
+    target: CameraFrame,
+
+    /// The view chosen by the player, the cinematic camera returns to it.
+    home: CameraFrame,
+}
+
+impl Camera {
+    fn update(&mut self, dtime: Duration) {
+        let k = (CAMERA_SPEED * dtime.as_secs_f32()).min(1.0);
+        self.current.pos += (self.target.pos - self.current.pos) * k;
+        self.current.zoom += (self.target.zoom - self.current.zoom) * k;
+        let is_close = (self.target.pos - self.current.pos).length() < 0.001
+            && (self.target.zoom - self.current.zoom).abs() < 0.001;
+        if is_close {
+            self.current = self.target;
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct BattleView {
     tile_size: f32,
@@ -162,6 +278,14 @@ pub struct BattleView {
     scene: Scene,
     sprites: Sprites,
     messages_map: MessagesMap,
+    camera: Rc<RefCell<Camera>>,
+    is_cinematic_camera: bool,
+
+    /// Half of the map's width and height, the camera can't leave these bounds.
+    map_half_size: Vec2,
+
+    /// Dialogue lines waiting for the current animations to end.
+    remarks: Vec<Remark>,
 }
 
 impl BattleView {
@@ -183,21 +307,137 @@ impl BattleView {
             selection_marker,
             current_tile_marker,
             highlighted_tiles: Vec::new(),
+            aura_tiles: Vec::new(),
+            banners: Vec::new(),
+            fuses: Vec::new(),
+            threat_tiles: Vec::new(),
+            tutorial_tiles: Vec::new(),
+            path: Vec::new(),
             labels: Vec::new(),
             id_to_sprite_map: HashMap::new(),
             id_to_shadow_map: HashMap::new(),
             agent_info: HashMap::new(),
             disappearing_sprites: Vec::new(),
+            decals: Vec::new(),
         };
+        // The widest row is the middle one and the tallest column is at `q == 0`.
+        let east = hex_to_point(
+            tile_size,
+            PosHex {
+                q: map_radius.0,
+                r: 0,
+            },
+        );
+        let south = hex_to_point(
+            tile_size,
+            PosHex {
+                q: 0,
+                r: map_radius.0,
+            },
+        );
+        let map_half_size = Vec2::new(east.x + tile_size, south.y + tile_size);
         Ok(Self {
             sprites,
             scene,
             layers,
             tile_size,
             messages_map: MessagesMap::new(map_radius),
+            camera: Rc::new(RefCell::new(Camera::default())),
+            is_cinematic_camera: false,
+            map_half_size,
+            remarks: Vec::new(),
         })
     }
 
+    pub fn is_cinematic_camera(&self) -> bool {
+        self.is_cinematic_camera
+    }
+
+    pub fn set_cinematic_camera(&mut self, is_enabled: bool) {
+        self.is_cinematic_camera = is_enabled;
+        if !is_enabled {
+            let mut camera = self.camera.borrow_mut();
+            camera.current = camera.home;
+            camera.target = camera.home;
+        }
+    }
+
+    /// The whole map fits the screen at this zoom.
+    fn min_zoom(&self) -> f32 {
+        let aspect_ratio = utils::aspect_ratio();
+        let zoom_x = aspect_ratio / self.map_half_size.x;
+        let zoom_y = 1.0 / self.map_half_size.y;
+        zoom_x.min(zoom_y).min(1.0)
+    }
+
+    /// Keeps the camera inside the map.
+    fn clamp_frame(&self, frame: CameraFrame) -> CameraFrame {
+        let zoom = clamp(frame.zoom, self.min_zoom(), MAX_ZOOM);
+        let aspect_ratio = utils::aspect_ratio();
+        let max_x = (self.map_half_size.x - aspect_ratio / zoom).max(0.0);
+        let max_y = (self.map_half_size.y - 1.0 / zoom).max(0.0);
+        let pos = Vec2::new(
+            clamp(frame.pos.x, -max_x, max_x),
+            clamp(frame.pos.y, -max_y, max_y),
+        );
+        CameraFrame { pos, zoom }
+    }
+
+    fn set_home_camera(&self, frame: CameraFrame) {
+        let frame = self.clamp_frame(frame);
+        let mut camera = self.camera.borrow_mut();
+        camera.home = frame;
+        camera.current = frame;
+        camera.target = frame;
+    }
+
+    /// Drags the map along with the cursor, `delta` is in the default camera's coordinates.
+    pub fn pan_camera(&self, delta: Vec2) {
+        let home = self.camera.borrow().home;
+        let pos = home.pos - delta / home.zoom;
+        self.set_home_camera(CameraFrame { pos, ..home });
+    }
+
+    /// Zooms in (`k > 1.0`) or out keeping the map's point under the cursor in place.
+    pub fn zoom_camera(&self, k: f32, point: Vec2) {
+        let home = self.camera.borrow().home;
+        let map_point = home.pos + point / home.zoom;
+        let zoom = clamp(home.zoom * k, self.min_zoom(), MAX_ZOOM);
+        let pos = map_point - point / zoom;
+        self.set_home_camera(CameraFrame { pos, zoom });
+    }
+
+    /// Moves the camera to the point if the cinematic mode is enabled.
+    pub fn focus_camera(&self, pos: Vec2, zoom: f32) -> Box<dyn Action> {
+        if !self.is_cinematic_camera {
+            return action::Empty::new().boxed();
+        }
+        let camera = self.camera.clone();
+        let closure = Box::new(move || {
+            camera.borrow_mut().target = CameraFrame { pos, zoom };
+        });
+        action::Custom::new(closure).boxed()
+    }
+
+    /// Converts a point from the default camera's coordinates to the current one's.
+    pub fn to_camera_point(&self, point: Vec2) -> Vec2 {
+        let frame = self.camera.borrow().current;
+        frame.pos + point / frame.zoom
+    }
+
+    /// Returns the camera to the player's view of the battlefield.
+    pub fn reset_camera(&self) -> Box<dyn Action> {
+        if !self.is_cinematic_camera {
+            return action::Empty::new().boxed();
+        }
+        let camera = self.camera.clone();
+        let closure = Box::new(move || {
+            let mut camera = camera.borrow_mut();
+            camera.target = camera.home;
+        });
+        action::Custom::new(closure).boxed()
+    }
+
     pub fn object_sprite(&self, obj_type: &ObjType) -> Sprite {
         let assets = assets::get();
         let info = assets.sprites_info.get(obj_type).expect("No such object");
@@ -215,6 +455,14 @@ impl BattleView {
         &mut self.messages_map
     }
 
+    pub fn queue_remarks(&mut self, remarks: &[Remark]) {
+        self.remarks.extend_from_slice(remarks);
+    }
+
+    pub fn take_remarks(&mut self) -> Vec<Remark> {
+        mem::take(&mut self.remarks)
+    }
+
     pub fn message(&mut self, pos: PosHex, text: &str) -> ZResult {
         let action = visualize::message(self, pos, text)?;
         self.add_action(action);
@@ -223,9 +471,18 @@ impl BattleView {
 
     pub fn tick(&mut self, dtime: Duration) {
         self.scene.tick(dtime);
+        self.camera.borrow_mut().update(dtime);
     }
 
     pub fn draw(&self) -> ZResult {
+        let frame = self.camera.borrow().current;
+        if frame != CameraFrame::default() {
+            let aspect_ratio = utils::aspect_ratio();
+            let w = aspect_ratio * 2.0 / frame.zoom;
+            let h = 2.0 / frame.zoom;
+            let rect = Rect::new(frame.pos.x - w / 2.0, frame.pos.y - h / 2.0, w, h);
+            set_camera(&Camera2D::from_display_rect(rect));
+        }
         self.scene.draw();
         Ok(())
     }
@@ -234,6 +491,10 @@ impl BattleView {
         self.scene.add_action(action);
     }
 
+    pub fn is_busy(&self) -> bool {
+        self.scene.is_busy()
+    }
+
     // TODO: return `(f32, f32)`? width and height separately?
     pub fn tile_size(&self) -> f32 {
         self.tile_size
@@ -293,6 +554,43 @@ impl BattleView {
         visualize::seq(actions)
     }
 
+    /// Shows all the state's decals that aren't visualized yet.
+    pub fn sync_decals(&mut self, state: &State) -> Box<dyn Action> {
+        let mut actions = Vec::new();
+        let shown_count = self.sprites.decals.len();
+        for decal in &state.decals()[shown_count..] {
+            let (sprite, color) = self.make_decal_sprite(decal);
+            actions.push(visualize::fork(visualize::seq([
+                action::Show::new(&self.layers.decals, &sprite).boxed(),
+                action::ChangeColorTo::new(&sprite, color, time_s(1.0)).boxed(),
+            ])));
+            self.sprites.decals.push(sprite);
+        }
+        visualize::seq(actions)
+    }
+
+    fn make_decal_sprite(&self, decal: &decal::Decal) -> (Sprite, Color) {
+        let map = &textures().map;
+        let (texture, color, scale) = match decal.kind {
+            decal::Kind::Blood => (map.blood, Color::new(1.0, 1.0, 1.0, 0.25), 1.2),
+            decal::Kind::Scorch => (
+                map.explosion_ground_mark,
+                Color::new(0.2, 0.1, 0.0, 0.4),
+                1.8,
+            ),
+            decal::Kind::Crater => (
+                map.explosion_ground_mark,
+                Color::new(0.4, 0.4, 0.4, 0.4),
+                1.6,
+            ),
+        };
+        let mut sprite = Sprite::from_texture(texture, self.tile_size() * scale);
+        sprite.set_centered(true);
+        sprite.set_pos(self.hex_to_point(decal.pos));
+        sprite.set_color(Color { a: 0.0, ..color });
+        (sprite, color)
+    }
+
     pub fn id_to_sprite(&mut self, id: Id) -> &Sprite {
         &self.sprites.id_to_sprite_map[&id]
     }
@@ -326,23 +624,162 @@ impl BattleView {
         }
     }
 
+    /// Highlights the tiles where the next agent can be placed.
+    pub fn show_deployment_zone(&mut self, tiles: &[PosHex]) -> ZResult {
+        for &pos in tiles {
+            self.highlight_tile(pos, TILE_COLOR_DEPLOYMENT)?;
+        }
+        Ok(())
+    }
+
     pub fn remove_highlights(&mut self) {
         self.clean_highlighted_tiles();
         self.clean_labels();
     }
 
     fn clean_highlighted_tiles(&mut self) {
-        for sprite in self.sprites.highlighted_tiles.split_off(0) {
+        let sprites = self.sprites.highlighted_tiles.split_off(0);
+        let layer = self.layers.highlighted_tiles.clone();
+        self.fade_out_tiles(sprites, &layer);
+    }
+
+    /// Tints the tiles under the auras, replacing the previously shown ones:
+    /// `true` marks the auras that help the player's side.
+    pub fn show_auras(&mut self, tiles: &[(PosHex, bool)]) -> ZResult {
+        let sprites = self.sprites.aura_tiles.split_off(0);
+        let layer = self.layers.auras.clone();
+        self.fade_out_tiles(sprites, &layer);
+        for &(pos, is_friendly) in tiles {
+            let color = if is_friendly {
+                TILE_COLOR_AURA_FRIENDLY
+            } else {
+                TILE_COLOR_AURA_HOSTILE
+            };
+            let sprite = self.fade_in_tile(pos, color, &layer)?;
+            self.sprites.aura_tiles.push(sprite);
+        }
+        Ok(())
+    }
+
+    /// Colors the control points by their holders: `Some(true)` is the player's side
+    /// and `None` is for the points that nobody has captured yet.
+    pub fn show_control_points(&mut self, points: &[(PosHex, Option<bool>)]) -> ZResult {
+        let sprites = self.sprites.banners.split_off(0);
+        let layer = self.layers.banners.clone();
+        self.fade_out_tiles(sprites, &layer);
+        for &(pos, holder) in points {
+            let color = match holder {
+                None => BANNER_COLOR_NEUTRAL,
+                Some(true) => BANNER_COLOR_FRIENDLY,
+                Some(false) => BANNER_COLOR_HOSTILE,
+            };
+            let sprite = self.fade_in_tile(pos, color, &layer)?;
+            self.sprites.banners.push(sprite);
+        }
+        Ok(())
+    }
+
+    /// Numbers over the bombs, replacing the previously shown ones:
+    /// how many times their thrower's turn has to begin before they explode.
+    pub fn show_fuses(&mut self, fuses: &[(PosHex, i32)]) {
+        for sprite in self.sprites.fuses.split_off(0) {
+            let action = action::Hide::new(&self.layers.text, &sprite).boxed();
+            self.scene.add_action(action);
+        }
+        let font = assets::get().font;
+        let offset = Vec2::new(0.0, -self.tile_size() * 0.8);
+        for &(pos, fuse) in fuses {
+            let text = fuse.to_string();
+            let mut sprite = Sprite::from_text((text.as_str(), font), 0.1);
+            sprite.set_centered(true);
+            sprite.set_color(FUSE_COLOR);
+            sprite.set_pos(hex_to_point(self.tile_size(), pos) + offset);
+            self.scene
+                .add_action(action::Show::new(&self.layers.text, &sprite).boxed());
+            self.sprites.fuses.push(sprite);
+        }
+    }
+
+    /// Highlights the tiles that the enemies can attack on their next turn,
+    /// replacing the previously shown ones.
+    ///
+    /// Stays on its own layer, so the selection doesn't affect it.
+    pub fn show_threat_zone(&mut self, tiles: &HexMap<bool>) -> ZResult {
+        self.hide_threat_zone();
+        let layer = self.layers.threat.clone();
+        for pos in tiles.iter() {
+            if tiles.tile(pos) {
+                let sprite = self.fade_in_tile(pos, tile_color_threat(), &layer)?;
+                self.sprites.threat_tiles.push(sprite);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn hide_threat_zone(&mut self) {
+        let sprites = self.sprites.threat_tiles.split_off(0);
+        let layer = self.layers.threat.clone();
+        self.fade_out_tiles(sprites, &layer);
+    }
+
+    /// Points the player at the tile that the current tutorial step is about.
+    pub fn show_tutorial_tile(&mut self, pos: PosHex) -> ZResult {
+        self.hide_tutorial_tile();
+        let layer = self.layers.tutorial.clone();
+        let sprite = self.fade_in_tile(pos, TILE_COLOR_TUTORIAL, &layer)?;
+        self.sprites.tutorial_tiles.push(sprite);
+        Ok(())
+    }
+
+    pub fn hide_tutorial_tile(&mut self) {
+        let sprites = self.sprites.tutorial_tiles.split_off(0);
+        let layer = self.layers.tutorial.clone();
+        self.fade_out_tiles(sprites, &layer);
+    }
+
+    /// Dots along the path and its cost in move points at the destination.
+    pub fn show_path(&mut self, path: &movement::Path, cost: MovePoints) -> ZResult {
+        self.hide_path();
+        let tiles = path.tiles();
+        let size = self.tile_size() * 0.3;
+        let last = tiles.len() - 1;
+        for &pos in &tiles[1..last] {
+            let mut sprite = Sprite::from_texture(textures().dot, size);
+            sprite.set_centered(true);
+            sprite.set_color(PATH_DOT_COLOR);
+            sprite.set_pos(hex_to_point(self.tile_size(), pos));
+            self.scene
+                .add_action(action::Show::new(&self.layers.path, &sprite).boxed());
+            self.sprites.path.push(sprite);
+        }
+        let font = assets::get().font;
+        let text = cost.0.to_string();
+        let mut sprite = Sprite::from_text((text.as_str(), font), 0.1);
+        sprite.set_centered(true);
+        sprite.set_color(PATH_DOT_COLOR);
+        sprite.set_pos(hex_to_point(self.tile_size(), tiles[last]));
+        self.scene
+            .add_action(action::Show::new(&self.layers.path, &sprite).boxed());
+        self.sprites.path.push(sprite);
+        Ok(())
+    }
+
+    pub fn hide_path(&mut self) {
+        for sprite in self.sprites.path.split_off(0) {
+            let action = action::Hide::new(&self.layers.path, &sprite).boxed();
+            self.scene.add_action(action);
+        }
+    }
+
+    fn fade_out_tiles(&mut self, sprites: Vec<Sprite>, layer: &Layer) {
+        for sprite in sprites {
             let color = sprite.color();
             let color = Color { a: 0.0, ..color };
-            let action = {
-                let layer = &self.layers().highlighted_tiles;
-                let time = time_s(0.3);
-                visualize::seq(vec![
-                    action::ChangeColorTo::new(&sprite, color, time).boxed(),
-                    action::Hide::new(layer, &sprite).boxed(),
-                ])
-            };
+            let time = time_s(0.3);
+            let action = visualize::seq(vec![
+                action::ChangeColorTo::new(&sprite, color, time).boxed(),
+                action::Hide::new(layer, &sprite).boxed(),
+            ]);
             self.add_action(action);
         }
     }
@@ -420,11 +857,12 @@ impl BattleView {
     fn show_attackable_tiles(&mut self, state: &State, id: Id) -> ZResult {
         let parts = state.parts();
         let selected_agent_player_id = parts.belongs_to.get(id).0;
-        for target_id in parts.agent.ids() {
+        for target_id in parts.strength.ids() {
             let target_pos = parts.pos.get(target_id).0;
-            let target_player_id = parts.belongs_to.get(target_id).0;
-            if target_player_id == selected_agent_player_id {
-                continue;
+            if let Some(target_player_id) = parts.belongs_to.get_opt(target_id) {
+                if state::are_allies(state, target_player_id.0, selected_agent_player_id) {
+                    continue;
+                }
             }
             let command_attack = command::Attack {
                 attacker_id: id,
@@ -435,7 +873,7 @@ impl BattleView {
                 continue;
             }
             self.show_hit_chance_label(state, id, target_id)?;
-            self.highlight_tile(target_pos, TILE_COLOR_ATTACKABLE)?;
+            self.highlight_tile(target_pos, tile_color_attackable())?;
         }
         Ok(())
     }
@@ -450,16 +888,28 @@ impl BattleView {
         if agent.moves == Moves(0) && agent.jokers == Jokers(0) {
             return Ok(());
         }
+        let agent_pos = state.parts().pos.get(id).0;
         for pos in map.iter() {
             if map.tile(pos).cost() > agent.move_points {
                 continue;
             }
-            self.highlight_tile(pos, TILE_COLOR_WALKABLE)?
+            // The flyers pass over the blockers, but can't stop there.
+            if pos != agent_pos && state::is_tile_blocked(state, pos) {
+                continue;
+            }
+            self.highlight_tile(pos, tile_color_walkable())?
         }
         Ok(())
     }
 
     fn highlight_tile(&mut self, pos: PosHex, color: Color) -> ZResult {
+        let layer = self.layers.highlighted_tiles.clone();
+        let sprite = self.fade_in_tile(pos, color, &layer)?;
+        self.sprites.highlighted_tiles.push(sprite);
+        Ok(())
+    }
+
+    fn fade_in_tile(&mut self, pos: PosHex, color: Color, layer: &Layer) -> ZResult<Sprite> {
         let size = self.tile_size() * 2.0 * geom::FLATNESS_COEFFICIENT;
         let mut sprite = Sprite::from_texture(textures().map.white_hex, size);
         let color_from = Color { a: 0.0, ..color };
@@ -467,14 +917,12 @@ impl BattleView {
         sprite.set_color(color_from);
         sprite.set_pos(hex_to_point(self.tile_size(), pos));
         let time = time_s(0.3);
-        let layer = &self.layers.highlighted_tiles;
         let actions = vec![
             action::Show::new(layer, &sprite).boxed(),
             action::ChangeColorTo::new(&sprite, color, time).boxed(),
         ];
         self.scene.add_action(visualize::seq(actions));
-        self.sprites.highlighted_tiles.push(sprite);
-        Ok(())
+        Ok(sprite)
     }
 
     fn show_hit_chance_label(&mut self, state: &State, attacker_id: Id, target_id: Id) -> ZResult {
@@ -494,16 +942,46 @@ impl BattleView {
     }
 }
 
-fn make_action_show_tile(state: &State, view: &BattleView, at: PosHex) -> ZResult<Box<dyn Action>> {
+pub fn make_action_show_tile(
+    view: &BattleView,
+    at: PosHex,
+    tile: TileType,
+) -> ZResult<Box<dyn Action>> {
     let screen_pos = hex_to_point(view.tile_size(), at);
-    let texture = match state.map().tile(at) {
-        TileType::Plain => textures().map.tile,
-        TileType::Rocks => textures().map.tile_rocks,
-    };
     let size = view.tile_size() * 2.0 * geom::FLATNESS_COEFFICIENT;
+    let mut sprite = tile_sprite(tile, size);
+    sprite.set_pos(screen_pos);
+    Ok(action::Show::new(&view.layers().bg, &sprite).boxed())
+}
+
+/// A centered sprite of the terrain, shared with the editor.
+pub fn tile_sprite(tile: TileType, size: f32) -> Sprite {
+    let texture = match tile {
+        TileType::Plain | TileType::Water => textures().map.tile,
+        TileType::Rocks | TileType::Thicket => textures().map.tile_rocks,
+    };
     let mut sprite = Sprite::from_texture(texture, size);
     sprite.set_centered(true);
+    match tile {
+        TileType::Water => sprite.set_color(TILE_COLOR_WATER),
+        TileType::Thicket => sprite.set_color(TILE_COLOR_THICKET),
+        TileType::Plain | TileType::Rocks => {}
+    }
+    sprite
+}
+
+/// Marks a tile that the player has to reach or a tile of some scenario region.
+fn make_action_marked_tile(
+    view: &BattleView,
+    at: PosHex,
+    color: Color,
+) -> ZResult<Box<dyn Action>> {
+    let screen_pos = hex_to_point(view.tile_size(), at);
+    let size = view.tile_size() * 2.0 * geom::FLATNESS_COEFFICIENT;
+    let mut sprite = Sprite::from_texture(textures().map.white_hex, size);
+    sprite.set_centered(true);
     sprite.set_pos(screen_pos);
+    sprite.set_color(color);
     Ok(action::Show::new(&view.layers().bg, &sprite).boxed())
 }
 
@@ -521,12 +999,25 @@ fn make_action_grass(view: &BattleView, at: PosHex) -> ZResult<Box<dyn Action>>
 pub fn make_action_create_map(state: &State, view: &BattleView) -> ZResult<Box<dyn Action>> {
     let mut actions = Vec::new();
     for hex_pos in state.map().iter() {
-        actions.push(make_action_show_tile(state, view, hex_pos)?);
+        let tile = state.map().tile(hex_pos);
+        actions.push(make_action_show_tile(view, hex_pos, tile)?);
         let is_free = state::is_tile_completely_free(state, hex_pos);
         let is_plain = state.map().tile(hex_pos) == TileType::Plain;
         if is_free && is_plain && roll_dice(0, 10) < 2 {
             actions.push(make_action_grass(view, hex_pos)?);
         }
     }
+    for objective in &state.scenario().objectives {
+        if let Objective::ReachHex(pos) | Objective::Escort { exit: pos, .. } = *objective {
+            actions.push(make_action_marked_tile(view, pos, TILE_COLOR_OBJECTIVE)?);
+        }
+    }
+    for region in &state.scenario().regions {
+        if region.is_marked {
+            for &pos in &region.tiles {
+                actions.push(make_action_marked_tile(view, pos, TILE_COLOR_REGION)?);
+            }
+        }
+    }
     Ok(visualize::seq(actions))
 }
@@ -6,25 +6,28 @@ use mq::{
     math::{Mat2, Vec2},
     texture::Texture2D,
 };
-use zscene::{action, Action, Boxed, Facing, Sprite};
+use zscene::{action, Action, Boxed, Sprite};
 
 use crate::{
     assets,
     core::{
         battle::{
             ability::Ability,
+            command::JokerUse,
             component::{Component, WeaponType},
             effect::{self, Effect},
             event::{self, ActiveEvent, Event},
             execute::{hit_chance, ApplyPhase},
+            scenario::ScriptAction,
             state, Id, PlayerId, State, Turns,
         },
-        map::PosHex,
+        map::{self, PosHex},
         utils::roll_dice,
     },
     geom,
-    screen::battle::view::BattleView,
-    utils::time_s,
+    i18n::tr,
+    screen::battle::view::{self, BattleView},
+    utils::{time_s, PORTRAIT_TINTS},
     ZResult,
 };
 
@@ -37,6 +40,7 @@ pub mod color {
     pub const JOKERS: Color = Color::new(1.0, 1.0, 1.0, 1.0);
     pub const ATTACKS: Color = Color::new(1.0, 0.0, 0.0, 1.0);
     pub const MOVES: Color = Color::new(0.2, 0.2, 1.0, 1.0);
+    pub const STAMINA: Color = Color::new(1.0, 0.6, 0.0, 1.0);
 }
 
 const BLOOD_SPRITE_DURATION_TURNS: Turns = Turns(6);
@@ -437,18 +441,25 @@ fn generate_brief_obj_info(
     point += Vec2::new(view.tile_size() * 0.8, -view.tile_size() * 1.6);
     let mut dots = Vec::new();
     let base = point;
-    let rows: &[&[_]] = &[
-        &[
+    let mut rows = vec![
+        vec![
             (color::STRENGTH, strength.strength.0),
             (color::DAMAGE, damage),
             (color::ARMOR, armor.0),
         ],
-        &[(color::JOKERS, agent.jokers.0)],
-        &[(color::ATTACKS, agent.attacks.0)],
-        &[(color::MOVES, agent.moves.0)],
+        vec![(color::JOKERS, agent.jokers.0)],
+        vec![(color::ATTACKS, agent.attacks.0)],
+        vec![(color::MOVES, agent.moves.0)],
     ];
+    if let Some(stamina) = parts.stamina.get_opt(id) {
+        let spent = stamina.base_stamina - stamina.stamina;
+        rows.push(vec![
+            (color::STAMINA, stamina.stamina),
+            (color::DAMAGE, spent),
+        ]);
+    }
     let actual_dot_size_k = 0.8;
-    for &row in rows {
+    for row in &rows {
         for &(color, n) in row {
             for _ in 0..n {
                 dots.push((color, point));
@@ -502,7 +513,9 @@ fn refresh_brief_agent_info(
     if view.agent_info_check(id) {
         actions.push(remove_brief_agent_info(view, id)?);
     }
-    if state.parts().agent.get_opt(id).is_some() {
+    // The dots would give the hidden enemies away.
+    let is_shown = !state::is_hidden_from(state, id, PlayerId(0));
+    if state.parts().agent.get_opt(id).is_some() && is_shown {
         actions.push(generate_brief_obj_info(state, view, id)?);
     }
     Ok(seq(actions))
@@ -521,8 +534,62 @@ pub fn visualize(
     }
 }
 
+/// Zoom of the cinematic camera when it follows an AI agent.
+const CAMERA_ZOOM_AGENT: f32 = 1.4;
+
+/// Zoom of the cinematic camera when it shows a blast.
+const CAMERA_ZOOM_BLAST: f32 = 1.8;
+
+fn is_explosion(ability: Ability) -> bool {
+    matches!(
+        ability,
+        Ability::ExplodePush
+            | Ability::ExplodeDamage
+            | Ability::ExplodeFire
+            | Ability::ExplodePoison
+    )
+}
+
+/// Frames the camera on the interesting part of the event, if there's any.
+fn focus_camera(state: &State, view: &BattleView, event: &ActiveEvent) -> Box<dyn Action> {
+    let is_ai_turn = state.player_id() != PlayerId(0);
+    let pos = |id| view.hex_to_point(state.parts().pos.get(id).0);
+    let focus = match *event {
+        ActiveEvent::UseAbility(ref ev) if is_explosion(ev.ability) => {
+            Some((view.hex_to_point(ev.pos), CAMERA_ZOOM_BLAST))
+        }
+        ActiveEvent::UseAbility(ref ev) if is_ai_turn => {
+            let between = (pos(ev.id) + view.hex_to_point(ev.pos)) / 2.0;
+            Some((between, CAMERA_ZOOM_AGENT))
+        }
+        ActiveEvent::Attack(ref ev) if is_ai_turn => {
+            let between = (pos(ev.attacker_id) + pos(ev.target_id)) / 2.0;
+            Some((between, CAMERA_ZOOM_AGENT))
+        }
+        ActiveEvent::MoveTo(ref ev) if is_ai_turn => {
+            let between =
+                (view.hex_to_point(ev.path.from()) + view.hex_to_point(ev.path.to())) / 2.0;
+            Some((between, CAMERA_ZOOM_AGENT))
+        }
+        ActiveEvent::BeginTurn(event::BeginTurn {
+            player_id: PlayerId(0),
+        }) => return view.reset_camera(),
+        _ => None,
+    };
+    match focus {
+        Some((pos, zoom)) if view.is_cinematic_camera() => seq([
+            view.focus_camera(pos, zoom),
+            action::Sleep::new(time_s(0.3)).boxed(),
+        ]),
+        _ => action::Empty::new().boxed(),
+    }
+}
+
 fn visualize_pre(state: &State, view: &mut BattleView, event: &Event) -> ZResult<Box<dyn Action>> {
-    let mut actions = vec![visualize_event(state, view, &event.active_event)?];
+    let mut actions = vec![
+        focus_camera(state, view, &event.active_event),
+        visualize_event(state, view, &event.active_event)?,
+    ];
     for &(id, ref effects) in &event.instant_effects {
         for effect in effects {
             actions.push(visualize_instant_effect(state, view, id, effect)?);
@@ -547,9 +614,25 @@ fn visualize_post(state: &State, view: &mut BattleView, event: &Event) -> ZResul
     for &(id, _) in &event.timed_effects {
         actions.push(refresh_brief_agent_info(state, view, id)?);
     }
+    // The riders of the killed mounts are back on the map.
+    let ids: Vec<_> = state.parts().agent.ids().collect();
+    for id in ids {
+        if !view.agent_info_check(id) && !state::is_hidden_from(state, id, PlayerId(0)) {
+            actions.push(generate_brief_obj_info(state, view, id)?);
+        }
+    }
+    actions.push(view.sync_decals(state));
     Ok(seq(actions))
 }
 
+/// The banners are updated when the whole turn is shown, see `BattleView::show_control_points`.
+fn visualize_event_capture(
+    view: &mut BattleView,
+    event: &event::Capture,
+) -> ZResult<Box<dyn Action>> {
+    message(view, event.pos, &tr!("captured"))
+}
+
 fn visualize_event(
     state: &State,
     view: &mut BattleView,
@@ -565,11 +648,90 @@ fn visualize_event(
         ActiveEvent::BeginTurn(ref ev) => visualize_event_begin_turn(state, view, ev)?,
         ActiveEvent::EffectTick(ref ev) => visualize_event_effect_tick(state, view, ev)?,
         ActiveEvent::EffectEnd(ref ev) => visualize_event_effect_end(state, view, ev)?,
+        ActiveEvent::Unsummon(_)
+        | ActiveEvent::Reveal(_)
+        | ActiveEvent::MoraleLoss(_)
+        | ActiveEvent::LeaveCorpse(_)
+        | ActiveEvent::Injury(_)
+        | ActiveEvent::Reaction(_)
+        | ActiveEvent::Aura(_) => action::Empty::new().boxed(),
         ActiveEvent::UseAbility(ref ev) => visualize_event_use_ability(state, view, ev)?,
+        ActiveEvent::ConvertJoker(ref ev) => visualize_event_convert_joker(state, view, ev)?,
+        ActiveEvent::Mount(ref ev) => visualize_event_mount(state, view, ev)?,
+        ActiveEvent::Dismount(ref ev) => visualize_event_dismount(state, view, ev)?,
+        ActiveEvent::Capture(ref ev) => visualize_event_capture(view, ev)?,
+        ActiveEvent::SuddenDeath(ref ev) => visualize_event_sudden_death(view, ev)?,
+        ActiveEvent::ScriptTriggered(ref ev) => visualize_event_script_triggered(state, view, ev)?,
+        ActiveEvent::SetTiles(ref ev) => visualize_event_set_tiles(view, ev)?,
+        ActiveEvent::BeginBossPhase(ref ev) => visualize_event_begin_boss_phase(state, view, ev)?,
     };
     Ok(action)
 }
 
+fn visualize_event_convert_joker(
+    state: &State,
+    view: &mut BattleView,
+    event: &event::ConvertJoker,
+) -> ZResult<Box<dyn Action>> {
+    let pos = state.parts().pos.get(event.id).0;
+    let text = match event.into {
+        JokerUse::Move => tr!("joker: +1 move"),
+        JokerUse::Attack => tr!("joker: +1 attack"),
+    };
+    message(view, pos, &text)
+}
+
+fn visualize_event_mount(
+    state: &State,
+    view: &mut BattleView,
+    event: &event::Mount,
+) -> ZResult<Box<dyn Action>> {
+    let from = state.parts().pos.get(event.id).0;
+    let to = state.parts().pos.get(event.mount_id).0;
+    let diff = view.hex_to_point(to) - view.hex_to_point(from);
+    Ok(seq([
+        move_object_with_shadow(view, event.id, diff, time_s(0.3)),
+        message(view, to, &tr!("mounted"))?,
+        vanish_with_duration(view, event.id, time_s(0.3)),
+    ]))
+}
+
+/// The rider has no sprite while it's mounted, so it's shown like a created object.
+fn show_rider(
+    state: &State,
+    view: &mut BattleView,
+    mount_id: Id,
+    pos: PosHex,
+) -> ZResult<Box<dyn Action>> {
+    let rider = state::rider(state, mount_id).expect("No rider");
+    let prototype = rider
+        .components
+        .iter()
+        .find_map(|component| match component {
+            Component::Meta(meta) => Some(meta.name.clone()),
+            _ => None,
+        })
+        .expect("The rider has no meta");
+    let effect = effect::Create {
+        pos,
+        prototype,
+        components: rider.components.clone(),
+        is_teleported: false,
+    };
+    visualize_effect_create(state, view, rider.id, &effect)
+}
+
+fn visualize_event_dismount(
+    state: &State,
+    view: &mut BattleView,
+    event: &event::Dismount,
+) -> ZResult<Box<dyn Action>> {
+    Ok(seq([
+        show_rider(state, view, event.mount_id, event.pos)?,
+        message(view, event.pos, &tr!("dismounted"))?,
+    ]))
+}
+
 fn visualize_event_move_to(
     _: &State,
     view: &mut BattleView,
@@ -578,7 +740,7 @@ fn visualize_event_move_to(
     let sprite = view.id_to_sprite(event.id).clone();
     let mut actions = Vec::new();
     if let [pos] = event.path.tiles() {
-        actions.push(message(view, *pos, "move interrupted")?);
+        actions.push(message(view, *pos, &tr!("move interrupted"))?);
     }
     for step in event.path.steps() {
         let from = view.hex_to_point(step.from);
@@ -632,8 +794,13 @@ fn visualize_event_attack(
     let chances = hit_chance(state, id, event.target_id);
     let attack_msg = format!("{}%", chances.1 * 10);
     actions.push(attack_message(view, from, &attack_msg)?);
-    if event.mode == event::AttackMode::Reactive {
-        actions.push(message(view, map_from, "reaction")?);
+    match event.mode {
+        event::AttackMode::Active => {}
+        event::AttackMode::Reactive => actions.push(message(view, map_from, &tr!("reaction"))?),
+        event::AttackMode::Counter => actions.push(message(view, map_from, &tr!("counter"))?),
+    }
+    if state::is_backstab(state, id, event.target_id) {
+        actions.push(message(view, map_to, &tr!("backstab"))?);
     }
     let time_to = time_s(TIME_LUNGE_TO);
     let time_from = time_s(TIME_LUNGE_FROM);
@@ -664,13 +831,14 @@ fn visualize_event_end_battle(
     event: &event::EndBattle,
 ) -> ZResult<Box<dyn Action>> {
     let text = match event.result.winner_id {
-        PlayerId(0) => "YOU WON!",
-        PlayerId(1) => "YOU LOSE!",
+        _ if event.result.is_retreat => tr!("YOU RETREATED!"),
+        PlayerId(0) => tr!("YOU WON!"),
+        PlayerId(1) => tr!("YOU LOSE!"),
         _ => unreachable!(),
     };
     Ok(seq([
         action::Sleep::new(time_s(1.0)).boxed(),
-        announce(view, text, time_s(4.0))?,
+        announce(view, &text, time_s(4.0))?,
         action::Sleep::new(time_s(1.0)).boxed(),
     ]))
 }
@@ -687,16 +855,78 @@ fn visualize_event_end_turn(
 }
 
 fn visualize_event_begin_turn(
-    _: &State,
+    state: &State,
     view: &mut BattleView,
     event: &event::BeginTurn,
 ) -> ZResult<Box<dyn Action>> {
-    let text = match event.player_id {
-        PlayerId(0) => "YOUR TURN",
-        PlayerId(1) => "ENEMY TURN",
-        _ => unreachable!(),
+    let text = if event.player_id == PlayerId(0) {
+        tr!("YOUR TURN")
+    } else if state::is_on_human_side(state, event.player_id) {
+        tr!("ALLIES' TURN")
+    } else {
+        tr!("ENEMY TURN")
     };
-    announce(view, text, time_s(1.5))
+    announce(view, &text, time_s(1.5))
+}
+
+fn visualize_event_sudden_death(
+    view: &mut BattleView,
+    event: &event::SuddenDeath,
+) -> ZResult<Box<dyn Action>> {
+    if event.rounds_over == 0 {
+        announce(view, &tr!("SUDDEN DEATH"), time_s(1.5))
+    } else {
+        Ok(action::Empty::new().boxed())
+    }
+}
+
+fn visualize_event_script_triggered(
+    state: &State,
+    view: &mut BattleView,
+    event: &event::ScriptTriggered,
+) -> ZResult<Box<dyn Action>> {
+    let scenario = state.scenario();
+    let script = &scenario.scripts[event.index];
+    let pos = script
+        .condition
+        .region()
+        .and_then(|name| scenario.region(name))
+        .and_then(|region| region.tiles.first().cloned());
+    let mut actions = Vec::new();
+    for action in &script.actions {
+        match action {
+            ScriptAction::Message(text) => match pos {
+                Some(pos) => actions.push(message(view, pos, &tr!(text))?),
+                None => actions.push(announce(view, &tr!(text), time_s(1.5))?),
+            },
+            // The dialogue overlay shows them after the current animations.
+            ScriptAction::Dialogue(remarks) => view.queue_remarks(remarks),
+            _ => {}
+        }
+    }
+    Ok(seq(actions))
+}
+
+fn visualize_event_begin_boss_phase(
+    state: &State,
+    view: &mut BattleView,
+    event: &event::BeginBossPhase,
+) -> ZResult<Box<dyn Action>> {
+    let pos = state.parts().pos.get(event.id).0;
+    // The first phase is the boss' initial state.
+    let text = tr!("phase {phase}!", phase = event.phase + 2);
+    message(view, pos, &text)
+}
+
+fn visualize_event_set_tiles(
+    view: &mut BattleView,
+    event: &event::SetTiles,
+) -> ZResult<Box<dyn Action>> {
+    let mut actions = Vec::new();
+    for &pos in &event.tiles {
+        actions.push(view::make_action_show_tile(view, pos, event.tile)?);
+    }
+    Ok(seq(actions))
 }
 
 fn visualize_event_use_ability_jump(
@@ -822,6 +1052,17 @@ fn visualize_event_use_ability_summon(
     ]))
 }
 
+fn visualize_event_use_ability_raise(
+    _: &State,
+    view: &mut BattleView,
+    event: &event::UseAbility,
+) -> ZResult<Box<dyn Action>> {
+    let color = [0.6, 1.0, 0.6, 0.7].into();
+    let scale = 1.5;
+    let time = time_s(TIME_DEFAULT_FLARE);
+    show_flare_scale_time(view, event.pos, color, scale, time)
+}
+
 fn visualize_event_use_ability_bloodlust(
     _: &State,
     view: &mut BattleView,
@@ -849,10 +1090,13 @@ fn visualize_event_use_ability(
         Ability::Jump | Ability::LongJump => visualize_event_use_ability_jump(state, view, event)?,
         Ability::Dash => visualize_event_use_ability_dash(state, view, event)?,
         Ability::Summon => visualize_event_use_ability_summon(state, view, event)?,
+        Ability::Raise => visualize_event_use_ability_raise(state, view, event)?,
         Ability::Bloodlust => visualize_event_use_ability_bloodlust(state, view, event)?,
-        Ability::Heal | Ability::GreatHeal => visualize_event_use_ability_heal(state, view, event)?,
+        Ability::Heal => visualize_event_use_ability_heal(state, view, event)?,
         Ability::Rage => visualize_event_use_ability_rage(state, view, event)?,
-        Ability::Knockback => visualize_event_use_ability_knockback(state, view, event)?,
+        Ability::Knockback | Ability::Shove => {
+            visualize_event_use_ability_knockback(state, view, event)?
+        }
         Ability::Club => visualize_event_use_ability_club(state, view, event)?,
         Ability::ExplodePush
         | Ability::ExplodeDamage
@@ -866,7 +1110,7 @@ fn visualize_event_use_ability(
         _ => action::Empty::new().boxed(),
     };
     let pos = state.parts().pos.get(event.id).0;
-    let text = event.ability.title();
+    let text = tr!(&event.ability.title());
     let mut actions = Vec::new();
     if let Some(facing) = geom::Facing::from_positions(view.tile_size(), pos, event.pos) {
         let sprite = view.id_to_sprite(event.id).clone();
@@ -891,6 +1135,66 @@ fn visualize_event_effect_tick(
         effect::Lasting::Poison => show_flare(view, pos, [0.0, 0.8, 0.0, 0.7].into()),
         effect::Lasting::Stun => show_flare(view, pos, [1.0, 1.0, 1.0, 0.7].into()),
         effect::Lasting::Bloodlust => show_flare(view, pos, [1.0, 0.0, 0.0, 0.5].into()),
+        effect::Lasting::Phased => show_flare(view, pos, [0.6, 0.6, 1.0, 0.5].into()),
+        effect::Lasting::Hidden => show_flare(view, pos, [0.2, 0.3, 0.2, 0.5].into()),
+        // The injury doesn't change, there's nothing to show every turn.
+        effect::Lasting::Injured => Ok(action::Empty::new().boxed()),
+    }
+}
+
+/// The phased agents are half transparent till the effect is over.
+fn fade_phased(view: &mut BattleView, id: Id, is_phased: bool) -> Box<dyn Action> {
+    let sprite = view.id_to_sprite(id).clone();
+    let a = if is_phased { 0.5 } else { 1.0 };
+    let color = Color {
+        a,
+        ..sprite.color()
+    };
+    action::ChangeColorTo::new(&sprite, color, time_s(0.3)).boxed()
+}
+
+/// The player sees their own hidden agents half transparent,
+/// the hidden enemies aren't shown at all, their shadows included.
+fn fade_hidden(state: &State, view: &mut BattleView, id: Id, is_hidden: bool) -> Box<dyn Action> {
+    let owner = state.parts().belongs_to.get(id).0;
+    let is_enemy = !state::are_allies(state, PlayerId(0), owner);
+    let a = match (is_hidden, is_enemy) {
+        (false, _) => 1.0,
+        (true, false) => 0.5,
+        (true, true) => 0.0,
+    };
+    let sprite = view.id_to_sprite(id).clone();
+    let sprite_shadow = view.id_to_shadow_sprite(id).clone();
+    let color = Color {
+        a,
+        ..sprite.color()
+    };
+    let color_shadow = Color {
+        a: if is_enemy { a } else { 1.0 },
+        ..sprite_shadow.color()
+    };
+    let time = time_s(0.3);
+    seq([
+        fork(action::ChangeColorTo::new(&sprite_shadow, color_shadow, time).boxed()),
+        action::ChangeColorTo::new(&sprite, color, time).boxed(),
+    ])
+}
+
+/// Shows or hides the agent the way the lasting effect wants it to look.
+fn fade_by_effect(
+    state: &State,
+    view: &mut BattleView,
+    id: Id,
+    effect: effect::Lasting,
+    is_on: bool,
+) -> Option<Box<dyn Action>> {
+    match effect {
+        effect::Lasting::Phased => Some(fade_phased(view, id, is_on)),
+        effect::Lasting::Hidden => Some(fade_hidden(state, view, id, is_on)),
+        effect::Lasting::Poison
+        | effect::Lasting::Stun
+        | effect::Lasting::Bloodlust
+        | effect::Lasting::Injured => None,
     }
 }
 
@@ -900,8 +1204,12 @@ fn visualize_event_effect_end(
     event: &event::EffectEnd,
 ) -> ZResult<Box<dyn Action>> {
     let pos = state.parts().pos.get(event.id).0;
-    let s = event.effect.title();
-    message(view, pos, &format!("[{}] ended", s))
+    let s = tr!(event.effect.title());
+    let action_message = message(view, pos, &tr!("[{effect}] ended", effect = s))?;
+    if let Some(action_fade) = fade_by_effect(state, view, event.id, event.effect, false) {
+        return Ok(seq([fork(action_fade), action_message]));
+    }
+    Ok(action_message)
 }
 
 fn visualize_lasting_effect(
@@ -915,12 +1223,17 @@ fn visualize_lasting_effect(
         effect::Lasting::Poison => show_flare(view, pos, [0.0, 0.8, 0.0, 0.7].into())?,
         effect::Lasting::Stun => show_flare(view, pos, [1.0, 1.0, 1.0, 0.7].into())?,
         effect::Lasting::Bloodlust => show_flare(view, pos, [1.0, 0.0, 0.0, 0.5].into())?,
+        effect::Lasting::Phased => show_flare(view, pos, [0.6, 0.6, 1.0, 0.5].into())?,
+        effect::Lasting::Hidden => show_flare(view, pos, [0.2, 0.3, 0.2, 0.5].into())?,
+        effect::Lasting::Injured => show_flare(view, pos, [0.6, 0.0, 0.0, 0.6].into())?,
     };
-    let s = timed_effect.effect.title();
-    Ok(seq([
-        action_flare,
-        message(view, pos, &format!("[{}]", s))?,
-    ]))
+    let s = tr!(timed_effect.effect.title());
+    let mut actions = vec![action_flare];
+    if let Some(action_fade) = fade_by_effect(state, view, target_id, timed_effect.effect, true) {
+        actions.push(fork(action_fade));
+    }
+    actions.push(message(view, pos, &format!("[{}]", s))?);
+    Ok(seq(actions))
 }
 
 fn visualize_instant_effect(
@@ -942,12 +1255,14 @@ fn visualize_instant_effect(
         Effect::Throw(ref e) => visualize_effect_throw(state, view, target_id, e)?,
         Effect::Dodge(ref e) => visualize_effect_dodge(state, view, target_id, e)?,
         Effect::Bloodlust => action::Empty.boxed(),
+        Effect::Demoralize(ref e) => visualize_effect_demoralize(state, view, target_id, e)?,
+        Effect::Cleanse(ref e) => visualize_effect_cleanse(state, view, target_id, e)?,
     };
     Ok(action)
 }
 
 fn visualize_effect_create(
-    _: &State,
+    state: &State,
     view: &mut BattleView,
     target_id: Id,
     effect: &effect::Create,
@@ -956,19 +1271,34 @@ fn visualize_effect_create(
     let z = hex_pos_to_z(effect.pos) + info.sub_tile_z;
     let point = view.hex_to_point(effect.pos);
     let color = Color::new(1.0, 1.0, 1.0, 1.0);
+    let mut tint = color;
+    for component in &effect.components {
+        if let Component::Persona(persona) = component {
+            tint = PORTRAIT_TINTS[persona.portrait % PORTRAIT_TINTS.len()];
+        }
+    }
     let size = view.tile_size() * 2.0;
     let sprite_object = {
         let mut sprite = view.object_sprite(&effect.prototype);
-        sprite.set_color(Color { a: 0.0, ..color });
+        sprite.set_color(Color { a: 0.0, ..tint });
         sprite.set_pos(point);
-        // Turn enemies left.
+        let mut owner = None;
+        let mut dir = None;
         for component in &effect.components {
-            if let Component::BelongsTo(belongs_to) = component {
-                if belongs_to.0 == PlayerId(1) {
-                    sprite.set_facing(Facing::Left);
-                }
+            match component {
+                Component::BelongsTo(belongs_to) => owner = Some(belongs_to.0),
+                Component::Facing(facing) => dir = Some(facing.0),
+                _ => {}
             }
         }
+        let is_agent = effect
+            .components
+            .iter()
+            .any(|component| matches!(component, Component::Agent(_)));
+        if is_agent {
+            let dir = dir.unwrap_or_else(|| state::initial_facing(state, owner));
+            sprite.set_facing(geom::Facing::from_dir(dir).to_scene_facing());
+        }
         sprite
     };
     let sprite_shadow = {
@@ -996,7 +1326,7 @@ fn visualize_effect_create(
     actions.push(action::Show::new(&view.layers().objects, &sprite_object).boxed());
     actions.push(action_set_z(&view.layers().objects, &sprite_object, z));
     actions.push(fork(action_change_shadow_color));
-    actions.push(action::ChangeColorTo::new(&sprite_object, color, time_appear).boxed());
+    actions.push(action::ChangeColorTo::new(&sprite_object, tint, time_appear).boxed());
     Ok(fork(seq(actions)))
 }
 
@@ -1008,9 +1338,27 @@ fn visualize_effect_kill(
 ) -> ZResult<Box<dyn Action>> {
     let particles_count = 6;
     let pos = state.parts().pos.get(target_id).0;
+    // The splash is shown by the push itself, see `push_message`.
+    if effect.is_drowned {
+        return Ok(fork(vanish_with_duration(view, target_id, time_s(1.0))));
+    }
+    if state::rider_id(state, target_id).is_some() {
+        let action_show_rider = show_rider(state, view, target_id, pos)?;
+        return Ok(seq([
+            fork(vanish_with_duration(view, target_id, time_s(1.5))),
+            message(view, pos, &tr!("killed"))?,
+            action_show_rider,
+        ]));
+    }
+    if state::is_destructible(state, target_id) {
+        return Ok(fork(seq([
+            message(view, pos, &tr!("destroyed"))?,
+            vanish_with_duration(view, target_id, time_s(1.0)),
+        ])));
+    }
     Ok(fork(seq([
         show_blood_particles(view, pos, effect.attacker_pos, particles_count)?,
-        message(view, pos, "killed")?,
+        message(view, pos, &tr!("killed"))?,
         fork(show_blood_spot(view, pos)?),
         vanish_with_duration(view, target_id, time_s(1.5)),
     ])))
@@ -1035,7 +1383,7 @@ fn visualize_effect_heal(
     effect: &effect::Heal,
 ) -> ZResult<Box<dyn Action>> {
     let pos = state.parts().pos.get(target_id).0;
-    let s = format!("healed +{}", effect.strength.0);
+    let s = tr!("healed +{strength}", strength = effect.strength.0);
     Ok(seq([
         action::Sleep::new(time_s(0.5)).boxed(),
         message(view, pos, &s)?,
@@ -1043,19 +1391,54 @@ fn visualize_effect_heal(
     ]))
 }
 
+fn visualize_effect_demoralize(
+    state: &State,
+    view: &mut BattleView,
+    target_id: Id,
+    effect: &effect::Demoralize,
+) -> ZResult<Box<dyn Action>> {
+    let pos = state.parts().pos.get(target_id).0;
+    let morale = state.parts().morale.get(target_id).morale;
+    let s = if morale - effect.morale <= 0 {
+        tr!("routed!")
+    } else {
+        tr!("-{morale} morale", morale = effect.morale)
+    };
+    message(view, pos, &s)
+}
+
+fn visualize_effect_cleanse(
+    state: &State,
+    view: &mut BattleView,
+    target_id: Id,
+    effect: &effect::Cleanse,
+) -> ZResult<Box<dyn Action>> {
+    let pos = state.parts().pos.get(target_id).0;
+    let s = tr!(effect.effect.title());
+    let action_message = message(view, pos, &tr!("[{effect}] removed", effect = s))?;
+    if let Some(action_fade) = fade_by_effect(state, view, target_id, effect.effect, false) {
+        return Ok(seq([fork(action_fade), action_message]));
+    }
+    Ok(action_message)
+}
+
 fn wound_msg(effect: &effect::Wound) -> String {
     let damage = effect.damage.0;
     let armor_break = effect.armor_break.0;
     if damage > 0 || armor_break > 0 {
         if armor_break == 0 {
-            format!("-{} strength", damage)
+            tr!("-{damage} strength", damage = damage)
         } else if damage == 0 {
-            format!("-{} armor", armor_break)
+            tr!("-{armor} armor", armor = armor_break)
         } else {
-            format!("-{} strength & {} armor", damage, armor_break)
+            tr!(
+                "-{damage} strength & {armor} armor",
+                damage = damage,
+                armor = armor_break
+            )
         }
     } else {
-        "no damage".into()
+        tr!("no damage")
     }
 }
 
@@ -1074,7 +1457,8 @@ fn visualize_effect_wound(
     let time = time_s(0.2);
     let mut actions = Vec::new();
     let msg = wound_msg(effect);
-    if effect.damage.0 > 0 || effect.armor_break.0 > 0 {
+    let is_destructible = state::is_destructible(state, id);
+    if (effect.damage.0 > 0 || effect.armor_break.0 > 0) && !is_destructible {
         let count = effect.damage.0 * 3;
         let from = effect.attacker_pos;
         actions.push(show_blood_particles(view, pos, from, count)?);
@@ -1085,14 +1469,23 @@ fn visualize_effect_wound(
     Ok(fork(seq(actions)))
 }
 
+/// The pushed object is about to drown if there's water at `to`.
+fn push_message(state: &State, id: Id, to: PosHex, message: String) -> String {
+    if state::is_drowning_at(state, id, to) {
+        tr!("splash!")
+    } else {
+        message
+    }
+}
+
 fn visualize_effect_knockback(
-    _: &State,
+    state: &State,
     view: &mut BattleView,
     target_id: Id,
     effect: &effect::Knockback,
 ) -> ZResult<Box<dyn Action>> {
     if effect.from == effect.to {
-        return message(view, effect.from, "Resisted knockback");
+        return message(view, effect.from, &tr!("Resisted knockback"));
     }
     let sprite = view.id_to_sprite(target_id).clone();
     let z = hex_pos_to_z(effect.to);
@@ -1101,20 +1494,24 @@ fn visualize_effect_knockback(
     let diff = to - from;
     let time = time_s(0.15);
     Ok(fork(seq([
-        message(view, effect.to, "bump")?,
+        message(
+            view,
+            effect.to,
+            &push_message(state, target_id, effect.to, tr!("bump")),
+        )?,
         action_set_z(&view.layers().objects, &sprite, z),
         move_object_with_shadow(view, target_id, diff, time),
     ])))
 }
 
 fn visualize_effect_fly_off(
-    _: &State,
+    state: &State,
     view: &mut BattleView,
     target_id: Id,
     effect: &effect::FlyOff,
 ) -> ZResult<Box<dyn Action>> {
     if effect.from == effect.to {
-        return message(view, effect.from, "Resisted fly off");
+        return message(view, effect.from, &tr!("Resisted fly off"));
     }
     let sprite_object = view.id_to_sprite(target_id).clone();
     let sprite_shadow = view.id_to_shadow_sprite(target_id).clone();
@@ -1130,7 +1527,11 @@ fn visualize_effect_fly_off(
         fork(action_move_shadow),
         action_set_z(&view.layers().objects, &sprite_object, z),
         action_main_move,
-        message(view, effect.to, "fly off")?,
+        message(
+            view,
+            effect.to,
+            &push_message(state, target_id, effect.to, tr!("fly off")),
+        )?,
         action_dust,
     ])))
 }
@@ -1147,6 +1548,14 @@ fn visualize_effect_throw(
     let from = view.hex_to_point(effect.from);
     let to = view.hex_to_point(effect.to);
     let diff = to - from;
+    if effect.is_rolled {
+        let tiles = map::distance_hex(effect.from, effect.to).0;
+        let time = time_s(0.15 * tiles as f32);
+        return Ok(seq([
+            move_object_with_shadow(view, target_id, diff, time),
+            action_set_z(&view.layers().objects, &sprite, z),
+        ]));
+    }
     let arc_move = arc_move(view, &sprite, diff);
     let action_move_shadow = action::MoveBy::new(&sprite_shadow, diff, arc_move.duration()).boxed();
     Ok(seq([
@@ -1166,7 +1575,7 @@ fn visualize_effect_dodge(
     let pos = state.parts().pos.get(target_id).0;
     let time_to = time_s(0.05);
     let time_from = time_s(0.3);
-    let mut actions = vec![message(view, pos, "dodged")?];
+    let mut actions = vec![message(view, pos, &tr!("dodged"))?];
     let point_a = view.hex_to_point(pos);
     let point_b = view.hex_to_point(effect.attacker_pos);
     let diff = (point_a - point_b).normalize() * view.tile_size() * 0.5;
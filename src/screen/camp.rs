@@ -0,0 +1,139 @@
+use std::{sync::mpsc::Sender, time::Duration};
+
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    core::campaign::{CampChoice, State},
+    i18n::{self, tr},
+    screen::{Screen, StackCommand},
+    utils, ZResult,
+};
+
+#[derive(Clone, Debug)]
+enum Message {
+    Back,
+    Choice(CampChoice, Option<usize>),
+}
+
+fn choice_title(state: &State, choice: &CampChoice) -> String {
+    match choice {
+        CampChoice::Heal => tr!("Heal"),
+        CampChoice::Train { xp } => tr!("Train (+{xp} xp)", xp = xp),
+        CampChoice::Scout => tr!("Scout the next battles"),
+        CampChoice::Craft(item) => {
+            let modifiers = utils::item_modifiers_text(state.item_info(item));
+            let item = i18n::name_title(&item.0);
+            tr!(
+                "Craft {item} ({modifiers})",
+                item = item,
+                modifiers = modifiers
+            )
+        }
+    }
+}
+
+fn is_useful(state: &State, choice: &CampChoice, agent_index: usize) -> bool {
+    match choice {
+        CampChoice::Heal => state.agents_progress()[agent_index].is_injured(),
+        _ => true,
+    }
+}
+
+/// Lets the player spend the camp actions.
+#[derive(Debug)]
+pub struct Camp {
+    gui: Gui<Message>,
+    sender: Sender<(CampChoice, Option<usize>)>,
+}
+
+impl Camp {
+    pub fn new(state: &State, sender: Sender<(CampChoice, Option<usize>)>) -> ZResult<Self> {
+        let font = assets::get().font;
+        let mut gui = ui::Gui::new();
+        let h = utils::line_heights().normal;
+        let text = |s: &str| ui::Drawable::text(s, font);
+        let camp = state.camp().expect("Not in a camp");
+        let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+        let title = ui::Label::new(text(&tr!("~~~ Camp ~~~")), h)?.stretchable(true);
+        layout.add(Box::new(title));
+        let actions = tr!("Actions left: {count}", count = camp.actions);
+        layout.add(Box::new(ui::Label::new(text(&actions), h)?));
+        layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+        for choice in &camp.choices {
+            let title = choice_title(state, choice);
+            let can_make = state.can_make_camp_choice(choice);
+            let mut targets = Vec::new();
+            if choice.needs_agent() {
+                let agents = state.agents().iter().zip(state.agents_progress());
+                for (agent_index, (agent_type, progress)) in agents.enumerate() {
+                    let name = utils::agent_name(progress.name.as_deref(), &agent_type.0);
+                    let title = format!("{}: {}", title, name);
+                    let is_active = can_make && is_useful(state, choice, agent_index);
+                    targets.push((title, Some(agent_index), is_active));
+                }
+            } else {
+                targets.push((title, None, can_make));
+            }
+            for (title, agent_index, is_active) in targets {
+                let message = Message::Choice(choice.clone(), agent_index);
+                let mut button = ui::Button::new(text(&title), h, gui.sender(), message)?;
+                button.set_active(is_active);
+                layout.add(Box::new(button));
+                layout.add(Box::new(ui::Spacer::new_vertical(h * 0.25)));
+            }
+        }
+        layout.add(Box::new(ui::Spacer::new_vertical(h * 0.25)));
+        {
+            let mut button = ui::Button::new(text(&tr!("back")), h, gui.sender(), Message::Back)?
+                .stretchable(true);
+            button.stretch(layout.rect().w / 3.0);
+            button.set_stretchable(false);
+            layout.add(Box::new(button));
+        }
+        layout.stretch_to_self();
+        let layout = utils::add_offsets_and_bg_big(layout)?;
+        let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+        gui.add(&ui::pack(layout), anchor);
+        Ok(Self { gui, sender })
+    }
+}
+
+impl Screen for Camp {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::Choice(choice, agent_index)) => {
+                self.sender
+                    .send((choice, agent_index))
+                    .expect("Can't report the camp choice");
+                Ok(StackCommand::Pop)
+            }
+            Some(Message::Back) => Ok(StackCommand::Pop),
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
@@ -3,33 +3,52 @@ use std::{
     time::Duration,
 };
 
-use heck::TitleCase;
 use log::info;
 use mq::{math::Vec2, text::Font};
 use ui::{self, Drawable, Gui, Widget};
 
 use crate::{
-    assets,
+    achievements, assets, campaign_save,
     core::{
+        achievements::Trigger,
         battle::{
+            ai,
             component::ObjType,
-            scenario::{self, BattleType},
-            state::BattleResult,
+            dice,
+            scenario::{BattleType, Scenario},
+            simulate,
+            state::{BattleResult, DeathCause},
             PlayerId,
         },
-        campaign::{Action, Mode, State},
+        campaign::{
+            AbilityUses, Action, CampChoice, FallenAgent, HallOfFame, Mode, Offer, Perk, Run,
+            SaveMode, State, Summary, TurningPoint,
+        },
+        item::{ItemType, Slot},
     },
+    i18n::{self, tr},
     screen::{self, Screen, StackCommand},
     utils, ZResult,
 };
 
+/// How many times the battle is simulated for the forecast of an auto-resolve.
+const AUTO_RESOLVE_FORECAST_BATTLES: i32 = 10;
+
 #[derive(Clone, Debug)]
 enum Message {
     Menu,
-    StartBattle,
+    Memorial,
+    WorldMap,
+    Shop,
+    Camp,
     AgentInfo(ObjType),
     UpgradeInfo { from: ObjType, to: ObjType },
     Action(Action),
+    SelectAgent(usize),
+    Rename(usize),
+    NextPortrait(usize),
+    Equip(ItemType),
+    Unequip { agent_index: usize, slot: Slot },
 }
 
 // The main line height of this screen.
@@ -48,34 +67,346 @@ fn basic_gui() -> ZResult<Gui<Message>> {
         let icon = Drawable::Texture(assets::get().textures.icons.main_menu);
         ui::Button::new(icon, h, gui.sender(), Message::Menu)?
     };
+    let button_memorial = {
+        let text = Drawable::text(tr!("memorial"), assets::get().font);
+        ui::Button::new(text, line_height(), gui.sender(), Message::Memorial)?
+    };
     let mut layout = ui::VLayout::new();
     layout.add(Box::new(button_menu));
+    layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
+    layout.add(Box::new(button_memorial));
     let anchor = ui::Anchor(ui::HAnchor::Left, ui::VAnchor::Top);
     gui.add(&ui::pack(layout), anchor);
     Ok(gui)
 }
 
+fn item_title(state: &State, item: &ItemType) -> String {
+    let modifiers = utils::item_modifiers_text(state.item_info(item));
+    format!("{} ({})", i18n::name_title(&item.0), modifiers)
+}
+
+fn death_cause_text(cause: &DeathCause) -> String {
+    match cause {
+        DeathCause::Attack(killer) => {
+            tr!("killed by {killer}", killer = i18n::name_title(&killer.0))
+        }
+        DeathCause::Ability(killer, ability) => tr!(
+            "killed by {killer} ({ability})",
+            killer = i18n::name_title(&killer.0),
+            ability = tr!(&ability.title()),
+        ),
+        DeathCause::PassiveAbility(ability) => {
+            tr!("died from {cause}", cause = tr!(&ability.title()))
+        }
+        DeathCause::Effect(effect) => tr!("died from {cause}", cause = tr!(effect.title())),
+        DeathCause::Unknown => tr!("fell in battle"),
+    }
+}
+
+fn memorial_line(fallen: &FallenAgent) -> String {
+    tr!(
+        "{name} (level {level}): {battles} battles, {kills} kills, {cause} at {node}",
+        name = utils::agent_name(fallen.name.as_deref(), &fallen.agent_type.0),
+        level = fallen.level + 1,
+        battles = fallen.battles_survived,
+        kills = fallen.kills,
+        cause = death_cause_text(&fallen.cause),
+        node = i18n::name_title(&fallen.node),
+    )
+}
+
+/// Enemies that wait in the next battles.
+fn scouting_report(state: &State) -> Vec<String> {
+    let mut lines = Vec::new();
+    for node_index in state.available_nodes() {
+        let node = &state.nodes()[node_index];
+        if node.camp.is_some() {
+            continue;
+        }
+        lines.push(format!("{}:", i18n::name_title(&node.name)));
+        let scenario = &node.scenario;
+        for group in &scenario.randomized_objects {
+            if group.owner == Some(PlayerId(1)) {
+                let typename = i18n::name_title(&group.typename.0);
+                lines.push(format!("- {} x {}", group.count, typename));
+            }
+        }
+        for object in &scenario.objects {
+            if object.owner == Some(PlayerId(1)) {
+                lines.push(format!("- {}", i18n::name_title(&object.typename.0)));
+            }
+        }
+    }
+    if lines.is_empty() {
+        lines.push(tr!("There are no more battles ahead."));
+    }
+    lines
+}
+
+fn turning_point_text(point: &TurningPoint) -> String {
+    match point {
+        TurningPoint::FirstLoss { node } => {
+            tr!(
+                "- {node}: the first of us fell",
+                node = i18n::name_title(node)
+            )
+        }
+        TurningPoint::CostlyVictory { node, casualties } => tr!(
+            "- {node}: a victory that cost {count} lives",
+            node = i18n::name_title(node),
+            count = casualties,
+        ),
+        TurningPoint::FlawlessVictory { node, kills } => tr!(
+            "- {node}: {count} enemies slain without a loss",
+            node = i18n::name_title(node),
+            count = kills,
+        ),
+        TurningPoint::Retreat { node } => {
+            tr!(
+                "- {node}: we fell back to fight again",
+                node = i18n::name_title(node)
+            )
+        }
+        TurningPoint::Defeat { node } => {
+            tr!(
+                "- {node}: the group was broken",
+                node = i18n::name_title(node)
+            )
+        }
+    }
+}
+
+/// A few templated sentences that retell the campaign.
+fn narrative(summary: &Summary) -> Vec<String> {
+    let mut lines = Vec::new();
+    let battles = match summary.battles {
+        1 => tr!("a single battle"),
+        n => tr!("{count} battles", count = n),
+    };
+    if summary.is_won {
+        lines.push(tr!(
+            "After {battles} the land is free of the imps.",
+            battles = battles
+        ));
+    } else {
+        lines.push(tr!(
+            "After {battles} the group was driven away.",
+            battles = battles
+        ));
+    }
+    if let Some(mvp) = &summary.mvp {
+        let name = utils::agent_name(mvp.name.as_deref(), &mvp.agent_type.0);
+        let line = if mvp.is_fallen {
+            tr!(
+                "{name} slew {count} foes but didn't live to see the end.",
+                name = name,
+                count = mvp.kills,
+            )
+        } else {
+            tr!(
+                "{name} slew {count} foes and lived to tell the tale.",
+                name = name,
+                count = mvp.kills,
+            )
+        };
+        lines.push(line);
+    }
+    if let Some(ability) = summary.favorite_ability {
+        lines.push(tr!(
+            "{ability} was the trick they trusted the most.",
+            ability = tr!(&ability.title()),
+        ));
+    }
+    match summary.casualties {
+        0 => lines.push(tr!("Everyone came back home.")),
+        1 => lines.push(tr!("One fighter never came back.")),
+        n => lines.push(tr!("{count} fighters never came back.", count = n)),
+    }
+    lines
+}
+
+/// How many of the best runs the final screen lists, the whole hall doesn't fit.
+const HALL_OF_FAME_LINES: usize = 5;
+
+/// Adds the run to the hall of fame, returns its place and the updated hall.
+#[cfg(not(target_arch = "wasm32"))]
+fn enter_hall_of_fame(run: Run) -> (Option<usize>, HallOfFame) {
+    let mut hall = crate::hall_of_fame::load();
+    let place = hall.add(run);
+    if place.is_some() {
+        crate::hall_of_fame::save(&hall);
+    }
+    (place, hall)
+}
+
+/// There are no files in browsers, the hall holds only the current run.
+#[cfg(target_arch = "wasm32")]
+fn enter_hall_of_fame(run: Run) -> (Option<usize>, HallOfFame) {
+    let mut hall = HallOfFame::default();
+    let place = hall.add(run);
+    (place, hall)
+}
+
+fn hall_of_fame_line(index: usize, run: &Run) -> String {
+    let result = if run.is_won {
+        tr!("won")
+    } else {
+        tr!("failed")
+    };
+    tr!(
+        "{place}. {score} points: {result}, {victories} victories, {kills} kills, {lost} lost",
+        place = index + 1,
+        score = run.score,
+        result = result,
+        victories = run.battles_won,
+        kills = run.kills,
+        lost = run.casualties,
+    )
+}
+
+fn hall_of_fame_lines(hall: &HallOfFame, place: Option<usize>) -> Vec<String> {
+    let mut lines = vec![tr!("Hall of fame:")];
+    for (index, run) in hall.runs().iter().enumerate() {
+        if index >= HALL_OF_FAME_LINES && place != Some(index) {
+            continue;
+        }
+        let mut line = hall_of_fame_line(index, run);
+        if place == Some(index) {
+            line = format!("{} <", line);
+        }
+        lines.push(line);
+    }
+    if place.is_none() {
+        lines.push(tr!("This run hasn't made it to the hall of fame."));
+    }
+    lines
+}
+
+fn campaign_summary(summary: &Summary, hall: &HallOfFame, place: Option<usize>) -> Vec<String> {
+    let mut lines = vec![
+        tr!("Score: {score}", score = summary.score()),
+        tr!("Battles fought: {count}", count = summary.battles),
+        tr!("Battles won: {count}", count = summary.battles_won),
+        tr!("Enemies slain: {count}", count = summary.kills),
+        tr!("Fighters lost: {count}", count = summary.casualties),
+    ];
+    if let Some(mvp) = &summary.mvp {
+        let name = utils::agent_name(mvp.name.as_deref(), &mvp.agent_type.0);
+        lines.push(tr!(
+            "Most valuable fighter: {name} ({count} kills)",
+            name = name,
+            count = mvp.kills,
+        ));
+    }
+    if let Some(ability) = summary.favorite_ability {
+        let ability = tr!(&ability.title());
+        lines.push(tr!("Favorite ability: {ability}", ability = ability));
+    }
+    if let Some(unit) = &summary.favorite_unit {
+        let unit = i18n::name_title(&unit.0);
+        lines.push(tr!("Favorite unit: {unit}", unit = unit));
+    }
+    if !summary.turning_points.is_empty() {
+        lines.push(String::new());
+        lines.push(tr!("Turning points:"));
+        lines.extend(summary.turning_points.iter().map(turning_point_text));
+    }
+    lines.push(String::new());
+    lines.extend(narrative(summary));
+    lines.push(String::new());
+    lines.extend(hall_of_fame_lines(hall, place));
+    lines
+}
+
 fn build_panel_agents(
     gui: &mut ui::Gui<Message>,
-    agents: &[ObjType],
+    state: &State,
+    selected_agent: Option<usize>,
 ) -> ZResult<Box<dyn ui::Widget>> {
     let font = assets::get().font;
+    let h = line_height();
     let mut layout = Box::new(ui::VLayout::new().stretchable(true));
-    layout.add(label(font, "Your group consists of:")?);
+    layout.add(label(font, &tr!("Your group consists of:"))?);
     layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
-    for agent_type in agents {
+    let agents = state.agents().iter().zip(state.agents_progress());
+    for (agent_index, (agent_type, progress)) in agents.enumerate() {
         let mut line = ui::HLayout::new().stretchable(true);
-        let title = agent_type.0.to_title_case();
-        line.add(label(font, &format!("- {}", title))?);
-        let spacer = ui::Spacer::new_horizontal(line_height_small()).stretchable(true);
-        line.add(Box::new(spacer));
+        let title = utils::agent_name(progress.name.as_deref(), &agent_type.0);
+        let marker = if selected_agent == Some(agent_index) {
+            ">"
+        } else {
+            "-"
+        };
+        let mut text = tr!(
+            "{marker} {name} (level {level}, xp {xp}/{max_xp})",
+            marker = marker,
+            name = title,
+            level = progress.level + 1,
+            xp = progress.xp,
+            max_xp = progress.xp_for_next_level(),
+        );
+        if progress.is_injured() {
+            let count = progress.injured_for;
+            text = if count == 1 {
+                tr!("{agent} - injured for 1 battle", agent = text)
+            } else {
+                tr!(
+                    "{agent} - injured for {count} battles",
+                    agent = text,
+                    count = count
+                )
+            };
+        }
+        {
+            let text = ui::Drawable::text(text, font);
+            let message = Message::SelectAgent(agent_index);
+            let button = ui::Button::new(text, h, gui.sender(), message)?.stretchable(true);
+            line.add(Box::new(button));
+        }
+        line.add(Box::new(ui::Spacer::new_horizontal(line_height_small())));
         {
             let icon = Drawable::Texture(assets::get().textures.icons.info);
             let message = Message::AgentInfo(agent_type.clone());
-            let button = ui::Button::new(icon, line_height(), gui.sender(), message)?;
+            let button = ui::Button::new(icon, h, gui.sender(), message)?;
             line.add(Box::new(button));
         }
         layout.add(Box::new(line));
+        if selected_agent == Some(agent_index) {
+            let mut line = ui::HLayout::new().stretchable(true);
+            let spacer = ui::Spacer::new_horizontal(line_height_small()).stretchable(true);
+            line.add(Box::new(spacer));
+            let text = ui::Drawable::text(tr!("rename"), font);
+            let message = Message::Rename(agent_index);
+            line.add(Box::new(ui::Button::new(text, h, gui.sender(), message)?));
+            line.add(Box::new(ui::Spacer::new_horizontal(line_height_small())));
+            let portrait = progress.portrait % utils::PORTRAIT_TINTS.len();
+            let text = tr!(
+                "portrait {index}/{count}",
+                index = portrait + 1,
+                count = utils::PORTRAIT_TINTS.len(),
+            );
+            let text = ui::Drawable::text(text, font);
+            let message = Message::NextPortrait(agent_index);
+            line.add(Box::new(ui::Button::new(text, h, gui.sender(), message)?));
+            layout.add(Box::new(line));
+        }
+        for &slot in &[Slot::Weapon, Slot::Trinket] {
+            let item = match progress.equipment.slot(slot) {
+                Some(item) => item,
+                None => continue,
+            };
+            let mut line = ui::HLayout::new().stretchable(true);
+            let slot_title = tr!(&format!("{:?}", slot));
+            let text = format!("    {}: {}", slot_title, item_title(state, item));
+            line.add(label(font, &text)?);
+            let spacer = ui::Spacer::new_horizontal(line_height_small()).stretchable(true);
+            line.add(Box::new(spacer));
+            let text = ui::Drawable::text(tr!("unequip"), font);
+            let message = Message::Unequip { agent_index, slot };
+            let button = ui::Button::new(text, h, gui.sender(), message)?;
+            line.add(Box::new(button));
+            layout.add(Box::new(line));
+        }
         layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
     }
     layout.stretch_to_self();
@@ -83,6 +414,41 @@ fn build_panel_agents(
     Ok(Box::new(layout))
 }
 
+fn build_panel_items(
+    gui: &mut ui::Gui<Message>,
+    state: &State,
+    selected_agent: Option<usize>,
+) -> ZResult<Option<Box<dyn ui::Widget>>> {
+    let font = assets::get().font;
+    let h = line_height();
+    if state.items().is_empty() {
+        return Ok(None);
+    }
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    layout.add(label(font, &tr!("Items:"))?);
+    layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
+    for item in state.items() {
+        let title = item_title(state, item);
+        if let Some(agent_index) = selected_agent {
+            let agent = i18n::name_title(&state.agents()[agent_index].0);
+            let text = tr!("Give {item} to {agent}", item = title, agent = agent);
+            let text = ui::Drawable::text(text, font);
+            let message = Message::Equip(item.clone());
+            let button = ui::Button::new(text, h, gui.sender(), message)?.stretchable(true);
+            layout.add(Box::new(button));
+        } else {
+            layout.add(label(font, &format!("- {}", title))?);
+        }
+        layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
+    }
+    if selected_agent.is_none() {
+        layout.add(label(font, &tr!("Select a fighter to give them an item."))?);
+    }
+    layout.stretch_to_self();
+    let layout = utils::add_offsets_and_bg_big(layout)?.stretchable(true);
+    Ok(Some(Box::new(layout)))
+}
+
 fn build_panel_casualties(state: &State) -> ZResult<Option<Box<dyn ui::Widget>>> {
     let font = assets::get().font;
     let casualties = state.last_battle_casualties();
@@ -90,10 +456,10 @@ fn build_panel_casualties(state: &State) -> ZResult<Option<Box<dyn ui::Widget>>>
         return Ok(None);
     }
     let mut layout = Box::new(ui::VLayout::new());
-    let section_title = "In the last battle you have lost:";
-    layout.add(label(font, section_title)?);
+    let section_title = tr!("In the last battle you have lost:");
+    layout.add(label(font, &section_title)?);
     for agent_type in casualties {
-        let text = &format!("- {}", agent_type.0.to_title_case());
+        let text = &format!("- {}", i18n::name_title(&agent_type.0));
         layout.add(label(font, text)?);
         layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
     }
@@ -104,8 +470,12 @@ fn build_panel_casualties(state: &State) -> ZResult<Option<Box<dyn ui::Widget>>>
 fn build_panel_renown(state: &State) -> ZResult<Box<dyn ui::Widget>> {
     let font = assets::get().font;
     let mut layout = Box::new(ui::VLayout::new().stretchable(true));
-    let renown_text = &format!("Your renown is: {}r", state.renown().0);
+    let renown_text = &tr!("Your renown is: {renown}r", renown = state.renown().0);
     layout.add(label(font, renown_text)?);
+    for boon in state.boons() {
+        let text = tr!("- boon: {boon}", boon = tr!(boon.title()));
+        layout.add(label(font, &text)?);
+    }
     let layout = utils::add_offsets_and_bg_big(layout)?.stretchable(true);
     Ok(Box::new(layout))
 }
@@ -114,21 +484,43 @@ fn build_panel_actions(gui: &mut ui::Gui<Message>, state: &State) -> ZResult<Box
     let font = assets::get().font;
     let h = line_height();
     let mut layout = Box::new(ui::VLayout::new().stretchable(true));
-    layout.add(label(font, "Actions:")?);
+    layout.add(label(font, &tr!("Actions:"))?);
     layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
     for action in state.available_actions() {
         let mut line = ui::HLayout::new().stretchable(true);
         let action_cost = state.action_cost(action);
         let text = match action {
-            Action::Recruit { agent_type } => {
-                let title = agent_type.0.to_title_case();
-                format!("Recruit {} for {}r", title, action_cost.0)
-            }
-            Action::Upgrade { from, to } => {
-                let from = from.0.to_title_case();
-                let to = to.0.to_title_case();
-                format!("Upgrade {} to {} for {}r", from, to, action_cost.0)
+            Action::Recruit { agent_type } => tr!(
+                "Recruit {agent} for {price}r",
+                agent = i18n::name_title(&agent_type.0),
+                price = action_cost.0,
+            ),
+            Action::Upgrade { from, to } => tr!(
+                "Upgrade {from} to {to} for {price}r",
+                from = i18n::name_title(&from.0),
+                to = i18n::name_title(&to.0),
+                price = action_cost.0,
+            ),
+            Action::LevelUp { agent_index, perk } => {
+                let agent = i18n::name_title(&state.agents()[*agent_index].0);
+                match perk {
+                    Perk::Strength => tr!("Train {agent}: +1 strength", agent = agent),
+                    Perk::Ability(ability) => tr!(
+                        "Train {agent}: learn {ability}",
+                        agent = agent,
+                        ability = tr!(&ability.title()),
+                    ),
+                    Perk::Upgrade(ability) => tr!(
+                        "Train {agent}: improve {ability}",
+                        agent = agent,
+                        ability = tr!(&ability.title()),
+                    ),
+                }
             }
+            Action::Rest => tr!(
+                "Rest: restore the abilities for {price}r",
+                price = action_cost.0
+            ),
         };
         {
             let text = ui::Drawable::text(text, font);
@@ -140,13 +532,17 @@ fn build_panel_actions(gui: &mut ui::Gui<Message>, state: &State) -> ZResult<Box
             }
             line.add(Box::new(button));
         }
-        line.add(Box::new(ui::Spacer::new_horizontal(line_height_small())));
-        {
+        let message = match action.clone() {
+            Action::Recruit { agent_type, .. } => Some(Message::AgentInfo(agent_type)),
+            Action::Upgrade { from, to } => Some(Message::UpgradeInfo { from, to }),
+            Action::LevelUp { agent_index, .. } => {
+                Some(Message::AgentInfo(state.agents()[agent_index].clone()))
+            }
+            Action::Rest => None,
+        };
+        if let Some(message) = message {
+            line.add(Box::new(ui::Spacer::new_horizontal(line_height_small())));
             let icon = Drawable::Texture(assets::get().textures.icons.info);
-            let message = match action.clone() {
-                Action::Recruit { agent_type, .. } => Message::AgentInfo(agent_type),
-                Action::Upgrade { from, to } => Message::UpgradeInfo { from, to },
-            };
             let sender = gui.sender();
             let button = ui::Button::new(icon, h, sender, message)?;
             line.add(Box::new(button));
@@ -154,14 +550,29 @@ fn build_panel_actions(gui: &mut ui::Gui<Message>, state: &State) -> ZResult<Box
         layout.add(Box::new(line));
         layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
     }
+    if let Some(camp) = state.camp() {
+        let text = tr!(
+            "Spend the camp actions ({count} left)",
+            count = camp.actions
+        );
+        let text = ui::Drawable::text(text, font);
+        let button = ui::Button::new(text, h, gui.sender(), Message::Camp)?.stretchable(true);
+        layout.add(Box::new(button));
+        layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
+    }
+    if !state.shop().is_empty() {
+        let text = ui::Drawable::text(tr!("Visit the shop"), font);
+        let button = ui::Button::new(text, h, gui.sender(), Message::Shop)?.stretchable(true);
+        layout.add(Box::new(button));
+        layout.add(Box::new(ui::Spacer::new_vertical(line_height_small())));
+    }
     {
-        let text = &format!(
-            "Start battle - {}/{}",
-            state.current_scenario_index() + 1,
-            state.scenarios_count()
+        let text = &tr!(
+            "Choose the next battle ({count} won so far)",
+            count = state.completed_nodes().len(),
         );
         let text = ui::Drawable::text(text, font);
-        let command = Message::StartBattle;
+        let command = Message::WorldMap;
         let button = ui::Button::new(text, h, gui.sender(), command)?.stretchable(true);
         layout.add(Box::new(button));
     }
@@ -178,31 +589,97 @@ fn label(font: Font, text: &str) -> ZResult<Box<dyn ui::Widget>> {
 #[derive(Debug)]
 pub struct Campaign {
     state: State,
+    dice: dice::Mode,
+    ai: ai::Mode,
+    selected_agent: Option<usize>,
     receiver_battle_result: Option<Receiver<Option<BattleResult>>>,
     receiver_exit_confirmation: Option<Receiver<screen::confirm::Message>>,
+    receiver_world_map: Option<Receiver<screen::world_map::Choice>>,
+    receiver_rename: Option<Receiver<String>>,
+    receiver_shop: Option<Receiver<Offer>>,
+    receiver_camp: Option<Receiver<(CampChoice, Option<usize>)>>,
+    receiver_retry: Option<Receiver<screen::confirm::Message>>,
+    receiver_auto_resolve: Option<Receiver<screen::confirm::Message>>,
+
+    /// The state before the current battle, to fight it again if it's lost.
+    /// Never kept in the ironman mode.
+    before_battle: Option<State>,
+
     gui: Gui<Message>,
     layout: Option<ui::RcWidget>,
     label_central_message: Option<ui::RcWidget>,
 }
 
 impl Campaign {
-    pub fn new() -> ZResult<Self> {
+    pub fn new(
+        dice: dice::Mode,
+        ai: ai::Mode,
+        ability_uses: AbilityUses,
+        save_mode: SaveMode,
+    ) -> ZResult<Self> {
         let campaign_plan = assets::get().campaign_plan.clone();
         let agent_campaign_info = assets::get().agent_campaign_info.clone();
-        let state = State::new(campaign_plan, agent_campaign_info);
+        let items = assets::get().items.clone();
+        let mut state = State::new(campaign_plan, agent_campaign_info, items);
+        state.set_ability_uses(ability_uses);
+        state.set_save_mode(save_mode);
+        let this = Self::from_state(state, dice, ai)?;
+        this.autosave(false);
+        Ok(this)
+    }
+
+    /// Continues the saved ironman campaign.
+    pub fn from_save(save: campaign_save::Save) -> ZResult<Self> {
+        Self::from_state(save.state, save.dice, save.ai)
+    }
+
+    fn from_state(state: State, dice: dice::Mode, ai: ai::Mode) -> ZResult<Self> {
         let gui = basic_gui()?;
         let mut this = Self {
             gui,
             state,
+            dice,
+            ai,
+            selected_agent: None,
             receiver_battle_result: None,
             receiver_exit_confirmation: None,
+            receiver_world_map: None,
+            receiver_rename: None,
+            receiver_shop: None,
+            receiver_camp: None,
+            receiver_retry: None,
+            receiver_auto_resolve: None,
+            before_battle: None,
             layout: None,
             label_central_message: None,
         };
-        this.set_mode(Mode::PreparingForBattle)?;
+        this.set_mode(this.state.mode())?;
         Ok(this)
     }
 
+    /// The ironman campaign is saved after every decision and forgotten when it's over.
+    fn autosave(&self, is_in_battle: bool) {
+        if self.state.save_mode() != SaveMode::Ironman {
+            return;
+        }
+        if self.state.mode() == Mode::PreparingForBattle {
+            campaign_save::save(&campaign_save::Save {
+                state: self.state.clone(),
+                dice: self.dice,
+                ai: self.ai,
+                is_in_battle,
+            });
+        } else {
+            campaign_save::remove();
+        }
+    }
+
+    /// Must be called after every change of the state.
+    fn update_state(&mut self) -> ZResult {
+        self.autosave(false);
+        self.set_mode(self.state.mode())
+    }
+
     fn set_mode(&mut self, mode: Mode) -> ZResult {
         self.clean_ui()?;
         match mode {
@@ -223,10 +700,14 @@ impl Campaign {
             layout.add(Box::new(ui::Spacer::new_vertical(line_height())));
         }
         let mut line = ui::HLayout::new().stretchable(true);
-        line.add(build_panel_agents(gui, state.agents())?);
+        line.add(build_panel_agents(gui, state, self.selected_agent)?);
         line.add(Box::new(ui::Spacer::new_horizontal(line_height())));
         line.add(build_panel_renown(state)?);
         layout.add(Box::new(line));
+        if let Some(panel) = build_panel_items(gui, state, self.selected_agent)? {
+            layout.add(Box::new(ui::Spacer::new_vertical(line_height())));
+            layout.add(panel);
+        }
         layout.add(Box::new(ui::Spacer::new_vertical(line_height())));
         layout.add(build_panel_actions(gui, state)?);
         layout.stretch_to_self();
@@ -238,11 +719,11 @@ impl Campaign {
     }
 
     fn set_mode_won(&mut self) -> ZResult {
-        self.add_label_central_message("You have won!")
+        self.add_label_central_message(&tr!("You have won!"))
     }
 
     fn set_mode_failed(&mut self) -> ZResult {
-        self.add_label_central_message("You have failed!")
+        self.add_label_central_message(&tr!("You have failed!"))
     }
 
     fn clean_ui(&mut self) -> ZResult {
@@ -262,20 +743,145 @@ impl Campaign {
         Ok(())
     }
 
+    fn camp_popup(&mut self) -> ZResult<StackCommand> {
+        let (sender, receiver) = channel();
+        self.receiver_camp = Some(receiver);
+        let screen = screen::Camp::new(&self.state, sender)?;
+        Ok(StackCommand::PushPopup(Box::new(screen)))
+    }
+
+    /// Returns the popup that explains the result, if it needs any.
+    fn report_battle_result(&mut self, result: &BattleResult) -> ZResult<Option<StackCommand>> {
+        self.state
+            .report_battle_results(result)
+            .expect("Campaign: Can't report battle results");
+        self.selected_agent = None;
+        self.update_state()?;
+        let before_battle = self.before_battle.take();
+        if self.state.mode() == Mode::Failed && before_battle.is_some() {
+            self.before_battle = before_battle;
+            let (sender, receiver) = channel();
+            self.receiver_retry = Some(receiver);
+            let lines = [tr!("The battle is lost."), tr!("Fight it again?")];
+            let screen = screen::Confirm::from_lines(&lines, sender)?;
+            return Ok(Some(StackCommand::PushPopup(Box::new(screen))));
+        }
+        if self.state.summary().is_some() {
+            return self.summary_popup().map(Some);
+        }
+        if result.is_retreat {
+            let lines = [
+                tr!("Your fighters have fled the battle."),
+                tr!("There's no award, but the battle can be fought again."),
+            ];
+            let popup = screen::GeneralInfo::new(&tr!("Retreat"), &lines)?;
+            return Ok(Some(StackCommand::PushPopup(Box::new(popup))));
+        }
+        Ok(None)
+    }
+
+    /// The campaign is over.
+    fn summary_popup(&mut self) -> ZResult<StackCommand> {
+        let summary = self.state.summary().expect("The campaign isn't over");
+        let (place, hall) = enter_hall_of_fame(summary.to_run());
+        let mut lines = campaign_summary(&summary, &hall, place);
+        let trigger = Trigger::CampaignEnd {
+            is_won: summary.is_won,
+            ability_uses: self.state.ability_uses(),
+            ai: self.ai,
+        };
+        for achievement in achievements::unlock(trigger) {
+            let title = tr!(&achievement.title);
+            lines.push(tr!("Achievement unlocked: {title}", title = title));
+        }
+        let popup = screen::GeneralInfo::new(&tr!("Campaign summary"), &lines)?;
+        Ok(StackCommand::PushPopup(Box::new(popup)))
+    }
+
+    fn battle_scenario(&self) -> Scenario {
+        let mut scenario = self.state.battle_scenario(&assets::get().prototypes);
+        scenario.dice = self.dice;
+        scenario.ai = self.ai;
+        scenario
+    }
+
+    /// Simulates the battle a few times to show what it's likely to cost.
+    fn auto_resolve_popup(&mut self) -> ZResult<StackCommand> {
+        let assets = assets::get();
+        let scenario = self.battle_scenario();
+        let report = simulate::simulate(
+            &assets.prototypes,
+            &assets.stack_rules,
+            &assets.ability_definitions,
+            &scenario,
+            AUTO_RESOLVE_FORECAST_BATTLES,
+        );
+        let chance = (report.win_rate(PlayerId(0)) * 100.0).round();
+        let losses = report.average_losses(PlayerId(0));
+        let lines = [
+            tr!("Let the fighters fight this battle on their own?"),
+            tr!("Chance of victory: {chance}%", chance = chance),
+            tr!(
+                "Expected losses: {losses}",
+                losses = format!("{:.1}", losses)
+            ),
+        ];
+        let (sender, receiver) = channel();
+        self.receiver_auto_resolve = Some(receiver);
+        let screen = screen::Confirm::from_lines(&lines, sender)?;
+        Ok(StackCommand::PushPopup(Box::new(screen)))
+    }
+
+    fn auto_resolve(&mut self) -> ZResult<StackCommand> {
+        let assets = assets::get();
+        let scenario = self.battle_scenario();
+        let result = simulate::resolve(
+            &assets.prototypes,
+            &assets.stack_rules,
+            &assets.ability_definitions,
+            &scenario,
+        );
+        let result = match result {
+            Some(result) => result,
+            None => {
+                let lines = [tr!("No one has won, the battle has to be fought by hand.")];
+                let popup = screen::GeneralInfo::new(&tr!("Auto-resolve"), &lines)?;
+                return Ok(StackCommand::PushPopup(Box::new(popup)));
+            }
+        };
+        if self.state.save_mode() != SaveMode::Ironman {
+            self.before_battle = Some(self.state.clone());
+        }
+        if let Some(command) = self.report_battle_result(&result)? {
+            return Ok(command);
+        }
+        let casualties: Vec<String> = self
+            .state
+            .last_battle_casualties()
+            .iter()
+            .map(|typename| i18n::name_title(&typename.0))
+            .collect();
+        let mut lines = vec![tr!("Your fighters have won the battle on their own.")];
+        if casualties.is_empty() {
+            lines.push(tr!("No one has fallen."));
+        } else {
+            let names = casualties.join(", ");
+            lines.push(tr!("Fallen: {names}", names = names));
+        }
+        let popup = screen::GeneralInfo::new(&tr!("Auto-resolve"), &lines)?;
+        Ok(StackCommand::PushPopup(Box::new(popup)))
+    }
+
     fn start_battle(&mut self) -> ZResult<Box<dyn Screen>> {
-        let mut scenario = self.state.scenario().clone();
-        // TODO: extract a function for this? add_player_agents_to_scenario?
-        for typename in self.state.agents() {
-            scenario.randomized_objects.push(scenario::ObjectsGroup {
-                owner: Some(PlayerId(0)),
-                typename: typename.clone(),
-                line: Some(scenario::Line::Middle),
-                count: 1,
-            });
+        if self.state.save_mode() == SaveMode::Ironman {
+            self.autosave(true);
+        } else {
+            self.before_battle = Some(self.state.clone());
         }
+        let prototypes = assets::get().prototypes.clone();
+        let scenario = self.battle_scenario();
         let (sender, receiver) = channel();
         self.receiver_battle_result = Some(receiver);
-        let prototypes = assets::get().prototypes.clone();
         let battle_type = BattleType::CampaignNode;
         let screen = screen::Battle::new(scenario, battle_type, prototypes, sender)?;
         Ok(Box::new(screen))
@@ -286,17 +892,80 @@ impl Screen for Campaign {
     fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
         if let Some(result) = utils::try_receive(&self.receiver_battle_result) {
             if let Some(result) = result {
-                self.state
-                    .report_battle_results(&result)
-                    .expect("Campaign: Can't report battle results");
-                let new_mode = self.state.mode();
-                self.set_mode(new_mode)?;
+                if let Some(command) = self.report_battle_result(&result)? {
+                    return Ok(command);
+                }
             } else {
                 // None result means that the player has abandoned the campaign battle.
                 // This means abandoning the campaign too.
+                if self.state.save_mode() == SaveMode::Ironman {
+                    campaign_save::remove();
+                }
                 return Ok(StackCommand::Pop);
             }
         };
+        if let Some(message) = utils::try_receive(&self.receiver_retry) {
+            self.receiver_retry = None;
+            if message == screen::confirm::Message::No {
+                self.before_battle = None;
+                return self.summary_popup();
+            }
+            self.state = self
+                .before_battle
+                .take()
+                .expect("No state to retry the battle from");
+            self.update_state()?;
+            let screen = self.start_battle()?;
+            return Ok(StackCommand::PushScreen(screen));
+        }
+        if let Some(name) = utils::try_receive(&self.receiver_rename) {
+            self.receiver_rename = None;
+            let agent_index = self.selected_agent.expect("No fighter selected");
+            self.state.rename(agent_index, &name);
+            self.update_state()?;
+        }
+        if let Some(offer) = utils::try_receive(&self.receiver_shop) {
+            self.receiver_shop = None;
+            if self.state.can_buy(&offer) {
+                self.state.buy(offer);
+                self.update_state()?;
+            }
+        }
+        if let Some((choice, agent_index)) = utils::try_receive(&self.receiver_camp) {
+            self.receiver_camp = None;
+            if self.state.can_make_camp_choice(&choice) {
+                let is_scouting = choice == CampChoice::Scout;
+                self.state.make_camp_choice(choice, agent_index);
+                self.update_state()?;
+                if is_scouting {
+                    let lines = scouting_report(&self.state);
+                    let popup = screen::GeneralInfo::new(&tr!("Scouting report"), &lines)?;
+                    return Ok(StackCommand::PushPopup(Box::new(popup)));
+                }
+            }
+        }
+        if screen::confirm::try_receive_yes(&self.receiver_auto_resolve) {
+            self.receiver_auto_resolve = None;
+            return self.auto_resolve();
+        }
+        if let Some(choice) = utils::try_receive(&self.receiver_world_map) {
+            self.receiver_world_map = None;
+            let node = choice.node;
+            if self.state.nodes()[node].camp.is_some() {
+                self.state.visit_camp(node);
+                self.update_state()?;
+                if self.state.mode() == Mode::PreparingForBattle {
+                    return self.camp_popup();
+                }
+                return Ok(StackCommand::None);
+            }
+            self.state.select_node(node);
+            if choice.is_auto_resolved {
+                return self.auto_resolve_popup();
+            }
+            let screen = self.start_battle()?;
+            return Ok(StackCommand::PushScreen(screen));
+        }
         if screen::confirm::try_receive_yes(&self.receiver_exit_confirmation) {
             Ok(StackCommand::Pop)
         } else {
@@ -316,30 +985,88 @@ impl Screen for Campaign {
             pos, message
         );
         match message {
-            Some(Message::StartBattle) => {
-                let screen = self.start_battle()?;
-                Ok(StackCommand::PushScreen(screen))
+            Some(Message::WorldMap) => {
+                let (sender, receiver) = channel();
+                self.receiver_world_map = Some(receiver);
+                let screen = screen::WorldMap::new(&self.state, sender)?;
+                Ok(StackCommand::PushPopup(Box::new(screen)))
+            }
+            Some(Message::Camp) => self.camp_popup(),
+            Some(Message::Shop) => {
+                let (sender, receiver) = channel();
+                self.receiver_shop = Some(receiver);
+                let screen = screen::Shop::new(&self.state, sender)?;
+                Ok(StackCommand::PushPopup(Box::new(screen)))
             }
             Some(Message::Action(action)) => {
                 let cost = self.state.action_cost(&action);
                 if cost.0 <= self.state.renown().0 {
                     self.state.execute_action(action);
-                    let new_mode = self.state.mode();
-                    self.set_mode(new_mode)?;
+                    self.update_state()?;
+                }
+                Ok(StackCommand::None)
+            }
+            Some(Message::SelectAgent(agent_index)) => {
+                if self.selected_agent == Some(agent_index) {
+                    self.selected_agent = None;
+                } else {
+                    self.selected_agent = Some(agent_index);
                 }
+                self.set_mode(self.state.mode())?;
+                Ok(StackCommand::None)
+            }
+            Some(Message::Rename(agent_index)) => {
+                let (sender, receiver) = channel();
+                self.receiver_rename = Some(receiver);
+                let progress = &self.state.agents_progress()[agent_index];
+                let name = progress.name.clone().unwrap_or_default();
+                let screen = screen::Rename::new(&tr!("Name the fighter:"), &name, sender)?;
+                Ok(StackCommand::PushPopup(Box::new(screen)))
+            }
+            Some(Message::NextPortrait(agent_index)) => {
+                let portrait = self.state.agents_progress()[agent_index].portrait + 1;
+                let portrait = portrait % utils::PORTRAIT_TINTS.len();
+                self.state.set_portrait(agent_index, portrait);
+                self.update_state()?;
+                Ok(StackCommand::None)
+            }
+            Some(Message::Equip(item)) => {
+                let agent_index = self.selected_agent.expect("No fighter selected");
+                self.state.equip(agent_index, item);
+                self.update_state()?;
+                Ok(StackCommand::None)
+            }
+            Some(Message::Unequip { agent_index, slot }) => {
+                self.state.unequip(agent_index, slot);
+                self.update_state()?;
                 Ok(StackCommand::None)
             }
             Some(Message::Menu) => {
                 // Ask only if the player hasn't won or failed, otherwise just pop the screen.
-                if self.state.mode() == Mode::PreparingForBattle {
+                // The ironman campaign is saved, so it isn't abandoned.
+                let is_ironman = self.state.save_mode() == SaveMode::Ironman;
+                if self.state.mode() == Mode::PreparingForBattle && !is_ironman {
                     let (sender, receiver) = channel();
                     self.receiver_exit_confirmation = Some(receiver);
-                    let screen = screen::Confirm::from_line("Abandon the campaign?", sender)?;
+                    let screen = screen::Confirm::from_line(&tr!("Abandon the campaign?"), sender)?;
                     Ok(StackCommand::PushPopup(Box::new(screen)))
                 } else {
                     Ok(StackCommand::Pop)
                 }
             }
+            Some(Message::Memorial) => {
+                let mut lines: Vec<String> = self
+                    .state
+                    .fallen_agents()
+                    .iter()
+                    .map(memorial_line)
+                    .collect();
+                if lines.is_empty() {
+                    lines.push(tr!("No one has fallen yet."));
+                }
+                let popup = screen::GeneralInfo::new(&tr!("Memorial"), &lines)?;
+                Ok(StackCommand::PushPopup(Box::new(popup)))
+            }
             Some(Message::AgentInfo(typename)) => {
                 let prototypes = &assets::get().prototypes;
                 let popup = screen::AgentInfo::new_agent_info(prototypes, &typename)?;
@@ -358,6 +1085,10 @@ impl Screen for Campaign {
         self.gui.resize_if_needed(aspect_ratio);
     }
 
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
     fn move_mouse(&mut self, pos: Vec2) -> ZResult {
         self.gui.move_mouse(pos);
         Ok(())
@@ -0,0 +1,277 @@
+use std::time::Duration;
+
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    core::{
+        battle::{
+            ability::{Ability, Definitions, PassiveAbility, Tier},
+            codex,
+            component::{DamageType, ObjType, Prototypes, Resistances},
+        },
+        map::Distance,
+    },
+    i18n::{self, tr},
+    screen::{self, Screen, StackCommand},
+    utils, ZResult,
+};
+
+/// How many buttons fit in one column of a page.
+const COLUMN_LEN: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Page {
+    Agents,
+    Abilities,
+}
+
+#[derive(Clone, Debug)]
+enum Message {
+    Back,
+    Page(Page),
+    Agent(ObjType),
+    Ability(Ability),
+    PassiveAbility(PassiveAbility),
+}
+
+fn names(typenames: &[ObjType]) -> String {
+    let names: Vec<_> = typenames.iter().map(|t| i18n::name_title(&t.0)).collect();
+    names.join(", ")
+}
+
+/// "Heal II" for the upgradable abilities, the ones with a single tier have no number.
+pub fn ability_title(definitions: &Definitions, ability: Ability, tier: Tier) -> String {
+    let title = tr!(&ability.title());
+    if definitions.tiers_count(ability) > 1 {
+        format!("{} {}", title, tier.title())
+    } else {
+        title
+    }
+}
+
+/// "Fire 1, Pierce 1", the damage types without any resistance are skipped.
+pub fn resistances_title(resistances: &Resistances) -> String {
+    let titles: Vec<_> = DamageType::ALL
+        .iter()
+        .map(|&damage_type| (damage_type, resistances.resistance(damage_type)))
+        .filter(|(_, resistance)| resistance.0 != 0)
+        .map(|(damage_type, resistance)| format!("{} {}", tr!(&damage_type.title()), resistance.0))
+        .collect();
+    titles.join(", ")
+}
+
+pub fn range_title(distance: Option<Distance>) -> String {
+    match distance {
+        Some(distance) => distance.0.to_string(),
+        None => tr!("any"),
+    }
+}
+
+/// What every tier of an upgradable ability can do, nothing for the other abilities.
+pub fn tier_lines(definitions: &Definitions, ability: Ability) -> Vec<String> {
+    let tiers = match definitions.get(ability) {
+        Some(definition) if definition.tiers.len() > 1 => &definition.tiers,
+        _ => return Vec::new(),
+    };
+    tiers
+        .iter()
+        .enumerate()
+        .map(|(i, params)| {
+            tr!(
+                "{title}: power {power}, range {distance}, cooldown {cooldown}t",
+                title = ability_title(definitions, ability, Tier(i as i32 + 1)),
+                power = params.power,
+                distance = range_title(params.distance),
+                cooldown = params.cooldown,
+            )
+        })
+        .collect()
+}
+
+/// The buttons in columns of `COLUMN_LEN`, going down and then right.
+fn grid(gui: &mut Gui<Message>, entries: Vec<(String, Message)>) -> ZResult<Box<dyn ui::Widget>> {
+    let font = assets::get().font;
+    let h = utils::line_heights().normal;
+    let mut grid = Box::new(ui::HLayout::new());
+    for (i, column_entries) in entries.chunks(COLUMN_LEN).enumerate() {
+        if i != 0 {
+            grid.add(Box::new(ui::Spacer::new_horizontal(h * 0.5)));
+        }
+        let mut column = Box::new(ui::VLayout::new());
+        for (text, message) in column_entries {
+            let text = ui::Drawable::text(text.as_str(), font);
+            let button = ui::Button::new(text, h, gui.sender(), message.clone())?;
+            column.add(Box::new(button.stretchable(true)));
+            column.add(Box::new(ui::Spacer::new_vertical(h / 8.0)));
+        }
+        column.stretch_to_self();
+        grid.add(column);
+    }
+    Ok(grid)
+}
+
+fn agents_page(gui: &mut Gui<Message>, prototypes: &Prototypes) -> ZResult<Box<dyn ui::Widget>> {
+    let entries = codex::agent_types(prototypes)
+        .into_iter()
+        .map(|typename| (i18n::name_title(&typename.0), Message::Agent(typename)))
+        .collect();
+    grid(gui, entries)
+}
+
+fn abilities_page(gui: &mut Gui<Message>, prototypes: &Prototypes) -> ZResult<Box<dyn ui::Widget>> {
+    let font = assets::get().font;
+    let h = utils::line_heights().normal;
+    let label = |text: &str| -> ZResult<_> {
+        let text = ui::Drawable::text(text, font);
+        Ok(Box::new(ui::Label::new(text, h)?.stretchable(true)))
+    };
+    let abilities = codex::all_abilities(prototypes)
+        .into_iter()
+        .map(|ability| (tr!(&ability.title()), Message::Ability(ability)))
+        .collect();
+    let passive_abilities = codex::all_passive_abilities(prototypes)
+        .into_iter()
+        .map(|ability| (tr!(&ability.title()), Message::PassiveAbility(ability)))
+        .collect();
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    layout.add(label(&tr!("~ abilities ~"))?);
+    layout.add(grid(gui, abilities)?);
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    layout.add(label(&tr!("~ passive abilities ~"))?);
+    layout.add(grid(gui, passive_abilities)?);
+    layout.stretch_to_self();
+    Ok(layout)
+}
+
+fn make_gui(prototypes: &Prototypes, page: Page) -> ZResult<Gui<Message>> {
+    let font = assets::get().font;
+    let mut gui = ui::Gui::new();
+    let h = utils::line_heights().normal;
+    let text = |s: &str| ui::Drawable::text(s, font);
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    let title = ui::Label::new(text(&tr!("~~~ Codex ~~~")), h)?.stretchable(true);
+    layout.add(Box::new(title));
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    {
+        let mut tabs = Box::new(ui::HLayout::new().stretchable(true));
+        for (i, &(tab, title)) in [(Page::Agents, "units"), (Page::Abilities, "abilities")]
+            .iter()
+            .enumerate()
+        {
+            if i != 0 {
+                tabs.add(Box::new(ui::Spacer::new_horizontal(h * 0.5)));
+            }
+            let title = if tab == page {
+                format!("[{}]", tr!(title))
+            } else {
+                tr!(title)
+            };
+            let button = ui::Button::new(text(&title), h, gui.sender(), Message::Page(tab))?;
+            tabs.add(Box::new(button.stretchable(true)));
+        }
+        layout.add(tabs);
+    }
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    let content = match page {
+        Page::Agents => agents_page(&mut gui, prototypes)?,
+        Page::Abilities => abilities_page(&mut gui, prototypes)?,
+    };
+    layout.add(content);
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    {
+        let mut button =
+            ui::Button::new(text(&tr!("back")), h, gui.sender(), Message::Back)?.stretchable(true);
+        button.stretch(layout.rect().w / 3.0);
+        button.set_stretchable(false);
+        layout.add(Box::new(button));
+    }
+    layout.stretch_to_self();
+    let layout = utils::add_offsets_and_bg_big(layout)?;
+    let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+    gui.add(&ui::pack(layout), anchor);
+    Ok(gui)
+}
+
+/// The encyclopedia of all the agent types and abilities.
+///
+/// All of it is built from the prototypes, so it's always up to date.
+#[derive(Debug)]
+pub struct Codex {
+    gui: Gui<Message>,
+    prototypes: Prototypes,
+}
+
+impl Codex {
+    pub fn new(prototypes: &Prototypes) -> ZResult<Self> {
+        let gui = make_gui(prototypes, Page::Agents)?;
+        let prototypes = prototypes.clone();
+        Ok(Self { gui, prototypes })
+    }
+
+    fn ability_info(&self, ability: Ability) -> ZResult<StackCommand> {
+        let definitions = &assets::get().ability_definitions;
+        let mut description = i18n::translate_lines(ability.description());
+        let tiers = tier_lines(definitions, ability);
+        if tiers.is_empty() {
+            let cooldown = definitions.params(ability, Tier::default()).cooldown;
+            description.push(tr!("Cooldown: {cooldown}t", cooldown = cooldown));
+        }
+        description.extend(tiers);
+        let users = codex::users_of_ability(&self.prototypes, ability);
+        description.push(tr!("Used by: {agents}", agents = names(&users)));
+        let screen = screen::GeneralInfo::new(&tr!(&ability.title()), &description)?;
+        Ok(StackCommand::PushPopup(Box::new(screen)))
+    }
+
+    fn passive_ability_info(&self, ability: PassiveAbility) -> ZResult<StackCommand> {
+        let mut description = i18n::translate_lines(ability.description());
+        let owners = codex::owners_of_passive_ability(&self.prototypes, ability);
+        description.push(tr!("Found in: {objects}", objects = names(&owners)));
+        let screen = screen::GeneralInfo::new(&tr!(&ability.title()), &description)?;
+        Ok(StackCommand::PushPopup(Box::new(screen)))
+    }
+}
+
+impl Screen for Codex {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::Back) => Ok(StackCommand::Pop),
+            Some(Message::Page(page)) => {
+                self.gui.replace(make_gui(&self.prototypes, page)?);
+                Ok(StackCommand::None)
+            }
+            Some(Message::Agent(typename)) => {
+                let screen = screen::AgentInfo::new_codex_entry(&self.prototypes, &typename)?;
+                Ok(StackCommand::PushPopup(Box::new(screen)))
+            }
+            Some(Message::Ability(ability)) => self.ability_info(ability),
+            Some(Message::PassiveAbility(ability)) => self.passive_ability_info(ability),
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
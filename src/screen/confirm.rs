@@ -8,6 +8,7 @@ use ui::{self, Gui, Widget};
 
 use crate::{
     assets,
+    i18n::tr,
     screen::{Screen, StackCommand},
     utils, ZResult,
 };
@@ -52,15 +53,15 @@ impl Confirm {
         let h = utils::line_heights().big;
         let mut layout = Box::new(ui::VLayout::new());
         let spacer = || Box::new(ui::Spacer::new_vertical(h * 0.5));
-        let button = |line, message| -> ZResult<_> {
+        let button = |line: &str, message| -> ZResult<_> {
             let text = ui::Drawable::text(line, font);
             let b = ui::Button::new(text, h, gui.sender(), message)?.stretchable(true);
             Ok(b)
         };
         let button_width = widget.rect().w / 3.0;
-        let mut yes = button("yes", Message::Yes)?;
+        let mut yes = button(&tr!("yes"), Message::Yes)?;
         yes.stretch(button_width);
-        let mut no = button("no", Message::No)?;
+        let mut no = button(&tr!("no"), Message::No)?;
         no.stretch(button_width);
         let spacer_width = widget.rect().w - yes.rect().w - no.rect().w;
         let mut line_layout = ui::HLayout::new();
@@ -105,6 +106,10 @@ impl Screen for Confirm {
         self.gui.resize_if_needed(aspect_ratio);
     }
 
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
     fn move_mouse(&mut self, pos: Vec2) -> ZResult {
         self.gui.move_mouse(pos);
         Ok(())
@@ -0,0 +1,292 @@
+use std::time::Duration;
+
+use mq::{color::Color, math::Vec2};
+use ui::{self, Gui, Widget};
+use zscene::{Layer, Scene, Sprite};
+
+use crate::{
+    assets,
+    core::{
+        battle::{scenario, PlayerId, TileType},
+        editor::{self, Brush, Draft},
+        map::{self, PosHex},
+    },
+    geom,
+    i18n::{self, tr},
+    screen::{battle, Screen, StackCommand},
+    utils, ZResult,
+};
+
+/// The map is moved to the right to leave some place for the palette.
+const MAP_SHIFT_X: f32 = 0.4;
+
+const COLOR_TILE_TO_REACH: Color = Color::new(0.9, 0.7, 0.0, 0.5);
+
+#[derive(Clone, Debug)]
+enum Message {
+    Back,
+    Save,
+    ToggleMapSize,
+    ToggleSurviveTurns,
+    Brush(Brush),
+}
+
+fn owner_title(owner: Option<PlayerId>) -> String {
+    match owner {
+        Some(PlayerId(0)) => tr!("Your agents:"),
+        Some(_) => tr!("Enemies:"),
+        None => tr!("Other objects:"),
+    }
+}
+
+fn error_text(error: &scenario::Error) -> String {
+    match error {
+        scenario::Error::MapIsTooSmall => tr!("the map is too small"),
+        scenario::Error::PosOutsideOfMap(pos) => {
+            tr!("({q}, {r}) is outside of the map", q = pos.q, r = pos.r)
+        }
+        scenario::Error::NoPlayerAgents => tr!("no agents of yours"),
+        scenario::Error::NoEnemyAgents => tr!("no enemies"),
+        scenario::Error::UnsupportedPlayersCount(n) => {
+            tr!("{count} players aren't supported", count = n)
+        }
+        scenario::Error::BadAlly(PlayerId(id)) => tr!("bad ally: player {id}", id = id),
+        scenario::Error::NoSuchRegion(name) => tr!("no '{name}' region", name = name),
+    }
+}
+
+fn brush_title(brush: &Brush) -> String {
+    match brush {
+        Brush::Object { typename, .. } => i18n::name_title(&typename.0),
+        _ => tr!(&brush.title()),
+    }
+}
+
+fn make_gui(draft: &Draft, brush: &Brush, status: &str) -> ZResult<Gui<Message>> {
+    let font = assets::get().font;
+    let mut gui = ui::Gui::new();
+    let h = utils::line_heights().small;
+    let text = |s: &str| ui::Drawable::text(s, font);
+    let space = || Box::new(ui::Spacer::new_vertical(h * 0.5));
+    let brush_button = |other: &Brush| -> ZResult<Box<ui::Button<Message>>> {
+        let prefix = if other == brush { "> " } else { "" };
+        let title = format!("{}{}", prefix, brush_title(other));
+        let message = Message::Brush(other.clone());
+        let button = ui::Button::new(text(&title), h, gui.sender(), message)?;
+        Ok(Box::new(button))
+    };
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    let mut columns = ui::HLayout::new();
+    {
+        let mut column = ui::VLayout::new();
+        column.add(Box::new(ui::Label::new(text(&tr!("Terrain:")), h)?));
+        for brush in editor::terrain_brushes() {
+            column.add(brush_button(&brush)?);
+        }
+        for (owner, brushes) in editor::object_brushes() {
+            column.add(space());
+            column.add(Box::new(ui::Label::new(text(&owner_title(owner)), h)?));
+            for brush in brushes {
+                column.add(brush_button(&brush)?);
+            }
+        }
+        columns.add(Box::new(column));
+    }
+    layout.add(Box::new(columns));
+    layout.add(space());
+    let radius = draft.scenario().map_radius.0;
+    let map_text = tr!("map size: {radius}", radius = radius);
+    let button = ui::Button::new(text(&map_text), h, gui.sender(), Message::ToggleMapSize)?;
+    layout.add(Box::new(button.stretchable(true)));
+    let survive_text = match draft.survive_turns() {
+        Some(turns) => tr!("survive: {turns} turns", turns = turns),
+        None => tr!("survive: -"),
+    };
+    let message = Message::ToggleSurviveTurns;
+    let button = ui::Button::new(text(&survive_text), h, gui.sender(), message)?;
+    layout.add(Box::new(button.stretchable(true)));
+    layout.add(space());
+    {
+        let mut buttons = ui::HLayout::new();
+        #[cfg(not(target_arch = "wasm32"))] // can't save files in browsers
+        {
+            let button = ui::Button::new(text(&tr!("save")), h, gui.sender(), Message::Save)?;
+            buttons.add(Box::new(button));
+            buttons.add(Box::new(ui::Spacer::new_horizontal(h)));
+        }
+        let button = ui::Button::new(text(&tr!("back")), h, gui.sender(), Message::Back)?;
+        buttons.add(Box::new(button));
+        layout.add(Box::new(buttons));
+    }
+    if !status.is_empty() {
+        layout.add(space());
+        layout.add(Box::new(ui::Label::new(text(status), h)?));
+    }
+    layout.stretch_to_self();
+    let layout = utils::add_offsets_and_bg_big(layout)?;
+    let anchor = ui::Anchor(ui::HAnchor::Left, ui::VAnchor::Middle);
+    gui.add(&ui::pack(layout), anchor);
+    Ok(gui)
+}
+
+fn tile_size(draft: &Draft) -> f32 {
+    battle::tile_size(map::radius_to_diameter(draft.scenario().map_radius))
+}
+
+fn hex_to_point(draft: &Draft, pos: PosHex) -> Vec2 {
+    geom::hex_to_point(tile_size(draft), pos) + Vec2::new(MAP_SHIFT_X, 0.0)
+}
+
+fn make_tile_sprite(draft: &Draft, pos: PosHex, texture: mq::texture::Texture2D) -> Sprite {
+    let size = tile_size(draft) * 2.0 * geom::FLATNESS_COEFFICIENT;
+    let mut sprite = Sprite::from_texture(texture, size);
+    sprite.set_centered(true);
+    sprite.set_pos(hex_to_point(draft, pos));
+    sprite
+}
+
+/// Draws the whole draft from scratch, it's small enough for that.
+fn make_scene(draft: &Draft) -> Scene {
+    let assets = assets::get();
+    let textures = &assets.textures.map;
+    let mut tiles = Layer::new();
+    let mut marks = Layer::new();
+    let mut objects = Layer::new();
+    let scene = Scene::new(vec![tiles.clone(), marks.clone(), objects.clone()]);
+    let radius = draft.scenario().map_radius;
+    for pos in map::HexMap::<TileType>::new(radius).iter() {
+        let tile = draft
+            .scenario()
+            .tiles
+            .get(&pos)
+            .copied()
+            .unwrap_or_default();
+        let size = tile_size(draft) * 2.0 * geom::FLATNESS_COEFFICIENT;
+        let mut sprite = battle::tile_sprite(tile, size);
+        sprite.set_pos(hex_to_point(draft, pos));
+        tiles.add(&sprite);
+        if draft.is_tile_to_reach(pos) {
+            let mut sprite = make_tile_sprite(draft, pos, textures.white_hex);
+            sprite.set_color(COLOR_TILE_TO_REACH);
+            marks.add(&sprite);
+        }
+    }
+    // The farther objects are drawn first.
+    let mut placed = draft.scenario().objects.clone();
+    placed.sort_by_key(|obj| (obj.pos.r, obj.pos.q));
+    for obj in placed {
+        let info = &assets.sprites_info[&obj.typename];
+        let frames = &assets.sprite_frames[&obj.typename];
+        let mut sprite = Sprite::from_textures(frames, tile_size(draft) * 2.0);
+        sprite.set_offset(Vec2::new(0.5 - info.offset_x, 1.0 - info.offset_y));
+        sprite.set_pos(hex_to_point(draft, obj.pos));
+        objects.add(&sprite);
+    }
+    scene
+}
+
+/// Lets the player paint a scenario and save it for the skirmish mode.
+#[derive(Debug)]
+pub struct Editor {
+    gui: Gui<Message>,
+    scene: Scene,
+    draft: Draft,
+    brush: Brush,
+
+    /// The result of the last save.
+    status: String,
+}
+
+impl Editor {
+    pub fn new() -> ZResult<Self> {
+        let draft = Draft::default();
+        let brush = Brush::Tile(TileType::Rocks);
+        let status = String::new();
+        Ok(Self {
+            gui: make_gui(&draft, &brush, &status)?,
+            scene: make_scene(&draft),
+            draft,
+            brush,
+            status,
+        })
+    }
+
+    fn update_gui(&mut self) -> ZResult<StackCommand> {
+        self.gui
+            .replace(make_gui(&self.draft, &self.brush, &self.status)?);
+        Ok(StackCommand::None)
+    }
+
+    fn update_draft(&mut self, f: impl FnOnce(&mut Draft)) -> ZResult<StackCommand> {
+        f(&mut self.draft);
+        self.scene = make_scene(&self.draft);
+        self.update_gui()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save(&mut self) -> ZResult<StackCommand> {
+        self.status = match self.draft.scenario().check() {
+            Ok(()) => {
+                let path = crate::user_scenarios::save(&self.draft)?;
+                tr!("Saved to '{path}'", path = path.display())
+            }
+            Err(err) => tr!("Can't save: {error}", error = error_text(&err)),
+        };
+        self.update_gui()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save(&mut self) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn click_map(&mut self, point: Vec2) -> ZResult<StackCommand> {
+        let point = point - Vec2::new(MAP_SHIFT_X, 0.0);
+        let pos = geom::point_to_hex(tile_size(&self.draft), point);
+        if !self.draft.is_inboard(pos) {
+            return Ok(StackCommand::None);
+        }
+        let brush = self.brush.clone();
+        self.update_draft(|draft| draft.apply(&brush, pos))
+    }
+}
+
+impl Screen for Editor {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.scene.draw();
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::Brush(brush)) => {
+                self.brush = brush;
+                self.update_gui()
+            }
+            Some(Message::ToggleMapSize) => self.update_draft(Draft::next_map_radius),
+            Some(Message::ToggleSurviveTurns) => self.update_draft(Draft::next_survive_turns),
+            Some(Message::Save) => self.save(),
+            Some(Message::Back) => Ok(StackCommand::Pop),
+            None => self.click_map(pos),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
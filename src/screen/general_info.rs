@@ -5,6 +5,7 @@ use ui::{self, Gui, Widget};
 
 use crate::{
     assets,
+    i18n::tr,
     screen::{Screen, StackCommand},
     utils, ZResult,
 };
@@ -37,8 +38,8 @@ impl GeneralInfo {
         }
         layout.add(spacer());
         {
-            let mut button =
-                ui::Button::new(text_("back"), h, gui.sender(), Message::Back)?.stretchable(true);
+            let mut button = ui::Button::new(text_(&tr!("back")), h, gui.sender(), Message::Back)?
+                .stretchable(true);
             button.stretch(layout.rect().w / 3.0);
             button.set_stretchable(false);
             layout.add(Box::new(button));
@@ -73,6 +74,10 @@ impl Screen for GeneralInfo {
         self.gui.resize_if_needed(aspect_ratio);
     }
 
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
     fn move_mouse(&mut self, pos: Vec2) -> ZResult {
         self.gui.move_mouse(pos);
         Ok(())
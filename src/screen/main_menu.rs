@@ -5,40 +5,141 @@ use std::{
 
 use log::trace;
 use mq::math::Vec2;
+use rand::seq::SliceRandom;
 use ui::{self, Gui, Widget};
 
 use crate::{
-    assets,
-    core::battle::{scenario, state},
+    achievements, assets, campaign_save,
+    core::{
+        battle::{
+            ai, dice,
+            scenario::{self, Difficulty, Scenario},
+            state,
+        },
+        campaign::{AbilityUses, SaveMode},
+        utils::zrng,
+    },
+    i18n::tr,
     screen::{self, Screen, StackCommand},
-    utils, ZResult,
+    settings, utils, ZResult,
 };
 
 #[derive(Copy, Clone, Debug)]
 enum Message {
     Exit,
     StartInstant,
+    StartTutorial,
+    StartSkirmish,
+    CustomSkirmish,
+    Editor,
     StartCampaign,
+    ContinueCampaign,
+    ToggleDifficulty,
+    ToggleDice,
+    ToggleAi,
+    ToggleAbilityUses,
+    ToggleSaveMode,
+    Settings,
+    Codex,
+    Achievements,
+    Mods,
 }
 
-fn make_gui() -> ZResult<ui::Gui<Message>> {
+/// All the achievements, the reached ones are marked.
+fn achievements_lines() -> Vec<String> {
+    let unlocked = achievements::get();
+    let mut lines = Vec::new();
+    for achievement in &assets::get().achievements {
+        let mark = if unlocked.contains(&achievement.id) {
+            "[x]"
+        } else {
+            "[ ]"
+        };
+        let title = tr!(&achievement.title);
+        let description = tr!(&achievement.description);
+        lines.push(format!("{} {}: {}", mark, title, description));
+    }
+    lines
+}
+
+fn make_gui(
+    dice: dice::Mode,
+    ai: ai::Mode,
+    difficulty: Difficulty,
+    ability_uses: AbilityUses,
+    save_mode: SaveMode,
+    has_saved_campaign: bool,
+) -> ZResult<ui::Gui<Message>> {
     let font = assets::get().font;
     let mut gui = ui::Gui::new();
     let h = utils::line_heights().large;
     let space = || Box::new(ui::Spacer::new_vertical(h / 8.0));
-    let button = &mut |text, message| -> ZResult<_> {
+    let button = &mut |text: &str, message| -> ZResult<_> {
         let text = ui::Drawable::text(text, font);
         let b = ui::Button::new(text, h, gui.sender(), message)?.stretchable(true);
         Ok(Box::new(b))
     };
     let mut layout = Box::new(ui::VLayout::new().stretchable(true));
-    layout.add(button("demo battle", Message::StartInstant)?);
+    {
+        let mut line = Box::new(ui::HLayout::new().stretchable(true));
+        line.add(button(&tr!("tutorial"), Message::StartTutorial)?);
+        line.add(Box::new(ui::Spacer::new_horizontal(h / 8.0)));
+        line.add(button(&tr!("demo battle"), Message::StartInstant)?);
+        layout.add(line);
+    }
+    layout.add(space());
+    let difficulty = tr!(difficulty.title());
+    let skirmish_text = tr!("skirmish ({difficulty})", difficulty = difficulty);
+    layout.add(button(&skirmish_text, Message::StartSkirmish)?);
+    layout.add(button(
+        &tr!("change difficulty"),
+        Message::ToggleDifficulty,
+    )?);
+    layout.add(button(&tr!("custom skirmish"), Message::CustomSkirmish)?);
+    layout.add(button(&tr!("scenario editor"), Message::Editor)?);
     layout.add(space());
-    layout.add(button("campaign", Message::StartCampaign)?);
+    if has_saved_campaign {
+        let mut line = Box::new(ui::HLayout::new().stretchable(true));
+        line.add(button(&tr!("campaign"), Message::StartCampaign)?);
+        line.add(Box::new(ui::Spacer::new_horizontal(h / 8.0)));
+        line.add(button(
+            &tr!("continue campaign"),
+            Message::ContinueCampaign,
+        )?);
+        layout.add(line);
+    } else {
+        layout.add(button(&tr!("campaign"), Message::StartCampaign)?);
+    }
+    layout.add(space());
+    let dice_text = tr!("dice: {mode}", mode = tr!(dice.title()));
+    layout.add(button(&dice_text, Message::ToggleDice)?);
+    let ai_text = tr!("ai: {mode}", mode = tr!(ai.title()));
+    layout.add(button(&ai_text, Message::ToggleAi)?);
+    layout.add(space());
+    let uses = tr!(ability_uses.title());
+    let ability_uses_text = tr!("campaign abilities: {uses}", uses = uses);
+    layout.add(button(&ability_uses_text, Message::ToggleAbilityUses)?);
+    let save_mode_text = tr!("campaign mode: {mode}", mode = tr!(save_mode.title()));
+    layout.add(button(&save_mode_text, Message::ToggleSaveMode)?);
+    layout.add(space());
+    {
+        // Side by side, the menu is tall enough already.
+        let mut line = Box::new(ui::HLayout::new().stretchable(true));
+        line.add(button(&tr!("settings"), Message::Settings)?);
+        line.add(Box::new(ui::Spacer::new_horizontal(h / 8.0)));
+        line.add(button(&tr!("codex"), Message::Codex)?);
+        line.add(Box::new(ui::Spacer::new_horizontal(h / 8.0)));
+        line.add(button(&tr!("achievements"), Message::Achievements)?);
+        layout.add(line);
+    }
+    // Only the PC builds load the mods.
+    if !assets::get().mods.names.is_empty() {
+        layout.add(button(&tr!("mods"), Message::Mods)?);
+    }
     #[cfg(not(target_arch = "wasm32"))] // can't quit WASM
     {
         layout.add(space());
-        layout.add(button("exit", Message::Exit)?);
+        layout.add(button(&tr!("exit"), Message::Exit)?);
     }
     layout.stretch_to_self();
     let layout = utils::add_offsets_and_bg_big(layout)?;
@@ -50,22 +151,133 @@ fn make_gui() -> ZResult<ui::Gui<Message>> {
 #[derive(Debug)]
 pub struct MainMenu {
     gui: Gui<Message>,
+    dice: dice::Mode,
+    ai: ai::Mode,
+    difficulty: Difficulty,
+    ability_uses: AbilityUses,
+    save_mode: SaveMode,
+
+    /// The campaign screen saves the ironman campaign and removes it when it's over.
+    saved_campaign: Option<campaign_save::Save>,
+
+    /// The campaign screen has been pushed, so the saved campaign may have changed.
+    is_saved_campaign_outdated: bool,
+
     receiver_battle_result: Option<Receiver<Option<state::BattleResult>>>,
+    receiver_settings: Option<Receiver<settings::Settings>>,
 }
 
 // TODO: add the game's version to one of the corners
 impl MainMenu {
     pub fn new() -> ZResult<Self> {
-        let gui = make_gui()?;
+        let settings = settings::get();
+        let dice = dice::Mode::default();
+        let ai = settings.ai;
+        let difficulty = settings.difficulty;
+        let ability_uses = AbilityUses::default();
+        let save_mode = SaveMode::default();
+        let saved_campaign = campaign_save::load();
+        let has_saved_campaign = saved_campaign.is_some();
+        let gui = make_gui(
+            dice,
+            ai,
+            difficulty,
+            ability_uses,
+            save_mode,
+            has_saved_campaign,
+        )?;
         Ok(Self {
             gui,
+            dice,
+            ai,
+            difficulty,
+            ability_uses,
+            save_mode,
+            saved_campaign,
+            is_saved_campaign_outdated: false,
             receiver_battle_result: None,
+            receiver_settings: None,
         })
     }
+
+    fn make_gui(&self) -> ZResult<Gui<Message>> {
+        make_gui(
+            self.dice,
+            self.ai,
+            self.difficulty,
+            self.ability_uses,
+            self.save_mode,
+            self.saved_campaign.is_some(),
+        )
+    }
+
+    /// A finished battle can't be started over in the ironman mode,
+    /// so leaving the game in the middle of one loses the campaign.
+    fn continue_campaign(&mut self) -> ZResult<StackCommand> {
+        let save = self.saved_campaign.take().expect("No saved campaign");
+        self.is_saved_campaign_outdated = true;
+        if save.is_in_battle {
+            campaign_save::remove();
+            let lines = [
+                tr!("The game was closed in the middle of a battle."),
+                tr!("In the ironman mode that means the campaign is lost."),
+            ];
+            let screen = screen::GeneralInfo::new(&tr!("Ironman"), &lines)?;
+            return Ok(StackCommand::PushPopup(Box::new(screen)));
+        }
+        let screen = screen::Campaign::from_save(save)?;
+        Ok(StackCommand::PushScreen(Box::new(screen)))
+    }
+
+    /// A random skirmish scenario of the chosen difficulty.
+    fn skirmish_scenario(&self) -> Scenario {
+        let query = scenario::Query {
+            difficulty: Some(self.difficulty),
+            ..Default::default()
+        };
+        let scenarios = scenario::select(&assets::get().skirmish_scenarios, &query);
+        match scenarios.choose(&mut zrng()) {
+            Some(&scenario) => scenario.clone(),
+            None => assets::get().demo_scenario.clone(),
+        }
+    }
+
+    fn start_battle(&mut self, mut scenario: Scenario) -> ZResult<StackCommand> {
+        let prototypes = assets::get().prototypes.clone();
+        scenario.dice = self.dice;
+        scenario.ai = self.ai;
+        let (sender, receiver) = channel();
+        self.receiver_battle_result = Some(receiver);
+        let battle_type = scenario::BattleType::Skirmish;
+        let screen = screen::Battle::new(scenario, battle_type, prototypes, sender)?;
+        Ok(StackCommand::PushScreen(Box::new(screen)))
+    }
+
+    /// The tutorial keeps its own dice and ai, its steps depend on them.
+    fn start_tutorial(&mut self) -> ZResult<StackCommand> {
+        let prototypes = assets::get().prototypes.clone();
+        let tutorial = assets::get().tutorial.clone();
+        let (sender, receiver) = channel();
+        self.receiver_battle_result = Some(receiver);
+        let screen = screen::Battle::new_tutorial(tutorial, prototypes, sender)?;
+        Ok(StackCommand::PushScreen(Box::new(screen)))
+    }
 }
 
 impl Screen for MainMenu {
     fn update(&mut self, _: Duration) -> ZResult<StackCommand> {
+        if self.is_saved_campaign_outdated {
+            self.is_saved_campaign_outdated = false;
+            self.saved_campaign = campaign_save::load();
+            self.gui.replace(self.make_gui()?);
+        }
+        // The new defaults and maybe the language.
+        if let Some(settings) = utils::try_receive(&self.receiver_settings) {
+            self.receiver_settings = None;
+            self.difficulty = settings.difficulty;
+            self.ai = settings.ai;
+            self.gui.replace(self.make_gui()?);
+        }
         Ok(StackCommand::None)
     }
 
@@ -79,16 +291,71 @@ impl Screen for MainMenu {
         trace!("MainMenu: click: pos={:?}, message={:?}", pos, message);
         match message {
             Some(Message::StartInstant) => {
-                let prototypes = assets::get().prototypes.clone();
                 let scenario = assets::get().demo_scenario.clone();
-                let (sender, receiver) = channel();
-                self.receiver_battle_result = Some(receiver);
-                let battle_type = scenario::BattleType::Skirmish;
-                let screen = screen::Battle::new(scenario, battle_type, prototypes, sender)?;
+                self.start_battle(scenario)
+            }
+            Some(Message::StartTutorial) => self.start_tutorial(),
+            Some(Message::StartSkirmish) => {
+                let scenario = self.skirmish_scenario();
+                self.start_battle(scenario)
+            }
+            Some(Message::CustomSkirmish) => {
+                let screen = screen::Skirmish::new(self.dice, self.ai)?;
+                Ok(StackCommand::PushScreen(Box::new(screen)))
+            }
+            Some(Message::Editor) => {
+                let screen = screen::Editor::new()?;
                 Ok(StackCommand::PushScreen(Box::new(screen)))
             }
             Some(Message::StartCampaign) => {
-                let screen = screen::Campaign::new()?;
+                self.is_saved_campaign_outdated = true;
+                let screen =
+                    screen::Campaign::new(self.dice, self.ai, self.ability_uses, self.save_mode)?;
+                Ok(StackCommand::PushScreen(Box::new(screen)))
+            }
+            Some(Message::ContinueCampaign) => self.continue_campaign(),
+            Some(Message::ToggleDice) => {
+                self.dice = self.dice.next();
+                self.gui.replace(self.make_gui()?);
+                Ok(StackCommand::None)
+            }
+            Some(Message::ToggleAi) => {
+                self.ai = self.ai.next();
+                self.gui.replace(self.make_gui()?);
+                Ok(StackCommand::None)
+            }
+            Some(Message::ToggleDifficulty) => {
+                self.difficulty = self.difficulty.next();
+                self.gui.replace(self.make_gui()?);
+                Ok(StackCommand::None)
+            }
+            Some(Message::ToggleAbilityUses) => {
+                self.ability_uses = self.ability_uses.next();
+                self.gui.replace(self.make_gui()?);
+                Ok(StackCommand::None)
+            }
+            Some(Message::ToggleSaveMode) => {
+                self.save_mode = self.save_mode.next();
+                self.gui.replace(self.make_gui()?);
+                Ok(StackCommand::None)
+            }
+            Some(Message::Settings) => {
+                let (sender, receiver) = channel();
+                self.receiver_settings = Some(receiver);
+                let screen = screen::Settings::new(sender)?;
+                Ok(StackCommand::PushScreen(Box::new(screen)))
+            }
+            Some(Message::Codex) => {
+                let screen = screen::Codex::new(&assets::get().prototypes)?;
+                Ok(StackCommand::PushScreen(Box::new(screen)))
+            }
+            Some(Message::Achievements) => {
+                let lines = achievements_lines();
+                let screen = screen::GeneralInfo::new(&tr!("Achievements"), &lines)?;
+                Ok(StackCommand::PushPopup(Box::new(screen)))
+            }
+            Some(Message::Mods) => {
+                let screen = screen::Mods::new()?;
                 Ok(StackCommand::PushScreen(Box::new(screen)))
             }
             Some(Message::Exit) => Ok(StackCommand::Pop),
@@ -100,6 +367,10 @@ impl Screen for MainMenu {
         self.gui.resize_if_needed(aspect_ratio);
     }
 
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
     fn move_mouse(&mut self, pos: Vec2) -> ZResult {
         self.gui.move_mouse(pos);
         Ok(())
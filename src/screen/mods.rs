@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets::{self, ModsSummary},
+    core::{battle::component::ObjType, mod_diff::Change},
+    i18n::{self, tr},
+    screen::{Screen, StackCommand},
+    settings, utils, ZResult,
+};
+
+#[derive(Copy, Clone, Debug)]
+enum Message {
+    Back,
+
+    /// The mod's index in the load order.
+    ToggleMod(usize),
+}
+
+fn list(names: impl IntoIterator<Item = String>) -> String {
+    names.into_iter().collect::<Vec<_>>().join(", ")
+}
+
+fn objects(typenames: &[ObjType]) -> String {
+    list(
+        typenames
+            .iter()
+            .map(|typename| i18n::name_title(&typename.0)),
+    )
+}
+
+/// `strength 3→5`, a missing value is a dash.
+fn changes(changes: &[Change]) -> String {
+    let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".into());
+    list(changes.iter().map(|change| {
+        let before = value(&change.before);
+        let after = value(&change.after);
+        format!("{} {}→{}", change.name, before, after)
+    }))
+}
+
+/// What the mods change compared to the built-in data.
+fn summary_lines(summary: &ModsSummary) -> Vec<String> {
+    let mut lines = vec![tr!(
+        "load order: {mods}",
+        mods = list(summary.names.iter().cloned())
+    )];
+    if !summary.disabled.is_empty() {
+        let mods = list(summary.disabled.iter().cloned());
+        lines.push(tr!("disabled at the start: {mods}", mods = mods));
+    }
+    let diff = &summary.diff;
+    if !diff.new_prototypes.is_empty() {
+        let objects = objects(&diff.new_prototypes);
+        lines.push(tr!("new objects: {objects}", objects = objects));
+    }
+    if !diff.changed_prototypes.is_empty() {
+        lines.push(tr!("changed objects:"));
+        for (typename, object_changes) in &diff.changed_prototypes {
+            let name = i18n::name_title(&typename.0);
+            lines.push(format!("  {}: {}", name, changes(object_changes)));
+        }
+    }
+    if !diff.changed_abilities.is_empty() {
+        lines.push(tr!("changed abilities:"));
+        for (ability, ability_changes) in &diff.changed_abilities {
+            let title = tr!(&ability.title());
+            lines.push(format!("  {}: {}", title, changes(ability_changes)));
+        }
+    }
+    if diff.new_skirmishes > 0 {
+        let count = diff.new_skirmishes;
+        lines.push(tr!("new skirmishes: {count}", count = count));
+    }
+    if !summary.files.is_empty() {
+        let files = list(summary.files.iter().map(|file| file.to_string()));
+        lines.push(tr!("changed files: {files}", files = files));
+    }
+    lines
+}
+
+fn make_gui(disabled_mods: &[String]) -> ZResult<Gui<Message>> {
+    let font = assets::get().font;
+    let mut gui = ui::Gui::new();
+    let h = utils::line_heights().normal;
+    let space = || Box::new(ui::Spacer::new_vertical(h * 0.5));
+    let text = |s: &str| ui::Drawable::text(s, font);
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    let title = ui::Label::new(text(&tr!("~~~ Mods ~~~")), h)?;
+    layout.add(Box::new(title.stretchable(true)));
+    layout.add(space());
+    let summary = &assets::get().mods;
+    for line in summary_lines(summary) {
+        layout.add(Box::new(ui::Label::new(text(&line), h)?));
+    }
+    layout.add(space());
+    for (index, name) in summary.names.iter().enumerate() {
+        let is_disabled = disabled_mods.contains(name);
+        let state = if is_disabled { tr!("off") } else { tr!("on") };
+        let toggle_text = tr!(
+            "{name}: {state} (after a restart)",
+            name = name,
+            state = state
+        );
+        let message = Message::ToggleMod(index);
+        let toggle = ui::Button::new(text(&toggle_text), h, gui.sender(), message)?;
+        layout.add(Box::new(toggle.stretchable(true)));
+    }
+    let back = ui::Button::new(text(&tr!("back")), h, gui.sender(), Message::Back)?;
+    layout.add(Box::new(back.stretchable(true)));
+    layout.stretch_to_self();
+    let layout = utils::add_offsets_and_bg_big(layout)?;
+    let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+    gui.add(&ui::pack(layout), anchor);
+    Ok(gui)
+}
+
+/// Shows what the loaded mods change and lets the player turn each of them off.
+#[derive(Debug)]
+pub struct Mods {
+    gui: Gui<Message>,
+}
+
+impl Mods {
+    pub fn new() -> ZResult<Self> {
+        let gui = make_gui(&settings::get().disabled_mods)?;
+        Ok(Self { gui })
+    }
+}
+
+impl Screen for Mods {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::ToggleMod(index)) => {
+                let name = &assets::get().mods.names[index];
+                let mut settings = settings::get();
+                let disabled = &mut settings.disabled_mods;
+                match disabled.iter().position(|other| other == name) {
+                    Some(position) => {
+                        disabled.remove(position);
+                    }
+                    None => disabled.push(name.clone()),
+                }
+                self.gui.replace(make_gui(disabled)?);
+                settings::set(settings);
+                Ok(StackCommand::None)
+            }
+            Some(Message::Back) => Ok(StackCommand::Pop),
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
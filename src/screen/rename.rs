@@ -0,0 +1,142 @@
+use std::{sync::mpsc::Sender, time::Duration};
+
+use mq::{
+    input::{get_char_pressed, is_key_pressed, KeyCode},
+    math::Vec2,
+};
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    i18n::tr,
+    screen::{Screen, StackCommand},
+    utils, ZResult,
+};
+
+const MAX_NAME_LEN: usize = 16;
+
+#[derive(Clone, Debug)]
+enum Message {
+    Ok,
+    Cancel,
+}
+
+fn make_gui(title: &str, name: &str) -> ZResult<Gui<Message>> {
+    let font = assets::get().font;
+    let mut gui = ui::Gui::new();
+    let h = utils::line_heights().big;
+    let text = |s: &str| ui::Drawable::text(s, font);
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    layout.add(Box::new(ui::Label::new(text(title), h)?));
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    let name_line = format!("> {}_", name);
+    layout.add(Box::new(ui::Label::new(text(&name_line), h)?));
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    let mut buttons = ui::HLayout::new();
+    buttons.add(Box::new(ui::Button::new(
+        text(&tr!("ok")),
+        h,
+        gui.sender(),
+        Message::Ok,
+    )?));
+    buttons.add(Box::new(ui::Spacer::new_horizontal(h)));
+    buttons.add(Box::new(ui::Button::new(
+        text(&tr!("cancel")),
+        h,
+        gui.sender(),
+        Message::Cancel,
+    )?));
+    layout.add(Box::new(buttons));
+    layout.stretch_to_self();
+    let layout = utils::add_offsets_and_bg_big(layout)?;
+    let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+    gui.add(&ui::pack(layout), anchor);
+    Ok(gui)
+}
+
+/// Asks the player to type a name.
+///
+/// Enter confirms the name, Escape cancels the renaming.
+#[derive(Debug)]
+pub struct Rename {
+    gui: Gui<Message>,
+    title: String,
+    name: String,
+    sender: Sender<String>,
+}
+
+impl Rename {
+    pub fn new(title: &str, name: &str, sender: Sender<String>) -> ZResult<Self> {
+        // Skip the keys that were pressed before the screen was opened.
+        while get_char_pressed().is_some() {}
+        let gui = make_gui(title, name)?;
+        Ok(Self {
+            gui,
+            title: title.into(),
+            name: name.into(),
+            sender,
+        })
+    }
+
+    fn send_name(&self) {
+        self.sender
+            .send(self.name.clone())
+            .expect("Can't report back the name");
+    }
+}
+
+impl Screen for Rename {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        if is_key_pressed(KeyCode::Enter) {
+            self.send_name();
+            return Ok(StackCommand::Pop);
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            return Ok(StackCommand::Pop);
+        }
+        let mut is_changed = false;
+        if is_key_pressed(KeyCode::Backspace) {
+            is_changed = self.name.pop().is_some();
+        }
+        while let Some(c) = get_char_pressed() {
+            let is_allowed = c.is_alphanumeric() || c == ' ' || c == '-' || c == '\'';
+            if is_allowed && self.name.chars().count() < MAX_NAME_LEN {
+                self.name.push(c);
+                is_changed = true;
+            }
+        }
+        if is_changed {
+            self.gui.replace(make_gui(&self.title, &self.name)?);
+        }
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        match self.gui.click(pos) {
+            Some(Message::Ok) => {
+                self.send_name();
+                Ok(StackCommand::Pop)
+            }
+            Some(Message::Cancel) => Ok(StackCommand::Pop),
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use std::{sync::mpsc::Sender, time::Duration};
+
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    i18n::tr,
+    screen::{Screen, StackCommand},
+    settings, utils, ZResult,
+};
+
+#[derive(Copy, Clone, Debug)]
+enum Message {
+    Back,
+    ToggleAnimationSpeed,
+    ToggleInstantAiTurns,
+    ToggleFullscreen,
+    ToggleLanguage,
+    ToggleColorblind,
+    ToggleConfirmTaps,
+    ToggleLargeButtons,
+    ToggleUiScale,
+    ToggleDifficulty,
+    ToggleAi,
+}
+
+fn on_off(value: bool) -> String {
+    if value {
+        tr!("on")
+    } else {
+        tr!("off")
+    }
+}
+
+fn make_gui(settings: &settings::Settings) -> ZResult<Gui<Message>> {
+    let font = assets::get().font;
+    let mut gui = ui::Gui::new();
+    let h = utils::line_heights().big;
+    let space = || Box::new(ui::Spacer::new_vertical(h / 8.0));
+    let button = &mut |text: &str, message| -> ZResult<_> {
+        let text = ui::Drawable::text(text, font);
+        let b = ui::Button::new(text, h, gui.sender(), message)?.stretchable(true);
+        Ok(Box::new(b))
+    };
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    let title = ui::Label::new(ui::Drawable::text(tr!("~~~ Settings ~~~"), font), h)?;
+    layout.add(Box::new(title.stretchable(true)));
+    layout.add(space());
+    let speed = tr!(settings.animation_speed.title());
+    let speed_text = tr!("animation speed: {speed}", speed = speed);
+    layout.add(button(&speed_text, Message::ToggleAnimationSpeed)?);
+    let instant = on_off(settings.instant_ai_turns);
+    let instant_text = tr!("instant ai turns: {state}", state = instant);
+    layout.add(button(&instant_text, Message::ToggleInstantAiTurns)?);
+    #[cfg(not(target_arch = "wasm32"))] // the browser decides the page's size
+    {
+        let fullscreen = on_off(settings.fullscreen);
+        let text = tr!("fullscreen: {state} (after a restart)", state = fullscreen);
+        layout.add(button(&text, Message::ToggleFullscreen)?);
+    }
+    // Not translated: it must be readable whatever the current language is.
+    let language_text = format!("language: {}", settings.language.title());
+    layout.add(button(&language_text, Message::ToggleLanguage)?);
+    let colorblind = on_off(settings.colorblind);
+    let colorblind_text = tr!("colorblind mode: {state}", state = colorblind);
+    layout.add(button(&colorblind_text, Message::ToggleColorblind)?);
+    let confirm_taps = on_off(settings.confirm_taps);
+    let confirm_taps_text = tr!(
+        "confirm moves with a second tap: {state}",
+        state = confirm_taps
+    );
+    layout.add(button(&confirm_taps_text, Message::ToggleConfirmTaps)?);
+    let large_buttons = on_off(settings.large_buttons);
+    let large_buttons_text = tr!("large ability buttons: {state}", state = large_buttons);
+    layout.add(button(&large_buttons_text, Message::ToggleLargeButtons)?);
+    let ui_scale_text = tr!("ui scale: {scale}", scale = tr!(settings.ui_scale.title()));
+    layout.add(button(&ui_scale_text, Message::ToggleUiScale)?);
+    layout.add(space());
+    let difficulty = tr!(settings.difficulty.title());
+    let difficulty_text = tr!("default difficulty: {difficulty}", difficulty = difficulty);
+    layout.add(button(&difficulty_text, Message::ToggleDifficulty)?);
+    let ai_text = tr!("default ai: {mode}", mode = tr!(settings.ai.title()));
+    layout.add(button(&ai_text, Message::ToggleAi)?);
+    layout.add(space());
+    layout.add(button(&tr!("back"), Message::Back)?);
+    layout.stretch_to_self();
+    let layout = utils::add_offsets_and_bg_big(layout)?;
+    let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+    gui.add(&ui::pack(layout), anchor);
+    Ok(gui)
+}
+
+/// Edits the settings, every change is saved at once.
+#[derive(Debug)]
+pub struct Settings {
+    gui: Gui<Message>,
+    settings: settings::Settings,
+
+    /// Gets the final settings when the screen is closed.
+    sender: Sender<settings::Settings>,
+}
+
+impl Settings {
+    pub fn new(sender: Sender<settings::Settings>) -> ZResult<Self> {
+        let settings = settings::get();
+        let gui = make_gui(&settings)?;
+        Ok(Self {
+            gui,
+            settings,
+            sender,
+        })
+    }
+
+    fn update_settings(
+        &mut self,
+        f: impl FnOnce(&mut settings::Settings),
+    ) -> ZResult<StackCommand> {
+        f(&mut self.settings);
+        settings::set(self.settings.clone());
+        self.gui.replace(make_gui(&self.settings)?);
+        Ok(StackCommand::None)
+    }
+}
+
+impl Screen for Settings {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::ToggleAnimationSpeed) => self.update_settings(|settings| {
+                settings.animation_speed = settings.animation_speed.next()
+            }),
+            Some(Message::ToggleInstantAiTurns) => self
+                .update_settings(|settings| settings.instant_ai_turns = !settings.instant_ai_turns),
+            Some(Message::ToggleFullscreen) => {
+                self.update_settings(|settings| settings.fullscreen = !settings.fullscreen)
+            }
+            Some(Message::ToggleLanguage) => {
+                self.update_settings(|settings| settings.language = settings.language.next())
+            }
+            Some(Message::ToggleColorblind) => {
+                self.update_settings(|settings| settings.colorblind = !settings.colorblind)
+            }
+            Some(Message::ToggleConfirmTaps) => {
+                self.update_settings(|settings| settings.confirm_taps = !settings.confirm_taps)
+            }
+            Some(Message::ToggleLargeButtons) => {
+                self.update_settings(|settings| settings.large_buttons = !settings.large_buttons)
+            }
+            Some(Message::ToggleUiScale) => {
+                self.update_settings(|settings| settings.ui_scale = settings.ui_scale.next())
+            }
+            Some(Message::ToggleDifficulty) => {
+                self.update_settings(|settings| settings.difficulty = settings.difficulty.next())
+            }
+            Some(Message::ToggleAi) => {
+                self.update_settings(|settings| settings.ai = settings.ai.next())
+            }
+            Some(Message::Back) => {
+                self.sender
+                    .send(self.settings.clone())
+                    .expect("Can't report back the settings");
+                Ok(StackCommand::Pop)
+            }
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::{sync::mpsc::Sender, time::Duration};
+
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    core::campaign::{Goods, Offer, State},
+    i18n::{self, tr},
+    screen::{Screen, StackCommand},
+    utils, ZResult,
+};
+
+#[derive(Clone, Debug)]
+enum Message {
+    Back,
+    Buy(Offer),
+}
+
+fn offer_title(state: &State, offer: &Offer) -> String {
+    let goods = match &offer.goods {
+        Goods::Recruit(agent_type) => {
+            let agent = i18n::name_title(&agent_type.0);
+            tr!("Recruit {agent}", agent = agent)
+        }
+        Goods::Item(item) => {
+            let modifiers = utils::item_modifiers_text(state.item_info(item));
+            format!("{} ({})", i18n::name_title(&item.0), modifiers)
+        }
+        Goods::Boon(boon) => tr!("Boon: {boon} (next battle)", boon = tr!(boon.title())),
+    };
+    tr!("{goods} for {price}r", goods = goods, price = offer.price.0)
+}
+
+/// Lets the player spend the renown on recruits, items and boons.
+#[derive(Debug)]
+pub struct Shop {
+    gui: Gui<Message>,
+    sender: Sender<Offer>,
+}
+
+impl Shop {
+    pub fn new(state: &State, sender: Sender<Offer>) -> ZResult<Self> {
+        let font = assets::get().font;
+        let mut gui = ui::Gui::new();
+        let h = utils::line_heights().normal;
+        let text = |s: &str| ui::Drawable::text(s, font);
+        let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+        let title = ui::Label::new(text(&tr!("~~~ Shop ~~~")), h)?.stretchable(true);
+        layout.add(Box::new(title));
+        let renown = tr!("Your renown is: {renown}r", renown = state.renown().0);
+        layout.add(Box::new(ui::Label::new(text(&renown), h)?));
+        layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+        for offer in state.shop() {
+            let title = offer_title(state, offer);
+            let message = Message::Buy(offer.clone());
+            let mut button = ui::Button::new(text(&title), h, gui.sender(), message)?;
+            if !state.can_buy(offer) {
+                button.set_active(false);
+            }
+            layout.add(Box::new(button));
+            layout.add(Box::new(ui::Spacer::new_vertical(h * 0.25)));
+        }
+        layout.add(Box::new(ui::Spacer::new_vertical(h * 0.25)));
+        {
+            let mut button = ui::Button::new(text(&tr!("back")), h, gui.sender(), Message::Back)?
+                .stretchable(true);
+            button.stretch(layout.rect().w / 3.0);
+            button.set_stretchable(false);
+            layout.add(Box::new(button));
+        }
+        layout.stretch_to_self();
+        let layout = utils::add_offsets_and_bg_big(layout)?;
+        let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+        gui.add(&ui::pack(layout), anchor);
+        Ok(Self { gui, sender })
+    }
+}
+
+impl Screen for Shop {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::Buy(offer)) => {
+                self.sender
+                    .send(offer)
+                    .expect("Can't report the bought offer");
+                Ok(StackCommand::Pop)
+            }
+            Some(Message::Back) => Ok(StackCommand::Pop),
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    sync::mpsc::{channel, Receiver},
+    time::Duration,
+};
+
+use heck::TitleCase;
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    core::{
+        battle::{
+            ai,
+            component::ObjType,
+            dice,
+            scenario::{self, Scenario},
+            state,
+        },
+        skirmish::{Setup, MAX_SQUAD_SIZE},
+    },
+    i18n::{self, tr},
+    screen::{self, Screen, StackCommand},
+    utils, ZResult,
+};
+
+#[derive(Clone, Debug)]
+enum Message {
+    Back,
+    Start,
+    ToggleMap,
+    ToggleEnemyForce,
+    AddFighter(ObjType),
+    RemoveFighter(usize),
+    StartSaved(usize),
+    StartDaily,
+}
+
+/// Days since the Unix epoch, in UTC, so the players all over the world share the challenge.
+fn today() -> u64 {
+    (mq::miniquad::date::now() / (24.0 * 60.0 * 60.0)) as u64
+}
+
+/// Every fighter that the player can have in the campaign, the cheapest first.
+fn unlocked_fighters() -> Vec<ObjType> {
+    let info = &assets::get().agent_campaign_info;
+    let mut fighters: Vec<_> = info.keys().cloned().collect();
+    fighters.sort_by_key(|typename| (info[typename].cost.0, typename.0.clone()));
+    fighters
+}
+
+/// The scenarios made in the editor.
+#[cfg(not(target_arch = "wasm32"))]
+fn saved_scenarios() -> Vec<(String, Scenario)> {
+    crate::user_scenarios::load()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn saved_scenarios() -> Vec<(String, Scenario)> {
+    Vec::new()
+}
+
+fn make_gui(setup: &Setup, saved: &[(String, Scenario)]) -> ZResult<Gui<Message>> {
+    let font = assets::get().font;
+    let mut gui = ui::Gui::new();
+    let h = utils::line_heights().normal;
+    let text = |s: &str| ui::Drawable::text(s, font);
+    let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+    let title = ui::Label::new(text(&tr!("~~~ Skirmish ~~~")), h)?.stretchable(true);
+    layout.add(Box::new(title));
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    let map_text = tr!("map: {biome}", biome = tr!(setup.biome.title()));
+    let button = ui::Button::new(text(&map_text), h, gui.sender(), Message::ToggleMap)?;
+    layout.add(Box::new(button.stretchable(true)));
+    let size = tr!(setup.enemy_force.title());
+    let enemies_text = tr!("enemy force: {size}", size = size);
+    let message = Message::ToggleEnemyForce;
+    let button = ui::Button::new(text(&enemies_text), h, gui.sender(), message)?;
+    layout.add(Box::new(button.stretchable(true)));
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    let mut columns = ui::HLayout::new();
+    {
+        let mut column = ui::VLayout::new();
+        let squad_text = tr!(
+            "Your squad ({count}/{max}):",
+            count = setup.squad.len(),
+            max = MAX_SQUAD_SIZE,
+        );
+        column.add(Box::new(ui::Label::new(text(&squad_text), h)?));
+        for (index, typename) in setup.squad.iter().enumerate() {
+            let title = format!("- {}", i18n::name_title(&typename.0));
+            let message = Message::RemoveFighter(index);
+            let button = ui::Button::new(text(&title), h, gui.sender(), message)?;
+            column.add(Box::new(button));
+            column.add(Box::new(ui::Spacer::new_vertical(h * 0.25)));
+        }
+        columns.add(Box::new(column));
+    }
+    columns.add(Box::new(ui::Spacer::new_horizontal(h)));
+    {
+        let mut column = ui::VLayout::new();
+        column.add(Box::new(ui::Label::new(text(&tr!("Add fighters:")), h)?));
+        for typename in unlocked_fighters() {
+            let title = format!("+ {}", i18n::name_title(&typename.0));
+            let message = Message::AddFighter(typename);
+            let mut button = ui::Button::new(text(&title), h, gui.sender(), message)?;
+            button.set_active(setup.can_add_fighter());
+            column.add(Box::new(button));
+            column.add(Box::new(ui::Spacer::new_vertical(h * 0.25)));
+        }
+        columns.add(Box::new(column));
+    }
+    if !saved.is_empty() {
+        columns.add(Box::new(ui::Spacer::new_horizontal(h)));
+        let mut column = ui::VLayout::new();
+        column.add(Box::new(ui::Label::new(text(&tr!("Saved scenarios:")), h)?));
+        for (index, (name, _)) in saved.iter().enumerate() {
+            let title = name.to_title_case();
+            let message = Message::StartSaved(index);
+            let button = ui::Button::new(text(&title), h, gui.sender(), message)?;
+            column.add(Box::new(button));
+            column.add(Box::new(ui::Spacer::new_vertical(h * 0.25)));
+        }
+        columns.add(Box::new(column));
+    }
+    layout.add(Box::new(columns));
+    layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+    {
+        let mut buttons = ui::HLayout::new();
+        let mut button = ui::Button::new(text(&tr!("start")), h, gui.sender(), Message::Start)?;
+        button.set_active(setup.is_ready());
+        buttons.add(Box::new(button));
+        buttons.add(Box::new(ui::Spacer::new_horizontal(h)));
+        let message = Message::StartDaily;
+        let button = ui::Button::new(text(&tr!("daily challenge")), h, gui.sender(), message)?;
+        buttons.add(Box::new(button));
+        buttons.add(Box::new(ui::Spacer::new_horizontal(h)));
+        let button = ui::Button::new(text(&tr!("back")), h, gui.sender(), Message::Back)?;
+        buttons.add(Box::new(button));
+        layout.add(Box::new(buttons));
+    }
+    layout.stretch_to_self();
+    let layout = utils::add_offsets_and_bg_big(layout)?;
+    let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+    gui.add(&ui::pack(layout), anchor);
+    Ok(gui)
+}
+
+/// Lets the player set up a one-off battle: the map, the squad and the enemies.
+#[derive(Debug)]
+pub struct Skirmish {
+    gui: Gui<Message>,
+    setup: Setup,
+    saved: Vec<(String, Scenario)>,
+    dice: dice::Mode,
+    ai: ai::Mode,
+    receiver_battle_result: Option<Receiver<Option<state::BattleResult>>>,
+}
+
+impl Skirmish {
+    pub fn new(dice: dice::Mode, ai: ai::Mode) -> ZResult<Self> {
+        let setup = Setup::default();
+        let saved = saved_scenarios();
+        let gui = make_gui(&setup, &saved)?;
+        Ok(Self {
+            gui,
+            setup,
+            saved,
+            dice,
+            ai,
+            receiver_battle_result: None,
+        })
+    }
+
+    fn update_setup(&mut self, f: impl FnOnce(&mut Setup)) -> ZResult<StackCommand> {
+        f(&mut self.setup);
+        self.gui.replace(make_gui(&self.setup, &self.saved)?);
+        Ok(StackCommand::None)
+    }
+
+    fn start_battle(&mut self, mut scenario: Scenario) -> ZResult<StackCommand> {
+        let prototypes = assets::get().prototypes.clone();
+        scenario.dice = self.dice;
+        scenario.ai = self.ai;
+        let (sender, receiver) = channel();
+        self.receiver_battle_result = Some(receiver);
+        let battle_type = scenario::BattleType::Skirmish;
+        let screen = screen::Battle::new(scenario, battle_type, prototypes, sender)?;
+        Ok(StackCommand::PushScreen(Box::new(screen)))
+    }
+
+    /// The same for everyone on the same day: the dice and the AI aren't the player's.
+    fn start_daily_challenge(&mut self) -> ZResult<StackCommand> {
+        let day = today();
+        let scenario = Setup::daily_challenge(day, &unlocked_fighters()).scenario();
+        let prototypes = assets::get().prototypes.clone();
+        let (sender, receiver) = channel();
+        self.receiver_battle_result = Some(receiver);
+        let screen = screen::Battle::new_daily_challenge(day, scenario, prototypes, sender)?;
+        Ok(StackCommand::PushScreen(Box::new(screen)))
+    }
+}
+
+impl Screen for Skirmish {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::Start) => self.start_battle(self.setup.scenario()),
+            Some(Message::StartSaved(index)) => {
+                let scenario = self.saved[index].1.clone();
+                self.start_battle(scenario)
+            }
+            Some(Message::StartDaily) => self.start_daily_challenge(),
+            Some(Message::ToggleMap) => self.update_setup(|setup| setup.biome = setup.biome.next()),
+            Some(Message::ToggleEnemyForce) => {
+                self.update_setup(|setup| setup.enemy_force = setup.enemy_force.next())
+            }
+            Some(Message::AddFighter(typename)) => {
+                self.update_setup(|setup| setup.add_fighter(typename))
+            }
+            Some(Message::RemoveFighter(index)) => {
+                self.update_setup(|setup| setup.remove_fighter(index))
+            }
+            Some(Message::Back) => Ok(StackCommand::Pop),
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
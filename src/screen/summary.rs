@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    core::battle::{
+        state::BattleResult,
+        stats::{AgentStats, Stats},
+        PlayerId,
+    },
+    i18n::{self, tr},
+    screen::{Screen, StackCommand},
+    utils, ZResult,
+};
+
+#[derive(Clone, Debug)]
+enum Message {
+    Continue,
+}
+
+fn agent_name(agent: &AgentStats) -> String {
+    match &agent.name {
+        Some(name) => name.clone(),
+        None => i18n::name_title(&agent.typename.0),
+    }
+}
+
+fn row(agent: &AgentStats) -> [String; 5] {
+    [
+        agent_name(agent),
+        agent.damage_dealt.to_string(),
+        agent.damage_taken.to_string(),
+        agent.kills.to_string(),
+        agent.abilities_used.to_string(),
+    ]
+}
+
+/// One row per agent under a header, the numbers are in columns.
+fn table(agents: &[&AgentStats]) -> ZResult<Box<dyn ui::Widget>> {
+    let font = assets::get().font;
+    let h = utils::line_heights().normal;
+    let label = |text: &str| -> ZResult<_> {
+        let text = ui::Drawable::text(text, font);
+        Ok(Box::new(ui::Label::new(text, h)?))
+    };
+    let header = [
+        tr!("unit"),
+        tr!("dealt"),
+        tr!("taken"),
+        tr!("kills"),
+        tr!("abilities"),
+    ];
+    let rows: Vec<_> = agents.iter().map(|agent| row(agent)).collect();
+    let mut layout = Box::new(ui::HLayout::new());
+    for (i, title) in header.iter().enumerate() {
+        if i != 0 {
+            layout.add(Box::new(ui::Spacer::new_horizontal(h * 0.5)));
+        }
+        let mut column = Box::new(ui::VLayout::new());
+        column.add(label(title)?);
+        for row in &rows {
+            column.add(label(&row[i])?);
+        }
+        layout.add(column);
+    }
+    Ok(layout)
+}
+
+fn title(result: &BattleResult) -> String {
+    if result.is_retreat {
+        tr!("You have retreated!")
+    } else if result.winner_id == PlayerId(0) {
+        tr!("You have won!")
+    } else {
+        tr!("You have failed!")
+    }
+}
+
+/// What every agent has done in the battle, shown after it's over.
+#[derive(Debug)]
+pub struct Summary {
+    gui: Gui<Message>,
+}
+
+impl Summary {
+    /// The notes are shown under the tables: the unlocked achievements, the records.
+    pub fn new(stats: &Stats, result: &BattleResult, notes: &[String]) -> ZResult<Self> {
+        let font = assets::get().font;
+        let mut gui = ui::Gui::new();
+        let h = utils::line_heights().normal;
+        let text = |s: &str| ui::Drawable::text(s, font);
+        let label_s =
+            |s: &str| -> ZResult<_> { Ok(Box::new(ui::Label::new(text(s), h)?.stretchable(true))) };
+        let spacer = || Box::new(ui::Spacer::new_vertical(h * 0.5));
+        let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+        layout.add(label_s(&format!("~~~ {} ~~~", title(result)))?);
+        let turns = tr!("Turns: {turns}", turns = stats.turns());
+        layout.add(label_s(&turns)?);
+        let (ours, theirs): (Vec<_>, Vec<_>) = stats
+            .agents()
+            .iter()
+            .partition(|agent| agent.player_id == PlayerId(0));
+        for (header, agents) in [(tr!("~ your units ~"), ours), (tr!("~ enemies ~"), theirs)] {
+            if agents.is_empty() {
+                continue;
+            }
+            layout.add(spacer());
+            layout.add(label_s(&header)?);
+            layout.add(table(&agents)?);
+        }
+        if !notes.is_empty() {
+            layout.add(spacer());
+            for note in notes {
+                layout.add(label_s(note)?);
+            }
+        }
+        layout.add(spacer());
+        {
+            let message = Message::Continue;
+            let mut button = ui::Button::new(text(&tr!("continue")), h, gui.sender(), message)?
+                .stretchable(true);
+            button.stretch(layout.rect().w / 3.0);
+            button.set_stretchable(false);
+            layout.add(Box::new(button));
+        }
+        layout.stretch_to_self();
+        let layout = utils::add_offsets_and_bg_big(layout)?;
+        let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+        gui.add(&ui::pack(layout), anchor);
+        Ok(Self { gui })
+    }
+}
+
+impl Screen for Summary {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::Continue) => Ok(StackCommand::Pop),
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
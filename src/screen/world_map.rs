@@ -0,0 +1,148 @@
+use std::{sync::mpsc::Sender, time::Duration};
+
+use mq::math::Vec2;
+use ui::{self, Gui, Widget};
+
+use crate::{
+    assets,
+    core::campaign::State,
+    i18n::{self, tr},
+    screen::{Screen, StackCommand},
+    utils, ZResult,
+};
+
+#[derive(Clone, Debug)]
+enum Message {
+    Back,
+    Node(usize),
+    AutoResolve(usize),
+}
+
+/// The next node and who fights its battle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Choice {
+    pub node: usize,
+
+    /// Both sides are played by the AI, the player only sees the result.
+    pub is_auto_resolved: bool,
+}
+
+/// Shows the campaign graph and lets the player pick the next battle.
+#[derive(Debug)]
+pub struct WorldMap {
+    gui: Gui<Message>,
+    sender: Sender<Choice>,
+}
+
+impl WorldMap {
+    pub fn new(state: &State, sender: Sender<Choice>) -> ZResult<Self> {
+        let font = assets::get().font;
+        let mut gui = ui::Gui::new();
+        let h = utils::line_heights().normal;
+        let text = |s: &str| ui::Drawable::text(s, font);
+        let depths = state.node_depths();
+        let available_nodes = state.available_nodes();
+        let max_depth = depths.iter().cloned().max().unwrap_or(0);
+        let mut layout = Box::new(ui::VLayout::new().stretchable(true));
+        let title = ui::Label::new(text(&tr!("~~~ World map ~~~")), h)?.stretchable(true);
+        layout.add(Box::new(title));
+        layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+        let mut columns = ui::HLayout::new();
+        for depth in 0..=max_depth {
+            let mut column = ui::VLayout::new();
+            for (i, node) in state.nodes().iter().enumerate() {
+                if depths[i] != depth {
+                    continue;
+                }
+                let note = if node.camp.is_some() {
+                    tr!("camp")
+                } else {
+                    tr!(node.scenario.tags.difficulty.title())
+                };
+                let mut title = format!("{} ({})", i18n::name_title(&node.name), note);
+                if state.completed_nodes().contains(&i) {
+                    title = tr!("{node} (won)", node = title);
+                }
+                if i == state.current_node() {
+                    title = format!("> {}", title);
+                }
+                let is_available = available_nodes.contains(&i);
+                let mut line = ui::HLayout::new();
+                let mut button = ui::Button::new(text(&title), h, gui.sender(), Message::Node(i))?;
+                button.set_active(is_available);
+                line.add(Box::new(button));
+                if node.camp.is_none() && is_available {
+                    line.add(Box::new(ui::Spacer::new_horizontal(h * 0.25)));
+                    let message = Message::AutoResolve(i);
+                    let button = ui::Button::new(text(&tr!("auto")), h, gui.sender(), message)?;
+                    line.add(Box::new(button));
+                }
+                column.add(Box::new(line));
+                column.add(Box::new(ui::Spacer::new_vertical(h * 0.25)));
+            }
+            columns.add(Box::new(column));
+            columns.add(Box::new(ui::Spacer::new_horizontal(h)));
+        }
+        layout.add(Box::new(columns));
+        layout.add(Box::new(ui::Spacer::new_vertical(h * 0.5)));
+        {
+            let mut button = ui::Button::new(text(&tr!("back")), h, gui.sender(), Message::Back)?
+                .stretchable(true);
+            button.stretch(layout.rect().w / 3.0);
+            button.set_stretchable(false);
+            layout.add(Box::new(button));
+        }
+        layout.stretch_to_self();
+        let layout = utils::add_offsets_and_bg_big(layout)?;
+        let anchor = ui::Anchor(ui::HAnchor::Middle, ui::VAnchor::Middle);
+        gui.add(&ui::pack(layout), anchor);
+        Ok(Self { gui, sender })
+    }
+}
+
+impl WorldMap {
+    fn choose(&self, node: usize, is_auto_resolved: bool) -> ZResult<StackCommand> {
+        let choice = Choice {
+            node,
+            is_auto_resolved,
+        };
+        self.sender
+            .send(choice)
+            .expect("Can't report the chosen node");
+        Ok(StackCommand::Pop)
+    }
+}
+
+impl Screen for WorldMap {
+    fn update(&mut self, _dtime: Duration) -> ZResult<StackCommand> {
+        Ok(StackCommand::None)
+    }
+
+    fn draw(&self) -> ZResult {
+        self.gui.draw();
+        Ok(())
+    }
+
+    fn click(&mut self, pos: Vec2) -> ZResult<StackCommand> {
+        let message = self.gui.click(pos);
+        match message {
+            Some(Message::Node(node)) => self.choose(node, false),
+            Some(Message::AutoResolve(node)) => self.choose(node, true),
+            Some(Message::Back) => Ok(StackCommand::Pop),
+            None => Ok(StackCommand::None),
+        }
+    }
+
+    fn resize(&mut self, aspect_ratio: f32) {
+        self.gui.resize_if_needed(aspect_ratio);
+    }
+
+    fn focus(&mut self) -> Option<&mut dyn ui::Focus> {
+        Some(&mut self.gui)
+    }
+
+    fn move_mouse(&mut self, pos: Vec2) -> ZResult {
+        self.gui.move_mouse(pos);
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+//! The player's options, kept in a RON file next to the game.
+
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    core::battle::{ai, scenario::Difficulty},
+    i18n::Language,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const PATH: &str = "config.ron";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AnimationSpeed {
+    #[default]
+    Normal,
+    Fast,
+    Faster,
+}
+
+impl AnimationSpeed {
+    pub fn title(self) -> &'static str {
+        match self {
+            AnimationSpeed::Normal => "normal",
+            AnimationSpeed::Fast => "fast",
+            AnimationSpeed::Faster => "faster",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            AnimationSpeed::Normal => AnimationSpeed::Fast,
+            AnimationSpeed::Fast => AnimationSpeed::Faster,
+            AnimationSpeed::Faster => AnimationSpeed::Normal,
+        }
+    }
+
+    /// How many times faster than normal the battle animations are played.
+    pub fn factor(self) -> f32 {
+        match self {
+            AnimationSpeed::Normal => 1.0,
+            AnimationSpeed::Fast => 2.0,
+            AnimationSpeed::Faster => 4.0,
+        }
+    }
+}
+
+/// The window's height in the logical pixels that the gui is designed for:
+/// bigger windows get a smaller gui in `UiScale::Auto`.
+const REFERENCE_HEIGHT: f32 = 1080.0;
+
+const MIN_AUTO_SCALE: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum UiScale {
+    /// Picked from the window's size and the screen's DPI.
+    #[default]
+    Auto,
+
+    Percent75,
+    Percent100,
+    Percent125,
+    Percent150,
+}
+
+impl UiScale {
+    pub fn title(self) -> &'static str {
+        match self {
+            UiScale::Auto => "auto",
+            UiScale::Percent75 => "75%",
+            UiScale::Percent100 => "100%",
+            UiScale::Percent125 => "125%",
+            UiScale::Percent150 => "150%",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            UiScale::Auto => UiScale::Percent75,
+            UiScale::Percent75 => UiScale::Percent100,
+            UiScale::Percent100 => UiScale::Percent125,
+            UiScale::Percent125 => UiScale::Percent150,
+            UiScale::Percent150 => UiScale::Auto,
+        }
+    }
+
+    /// How many times bigger than designed the gui is drawn.
+    ///
+    /// The gui is sized relative to the window, so on a big high-resolution
+    /// monitor it would be huge. The auto scale keeps the physical size of the gui
+    /// about the same as in a 1080p window, but never makes it bigger than designed:
+    /// the small windows just get the designed layout.
+    /// The DPI factor keeps a HiDPI laptop from being taken for a huge monitor.
+    pub fn factor(self, window_height: f32, dpi_scale: f32) -> f32 {
+        match self {
+            UiScale::Auto => {
+                let logical_height = window_height / dpi_scale;
+                (REFERENCE_HEIGHT / logical_height).clamp(MIN_AUTO_SCALE, 1.0)
+            }
+            UiScale::Percent75 => 0.75,
+            UiScale::Percent100 => 1.0,
+            UiScale::Percent125 => 1.25,
+            UiScale::Percent150 => 1.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub animation_speed: AnimationSpeed,
+
+    /// The AI's turns are played out without waiting for the animations.
+    pub instant_ai_turns: bool,
+
+    /// The window is created once, so this is applied on the next start.
+    pub fullscreen: bool,
+
+    pub language: Language,
+
+    /// Highlights the tiles with the colors that don't rely on telling red from green.
+    pub colorblind: bool,
+
+    /// The first tap at a tile only shows what a move or an attack would do there,
+    /// the second one does it. Prevents the accidental moves on touch screens.
+    pub confirm_taps: bool,
+
+    /// Bigger ability buttons that are easier to hit with a finger.
+    pub large_buttons: bool,
+
+    pub ui_scale: UiScale,
+
+    /// The mods of the load order that the game starts without,
+    /// e.g. when one of them breaks the balance. Applied on the next start, see `assets::mods`.
+    pub disabled_mods: Vec<String>,
+
+    /// What the main menu starts with.
+    pub difficulty: Difficulty,
+    pub ai: ai::Mode,
+}
+
+static INSTANCE: RwLock<Option<Settings>> = RwLock::new(None);
+
+pub fn get() -> Settings {
+    INSTANCE
+        .read()
+        .expect("Can't read the settings")
+        .clone()
+        .unwrap_or_default()
+}
+
+/// Reads the settings file. The missing or broken file means the default settings.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load() -> Settings {
+    let settings = match std::fs::read_to_string(PATH) {
+        Ok(s) => ron::de::from_str(&s).unwrap_or_else(|err| {
+            log::warn!("Can't parse '{}', using the defaults: {}", PATH, err);
+            Settings::default()
+        }),
+        Err(_) => Settings::default(),
+    };
+    *INSTANCE.write().expect("Can't set the settings") = Some(settings.clone());
+    settings
+}
+
+/// There are no files in browsers, the settings last till the page is closed.
+#[cfg(target_arch = "wasm32")]
+pub fn load() -> Settings {
+    get()
+}
+
+/// Applies the new settings and saves them. Failing to save isn't fatal:
+/// the settings still work till the game is closed.
+pub fn set(settings: Settings) {
+    *INSTANCE.write().expect("Can't set the settings") = Some(settings.clone());
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let config = ron::ser::PrettyConfig::new();
+        let result = ron::ser::to_string_pretty(&settings, config)
+            .map_err(|err| err.to_string())
+            .and_then(|s| std::fs::write(PATH, s).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            log::warn!("Can't save the settings to '{}': {}", PATH, err);
+        }
+    }
+}
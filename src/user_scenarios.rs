@@ -0,0 +1,67 @@
+//! Scenarios made in the editor, kept as RON files next to the game.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::{
+    core::{battle::scenario::Scenario, editor::Draft},
+    error::ZError,
+    ZResult,
+};
+
+const DIR: &str = "scenarios";
+
+/// Writes the draft to a new file and returns its path.
+pub fn save(draft: &Draft) -> ZResult<PathBuf> {
+    let s = draft.to_ron()?;
+    fs::create_dir_all(DIR)?;
+    let mut index = 1;
+    let path = loop {
+        let path = Path::new(DIR).join(format!("custom_{:02}.ron", index));
+        if !path.exists() {
+            break path;
+        }
+        index += 1;
+    };
+    fs::write(&path, s)?;
+    Ok(path)
+}
+
+fn read(path: &Path) -> ZResult<Scenario> {
+    let s = fs::read_to_string(path)?;
+    ron::de::from_str(&s).map_err(|e| ZError::from_ron_de_error(e, path.into()))
+}
+
+/// All the saved scenarios with their names, the broken ones are skipped.
+pub fn load() -> Vec<(String, Scenario)> {
+    let entries = match fs::read_dir(DIR) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("ron"))
+        .collect();
+    paths.sort();
+    let mut scenarios = Vec::new();
+    for path in paths {
+        let scenario = match read(&path) {
+            Ok(scenario) => scenario,
+            Err(err) => {
+                warn!("Skipping a saved scenario: {}", err);
+                continue;
+            }
+        };
+        if let Err(err) = scenario.check() {
+            warn!("Skipping a bad saved scenario {:?}: {:?}", path, err);
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        scenarios.push((name.into_owned(), scenario));
+    }
+    scenarios
+}
@@ -2,10 +2,15 @@ use std::{sync::mpsc::Receiver, time::Duration};
 
 use mq::{
     camera::{set_camera, Camera2D},
+    color::Color,
     math::{Rect, Vec2},
 };
 
-use crate::ZResult;
+use crate::{
+    core::item::Item,
+    i18n::{self, tr},
+    ZResult,
+};
 
 pub fn time_s(s: f32) -> Duration {
     let ms = s * 1000.0;
@@ -92,6 +97,33 @@ pub fn get_world_mouse_pos(camera: &Camera2D) -> Vec2 {
     camera.screen_to_world(mq::input::mouse_position().into())
 }
 
+/// Tints of the agents' portrait variants, the first one keeps the original look.
+pub const PORTRAIT_TINTS: [Color; 4] = [
+    Color::new(1.0, 1.0, 1.0, 1.0),
+    Color::new(1.0, 0.85, 0.75, 1.0),
+    Color::new(0.8, 0.9, 1.0, 1.0),
+    Color::new(0.85, 1.0, 0.8, 1.0),
+];
+
+/// "Boris the Swordsman" for the renamed agents, just "Swordsman" for the rest.
+pub fn agent_name(name: Option<&str>, typename: &str) -> String {
+    let typename = i18n::name_title(typename);
+    match name {
+        Some(name) => tr!("{name} the {typename}", name = name, typename = typename),
+        None => typename,
+    }
+}
+
+/// A short list of the item's stat modifiers, like "+1 distance, -1 move".
+pub fn item_modifiers_text(item: &Item) -> String {
+    let texts: Vec<String> = item
+        .modifiers()
+        .iter()
+        .map(|&(value, name)| format!("{:+} {}", value, tr!(name)))
+        .collect();
+    texts.join(", ")
+}
+
 pub fn try_receive<Message>(opt_rx: &Option<Receiver<Message>>) -> Option<Message> {
     opt_rx.as_ref().and_then(|rx| rx.try_recv().ok())
 }
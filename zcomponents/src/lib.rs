@@ -9,7 +9,7 @@
 //! ```rust
 //! use zcomponents::zcomponents_storage;
 //!
-//! #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
+//! #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default)]
 //! pub struct Id(i32);
 //!
 //! #[derive(Clone, Debug)]
@@ -86,26 +86,27 @@
 //! ```
 
 use std::{
-    collections::{hash_map, HashMap},
+    collections::{btree_map, BTreeMap},
     default::Default,
     fmt::Debug,
-    hash::Hash,
 };
 
+/// The values are kept sorted by their ids, so every copy of the container
+/// and every container with the same values iterates over them in the same order.
 #[derive(Debug, Clone)]
-pub struct ComponentContainer<Id: Hash + Eq, V> {
-    data: HashMap<Id, V>,
+pub struct ComponentContainer<Id: Ord, V> {
+    data: BTreeMap<Id, V>,
 }
 
-impl<Id: Hash + Eq + Copy + Debug, V: Clone> Default for ComponentContainer<Id, V> {
+impl<Id: Ord + Copy + Debug, V: Clone> Default for ComponentContainer<Id, V> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<Id: Hash + Eq + Copy + Debug, V: Clone> ComponentContainer<Id, V> {
+impl<Id: Ord + Copy + Debug, V: Clone> ComponentContainer<Id, V> {
     pub fn new() -> Self {
-        let data = HashMap::new();
+        let data = BTreeMap::new();
         Self { data }
     }
 
@@ -154,11 +155,11 @@ impl<Id: Hash + Eq + Copy + Debug, V: Clone> ComponentContainer<Id, V> {
 
 #[derive(Clone, Debug)]
 pub struct IdIter<'a, Id, V> {
-    iter: hash_map::Iter<'a, Id, V>,
+    iter: btree_map::Iter<'a, Id, V>,
 }
 
-impl<'a, Id: Eq + Hash + Clone + 'a, V: 'a> IdIter<'a, Id, V> {
-    pub fn new(map: &'a HashMap<Id, V>) -> Self {
+impl<'a, Id: Ord + Clone + 'a, V: 'a> IdIter<'a, Id, V> {
+    pub fn new(map: &'a BTreeMap<Id, V>) -> Self {
         Self { iter: map.iter() }
     }
 }
@@ -178,7 +179,7 @@ impl<'a, Id: Copy + 'a, V> Iterator for IdIter<'a, Id, V> {
 #[macro_export]
 macro_rules! zcomponents_storage {
     ($struct_name:ident<$id_type:ty>: { $($component:ident: $t:ty,)* } ) => {
-        use std::collections::HashMap;
+        use std::collections::BTreeMap;
 
         #[derive(Clone, Debug)]
         pub struct $struct_name {
@@ -186,7 +187,7 @@ macro_rules! zcomponents_storage {
                 pub $component: $crate::ComponentContainer<$id_type, $t>,
             )*
             next_obj_id: $id_type,
-            ids: HashMap<$id_type, ()>,
+            ids: BTreeMap<$id_type, ()>,
         }
 
         #[allow(dead_code)]
@@ -197,7 +198,7 @@ macro_rules! zcomponents_storage {
                         $component: $crate::ComponentContainer::new(),
                     )*
                     next_obj_id: Default::default(),
-                    ids: HashMap::new(),
+                    ids: BTreeMap::new(),
                 }
             }
 
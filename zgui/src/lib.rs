@@ -1,7 +1,7 @@
 //! Tiny and opinionated GUI.
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     error::Error as StdError,
     fmt::{self, Debug},
     rc::Rc,
@@ -56,6 +56,23 @@ impl StdError for Error {
     }
 }
 
+thread_local! {
+    static SCALE: Cell<f32> = const { Cell::new(1.0) };
+}
+
+/// Makes the widgets of all the guis `scale` times bigger (or smaller).
+/// The layouts stay at the same edges of the window.
+///
+/// The guis pick it up in `Gui::resize_if_needed`.
+pub fn set_scale(scale: f32) {
+    assert!(scale > 0.0);
+    SCALE.with(|s| s.set(scale));
+}
+
+pub fn scale() -> f32 {
+    SCALE.with(|s| s.get())
+}
+
 pub fn pack<W: Widget + 'static>(widget: W) -> RcWidget {
     Rc::new(RefCell::new(widget))
 }
@@ -246,6 +263,11 @@ pub trait Widget: Debug {
     fn draw(&self);
     fn click(&self, _: Vec2) {}
     fn move_mouse(&mut self, _: Vec2) {}
+
+    /// Collects the rects of the clickable widgets in the order of the layouts,
+    /// that's the order of the keyboard focus.
+    fn focusable_rects(&self, _rects: &mut Vec<Rect>) {}
+
     fn rect(&self) -> Rect;
     fn set_pos(&mut self, pos: Vec2);
 
@@ -277,6 +299,10 @@ fn stretch_checks(widget: &impl Widget, width: f32) -> Option<StretchStatus> {
 
 pub type RcWidget = Rc<RefCell<dyn Widget>>;
 
+fn center(rect: Rect) -> Vec2 {
+    Vec2::new(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0)
+}
+
 #[derive(Debug)]
 pub struct AnchoredWidget {
     widget: RcWidget,
@@ -286,9 +312,18 @@ pub struct AnchoredWidget {
 #[derive(Debug)]
 pub struct Gui<Message: Clone> {
     aspect_ratio: f32,
+
+    /// The `scale()` that the widgets are placed for.
+    scale: f32,
     anchored_widgets: Vec<AnchoredWidget>,
     receiver: Receiver<Message>,
     sender: Sender<Message>,
+
+    /// The index of the focused widget among the `focusable_rects`.
+    focus: Option<usize>,
+
+    /// The last position of the mouse, the focus is kept until it moves.
+    mouse_pos: Option<Vec2>,
 }
 
 impl<Message: Clone> Gui<Message> {
@@ -301,6 +336,9 @@ impl<Message: Clone> Gui<Message> {
             receiver,
             sender,
             aspect_ratio,
+            scale: scale(),
+            focus: None,
+            mouse_pos: None,
         }
     }
 
@@ -327,15 +365,23 @@ impl<Message: Clone> Gui<Message> {
         }
     }
 
+    /// The visible part of the gui's space: the bigger the scale, the smaller it is.
+    fn ui_coordinates(&self) -> Rect {
+        let w = self.aspect_ratio / self.scale;
+        let h = 1.0 / self.scale;
+        Rect::new(-w, -h, w * 2.0, h * 2.0)
+    }
+
     pub fn draw(&self) {
-        let ui_coordinates = Rect::new(-self.aspect_ratio, -1.0, self.aspect_ratio * 2.0, 2.0);
-        set_camera(&Camera2D::from_display_rect(ui_coordinates));
+        set_camera(&Camera2D::from_display_rect(self.ui_coordinates()));
         for AnchoredWidget { widget, .. } in &self.anchored_widgets {
             widget.borrow().draw();
         }
     }
 
+    /// The position is in the window's coordinates, vertically from -1 to 1.
     pub fn click(&mut self, pos: Vec2) -> Option<Message> {
+        let pos = pos / self.scale;
         for AnchoredWidget { widget, .. } in &self.anchored_widgets {
             widget.borrow_mut().click(pos);
         }
@@ -343,40 +389,125 @@ impl<Message: Clone> Gui<Message> {
     }
 
     pub fn move_mouse(&mut self, pos: Vec2) {
+        if self.focus.is_some() && self.mouse_pos == Some(pos) {
+            return;
+        }
+        self.focus = None;
+        self.mouse_pos = Some(pos);
+        self.highlight(pos / self.scale);
+    }
+
+    fn highlight(&self, pos: Vec2) {
         for AnchoredWidget { widget, .. } in &self.anchored_widgets {
             widget.borrow_mut().move_mouse(pos);
         }
     }
 
+    fn focusable_rects(&self) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        for AnchoredWidget { widget, .. } in &self.anchored_widgets {
+            widget.borrow().focusable_rects(&mut rects);
+        }
+        rects
+    }
+
+    /// Moves the focus by `step` widgets, wrapping around.
+    /// The first step from no focus takes the first (or the last) widget.
+    pub fn move_focus(&mut self, step: isize) {
+        let rects = self.focusable_rects();
+        if rects.is_empty() {
+            return;
+        }
+        let len = rects.len() as isize;
+        let index = match self.focus {
+            Some(index) => (index as isize + step).rem_euclid(len),
+            None if step > 0 => 0,
+            None => len - 1,
+        } as usize;
+        self.set_focus(Some(index));
+    }
+
+    pub fn focus(&self) -> Option<usize> {
+        self.focus
+    }
+
+    /// Restores the focus, e.g. in a rebuilt gui. Out of range indices are ignored.
+    pub fn set_focus(&mut self, focus: Option<usize>) {
+        let rects = self.focusable_rects();
+        self.focus = focus.filter(|&index| index < rects.len());
+        if let Some(index) = self.focus {
+            self.highlight(center(rects[index]));
+        }
+    }
+
+    /// Takes the place of the gui with a rebuilt one, e.g. after a button has changed its text,
+    /// keeping the focus on the same place.
+    pub fn replace(&mut self, gui: Self) {
+        let focus = self.focus;
+        let mouse_pos = self.mouse_pos;
+        *self = gui;
+        self.mouse_pos = mouse_pos;
+        self.set_focus(focus);
+    }
+
+    /// The center of the focused widget in the same coordinates as `click` takes,
+    /// so clicking there activates it.
+    pub fn focused_point(&self) -> Option<Vec2> {
+        let index = self.focus?;
+        let rects = self.focusable_rects();
+        rects.get(index).map(|&rect| center(rect) * self.scale)
+    }
+
     pub fn resize(&mut self, ratio: f32) {
         self.aspect_ratio = ratio;
-        trace!("Gui::resize: {}", ratio);
+        self.scale = scale();
+        trace!("Gui::resize: {} (scale: {})", ratio, self.scale);
         let offset = 0.02; // TODO: make configurable
+        let half_w = ratio / self.scale;
+        let half_h = 1.0 / self.scale;
         for AnchoredWidget { widget, anchor } in &mut self.anchored_widgets {
             let mut widget = widget.borrow_mut();
             let rect = widget.rect();
             let mut pos = rect.point();
             match anchor.0 {
-                HAnchor::Left => pos.x = (-ratio) + offset,
+                HAnchor::Left => pos.x = (-half_w) + offset,
                 HAnchor::Middle => pos.x = -rect.w / 2.0,
-                HAnchor::Right => pos.x = (ratio - rect.w) - offset,
+                HAnchor::Right => pos.x = (half_w - rect.w) - offset,
             }
             match anchor.1 {
-                VAnchor::Top => pos.y = (-1.0) + offset,
+                VAnchor::Top => pos.y = (-half_h) + offset,
                 VAnchor::Middle => pos.y = -rect.h / 2.0,
-                VAnchor::Bottom => pos.y = (1.0 - rect.h) - offset,
+                VAnchor::Bottom => pos.y = (half_h - rect.h) - offset,
             }
             widget.set_pos(pos);
         }
     }
 
     pub fn resize_if_needed(&mut self, aspect_ratio: f32) {
-        if (self.aspect_ratio - aspect_ratio).abs() > f32::EPSILON {
+        let is_ratio_changed = (self.aspect_ratio - aspect_ratio).abs() > f32::EPSILON;
+        let is_scale_changed = (self.scale - scale()).abs() > f32::EPSILON;
+        if is_ratio_changed || is_scale_changed {
             self.resize(aspect_ratio);
         }
     }
 }
 
+/// The keyboard focus of a `Gui`, whatever its messages are.
+pub trait Focus {
+    fn move_focus(&mut self, step: isize);
+    fn focused_point(&self) -> Option<Vec2>;
+}
+
+impl<Message: Clone> Focus for Gui<Message> {
+    fn move_focus(&mut self, step: isize) {
+        Gui::move_focus(self, step);
+    }
+
+    fn focused_point(&self) -> Option<Vec2> {
+        Gui::focused_point(self)
+    }
+}
+
 impl<Message: Clone> Default for Gui<Message> {
     fn default() -> Self {
         Self::new()
@@ -805,6 +936,10 @@ impl<Message: Clone + Debug> Widget for Button<Message> {
         };
     }
 
+    fn focusable_rects(&self, rects: &mut Vec<Rect>) {
+        rects.push(self.rect());
+    }
+
     fn rect(&self) -> Rect {
         self.border.rect()
     }
@@ -879,6 +1014,12 @@ impl Widget for Layout {
         }
     }
 
+    fn focusable_rects(&self, rects: &mut Vec<Rect>) {
+        for widget in &self.widgets {
+            widget.focusable_rects(rects);
+        }
+    }
+
     fn rect(&self) -> Rect {
         self.rect
     }
@@ -962,6 +1103,10 @@ impl Widget for VLayout {
         self.internal.move_mouse(pos);
     }
 
+    fn focusable_rects(&self, rects: &mut Vec<Rect>) {
+        self.internal.focusable_rects(rects);
+    }
+
     fn rect(&self) -> Rect {
         self.internal.rect()
     }
@@ -1025,6 +1170,10 @@ impl Widget for HLayout {
         self.internal.move_mouse(pos);
     }
 
+    fn focusable_rects(&self, rects: &mut Vec<Rect>) {
+        self.internal.focusable_rects(rects);
+    }
+
     fn rect(&self) -> Rect {
         self.internal.rect()
     }
@@ -1103,6 +1252,10 @@ impl Widget for LayersLayout {
         self.internal.move_mouse(pos);
     }
 
+    fn focusable_rects(&self, rects: &mut Vec<Rect>) {
+        self.internal.focusable_rects(rects);
+    }
+
     fn rect(&self) -> Rect {
         self.internal.rect()
     }
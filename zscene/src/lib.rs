@@ -130,6 +130,11 @@ impl Scene {
     pub fn tick(&mut self, dtime: Duration) {
         self.interpreter.tick(dtime);
     }
+
+    /// Some of the actions aren't finished yet.
+    pub fn is_busy(&self) -> bool {
+        !self.interpreter.actions.is_empty()
+    }
 }
 
 #[derive(Debug)]